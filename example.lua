@@ -0,0 +1,36 @@
+-- A script for --lua, to show what one can do. Run it with
+--
+--     cargo run --features lua -- --lua example.lua
+--
+-- and then edit it (the game needs starting again to notice). See
+-- src/lua.rs for everything a script gets to use.
+
+-- Anything not inside a function runs once, when the game starts, which
+-- makes it the place for settings.
+drift = 20       -- how fast enemies slide to the right, pixels a second
+wobble = 6       -- how far the player puffs up and down
+seconds = 0
+
+-- Called every tick for each player and each enemy.
+function on_update(entity, dt)
+    if entity.kind == "player" then
+        -- Breathe in and out. Only player one's clock counts, so two
+        -- players don't make time go twice as fast.
+        if entity.number == 1 then seconds = seconds + dt end
+        if expand(entity) < wobble then
+            set_expand(entity, wobble / 2 * (1 + math.sin(seconds * 4)))
+        end
+    else
+        local x, y = position(entity)
+        set_position(entity, x + drift * dt, y)
+    end
+end
+
+-- Called with the name of every key that's pressed.
+function on_key(action)
+    if action == "K" then
+        spawn("enemy", 600, 100)
+    elseif action == "L" then
+        spawn("pickup", 100, 300)
+    end
+end
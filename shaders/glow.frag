@@ -0,0 +1,9 @@
+// A pulsing glow round the player. It's meant for the player's box only:
+// "shader": "shaders/glow.frag" and "shader_target": "player".
+void main() {
+    vec4 drawn = texture(scene, uv);
+    float d = length(gl_FragCoord.xy - player) / resolution.y;
+    float pulse = 0.5 + 0.5 * sin(time * 4.0);
+    float glow = clamp(0.06 - d, 0.0, 0.06) * (4.0 + expand) * pulse;
+    color = vec4(drawn.rgb + vec3(glow), 1.0);
+}
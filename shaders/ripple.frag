@@ -0,0 +1,10 @@
+// Ripples going out from the player, bigger the more they've expanded.
+// Try it with "shader": "shaders/ripple.frag" in config.json.
+void main() {
+    vec2 away = gl_FragCoord.xy - player;
+    float d = length(away);
+    float strength = (2.0 + expand) / resolution.x;
+    float wave = sin(d * 0.08 - time * 6.0) * strength * exp(-d * 0.004);
+    vec2 direction = d > 0.0 ? away / d : vec2(0.0);
+    color = texture(scene, uv + direction * wave);
+}
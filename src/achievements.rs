@@ -0,0 +1,274 @@
+/*
+ * Achievements: little prizes for doing things, that you get to keep. So
+ * far there are three:
+ *
+ *     Globetrotter  travel 10,000 pixels, all told
+ *     Puffed up     expand 50 times
+ *     Untouchable   clear a level without anything hurting you
+ *
+ * Keeping them is the point, so they're written to achievements.json, with
+ * the running totals for the first two, and they carry on from one game
+ * (and one run of the game) to the next.
+ *
+ * App doesn't know anything about them. It just says what's happened, on
+ * its bus (see bus.rs), and `hear` picks out what they might care about,
+ * as a list of Progress on App that Game picks up after every update, the
+ * same way it picks up App's sounds. (Expanding is the one App lists
+ * itself: that's a key press, not part of a tick.) Game passes them on
+ * to Achievements, and whichever one that unlocks gets a toast: a note that
+ * drops in at the top of the screen for a few seconds to tell you, over
+ * whatever screen you're on. A replay's progress isn't yours, so Game
+ * leaves that out.
+ */
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use graphics::Context;
+use serde_json;
+
+use app::{App, LevelState, TRAVEL_STEP};
+use bus::GameEvent;
+use hud::Hud;
+use locale::Locale;
+use renderer::Renderer;
+use text::Text;
+
+pub const TRAVEL_DISTANCE: f64 = 10000.0;
+pub const EXPANDS: u32 = 50;
+
+// How long a toast stays up, and how long of that it takes to fade away.
+pub const TOAST_SECONDS: f64 = 4.0;
+pub const TOAST_FADE: f64 = 0.5;
+// Where the first one goes, how much bigger than its words it is, and the
+// box behind them, which shows even without a font.
+const TOAST_TOP: f64 = 40.0;
+const TOAST_PADDING: f64 = 8.0;
+const TOAST_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    Globetrotter,
+    PuffedUp,
+    Untouchable,
+}
+
+impl Achievement {
+    // Its key in the string tables, for its name, and with .description on
+    // the end for what it's for.
+    pub fn key(&self) -> &'static str {
+        match *self {
+            Achievement::Globetrotter => "achievement.globetrotter",
+            Achievement::PuffedUp => "achievement.puffed_up",
+            Achievement::Untouchable => "achievement.untouchable",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Achievement::Globetrotter => "Globetrotter",
+            Achievement::PuffedUp => "Puffed up",
+            Achievement::Untouchable => "Untouchable",
+        }
+    }
+}
+
+// What App says has happened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    // Player one moved this many pixels.
+    Travelled(f64),
+    Expanded,
+    ClearedUntouched,
+}
+
+// The ones you've got, and the totals towards the ones you haven't.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Achievements {
+    pub unlocked: Vec<Achievement>,
+    #[serde(default)]
+    pub travelled: f64,
+    #[serde(default)]
+    pub expands: u32,
+}
+
+impl Achievements {
+    pub fn has(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    // Count `progress`, and hand back the achievement it's unlocked, if it
+    // unlocked one you didn't already have.
+    pub fn record(&mut self, progress: Progress) -> Option<Achievement> {
+        let earned = match progress {
+            Progress::Travelled(distance) => {
+                self.travelled += distance;
+                if self.travelled >= TRAVEL_DISTANCE {
+                    Some(Achievement::Globetrotter)
+                } else {
+                    None
+                }
+            }
+            Progress::Expanded => {
+                self.expands += 1;
+                if self.expands >= EXPANDS { Some(Achievement::PuffedUp) } else { None }
+            }
+            Progress::ClearedUntouched => Some(Achievement::Untouchable),
+        };
+        match earned {
+            Some(achievement) if !self.has(achievement) => {
+                self.unlocked.push(achievement);
+                Some(achievement)
+            }
+            _ => None,
+        }
+    }
+}
+
+/*
+ * The Progress in what's happened. Only player one's steps count, and
+ * anything more than TRAVEL_STEP in one tick is wrapping round the world's
+ * edge, which isn't really going anywhere. Winning counts as untouched if
+ * nothing hurt you on the way.
+ */
+pub fn hear(event: &GameEvent, app: &mut App) {
+    match *event {
+        GameEvent::PlayerMoved { player: 0, from, to } => {
+            let step = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+            if step > 0.0 && step < TRAVEL_STEP {
+                app.progress.push(Progress::Travelled(step));
+            }
+        }
+        GameEvent::StateChanged { to: LevelState::Complete { .. }, .. } if !app.touched => {
+            app.progress.push(Progress::ClearedUntouched);
+        }
+        _ => {}
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    PathBuf::from("achievements.json")
+}
+
+// Nothing there yet is nothing unlocked yet.
+pub fn load(path: &Path) -> Result<Achievements, String> {
+    if !path.exists() {
+        return Ok(Achievements::default());
+    }
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))
+}
+
+pub fn save(path: &Path, achievements: &Achievements) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(achievements).map_err(|e| e.to_string())?;
+    File::create(path)
+        .and_then(|mut f| f.write_all(json.as_bytes()))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub left: f64,
+}
+
+impl Toast {
+    pub fn unlocked(achievement: Achievement, locale: &Locale) -> Toast {
+        let key = achievement.key();
+        let description = locale.get(&format!("{}.description", key)).to_string();
+        Toast {
+            text: locale.text("achievement.toast", &[&locale.get(key), &description]),
+            left: TOAST_SECONDS,
+        }
+    }
+}
+
+// `dt` seconds go by, and the toasts that have been up long enough go.
+pub fn tick_toasts(toasts: &mut Vec<Toast>, dt: f64) {
+    for toast in toasts.iter_mut() {
+        toast.left -= dt;
+    }
+    toasts.retain(|t| t.left > 0.0);
+}
+
+/*
+ * Each toast is its words in a dark box, across the middle of the top of a
+ * `width` wide window, with any more underneath it. For its last TOAST_FADE
+ * seconds it fades out, box and words together.
+ */
+pub fn draw_toasts(toasts: &[Toast], hud: &mut Hud, size: u32, color: [f32; 4], width: f64,
+                   context: &Context, r: &mut dyn Renderer) {
+    let height = size as f64 + TOAST_PADDING * 2.0;
+    for (i, toast) in toasts.iter().enumerate() {
+        let fade = (toast.left / TOAST_FADE).min(1.0) as f32;
+        let mut background = TOAST_BACKGROUND;
+        background[3] *= fade;
+        let mut color = color;
+        color[3] *= fade;
+        let style = Text::new(size, color);
+        let text_width = style.width(&toast.text);
+        let y = TOAST_TOP + i as f64 * (height + TOAST_PADDING);
+        r.rect(context, (width - text_width) / 2.0 - TOAST_PADDING, y,
+               text_width + TOAST_PADDING * 2.0, height, background);
+        hud.draw_banner(&toast.text, &style, width, y + TOAST_PADDING, context, r);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use renderer::Recorder;
+
+    #[test]
+    fn achievements_unlock_once() {
+        let mut achievements = Achievements::default();
+        for _ in 1..EXPANDS {
+            assert_eq!(achievements.record(Progress::Expanded), None);
+        }
+        assert_eq!(achievements.record(Progress::Expanded), Some(Achievement::PuffedUp));
+        assert_eq!(achievements.record(Progress::Expanded), None);
+        assert_eq!(achievements.record(Progress::Travelled(9999.5)), None);
+        assert_eq!(achievements.record(Progress::Travelled(0.5)),
+                   Some(Achievement::Globetrotter));
+        assert_eq!(achievements.record(Progress::ClearedUntouched),
+                   Some(Achievement::Untouchable));
+        assert_eq!(achievements.record(Progress::ClearedUntouched), None);
+        assert_eq!(achievements.unlocked.len(), 3);
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let path = env::temp_dir().join(format!("jumpy-achievements-test-{}.json",
+                                                ::std::process::id()));
+        assert_eq!(load(&path), Ok(Achievements::default()));
+        let mut achievements = Achievements::default();
+        achievements.record(Progress::ClearedUntouched);
+        achievements.record(Progress::Travelled(12.5));
+        save(&path, &achievements).unwrap();
+        assert_eq!(load(&path), Ok(achievements));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn toasts_fade_away() {
+        let mut toasts = vec![Toast::unlocked(Achievement::Untouchable, &Locale::english())];
+        assert_eq!(toasts[0].text, "Achievement: Untouchable - Clear a level without getting hurt");
+        let mut r = Recorder::new();
+        let context = Context::new_abs(640.0, 480.0);
+        draw_toasts(&toasts, &mut Hud::none(), 16, [1.0; 4], 640.0, &context, &mut r);
+        assert_eq!(r.rects_in(TOAST_BACKGROUND).len(), 1);
+        tick_toasts(&mut toasts, TOAST_SECONDS - 0.25);
+        r.calls.clear();
+        draw_toasts(&toasts, &mut Hud::none(), 16, [1.0; 4], 640.0, &context, &mut r);
+        assert_eq!(r.rects_in([0.0, 0.0, 0.0, 0.375]).len(), 1);
+        tick_toasts(&mut toasts, 0.25);
+        assert!(toasts.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Flipbook animation. An animation is just a list of frames and how long to
+ * show each one for; to find out what to draw right now we work out how far
+ * into the list we've got. Looping animations go back to the start when they
+ * run out, and the others stop on their last frame.
+ *
+ * The frames themselves live in a sprite sheet: one picture with every frame
+ * laid out in a grid, one row per animation. Frame numbers here are columns
+ * in that grid, and the row comes from which animation it is (see Row).
+ */
+use controls::{Facing, InputState};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub frames: Vec<u32>,
+    pub frame_duration: f64,
+    pub looping: bool,
+}
+
+impl Animation {
+    // Which frame to show `time` seconds after the animation started.
+    pub fn frame_at(&self, time: f64) -> u32 {
+        let n = self.frames.len();
+        let step = (time / self.frame_duration).max(0.0) as usize;
+        let i = if self.looping { step % n } else { step.min(n - 1) };
+        self.frames[i]
+    }
+}
+
+/*
+ * What the player is up to, as far as animation cares: standing still, or
+ * walking in some direction. There are only walk cycles for the four
+ * straight-line directions, so walking diagonally borrows the left or right
+ * one - side-on is how a diagonal walk looks from above anyway.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationState {
+    Idle,
+    WalkUp,
+    WalkRight,
+    WalkDown,
+    WalkLeft,
+}
+
+impl AnimationState {
+    pub fn from_input(input: &InputState, facing: Facing) -> AnimationState {
+        let (dx, dy) = input.direction();
+        if dx == 0.0 && dy == 0.0 { return AnimationState::Idle }
+        AnimationState::walking(facing)
+    }
+
+    // Walking, facing `facing`. The diagonals look left or right.
+    pub fn walking(facing: Facing) -> AnimationState {
+        match facing {
+            Facing::Up => AnimationState::WalkUp,
+            Facing::Down => AnimationState::WalkDown,
+            Facing::UpRight | Facing::Right | Facing::DownRight => AnimationState::WalkRight,
+            Facing::UpLeft | Facing::Left | Facing::DownLeft => AnimationState::WalkLeft,
+        }
+    }
+
+    // Which row of the sprite sheet this state's frames are on.
+    pub fn row(&self) -> u32 {
+        match *self {
+            AnimationState::Idle => 0,
+            AnimationState::WalkUp => 1,
+            AnimationState::WalkRight => 2,
+            AnimationState::WalkDown => 3,
+            AnimationState::WalkLeft => 4,
+        }
+    }
+}
+
+// How many rows the sprite sheet has, one per AnimationState.
+pub const ROWS: u32 = 5;
+
+/*
+ * The animations to use for each state. They're all the same shape for now -
+ * a slow two-frame breathe for idle and a brisk four-frame walk - but each
+ * state gets its own so a fancier sheet can do fancier things.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationSet {
+    pub idle: Animation,
+    pub walk: Animation,
+}
+
+impl Default for AnimationSet {
+    fn default() -> AnimationSet {
+        AnimationSet {
+            idle: Animation { frames: vec![0, 1], frame_duration: 0.5, looping: true },
+            walk: Animation { frames: vec![0, 1, 2, 3], frame_duration: 0.12, looping: true },
+        }
+    }
+}
+
+impl AnimationSet {
+    pub fn get(&self, state: AnimationState) -> &Animation {
+        match state {
+            AnimationState::Idle => &self.idle,
+            _ => &self.walk,
+        }
+    }
+}
+
+/*
+ * The state machine. It remembers which state we're in and how long we've
+ * been in it; changing state starts the new animation from its first frame,
+ * so you don't start walking halfway through a stride.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animator {
+    pub state: AnimationState,
+    pub time: f64,
+}
+
+impl Animator {
+    pub fn new() -> Animator {
+        Animator { state: AnimationState::Idle, time: 0.0 }
+    }
+
+    pub fn update(&mut self, dt: f64, state: AnimationState) {
+        if state != self.state {
+            self.state = state;
+            self.time = 0.0;
+        } else {
+            self.time += dt;
+        }
+    }
+
+    // The (column, row) in the sprite sheet to draw right now.
+    pub fn cell(&self, set: &AnimationSet) -> (u32, u32) {
+        (set.get(self.state).frame_at(self.time), self.state.row())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use controls::{Facing, InputState};
+    use input::keyboard;
+
+    #[test]
+    fn looping_wraps_and_one_shots_stop() {
+        let mut anim = Animation { frames: vec![4, 5, 6], frame_duration: 0.1, looping: true };
+        let looped: Vec<u32> = (0..7).map(|i| anim.frame_at(i as f64 * 0.1 + 0.05)).collect();
+        assert_eq!(looped, vec![4, 5, 6, 4, 5, 6, 4]);
+
+        anim.looping = false;
+        assert_eq!(anim.frame_at(0.25), 6);
+        assert_eq!(anim.frame_at(100.0), 6);
+    }
+
+    #[test]
+    fn state_follows_the_keys() {
+        let mut input = InputState::default();
+        assert_eq!(AnimationState::from_input(&input, Facing::Left), AnimationState::Idle);
+        input.press(keyboard::Up);
+        assert_eq!(AnimationState::from_input(&input, Facing::Up), AnimationState::WalkUp);
+        input.press(keyboard::Right);
+        assert_eq!(AnimationState::from_input(&input, Facing::UpRight),
+                   AnimationState::WalkRight);
+    }
+
+    #[test]
+    fn changing_state_restarts_the_animation() {
+        let set = AnimationSet::default();
+        let mut animator = Animator::new();
+        animator.update(0.7, AnimationState::Idle);
+        assert_eq!(animator.cell(&set), (1, 0));
+
+        animator.update(0.01, AnimationState::WalkDown);
+        assert_eq!(animator.cell(&set), (0, 3));
+        animator.update(0.13, AnimationState::WalkDown);
+        assert_eq!(animator.cell(&set), (1, 3));
+    }
+}
@@ -0,0 +1,4605 @@
+/*
+ * The game itself: App, and everything that happens to it each tick.
+ * Nothing in here needs a window, which is what lets the tests (and
+ * --headless) play whole games without one. Drawing it is in render.rs,
+ * the keys are in handlers.rs, and the screens around it (the menu and
+ * so on) are in game.rs.
+ */
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use input::{Button, Keyboard, UpdateArgs};
+
+use achievements::{self, Progress};
+use animation::{AnimationSet, AnimationState, Animator};
+use audio::{self, Sound};
+use background::Background;
+use bounce;
+use bus::{self, Bus, GameEvent, HasBus};
+use camera::Camera;
+use collision::{self, Aabb, Body, Hits, Quadtree};
+use config::{Config, ConfigWatcher};
+use console::Console;
+use dump::{Dump, State};
+use checkpoint::{self, Checkpoint, Flag};
+use controls::{self, Facing, InputState};
+use cutscene::{self, Cutscene};
+use dialogue::{Dialogue, Npc};
+use inventory::{self, Door, DoorSpot, Inventory, Item, ItemKind};
+use ecs::{self, Kind, Scene};
+use enemy;
+use entity::{self, Entity, Pickup, Wall, WorldInfo};
+use frametime::{self, FrameGraph, FrameTimes, SystemTimes};
+use gamepad::ControllerMap;
+use handlers::{handleKey, handleRelease};
+use hud;
+use keys;
+use level;
+use locale::Locale;
+use log::{self, Level, RateLimit, Target};
+use lua::{Lua, Scripted, Thing};
+use macros::{self, Macros};
+use movement;
+use net::{Held, Link, Remote};
+use object::{self, GameObject};
+use particles::{self, Emitter, ParticleSettings};
+use palette::Palette;
+use pathfinding::{self, Grid, Paths};
+use player::{self, grown_by, player_bounds, shrink_expand, Player};
+use postprocess::Flash;
+use powerup::{self, Active, Effect, PowerUp};
+use projectile;
+use rewind::{self, Rewind};
+use rng::Rng;
+use save::{self, Snapshot};
+use script::{Action, Script, Step};
+use shake::ScreenShake;
+use spectate::{Frame, Shown, Spectators};
+use timers::{self, HasTimers, Timers};
+use touch::Touch;
+use trail::Trail;
+use units::{Letterbox, ScreenPos, WorldPos};
+use watch::FileWatcher;
+use waves::{self, Waves};
+use world::{self, WorldMode};
+
+/*
+ * Our App struct contains our game state. We define render and update
+ * on it as methods for our convenience, though they could easily be
+ * pure functions if we so chose.
+ * The drawing backend gl used to live in here too, but it's now handed to
+ * render instead. That keeps App purely game state, which means tests can
+ * make one without needing a window and an OpenGL context.
+ * settings is what the App was created from, kept so reset can rebuild
+ * everything exactly as it was at the start.
+ * players is everyone playing: just player one, or player two as well in
+ * co-op (see --coop). Each one has a position in the game world, their
+ * own expand, and their own held keys. Player one comes first, and a lot
+ * of the game is theirs alone: the camera follows them (well, the middle
+ * of everyone), the enemies chase them, and dashing, shooting, bouncing,
+ * platforming and the controller are all for player one. Player two just
+ * walks about, expands, and helps pick things up. Playing over the network,
+ * player two is the player at the other end, and link is how we hear from
+ * them; they move about on their own computer, not ours. spectators are
+ * the people watching (see spectate.rs), and lua is the --lua script, which
+ * gets a say in where everything goes (see lua.rs).
+ * controller_map says which controller buttons and axes do what.
+ * entities is the world's furniture - walls, pickups, and so on - as one
+ * big list of things implementing the Entity trait (see entity.rs). scene
+ * is everything that moves about on its own, enemies and projectiles, as
+ * entities made of components (see ecs.rs). The player stays special for
+ * now since the keys drive it directly. objects is a GameObject for each
+ * player, enemy and pickup, which is how they have their turn every tick
+ * and get drawn every frame (see object.rs). collected is the pickups
+ * that have been picked up this tick, which stay in entities until
+ * everyone's had their turn.
+ * particles are the confetti from the expand power, which has an Emitter
+ * of its own (see particles.rs), and trail is where the player's been
+ * lately (see trail.rs).
+ * rng is where the particle bursts get their randomness from. Because the
+ * rng is seeded we get the exact same burst every run, which is handy for
+ * tests.
+ * config is the current set of tunables, and config_watcher keeps an eye on
+ * the file they came from so we can pick up edits while the game runs.
+ * levels_watcher does the same for the levels (see watch.rs).
+ * ups is how many updates per second we'd like the event loop to run.
+ * game::run keeps an eye on it and rebuilds the loop when it changes.
+ * However often updates come, the game itself always moves on in ticks of SIM_DT;
+ * accumulator is the time that's built up towards the next one, and
+ * previous_camera (and each player's previous) are where things were before
+ * the last tick, so rendering can draw them part of the way between the two.
+ * spawn is where the player starts, goal is the green box they're trying
+ * to get to, and level_state says whether they've got there yet. elapsed
+ * and score are what we brag about when they do.
+ * quit gets set when the quit key is pressed, and game::run stops the loop.
+ * help_open is whether the help overlay is up, and console is the debug
+ * console (see console.rs), which may be open too. The game holds still
+ * while either is.
+ * bounce_mode swaps the usual walking about for pinball physics, and
+ * platformer swaps it for gravity and jumping. grounded is whether the
+ * player's standing on something, and jumping whether Space is still held
+ * from the jump we're in. solid_tiles are the tilemap's solid tiles, which
+ * only get in the way in platformer mode.
+ * window_size is how big the window is, as of the last resize event.
+ * view_size is how big the bit of it we draw the game in is: the same, or
+ * smaller if it's letterboxed. Both of those are in the window's points,
+ * which on a HiDPI screen are bigger than its pixels: draw_scale is how
+ * many pixels across a point is (2, usually, or 1 on an ordinary screen),
+ * and drawing goes by it to line up with them. camera is
+ * which bit of the world that window is showing. cursor is where the mouse
+ * is in the window, once it's been moved over it, for aiming with, and
+ * touch is the touch controls that can take it over (see touch.rs). The
+ * world itself is the size the level says, and world_mode says whether its edge is a wall or
+ * wraps round to the other side. shake jiggles the whole view about for a
+ * moment after expand fires. timers are things waiting to happen in so many
+ * seconds, or every so many seconds (see timers.rs).
+ * levels is every level there is, and level_index is which one we're on.
+ * The enemies in the scene chase the player about, round the walls by the
+ * paths they were last given, if they need to (see plan_paths). Each one that catches
+ * them costs a point of health, and after that hurt_timer keeps them from
+ * being hurt again until they've had a chance to get away. Running out of
+ * health costs one of your lives, and you start again from the level's spawn
+ * point; the last one going is game over. effects are
+ * the power-ups in effect, and how long each has left (see powerup.rs).
+ * paused stops time; the game is still drawn, but dimmed, with PAUSED across
+ * the middle. While paused, step_requested asks for exactly one tick
+ * to happen; ticks counts how many have happened in total, and clock and
+ * last_step are for keeping the stepping from running away with itself.
+ * animator keeps track of which frame of which animation the player is on,
+ * and animations says what those animations are. (The pictures themselves,
+ * and the HUD's font, belong to Game, further down, since they outlast any
+ * one App.)
+ * frames, updates and stats_timer count how many of each happened in the
+ * last second, for the debug log. frame_times keeps a closer eye on how long
+ * each frame takes; F3 turns show_frame_stats on, and frame_stats is the
+ * summary we show, refreshed every so often so it's possible to read.
+ * frame_graph is how long each of the last few frames spent updating and
+ * drawing, which F3 shows as a graph. system_times is how long a couple of
+ * the systems in an update have taken, all told, for --bench. quadtree is
+ * this tick's walls, sorted by where they are (see collision::Quadtree).
+ * show_debug is F4's debug drawing, of hitboxes and the like. The
+ * rate limits keep the per-update log messages down to something a person
+ * could read.
+ * recording is every key pressed and let go this game, stamped with the
+ * tick it happened after, and replay is a recording we're playing back.
+ * dash_timer is how much longer the dash we're in lasts, dash_velocity is
+ * where it's taking us, and dash_cooldown is how long until we can dash
+ * again.
+ * sounds are the sound effects asked for since Game last played them, and
+ * bumping is whether the player was up against a wall last time they
+ * moved, so leaning on one only makes a noise once. progress is the same
+ * idea for the achievements: what's happened since Game last asked that
+ * they'd want to know about (see achievements.rs). touched is whether
+ * anything has hurt the player since the level started.
+ * bus is how the rest of the game hears about what happens in a tick
+ * without update having to tell each of them (see bus.rs), and knocked_out
+ * is how many enemies have been, for the HUD, which keeps count of that by
+ * listening for it.
+ */
+
+pub struct App {
+    pub settings: Settings,
+    pub players: Vec<Player>,
+    pub link: Option<Rc<RefCell<Link>>>,
+    pub spectators: Option<Rc<RefCell<Spectators>>>,
+    pub lua: Option<Rc<RefCell<Lua>>>,
+    pub dump: Option<Rc<RefCell<Dump>>>,
+    pub controller_map: ControllerMap,
+    pub entities: Vec<Box<dyn Entity>>,
+    pub objects: Vec<Box<dyn GameObject>>,
+    pub collected: Vec<usize>,
+    pub scene: Scene,
+    pub paths: Paths,
+    pub particles: Emitter,
+    pub trail: Trail,
+    pub background: Background,
+    // The cutscene that's playing, if there is one (see cutscene.rs).
+    pub cutscene: Option<Cutscene>,
+    pub rng: Rng,
+    pub config: Config,
+    pub config_watcher: ConfigWatcher,
+    pub levels_watcher: Option<FileWatcher>,
+    pub ups: u64,
+    pub spawn: (f64, f64),
+    pub goal: Aabb,
+    pub level_state: LevelState,
+    pub elapsed: f64,
+    pub score: u32,
+    pub quit: bool,
+    pub help_open: bool,
+    // What you're carrying, for opening doors with (see inventory.rs).
+    pub inventory: Inventory,
+    // The dialogue box, while someone's talking to you (see dialogue.rs).
+    pub dialogue: Option<Dialogue>,
+    pub console: Console,
+    pub bounce_mode: bool,
+    pub platformer: bool,
+    pub grounded: bool,
+    pub jumping: bool,
+    pub solid_tiles: Vec<Aabb>,
+    pub window_size: (f64, f64),
+    pub view_size: (f64, f64),
+    pub draw_scale: f64,
+    pub camera: Camera,
+    pub cursor: Option<(f64, f64)>,
+    pub touch: Touch,
+    pub shake: ScreenShake,
+    pub flash: Flash,
+    pub timers: Timers<App>,
+    // Which wave of enemies we're on, and how long until the next (see
+    // waves.rs).
+    pub waves: Waves,
+    pub levels: Vec<level::Level>,
+    pub level_index: usize,
+    pub health: u32,
+    pub lives: u32,
+    pub hurt_timer: f64,
+    pub effects: Vec<Active>,
+    pub dash_timer: f64,
+    pub dash_velocity: (f64, f64),
+    pub dash_cooldown: f64,
+    pub world_mode: WorldMode,
+    pub paused: bool,
+    pub step_requested: bool,
+    pub ticks: u64,
+    pub accumulator: f64,
+    pub previous_camera: (f64, f64),
+    pub clock: f64,
+    pub last_step: f64,
+    pub animator: Animator,
+    pub animations: AnimationSet,
+    pub frames: u32,
+    pub updates: u32,
+    pub stats_timer: f64,
+    pub frame_times: FrameTimes,
+    pub frame_graph: FrameGraph,
+    pub system_times: SystemTimes,
+    pub quadtree: Quadtree,
+    pub show_frame_stats: bool,
+    pub show_debug: bool,
+    pub frame_stats: String,
+    pub frame_stats_timer: f64,
+    pub recording: Vec<Step>,
+    pub replay: Option<Script>,
+    pub sounds: Vec<Sound>,
+    pub bumping: bool,
+    // The door we last walked into without what it needs, and what that
+    // was, so it's only said once.
+    pub locked_door: Option<(String, u32)>,
+    // The last checkpoint touched this level, if any, which losing a life
+    // goes back to.
+    pub checkpoint: Option<Checkpoint>,
+    // The last few seconds, a tick at a time, and whether the rewind key's
+    // held to go back through them (see rewind.rs).
+    pub rewind: Rewind,
+    pub rewinding: bool,
+    // The macros going, and the one being recorded (see macros.rs).
+    pub macros: Macros,
+    pub progress: Vec<Progress>,
+    pub touched: bool,
+    pub bus: Bus<App>,
+    pub knocked_out: u32,
+    pub position_log: RateLimit,
+    pub collision_log: RateLimit
+}
+
+/*
+ * Everything App::new needs to get going. The config here is the one we
+ * started with; the live one on App may have been hot reloaded since.
+ * levels has to have at least one level in it; game::run makes sure of that.
+ * levels_path is where they came from, if they came from anywhere (the
+ * levels directory, or the file given to --level), so that restarting
+ * after a game over can load them afresh, and so that they can be watched
+ * for changes while we play.
+ * replay is a recording to play back instead of listening to the keyboard,
+ * and record_path is where to write the recording of this game when it
+ * ends, if anywhere. coop brings in a second player, and so does link, only
+ * that one's on another computer (see net.rs). The link is shared by every
+ * game played this run, which is why it's in an Rc: each App gets a handle
+ * to the same socket, rather than a socket of its own. spectators is the
+ * same again, for the people watching (see spectate.rs), and so is lua,
+ * the script from --lua, so its variables carry on from one game to the
+ * next (see lua.rs), and dump, where --dump-state writes to (see dump.rs).
+ */
+#[derive(Clone)]
+pub struct Settings {
+    pub config: Config,
+    pub config_path: PathBuf,
+    pub ups: u64,
+    pub seed: u64,
+    pub levels: Vec<level::Level>,
+    pub levels_path: Option<PathBuf>,
+    pub replay: Option<Script>,
+    pub record_path: Option<PathBuf>,
+    pub coop: bool,
+    pub link: Option<Rc<RefCell<Link>>>,
+    pub spectators: Option<Rc<RefCell<Spectators>>>,
+    pub lua: Option<Rc<RefCell<Lua>>>,
+    pub dump: Option<Rc<RefCell<Dump>>>,
+    // The words for the HUD, in the config's language.
+    pub locale: Locale,
+}
+
+/*
+ * Either we're playing the level, or the player has just finished it and
+ * we're enjoying a little victory lap before starting again, or they've run
+ * out of health and it's all over. The Complete variant carries its own
+ * timer: in rust an enum variant can hold data, so the timer only exists
+ * while it actually means something.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelState {
+    Playing,
+    Complete { timer: f64 },
+    Lost,
+}
+
+
+// How many frames' times we average over, and how often the summary of them
+// that F3 shows gets refreshed.
+const FRAME_TIME_SAMPLES: usize = 120;
+const FRAME_STATS_REFRESH_SECONDS: f64 = 0.5;
+
+// How long one tick of the game is: 120 of them a second, whatever the
+// update rate. See App::update.
+pub const SIM_DT: f64 = 1.0 / 120.0;
+// The most time App::update will try to catch up on at once.
+pub const MAX_CATCH_UP_SECONDS: f64 = 0.25;
+
+// How far one press of . moves time on while paused (one tick, so stepping
+// through goes exactly the way playing would), and how soon after one step
+// the next is allowed.
+pub const STEP_DT: f64 = SIM_DT;
+const STEP_REPEAT_SECONDS: f64 = 0.1;
+
+// The update rates F7 and F8 cycle between, and the options screen.
+pub const UPS_STEPS: [u64; 3] = [30, 60, 120];
+
+// How far from player one an enemy spawned by enemy_spawn_seconds tries to
+// turn up.
+const ENEMY_SPAWN_DISTANCE: f64 = 200.0;
+
+// Further than player one can go in a tick, even dashing at 30 updates a
+// second. See achievements::hear.
+pub const TRAVEL_STEP: f64 = 100.0;
+
+/*
+ * Here's where we define methods on the App struct. Rust does not explicitly
+ * support classes, but we can attach functions to structs as methods, such
+ * that we can simulate them. We actually get more versatility than with 
+ * class based OO because of Rust's traits system, and because we don't have 
+ * classes, we dont have the wretched mess that is inheritance.
+ */
+impl App {
+    /*
+     * Making a new App. Rather than filling in every field here and then
+     * again in reset (and inevitably forgetting one in one of them), new
+     * fills in the things that survive a reset and lets reset do the rest.
+     * The placeholder values below get overwritten straight away.
+     */
+    pub fn new(settings: Settings) -> App {
+        let mut app = App {
+            config: settings.config.clone(),
+            config_watcher: ConfigWatcher::new(&settings.config_path),
+            levels_watcher: settings.levels_path.as_ref().map(|path| FileWatcher::new(path)),
+            ups: settings.ups,
+            rng: Rng::new(settings.seed),
+            players: (0..if settings.coop || settings.link.is_some() { 2 } else { 1 })
+                .map(|_| Player::at(0.0, 0.0))
+                .collect(),
+            link: settings.link.clone(),
+            spectators: settings.spectators.clone(),
+            lua: settings.lua.clone(),
+            dump: settings.dump.clone(),
+            controller_map: ControllerMap::default(),
+            entities: Vec::new(),
+            objects: Vec::new(),
+            collected: Vec::new(),
+            scene: Scene::new(),
+            paths: Paths::new(),
+            particles: Emitter::new(ParticleSettings::from_config(&settings.config)),
+            trail: Trail::new(),
+            background: Background::new(),
+            cutscene: None,
+            spawn: (0.0, 0.0),
+            goal: Aabb::new(0.0, 0.0, 0.0, 0.0),
+            level_state: LevelState::Playing,
+            elapsed: 0.0,
+            score: 0,
+            quit: false,
+            help_open: false,
+            inventory: Inventory::new(),
+            dialogue: None,
+            console: Console::default(),
+            bounce_mode: false,
+            platformer: false,
+            grounded: false,
+            jumping: false,
+            solid_tiles: Vec::new(),
+            window_size: (settings.config.window_width as f64,
+                          settings.config.window_height as f64),
+            view_size: (settings.config.window_width as f64,
+                        settings.config.window_height as f64),
+            camera: Camera::new(settings.config.window_width as f64,
+                                settings.config.window_height as f64),
+            cursor: None,
+            touch: Touch::new(settings.config.touch_controls),
+            draw_scale: 1.0,
+            shake: ScreenShake::new(settings.seed),
+            flash: Flash::default(),
+            timers: Timers::new(),
+            waves: Waves::new(settings.config.wave_grace_seconds),
+            levels: settings.levels.clone(),
+            level_index: 0,
+            health: 0,
+            lives: settings.config.lives,
+            hurt_timer: 0.0,
+            effects: Vec::new(),
+            dash_timer: 0.0,
+            dash_velocity: (0.0, 0.0),
+            dash_cooldown: 0.0,
+            world_mode: WorldMode::Bounded,
+            paused: false,
+            step_requested: false,
+            ticks: 0,
+            accumulator: 0.0,
+            previous_camera: (0.0, 0.0),
+            clock: 0.0,
+            last_step: std::f64::NEG_INFINITY,
+            animator: Animator::new(),
+            animations: AnimationSet::default(),
+            frames: 0,
+            updates: 0,
+            stats_timer: 0.0,
+            frame_times: FrameTimes::new(FRAME_TIME_SAMPLES),
+            frame_graph: FrameGraph::new(),
+            system_times: SystemTimes::default(),
+            quadtree: Quadtree::new(Vec::new()),
+            show_frame_stats: false,
+            show_debug: false,
+            frame_stats: String::new(),
+            frame_stats_timer: 0.0,
+            recording: Vec::new(),
+            replay: None,
+            sounds: Vec::new(),
+            bumping: false,
+            locked_door: None,
+            checkpoint: None,
+            rewind: Rewind::new(settings.config.rewind_seconds),
+            rewinding: false,
+            macros: Macros::new(),
+            progress: Vec::new(),
+            touched: false,
+            bus: Bus::new(),
+            knocked_out: 0,
+            // Last, since it's the one field that moves settings rather
+            // than copying bits out of it.
+            settings: settings,
+            position_log: RateLimit::new(0.25),
+            collision_log: RateLimit::new(0.5)
+        };
+        // Who's listening for what happens. Each says what it listens for
+        // where it does its listening.
+        app.bus.subscribe(audio::hear);
+        app.bus.subscribe(achievements::hear);
+        app.bus.subscribe(particles::hear);
+        app.bus.subscribe(hud::hear);
+        app.reset();
+        app
+    }
+
+    /*
+     * Put the current level back how it was at the start: players on the
+     * spawn point (player two a little to its right), no expand, no score,
+     * the walls and pickups rebuilt from the level (see build_level), any
+     * particles gone, and the rng reseeded so the next burst looks just like
+     * the first one did. If the level has a cutscene, it starts again too.
+     * What it deliberately leaves alone is your config tweaks and the update
+     * rate, since you probably don't want a reset to undo those. It also
+     * doesn't change which level you're on; next_level does that. Nor does
+     * it give you back any lives you've lost, or resetting would be a way
+     * out of ever running out; only a new game does that.
+     */
+    pub fn reset(&mut self) {
+        let level = self.level().clone();
+        let spawn = self.config.start_position.unwrap_or(level.spawn);
+        self.spawn = (spawn[0], spawn[1]);
+        let offset = self.config.player_two_offset;
+        let size = player_size(&self.config);
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.base_size = size;
+            player.respawn(spawn[0] + i as f64 * offset, spawn[1]);
+        }
+        self.build_level();
+        self.grounded = false;
+        self.jumping = false;
+        self.particles.clear();
+        self.health = self.config.player_health;
+        self.hurt_timer = 0.0;
+        self.touched = false;
+        self.effects.clear();
+        self.inventory.clear();
+        self.dash_timer = 0.0;
+        self.dash_cooldown = 0.0;
+        self.bumping = false;
+        self.locked_door = None;
+        self.checkpoint = None;
+        self.rewind = Rewind::new(self.config.rewind_seconds);
+        self.rng = Rng::new(self.settings.seed);
+        self.shake = ScreenShake::new(self.settings.seed);
+        self.flash = Flash::default();
+        self.timers.clear();
+        if self.config.enemy_spawn_seconds > 0.0 {
+            self.timers.every(self.config.enemy_spawn_seconds,
+                              |app: &mut App| app.spawn_enemy_somewhere());
+        }
+        self.waves = Waves::new(self.config.wave_grace_seconds);
+        self.paths.clear();
+        if self.config.enemy_path_seconds > 0.0 {
+            self.timers.every(self.config.enemy_path_seconds, |app: &mut App| app.plan_paths());
+        }
+        self.elapsed = 0.0;
+        self.score = 0;
+        self.knocked_out = 0;
+        self.ticks = 0;
+        self.animator = Animator::new();
+        self.snap_camera();
+        self.dialogue = None;
+        self.change_state(LevelState::Playing);
+        info!("Level {} started: {}", self.level_index + 1, level.name);
+        self.cutscene = if level.cutscene_steps.is_empty() {
+            None
+        } else {
+            info!("Playing its cutscene ({} steps)", level.cutscene_steps.len());
+            Some(Cutscene::new(level.cutscene_steps))
+        };
+    }
+
+    // Everything in the level that comes from its file: the goal, the walls
+    // and doors, the pickups and items, the people, the checkpoints, the
+    // ground, and the enemies.
+    pub fn build_level(&mut self) {
+        let level = self.level().clone();
+        let palette = self.config.palette();
+        let g = level.goal;
+        self.goal = Aabb::new(g[0], g[1], g[2], g[3]);
+        let mut entities: Vec<Box<dyn Entity>> = Vec::new();
+        for w in level.walls.iter() {
+            entities.push(Box::new(Wall {
+                bounds: Aabb::new(w[0], w[1], w[2], w[3]),
+                color: palette.wall,
+            }));
+        }
+        for p in level.collectibles.iter() {
+            entities.push(Box::new(Pickup::at(p[0], p[1], palette.pickup)));
+        }
+        for spot in level.power_ups.iter() {
+            let color = palette.power_up(spot.effect);
+            entities.push(Box::new(PowerUp::at(spot.at[0], spot.at[1], spot.effect, color)));
+        }
+        for spot in level.doors.iter() {
+            entities.push(Box::new(door(spot, &level.items, &palette)));
+        }
+        for kind in level.items.iter() {
+            for p in kind.at.iter() {
+                entities.push(Box::new(Item::at(p[0], p[1], &kind.name, kind.color)));
+            }
+        }
+        for npc in level.npcs.iter() {
+            entities.push(Box::new(Npc::at(npc.at[0], npc.at[1], palette.npc, npc.pages.clone())));
+        }
+        for p in level.checkpoints.iter() {
+            entities.push(Box::new(Flag::at(p[0], p[1], palette.wall, palette.goal)));
+        }
+        self.entities = entities;
+        self.solid_tiles = level.ground.solid_boxes();
+        self.scene = Scene::new();
+        for e in level.enemies.iter() {
+            self.spawn_enemy(e[0], e[1]);
+        }
+    }
+
+    // The level we're playing.
+    pub fn level(&self) -> &level::Level {
+        &self.levels[self.level_index]
+    }
+
+    // How big the world is, which is up to the level.
+    pub fn world_size(&self) -> (f64, f64) {
+        (self.level().world_width, self.level().world_height)
+    }
+
+    // On to the next level, or back round to the first after the last.
+    pub fn next_level(&mut self) {
+        self.level_index = (self.level_index + 1) % self.levels.len();
+        self.reset();
+    }
+
+    // Return, during a cutscene: everything goes straight to where the
+    // cutscene would have left it, and the camera back to the player.
+    pub fn skip_cutscene(&mut self) {
+        if let Some(mut cutscene) = self.cutscene.take() {
+            cutscene.skip(self);
+            self.snap_camera();
+            info!("Skipped the cutscene");
+        }
+    }
+    
+    /*
+     * update is what the event loop calls, and it decides whether the game
+     * actually moves forward this time. About once a second it asks the
+     * watchers whether config.json or the levels have changed, and swaps in
+     * the new ones if so; that happens whatever state we're in, so you can
+     * tweak things while paused too.
+     * clock counts real update time, paused or not. Step mode uses it to
+     * make sure a held-down . can't step more than ten times a second (your
+     * keyboard repeats a held key much faster than that). It's also the
+     * time every log line is stamped with.
+     */
+    pub fn update(&mut self, args: &UpdateArgs) {
+        self.clock += args.dt;
+        log::set_time(self.clock);
+        // Keys due from a replay go in first, so that a replayed P still
+        // gets us out of pause.
+        self.feed_replay();
+        self.log_stats(args.dt);
+        if let Some(config) = self.config_watcher.update(args.dt, &self.config) {
+            self.apply_config(config);
+        }
+        let levels_changed = match self.levels_watcher {
+            Some(ref mut watcher) => watcher.update(args.dt),
+            None => false,
+        };
+        if levels_changed {
+            self.reload_levels();
+        }
+        // The background's only scenery, so it goes on moving whatever the
+        // game's doing, and at the real time, not the ticks'.
+        self.background.update(args.dt);
+
+        // Nothing moves while you're reading the help, or typing, or being
+        // talked to, except the words coming up in the dialogue box.
+        if let Some(ref mut dialogue) = self.dialogue {
+            dialogue.update(args.dt, self.config.dialogue_chars_per_second);
+            return;
+        }
+        if self.help_open || self.console.open { return }
+
+        // Paused, the only way forward is one step at a time. A step always
+        // moves time on by exactly STEP_DT, however long it's really been.
+        if self.paused {
+            if self.step_requested && self.clock - self.last_step >= STEP_REPEAT_SECONDS {
+                self.last_step = self.clock;
+                self.advance(STEP_DT);
+                self.spectate();
+                self.dump_state();
+                debug!("Stepped to tick {}", self.ticks);
+            }
+            self.step_requested = false;
+            return;
+        }
+
+        /*
+         * The fixed timestep. Rather than moving the game on by however
+         * long this update happened to be, we save the time up, and spend
+         * it in ticks of exactly SIM_DT: none if not enough has built up
+         * yet, several if a lot has. So the game goes through exactly the
+         * same ticks whether updates come 30 or 144 times a second, which
+         * means exactly the same game. Whatever's left over waits for next
+         * time, and rendering uses it to draw the player the right fraction
+         * of the way to the next tick, so it still moves smoothly.
+         * If the game stalls for a while (dragging the window about, say)
+         * we don't try to catch all of it up at once, which would only make
+         * the next update slow too; past MAX_CATCH_UP_SECONDS, the time is
+         * just lost. The tiny allowance on the comparison stops rounding
+         * errors costing us a tick: 144 lots of 1/144 doesn't quite add up
+         * to 1.
+         */
+        self.accumulator = (self.accumulator + args.dt).min(MAX_CATCH_UP_SECONDS);
+        while self.accumulator >= SIM_DT - 1e-9 {
+            self.accumulator -= SIM_DT;
+            self.feed_replay();
+            macros::run(self);
+            self.advance(SIM_DT);
+            self.spectate();
+            self.dump_state();
+        }
+    }
+
+    // Swap in a config that's just been reloaded.
+    pub fn apply_config(&mut self, config: Config) {
+        if (&config.reset_key, &config.quit_key, &config.help_key) !=
+           (&self.config.reset_key, &self.config.quit_key, &self.config.help_key) {
+            info!("Keys are now {} to reset, {} to quit and {} for help",
+                  config.reset_key, config.quit_key, config.help_key);
+        }
+        self.config = config;
+        // The scene's colors are in its Renderables, so a new config has to
+        // repaint them.
+        let palette = self.config.palette();
+        self.scene.repaint(Kind::Enemy, palette.enemy);
+        self.scene.repaint(Kind::Shot, palette.projectile);
+        // The particles' knobs are in there too. The ones already flying
+        // keep the look they were born with.
+        self.particles.settings = ParticleSettings::from_config(&self.config);
+        // The players might be a different size now. If that's put them
+        // partway into a wall, the next tick pushes them out.
+        let size = player_size(&self.config);
+        for player in self.players.iter_mut() {
+            player.base_size = size;
+        }
+        // letterbox might have been switched on or off, or the view might be
+        // a new size.
+        let (width, height) = self.window_size;
+        self.resize_window(width, height);
+    }
+
+    /*
+     * Read the levels again, because one of their files has changed. The
+     * level we're on gets rebuilt from its new version - walls, pickups,
+     * enemies, goal and ground all come from the file again - but the
+     * players stay where they are and the score and the clock carry on, so
+     * you can watch the bit you're working on change without walking back
+     * to it. (Pickups you've already got come back, mind.) If the levels
+     * won't load any more, we keep playing the ones we had.
+     */
+    pub fn reload_levels(&mut self) {
+        let path = match self.settings.levels_path {
+            Some(ref path) => path.clone(),
+            None => return,
+        };
+        let levels = level::load_from(&path);
+        if levels.is_empty() {
+            warn!("Couldn't reload the levels from {}, keeping the ones we had", path.display());
+            return;
+        }
+        info!("Reloaded the levels from {}", path.display());
+        self.levels = levels;
+        // If the level we were on has gone, start again from the first.
+        if self.level_index >= self.levels.len() {
+            self.level_index = 0;
+            self.reset();
+            return;
+        }
+        self.build_level();
+        for i in 0..self.local_players() {
+            self.push_out_of_walls(i);
+        }
+    }
+
+    /*
+     * F10: read everything in again now, rather than waiting for the
+     * watchers to notice. (Game reloads the pictures and the sounds.) The
+     * watchers are told to look too, so they don't notice the same change
+     * a second later and do it all over again.
+     */
+    pub fn reload_files(&mut self) {
+        info!("Reloading everything");
+        if let Some(config) = self.config_watcher.reload(&self.config) {
+            self.apply_config(config);
+        }
+        if let Some(ref mut watcher) = self.levels_watcher {
+            watcher.changed();
+        }
+        self.reload_levels();
+    }
+
+    /*
+     * Tell anyone watching how the tick went. Most of the time nobody is,
+     * and then all this costs is a look to see if anyone's turned up.
+     */
+    pub fn spectate(&mut self) {
+        let spectators = match self.spectators {
+            Some(ref spectators) => spectators.clone(),
+            None => return,
+        };
+        let mut spectators = spectators.borrow_mut();
+        spectators.accept();
+        if spectators.watching() > 0 {
+            spectators.send(&self.frame());
+        }
+    }
+
+    // And tell --dump-state, if it's this tick's turn. See dump.rs.
+    pub fn dump_state(&mut self) {
+        let dump = match self.dump {
+            Some(ref dump) => dump.clone(),
+            None => return,
+        };
+        let mut dump = dump.borrow_mut();
+        if !dump.due(self.ticks) { return }
+        let state = State { frame: self.frame(), save: self.snapshot() };
+        if let Err(e) = dump.write(&state) {
+            warn!("{}", e);
+        }
+    }
+
+    // The game as spectators see it: see spectate.rs.
+    pub fn frame(&self) -> Frame {
+        let corners = |b: Aabb| [b.x, b.y, b.w, b.h];
+        let (world_width, world_height) = self.world_size();
+        Frame {
+            tick: self.ticks,
+            level: self.level_index,
+            level_name: self.level().name.clone(),
+            state: match self.level_state {
+                LevelState::Playing => "Playing",
+                LevelState::Complete { .. } => "Complete",
+                LevelState::Lost => "Lost",
+            }.to_string(),
+            score: self.score,
+            health: self.health,
+            elapsed: self.elapsed,
+            world: [world_width, world_height],
+            camera: [self.camera.x, self.camera.y],
+            view: [self.view_size.0, self.view_size.1],
+            players: (0..self.players.len()).map(|i| Shown {
+                bounds: corners(self.player_box_of(i)),
+                facing: self.players[i].facing,
+                color: self.player_color(i),
+            }).collect(),
+            walls: self.entities.iter().filter(|e| e.solid()).map(|e| corners(e.bounds()))
+                .collect(),
+            pickups: self.entities.iter().filter(|e| e.collectible())
+                .map(|e| corners(e.bounds()))
+                .collect(),
+            enemies: self.scene.boxes(Kind::Enemy).into_iter().map(corners).collect(),
+            projectiles: self.scene.boxes(Kind::Shot).into_iter().map(corners).collect(),
+            goal: corners(self.goal),
+        }
+    }
+
+    /*
+     * Replays. Every key that reaches handleKey or handleRelease gets
+     * written down in recording, stamped with the time of the tick it came
+     * after (ticks times SIM_DT, not the clock, which depends on how often
+     * updates happen to come). Playing a recording back hands each key to
+     * the same functions just before the same tick, and because the game
+     * only ever moves in ticks of SIM_DT, from the same seed, it goes
+     * through exactly the same ticks as the first time.
+     * Mostly. The D-pad and sticks aren't recorded, and step mode's limit
+     * of ten steps a second goes by the clock, so a replay that steps while
+     * paused can step a different number of times. Neither is worth the
+     * trouble for now.
+     */
+    pub fn feed_replay(&mut self) {
+        let steps = match self.replay {
+            Some(ref mut replay) => {
+                let steps = replay.due(self.ticks as f64 * SIM_DT);
+                if !steps.is_empty() && replay.finished() {
+                    info!("That's the last key in the replay");
+                }
+                steps
+            }
+            None => return,
+        };
+        for step in steps {
+            match step.action {
+                Action::Press => handleKey(Keyboard(step.key), self),
+                Action::Release => handleRelease(Keyboard(step.key), self),
+            }
+        }
+    }
+
+    // Whether we're playing a replay back rather than listening to the
+    // keyboard.
+    pub fn replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Note down a key for the recording, if it's a key.
+    pub fn record(&mut self, key: Button, action: Action) {
+        if let Keyboard(k) = key {
+            let at = self.ticks as f64 * SIM_DT;
+            self.recording.push(Step { at: at, action: action, key: k });
+        }
+    }
+
+    /*
+     * One tick of the game proper. Normal play and step mode both come
+     * through here, so stepping can't behave any differently to playing.
+     * Here, we shrink the value of expand every frame if it's set, so as to
+     * make the player square shrink back to normal, and set off any timers
+     * that are due.
+     * Every entity then gets its turn. Everything is scaled by dt, the number
+     * of seconds since the last update, so a particle moving at 100 pixels
+     * per second goes 100 pixels in a second no matter how often update gets
+     * called. (expand used to shrink by a flat 1.0 per update, which meant it
+     * shrank four times faster at 120 updates per second than at 30. Oops.)
+     * Anything that happened on the way that the rest of the game might
+     * want to hear about goes on the bus, and once the tick's over it all
+     * gets delivered (see bus.rs).
+     */
+    pub fn advance(&mut self, dt: f64) {
+        self.tick(dt);
+        bus::deliver(self);
+    }
+
+    fn tick(&mut self, dt: f64) {
+        self.ticks += 1;
+        for player in self.players.iter_mut() {
+            player.previous = (player.x, player.y);
+        }
+        self.previous_camera = (self.camera.x, self.camera.y);
+        self.shake.update(dt);
+        self.flash.update(dt);
+        self.exchange();
+
+        // If the level's done, everything holds still while the goal flashes
+        // and the timer runs down.
+        match self.level_state {
+            LevelState::Complete { timer } => {
+                let timer = timer + dt;
+                // Then it's on to the next level. Finishing the last one
+                // goes back round to the first.
+                if timer >= self.config.level_complete_seconds {
+                    info!("Victory lap over, on to the next level");
+                    self.next_level();
+                } else {
+                    self.level_state = LevelState::Complete { timer: timer };
+                }
+                return;
+            }
+            // It's over. Game notices, and takes us to the game-over screen.
+            LevelState::Lost => return,
+            LevelState::Playing => {}
+        }
+
+        // A cutscene has the level to itself while it's on: it moves
+        // whoever it's moving about, and nothing else happens.
+        if self.cutscene.is_some() {
+            cutscene::run(self, dt);
+            return;
+        }
+
+        // Holding the rewind key, the tick goes backwards instead. Going
+        // forwards, how things are before they move goes on the record
+        // for next time it's held.
+        if self.rewinding {
+            rewind::step_back(self, dt);
+            return;
+        }
+        rewind::record(self);
+
+        self.elapsed += dt;
+        timers::run(self, dt);
+        waves::update(self, dt);
+        for effect in powerup::tick(&mut self.effects, dt) {
+            info!("The {} power-up's worn off", effect.name());
+        }
+        // Where we were at the end of the last tick goes on the trail. Not
+        // where we are after this one: the player's drawn somewhere between
+        // the two, and the trail shouldn't stick out in front.
+        let was = self.player_box();
+        self.trail.record(was, self.config.trail_length);
+        // And the enemies can bite again once this runs out (see
+        // update_enemy).
+        self.hurt_timer = (self.hurt_timer - dt).max(0.0);
+
+        // Then everyone has their turn: the players move, the enemies go
+        // after them, and the pickups get picked up (see object.rs).
+        self.sort_walls();
+        object::update_all(self, dt);
+        self.drop_collected();
+        self.dash_timer = (self.dash_timer - dt).max(0.0);
+        self.dash_cooldown = (self.dash_cooldown - dt).max(0.0);
+        self.update_camera(dt);
+
+        // Then the rest of the scene's systems, each doing its one job to
+        // everything it applies to: projectiles hit whatever's in their
+        // way, anything with a velocity moves, and anything with a lifetime
+        // gets older. The shots hitting things are timed for --bench, like
+        // the enemies and the pickups are (see object.rs).
+        let started = Instant::now();
+        let damage = self.config.projectile_damage;
+        let hit = projectile::hit_all(&mut self.scene, &self.quadtree, damage, dt);
+        self.system_times.collision += frametime::seconds(started.elapsed());
+        if !hit.is_empty() {
+            info!(target: Collision, "Shot {} {}, {} left", hit.len(),
+                  if hit.len() == 1 { "enemy" } else { "enemies" }, self.scene.count(Kind::Enemy));
+        }
+        for b in hit {
+            self.bus.publish(GameEvent::EnemyKilled { at: (b.x + b.w / 2.0, b.y + b.h / 2.0) });
+        }
+        ecs::movement(&mut self.scene, dt);
+        ecs::ageing(&mut self.scene, dt);
+        self.scene.flush();
+        self.run_lua(dt);
+
+        // Where the player is, a few times a second, for anyone tracing.
+        if log::enabled(Level::Trace, Target::Game) && self.position_log.ready(self.clock) {
+            for (i, p) in self.players.iter().enumerate() {
+                trace!("Player {} at ({:.1}, {:.1}), facing {:?}, expand {:.1}",
+                       i + 1, p.x, p.y, p.facing, p.expand);
+            }
+        }
+
+        // Doors are collision too, as far as --bench is concerned.
+        let started = Instant::now();
+        self.open_doors();
+        checkpoint::reach(self);
+        self.system_times.collision += frametime::seconds(started.elapsed());
+
+        let (width, height) = self.world_size();
+        let world = WorldInfo {
+            player: self.player_box(),
+            width: width,
+            height: height,
+            checkpoint: self.checkpoint.as_ref().map(|c| c.at),
+        };
+        entity::update_all(&mut self.entities, dt, &world);
+        self.particles.update(dt);
+
+        // Checked last, after expand has had a chance to shrink, since a
+        // puffed-up player might be too big to fit in the goal. In co-op,
+        // either player getting there will do.
+        let goal = self.goal;
+        if self.local_boxes().into_iter().any(|b| reached_goal(b, goal)) {
+            info!("Level complete! Time: {:.2}s, score: {}", self.elapsed, self.score);
+            self.change_state(LevelState::Complete { timer: 0.0 });
+        }
+    }
+
+    // Go into state `to`, telling the bus if that's a different one.
+    pub fn change_state(&mut self, to: LevelState) {
+        let from = self.level_state;
+        self.level_state = to;
+        if !bus::same_state(from, to) {
+            self.bus.publish(GameEvent::StateChanged { from: from, to: to });
+        }
+    }
+
+    /*
+     * Playing over the network, once a tick: tell the other end where
+     * player one is and what they're holding, and hear the same about
+     * theirs. Their player is our player two. Whatever they said last holds
+     * until they say something new, so a lost datagram just means their
+     * square sits still for a tick. See net.rs.
+     */
+    pub fn exchange(&mut self) {
+        let link = match self.link {
+            Some(ref link) => link.clone(),
+            None => return,
+        };
+        let mut link = link.borrow_mut();
+        {
+            let me = &self.players[0];
+            link.send(Held::from_input(&me.input),
+                      Remote { x: me.x, y: me.y, expand: me.expand, facing: me.facing });
+        }
+        if let Some(message) = link.receive() {
+            let them = &mut self.players[1];
+            them.x = message.player.x;
+            them.y = message.player.y;
+            them.expand = message.player.expand;
+            them.facing = message.player.facing;
+            them.input = message.held.to_input();
+        }
+    }
+
+    /*
+     * Player `i`'s turn (see object::PlayerObject). Expand shrinks back
+     * down first, and expand grows the hitbox, not just the picture, so
+     * puffing up next to a wall would leave us partway inside it. Don't let
+     * it. The same goes for the size power-up.
+     *
+     * Then here are our movement controls. Because we use Cartesian
+     * coordinates to describe our world, we simply add a value
+     * to the x or y coordinate to represent movement; x for lateral
+     * movement, y for vertical movement. The held keys give us a
+     * direction one unit long, and the player's velocity speeds up
+     * towards move_speed that way, or slows down to nothing if no keys
+     * are held (movement.rs has the details). Then we move by the
+     * velocity times dt, so a second of holding Right goes just as far
+     * however many updates that second was chopped into. Running into a
+     * wall stops us dead in that direction.
+     * Player two, if there is one, always just walks, whatever mode
+     * player one is in.
+     * Q and E turn the player first, at rotate_speed. With tank
+     * controls, which way they face is which way that's turned them,
+     * rather than which way the keys point (see walk_direction).
+     */
+    pub fn update_player(&mut self, i: usize, dt: f64) {
+        let size = if self.has_power(Effect::Size) { self.config.size_boost } else { 1.0 };
+        self.players[i].size = size;
+        shrink_expand(&mut self.players[i], self.config.expand_decay,
+                      self.config.expand_easing, dt);
+        player::settle(&mut self.players[i], dt);
+        self.push_out_of_walls(i);
+
+        let tank = self.tank_controls();
+        let turn_speed = self.config.rotate_speed.to_radians();
+        {
+            let player = &mut self.players[i];
+            let turned = player.angle + player.input.turning(tank) * turn_speed * dt;
+            player.angle = turned % (2.0 * PI);
+            if player.angle < 0.0 { player.angle += 2.0 * PI }
+            player.facing = if tank {
+                Facing::from_angle(player.angle)
+            } else {
+                controls::next_facing(player.facing, &player.input)
+            };
+        }
+        if i > 0 {
+            self.update_walking(i, dt);
+        } else {
+            let (dx, dy) = self.players[0].input.direction();
+            let state = AnimationState::from_input(&self.players[0].input,
+                                                   self.players[0].facing);
+            self.animator.update(dt, state);
+            if self.platformer {
+                self.update_platformer(dx, dt);
+            } else if self.bounce_mode {
+                self.update_bounce(dx, dy, dt);
+            } else {
+                self.update_walking(0, dt);
+            }
+        }
+        // If they've gone anywhere, for anyone who cares where (the
+        // achievements do).
+        let (from, to) = (self.players[i].previous, (self.players[i].x, self.players[i].y));
+        if from != to {
+            self.bus.publish(GameEvent::PlayerMoved { player: i, from: from, to: to });
+        }
+    }
+
+    // Walking about, the usual way of moving: see update_player.
+    pub fn update_walking(&mut self, i: usize, dt: f64) {
+        // Dashing, the dash decides where we go, not the keys.
+        let (vx, vy) = if i == 0 && self.dash_timer > 0.0 {
+            self.dash_velocity
+        } else {
+            let player = &self.players[i];
+            movement::walk((player.vx, player.vy), self.walk_direction(i),
+                           self.move_speed(), self.config.acceleration,
+                           self.config.friction, dt)
+        };
+        self.players[i].vx = vx;
+        self.players[i].vy = vy;
+        let mut hits = self.move_player(i, vx * dt, vy * dt);
+        // The edges of the window are walls too, so you can't wander off
+        // the screen and get lost. (Unless they wrap, that is.)
+        let edges = self.apply_edges(i);
+        hits.left |= edges.left;
+        hits.right |= edges.right;
+        hits.top |= edges.top;
+        hits.bottom |= edges.bottom;
+        if hits.left || hits.right { self.players[i].vx = 0.0 }
+        if hits.top || hits.bottom { self.players[i].vy = 0.0 }
+    }
+
+    /*
+     * Tank controls are for walking about. Platformer and bounce modes
+     * have Left and Right doing their own thing, so there they're off,
+     * even with tank_controls on.
+     */
+    pub fn tank_controls(&self) -> bool {
+        self.config.tank_controls && !self.platformer && !self.bounce_mode
+    }
+
+    // Which way player `i`'s keys are taking them, walking: up, down, left
+    // and right on the screen, or with tank controls, forwards and
+    // backwards.
+    pub fn walk_direction(&self, i: usize) -> (f64, f64) {
+        let player = &self.players[i];
+        if self.tank_controls() {
+            player.input.tank_direction(player.angle)
+        } else {
+            player.input.direction()
+        }
+    }
+
+    /*
+     * Bounce mode's version of movement. The held keys push on the velocity
+     * instead of setting the position, we move by the velocity (sped up by
+     * however puffed up expand has us), and then bounce off anything we hit:
+     * walls first, via move_player, then the edges of the window.
+     */
+    pub fn update_bounce(&mut self, dx: f64, dy: f64, dt: f64) {
+        let nudge = self.config.bounce_nudge * dt;
+        self.players[0].vx += dx * nudge;
+        self.players[0].vy += dy * nudge;
+
+        let boost = (1.0 + self.players[0].expand * self.config.bounce_boost)
+            * self.move_speed() / self.config.move_speed;
+        let (restitution, rest_speed) = (self.config.bounce_restitution,
+                                         self.config.bounce_rest_speed);
+        let hits = self.move_player(0, self.players[0].vx * boost * dt,
+                                    self.players[0].vy * boost * dt);
+        self.bounce_velocity(hits, restitution, rest_speed);
+
+        let hits = self.apply_edges(0);
+        self.bounce_velocity(hits, restitution, rest_speed);
+    }
+
+    /*
+     * Platformer mode's version. Left and right walk, same as ever, but up
+     * and down do nothing: gravity does the vertical, pulling us down a
+     * little faster every tick until we hit something. Landing on top of
+     * something (the bottom of the world counts) is what makes us grounded,
+     * and only grounded can we jump. Bumping our head stops us going up.
+     * A dash is the one thing gravity doesn't get a say in.
+     */
+    pub fn update_platformer(&mut self, dx: f64, dt: f64) {
+        let move_speed = self.move_speed();
+        {
+            let player = &mut self.players[0];
+            if self.dash_timer > 0.0 {
+                player.vx = self.dash_velocity.0;
+                player.vy = self.dash_velocity.1;
+            } else {
+                // Only sideways gets the speeding up and slowing down;
+                // falling is gravity's business.
+                player.vx = movement::walk((player.vx, 0.0), (dx, 0.0),
+                                           move_speed, self.config.acceleration,
+                                           self.config.friction, dt).0;
+                player.vy = (player.vy + self.config.gravity * dt).min(self.config.max_fall_speed);
+            }
+        }
+        let (vx, vy) = (self.players[0].vx, self.players[0].vy);
+        let mut hits = self.move_player(0, vx * dt, vy * dt);
+        let edges = self.apply_edges(0);
+        hits.left |= edges.left;
+        hits.right |= edges.right;
+        hits.top |= edges.top;
+        hits.bottom |= edges.bottom;
+        if hits.left || hits.right { self.players[0].vx = 0.0 }
+        if hits.top { self.players[0].vy = self.players[0].vy.max(0.0) }
+        self.grounded = hits.bottom;
+        if self.grounded {
+            self.players[0].vy = 0.0;
+            self.jumping = false;
+        }
+    }
+
+    // Space, in platformer mode. Only from the ground: no jumping off thin
+    // air.
+    pub fn jump(&mut self) {
+        if !self.grounded {
+            debug!("Can't jump: not standing on anything");
+            return;
+        }
+        self.players[0].vy = -self.config.jump_speed;
+        self.grounded = false;
+        self.jumping = true;
+        self.play(Sound::Move);
+    }
+
+    // Space let go. If we're still on the way up, the rest of the jump gets
+    // cut short.
+    pub fn end_jump(&mut self) {
+        if self.jumping && self.players[0].vy < 0.0 {
+            self.players[0].vy *= self.config.jump_cut;
+        }
+        self.jumping = false;
+    }
+
+    // Into or out of platformer mode. It's one or the other with bounce
+    // mode, so this switches that off.
+    pub fn toggle_platformer(&mut self) {
+        self.platformer = !self.platformer;
+        self.bounce_mode = false;
+        self.players[0].vx = 0.0;
+        self.players[0].vy = 0.0;
+        self.grounded = false;
+        self.jumping = false;
+        info!("Platformer mode {}", if self.platformer { "on" } else { "off" });
+    }
+
+    // Deal with player `i` going past the edge of the window, in whichever
+    // way the world mode says. Wrapping round never hits anything.
+    pub fn apply_edges(&mut self, i: usize) -> Hits {
+        match self.world_mode {
+            WorldMode::Bounded => self.confine_player(i),
+            WorldMode::Wrap => {
+                let (width, height) = self.world_size();
+                let wrapped = {
+                    let player = &mut self.players[i];
+                    let before = (player.x, player.y);
+                    player.x = world::wrap(player.x, width);
+                    player.y = world::wrap(player.y, height);
+                    (player.x, player.y) != before
+                };
+                // Gliding the camera all the way across the world after
+                // the player would look daft, so it jumps with them.
+                if wrapped { self.snap_camera() }
+                Hits::default()
+            }
+        }
+    }
+
+    // The walls, sorted into a quadtree, once a tick before anyone has
+    // their turn: the enemies go round them, and the shots after them hit
+    // them.
+    pub fn sort_walls(&mut self) {
+        self.quadtree = Quadtree::new(self.obstacles());
+    }
+
+    /*
+     * Enemy `id`'s turn (see object::EnemyObject): it takes a step after
+     * the player, and then if it's caught them, that's a point of health
+     * gone. Only one point per catch: for a little while afterwards the
+     * player can't be hurt at all (advance counts hurt_timer down),
+     * otherwise an enemy sitting on top of them would take all their health
+     * in a few updates.
+     * The walls it might bump into are in this tick's quadtree (see
+     * sort_walls).
+     */
+    pub fn update_enemy(&mut self, id: ecs::Id, dt: f64) {
+        let target = self.player_centre();
+        let speed = self.config.enemy_speed
+                    * waves::speed_up(self.waves.number, self.config.wave_speed_growth);
+        enemy::chase_one(&mut self.scene, id, target, speed, dt, &self.quadtree,
+                         &mut self.paths);
+
+        // They only chase player one, but they'll take a bite out of
+        // anyone they bump into, and the health is shared.
+        let players = self.local_boxes();
+        let body = self.player_body();
+        let caught = self.scene.bounds(id)
+            .map_or(false, |e| players.iter().any(|p| collision::overlaps(body, p, &e)));
+        if self.hurt_timer == 0.0 && caught && !self.has_power(Effect::Invulnerable) {
+            self.hurt();
+        }
+    }
+
+    /*
+     * Work out a way to player one for every enemy that can't just go
+     * straight there (see pathfinding.rs). The grid's cells are the
+     * tilemap's tiles, but never smaller than two enemies across, so an
+     * enemy always fits through a gap a cell wide with room to spare. One
+     * with no way through at all is left to head straight for you, and
+     * lean on the wall.
+     */
+    pub fn plan_paths(&mut self) {
+        self.paths.clear();
+        let target = self.player_centre();
+        let (width, height) = self.world_size();
+        let cell = self.level().ground.tile_size.max(enemy::ENEMY_SIZE * 2.0);
+        let obstacles = self.obstacles();
+        let grid = Grid::new(width, height, cell, &obstacles);
+        let walls = Quadtree::new(obstacles);
+        for id in self.scene.ids() {
+            if self.scene.chasers[id.index].is_none() { continue }
+            let b = match self.scene.bounds(id) {
+                Some(b) => b,
+                None => continue,
+            };
+            let from = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+            if pathfinding::line_clear(from, target, b.w.max(b.h), &walls) { continue }
+            if let Some(route) = grid.route(from, target) {
+                self.paths.set(id, pathfinding::smooth(from, route, b.w.max(b.h), &walls));
+            }
+        }
+    }
+
+    /*
+     * Hand everyone to the --lua script's on_update, if there is a script:
+     * each of our players, then each enemy, and then make whatever it
+     * spawned. Whatever the script does to a player, walls still win, the
+     * same as they do against expand. Enemies only have a position for it
+     * to change; it can't puff them up.
+     */
+    pub fn run_lua(&mut self, dt: f64) {
+        let lua = match self.lua {
+            Some(ref lua) => lua.clone(),
+            None => return,
+        };
+        let mut lua = lua.borrow_mut();
+        for i in 0..self.local_players() {
+            let was = (self.players[i].x, self.players[i].y, self.players[i].expand);
+            let mut scripted = Scripted { thing: Thing::Player, number: i + 1,
+                                          x: was.0, y: was.1, expand: was.2 };
+            lua.update(&mut scripted, dt);
+            if (scripted.x, scripted.y, scripted.expand) != was {
+                {
+                    let player = &mut self.players[i];
+                    player.x = scripted.x;
+                    player.y = scripted.y;
+                    player.expand = scripted.expand.max(0.0);
+                }
+                self.push_out_of_walls(i);
+            }
+        }
+        for (n, id) in self.scene.of_kind(Kind::Enemy).into_iter().enumerate() {
+            if let Some(position) = self.scene.positions[id.index].as_mut() {
+                let mut scripted = Scripted { thing: Thing::Enemy, number: n + 1,
+                                              x: position.x, y: position.y, expand: 0.0 };
+                lua.update(&mut scripted, dt);
+                position.x = scripted.x;
+                position.y = scripted.y;
+            }
+        }
+        for spawn in lua.spawned() {
+            debug!("The script made a {} at ({:.1}, {:.1})", spawn.thing.name(), spawn.x, spawn.y);
+            match spawn.thing {
+                Thing::Enemy => {
+                    self.spawn_enemy(spawn.x, spawn.y);
+                }
+                Thing::Pickup => {
+                    let color = self.config.palette().pickup;
+                    self.entities.push(Box::new(Pickup::at(spawn.x, spawn.y, color)));
+                }
+                Thing::Player => {}
+            }
+        }
+    }
+
+    // Tell the --lua script about a key going down.
+    pub fn tell_lua(&mut self, key: Button) {
+        if let Some(ref lua) = self.lua {
+            lua.borrow_mut().key(&keys::button_name(key));
+        }
+    }
+
+    /*
+     * Another enemy, somewhere random in the world, which is what the
+     * enemy_spawn_seconds timer does. Not too near player one, though: one
+     * appearing right on top of them would be a bit unfair, so it has
+     * another go, up to a point. `at` is its middle, kept far enough in
+     * from the edges that all of it is in the world.
+     */
+    pub fn spawn_enemy_somewhere(&mut self) {
+        let (width, height) = self.world_size();
+        let target = self.player_centre();
+        let half = enemy::ENEMY_SIZE / 2.0;
+        let mut at = (half, half);
+        for _ in 0..10 {
+            at = (self.rng.range(half, width - half), self.rng.range(half, height - half));
+            let (dx, dy) = (at.0 - target.0, at.1 - target.1);
+            if (dx * dx + dy * dy).sqrt() >= ENEMY_SPAWN_DISTANCE { break }
+        }
+        debug!("An enemy turns up at ({:.1}, {:.1})", at.0, at.1);
+        self.spawn_enemy(at.0, at.1);
+    }
+
+    // An enemy centred on (x, y), in the config's color, with the config's
+    // health.
+    pub fn spawn_enemy(&mut self, x: f64, y: f64) {
+        let (color, health) = (self.config.palette().enemy, self.config.enemy_health);
+        enemy::spawn_with_health(&mut self.scene, x, y, color, health);
+    }
+
+    // Lose enemy_damage points of health. Out of health, that's a life
+    // gone, and out of lives, it's game over.
+    pub fn hurt(&mut self) {
+        self.touched = true;
+        self.health = self.health.saturating_sub(self.config.enemy_damage);
+        if self.health == 0 {
+            self.lives = self.lives.saturating_sub(1);
+            if self.lives == 0 {
+                info!("Game over! Score: {}", self.score);
+                self.change_state(LevelState::Lost);
+            } else {
+                self.lose_life();
+            }
+        } else {
+            info!("Ouch! {} health left", self.health);
+            self.play(Sound::Collision);
+            self.hurt_timer = self.config.hurt_seconds;
+            self.shake.start(self.config.shake_amplitude, self.config.shake_duration);
+        }
+    }
+
+    /*
+     * Out of health, but with a life to spare: everyone goes back to the
+     * spawn point (the level file's, unless the config says otherwise, the
+     * same as reset), with their health back and any power-ups gone. The
+     * enemies stay where they are, so there's respawn_seconds of blinking
+     * about where they can't hurt you, in case one of them was waiting
+     * there. The level itself carries on as it was: the pickups you've
+     * got, and the score, are still yours. Unless you've touched a
+     * checkpoint, that is, in which case it's everything back to how it was
+     * then, and the checkpoint's flag instead of the spawn (see
+     * checkpoint.rs).
+     */
+    fn lose_life(&mut self) {
+        info!("Lost a life! {} left", self.lives);
+        let (x, y) = if checkpoint::respawn(self) {
+            (self.players[0].x, self.players[0].y)
+        } else {
+            self.spawn
+        };
+        let offset = self.config.player_two_offset;
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.respawn(x + i as f64 * offset, y);
+        }
+        self.grounded = false;
+        self.jumping = false;
+        self.health = self.config.player_health;
+        self.effects.clear();
+        self.dash_timer = 0.0;
+        self.dash_cooldown = 0.0;
+        self.play(Sound::Collision);
+        self.hurt_timer = self.config.respawn_seconds;
+        self.snap_camera();
+    }
+
+    // The middle of player one.
+    pub fn player_centre(&self) -> (f64, f64) {
+        let b = self.player_box();
+        (b.x + b.w / 2.0, b.y + b.h / 2.0)
+    }
+
+    // What the camera keeps an eye on: the middle of player one, or in
+    // co-op, halfway between the two of them, so nobody gets left behind
+    // (unless they wander off a whole screen away).
+    pub fn camera_target(&self) -> (f64, f64) {
+        let boxes = self.local_boxes();
+        let n = boxes.len() as f64;
+        let (x, y) = boxes.iter().fold((0.0, 0.0), |(x, y), b| {
+            (x + b.x + b.w / 2.0, y + b.y + b.h / 2.0)
+        });
+        (x / n, y / n)
+    }
+
+    // Move the camera smoothly after the players, per the config.
+    pub fn update_camera(&mut self, dt: f64) {
+        let target = self.camera_target();
+        self.camera.follow(target,
+                           (self.config.camera_deadzone_width, self.config.camera_deadzone_height),
+                           self.config.camera_smoothing,
+                           dt);
+        let (width, height) = self.world_size();
+        self.camera.clamp_to(width, height);
+    }
+
+    // Zoom in (factor above 1) or out (below 1), keeping the player where
+    // they are on screen.
+    pub fn zoom_by(&mut self, factor: f64) {
+        let anchor = self.camera_target();
+        let zoom = self.camera.zoom * factor;
+        self.camera.set_zoom(zoom, anchor.into());
+        let (width, height) = self.world_size();
+        self.camera.clamp_to(width, height);
+        debug!(target: Render, "Zoom {:.2}", self.camera.zoom);
+    }
+
+    // The mouse wheel reports how many notches it turned; each one is a
+    // step in or out.
+    pub fn scroll_zoom(&mut self, notches: f64) {
+        self.zoom_by(self.config.zoom_step.powf(notches));
+    }
+
+    // Put the camera straight on the player, no gliding.
+    pub fn snap_camera(&mut self) {
+        let target = self.camera_target();
+        self.camera.centre_on(target.into());
+        let (width, height) = self.world_size();
+        self.camera.clamp_to(width, height);
+        // A jump is a jump: don't draw the player (or the view) sliding
+        // from wherever they were before, or leave a trail back there.
+        for player in self.players.iter_mut() {
+            player.previous = (player.x, player.y);
+        }
+        self.previous_camera = (self.camera.x, self.camera.y);
+        self.trail.clear();
+    }
+
+    // How far we are from the last tick to the next one, from 0 to 1.
+    // Paused, there's no next tick coming, so what's drawn is where things
+    // are.
+    pub fn blend(&self) -> f64 {
+        if self.paused { return 1.0 }
+        (self.accumulator / SIM_DT).max(0.0).min(1.0)
+    }
+
+    // Where to draw player one, and the camera, this frame: part of the
+    // way from where they were before the last tick to where they are now.
+    pub fn interpolated(&self) -> ((f64, f64), (f64, f64)) {
+        (self.drawn_at(0), lerp(self.previous_camera, (self.camera.x, self.camera.y), self.blend()))
+    }
+
+    // The same for any player.
+    pub fn drawn_at(&self, i: usize) -> (f64, f64) {
+        let player = &self.players[i];
+        lerp(player.previous, (player.x, player.y), self.blend())
+    }
+
+    /*
+     * The window is a new size: it's been dragged bigger, or gone
+     * fullscreen. Letterboxed, the game's view stays the size the config
+     * says and just gets scaled to fit; otherwise the view is the whole
+     * window, and a bigger window shows more of the world. The camera's
+     * position is its top-left corner, so if we just changed its size the
+     * player would suddenly be somewhere else on screen. Instead we keep
+     * them the same fraction of the way across and down the view as they
+     * were, so a player in the middle of a small window is in the middle of
+     * the big one too.
+     */
+    pub fn resize_window(&mut self, width: f64, height: f64) {
+        self.window_size = (width, height);
+        let view = if self.config.letterbox {
+            self.config.view_size()
+        } else {
+            (width, height)
+        };
+        self.resize_view(view.0, view.1);
+    }
+
+    pub fn resize_view(&mut self, width: f64, height: f64) {
+        if (width, height) == self.view_size { return }
+        let centre = WorldPos::from(self.player_centre());
+        let at = self.camera.to_screen(centre);
+        let (fx, fy) = (at.x / self.camera.width, at.y / self.camera.height);
+        self.view_size = (width, height);
+        self.camera.width = width;
+        self.camera.height = height;
+        self.camera.put(centre, ScreenPos::new(fx * width, fy * height));
+        let (world_width, world_height) = self.world_size();
+        self.camera.clamp_to(world_width, world_height);
+    }
+
+    /*
+     * Shove the player back inside the world if they've gone over an edge,
+     * and say which edges those were. It's the player's whole hitbox that
+     * has to fit, expand and all, so puffing up next to an edge pushes you
+     * away from it.
+     */
+    pub fn confine_player(&mut self, i: usize) -> Hits {
+        let (width, height) = self.world_size();
+        let arena = Aabb::new(0.0, 0.0, width, height);
+        let (confined, hits) = bounce::confine(self.player_box_of(i), arena);
+        self.place_player(i, confined);
+        hits
+    }
+
+    pub fn bounce_velocity(&mut self, hits: Hits, restitution: f64, rest_speed: f64) {
+        let (vx, vy) = bounce::reflect((self.players[0].vx, self.players[0].vy),
+                                       hits, restitution, rest_speed);
+        self.players[0].vx = vx;
+        self.players[0].vy = vy;
+    }
+
+    /*
+     * Once a second, at debug level, say how many frames got drawn and how
+     * many updates ran. If the frame count is well under --max-fps, the
+     * drawing is struggling to keep up; if the update count is under the
+     * UPS, the game is running slow.
+     */
+    pub fn log_stats(&mut self, dt: f64) {
+        self.updates += 1;
+        self.stats_timer += dt;
+        if self.stats_timer >= 1.0 {
+            debug!(target: Render, "{} frames and {} updates in the last {:.2}s",
+                   self.frames, self.updates, self.stats_timer);
+            self.frames = 0;
+            self.updates = 0;
+            self.stats_timer = 0.0;
+        }
+    }
+
+    pub fn toggle_world_mode(&mut self) {
+        self.world_mode = self.world_mode.toggled();
+        info!("World mode: {:?}", self.world_mode);
+    }
+
+    // What goes in the title bar (see Game::render): the game's name, plus
+    // the tick counter
+    // when paused, since that's when you're watching it tick by. The frame
+    // stats go up there too when they're on, for anyone without a HUD font.
+    pub fn title_text(&self) -> String {
+        let mut title = if self.paused {
+            format!("{} - PAUSED - tick {}", self.config.window_title, self.ticks)
+        } else {
+            self.config.window_title.clone()
+        };
+        if self.show_frame_stats {
+            title.push_str(" - ");
+            title.push_str(&self.frame_stats);
+        }
+        title
+    }
+
+    /*
+     * game::run tells us how long each frame took, start to start, as measured
+     * by the clock on the wall rather than the game's own. The summary is
+     * refreshed a couple of times a second rather than every frame: numbers
+     * that change sixty times a second are impossible to read.
+     */
+    pub fn record_frame(&mut self, seconds: f64) {
+        self.frame_times.record(seconds);
+        self.frame_stats_timer += seconds;
+        if self.frame_stats.is_empty() || self.frame_stats_timer >= FRAME_STATS_REFRESH_SECONDS {
+            self.frame_stats = self.frame_times.summary();
+            self.frame_stats_timer = 0.0;
+        }
+    }
+
+    pub fn toggle_frame_stats(&mut self) {
+        self.show_frame_stats = !self.show_frame_stats;
+        debug!(target: Render, "Frame stats {}", if self.show_frame_stats { "on" } else { "off" });
+    }
+
+    // F4: outline everything that collides, and say where it's all going.
+    // See App::draw_debug, in render.rs.
+    pub fn toggle_debug(&mut self) {
+        self.show_debug = !self.show_debug;
+        debug!(target: Render, "Debug drawing {}", if self.show_debug { "on" } else { "off" });
+    }
+
+    // Into or out of bounce mode (and out of platformer mode). Either way we
+    // start from a standstill.
+    pub fn toggle_bounce(&mut self) {
+        self.bounce_mode = !self.bounce_mode;
+        self.platformer = false;
+        self.players[0].vx = 0.0;
+        self.players[0].vy = 0.0;
+        info!("Bounce mode {}", if self.bounce_mode { "on" } else { "off" });
+    }
+
+    /*
+     * What color player `i` is drawn in: their own, unless there's a
+     * power-up on, in which case it's the newest one's. In its last second
+     * it flickers back to their own every tenth of a second, as a warning.
+     */
+    pub fn player_color(&self, i: usize) -> [f32; 4] {
+        let palette = self.config.palette();
+        let own = if i == 0 { palette.player } else { palette.player_two };
+        match self.effects.last() {
+            Some(a) if a.left > 1.0 || ((a.left / 0.1) as u32) % 2 == 0 => {
+                palette.power_up(a.effect)
+            }
+            _ => own,
+        }
+    }
+
+    // How many of the players are playing on this computer. All of them,
+    // unless the last one's at the other end of a network link.
+    pub fn local_players(&self) -> usize {
+        if self.link.is_some() { self.players.len() - 1 } else { self.players.len() }
+    }
+
+    // The hitboxes of the players on this computer. Only they can pick
+    // things up, get caught, or reach the goal here; the other end takes
+    // care of the player that's there.
+    pub fn local_boxes(&self) -> Vec<Aabb> {
+        self.players[..self.local_players()].iter().map(player_bounds).collect()
+    }
+
+    // Player one's hitbox: the same square we draw, grown by expand.
+    pub fn player_box(&self) -> Aabb {
+        self.player_box_of(0)
+    }
+
+    // Anyone's.
+    pub fn player_box_of(&self, i: usize) -> Aabb {
+        player_bounds(&self.players[i])
+    }
+
+    // Put player `i` wherever makes their hitbox `b`; the opposite of
+    // player_box_of.
+    pub fn place_player(&mut self, i: usize, b: Aabb) {
+        let player = &mut self.players[i];
+        let grown = grown_by(player);
+        player.x = b.x + (grown / 2.0);
+        player.y = b.y + (grown / 2.0);
+    }
+
+    /*
+     * Pickup `i`'s turn, `i` being where it is in entities (see
+     * object::PickupObject): if a player's touching it, it's picked up, and
+     * the score goes up by one. In co-op the score is shared, so it doesn't
+     * matter who gets there first. The HUD shows the score, but not
+     * everyone has a font for it, so it goes in the log too.
+     * Power-ups are collectible too, but they give a power instead of a
+     * point, and items go in the inventory.
+     * It stays in entities until everyone's had their turn, so that the
+     * pickups after it are still where their objects think they are;
+     * drop_collected takes it away after that.
+     */
+    pub fn touch_pickup(&mut self, i: usize) {
+        let b = self.entities[i].bounds();
+        let body = self.player_body();
+        if !self.local_boxes().iter().any(|p| collision::overlaps(body, p, &b)) { return }
+        self.collected.push(i);
+        let power_up = self.entities[i].power_up();
+        let item = self.entities[i].item().map(|name| name.to_string());
+        self.bus.publish(GameEvent::PickupCollected { at: (b.x + b.w / 2.0, b.y + b.h / 2.0),
+                                                      power_up: power_up.is_some() });
+        match (power_up, item) {
+            (Some(effect), _) => {
+                powerup::grant(&mut self.effects, effect, self.config.power_up_seconds);
+                info!("Power-up: {} for {} seconds", effect.name(), self.config.power_up_seconds);
+            }
+            (None, Some(name)) => {
+                self.inventory.add(&name);
+                info!("Picked up a {}: that's {} now", name, self.inventory.count(&name));
+            }
+            (None, None) => {
+                self.score += 1;
+                info!("Picked one up! Score: {}", self.score);
+            }
+        }
+    }
+
+    // Take away whatever was picked up this tick.
+    pub fn drop_collected(&mut self) {
+        if self.collected.is_empty() { return }
+        let collected = ::std::mem::replace(&mut self.collected, Vec::new());
+        let mut i = 0;
+        self.entities.retain(|_| {
+            i += 1;
+            !collected.contains(&(i - 1))
+        });
+    }
+
+    /*
+     * Any door a player's up against that they've got what it needs for
+     * opens: it takes that out of the inventory, and goes. One they haven't
+     * is just a wall, but it says what it wants in the log, once each time
+     * you walk into it.
+     */
+    pub fn open_doors(&mut self) {
+        let players: Vec<Aabb> = self.local_boxes().iter().map(|p| {
+            let reach = inventory::DOOR_REACH;
+            Aabb::new(p.x - reach, p.y - reach, p.w + reach * 2.0, p.h + reach * 2.0)
+        }).collect();
+        let mut opened = Vec::new();
+        let mut locked = None;
+        {
+            let inventory = &mut self.inventory;
+            self.entities.retain(|e| {
+                let (needs, count) = match e.lock() {
+                    Some(lock) => lock,
+                    None => return true,
+                };
+                if !players.iter().any(|p| p.intersects(&e.bounds())) { return true }
+                if inventory.take(needs, count) {
+                    opened.push(needs.to_string());
+                    false
+                } else {
+                    locked = Some((needs.to_string(), count));
+                    true
+                }
+            });
+        }
+        for needs in opened {
+            self.play(Sound::Pickup);
+            info!("Opened a door with the {}", needs);
+        }
+        if let Some((needs, count)) = locked.clone() {
+            if locked != self.locked_door {
+                info!("This door needs {} {} (you've got {})", count, needs,
+                      self.inventory.count(&needs));
+            }
+        }
+        self.locked_door = locked;
+    }
+
+    pub fn has_power(&self, effect: Effect) -> bool {
+        powerup::has(&self.effects, effect)
+    }
+
+    // How fast walking goes, sped up by the speed power-up if it's on.
+    pub fn move_speed(&self) -> f64 {
+        if self.has_power(Effect::Speed) {
+            self.config.move_speed * self.config.speed_boost
+        } else {
+            self.config.move_speed
+        }
+    }
+
+    // The hitboxes of every solid entity, i.e. everything the player can't
+    // walk through.
+    pub fn obstacles(&self) -> Vec<Aabb> {
+        let mut obstacles: Vec<Aabb> = self.entities.iter()
+            .filter(|e| e.solid())
+            .map(|e| e.bounds())
+            .collect();
+        if self.platformer {
+            obstacles.extend(self.solid_tiles.iter().cloned());
+        }
+        obstacles
+    }
+
+    // What shape the players are when they bump into things: round, for
+    // the round shapes. See player::Shape.
+    pub fn player_body(&self) -> Body {
+        self.config.player_shape.body()
+    }
+
+    // Undo any overlap between player `i` and the walls. See
+    // collision::push_out.
+    pub fn push_out_of_walls(&mut self, i: usize) {
+        let obstacles = self.obstacles();
+        let (pushed, _) = collision::push_body_out(self.player_body(), self.player_box_of(i),
+                                                   &obstacles);
+        self.place_player(i, pushed);
+    }
+
+    /*
+     * All player movement goes through here rather than poking at x and y
+     * directly, so that walls get a say. move_and_collide does the heavy
+     * lifting (see collision.rs) and tells us which sides of player `i` hit
+     * something, which we hand back to the caller in case it cares.
+     */
+    pub fn move_player(&mut self, i: usize, dx: f64, dy: f64) -> Hits {
+        let obstacles = self.obstacles();
+        let (moved, hits) = collision::move_body(
+            self.player_body(), self.player_box_of(i), dx, dy, &obstacles);
+        self.place_player(i, moved);
+        // Leaning on a wall hits it every single update, so this is limited
+        // to a couple of lines a second.
+        if hits.any() && log::enabled(Level::Debug, Target::Collision)
+           && self.collision_log.ready(self.clock) {
+            debug!(target: Collision, "Hit something: {:?}", hits);
+        }
+        // The same goes for the bump sound, except that it only goes off
+        // when we first hit. Only player one's bumps, or two players
+        // leaning on walls would take it in turns to set it off.
+        if i > 0 { return hits }
+        if hits.any() && !self.bumping {
+            self.play(Sound::Collision);
+        }
+        self.bumping = hits.any();
+        hits
+    }
+
+    // Step through UPS_STEPS, wrapping around at either end. If we're at some
+    // rate that isn't in the list (from --ups, say) we start from the top.
+    pub fn cycle_ups(&mut self, forward: bool) {
+        let n = UPS_STEPS.len();
+        let next = match UPS_STEPS.iter().position(|&u| u == self.ups) {
+            Some(i) if forward => (i + 1) % n,
+            Some(i) => (i + n - 1) % n,
+            None => 0,
+        };
+        self.ups = UPS_STEPS[next];
+        info!("Updates per second: {}", self.ups);
+    }
+
+    /*
+     * Open or close the help overlay. Opening it forgets any held arrow
+     * keys, otherwise the player would carry on walking the moment it
+     * closes, even though you let go while it was up. The list of keys
+     * is drawn on the overlay itself (see render.rs).
+     */
+    pub fn toggle_help(&mut self) {
+        self.help_open = !self.help_open;
+        debug!("Help {}", if self.help_open { "opened" } else { "closed" });
+        if self.help_open {
+            self.forget_held_keys();
+        }
+    }
+
+    // Whoever player one's standing on that has something to say, if
+    // anyone.
+    pub fn talker(&self) -> Option<&Box<dyn Entity>> {
+        let player = self.player_box();
+        self.entities.iter().find(|e| {
+            e.dialogue().map_or(false, |pages| !pages.is_empty()) && e.bounds().intersects(&player)
+        })
+    }
+
+    // Return: talk to whoever that is. Like the help, the box forgets any
+    // held keys, so nobody wanders off the moment it closes.
+    pub fn talk(&mut self) {
+        let pages = match self.talker().and_then(|e| e.dialogue()) {
+            Some(pages) => pages.to_vec(),
+            None => {
+                debug!("There's nobody here to talk to");
+                return;
+            }
+        };
+        self.forget_held_keys();
+        self.dialogue = Some(Dialogue::new(pages));
+        debug!("Dialogue opened");
+    }
+
+    // Return again, with the box open: on with what they're saying, and
+    // once they've said it all, the box closes.
+    pub fn advance_dialogue(&mut self) {
+        let open = self.dialogue.as_mut().map_or(false, |d| d.advance());
+        if !open && self.dialogue.take().is_some() {
+            debug!("Dialogue closed");
+        }
+    }
+
+    /*
+     * The expand power, which Space and the controller's face button both
+     * use. It adds to the expand value to give the impression of a player
+     * 'power' or something. I mostly just wanted to do something more than
+     * just movement. It also throws out a burst of particles, and flashes
+     * the screen white (see postprocess.rs), so the power has a bit of
+     * oomph. In co-op, player two has a key of their own for
+     * it, and `i` says whose it is.
+     */
+    pub fn trigger_expand(&mut self, i: usize) {
+        self.players[i].expand += self.config.expand_step;
+        player::boing(&mut self.players[i]);
+        debug!("Player {} expands up to {:.1}", i + 1, self.players[i].expand);
+        self.shake.start(self.config.shake_amplitude, self.config.shake_duration);
+        self.flash.fire(self.config.flash_strength, self.config.flash_seconds);
+        self.burst(i);
+        self.play(Sound::Expand);
+        if i == 0 {
+            self.progress.push(Progress::Expanded);
+        }
+    }
+
+    // Ask for a sound effect. Game does the actual playing; see audio.rs.
+    pub fn play(&mut self, sound: Sound) {
+        self.sounds.push(sound);
+    }
+
+    // M turns the sound off, or back on.
+    pub fn toggle_mute(&mut self) {
+        self.config.audio.muted = !self.config.audio.muted;
+        info!("Sound {}", if self.config.audio.muted { "off" } else { "on" });
+    }
+
+    /*
+     * Shift's dash: a big burst of speed in whichever direction we're
+     * moving, or the way we're facing if we're standing still. Walking,
+     * the dash takes over from the keys for dash_duration and then lets go;
+     * bouncing, where speed stays put until something changes it, it's one
+     * big shove, and the bounce takes it from there. Either way, no more
+     * dashing until the cooldown's run out.
+     */
+    pub fn dash(&mut self) {
+        if self.dash_cooldown > 0.0 {
+            debug!("Can't dash for another {:.2}s", self.dash_cooldown);
+            return;
+        }
+        let moving = self.walk_direction(0);
+        let forward = self.forward();
+        let player = &mut self.players[0];
+        let (dx, dy) = if moving == (0.0, 0.0) { forward } else { moving };
+        let speed = self.config.dash_speed;
+        if self.bounce_mode {
+            player.vx += dx * speed;
+            player.vy += dy * speed;
+        } else {
+            self.dash_velocity = (dx * speed, dy * speed);
+        }
+        self.dash_timer = self.config.dash_duration;
+        self.dash_cooldown = self.config.dash_cooldown;
+        debug!("Dash!");
+    }
+
+    // Which way's in front of player one: where they're facing, or with
+    // tank controls, exactly where they're pointing.
+    pub fn forward(&self) -> (f64, f64) {
+        if self.tank_controls() {
+            controls::angle_vector(self.players[0].angle)
+        } else {
+            self.players[0].facing.vector()
+        }
+    }
+
+    // Shoot a projectile out of the middle of the player, whichever way
+    // they're facing.
+    pub fn fire(&mut self) {
+        let forward = self.forward();
+        self.fire_towards(forward);
+    }
+
+    // The same, but at wherever the mouse is pointing, for a click. With
+    // the mouse not over the window, or right on top of the player, there's
+    // nowhere in particular, so it's the usual way.
+    pub fn fire_at_cursor(&mut self) {
+        let from = WorldPos::from(self.player_centre());
+        let direction = match self.cursor_world().and_then(|at| from.towards(at)) {
+            Some(direction) => direction,
+            None => self.forward(),
+        };
+        self.fire_towards(direction);
+    }
+
+    // One shot, along the unit-length `direction`.
+    pub fn fire_towards(&mut self, direction: (f64, f64)) {
+        let from = self.player_centre();
+        projectile::fire(&mut self.scene, from, direction,
+                         self.config.projectile_speed, self.config.projectile_lifetime,
+                         self.config.palette().projectile);
+        debug!("Fired towards ({:.2}, {:.2})", direction.0, direction.1);
+    }
+
+    // The mouse moved to (x, y) in the window.
+    pub fn mouse_moved(&mut self, x: f64, y: f64) {
+        self.cursor = Some((x, y));
+        self.touch_moved();
+    }
+
+    // Where the mouse is pointing in the world, if it's been over the
+    // window at all. See cursor_seen_from.
+    pub fn cursor_world(&self) -> Option<WorldPos> {
+        self.cursor_seen_from(self.camera.corner())
+    }
+
+    /*
+     * The same, with the camera's corner at `corner`. Drawing puts the
+     * camera part of the way between ticks (see interpolated), so to draw
+     * something right under the mouse it has to be worked out from there,
+     * not from where the camera is as of the last tick. The window gets
+     * turned into the screen first (see cursor_on_screen), and then the
+     * screen into the world by the camera, its zoom and where it is.
+     */
+    pub fn cursor_seen_from(&self, corner: WorldPos) -> Option<WorldPos> {
+        let at = self.cursor_on_screen()?;
+        Some(self.camera_at(corner).to_world(at))
+    }
+
+    // The camera as it is, only with its corner at `corner`.
+    fn camera_at(&self, corner: WorldPos) -> Camera {
+        Camera { x: corner.x, y: corner.y, ..self.camera.clone() }
+    }
+
+    // Where the screen is in the window: all of it, unless it's letterboxed
+    // (see units.rs).
+    pub fn letterboxing(&self) -> Letterbox {
+        if self.config.letterbox {
+            Letterbox::fit(self.window_size, self.view_size)
+        } else {
+            Letterbox::none()
+        }
+    }
+
+    /*
+     * Where player one's box is drawn in the window this frame, in the
+     * window's points: the other way round to cursor_seen_from, the
+     * camera first and then the letterbox. A shader wants to know (see
+     * shader.rs).
+     */
+    pub fn player_on_window(&self) -> Aabb {
+        let (_, corner) = self.interpolated();
+        let (x, y) = self.drawn_at(0);
+        let b = self.player_box();
+        let top_left = WorldPos::new(b.x + x - self.players[0].x, b.y + y - self.players[0].y);
+        let camera = self.camera_at(corner.into());
+        let letterbox = self.letterboxing();
+        let (left, top) = letterbox.to_window(camera.to_screen(top_left));
+        let scale = camera.zoom * letterbox.scale;
+        Aabb::new(left, top, b.w * scale, b.h * scale)
+    }
+
+    // Everything a save needs to know about the game so far. See save.rs.
+    pub fn snapshot(&self) -> Snapshot {
+        let centre = |b: Aabb| [b.x + b.w / 2.0, b.y + b.h / 2.0];
+        let player = &self.players[0];
+        Snapshot {
+            level: self.level_index,
+            level_name: self.level().name.clone(),
+            player: [player.x, player.y],
+            velocity: [player.vx, player.vy],
+            facing: player.facing,
+            angle: player.angle,
+            expand: player.expand,
+            score: self.score,
+            health: self.health,
+            lives: Some(self.lives),
+            elapsed: self.elapsed,
+            pickups: self.entities.iter()
+                .filter(|e| e.collectible() && e.power_up().is_none() && e.item().is_none())
+                .map(|e| centre(e.bounds()))
+                .collect(),
+            power_ups: self.entities.iter()
+                .filter_map(|e| e.power_up().map(|effect| {
+                    powerup::Spot { effect: effect, at: centre(e.bounds()) }
+                }))
+                .collect(),
+            effects: self.effects.clone(),
+            enemies: self.scene.boxes(Kind::Enemy).into_iter().map(centre).collect(),
+            inventory: Some(self.inventory.clone()),
+            items: Some(self.level().items.iter().map(|kind| ItemKind {
+                at: self.entities.iter()
+                    .filter(|e| e.item() == Some(&kind.name[..]))
+                    .map(|e| centre(e.bounds()))
+                    .collect(),
+                ..kind.clone()
+            }).collect()),
+            doors: Some(self.entities.iter()
+                .filter(|e| e.lock().is_some())
+                .map(|e| { let b = e.bounds(); [b.x, b.y, b.w, b.h] })
+                .collect()),
+        }
+    }
+
+    /*
+     * Pick up where a snapshot left off. We start the level over, which
+     * puts the walls and the goal back, then swap the level's own pickups,
+     * items, doors and enemies for the ones in the snapshot and put the
+     * player back where they were. A snapshot for a level we haven't got is
+     * an error, and leaves the game as it was.
+     */
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        match self.levels.get(snapshot.level) {
+            Some(level) if level.name == snapshot.level_name => {}
+            _ => return Err(format!("there's no level {} called \"{}\" any more",
+                                    snapshot.level + 1, snapshot.level_name)),
+        }
+        self.level_index = snapshot.level;
+        self.reset();
+        // A save's from partway through the level, long after any cutscene.
+        self.cutscene = None;
+        self.particles.clear();
+        self.put_back(snapshot);
+        self.snap_camera();
+        Ok(())
+    }
+
+    /*
+     * The rest of restore: everything in a snapshot but which level it's
+     * of, put back into the level we're in. Rewinding (see rewind.rs) does
+     * this every tick without starting the level over, so it can't count
+     * on reset having just put everything back where the level file has
+     * it: whatever the snapshot has a list of goes, and comes back as the
+     * list says.
+     */
+    pub fn put_back(&mut self, snapshot: &Snapshot) {
+        // Only player one is in a save; player two (if any) is wherever
+        // they were, which after a reset is next to the spawn point.
+        let player = &mut self.players[0];
+        player.respawn(snapshot.player[0], snapshot.player[1]);
+        player.vx = snapshot.velocity[0];
+        player.vy = snapshot.velocity[1];
+        player.facing = snapshot.facing;
+        player.angle = snapshot.angle;
+        player.expand = snapshot.expand;
+        self.score = snapshot.score;
+        self.health = snapshot.health;
+        if let Some(lives) = snapshot.lives {
+            self.lives = lives;
+        }
+        self.elapsed = snapshot.elapsed;
+        let palette = self.config.palette();
+        // A save from before the inventory doesn't have it in, so the items
+        // and the doors are left as they are.
+        if let Some(ref inventory) = snapshot.inventory {
+            self.inventory = inventory.clone();
+        }
+        if let Some(ref doors) = snapshot.doors {
+            let shut: Vec<Door> = {
+                let level = self.level();
+                level.doors.iter()
+                    .filter(|spot| doors.contains(&spot.bounds))
+                    .map(|spot| door(spot, &level.items, &palette))
+                    .collect()
+            };
+            self.entities.retain(|e| e.lock().is_none());
+            for d in shut {
+                self.entities.push(Box::new(d));
+            }
+        }
+        let items = snapshot.items.is_some();
+        self.entities.retain(|e| !e.collectible() || (e.item().is_some() && !items));
+        for kind in snapshot.items.iter().flat_map(|kinds| kinds.iter()) {
+            for p in kind.at.iter() {
+                self.entities.push(Box::new(Item::at(p[0], p[1], &kind.name, kind.color)));
+            }
+        }
+        for p in snapshot.pickups.iter() {
+            self.entities.push(Box::new(Pickup::at(p[0], p[1], palette.pickup)));
+        }
+        for spot in snapshot.power_ups.iter() {
+            let color = palette.power_up(spot.effect);
+            self.entities.push(Box::new(PowerUp::at(spot.at[0], spot.at[1], spot.effect, color)));
+        }
+        self.effects = snapshot.effects.clone();
+        // Shots aren't in a save, so any in flight go.
+        self.scene.despawn_all(Kind::Enemy);
+        self.scene.despawn_all(Kind::Shot);
+        for e in snapshot.enemies.iter() {
+            self.spawn_enemy(e[0], e[1]);
+        }
+    }
+
+    // Let go of every key, as far as the players can tell.
+    pub fn forget_held_keys(&mut self) {
+        for player in self.players.iter_mut() {
+            player.input = InputState::default();
+        }
+        self.rewinding = false;
+        self.touch.release();
+    }
+
+    // F5 and F9. Saving and loading can both go wrong - no disk space, no
+    // save file yet - and neither is worth stopping the game over.
+    pub fn save_game(&mut self) {
+        let path = save::default_path();
+        match save::save(&path, &self.snapshot()) {
+            Ok(()) => info!("Saved to {}", path.display()),
+            Err(e) => error!("Couldn't save: {}", e),
+        }
+    }
+
+    pub fn load_game(&mut self) {
+        let path = save::default_path();
+        match save::load(&path).and_then(|snapshot| self.restore(&snapshot)) {
+            Ok(()) => info!("Loaded {}", path.display()),
+            Err(e) => error!("Couldn't load: {}", e),
+        }
+    }
+
+    // A controller axis moved. Like held keys, the stick position gets
+    // noted down whatever the game's up to, and update does the moving.
+    pub fn controller_axis(&mut self, axis: u8, position: f64) {
+        if !self.controller_map.axis(&mut self.players[0].input, axis, position) {
+            trace!(target: Input, "Ignored controller axis {}", axis);
+        }
+    }
+
+    // Fire off a burst of particles from the middle of the player square,
+    // however big it's grown.
+    pub fn burst(&mut self, i: usize) {
+        let b = player_bounds(&self.players[i]);
+        let (cx, cy) = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+        self.particles.burst(cx, cy, &mut self.rng);
+    }
+}
+
+// So bus::deliver can hand App to its subscribers.
+impl HasBus for App {
+    fn bus(&mut self) -> &mut Bus<App> {
+        &mut self.bus
+    }
+}
+
+// So timers::run can take App's timers out of it while they run.
+impl HasTimers for App {
+    fn timers(&mut self) -> &mut Timers<App> {
+        &mut self.timers
+    }
+}
+
+// Part of the way from a to b: none of it at t = 0, all of it at t = 1.
+fn lerp(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+// How big the config says the players are. Anything smaller than a pixel
+// would be impossible to see, or to hit.
+fn player_size(config: &Config) -> f64 {
+    config.player_size.max(1.0)
+}
+
+// The door at `spot`, in the color of the walls, with its lock in the color
+// of whichever of `kinds` it needs (or the text's, if none of them).
+fn door(spot: &DoorSpot, kinds: &[ItemKind], palette: &Palette) -> Door {
+    let b = spot.bounds;
+    let lock = kinds.iter().find(|kind| kind.name == spot.needs)
+        .map_or(palette.text, |kind| kind.color);
+    Door {
+        bounds: Aabb::new(b[0], b[1], b[2], b[3]),
+        color: palette.wall,
+        needs: spot.needs.clone(),
+        count: spot.count,
+        lock_color: lock,
+    }
+}
+
+// Only getting *entirely* inside the goal counts; poking a corner in doesn't.
+fn reached_goal(player: Aabb, goal: Aabb) -> bool {
+    goal.contains(&player)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::{reached_goal, App, LevelState, Settings, MAX_CATCH_UP_SECONDS, SIM_DT, STEP_DT};
+    use game::{Game, GameState};
+    use handlers::{handleKey, handleRelease};
+    use player::{self, player_bounds, shrink_expand, Player, Shape};
+    use tween::Easing;
+    use assets::Assets;
+    use hud::Hud;
+    use menu::{Choice, Menu};
+    use autosave::Autosave;
+    use inspector::{self, Inspector};
+    use macros;
+    use options::{self, Setting};
+    use audio::{Sound, Track};
+    use bus::{self, GameEvent};
+    use graphics::Context;
+    use renderer::{Call, Recorder};
+    use script::{Action, Script};
+    use world::WorldMode;
+    use level::Level;
+    use input::{keyboard, mouse, Keyboard, Mouse, UpdateArgs};
+    use engine::{Event, EventLoop, Scripted};
+    use config::Config;
+    use simulation;
+    use units::WorldPos;
+    use controls::{Facing, InputState};
+    use cutscene;
+    use dialogue::NpcSpot;
+    use inventory::{DoorSpot, ItemKind};
+    use collision::Aabb;
+    use ecs::Kind;
+    use minimap;
+    use bench;
+    use palette::faded;
+    use enemy;
+    use waves::{self, Waves};
+    use entity::Pickup;
+    use powerup::{self, Effect, PowerUp};
+    use net::Link;
+    use object;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+    use particles::Particle;
+    use tilemap::Tilemap;
+    use transition;
+
+    // Run one second of game time at the given update rate, and report where
+    // expand and a particle ended up.
+    fn one_second_at(ups: u32) -> (f64, Aabb) {
+        let dt = 1.0 / ups as f64;
+        let mut player = Player::at(0.0, 0.0);
+        player.expand = 100.0;
+        let mut particle = Particle {
+            x: 0.0, y: 0.0, vx: 90.0, vy: -45.0,
+            age: 0.0, lifetime: 10.0, size: 2.0, color: [1.0; 4],
+        };
+        for _ in 0..ups {
+            shrink_expand(&mut player, 60.0, Easing::Linear, dt);
+            particle.update(dt);
+        }
+        (player.expand, particle.bounds())
+    }
+
+    #[test]
+    fn same_second_at_30_and_120_ups() {
+        let (slow_expand, slow_particle) = one_second_at(30);
+        let (fast_expand, fast_particle) = one_second_at(120);
+        assert!((slow_expand - 40.0).abs() < 1e-9);
+        assert!((slow_expand - fast_expand).abs() < 1e-9);
+        assert!((slow_particle.x - fast_particle.x).abs() < 1e-9);
+        assert!((slow_particle.y - fast_particle.y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn expand_counts_towards_reaching_the_goal() {
+        let goal = Aabb::new(100.0, 100.0, 40.0, 40.0);
+        let mut player = Player::at(115.0, 115.0);
+        assert!(reached_goal(player_bounds(&player), goal));
+        // Puffed up by 30 it fills the goal exactly, which still counts...
+        player.expand = 30.0;
+        assert!(reached_goal(player_bounds(&player), goal));
+        // ...but any bigger and it spills over the edges.
+        player.expand = 40.0;
+        assert!(!reached_goal(player_bounds(&player), goal));
+    }
+
+    #[test]
+    fn expanding_flashes_the_screen() {
+        let mut app = App::new(settings());
+        app.trigger_expand(0);
+        assert_eq!(app.flash.amount, app.config.flash_strength);
+        let ticks = (app.config.flash_seconds / SIM_DT).ceil() as usize + 1;
+        for _ in 0..ticks {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.flash.amount, 0.0);
+    }
+
+    #[test]
+    fn the_achievements_hear_what_happens() {
+        let mut app = App::new(settings());
+        app.trigger_expand(0);
+        app.players[0].input.press(keyboard::Right);
+        app.advance(SIM_DT);
+        assert_eq!(app.progress[0], Progress::Expanded);
+        assert!(match app.progress[1] { Progress::Travelled(d) => d > 0.0, _ => false });
+        // Straight into the goal, without a scratch...
+        app.progress.clear();
+        app.players[0].x = 1200.0;
+        app.players[0].y = 880.0;
+        app.advance(SIM_DT);
+        assert!(app.progress.contains(&Progress::ClearedUntouched));
+        // ...which doesn't count once something's had a bite.
+        app.reset();
+        app.hurt();
+        app.progress.clear();
+        app.players[0].x = 1200.0;
+        app.players[0].y = 880.0;
+        app.advance(SIM_DT);
+        assert!(match app.level_state { LevelState::Complete { .. } => true, _ => false });
+        assert!(!app.progress.contains(&Progress::ClearedUntouched));
+    }
+
+    // The same as a Simulation's, with nothing on disk; see simulation.rs.
+    fn settings() -> Settings {
+        simulation::settings(Config::default())
+    }
+
+    // Compare everything reset is responsible for. Entities can't be
+    // compared directly, so we settle for checking they're all in the same
+    // places.
+    fn assert_same_state(a: &App, b: &App) {
+        assert_eq!(a.players, b.players);
+        assert_eq!(a.score, b.score);
+        assert_eq!(a.elapsed, b.elapsed);
+        assert_eq!(a.level_state, b.level_state);
+        assert_eq!(a.goal, b.goal);
+        assert_eq!(a.rng, b.rng);
+        assert_eq!(a.ticks, b.ticks);
+        assert_eq!(a.scene, b.scene);
+        assert_eq!((a.health, a.hurt_timer), (b.health, b.hurt_timer));
+        let bounds = |app: &App| app.entities.iter().map(|e| e.bounds()).collect::<Vec<_>>();
+        assert_eq!(bounds(a), bounds(b));
+        let particles = |app: &App| app.particles.particles.iter().map(|p| p.bounds())
+                                                           .collect::<Vec<_>>();
+        assert_eq!(particles(a), particles(b));
+    }
+
+    #[test]
+    fn reset_matches_a_fresh_app() {
+        let fresh = App::new(settings());
+        let mut app = App::new(settings());
+
+        app.move_player(0, 120.0, 35.0);
+        app.players[0].expand = 45.0;
+        app.score = 7;
+        app.elapsed = 12.5;
+        app.burst(0);
+        app.level_state = LevelState::Complete { timer: 1.0 };
+        app.reset();
+
+        assert_same_state(&app, &fresh);
+    }
+
+    #[test]
+    fn resizing_keeps_the_player_in_the_same_place_on_screen() {
+        let mut settings = settings();
+        settings.levels[0].world_width = 4000.0;
+        settings.levels[0].world_height = 3000.0;
+        settings.levels[0].spawn = [2000.0, 1500.0];
+        let mut app = App::new(settings);
+        let before = app.player_centre();
+        let fraction = |app: &App| ((before.0 - app.camera.x) / app.camera.view_width(),
+                                    (before.1 - app.camera.y) / app.camera.view_height());
+        let was = fraction(&app);
+        app.resize_view(1920.0, 1080.0);
+        assert_eq!(app.camera.width, 1920.0);
+        let now = fraction(&app);
+        assert!((now.0 - was.0).abs() < 1e-9 && (now.1 - was.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn letterboxing_keeps_the_view_the_configured_size() {
+        let mut settings = settings();
+        settings.config.letterbox = true;
+        let mut game = Game::new(settings);
+        game.resize(1920.0, 1080.0);
+        game.start();
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!(app.window_size, (1920.0, 1080.0));
+                assert_eq!(app.view_size, (640.0, 480.0));
+            }
+            _ => panic!("expected to be playing"),
+        }
+    }
+
+    #[test]
+    fn shaders_know_where_the_player_is_in_the_window() {
+        let mut settings = settings();
+        settings.config.letterbox = true;
+        let mut app = App::new(settings);
+        // Twice the view's size, so everything's twice as big, with bars
+        // 80 points deep top and bottom.
+        app.resize_window(1280.0, 1120.0);
+        let (_, (cx, cy)) = app.interpolated();
+        let (x, y) = app.drawn_at(0);
+        let size = app.config.player_size * 2.0;
+        assert_eq!(app.player_on_window(),
+                   Aabb::new((x - cx) * 2.0, 80.0 + (y - cy) * 2.0, size, size));
+    }
+
+    #[test]
+    fn new_screens_come_in_from_black() {
+        let mut game = Game::new(settings());
+        assert!(game.transition.is_none());
+        game.start();
+        assert_eq!(game.transition.map(|t| t.cover()), Some(1.0));
+        let seconds = game.settings.config.transition_seconds;
+        game.update(&UpdateArgs { dt: seconds / 2.0 });
+        let cover = game.transition.unwrap().cover();
+        assert!(cover > 0.0 && cover < 1.0);
+        game.update(&UpdateArgs { dt: seconds });
+        assert!(game.transition.is_none());
+
+        // Pausing only dims it to start with.
+        game.handle_key(Keyboard(keyboard::P));
+        game.update(&UpdateArgs { dt: 0.0 });
+        assert_eq!(game.transition.map(|t| t.cover()), Some(transition::PAUSE_COVER));
+
+        // And with no time to do it in, it's a straight cut.
+        game.settings.config.transition_seconds = 0.0;
+        game.finish();
+        assert!(game.transition.is_none());
+    }
+
+    #[test]
+    fn cutscenes_have_the_level_until_theyre_over() {
+        let mut settings = settings();
+        let mut level = Level::fallback();
+        level.enemies = vec![[900.0, 600.0]];
+        level.cutscene_steps = cutscene::parse(r#"[
+            { "say": { "text": "Hello", "seconds": 0.5 } },
+            { "walk": { "to": [150.0, 50.0], "seconds": 1.0 } },
+            { "move_enemy": { "enemy": 0, "to": [800.0, 600.0], "seconds": 0.5 } }
+        ]"#).unwrap();
+        settings.levels = vec![level];
+        let mut app = App::new(settings);
+        // The keys don't do anything while it's on...
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        assert_eq!(app.players[0].expand, 0.0);
+        for _ in 0..120 {
+            app.advance(SIM_DT);
+        }
+        // ...and neither does anything else, but the steps: half a second
+        // in, the walk's half done, and the level's clock hasn't started.
+        assert!((app.players[0].x - 100.0).abs() < 1e-6);
+        assert_eq!(app.players[0].facing, Facing::Right);
+        assert_eq!(app.cutscene.as_ref().unwrap().text, Some("Hello".to_string()));
+        assert_eq!(app.elapsed, 0.0);
+
+        // Return skips to the end of it, and then it's an ordinary level.
+        handleKey(Keyboard(keyboard::Return), &mut app);
+        assert!(app.cutscene.is_none());
+        assert_eq!((app.players[0].x, app.players[0].y), (150.0, 50.0));
+        assert_eq!(app.scene.boxes(Kind::Enemy)[0].x, 800.0);
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        assert!(app.players[0].expand > 0.0);
+
+        // Starting the level again starts it again, and it's over as soon
+        // as it's had all its seconds.
+        app.reset();
+        assert!(app.cutscene.is_some());
+        for _ in 0..250 {
+            app.advance(SIM_DT);
+        }
+        assert!(app.cutscene.is_none());
+        assert!(app.elapsed > 0.0);
+    }
+
+    #[test]
+    fn talking_holds_the_game_still_until_theyve_had_their_say() {
+        let mut settings = settings();
+        let mut level = Level::fallback();
+        let pages = vec!["Hello there.".to_string(), "Bye.".to_string()];
+        level.npcs = vec![NpcSpot { at: [55.0, 55.0], dialogue: "hi.txt".to_string(),
+                                    pages: pages }];
+        settings.levels = vec![level];
+        let mut app = App::new(settings);
+        assert!(app.talker().is_some());
+        handleKey(Keyboard(keyboard::Return), &mut app);
+        assert!(app.dialogue.is_some());
+
+        // Underneath, nothing moves, and the keys wait, but the words come
+        // up.
+        handleKey(Keyboard(keyboard::Right), &mut app);
+        app.update(&UpdateArgs { dt: 0.1 });
+        assert_eq!(app.ticks, 0);
+        assert_eq!(app.players[0].input.direction(), (0.0, 0.0));
+        assert_eq!(app.dialogue.as_ref().unwrap().shown, 4.0);
+
+        // Return shows the rest of the page, then turns over, and so on
+        // until it's all been said.
+        for _ in 0..3 {
+            handleKey(Keyboard(keyboard::Return), &mut app);
+        }
+        assert_eq!(app.dialogue.as_ref().map(|d| d.page), Some(1));
+        handleKey(Keyboard(keyboard::Return), &mut app);
+        assert!(app.dialogue.is_none());
+        app.update(&UpdateArgs { dt: 0.1 });
+        assert!(app.ticks > 0);
+
+        // Away from them, Return's nobody to talk to.
+        app.players[0].x = 500.0;
+        assert!(app.talker().is_none());
+        handleKey(Keyboard(keyboard::Return), &mut app);
+        assert!(app.dialogue.is_none());
+    }
+
+    #[test]
+    fn doors_open_for_whoever_has_the_key() {
+        let mut settings = settings();
+        let mut level = Level::fallback();
+        level.items = vec![ItemKind { name: "key".to_string(), color: [1.0, 0.85, 0.2, 1.0],
+                                      at: vec![[400.0, 500.0]] }];
+        level.doors = vec![DoorSpot { bounds: [120.0, 0.0, 10.0, 960.0],
+                                      needs: "key".to_string(), count: 1 }];
+        settings.levels = vec![level];
+        let mut app = App::new(settings);
+
+        // Without the key, the door's a wall, and says what it wants.
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..120 {
+            app.advance(SIM_DT);
+        }
+        assert!(app.player_box().right() <= 120.0);
+        assert_eq!(app.locked_door, Some(("key".to_string(), 1)));
+        // With it, it takes the key and opens.
+        app.inventory.add("key");
+        app.advance(SIM_DT);
+        assert!(app.entities.iter().all(|e| e.lock().is_none()));
+        assert_eq!(app.inventory.count("key"), 0);
+        assert_eq!(app.locked_door, None);
+
+        // Walking over a key puts it in the inventory, and isn't a point.
+        app.place_player(0, Aabb::new(390.0, 490.0, 20.0, 20.0));
+        app.advance(SIM_DT);
+        assert_eq!((app.inventory.count("key"), app.score), (1, 0));
+        assert!(app.entities.iter().all(|e| e.item().is_none()));
+        // Starting over, it's back where it was, and you haven't got it.
+        app.reset();
+        assert!(app.inventory.is_empty());
+        assert_eq!(app.entities.iter().filter(|e| e.item() == Some("key")).count(), 1);
+    }
+
+    #[test]
+    fn losing_a_life_goes_back_to_the_last_checkpoint() {
+        let mut settings = settings();
+        let mut level = Level::fallback();
+        level.checkpoints = vec![[600.0, 60.0]];
+        level.collectibles = vec![[900.0, 500.0]];
+        settings.levels = vec![level];
+        let mut app = App::new(settings);
+
+        // Touching the flag saves the game there, and lights it.
+        app.score = 2;
+        app.place_player(0, Aabb::new(590.0, 50.0, 20.0, 20.0));
+        app.advance(SIM_DT);
+        assert_eq!(app.checkpoint.as_ref().map(|c| c.at), Some((600.0, 60.0)));
+        let flag = app.entities.iter().find(|e| e.checkpoint().is_some()).unwrap().bounds();
+        assert_eq!(app.entities.iter().filter(|e| e.sprite() == Some("checkpoint_lit")).count(), 1);
+        let saved = app.checkpoint.clone();
+
+        // Off somewhere else, a point better off, and then caught.
+        app.place_player(0, Aabb::new(890.0, 490.0, 20.0, 20.0));
+        app.advance(SIM_DT);
+        assert_eq!(app.score, 3);
+        app.health = 1;
+        enemy::spawn(&mut app.scene, 905.0, 505.0, [0.0; 4]);
+        app.advance(SIM_DT);
+        // Back at the flag, with the score and the pickup as they were then,
+        // but a life down and all the health back.
+        assert_eq!((app.lives, app.health, app.score), (2, app.config.player_health, 2));
+        assert!(app.player_box().intersects(&flag));
+        assert_eq!(app.entities.iter().filter(|e| e.collectible()).count(), 1);
+        assert!(app.scene.boxes(Kind::Enemy).is_empty());
+        assert_eq!(app.checkpoint, saved);
+
+        // Starting the level over forgets it.
+        app.reset();
+        assert_eq!(app.checkpoint, None);
+        assert_eq!((app.players[0].x, app.players[0].y), app.spawn);
+    }
+
+    #[test]
+    fn holding_the_rewind_key_runs_time_backwards() {
+        let mut settings = settings();
+        let mut level = Level::fallback();
+        level.collectibles = vec![[120.0, 55.0]];
+        settings.levels = vec![level];
+        let mut app = App::new(settings);
+
+        // Half a second of walking right, past a pickup, noting where we
+        // were after each tick.
+        let mut xs = vec![app.players[0].x];
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+            xs.push(app.players[0].x);
+        }
+        app.players[0].input.release(keyboard::Right);
+        assert_eq!(app.score, 1);
+
+        // Each tick of holding the key is a tick back...
+        handleKey(Keyboard(keyboard::Z), &mut app);
+        assert!(app.rewinding);
+        for _ in 0..20 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.players[0].x, xs[40]);
+        // ...until there's no further back to go, the pickup back where it was.
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.players[0].x, xs[0]);
+        assert_eq!(app.score, 0);
+        assert_eq!(app.entities.iter().filter(|e| e.collectible()).count(), 1);
+        assert!(app.rewind.is_empty());
+
+        // Letting go, it's forwards again from there.
+        handleRelease(Keyboard(keyboard::Z), &mut app);
+        assert!(!app.rewinding);
+        app.advance(SIM_DT);
+        assert_eq!(app.rewind.len(), 1);
+    }
+
+    #[test]
+    fn the_benchmark_times_every_part() {
+        let mut game = Game::new(settings());
+        let report = game.run_bench(20, true);
+        let counts: Vec<usize> = report.times.iter().map(|t| t.len()).collect();
+        assert_eq!(counts, vec![20, 20, 20, 20]);
+        assert_eq!(report.lines().len(), 1 + bench::SYSTEMS.len());
+        assert!(report.lines()[0].starts_with("20 updates in "));
+        // Without drawing, there's nothing to say about it.
+        let report = game.run_bench(5, false);
+        assert!(report.times[3].is_empty());
+        assert_eq!(report.lines().len(), bench::SYSTEMS.len());
+    }
+
+    #[test]
+    fn hidpi_screens_get_passed_on() {
+        let mut game = Game::new(settings());
+        game.set_draw_scale(2.0);
+        game.start();
+        match game.state {
+            GameState::Playing(ref app) => assert_eq!(app.draw_scale, 2.0),
+            _ => panic!("expected to be playing"),
+        }
+        // A window that's no size at all yet doesn't say anything useful.
+        game.set_draw_scale(0.0);
+        assert_eq!(game.draw_scale, 2.0);
+    }
+
+    #[test]
+    fn the_view_can_be_its_own_size() {
+        let mut settings = settings();
+        settings.config.letterbox = true;
+        settings.config.view_width = 800;
+        settings.config.view_height = 600;
+        let mut app = App::new(settings);
+        app.resize_window(1920.0, 1080.0);
+        assert_eq!(app.view_size, (800.0, 600.0));
+        assert_eq!((app.camera.width, app.camera.height), (800.0, 600.0));
+        // Scaled up by 1.8 to fill the height, with 240 pixel bars either
+        // side: the middle of the window is the middle of the view.
+        app.mouse_moved(960.0, 540.0);
+        let (x, y) = app.cursor_world().unwrap().tuple();
+        assert!((x - app.camera.x - 400.0).abs() < 1e-9);
+        assert!((y - app.camera.y - 300.0).abs() < 1e-9);
+        // And it's picked up from a new config, like letterbox is.
+        let mut config = app.config.clone();
+        config.view_height = 0;
+        app.apply_config(config);
+        assert_eq!(app.view_size, (800.0, 480.0));
+    }
+
+    #[test]
+    fn the_confetti_comes_from_the_config() {
+        let mut settings = settings();
+        settings.config.particle_min_count = 3;
+        settings.config.particle_max_count = 3;
+        let mut app = App::new(settings);
+        app.trigger_expand(0);
+        assert_eq!(app.particles.particles.len(), 3);
+        // A new config changes the next burst, color and all.
+        let mut config = app.config.clone();
+        config.particle_color = [0.0, 1.0, 0.0, 1.0];
+        app.apply_config(config);
+        assert_eq!(app.particles.settings.color, [0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn f11_goes_fullscreen_from_any_screen() {
+        let mut game = Game::new(settings());
+        game.handle_key(Keyboard(keyboard::F11));
+        assert!(game.fullscreen);
+        game.start();
+        game.handle_key(Keyboard(keyboard::F11));
+        assert!(!game.fullscreen);
+    }
+
+    #[test]
+    fn any_event_loop_can_drive_the_game() {
+        let mut game = Game::new(settings());
+        let mut events = Scripted::new(vec![
+            Event::Resize(800.0, 600.0),
+            Event::Press(Keyboard(keyboard::Return)),
+            Event::Release(Keyboard(keyboard::Return)),
+            Event::Press(Keyboard(keyboard::Right)),
+            Event::Update(UpdateArgs { dt: 0.25 }),
+            Event::Other,
+        ]);
+        while let Some(e) = events.next(&mut ()) {
+            game.handle_event(&e);
+        }
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!(app.window_size, (800.0, 600.0));
+                assert!(app.players[0].x > Level::fallback().spawn[0]);
+            }
+            _ => panic!("Enter should have started a game"),
+        }
+    }
+
+    #[test]
+    fn trying_again_keeps_the_window_size() {
+        let mut game = Game::new(settings());
+        game.resize(800.0, 600.0);
+        game.restart(0);
+        match game.state {
+            GameState::Playing(ref app) => assert_eq!(app.window_size, (800.0, 600.0)),
+            _ => panic!("restart should have started a game"),
+        }
+    }
+
+    #[test]
+    fn the_game_hears_about_the_window_after_the_options() {
+        let mut game = Game::new(settings());
+        game.start();
+        game.options_from_pause();
+        game.resize(800.0, 600.0);
+        game.set_draw_scale(2.0);
+        game.leave_options();
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!((app.window_size, app.draw_scale), ((800.0, 600.0), 2.0));
+            }
+            _ => panic!("leaving the options should have gone back to the game"),
+        }
+    }
+
+    #[test]
+    fn start_position_beats_the_spawn_point() {
+        let mut settings = settings();
+        settings.config.start_position = Some([300.0, 400.0]);
+        let app = App::new(settings);
+        assert_eq!((app.players[0].x, app.players[0].y), (300.0, 400.0));
+    }
+
+    /*
+     * Holding a key down should move the player by the same amount in a
+     * second whatever the update rate, and where it's drawn should never go
+     * backwards. 144 is in there because it's what plenty of monitors run
+     * at, and it doesn't divide nicely into anything: some updates have no
+     * tick at all, and it's the interpolation that keeps those moving.
+     */
+    #[test]
+    fn held_keys_move_smoothly_at_any_ups() {
+        let mut distances = Vec::new();
+        for &ups in [30, 60, 144].iter() {
+            let mut app = App::new(settings());
+            let start = app.players[0].x;
+            handleKey(Keyboard(keyboard::Right), &mut app);
+
+            let dt = 1.0 / ups as f64;
+            let mut drawn = start;
+            for _ in 0..ups {
+                app.update(&UpdateArgs { dt: dt });
+                let ((x, _), _) = app.interpolated();
+                assert!(x >= drawn);
+                drawn = x;
+            }
+            assert!(drawn > start);
+            assert_eq!(app.ticks, 120);
+            // Nearly move_speed pixels: a little goes on getting up to
+            // speed.
+            let distance = app.players[0].x - start;
+            assert!(distance < app.config.move_speed && distance > app.config.move_speed * 0.9);
+            distances.push(distance);
+
+            // And letting go slows it down, then stops it.
+            handleRelease(Keyboard(keyboard::Right), &mut app);
+            app.update(&UpdateArgs { dt: dt });
+            assert!(app.players[0].vx > 0.0 && app.players[0].vx < app.config.move_speed);
+            for _ in 0..ups {
+                app.update(&UpdateArgs { dt: dt });
+            }
+            let last = app.players[0].x;
+            app.update(&UpdateArgs { dt: dt });
+            assert_eq!((app.players[0].x, app.players[0].vx), (last, 0.0));
+        }
+        // The same distance whatever the update rate.
+        assert!(distances.iter().all(|d| (d - distances[0]).abs() < 1e-9));
+    }
+
+    #[test]
+    fn drawing_goes_part_way_to_the_next_tick() {
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Right);
+        app.update(&UpdateArgs { dt: SIM_DT * 1.5 });
+        assert_eq!(app.ticks, 1);
+        // However far the first tick went, we're drawn half of that.
+        let step = app.players[0].x - 50.0;
+        assert!(step > 0.0);
+        let ((x, _), _) = app.interpolated();
+        assert!((x - (50.0 + step * 0.5)).abs() < 1e-9);
+        // A big stall only catches up so much.
+        app.update(&UpdateArgs { dt: 10.0 });
+        assert!(app.ticks <= 2 + (MAX_CATCH_UP_SECONDS / SIM_DT) as u64);
+    }
+
+    /*
+     * The same as same_second_at_30_and_120_ups, but with the whole App:
+     * a second of walking diagonally while expand shrinks should end up in
+     * the same place at any update rate.
+     */
+    #[test]
+    fn whole_app_second_is_the_same_at_any_ups() {
+        let second_at = |ups: u32| {
+            let mut app = App::new(settings());
+            app.players[0].input.press(keyboard::Down);
+            app.players[0].input.press(keyboard::Right);
+            app.players[0].expand = 50.0;
+            for _ in 0..ups {
+                app.update(&UpdateArgs { dt: 1.0 / ups as f64 });
+            }
+            app
+        };
+        let slow = second_at(30);
+        for &ups in [60, 144].iter() {
+            let fast = second_at(ups);
+            assert!((slow.players[0].x - fast.players[0].x).abs() < 1e-9);
+            assert!((slow.players[0].y - fast.players[0].y).abs() < 1e-9);
+            assert!((slow.players[0].expand - fast.players[0].expand).abs() < 1e-9);
+            assert_eq!((slow.players[0].vx, slow.players[0].vy),
+                       (fast.players[0].vx, fast.players[0].vy));
+        }
+    }
+
+    #[test]
+    fn walking_into_a_wall_stops_the_player() {
+        let mut app = App::new(settings());
+        // The wall's 250 pixels to the right, once we're level with it; two
+        // seconds will get us there.
+        app.players[0].y = 150.0;
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..240 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!(app.players[0].x + 10.0, 300.0);
+        assert_eq!(app.players[0].vx, 0.0);
+    }
+
+    #[test]
+    fn expanding_next_to_a_wall_pushes_the_player_away() {
+        let mut app = App::new(settings());
+        // Flush against the left face of the wall at x = 300.
+        app.players[0].x = 290.0;
+        app.players[0].y = 150.0;
+        app.players[0].expand = 20.0;
+        app.advance(0.0);
+        let b = app.player_box();
+        assert_eq!(b.right(), 300.0);
+        assert!(app.obstacles().iter().all(|o| !b.intersects(o)));
+    }
+
+    #[test]
+    fn enemies_chase_and_hurt_the_player() {
+        let mut app = App::new(settings());
+        let health = app.health;
+        // An enemy a little way to the right comes after us...
+        let enemy = enemy::spawn(&mut app.scene, 100.0, 55.0, [0.0; 4]);
+        let start = app.scene.bounds(enemy).unwrap();
+        app.update(&UpdateArgs { dt: 0.1 });
+        assert!(app.scene.bounds(enemy).unwrap().x < start.x);
+        // ...and once it's caught up, that's a point of health, but only the
+        // one, however long it sits on top of us.
+        for _ in 0..12 {
+            app.update(&UpdateArgs { dt: 0.05 });
+        }
+        assert_eq!(app.health, health - 1);
+        assert!(app.hurt_timer > 0.0);
+    }
+
+    #[test]
+    fn running_out_of_health_ends_the_game() {
+        let mut app = App::new(settings());
+        app.health = 1;
+        app.lives = 1;
+        app.players[0].x = 200.0;
+        enemy::spawn(&mut app.scene, 205.0, 55.0, [0.0; 4]);
+        app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        assert_eq!(app.health, 0);
+        assert_eq!(app.level_state, LevelState::Lost);
+        // And after that, nothing happens at all.
+        let ticks = app.ticks;
+        app.players[0].input.press(keyboard::Right);
+        app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        assert_eq!(app.players[0].x, 200.0);
+        assert_eq!(app.ticks, ticks + 1);
+    }
+
+    #[test]
+    fn running_out_of_health_with_lives_left_starts_over_at_the_spawn() {
+        let mut app = App::new(settings());
+        assert_eq!(app.lives, 3);
+        app.health = 1;
+        app.score = 2;
+        app.players[0].x = 200.0;
+        enemy::spawn(&mut app.scene, 205.0, 55.0, [0.0; 4]);
+        app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        assert_eq!(app.level_state, LevelState::Playing);
+        assert_eq!((app.lives, app.health, app.score), (2, app.config.player_health, 2));
+        assert_eq!((app.players[0].x, app.players[0].y), app.spawn);
+        assert_eq!(app.hurt_timer, app.config.respawn_seconds);
+        // A reset doesn't give the life back.
+        app.reset();
+        assert_eq!(app.lives, 2);
+    }
+
+    #[test]
+    fn menu_then_game_then_game_over_then_menu() {
+        let mut game = Game::new(settings());
+        assert!(match game.state { GameState::Menu(_) => true, _ => false });
+        // Nothing but Enter gets us started.
+        game.handle_key(Keyboard(keyboard::Space));
+        assert!(match game.state { GameState::Menu(_) => true, _ => false });
+        game.handle_key(Keyboard(keyboard::Return));
+        match game.state {
+            GameState::Playing(ref mut app) => {
+                app.score = 4;
+                app.health = 1;
+                app.lives = 1;
+                enemy::spawn(&mut app.scene, 55.0, 55.0, [0.0; 4]);
+            }
+            _ => panic!("Enter should have started a game"),
+        }
+        game.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        match game.state {
+            GameState::GameOver { score, level, ref name } => {
+                assert_eq!((score, level, name.clone()), (4, 0, Some(String::new())))
+            }
+            _ => panic!("the enemy should have finished us off"),
+        }
+        // It's a high score, so first there's a name to type, and X and R
+        // are letters in it rather than quitting or trying again.
+        game.handle_text("X");
+        game.handle_key(Keyboard(keyboard::X));
+        game.handle_text("xR");
+        game.handle_key(Keyboard(keyboard::R));
+        game.handle_key(Keyboard(keyboard::Backspace));
+        game.handle_key(Keyboard(keyboard::Return));
+        assert!(!game.quit);
+        assert_eq!(game.high_scores.entries[0].name, "Xx");
+        assert_eq!(game.high_scores.entries[0].score, 4);
+        assert!(match game.state { GameState::GameOver { name: None, .. } => true, _ => false });
+        game.handle_key(Keyboard(keyboard::Return));
+        assert!(match game.state { GameState::Menu(_) => true, _ => false });
+        // X quits from anywhere that isn't the game itself.
+        game.handle_key(Keyboard(keyboard::X));
+        assert!(game.quit);
+    }
+
+    #[test]
+    fn trying_again_after_a_game_over_starts_the_same_level_afresh() {
+        let mut settings = settings();
+        settings.levels.push(Level::fallback());
+        let mut game = Game::new(settings);
+        game.state = GameState::GameOver { score: 3, level: 1, name: None };
+        game.handle_key(Keyboard(keyboard::R));
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!(app.level_index, 1);
+                assert_eq!((app.players[0].x, app.players[0].y), (50.0, 50.0));
+                assert_eq!((app.score, app.players[0].expand), (0, 0.0));
+                assert_eq!(app.health, app.config.player_health);
+            }
+            _ => panic!("R should have started the game again"),
+        }
+    }
+
+    #[test]
+    fn a_snapshot_puts_everything_back() {
+        let mut settings = settings();
+        settings.levels[0].collectibles = vec![[100.0, 55.0], [400.0, 400.0]];
+        settings.levels[0].enemies = vec![[900.0, 900.0], [1000.0, 900.0]];
+        let mut app = App::new(settings.clone());
+        // Pick one thing up and shoot one enemy, then save.
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..60 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        let first = app.scene.of_kind(Kind::Enemy)[0];
+        app.scene.despawn(first);
+        app.players[0].expand = 7.5;
+        let snapshot = app.snapshot();
+        assert_eq!((snapshot.score, snapshot.pickups.len(), snapshot.enemies.len()), (1, 1, 1));
+
+        // A fresh game, loaded from the snapshot, is the same game.
+        let mut loaded = App::new(settings);
+        loaded.restore(&snapshot).unwrap();
+        assert_eq!(loaded.snapshot(), snapshot);
+        // Everything about the player that's saved, that is: held keys
+        // aren't.
+        let (a, b) = (&loaded.players[0], &app.players[0]);
+        assert_eq!((a.x, a.y, a.vx, a.vy, a.facing, a.expand),
+                   (b.x, b.y, b.vx, b.vy, b.facing, b.expand));
+        assert_eq!(loaded.obstacles(), app.obstacles());
+    }
+
+    #[test]
+    fn a_snapshot_for_another_level_is_refused() {
+        let mut app = App::new(settings());
+        let mut snapshot = app.snapshot();
+        snapshot.level_name = "Somewhere else".to_string();
+        app.score = 5;
+        assert!(app.restore(&snapshot).is_err());
+        assert_eq!(app.score, 5);
+    }
+
+    #[test]
+    fn headless_games_play_themselves() {
+        let mut settings = settings();
+        settings.levels[0].enemies = vec![[60.0, 60.0]];
+        settings.config.lives = 1;
+        let mut game = Game::new(settings);
+        // Nobody moves, so the enemy on the spawn point keeps catching the
+        // player until it's game over.
+        assert_eq!(game.run_headless(10.0, &mut Script::empty()), (0, 0));
+        match game.state {
+            GameState::GameOver { .. } => {}
+            _ => panic!("expected a game over"),
+        }
+    }
+
+    #[test]
+    fn headless_games_follow_the_script() {
+        let mut settings = settings();
+        settings.levels[0].collectibles = vec![[150.0, 55.0]];
+        let mut game = Game::new(settings);
+        let mut script = Script::parse("0 press Right\n0.75 release Right").unwrap();
+        assert_eq!(game.run_headless(2.0, &mut script), (1, 0));
+        match game.state {
+            GameState::Playing(ref app) => assert!(app.players[0].x > 150.0),
+            _ => panic!("expected to still be playing"),
+        }
+    }
+
+    #[test]
+    fn bots_play_at_their_own_pace() {
+        use std::sync::mpsc;
+        use bot::Bot;
+        let (send, lines) = mpsc::channel();
+        for line in ["move right", "step 60", "move", "quit"].iter() {
+            send.send(line.to_string()).unwrap();
+        }
+        let mut game = Game::new(settings());
+        game.bot = Some(Bot::new(lines));
+        // The bot quits long before the usual ten seconds are up.
+        game.run_headless(10.0, &mut Script::empty());
+        assert!(game.quit);
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!(app.ticks, 60);
+                assert!(app.players[0].x > 60.0);
+                assert_eq!(app.players[0].input, InputState::default());
+            }
+            _ => panic!("expected to still be playing"),
+        }
+    }
+
+    #[test]
+    fn replays_play_out_the_same() {
+        let mut settings = settings();
+        settings.levels[0].collectibles = vec![[150.0, 55.0]];
+        settings.levels[0].enemies = vec![[300.0, 300.0]];
+        let mut played = App::new(settings.clone());
+        let frame = UpdateArgs { dt: 1.0 / 60.0 };
+        for i in 0..120 {
+            match i {
+                10 => handleKey(Keyboard(keyboard::Right), &mut played),
+                50 => handleKey(Keyboard(keyboard::Down), &mut played),
+                70 => handleRelease(Keyboard(keyboard::Right), &mut played),
+                90 => handleKey(Keyboard(keyboard::Space), &mut played),
+                _ => {}
+            }
+            played.update(&frame);
+        }
+        assert_eq!(played.recording.len(), 4);
+
+        // Written out and read back in, and played at another rate entirely.
+        let text = Script::from_steps(played.recording.clone()).to_text("test");
+        let mut replayed = App::new(settings);
+        replayed.replay = Some(Script::parse(&text).unwrap());
+        for _ in 0..60 {
+            replayed.update(&UpdateArgs { dt: 1.0 / 30.0 });
+        }
+        assert!(replayed.replay.as_ref().unwrap().finished());
+        assert_same_state(&played, &replayed);
+    }
+
+    #[test]
+    fn things_that_happen_make_a_noise() {
+        let mut settings = settings();
+        settings.levels[0].collectibles = vec![[150.0, 55.0]];
+        let mut app = App::new(settings);
+        handleKey(Keyboard(keyboard::Right), &mut app);
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        for _ in 0..120 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.sounds, vec![Sound::Move, Sound::Expand, Sound::Pickup]);
+
+        // M mutes, which is up to Game when it plays them, not the App.
+        handleKey(Keyboard(keyboard::M), &mut app);
+        assert!(app.config.audio.muted);
+    }
+
+    #[test]
+    fn starting_a_game_fades_the_music_over() {
+        let mut game = Game::new(settings());
+        let second = UpdateArgs { dt: 1.0 };
+        game.update(&second);
+        assert!(game.music.gain(Track::Menu) > 0.0);
+        game.start();
+        for _ in 0..2 {
+            game.update(&second);
+        }
+        assert_eq!((game.music.gain(Track::Menu), game.music.gain(Track::Game)), (0.0, 1.0));
+    }
+
+    #[test]
+    fn the_trail_is_left_behind_but_not_across_jumps() {
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..30 {
+            app.advance(SIM_DT);
+        }
+        let length = app.config.trail_length;
+        let mut r = Recorder::new();
+        app.trail.draw([1.0; 4], 0.5, &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls.len(), length);
+        app.reset();
+        r.calls.clear();
+        app.trail.draw([1.0; 4], 0.5, &Context::new_abs(640.0, 480.0), &mut r);
+        assert!(r.calls.is_empty());
+    }
+
+    #[test]
+    fn dashing_goes_a_long_way_then_has_to_wait() {
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Down);
+        handleKey(Keyboard(keyboard::LShift), &mut app);
+        let start = app.players[0].y;
+        // A tenth of a second of dash is ninety pixels, where walking
+        // would only have gone twenty.
+        for _ in 0..12 {
+            app.advance(SIM_DT);
+        }
+        assert!((app.players[0].y - start - 90.0).abs() < 1e-6);
+
+        // Still cooling down, so this one does nothing.
+        handleKey(Keyboard(keyboard::RShift), &mut app);
+        assert_eq!(app.dash_timer, 0.0);
+        for _ in 0..120 {
+            app.advance(SIM_DT);
+        }
+        handleKey(Keyboard(keyboard::RShift), &mut app);
+        assert!(app.dash_timer > 0.0);
+    }
+
+    #[test]
+    fn dashing_players_flash() {
+        let mut app = App::new(settings());
+        let flashes = |app: &mut App| {
+            let mut r = Recorder::new();
+            app.render(&mut r, &Assets::none(), &mut Hud::none());
+            !r.rects_in(Config::default().dash_flash_color).is_empty()
+        };
+        assert!(!flashes(&mut app));
+        app.dash();
+        assert!(flashes(&mut app));
+    }
+
+    // A level with a stone floor at y = 96, under the spawn point, in
+    // platformer mode.
+    fn platformer_app() -> App {
+        let mut settings = settings();
+        settings.levels[0].ground = Tilemap::parse(
+            r#"{ "tile_size": 32.0, "tiles": [[0, 0, 0], [0, 0, 0], [0, 0, 0], [5, 5, 5]] }"#)
+            .unwrap();
+        let mut app = App::new(settings);
+        handleKey(Keyboard(keyboard::G), &mut app);
+        app
+    }
+
+    #[test]
+    fn platformers_fall_onto_solid_tiles() {
+        let mut app = platformer_app();
+        assert!(app.platformer);
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        assert!(app.grounded);
+        assert_eq!(app.players[0].y, 86.0);
+    }
+
+    #[test]
+    fn holding_jump_goes_higher() {
+        let peak = |hold: usize| {
+            let mut app = platformer_app();
+            for _ in 0..60 {
+                app.advance(SIM_DT);
+            }
+            handleKey(Keyboard(keyboard::Space), &mut app);
+            let mut top = app.players[0].y;
+            for tick in 0..120 {
+                if tick == hold { handleRelease(Keyboard(keyboard::Space), &mut app) }
+                app.advance(SIM_DT);
+                top = top.min(app.players[0].y);
+            }
+            assert!(app.grounded);
+            86.0 - top
+        };
+        let (tap, leap) = (peak(3), peak(120));
+        assert!(tap > 0.0);
+        assert!(leap > tap * 2.0);
+    }
+
+    #[test]
+    fn no_jumping_off_thin_air() {
+        let mut app = platformer_app();
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        assert!(app.players[0].vy >= 0.0);
+        assert!(!app.jumping);
+    }
+
+    #[test]
+    fn leaning_on_a_wall_only_bumps_once() {
+        // The fallback level's wall starts at x = 300, and this is 100
+        // pixels of walking right, from 40 pixels short of it.
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Right);
+        app.move_player(0, 200.0, 100.0);
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.sounds, vec![Sound::Collision]);
+    }
+
+    #[test]
+    fn drawing_works_without_a_window() {
+        let mut settings = settings();
+        settings.levels[0].enemies = vec![[300.0, 300.0]];
+        let enemy_color = settings.config.enemy_color;
+        let mut game = Game::new(settings);
+        let mut r = Recorder::new();
+        game.draw(640.0, 480.0, &mut r);
+        assert!(r.rects_in(enemy_color).is_empty());
+        game.start();
+        game.draw(640.0, 480.0, &mut r);
+        assert_eq!(r.rects_in(enemy_color), vec![[292.0, 292.0, 16.0, 16.0]]);
+    }
+
+    #[test]
+    fn the_menu_goes_where_you_pick() {
+        let selected = |game: &Game| match game.state {
+            GameState::Menu(ref menu) => Some(menu.selected()),
+            _ => None,
+        };
+        let mut game = Game::new(settings());
+        game.handle_key(Keyboard(keyboard::Down));
+        assert_eq!(selected(&game), Some(Choice::Options));
+        game.handle_key(Keyboard(keyboard::Return));
+        assert!(match game.state { GameState::Options(_) => true, _ => false });
+        // Left and Right there go through the palettes.
+        game.handle_key(Keyboard(keyboard::Right));
+        assert_eq!(game.settings.config.palette, "deuteranopia");
+        game.handle_key(Keyboard(keyboard::Left));
+        game.handle_key(Keyboard(keyboard::Left));
+        assert_eq!(game.settings.config.palette, "high_contrast");
+        // Back from the options lands on Options again.
+        game.handle_key(Keyboard(keyboard::Escape));
+        assert_eq!(selected(&game), Some(Choice::Options));
+        game.handle_key(Keyboard(keyboard::Down));
+        game.handle_key(Keyboard(keyboard::Return));
+        assert!(game.quit);
+    }
+
+    #[test]
+    fn the_options_from_the_pause_go_back_to_the_same_game() {
+        let mut game = Game::new(settings());
+        game.start();
+        if let GameState::Playing(ref mut app) = game.state {
+            app.score = 7;
+        }
+        game.handle_key(Keyboard(keyboard::P));
+        game.handle_key(Keyboard(keyboard::O));
+        assert!(match game.state { GameState::Options(_) => true, _ => false });
+        game.handle_key(Keyboard(keyboard::Right));
+        game.handle_key(Keyboard(keyboard::Escape));
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert!(app.paused);
+                assert_eq!((app.score, &app.config.palette[..]), (7, "deuteranopia"));
+            }
+            _ => panic!("should be back in the game"),
+        }
+        assert!(game.suspended.is_none());
+    }
+
+    #[test]
+    fn the_picked_number_can_be_nudged_or_typed_in() {
+        let mut app = App::new(settings());
+        app.scene.clear();
+        app.spawn_enemy(300.0, 200.0);
+        let mut inspecting = Inspector::new();
+        let x = app.players[0].x;
+        inspecting.handle_key(keyboard::Equals, &mut app);
+        assert_eq!(app.players[0].x, x + inspector::NUDGE);
+
+        // Down to the enemy, across to y, and type a new one in.
+        let rows = inspector::rows(&app);
+        let enemy = rows.iter().position(|r| r.name.starts_with("enemy")).unwrap();
+        for _ in 0..enemy {
+            inspecting.handle_key(keyboard::Down, &mut app);
+        }
+        inspecting.handle_key(keyboard::Right, &mut app);
+        inspecting.handle_key(keyboard::Return, &mut app);
+        inspecting.handle_event(&Event::Text("12x5".to_string()), Some(&mut app));
+        assert!(inspecting.lines(&inspector::rows(&app), 40)[enemy].contains("y [125_]"));
+        inspecting.handle_key(keyboard::Return, &mut app);
+        assert_eq!(app.scene.boxes(Kind::Enemy)[0].y, 125.0);
+        // An enemy has no speed of its own to change.
+        inspecting.handle_key(keyboard::Right, &mut app);
+        inspecting.handle_key(keyboard::Return, &mut app);
+        assert_eq!(inspecting.typing, None);
+    }
+
+    #[test]
+    fn a_macro_key_presses_the_keys_it_stands_for() {
+        let mut settings = settings();
+        settings.config.macros.insert("V".to_string(), vec![
+            "0 press LShift".to_string(), "0 release LShift".to_string(),
+            "0.1 press Space".to_string(), "0.1 release Space".to_string(),
+        ]);
+        let mut app = App::new(settings);
+        handleKey(Keyboard(keyboard::V), &mut app);
+        // The dash straight away, and the expand a tenth of a second later.
+        assert!(app.dash_timer > 0.0);
+        assert_eq!(app.players[0].expand, 0.0);
+        for _ in 0..14 {
+            app.update(&UpdateArgs { dt: SIM_DT });
+        }
+        handleRelease(Keyboard(keyboard::V), &mut app);
+        assert!(app.players[0].expand > 0.0);
+        assert!(app.macros.playing.is_empty());
+        // A replay has the keys it pressed, to press them again itself.
+        let pressed: Vec<_> = app.recording.iter().map(|s| s.key).collect();
+        assert!(pressed.contains(&keyboard::LShift) && pressed.contains(&keyboard::Space));
+    }
+
+    #[test]
+    fn a_macro_can_be_recorded_and_put_on_a_key() {
+        let mut app = App::new(settings());
+        handleKey(Keyboard(keyboard::C), &mut app);
+        // The keys still do what they do while they're being recorded.
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        assert_eq!(app.players[0].expand, app.config.expand_step);
+        for _ in 0..30 {
+            app.update(&UpdateArgs { dt: SIM_DT });
+        }
+        handleKey(Keyboard(keyboard::Right), &mut app);
+        handleKey(Keyboard(keyboard::C), &mut app);
+        assert!(macros::status(&app.macros, &app.config, &app.settings.locale).is_some());
+        handleKey(Keyboard(keyboard::V), &mut app);
+        handleRelease(Keyboard(keyboard::V), &mut app);
+        assert_eq!(app.macros.capture, None);
+
+        // Space and Right, and then both let go of, since they were still
+        // down at the end.
+        let steps = macros::bound(&app.config, keyboard::V).unwrap();
+        let keys: Vec<_> = steps.iter().map(|s| (s.action, s.key)).collect();
+        assert_eq!(keys, vec![(Action::Press, keyboard::Space), (Action::Press, keyboard::Right),
+                              (Action::Release, keyboard::Space),
+                              (Action::Release, keyboard::Right)]);
+        assert!((steps[1].at - 0.25).abs() < 1e-9);
+
+        handleRelease(Keyboard(keyboard::Space), &mut app);
+        handleRelease(Keyboard(keyboard::Right), &mut app);
+        app.players[0].expand = 0.0;
+        handleKey(Keyboard(keyboard::V), &mut app);
+        assert_eq!(app.players[0].expand, app.config.expand_step);
+
+        // And pressing C twice forgets about it.
+        handleKey(Keyboard(keyboard::C), &mut app);
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        handleKey(Keyboard(keyboard::C), &mut app);
+        handleKey(Keyboard(keyboard::C), &mut app);
+        assert_eq!(app.macros.capture, None);
+        assert_eq!(app.config.macros.len(), 1);
+    }
+
+    #[test]
+    fn a_turbo_key_goes_on_pressing_itself() {
+        let mut settings = settings();
+        settings.config.turbo_keys = vec!["Space".to_string()];
+        let mut app = App::new(settings);
+        let every = macros::turbo_ticks(&app.config);
+        assert_eq!(every, 12);
+        let presses = |app: &App| app.recording.iter()
+            .filter(|s| s.key == keyboard::Space && s.action == Action::Press)
+            .count();
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        for _ in 0..every * 3 {
+            app.update(&UpdateArgs { dt: SIM_DT });
+        }
+        assert_eq!(presses(&app), 3);
+        handleRelease(Keyboard(keyboard::Space), &mut app);
+        for _ in 0..every * 3 {
+            app.update(&UpdateArgs { dt: SIM_DT });
+        }
+        assert_eq!(presses(&app), 3);
+    }
+
+    #[test]
+    fn an_autosave_is_there_to_resume_next_time() {
+        let dir = ::std::env::temp_dir().join(format!("jumpy-resume-{}", ::std::process::id()));
+        let mut game = Game::new(settings());
+        game.autosave = Some(Autosave::new(&dir, 0.5, 2));
+        game.start();
+        if let GameState::Playing(ref mut app) = game.state {
+            app.score = 11;
+        }
+        for _ in 0..4 {
+            game.update(&UpdateArgs { dt: 0.25 });
+        }
+        assert!(game.autosave.as_ref().unwrap().slot_path(1).exists());
+        // Quitting saves the very last of it, and says it wasn't a crash.
+        if let GameState::Playing(ref mut app) = game.state {
+            app.score = 12;
+        }
+        game.end_session();
+
+        let mut next_time = Game::new(settings());
+        let mut autosave = Autosave::new(&dir, 0.5, 2);
+        next_time.resume = autosave.find();
+        assert!(!autosave.session.as_ref().unwrap().running);
+        next_time.autosave = Some(autosave);
+        next_time.state = GameState::Menu(Menu::resuming());
+        next_time.handle_key(Keyboard(keyboard::Return));
+        match next_time.state {
+            GameState::Playing(ref app) => assert_eq!(app.score, 12),
+            _ => panic!("should have resumed"),
+        }
+        assert!(next_time.resume.is_none());
+        ::std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dropping_a_save_or_a_level_on_the_window_plays_it() {
+        let dir = ::std::env::temp_dir();
+        let save_path = dir.join(format!("jumpy-drop-save-{}.json", ::std::process::id()));
+        let mut app = App::new(settings());
+        app.score = 9;
+        ::save::save(&save_path, &app.snapshot()).unwrap();
+        // From the menu, a save's a game, picking up where it left off.
+        let mut game = Game::new(settings());
+        game.handle_event(&Event::Drop(save_path.clone()));
+        ::std::fs::remove_file(&save_path).unwrap();
+        match game.state {
+            GameState::Playing(ref app) => assert_eq!(app.score, 9),
+            _ => panic!("the save should be playing"),
+        }
+
+        // A level goes in with the others, and starts over on it.
+        let level_path = dir.join(format!("jumpy-drop-{}.level.json", ::std::process::id()));
+        let mut level = Level::fallback();
+        level.name = "Dropped in".to_string();
+        ::level::save(&level_path, &level).unwrap();
+        game.handle_event(&Event::Drop(level_path.clone()));
+        ::std::fs::remove_file(&level_path).unwrap();
+        match game.state {
+            GameState::Playing(ref app) => {
+                assert_eq!((app.level_index, &app.level().name[..], app.score),
+                           (1, "Dropped in", 0));
+            }
+            _ => panic!("the level should be playing"),
+        }
+        assert_eq!(game.settings.levels.len(), 2);
+
+        // Something that's neither leaves the game alone.
+        game.handle_event(&Event::Drop(dir.join("jumpy-drop-not-there.json")));
+        assert!(match game.state { GameState::Playing(ref app) => app.level_index == 1,
+                                   _ => false });
+    }
+
+    #[test]
+    fn the_mouse_works_the_buttons() {
+        // The middle of button number `i` on whatever screen it's on.
+        let middle = |game: &Game, i: usize| {
+            let (w, h) = game.window_size;
+            let ui = game.screen_ui(w, h).unwrap();
+            let b = ui.focusable_widget(i).and_then(|widget| widget.target()).unwrap();
+            (b.x + b.w / 2.0, b.y + b.h / 2.0)
+        };
+        let mut game = Game::new(settings());
+        let (x, y) = middle(&game, 1);
+        game.mouse_moved(x, y);
+        assert!(match game.state {
+            GameState::Menu(ref menu) => menu.selected() == Choice::Options,
+            _ => false,
+        });
+        game.handle_key(Mouse(mouse::Left));
+        assert!(match game.state { GameState::Options(_) => true, _ => false });
+        // Clicking on a slider sets it to there, whole tenths at a time.
+        let volume = options::SETTINGS.iter().position(|&s| s == Setting::Volume).unwrap();
+        game.handle_key(Keyboard(keyboard::Up));
+        let first = match game.state { GameState::Options(ref o) => o.first, _ => 0 };
+        let (x, y) = middle(&game, volume - first);
+        game.mouse_moved(x - 25.0, y);
+        game.handle_key(Mouse(mouse::Left));
+        assert_eq!(game.settings.config.audio.volume, 0.4);
+
+        // Paused, Options is the second button.
+        let mut game = Game::new(settings());
+        game.start();
+        game.handle_key(Keyboard(keyboard::P));
+        let (x, y) = middle(&game, 1);
+        game.mouse_moved(x, y);
+        game.handle_key(Mouse(mouse::Left));
+        assert!(match game.state { GameState::Options(_) => true, _ => false });
+    }
+
+    #[test]
+    fn shooting_an_enemy_gets_rid_of_it() {
+        let mut app = App::new(settings());
+        // Facing right, with one enemy straight ahead and one well out of
+        // the way.
+        app.players[0].facing = Facing::Right;
+        enemy::spawn(&mut app.scene, 250.0, 55.0, [0.0; 4]);
+        enemy::spawn(&mut app.scene, 1000.0, 900.0, [0.0; 4]);
+        handleKey(Keyboard(keyboard::F), &mut app);
+        assert_eq!(app.scene.count(Kind::Shot), 1);
+        for _ in 0..60 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!(app.scene.count(Kind::Enemy), 1);
+        assert_eq!(app.scene.count(Kind::Shot), 0);
+        assert_eq!(app.knocked_out, 1);
+    }
+
+    #[test]
+    fn the_bus_tells_everyone_who_listens() {
+        let mut app = App::new(settings());
+        app.bus.publish(GameEvent::EnemyKilled { at: (300.0, 200.0) });
+        app.bus.publish(GameEvent::PickupCollected { at: (10.0, 10.0), power_up: false });
+        app.bus.publish(GameEvent::PickupCollected { at: (20.0, 10.0), power_up: true });
+        bus::deliver(&mut app);
+        // The HUD counted it, and it went out with a bang...
+        assert_eq!(app.knocked_out, 1);
+        assert!(app.particles.particles.len() > 0);
+        // ...and both pickups together made the one noise.
+        assert_eq!(app.sounds, vec![Sound::Pickup]);
+        // Winning's only heard once, however long the victory lap.
+        app.change_state(LevelState::Complete { timer: 0.0 });
+        app.change_state(LevelState::Complete { timer: 0.5 });
+        assert_eq!(app.bus.pending(), &[GameEvent::StateChanged {
+            from: LevelState::Playing, to: LevelState::Complete { timer: 0.0 } }]);
+    }
+
+    #[test]
+    fn clicking_shoots_at_the_mouse() {
+        let mut app = App::new(settings());
+        app.players[0].facing = Facing::Right;
+        // Straight below the player, as the window sees it, wherever the
+        // camera's got to.
+        let (x, y) = app.player_centre();
+        let below = app.camera.to_screen(WorldPos::new(x, y + 100.0));
+        app.mouse_moved(below.x, below.y);
+        let (cx, cy) = app.cursor_world().unwrap().tuple();
+        assert!((cx - x).abs() < 1e-9 && (cy - y - 100.0).abs() < 1e-9);
+        handleKey(Mouse(mouse::Left), &mut app);
+        let shot = app.scene.of_kind(Kind::Shot)[0];
+        let before = app.scene.bounds(shot).unwrap();
+        app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        let after = app.scene.bounds(shot).unwrap();
+        assert!(after.y > before.y && (after.x - before.x).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tough_enemies_take_a_few_shots_and_bite_harder() {
+        let mut settings = settings();
+        settings.config.enemy_health = 2;
+        settings.config.enemy_damage = 2;
+        settings.levels[0].enemies = vec![[250.0, 55.0]];
+        let mut app = App::new(settings);
+        app.players[0].facing = Facing::Right;
+        app.fire();
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        // Still there after one, at half health...
+        let enemy = app.scene.of_kind(Kind::Enemy)[0];
+        assert_eq!(app.scene.healths[enemy.index].map(|h| h.points), Some(1));
+        app.fire();
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        // ...but not after two.
+        assert_eq!(app.scene.count(Kind::Enemy), 0);
+        // And one that catches us takes two points at once.
+        let health = app.health;
+        let (x, y) = app.player_centre();
+        app.spawn_enemy(x, y);
+        app.advance(SIM_DT);
+        assert_eq!(app.health, health - 2);
+    }
+
+    #[test]
+    fn walking_over_pickups_scores_points() {
+        let mut settings = settings();
+        settings.levels[0].collectibles = vec![[100.0, 55.0], [150.0, 55.0], [100.0, 400.0]];
+        let mut app = App::new(settings);
+        assert_eq!(app.entities.len(), 4);
+        // A second walking right takes us over the first two but nowhere
+        // near the third.
+        app.players[0].input.press(keyboard::Right);
+        for _ in 0..120 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!(app.score, 2);
+        assert_eq!(app.entities.len(), 2);
+    }
+
+    #[test]
+    fn every_player_enemy_and_pickup_is_a_game_object() {
+        let mut settings = settings();
+        settings.coop = true;
+        settings.levels[0].collectibles = vec![[100.0, 400.0], [150.0, 400.0]];
+        let mut app = App::new(settings);
+        enemy::spawn(&mut app.scene, 300.0, 300.0, [0.0; 4]);
+        // Both players, the enemy and both pickups, but not the wall, in
+        // the order they update, with the players drawn on top.
+        let layers: Vec<u32> = object::gather(&app).iter().map(|o| o.layer()).collect();
+        assert_eq!(layers, vec![2, 2, 1, 0, 0]);
+        // Player two walking over a pickup picks it up, on their own turn.
+        app.players[1].x = 95.0;
+        app.players[1].y = 395.0;
+        app.advance(SIM_DT);
+        assert_eq!((app.score, app.entities.len()), (1, 2));
+        assert_eq!(app.objects.len(), 5);
+        assert_eq!(object::gather(&app).len(), 4);
+    }
+
+    #[test]
+    fn f4_outlines_things_and_says_where_they_are() {
+        let mut settings = settings();
+        settings.levels[0].enemies = vec![[300.0, 300.0]];
+        let mut app = App::new(settings);
+        app.players[0].vx = 12.0;
+        let drawn = |app: &mut App| {
+            let mut r = Recorder::new();
+            app.render(&mut r, &Assets::none(), &mut Hud::none());
+            r.calls.len()
+        };
+        let plain = drawn(&mut app);
+        handleKey(Keyboard(keyboard::F4), &mut app);
+        // A grid line every 100 pixels across the 640x480 view, and four
+        // edges each for the wall, the enemy, the player and the goal.
+        assert_eq!(drawn(&mut app), plain + 7 + 5 + 4 * 4);
+        // The quadtree's made once a tick; one wall is one cell.
+        app.sort_walls();
+        assert_eq!(app.quadtree.cells().len(), 1);
+        assert_eq!(drawn(&mut app), plain + 7 + 5 + 5 * 4);
+        let labels = app.debug_labels();
+        assert_eq!(labels[0].2, "P1 (50, 50) v (12, 0)");
+        assert!(labels[1].2.starts_with("(300, 300) v"));
+        assert_eq!(app.pool_stats(), vec!["Scene: 1/1 slots", "Particles: 0/0 slots"]);
+        // The wall's in the enemy's way, so once it's worked out a way
+        // round, that's drawn too.
+        app.plan_paths();
+        assert_eq!(app.paths.all().len(), 1);
+        assert!(drawn(&mut app) > plain + 7 + 5 + 5 * 4);
+        handleKey(Keyboard(keyboard::F4), &mut app);
+        assert_eq!(drawn(&mut app), plain);
+    }
+
+    #[test]
+    fn the_background_moves_unless_that_would_distract() {
+        let mut app = App::new(settings());
+        app.paused = true;
+        app.update(&UpdateArgs { dt: 0.5 });
+        assert_eq!(app.background.time, 0.5);
+        let bg = app.config.palette().background;
+        let plain = |app: &mut App| {
+            let mut r = Recorder::new();
+            app.render(&mut r, &Assets::none(), &mut Hud::none());
+            r.rects_in(bg)
+        };
+        assert!(plain(&mut app).is_empty());
+        app.config.low_distraction = true;
+        assert_eq!(plain(&mut app), vec![[0.0, 0.0, 640.0, 480.0]]);
+    }
+
+    #[test]
+    fn a_level_bigger_than_the_screen_gets_a_minimap() {
+        let mut settings = settings();
+        settings.levels[0].enemies = vec![[300.0, 300.0]];
+        let mut app = App::new(settings);
+        let dot = faded(app.config.enemy_color, minimap::DOT_ALPHA);
+        let dots = |app: &mut App| {
+            let mut r = Recorder::new();
+            app.render(&mut r, &Assets::none(), &mut Hud::none());
+            r.rects_in(dot)
+        };
+        // The world's 1280x960, so the map's an eighth of the size, in the
+        // corner of the 640x480 view at (470, 350), and the enemy's dot
+        // goes on its middle, at (300, 300) in the world.
+        assert_eq!(dots(&mut app), vec![[505.5, 385.5, 4.0, 4.0]]);
+        app.config.minimap = false;
+        assert!(dots(&mut app).is_empty());
+    }
+
+    #[test]
+    fn f3_graphs_the_frames_in_the_corner() {
+        let mut app = App::new(settings());
+        app.frame_graph.update_took(0.001);
+        app.frame_graph.render_took(0.004);
+        app.frame_graph.render_took(0.030);
+        let drawn = |app: &mut App| {
+            let mut r = Recorder::new();
+            app.render(&mut r, &Assets::none(), &mut Hud::none());
+            r.calls.len()
+        };
+        let plain = drawn(&mut app);
+        handleKey(Keyboard(keyboard::F3), &mut app);
+        // The backing, two bars for each frame, and the budget line.
+        assert_eq!(drawn(&mut app), plain + 1 + 2 * 2 + 1);
+    }
+
+    #[test]
+    fn frame_stats_go_in_the_title_when_asked_for() {
+        let mut app = App::new(settings());
+        app.record_frame(0.25);
+        assert_eq!(app.title_text(), "jumpy");
+        handleKey(Keyboard(keyboard::F3), &mut app);
+        assert_eq!(app.title_text(), "jumpy - 4 fps, 250.0 ms avg, 250.0 ms p99");
+        // The summary only catches up every half a second.
+        app.record_frame(0.125);
+        assert_eq!(app.title_text(), "jumpy - 4 fps, 250.0 ms avg, 250.0 ms p99");
+        app.record_frame(0.5);
+        // (0.25 + 0.125 + 0.5) / 3 is a bit over 0.29 seconds a frame.
+        assert!(app.title_text().starts_with("jumpy - 3 fps"));
+    }
+
+    #[test]
+    fn escape_and_p_both_pause() {
+        let mut app = App::new(settings());
+        handleKey(Keyboard(keyboard::Escape), &mut app);
+        assert!(app.paused && !app.quit);
+        assert_eq!(app.title_text(), "jumpy - PAUSED - tick 0");
+        // Nothing moves while paused...
+        app.players[0].input.press(keyboard::Right);
+        app.update(&UpdateArgs { dt: 0.5 });
+        assert_eq!(app.players[0].x, 50.0);
+        // ...until it's unpaused, which the other key does just as well.
+        handleKey(Keyboard(keyboard::P), &mut app);
+        app.update(&UpdateArgs { dt: 0.5 });
+        assert!(app.players[0].x > 50.0);
+    }
+
+    #[test]
+    fn camera_follows_the_player() {
+        let mut app = App::new(settings());
+        // Starting in the top-left corner, the camera can't go any further
+        // up or left than the edge of the world.
+        assert_eq!((app.camera.x, app.camera.y), (0.0, 0.0));
+        app.players[0].input.press(keyboard::Down);
+        for _ in 0..480 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        // Four seconds at 200 pixels a second is 800 pixels down the world,
+        // and the camera's come some of the way with us.
+        assert!(app.camera.y > 0.0);
+        assert_eq!(app.camera.x, 0.0);
+        let (_, cy) = app.player_centre();
+        assert!(cy > app.camera.y && cy < app.camera.y + app.camera.height);
+    }
+
+    #[test]
+    fn zooming_keeps_the_player_in_place_on_screen() {
+        let mut app = App::new(settings());
+        app.players[0].x = 600.0;
+        app.players[0].y = 500.0;
+        app.snap_camera();
+        let on_screen = |app: &App| app.camera.to_screen(app.player_centre().into()).tuple();
+        let before = on_screen(&app);
+        app.scroll_zoom(2.0);
+        let after = on_screen(&app);
+        assert!((app.camera.zoom - 1.5625).abs() < 1e-12);
+        assert!((before.0 - after.0).abs() < 1e-9 && (before.1 - after.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn walking_off_the_edge_is_not_allowed() {
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Left);
+        app.players[0].input.press(keyboard::Up);
+        for _ in 0..120 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!((app.players[0].x, app.players[0].y), (0.0, 0.0));
+        assert_eq!((app.players[0].vx, app.players[0].vy), (0.0, 0.0));
+
+        // Puffing up in the corner pushes the whole square back on screen.
+        app.players[0].expand = 20.0;
+        app.advance(0.0);
+        assert_eq!(app.player_box().x, 0.0);
+        assert_eq!(app.player_box().y, 0.0);
+    }
+
+    #[test]
+    fn wrap_mode_comes_back_on_the_other_side() {
+        let mut app = App::new(settings());
+        app.world_mode = WorldMode::Wrap;
+        // Setting off at full speed keeps the sums simple.
+        app.config.acceleration = 0.0;
+        app.players[0].input.press(keyboard::Up);
+        // 50 pixels up to the top edge, then 50 more takes us past it and
+        // round to the bottom.
+        for _ in 0..60 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        let (_, height) = app.world_size();
+        assert!((app.players[0].y - (height - 50.0)).abs() < 1e-9);
+        assert_eq!(app.players[0].vy, -200.0);
+        // And the camera went with us, right down to the bottom of the world.
+        assert_eq!(app.camera.y, height - app.camera.height);
+    }
+
+    #[test]
+    fn levels_come_round_in_order() {
+        let mut second = Level::fallback();
+        second.name = "Second".to_string();
+        second.spawn = [200.0, 300.0];
+        second.walls = Vec::new();
+        second.collectibles = vec![[100.0, 100.0], [400.0, 100.0]];
+        let mut settings = settings();
+        settings.levels.push(second);
+
+        let mut app = App::new(settings);
+        assert_eq!(app.entities.len(), 1);
+        app.next_level();
+        assert_eq!(app.level_index, 1);
+        assert_eq!((app.players[0].x, app.players[0].y), (200.0, 300.0));
+        // Two pickups and no walls.
+        assert_eq!(app.entities.len(), 2);
+        assert!(app.obstacles().is_empty());
+        // And after the last level comes the first again.
+        app.next_level();
+        assert_eq!(app.level_index, 0);
+        assert_eq!((app.players[0].x, app.players[0].y), (50.0, 50.0));
+    }
+
+    #[test]
+    fn finishing_a_level_moves_on_to_the_next() {
+        let mut settings = settings();
+        settings.levels.push(Level::fallback());
+        let mut app = App::new(settings);
+        app.level_state = LevelState::Complete { timer: 0.0 };
+        for _ in 0..250 {
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!(app.level_index, 1);
+        assert_eq!(app.level_state, LevelState::Playing);
+    }
+
+    // Set both apps up the same way: walking right with a burst in the air,
+    // so there's something for the ticks to move about.
+    fn moving_app() -> App {
+        let mut app = App::new(settings());
+        app.players[0].input.press(keyboard::Right);
+        app.players[0].expand = 30.0;
+        app.burst(0);
+        app
+    }
+
+    #[test]
+    fn single_steps_match_normal_updates() {
+        let mut stepped = moving_app();
+        stepped.paused = true;
+        for _ in 0..20 {
+            stepped.step_requested = true;
+            // A fifth of a second of real time between presses, so the
+            // debounce never gets in the way.
+            stepped.update(&UpdateArgs { dt: 0.2 });
+        }
+
+        let mut played = moving_app();
+        for _ in 0..20 {
+            played.update(&UpdateArgs { dt: STEP_DT });
+        }
+
+        assert_eq!(stepped.ticks, 20);
+        assert_same_state(&stepped, &played);
+    }
+
+    #[test]
+    fn held_step_key_is_debounced() {
+        let mut app = moving_app();
+        app.paused = true;
+        // A key held for one second, repeating every update at 120 updates a
+        // second, only manages ten steps.
+        for _ in 0..120 {
+            app.step_requested = true;
+            app.update(&UpdateArgs { dt: 1.0 / 120.0 });
+        }
+        assert_eq!(app.ticks, 10);
+    }
+
+    #[test]
+    fn enemies_find_their_way_round_walls() {
+        // The fallback level's wall runs from (300, 100) to (305, 300),
+        // right between the two of them.
+        let chase_round = |path_seconds: f64| {
+            let mut settings = settings();
+            settings.config.enemy_path_seconds = path_seconds;
+            settings.config.enemy_damage = 0;
+            let mut app = App::new(settings);
+            app.players[0].x = 200.0;
+            app.players[0].y = 200.0;
+            let id = enemy::spawn(&mut app.scene, 400.0, 200.0, [0.0; 4]);
+            if path_seconds > 0.0 { app.plan_paths() }
+            let planned = app.paths.route(id).is_some();
+            for _ in 0..720 {
+                app.advance(SIM_DT);
+            }
+            (planned, app.scene.boxes(Kind::Enemy)[0])
+        };
+        let (planned, b) = chase_round(0.5);
+        assert!(planned);
+        assert!(b.right() < 300.0);
+        // Without, it's stuck leaning on the wall.
+        let (planned, b) = chase_round(0.0);
+        assert!(!planned);
+        assert!((b.x - 305.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn enemies_turn_up_every_so_often_if_asked() {
+        let mut settings = settings();
+        settings.config.enemy_spawn_seconds = 0.5;
+        let mut app = App::new(settings);
+        assert_eq!(app.scene.count(Kind::Enemy), 0);
+        // A second and a bit: two of them, away from the player, and all
+        // of them in the world.
+        for _ in 0..130 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.scene.count(Kind::Enemy), 2);
+        let (px, py) = app.player_centre();
+        let (width, height) = app.world_size();
+        for enemy in app.scene.boxes(Kind::Enemy) {
+            assert!((enemy.x - px).hypot(enemy.y - py) > 100.0);
+            assert!(enemy.x >= 0.0 && enemy.right() <= width);
+            assert!(enemy.y >= 0.0 && enemy.bottom() <= height);
+        }
+        // And a reset starts the count again.
+        app.reset();
+        app.advance(SIM_DT);
+        assert_eq!(app.scene.count(Kind::Enemy), 0);
+        // The spawning, and the path planning.
+        assert_eq!(app.timers.len(), 2);
+    }
+
+    #[test]
+    fn enemies_come_in_waves_with_a_breather_between() {
+        let mut settings = settings();
+        settings.config.wave_seconds = 10.0;
+        settings.config.wave_grace_seconds = 1.0;
+        settings.config.wave_size = 2;
+        settings.config.wave_growth = 1.0;
+        settings.config.enemy_speed = 0.0;
+        let mut app = App::new(settings);
+        app.scene.clear();
+        // Nothing for the first second, and then the first wave.
+        for _ in 0..110 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!((app.waves.number, app.scene.count(Kind::Enemy)), (0, 0));
+        for _ in 0..20 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!((app.waves.number, app.scene.count(Kind::Enemy)), (1, 2));
+        // Knocking them all out brings the next on a second later, not ten,
+        // one bigger, and a bit faster.
+        app.scene.clear();
+        for _ in 0..130 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!((app.waves.number, app.scene.count(Kind::Enemy)), (2, 3));
+        assert!((waves::speed_up(app.waves.number, app.config.wave_speed_growth) - 1.1).abs()
+                < 1e-9);
+        // And a reset starts them over.
+        app.reset();
+        assert_eq!(app.waves, Waves::new(1.0));
+    }
+
+    #[test]
+    fn expand_never_goes_negative() {
+        let mut player = Player::at(0.0, 0.0);
+        player.expand = 1.0;
+        shrink_expand(&mut player, 60.0, Easing::Linear, 1.0);
+        assert_eq!(player.expand, 0.0);
+        // And puffing up again part way down starts shrinking from there.
+        player.expand = 30.0;
+        shrink_expand(&mut player, 60.0, Easing::Linear, 0.25);
+        assert_eq!(player.expand, 15.0);
+        player.expand += 15.0;
+        shrink_expand(&mut player, 60.0, Easing::Linear, 0.25);
+        assert_eq!(player.expand, 15.0);
+        // Eased out, it goes quicker to begin with, and gets there at the
+        // same time.
+        player.expand = 30.0;
+        shrink_expand(&mut player, 60.0, Easing::EaseOut, 0.25);
+        assert_eq!(player.expand, 7.5);
+        shrink_expand(&mut player, 60.0, Easing::EaseOut, 0.25);
+        assert_eq!(player.expand, 0.0);
+    }
+
+    #[test]
+    fn the_player_squashes_and_stretches() {
+        let mut app = App::new(settings());
+        let still = player::squash_stretch(&app.players[0], 200.0, 0.25);
+        assert_eq!(still, (1.0, 1.0));
+        // Puffing up starts wider than tall, and then wobbles the other
+        // way, before settling down again.
+        app.trigger_expand(0);
+        let (wide, tall) = player::squash_stretch(&app.players[0], 200.0, 0.25);
+        assert!(wide > 1.2 && tall < 0.85);
+        let mut taller = false;
+        for _ in 0..80 {
+            app.advance(SIM_DT);
+            let (wide, tall) = player::squash_stretch(&app.players[0], 200.0, 0.25);
+            taller = taller || tall > wide;
+        }
+        assert!(taller);
+        assert!(app.players[0].squash.is_none());
+        // Going flat out down the screen, they're taller, but no bigger.
+        let mut going = Player::at(0.0, 0.0);
+        going.vy = 300.0;
+        let (wide, tall) = player::squash_stretch(&going, 200.0, 0.25);
+        assert_eq!(tall, 1.25);
+        assert!((wide * tall - 1.0).abs() < 1e-9);
+        // Turned a quarter of the way round, that's across the way they're
+        // drawn.
+        going.angle = PI / 2.0;
+        assert!(player::squash_stretch(&going, 200.0, 0.25).0 > 1.2);
+    }
+
+    fn coop_app() -> App {
+        let mut settings = settings();
+        settings.coop = true;
+        App::new(settings)
+    }
+
+    #[test]
+    fn coop_players_each_have_their_own_keys() {
+        let mut app = coop_app();
+        assert_eq!(app.players.len(), 2);
+        let offset = app.config.player_two_offset;
+        assert_eq!((app.players[1].x, app.players[1].y), (50.0 + offset, 50.0));
+
+        // S walks player two down, and leaves player one (and the world
+        // mode) alone.
+        handleKey(Keyboard(keyboard::S), &mut app);
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        assert!(app.players[1].y > 100.0);
+        assert_eq!((app.players[0].x, app.players[0].y), (50.0, 50.0));
+        handleKey(Keyboard(keyboard::W), &mut app);
+        assert_eq!(app.world_mode, WorldMode::Bounded);
+
+        // E puffs up player two; Space is still player one's.
+        handleKey(Keyboard(keyboard::E), &mut app);
+        assert_eq!((app.players[0].expand, app.players[1].expand), (0.0, app.config.expand_step));
+        handleKey(Keyboard(keyboard::Space), &mut app);
+        assert_eq!(app.players[0].expand, app.config.expand_step);
+
+        // And letting go of S stops only player two's walking.
+        handleRelease(Keyboard(keyboard::S), &mut app);
+        assert!(!app.players[1].input.down);
+    }
+
+    #[test]
+    fn either_player_can_pick_things_up() {
+        let mut app = coop_app();
+        app.entities.clear();
+        app.entities.push(Box::new(Pickup::at(200.0, 400.0, [1.0; 4])));
+        app.players[1].x = 195.0;
+        app.players[1].y = 395.0;
+        app.advance(SIM_DT);
+        assert_eq!(app.score, 1);
+    }
+
+    #[test]
+    fn power_ups_give_powers_for_a_while_instead_of_points() {
+        let mut app = App::new(settings());
+        app.entities.clear();
+        app.entities.push(Box::new(PowerUp::at(55.0, 55.0, Effect::Size, [1.0; 4])));
+        // Picked up in one tick, and grown by the next.
+        app.advance(SIM_DT);
+        app.advance(SIM_DT);
+        assert_eq!((app.score, app.entities.len()), (0, 0));
+        assert_eq!(app.player_box().w, 10.0 * app.config.size_boost);
+        assert_eq!(app.player_color(0), app.config.size_color);
+        // Until it wears off.
+        for _ in 0..(app.config.power_up_seconds / SIM_DT) as usize {
+            app.advance(SIM_DT);
+        }
+        assert!(!app.has_power(Effect::Size));
+        assert_eq!(app.player_box().w, 10.0);
+        assert_eq!(app.player_color(0), app.config.player_color);
+    }
+
+    #[test]
+    fn speed_and_invulnerability_do_what_they_say() {
+        let mut app = App::new(settings());
+        let walking = app.move_speed();
+        powerup::grant(&mut app.effects, Effect::Speed, 5.0);
+        assert_eq!(app.move_speed(), walking * app.config.speed_boost);
+        // An enemy sitting right on top of us can't do a thing.
+        let (x, y) = (app.players[0].x, app.players[0].y);
+        enemy::spawn(&mut app.scene, x, y, [0.0; 4]);
+        powerup::grant(&mut app.effects, Effect::Invulnerable, 5.0);
+        let health = app.health;
+        for _ in 0..10 {
+            app.advance(SIM_DT);
+        }
+        assert_eq!(app.health, health);
+    }
+
+    #[test]
+    fn the_camera_keeps_both_players_in_view() {
+        let mut app = coop_app();
+        app.players[0].x = 500.0;
+        app.players[1].x = 700.0;
+        app.players[1].y = app.players[0].y;
+        app.snap_camera();
+        let middle = app.camera.x + app.camera.view_width() / 2.0;
+        assert!((middle - 605.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_console_runs_commands_typed_into_it() {
+        let mut game = Game::new(settings());
+        game.start();
+        let type_line = |game: &mut Game, line: &str| {
+            game.handle_text(line);
+            game.handle_key(Keyboard(keyboard::Return));
+        };
+        // Typing does nothing until the console's open.
+        game.handle_text("tp 1 1");
+        game.handle_key(Keyboard(keyboard::Backquote));
+        game.handle_text("`");
+        type_line(&mut game, "tp 100 200");
+        type_line(&mut game, "set expand 50");
+        type_line(&mut game, "set move_speed 99");
+        type_line(&mut game, "spawn enemy");
+        // X types an x; it doesn't quit.
+        game.handle_key(Keyboard(keyboard::X));
+        type_line(&mut game, "fly");
+        let path = ::std::env::temp_dir().join(format!("jumpy-console-{}.json",
+                                                        ::std::process::id()));
+        type_line(&mut game, &format!("save {}", path.display()));
+        let app = match game.state {
+            GameState::Playing(ref mut app) => app,
+            _ => panic!("expected to be playing"),
+        };
+        assert!(!app.quit);
+        assert_eq!((app.players[0].x, app.players[0].y, app.players[0].expand),
+                   (100.0, 200.0, 50.0));
+        assert_eq!(app.config.move_speed, 99.0);
+        assert_eq!(app.scene.of_kind(Kind::Enemy).len(), 1);
+        assert!(app.console.output.contains(&"no such command: fly".to_string()));
+        assert_eq!(::save::load(&path).unwrap().expand, 50.0);
+        ::std::fs::remove_file(&path).unwrap();
+
+        // While it's open, nothing moves; closed, the game carries on.
+        let ticks = app.ticks;
+        app.update(&UpdateArgs { dt: 0.1 });
+        assert_eq!(app.ticks, ticks);
+        handleKey(Keyboard(keyboard::Escape), app);
+        assert!(!app.console.open);
+        app.update(&UpdateArgs { dt: 0.1 });
+        assert!(app.ticks > ticks);
+    }
+
+    #[test]
+    fn f10_reloads_the_config_and_the_level_in_place() {
+        use std::env;
+        use std::fs;
+        let dir = env::temp_dir().join(format!("jumpy-reload-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (level_path, config_path) = (dir.join("level1.json"), dir.join("config.json"));
+        let layout = |walls: &str| format!(r#"{{
+            "name": "Reloading", "world_width": 800.0, "world_height": 600.0,
+            "spawn": [50.0, 50.0], "goal": [700.0, 500.0, 40.0, 40.0],
+            "walls": [{}]
+        }}"#, walls);
+        fs::write(&level_path, layout("")).unwrap();
+        fs::write(&config_path, r#"{ "move_speed": 10.0 }"#).unwrap();
+        let mut settings = settings();
+        settings.levels = ::level::load_from(&level_path);
+        settings.levels_path = Some(level_path.clone());
+        settings.config_path = config_path.clone();
+        let mut app = App::new(settings);
+        app.players[0].x = 120.0;
+        let entities = app.entities.len();
+
+        fs::write(&level_path, layout("[400.0, 0.0, 10.0, 600.0]")).unwrap();
+        fs::write(&config_path, r#"{ "move_speed": 99.0 }"#).unwrap();
+        app.reload_files();
+        assert_eq!(app.config.move_speed, 99.0);
+        assert_eq!(app.entities.len(), entities + 1);
+        // The player's still where they were.
+        assert_eq!(app.players[0].x, 120.0);
+
+        // A level that won't load leaves the one we've got alone.
+        fs::write(&level_path, "{ oops").unwrap();
+        app.reload_levels();
+        assert_eq!(app.entities.len(), entities + 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spectators_see_everything() {
+        let mut app = App::new(settings());
+        app.advance(SIM_DT);
+        let frame = app.frame();
+        assert_eq!(frame.tick, 1);
+        assert_eq!(frame.state, "Playing");
+        assert_eq!(frame.players.len(), 1);
+        let b = app.player_box();
+        assert_eq!(frame.players[0].bounds, [b.x, b.y, b.w, b.h]);
+        assert!(frame.walls.iter().any(|w| w[0] == 300.0));
+        assert_eq!(frame.world, [1280.0, 960.0]);
+    }
+
+    #[test]
+    fn the_state_is_dumped_every_so_often() {
+        use std::env;
+        use std::fs;
+        use dump::{self, Dump};
+        let path = env::temp_dir().join(format!("jumpy-dump-{}.jsonl", ::std::process::id()));
+        let out = fs::File::create(&path).unwrap();
+        let mut settings = settings();
+        settings.dump = Some(Rc::new(RefCell::new(Dump::new(Box::new(out), 4))));
+        let mut app = App::new(settings);
+        // Ten ticks is tick 4 and tick 8.
+        app.update(&UpdateArgs { dt: SIM_DT * 10.0 + 1e-6 });
+        let written = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let lines: Vec<_> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("{\"frame\":{\"tick\":8,"), "{}", lines[1]);
+        assert!(lines[1].contains("\"save\":{\"level\":0,"));
+        assert_eq!(app.dump.as_ref().unwrap().borrow().written, 2);
+        assert_eq!(dump::Destination::parse("-"), dump::Destination::Stdout);
+    }
+
+    #[cfg(feature = "lua")]
+    #[test]
+    fn lua_scripts_move_players_and_make_enemies() {
+        use std::env;
+        use std::fs;
+        use lua::Lua;
+        let path = env::temp_dir().join(format!("jumpy-app-{}.lua", ::std::process::id()));
+        fs::write(&path, "
+            function on_update(entity, dt)
+                if entity.kind == 'player' then set_position(entity, 200, 50) end
+            end
+            function on_key(action)
+                if action == 'K' then spawn('enemy', 600, 600) end
+            end").unwrap();
+        let lua = Lua::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let mut settings = settings();
+        settings.lua = Some(Rc::new(RefCell::new(lua)));
+        let mut app = App::new(settings);
+        let enemies = app.scene.count(Kind::Enemy);
+        handleKey(Keyboard(keyboard::K), &mut app);
+        app.advance(SIM_DT);
+        assert_eq!((app.players[0].x, app.players[0].y), (200.0, 50.0));
+        assert_eq!(app.scene.count(Kind::Enemy), enemies + 1);
+    }
+
+    #[test]
+    fn networked_players_see_each_other() {
+        let host = Link::host(0).unwrap();
+        let port = host.local_addr().unwrap().port();
+        let client = Link::connect(&format!("127.0.0.1:{}", port)).unwrap();
+        let linked = |link: Link| {
+            let mut settings = settings();
+            settings.link = Some(Rc::new(RefCell::new(link)));
+            App::new(settings)
+        };
+        let (mut here, mut there) = (linked(host), linked(client));
+        assert_eq!((here.players.len(), here.local_players()), (2, 1));
+
+        // The far end moves its player and says so...
+        there.players[0].x = 200.0;
+        there.players[0].expand = 12.0;
+        there.advance(SIM_DT);
+        // ...and a moment later, that's where our player two is.
+        for _ in 0..100 {
+            here.advance(SIM_DT);
+            if here.players[1].x == 200.0 { break }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!((here.players[1].x, here.players[1].expand), (200.0, 12.0));
+        // Our own player, meanwhile, stayed where it was.
+        assert_eq!(here.players[0].x, 50.0);
+    }
+
+    #[test]
+    fn everyone_gets_drawn_in_their_own_color() {
+        let mut app = coop_app();
+        let mut r = Recorder::new();
+        app.render(&mut r, &Assets::none(), &mut Hud::none());
+        assert_eq!(r.rects_in(app.config.player_color).len(), 1);
+        assert_eq!(r.rects_in(app.config.player_two_color).len(), 1);
+    }
+
+    #[test]
+    fn the_config_says_how_big_and_what_shape_the_player_is() {
+        let mut settings = settings();
+        settings.config.player_size = 20.0;
+        settings.config.player_shape = Shape::Hollow;
+        let mut app = App::new(settings);
+        assert_eq!(app.player_box(), Aabb::new(50.0, 50.0, 20.0, 20.0));
+        let mut r = Recorder::new();
+        app.render(&mut r, &Assets::none(), &mut Hud::none());
+        // Hollow is four edges, each a fifth of the way in.
+        let edges = r.rects_in(app.config.player_color);
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges[0][3], 4.0);
+    }
+
+    #[test]
+    fn tank_controls_go_the_way_the_player_points() {
+        let mut settings = settings();
+        settings.config.tank_controls = true;
+        let mut app = App::new(settings);
+        // Half a second of E at 180 degrees a second is a quarter turn.
+        handleKey(Keyboard(keyboard::E), &mut app);
+        for _ in 0..60 {
+            app.advance(SIM_DT);
+        }
+        handleRelease(Keyboard(keyboard::E), &mut app);
+        assert!((app.players[0].angle - PI / 2.0).abs() < 1e-9);
+        assert_eq!(app.players[0].facing, Facing::Right);
+        // So Up goes right, and not up at all.
+        handleKey(Keyboard(keyboard::Up), &mut app);
+        for _ in 0..30 {
+            app.advance(SIM_DT);
+        }
+        assert!(app.players[0].x > 60.0);
+        assert!((app.players[0].y - 50.0).abs() < 1e-9);
+        // Without tank controls, E still turns, but Up is up.
+        app.config.tank_controls = false;
+        app.advance(SIM_DT);
+        assert!(app.players[0].y < 50.0);
+    }
+
+    #[test]
+    fn round_players_only_get_caught_by_what_touches_the_circle() {
+        let mut settings = settings();
+        settings.config.player_shape = Shape::Circle;
+        settings.config.enemy_speed = 0.0;
+        let mut app = App::new(settings);
+        let mut r = Recorder::new();
+        app.render(&mut r, &Assets::none(), &mut Hud::none());
+        let color = app.config.player_color;
+        // Drawn round its middle, which the context has been moved to.
+        assert!(r.calls.contains(&Call::Ellipse([-5.0, -5.0, 10.0, 10.0], color)));
+        // The enemy's box overlaps the corner of the player's, but the
+        // circle in it is 5.66 from the enemy's corner, with a radius of 5.
+        app.spawn_enemy(67.0, 67.0);
+        let enemy = app.scene.of_kind(Kind::Enemy)[0];
+        let health = app.health;
+        app.update_enemy(enemy, SIM_DT);
+        assert_eq!(app.health, health);
+        // A square player would be caught.
+        app.config.player_shape = Shape::Square;
+        app.update_enemy(enemy, SIM_DT);
+        assert!(app.health < health);
+    }
+}
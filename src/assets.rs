@@ -0,0 +1,406 @@
+/*
+ * Pictures. For now that's just the player, who can be drawn from an
+ * animated sprite sheet (player_sheet.png, see animation.rs for the layout),
+ * a single still sprite (player.png), or - if there's neither - a plain red
+ * square. They're all optional; the game's perfectly playable without them,
+ * so if a file isn't there we just say so and carry on.
+ *
+ * Then there's the atlas, for everything else: one picture (atlas.png)
+ * with lots of little ones packed into it, and a list of where each of
+ * them is (atlas.json), by name:
+ *
+ *     { "enemy": [0, 0, 16, 16], "pickup": [16, 0, 12, 12], "wall": [0, 16, 32, 32] }
+ *
+ * Each is [x, y, w, h] in the picture's pixels. A thing with a picture in
+ * the atlas gets it, stretched over its box, instead of its plain shape:
+ * "player" for player one (when there's no player.png), "enemy", "shot",
+ * "wall", "pickup", "goal", and the power-ups by their effect, "speed",
+ * "size" and "invulnerable". Anything it hasn't got a picture for is the
+ * same shape it always was. One texture for all of it means the graphics
+ * card isn't forever swapping one picture for another to draw the next
+ * thing along, which matters once there are a lot of them.
+ *
+ * Textures live on the graphics card, which means loading one needs OpenGL
+ * to be up and running. That's why main loads these after making the
+ * window, rather than App::new doing it, and why tests (which have no
+ * window) get Assets::none().
+ *
+ * Assets is also the asset manager: the font, the sounds, the music and
+ * the levels are asked for through here as well, rather than each part of
+ * the game going to the disk for them itself. That way everything that
+ * wasn't there is noted down in one place, in `missing`, and report says
+ * so in one go once the game's started, instead of it coming out a line
+ * at a time from all over. Nothing's needed, so nothing missing stops the
+ * game; it goes without.
+ *
+ * Pictures are kept in a Cache, by path, and what the rest of the game
+ * holds on to is a Handle: a number saying which one, that's cheap to copy
+ * about and can't go stale the way a reference would. Asking for a picture
+ * that's already loaded hands back the one we've got. A new window is a
+ * new OpenGL context, which can't use the old one's textures, so then the
+ * pictures are loaded all over again (see load_pictures). The font, the
+ * sounds and the music belong to whatever plays them, so they're handed
+ * over, loaded, rather than kept; and the levels are read fresh each time
+ * they're asked for, since the point of asking again (trying again after a
+ * game over, say) is to pick up any changes. App's own reloading, when a
+ * level file changes under it, goes straight to level.rs for the same
+ * reason.
+ */
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use graphics::{Context, ImageSize, Transformed};
+use opengl_graphics::{Texture, TextureSettings};
+use serde_json;
+
+use audio::{Audio, AudioSettings, Music, Sound};
+use config::Config;
+use hud::{self, Hud};
+use level::{self, Level};
+use locale::{self, Locale};
+use renderer::Renderer;
+use text::DEFAULT_FONT;
+
+// Which of a Cache's things.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handle(usize);
+
+/*
+ * Things loaded from files, each loaded once. A file that wouldn't load
+ * still gets a Handle, with nothing behind it, so it isn't tried again
+ * every time something asks.
+ */
+pub struct Cache<T> {
+    paths: Vec<PathBuf>,
+    items: Vec<Option<T>>,
+}
+
+impl<T> Cache<T> {
+    pub fn new() -> Cache<T> {
+        Cache { paths: Vec::new(), items: Vec::new() }
+    }
+
+    // The Handle for `path`, loading it with `load` if it isn't loaded
+    // already.
+    pub fn load<F>(&mut self, path: &Path, load: F) -> Handle
+        where F: FnOnce(&Path) -> Option<T>
+    {
+        if let Some(i) = self.paths.iter().position(|p| p == path) {
+            return Handle(i);
+        }
+        self.paths.push(path.to_path_buf());
+        self.items.push(load(path));
+        Handle(self.items.len() - 1)
+    }
+
+    // What's behind `handle`, if it loaded.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.items.get(handle.0).and_then(|item| item.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+pub struct Assets {
+    textures: Cache<Texture>,
+    player: Option<Handle>,
+    player_sheet: Option<Handle>,
+    pub atlas: Option<Atlas>,
+    // Every file asked for that wasn't there, in the order they were
+    // asked for.
+    pub missing: Vec<PathBuf>,
+}
+
+impl Assets {
+    pub fn none() -> Assets {
+        Assets { textures: Cache::new(), player: None, player_sheet: None, atlas: None,
+                 missing: Vec::new() }
+    }
+
+    // A manager with the pictures in `dir` loaded. Missing or broken files
+    // are a warning, not an error.
+    pub fn load(dir: &Path) -> Assets {
+        let mut assets = Assets::none();
+        assets.load_pictures(dir);
+        assets
+    }
+
+    // (Re)load the pictures in `dir`, into whatever OpenGL context there
+    // is now. Anything loaded for an older one is let go of.
+    pub fn load_pictures(&mut self, dir: &Path) {
+        self.textures = Cache::new();
+        self.player = self.picture(&dir.join("player.png"));
+        self.player_sheet = self.picture(&dir.join("player_sheet.png"));
+        self.atlas = self.load_atlas(dir);
+    }
+
+    // Whether `path` is there to load, noting it down if not.
+    fn found(&mut self, path: &Path) -> bool {
+        let there = path.exists();
+        if !there && !self.missing.iter().any(|p| p == path) {
+            self.missing.push(path.to_path_buf());
+        }
+        there
+    }
+
+    // The picture at `path`, if it's there and loads, as a Handle to it.
+    pub fn picture(&mut self, path: &Path) -> Option<Handle> {
+        if !self.found(path) { return None }
+        let handle = self.textures.load(path, load_texture);
+        self.textures.get(handle).map(|_| handle)
+    }
+
+    pub fn texture(&self, handle: Handle) -> Option<&Texture> {
+        self.textures.get(handle)
+    }
+
+    pub fn player(&self) -> Option<&Texture> {
+        self.player.and_then(|h| self.texture(h))
+    }
+
+    pub fn player_sheet(&self) -> Option<&Texture> {
+        self.player_sheet.and_then(|h| self.texture(h))
+    }
+
+    // Register the font at `path` with `hud`, as `name`. With no font
+    // there's no writing in it, but the game's the same.
+    pub fn font(&mut self, hud: &mut Hud, name: &str, path: &Path) {
+        if !self.found(path) { return }
+        if let Some(glyphs) = hud::load_font(path) {
+            hud.register(name, glyphs);
+        }
+    }
+
+    // Every font `config` names: font_path as the default one, and the
+    // rest by their own names.
+    pub fn fonts(&mut self, config: &Config) -> Hud {
+        let mut hud = Hud::none();
+        self.font(&mut hud, DEFAULT_FONT, Path::new(&config.font_path));
+        for (name, path) in config.fonts.iter() {
+            self.font(&mut hud, name, Path::new(path));
+        }
+        hud
+    }
+
+    // The sound effects in `dir`. Each one that's missing is just silent.
+    pub fn sounds(&mut self, dir: &Path) -> Audio {
+        for &sound in Sound::all().iter() {
+            self.found(&dir.join(sound.file_name()));
+        }
+        Audio::load(dir)
+    }
+
+    // The music `settings` names.
+    pub fn music(&mut self, settings: &AudioSettings) -> Music {
+        self.found(Path::new(&settings.menu_music));
+        self.found(Path::new(&settings.game_music));
+        Music::load(settings)
+    }
+
+    // The level at `path`. One that's been asked for by name is an error
+    // if it isn't there, rather than something to go without.
+    pub fn level(&mut self, path: &Path) -> Result<Level, String> {
+        self.found(path);
+        level::load(path)
+    }
+
+    // Everything at `path` (see level::load_from).
+    pub fn levels(&mut self, path: &Path) -> Vec<Level> {
+        if !self.found(path) { return Vec::new() }
+        level::load_from(path)
+    }
+
+    // The words for `language`. One that won't load is a warning, and
+    // English instead.
+    pub fn locale(&mut self, language: &str) -> Locale {
+        let dir = locale::default_dir();
+        if language != "en" {
+            self.found(&locale::path(&dir, language));
+        }
+        match locale::load(&dir, language) {
+            Ok(locale) => {
+                let missing = locale.missing();
+                if !missing.is_empty() {
+                    warn!("{} is missing {} strings, so they're in English: {}", language,
+                          missing.len(), missing.join(", "));
+                }
+                locale
+            }
+            Err(e) => {
+                warn!("{}, so the game's in English", e);
+                Locale::english()
+            }
+        }
+    }
+
+    // Say what wasn't there, all in one go. It's only info, since none of
+    // it ships with the game.
+    pub fn report(&self) {
+        if self.missing.is_empty() {
+            info!("Found every asset");
+            return;
+        }
+        let names: Vec<String> = self.missing.iter().map(|p| p.display().to_string()).collect();
+        info!("Going without {} missing files: {}", names.len(), names.join(", "));
+    }
+
+    // Whether there's an atlas with a picture called `name` in it.
+    pub fn has_sprite(&self, name: &str) -> bool {
+        self.atlas.as_ref().map_or(false, |atlas| atlas.regions.contains_key(name))
+    }
+
+    // The atlas's `name` picture over the [x, y, w, h] box `b`, if there's
+    // an atlas with one. Whether it got drawn, so the caller knows to draw
+    // the usual shape if not.
+    // The region's scaled to fill `b` whatever its own size, the way
+    // player.png is.
+    pub fn sprite(&self, name: &str, b: [f64; 4], context: &Context, r: &mut dyn Renderer) -> bool {
+        let atlas = match self.atlas {
+            Some(ref atlas) => atlas,
+            None => return false,
+        };
+        let (region, texture) = match (atlas.regions.get(name), self.texture(atlas.texture)) {
+            (Some(&region), Some(texture)) => (region, texture),
+            _ => return false,
+        };
+        let scaled = context.trans(b[0], b[1])
+                            .scale(b[2] / region[2] as f64, b[3] / region[3] as f64);
+        r.image(&scaled, texture, Some(region));
+        true
+    }
+
+    /*
+     * atlas.png and atlas.json, if they're both there and they both load.
+     * A region poking out past the picture is a warning; it still gets
+     * drawn, but whatever's past the edge will be garbage.
+     */
+    fn load_atlas(&mut self, dir: &Path) -> Option<Atlas> {
+        let path = dir.join("atlas.json");
+        if !self.found(&path) { return None }
+        let mut contents = String::new();
+        let regions = File::open(&path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| e.to_string())
+            .and_then(|_| parse_regions(&contents));
+        let regions = match regions {
+            Ok(regions) => regions,
+            Err(e) => {
+                warn!("Couldn't read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let texture = self.picture(&dir.join("atlas.png"))?;
+        if let Some((w, h)) = self.texture(texture).map(|t| t.get_size()) {
+            for (name, r) in regions.iter() {
+                if r[0] < 0 || r[1] < 0 || (r[0] + r[2]) as u32 > w || (r[1] + r[3]) as u32 > h {
+                    warn!("{} in {} goes off the edge of the picture", name, path.display());
+                }
+            }
+        }
+        info!("{} pictures in the atlas", regions.len());
+        Some(Atlas { texture: texture, regions: regions })
+    }
+}
+
+// Where each of the atlas's pictures is in it, by name.
+pub type Regions = BTreeMap<String, [i32; 4]>;
+
+pub struct Atlas {
+    pub texture: Handle,
+    pub regions: Regions,
+}
+
+/*
+ * Read the regions from atlas.json's `json`. A region has to have some
+ * size to it, or there'd be nothing to stretch over the box (and a
+ * division by nothing in the stretching).
+ */
+pub fn parse_regions(json: &str) -> Result<Regions, String> {
+    let regions: Regions = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    match regions.iter().find(|&(_, r)| r[2] <= 0 || r[3] <= 0) {
+        Some((name, _)) => Err(format!("{} has no size", name)),
+        None => Ok(regions),
+    }
+}
+
+// Where the assets live, next to config.json.
+pub fn default_dir() -> PathBuf {
+    PathBuf::from("assets")
+}
+
+/*
+ * The manager checks the file is there before handing it to
+ * Texture::from_path. It would tell us itself, but this way a missing file
+ * (the usual case, since no sprite ships with the game) never goes near the
+ * graphics card.
+ */
+fn load_texture(path: &Path) -> Option<Texture> {
+    match Texture::from_path(path, &TextureSettings::new()) {
+        Ok(texture) => {
+            info!("Loaded {}", path.display());
+            Some(texture)
+        }
+        Err(e) => {
+            warn!("Couldn't load {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn missing_files_fall_back_to_nothing() {
+        let mut assets = Assets::load(Path::new("no-such-assets-dir"));
+        assert!(assets.player().is_none());
+        assert!(assets.player_sheet().is_none());
+        assert!(assets.atlas.is_none());
+        let mut config = Config::default();
+        config.font_path = "no-such-assets-dir/font.ttf".to_string();
+        assert!(!assets.fonts(&config).has_font(DEFAULT_FONT));
+        assert!(assets.levels(Path::new("no-such-levels")).is_empty());
+        // Each is noted down once, however often it's asked for.
+        assets.load_pictures(Path::new("no-such-assets-dir"));
+        assert_eq!(assets.missing, vec![PathBuf::from("no-such-assets-dir/player.png"),
+                                        PathBuf::from("no-such-assets-dir/player_sheet.png"),
+                                        PathBuf::from("no-such-assets-dir/atlas.json"),
+                                        PathBuf::from("no-such-assets-dir/font.ttf"),
+                                        PathBuf::from("no-such-levels")]);
+    }
+
+    #[test]
+    fn caches_load_each_file_once() {
+        let mut cache = Cache::new();
+        let mut loads = 0;
+        let a = cache.load(Path::new("a"), |_| { loads += 1; Some(1) });
+        let b = cache.load(Path::new("b"), |_| None);
+        assert_eq!(cache.load(Path::new("a"), |_| { loads += 1; Some(2) }), a);
+        assert_eq!(loads, 1);
+        assert_eq!(cache.get(a), Some(&1));
+        // One that didn't load has a Handle too, but nothing behind it.
+        assert!(a != b && cache.get(b).is_none());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn reads_the_atlas_regions() {
+        let regions = parse_regions(r#"{ "enemy": [0, 0, 16, 16], "wall": [16, 0, 8, 4] }"#)
+            .unwrap();
+        assert_eq!(regions.get("wall"), Some(&[16, 0, 8, 4]));
+        assert_eq!(regions.len(), 2);
+        assert_eq!(parse_regions(r#"{ "goal": [0, 0, 0, 16] }"#),
+                   Err("goal has no size".to_string()));
+        assert!(parse_regions(r#"{ "goal": [0, 0] }"#).is_err());
+    }
+}
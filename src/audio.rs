@@ -0,0 +1,387 @@
+/*
+ * Sound effects. There's a short list of things that make a noise - moving
+ * off, expanding, picking something up, bumping into things - and each one
+ * is a .wav file in assets/sounds/. Like the pictures, they're all
+ * optional: a sound that isn't there just doesn't play.
+ *
+ * The game itself never plays anything. App only notes down which sounds
+ * it wants (see App::play), and Game plays them once the update's done.
+ * That keeps the game the same whether or not there's a sound card, which
+ * matters for the tests and for headless runs, and it means a replay asks
+ * for exactly the same sounds as the game it was recorded from.
+ *
+ * There's music too, one track for the menus and one for playing, which
+ * fades from one to the other when you start a game or it ends. See Music,
+ * further down.
+ *
+ * The playing is done by the ears crate (a thin layer over OpenAL), behind
+ * the "sound" cargo feature. Build with --no-default-features --features
+ * sdl2 and you get a game with no sound at all, and no OpenAL to install.
+ */
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "sound")]
+use ears::{self, AudioController};
+
+use app::App;
+use assets;
+use bus::GameEvent;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    Move,
+    Expand,
+    Pickup,
+    Collision,
+}
+
+impl Sound {
+    pub fn all() -> [Sound; 4] {
+        [Sound::Move, Sound::Expand, Sound::Pickup, Sound::Collision]
+    }
+
+    // The file in assets/sounds/ each one is played from.
+    pub fn file_name(&self) -> &'static str {
+        match *self {
+            Sound::Move => "move.wav",
+            Sound::Expand => "expand.wav",
+            Sound::Pickup => "pickup.wav",
+            Sound::Collision => "collision.wav",
+        }
+    }
+}
+
+// The sounds that go with what's happened, off App's bus (see bus.rs).
+// Picking up two things in the same tick sounds like picking up one.
+pub fn hear(event: &GameEvent, app: &mut App) {
+    match *event {
+        GameEvent::PickupCollected { .. } if !app.sounds.contains(&Sound::Pickup) => {
+            app.play(Sound::Pickup);
+        }
+        _ => {}
+    }
+}
+
+/*
+ * How loud. These go in the config with everything else, so they can be
+ * changed while the game's running; M flips muted without going near the
+ * file. The volumes go from 0 (silent) to 1 (as loud as the file is), and
+ * music_fade is how many seconds one track takes to fade into the other.
+ * The music files are only opened at startup, so changing which ones
+ * needs a restart.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub muted: bool,
+    pub volume: f32,
+    pub music_volume: f32,
+    pub music_fade: f64,
+    pub menu_music: String,
+    pub game_music: String,
+}
+
+impl Default for AudioSettings {
+    fn default() -> AudioSettings {
+        AudioSettings {
+            muted: false,
+            volume: 0.8,
+            music_volume: 0.5,
+            music_fade: 1.5,
+            menu_music: "assets/music/menu.ogg".to_string(),
+            game_music: "assets/music/game.ogg".to_string(),
+        }
+    }
+}
+
+/*
+ * A loaded sound, ready to play. Without the sound feature there's no such
+ * thing, so Clip is an enum with nothing in it: it can be named, but never
+ * made, and the compiler knows every list of them is empty.
+ */
+#[cfg(feature = "sound")]
+type Clip = ears::Sound;
+#[cfg(not(feature = "sound"))]
+enum Clip {}
+
+pub struct Audio {
+    clips: Vec<(Sound, Clip)>,
+}
+
+impl Audio {
+    pub fn none() -> Audio {
+        Audio { clips: Vec::new() }
+    }
+
+    // Load whichever of the sounds are in `dir`. Missing or broken files
+    // are left out, with a line in the log to say so.
+    pub fn load(dir: &Path) -> Audio {
+        let mut clips = Vec::new();
+        for &sound in Sound::all().iter() {
+            if let Some(clip) = load_clip(&dir.join(sound.file_name())) {
+                clips.push((sound, clip));
+            }
+        }
+        Audio { clips: clips }
+    }
+
+    // Play `sound` at the volume `settings` says, unless it says not to.
+    pub fn play(&mut self, sound: Sound, settings: &AudioSettings) {
+        if settings.muted { return }
+        let volume = settings.volume.max(0.0).min(1.0);
+        for &mut (s, ref mut clip) in self.clips.iter_mut() {
+            if s == sound {
+                play_clip(clip, volume);
+            }
+        }
+    }
+}
+
+// Where the sounds live.
+pub fn default_dir() -> PathBuf {
+    assets::default_dir().join("sounds")
+}
+
+#[cfg(feature = "sound")]
+fn load_clip(path: &Path) -> Option<Clip> {
+    if !path.exists() {
+        info!("No {}, so that one's silent", path.display());
+        return None;
+    }
+    match path.to_str().and_then(|p| ears::Sound::new(p)) {
+        Some(clip) => {
+            info!("Loaded {}", path.display());
+            Some(clip)
+        }
+        None => {
+            warn!("Couldn't load {}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "sound"))]
+fn load_clip(_: &Path) -> Option<Clip> {
+    None
+}
+
+// Playing a sound that's still going starts it again from the top, which
+// for short effects is what you want.
+#[cfg(feature = "sound")]
+fn play_clip(clip: &mut Clip, volume: f32) {
+    clip.set_volume(volume);
+    clip.play();
+}
+
+#[cfg(not(feature = "sound"))]
+fn play_clip(clip: &mut Clip, _: f32) {
+    match *clip {}
+}
+
+/*
+ * Music. The menus have one track and playing has another, and rather than
+ * cutting from one to the other we fade: the old one gets quieter while the
+ * new one gets louder, over music_fade seconds. Each track plays on its own
+ * channel, which keeps hold of the stream (music is played straight from
+ * the file a bit at a time, rather than loaded all at once like the sound
+ * effects) and of how far it's faded in.
+ * A channel that's faded right out gets stopped, so coming back to it
+ * starts the track from the top. One that's faded in loops forever.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Track {
+    Menu,
+    Game,
+}
+
+#[cfg(feature = "sound")]
+type Stream = ears::Music;
+#[cfg(not(feature = "sound"))]
+enum Stream {}
+
+pub struct Channel {
+    pub track: Track,
+    // How far faded in, from 0 (silent) to 1 (at music_volume).
+    pub gain: f64,
+    stream: Option<Stream>,
+    playing: bool,
+}
+
+impl Channel {
+    fn new(track: Track, stream: Option<Stream>) -> Channel {
+        Channel { track: track, gain: 0.0, stream: stream, playing: false }
+    }
+}
+
+pub struct Music {
+    pub channels: Vec<Channel>,
+    // The track that ought to be playing, if any.
+    current: Option<Track>,
+}
+
+impl Music {
+    // Music with nothing to play. The fading still happens; it's just
+    // silent.
+    pub fn none() -> Music {
+        Music {
+            channels: vec![Channel::new(Track::Menu, None), Channel::new(Track::Game, None)],
+            current: None,
+        }
+    }
+
+    pub fn load(settings: &AudioSettings) -> Music {
+        Music {
+            channels: vec![
+                Channel::new(Track::Menu, load_stream(Path::new(&settings.menu_music))),
+                Channel::new(Track::Game, load_stream(Path::new(&settings.game_music))),
+            ],
+            current: None,
+        }
+    }
+
+    // Fade over to `track`. Asking for the one that's already playing
+    // changes nothing, so it's fine to ask every update.
+    pub fn play(&mut self, track: Track) {
+        if self.current != Some(track) {
+            debug!("Music: fading over to {:?}", track);
+            self.current = Some(track);
+        }
+    }
+
+    // How far faded in `track` is.
+    pub fn gain(&self, track: Track) -> f64 {
+        self.channels.iter().find(|c| c.track == track).map(|c| c.gain).unwrap_or(0.0)
+    }
+
+    /*
+     * Move every channel `dt` seconds further along its fade, then set the
+     * streams' volumes to match. Muting turns the volume down rather than
+     * stopping anything, so unmuting carries on from wherever the track
+     * had got to.
+     */
+    pub fn update(&mut self, dt: f64, settings: &AudioSettings) {
+        let step = if settings.music_fade > 0.0 { dt / settings.music_fade } else { 1.0 };
+        for channel in self.channels.iter_mut() {
+            let target = if self.current == Some(channel.track) { 1.0 } else { 0.0 };
+            channel.gain = if channel.gain < target {
+                (channel.gain + step).min(target)
+            } else {
+                (channel.gain - step).max(target)
+            };
+            let volume = if settings.muted { 0.0 } else {
+                channel.gain as f32 * settings.music_volume.max(0.0).min(1.0)
+            };
+            if let Some(ref mut stream) = channel.stream {
+                if channel.gain > 0.0 && !channel.playing {
+                    start_stream(stream);
+                    channel.playing = true;
+                } else if channel.gain == 0.0 && channel.playing {
+                    stop_stream(stream);
+                    channel.playing = false;
+                }
+                set_stream_volume(stream, volume);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sound")]
+fn load_stream(path: &Path) -> Option<Stream> {
+    if !path.exists() {
+        info!("No {}, so no music there", path.display());
+        return None;
+    }
+    match path.to_str().and_then(|p| ears::Music::new(p)) {
+        Some(mut stream) => {
+            info!("Loaded {}", path.display());
+            stream.set_looping(true);
+            Some(stream)
+        }
+        None => {
+            warn!("Couldn't load {}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "sound"))]
+fn load_stream(_: &Path) -> Option<Stream> {
+    None
+}
+
+#[cfg(feature = "sound")]
+fn start_stream(stream: &mut Stream) {
+    stream.play();
+}
+
+#[cfg(feature = "sound")]
+fn stop_stream(stream: &mut Stream) {
+    stream.stop();
+}
+
+#[cfg(feature = "sound")]
+fn set_stream_volume(stream: &mut Stream, volume: f32) {
+    stream.set_volume(volume);
+}
+
+#[cfg(not(feature = "sound"))]
+fn start_stream(stream: &mut Stream) {
+    match *stream {}
+}
+
+#[cfg(not(feature = "sound"))]
+fn stop_stream(stream: &mut Stream) {
+    match *stream {}
+}
+
+#[cfg(not(feature = "sound"))]
+fn set_stream_volume(stream: &mut Stream, _: f32) {
+    match *stream {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn every_sound_has_its_own_file() {
+        let all = Sound::all();
+        for (i, a) in all.iter().enumerate() {
+            for b in all[i + 1..].iter() {
+                assert!(a.file_name() != b.file_name());
+            }
+        }
+    }
+
+    #[test]
+    fn missing_sounds_are_silent() {
+        let mut audio = Audio::load(Path::new("no-such-sounds"));
+        assert!(audio.clips.is_empty());
+        audio.play(Sound::Pickup, &AudioSettings::default());
+    }
+
+    #[test]
+    fn music_fades_from_one_track_to_the_other() {
+        let settings = AudioSettings { music_fade: 1.0, ..AudioSettings::default() };
+        let mut music = Music::none();
+        music.play(Track::Menu);
+        music.update(2.0, &settings);
+        assert_eq!((music.gain(Track::Menu), music.gain(Track::Game)), (1.0, 0.0));
+
+        music.play(Track::Game);
+        music.update(0.25, &settings);
+        assert_eq!((music.gain(Track::Menu), music.gain(Track::Game)), (0.75, 0.25));
+        music.update(1.0, &settings);
+        assert_eq!((music.gain(Track::Menu), music.gain(Track::Game)), (0.0, 1.0));
+    }
+
+    #[test]
+    fn no_fade_means_cutting_straight_over() {
+        let settings = AudioSettings { music_fade: 0.0, ..AudioSettings::default() };
+        let mut music = Music::none();
+        music.play(Track::Game);
+        music.update(0.01, &settings);
+        assert_eq!(music.gain(Track::Game), 1.0);
+    }
+}
@@ -0,0 +1,246 @@
+/*
+ * Autosaving, and picking up where you left off. Every autosave_seconds of
+ * play, the game in progress is saved (the same snapshot as F5's, see
+ * save.rs) into the autosave directory, and next time the game starts, the
+ * title screen's menu has Resume at the top, which carries on from it. It
+ * doesn't matter how the last game ended: quitting mid-game saves one last
+ * time on the way out, and if the game crashed, or the computer did,
+ * there's the autosave from a few seconds before.
+ *
+ * That last case is the one to be careful about, since a crash can come at
+ * any moment, in the middle of writing a save included. So two things. A
+ * save's written atomically (see save::write_atomically), so a file is
+ * always a whole save, old or new. And they go round autosave_slots files
+ * in turn, autosave-0.json, autosave-1.json and so on, writing over the
+ * oldest each time, so that if the newest is no good for any reason the
+ * one before it's still there, and Resume falls back on that.
+ *
+ * Which slot is the newest is in session.json, next to them, along with
+ * where you'd got to, and whether the game was still running when it was
+ * written. Quitting properly writes that it wasn't; so if it says it was,
+ * the game never got the chance, and it's crashed. The game says so in
+ * the log, but it's Resume just the same. A game over has nothing to
+ * resume, so that crosses out the session; so does losing it by starting
+ * afresh, though not until the new game's first autosave overwrites it.
+ *
+ * Only a game with a window autosaves: --headless and --bench, and
+ * replays, are somebody else's games.
+ */
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use app::{App, LevelState};
+use save::{self, Snapshot};
+
+// Where the autosaves go: a directory, and the files in it.
+pub fn default_dir() -> PathBuf {
+    PathBuf::from("autosave")
+}
+
+const SESSION_FILE: &'static str = "session.json";
+
+// What's in session.json.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    // The slot with the newest autosave in.
+    pub slot: u32,
+    pub level_name: String,
+    pub score: u32,
+    // Whether the game was still going (or had crashed) when this was
+    // written, and not quit.
+    pub running: bool,
+}
+
+pub struct Autosave {
+    pub dir: PathBuf,
+    pub slots: u32,
+    // How often, and how long until the next.
+    pub every: f64,
+    pub left: f64,
+    // The session so far, if there's been one.
+    pub session: Option<Session>,
+}
+
+impl Autosave {
+    pub fn new(dir: &Path, every: f64, slots: u32) -> Autosave {
+        Autosave {
+            dir: dir.to_path_buf(),
+            slots: slots.max(1),
+            every: every,
+            left: every,
+            session: None,
+        }
+    }
+
+    pub fn slot_path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("autosave-{}.json", slot))
+    }
+
+    pub fn session_path(&self) -> PathBuf {
+        self.dir.join(SESSION_FILE)
+    }
+
+    // `dt` seconds of playing have gone by. Whether it's time for another.
+    pub fn due(&mut self, dt: f64) -> bool {
+        if self.every <= 0.0 { return false }
+        self.left -= dt;
+        if self.left > 0.0 { return false }
+        self.left = self.every;
+        true
+    }
+
+    /*
+     * The last session, if there was one, from session.json, and the newest
+     * autosave of it that loads, going back through the older ones if it
+     * has to. Nothing if there isn't one, or none of them load.
+     */
+    pub fn find(&mut self) -> Option<Snapshot> {
+        let session: Session = match read(&self.session_path()) {
+            Ok(Some(json)) => match serde_json::from_str(&json) {
+                Ok(session) => session,
+                Err(e) => {
+                    warn!("couldn't parse {}: {} (there's nothing to resume)",
+                          self.session_path().display(), e);
+                    return None;
+                }
+            },
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("{} (there's nothing to resume)", e);
+                return None;
+            }
+        };
+        for back in 0..self.slots {
+            let slot = (session.slot % self.slots + self.slots - back) % self.slots;
+            match save::load(&self.slot_path(slot)) {
+                Ok(snapshot) => {
+                    if session.running {
+                        warn!("The last game didn't get to finish; Resume picks it up from \
+                               the last autosave");
+                    }
+                    self.session = Some(Session { slot: slot, ..session });
+                    return Some(snapshot);
+                }
+                Err(e) => warn!("{} (trying an older one)", e),
+            }
+        }
+        None
+    }
+
+    // Save `snapshot` over the oldest of the slots, and say in the session
+    // that it's the newest now.
+    pub fn write(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("couldn't make {}: {}", self.dir.display(), e))?;
+        let slot = self.session.as_ref().map_or(0, |s| (s.slot + 1) % self.slots);
+        save::save(&self.slot_path(slot), snapshot)?;
+        let session = Session {
+            slot: slot,
+            level_name: snapshot.level_name.clone(),
+            score: snapshot.score,
+            running: true,
+        };
+        self.write_session(&session)?;
+        self.session = Some(session);
+        Ok(())
+    }
+
+    // An autosave of `app`, if it's a game of yours still going. Going
+    // wrong is only worth a warning; there'll be another one along soon.
+    pub fn write_game(&mut self, app: &App) {
+        if app.replaying() || app.level_state != LevelState::Playing { return }
+        match self.write(&app.snapshot()) {
+            Ok(()) => debug!("Autosaved"),
+            Err(e) => warn!("Couldn't autosave: {}", e),
+        }
+    }
+
+    // The game's quitting properly, so the session can say it wasn't a
+    // crash.
+    pub fn finish(&mut self) -> Result<(), String> {
+        let session = match self.session.take() {
+            Some(session) => Session { running: false, ..session },
+            None => return Ok(()),
+        };
+        let written = self.write_session(&session);
+        self.session = Some(session);
+        written
+    }
+
+    // The game's over, so there's nothing to resume.
+    pub fn forget(&mut self) {
+        if self.session.take().is_some() {
+            let _ = fs::remove_file(self.session_path());
+        }
+    }
+
+    fn write_session(&self, session: &Session) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(session).map_err(|e| e.to_string())?;
+        save::write_atomically(&self.session_path(), json.as_bytes())
+    }
+}
+
+// What's in the file at `path`, or None if there isn't one.
+fn read(path: &Path) -> Result<Option<String>, String> {
+    if !path.exists() { return Ok(None) }
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use controls::Facing;
+
+    fn snapshot(score: u32) -> Snapshot {
+        Snapshot {
+            level: 0, level_name: "Empty field".to_string(),
+            player: [50.0, 50.0], velocity: [0.0, 0.0], facing: Facing::Down, angle: 0.0,
+            expand: 0.0, score: score, health: 3, lives: Some(3), elapsed: 0.0,
+            pickups: Vec::new(), enemies: Vec::new(), power_ups: Vec::new(),
+            effects: Vec::new(), inventory: None, items: None, doors: None,
+        }
+    }
+
+    #[test]
+    fn autosaves_go_round_the_slots_and_fall_back_on_the_last_good_one() {
+        let dir = env::temp_dir().join(format!("jumpy-autosave-{}", ::std::process::id()));
+        let mut autosave = Autosave::new(&dir, 1.0, 2);
+        assert!(!autosave.due(0.5) && autosave.due(0.5));
+        assert_eq!(autosave.find(), None);
+        for score in 1..4 {
+            autosave.write(&snapshot(score)).unwrap();
+        }
+        // 1 went in 0, 2 in 1, and 3 back in 0.
+        assert_eq!(autosave.session.as_ref().map(|s| (s.slot, s.score)), Some((0, 3)));
+        assert_eq!(save::load(&autosave.slot_path(1)).unwrap().score, 2);
+
+        // Without quitting, it's a crash, but there's still the game.
+        let mut next_time = Autosave::new(&dir, 1.0, 2);
+        assert_eq!(next_time.find().map(|s| s.score), Some(3));
+        assert!(next_time.session.as_ref().unwrap().running);
+        next_time.finish().unwrap();
+        let mut after_that = Autosave::new(&dir, 1.0, 2);
+        after_that.find();
+        assert!(!after_that.session.as_ref().unwrap().running);
+
+        // A newest one that's broken, and it's the one before.
+        File::create(autosave.slot_path(0)).unwrap().write_all(b"{ \"level\":").unwrap();
+        let mut broken = Autosave::new(&dir, 1.0, 2);
+        assert_eq!(broken.find().map(|s| s.score), Some(2));
+        assert_eq!(broken.session.as_ref().map(|s| s.slot), Some(1));
+
+        // And after a game over, there's nothing.
+        broken.forget();
+        assert_eq!(Autosave::new(&dir, 1.0, 2).find(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+/*
+ * What's behind everything. It used to be one flat rectangle in the
+ * palette's background color; now it's two slow things going on in that
+ * color, so the screen's never quite standing still:
+ *
+ * - The gradient: the view's cut into BANDS strips, top to bottom, each a
+ *   touch lighter or darker than the background color, by how far round a
+ *   wave it is. The wave rolls down the screen once every PERIOD seconds,
+ *   so the light and dark drift slowly down it, over and over.
+ *
+ * - The shapes: a few big faint squares, a little lighter than the
+ *   background, that move as the camera does but slower, by their depth,
+ *   so they look far away behind the world (that's parallax, the same
+ *   trick as the hills in every platformer). They drift along on their
+ *   own a bit too. They don't belong anywhere in the world, they just go
+ *   round: one off one side of the view comes back on the other. They're
+ *   cut off at the view's edges, or with the letterbox on they'd be drawn
+ *   over the bars.
+ *
+ * All of it goes by `time`, which App::update moves on by however long's
+ * really gone by, so it doesn't care about the game's ticks (or pausing).
+ * With low_distraction on (it's on the options screen) none of this gets
+ * drawn, and it's the flat rectangle again.
+ */
+use std::f64::consts::PI;
+
+use graphics::Context;
+
+use renderer::Renderer;
+
+// How many strips the gradient's made of, how long the wave takes to go
+// round, and how much lighter or darker it goes.
+pub const BANDS: usize = 16;
+pub const PERIOD: f64 = 20.0;
+pub const SHADE: f32 = 0.06;
+
+// How many shapes there are, how much lighter than the background, how
+// much they drift on their own in world units a second, and how far off the
+// view they can go before coming back on the other side.
+pub const SHAPES: usize = 8;
+pub const SHAPE_LIGHTER: f32 = 0.05;
+pub const SHAPE_ALPHA: f32 = 0.6;
+pub const DRIFT: f64 = 20.0;
+const MARGIN: f64 = 120.0;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Background {
+    // Seconds since it started.
+    pub time: f64,
+}
+
+impl Background {
+    pub fn new() -> Background {
+        Background { time: 0.0 }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time += dt;
+    }
+
+    // The color of strip `band`, now.
+    pub fn band_color(&self, base: [f32; 4], band: usize) -> [f32; 4] {
+        let down = band as f64 / BANDS as f64;
+        let wave = (2.0 * PI * (self.time / PERIOD - down)).sin() as f32;
+        shade(base, SHADE * wave)
+    }
+
+    /*
+     * Shape `i`: where it is in a view of `size` with the camera's corner at
+     * `camera`, as [x, y, w, h]. Where it starts and how deep it is are made
+     * up from `i` rather than taken from the random numbers, so drawing the
+     * background doesn't change what happens next in the game. The deeper
+     * (nearer 0) the slower it goes by, and the smaller it is.
+     */
+    pub fn shape(&self, i: usize, size: (f64, f64), camera: (f64, f64)) -> [f64; 4] {
+        let depth = 0.1 + 0.1 * (i % 3) as f64;
+        let radius = 40.0 + 200.0 * depth;
+        let (span_x, span_y) = (size.0 + MARGIN * 2.0, size.1 + MARGIN * 2.0);
+        // The golden ratio spreads them out so they don't bunch up.
+        let start_x = (i as f64 * 0.618).fract() * span_x;
+        let start_y = (i as f64 * 0.382 + 0.5).fract() * span_y;
+        let x = wrap(start_x - camera.0 * depth + self.time * DRIFT * depth, span_x) - MARGIN;
+        let y = wrap(start_y - camera.1 * depth, span_y) - MARGIN;
+        [x - radius, y - radius, radius * 2.0, radius * 2.0]
+    }
+
+    // Fill a view of `size` on `context`, with the camera's corner at
+    // `camera`, around the color `base`.
+    pub fn draw(&self, context: &Context, r: &mut dyn Renderer, size: (f64, f64),
+                camera: (f64, f64), base: [f32; 4]) {
+        let height = size.1 / BANDS as f64;
+        for band in 0..BANDS {
+            // A pixel over, so there are no gaps between strips when the
+            // view's scaled.
+            r.rect(context, 0.0, band as f64 * height, size.0, height + 1.0,
+                   self.band_color(base, band));
+        }
+        let mut color = shade(base, SHAPE_LIGHTER);
+        color[3] *= SHAPE_ALPHA;
+        for i in 0..SHAPES {
+            let s = self.shape(i, size, camera);
+            let (left, top) = (s[0].max(0.0), s[1].max(0.0));
+            let (right, bottom) = ((s[0] + s[2]).min(size.0), (s[1] + s[3]).min(size.1));
+            if right > left && bottom > top {
+                r.rect(context, left, top, right - left, bottom - top, color);
+            }
+        }
+    }
+}
+
+// `color` lighter by `by` (or darker, if it's less than 0), keeping it a
+// color.
+fn shade(color: [f32; 4], by: f32) -> [f32; 4] {
+    let c = |v: f32| (v + by).max(0.0).min(1.0);
+    [c(color[0]), c(color[1]), c(color[2]), color[3]]
+}
+
+// `v` brought round into 0 to `span`.
+fn wrap(v: f64, span: f64) -> f64 {
+    ((v % span) + span) % span
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::Recorder;
+
+    #[test]
+    fn the_gradient_rolls_down_and_the_shapes_lag_behind() {
+        let grey = [0.6, 0.6, 0.6, 1.0];
+        let mut background = Background::new();
+        let first = background.band_color(grey, 0);
+        assert_eq!(first, grey);
+        background.update(PERIOD / 4.0);
+        assert!(background.band_color(grey, 0)[0] > 0.6);
+        // A whole way round, it's back where it was.
+        background.update(PERIOD * 3.0 / 4.0);
+        assert!((background.band_color(grey, 0)[0] - first[0]).abs() < 1e-4);
+
+        // The camera going 100 pixels right moves the nearest shapes 30
+        // pixels left, and the farthest only 10.
+        let still = Background::new();
+        let before = still.shape(2, (640.0, 480.0), (0.0, 0.0));
+        let after = still.shape(2, (640.0, 480.0), (100.0, 0.0));
+        assert!((before[0] - after[0] - 30.0).abs() < 1e-9);
+        let far = still.shape(3, (640.0, 480.0), (100.0, 0.0));
+        assert!((still.shape(3, (640.0, 480.0), (0.0, 0.0))[0] - far[0] - 10.0).abs() < 1e-9);
+
+        let mut r = Recorder::new();
+        still.draw(&Context::new_abs(640.0, 480.0), &mut r, (640.0, 480.0), (0.0, 0.0), grey);
+        let mut color = shade(grey, SHAPE_LIGHTER);
+        color[3] *= SHAPE_ALPHA;
+        let shapes = r.rects_in(color);
+        assert!(shapes.len() > 0 && shapes.len() <= SHAPES);
+        assert_eq!(r.calls.len(), BANDS + shapes.len());
+        for s in shapes {
+            assert!(s[0] >= 0.0 && s[1] >= 0.0 && s[0] + s[2] <= 640.0 && s[1] + s[3] <= 480.0);
+        }
+    }
+}
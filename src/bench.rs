@@ -0,0 +1,93 @@
+/*
+ * --bench: how fast is the game, really? It plays so many updates of the
+ * game one after another, with no waiting in between and nobody at the
+ * controls (like --headless without a script), and times each part: the
+ * whole update, and the enemies and the collision checks inside it (see
+ * SystemTimes), and with --bench-render, drawing a frame after each one.
+ * There's no window, so the drawing goes to a Recorder: it's our half of
+ * drawing that gets timed, working out what goes where, and not the
+ * graphics card's.
+ *
+ * At the end it prints the average for each part, the 99th percentile (see
+ * frametime.rs) and the total. Something that gets slower the more of it
+ * there is in a level shows up here long before you'd feel it playing.
+ *
+ * Nobody at the controls means the enemies catch you before long, so a
+ * game that's over gets started again, and the timing carries on.
+ */
+use std::time::Instant;
+
+use input::UpdateArgs;
+
+use frametime::{seconds, FrameTimes, SystemTimes};
+use game::{Game, GameState};
+use renderer::Recorder;
+
+// The parts that get timed, in the order they're printed.
+pub const SYSTEMS: [&'static str; 4] = ["update", "enemies", "collision", "render"];
+
+pub struct Report {
+    pub updates: usize,
+    pub seconds: f64,
+    // One for each of SYSTEMS. Without --bench-render, the last one's
+    // empty.
+    pub times: Vec<FrameTimes>,
+}
+
+impl Report {
+    // The report, ready to print.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} updates in {:.2}s", self.updates, self.seconds)];
+        for (name, times) in SYSTEMS.iter().zip(self.times.iter()) {
+            if times.is_empty() { continue }
+            lines.push(format!("{:<10} {:8.3} ms avg {:8.3} ms p99 {:10.1} ms total", name,
+                               times.average() * 1000.0, times.percentile(0.99) * 1000.0,
+                               times.total() * 1000.0));
+        }
+        lines
+    }
+}
+
+/*
+ * Running the benchmark is Game's business, since it's the whole game
+ * that's being run, so Game gets another `impl` here, like App does in
+ * render.rs.
+ */
+impl Game {
+    pub fn run_bench(&mut self, updates: usize, render: bool) -> Report {
+        self.start();
+        let dt = 1.0 / self.ups() as f64;
+        let (w, h) = self.window_size;
+        let mut renderer = Recorder::new();
+        let mut times: Vec<FrameTimes> = SYSTEMS.iter().map(|_| FrameTimes::new(updates))
+                                                .collect();
+        let began = Instant::now();
+        for _ in 0..updates {
+            if let GameState::Playing(ref mut app) = self.state {
+                app.system_times = SystemTimes::default();
+            }
+            let started = Instant::now();
+            self.update(&UpdateArgs { dt: dt });
+            times[0].record(seconds(started.elapsed()));
+            // Only if we're still playing: a game that's just ended has
+            // taken its App, and its times, with it.
+            if let GameState::Playing(ref app) = self.state {
+                times[1].record(app.system_times.enemies);
+                times[2].record(app.system_times.collision);
+            }
+            if render {
+                renderer.calls.clear();
+                let started = Instant::now();
+                self.draw(w, h, &mut renderer);
+                times[3].record(seconds(started.elapsed()));
+            }
+            match self.state {
+                GameState::Playing(_) => {}
+                _ => self.start(),
+            }
+        }
+        let report = Report { updates: updates, seconds: seconds(began.elapsed()), times: times };
+        info!("Benchmark done: {} updates in {:.2}s", updates, report.seconds);
+        report
+    }
+}
@@ -0,0 +1,269 @@
+/*
+ * Playing the game from another program. With --bot, commands come in a
+ * line at a time, on stdin (--bot stdin) or from whoever connects to a TCP
+ * port on this computer (--bot 7779, say), and go into the game as key
+ * presses, the same as a person's:
+ *
+ *     move up right     hold Up and Right, and let go of the other arrows
+ *     move              let go of all the arrows
+ *     expand            tap Space: expand, or jump in platformer mode
+ *     fire              tap F
+ *     press <key>       hold any key, by the names the config uses
+ *     release <key>     and let go of it
+ *     step <n>          with --headless, let n ticks go by
+ *     quit              stop
+ *
+ * Blank lines and anything after a # are ignored, like in a script. What
+ * the bot can see comes the other way, from --dump-state (see dump.rs), so
+ * a bot is something like:
+ *
+ *     game --headless --bot stdin --dump-state stdout
+ *
+ * reading a line of state, deciding what to do, and writing commands back.
+ *
+ * Headless, the game goes at the bot's pace: nothing happens until it says
+ * `step`, and then exactly that many ticks (1/120 of a second each) go by,
+ * each with a state line if --dump-state is on, before the game waits for
+ * it again. So a slow bot plays the same game as a quick one, and a test
+ * can walk the player into a wall and know exactly how many ticks it took.
+ * With a window there's a real clock to keep up with, so the commands are
+ * done as soon as they come, and step doesn't do anything. The game stops
+ * when the bot does: on quit, or when stdin closes. A socket closing just
+ * means waiting for the next bot to connect.
+ *
+ * Reading stdin waits until there's a line, and the game can't be kept
+ * waiting, so the reading happens on a thread of its own, which hands each
+ * line over through a channel, like F2's clips come back (see capture.rs).
+ * The socket gets one too, so both arrive the same way. Bot keys go through
+ * handleKey like any others, so they're in the replay afterwards.
+ */
+use std::io::{self, BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use input::keyboard::{self, Key};
+
+use keys;
+use script::Action;
+
+// What --bot is given to mean stdin rather than a port.
+pub const STDIN: &'static str = "stdin";
+
+// The arrow keys `move` holds, by the names it knows them by.
+const DIRECTIONS: &'static [(&'static str, Key)] = &[
+    ("up", keyboard::Up),
+    ("down", keyboard::Down),
+    ("left", keyboard::Left),
+    ("right", keyboard::Right),
+];
+
+// Where the commands come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Stdin,
+    Port(u16),
+}
+
+impl Source {
+    pub fn parse(arg: &str) -> Option<Source> {
+        if arg == STDIN { return Some(Source::Stdin) }
+        arg.parse().ok().map(Source::Port)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // The arrows to hold; the rest get let go of.
+    Move(Vec<Key>),
+    Press(Key),
+    Release(Key),
+    // Press and let go again straight away.
+    Tap(Key),
+    Step(u64),
+    Quit,
+}
+
+// A line of what the bot says. Nothing to do (a blank line or a comment)
+// is None.
+pub fn parse(line: &str) -> Result<Option<Command>, String> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let key = |name: &str| keys::parse_key(name)
+        .ok_or_else(|| format!("there's no key called \"{}\"", name));
+    let command = match (words.first().map(|w| &w[..]), words.len()) {
+        (None, _) => return Ok(None),
+        (Some("move"), _) => {
+            let mut held = Vec::new();
+            for word in words[1..].iter() {
+                match DIRECTIONS.iter().find(|&&(name, _)| name.eq_ignore_ascii_case(word)) {
+                    Some(&(_, k)) => held.push(k),
+                    None => return Err(format!("{} isn't up, down, left or right", word)),
+                }
+            }
+            Command::Move(held)
+        }
+        (Some("expand"), 1) => Command::Tap(keyboard::Space),
+        (Some("fire"), 1) => Command::Tap(keyboard::F),
+        (Some("press"), 2) => Command::Press(key(words[1])?),
+        (Some("release"), 2) => Command::Release(key(words[1])?),
+        (Some("step"), 2) => match words[1].parse() {
+            Ok(n) => Command::Step(n),
+            Err(_) => return Err("step needs a number of ticks".to_string()),
+        },
+        (Some("quit"), 1) => Command::Quit,
+        _ => return Err(format!("don't know how to \"{}\"", line)),
+    };
+    Ok(Some(command))
+}
+
+pub struct Bot {
+    lines: Receiver<String>,
+    // The arrows `move` is holding down.
+    held: Vec<Key>,
+}
+
+impl Bot {
+    // A bot saying whatever comes down `lines`.
+    pub fn new(lines: Receiver<String>) -> Bot {
+        Bot { lines: lines, held: Vec::new() }
+    }
+
+    pub fn listen(source: &Source) -> Result<Bot, String> {
+        let (send, lines) = mpsc::channel();
+        match *source {
+            Source::Stdin => {
+                thread::spawn(move || {
+                    let stdin = io::stdin();
+                    pass_on(stdin.lock(), &send);
+                });
+            }
+            Source::Port(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", port))
+                    .map_err(|e| format!("couldn't listen for a bot on port {}: {}", port, e))?;
+                thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        match stream {
+                            Ok(stream) => {
+                                info!("A bot has connected");
+                                if !pass_on(BufReader::new(stream), &send) { return }
+                                info!("The bot has gone");
+                            }
+                            Err(e) => warn!("A bot couldn't connect: {}", e),
+                        }
+                    }
+                });
+            }
+        }
+        Ok(Bot::new(lines))
+    }
+
+    // Everything the bot's said that hasn't been done yet, without
+    // waiting for more. Lines that don't make sense are complained about
+    // and skipped.
+    pub fn received(&mut self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        while let Ok(line) = self.lines.try_recv() {
+            commands.extend(understood(&line));
+        }
+        commands
+    }
+
+    // The next thing the bot says, waiting for it if need be. None means
+    // it's gone, and won't be saying anything else.
+    pub fn wait(&mut self) -> Option<Command> {
+        loop {
+            let line = self.lines.recv().ok()?;
+            if let Some(command) = understood(&line) {
+                return Some(command);
+            }
+        }
+    }
+
+    /*
+     * The keys that go down and come up for `command`. A move presses the
+     * arrows it wants that aren't held yet, and lets go of the ones held
+     * that it doesn't, so holding Right and then moving up right doesn't
+     * press Right again.
+     */
+    pub fn keys(&mut self, command: &Command) -> Vec<(Action, Key)> {
+        match *command {
+            Command::Move(ref want) => {
+                let mut keys: Vec<_> = self.held.iter().filter(|&k| !want.contains(k))
+                    .map(|&k| (Action::Release, k)).collect();
+                keys.extend(want.iter().filter(|&k| !self.held.contains(k))
+                    .map(|&k| (Action::Press, k)));
+                self.held = want.clone();
+                keys
+            }
+            Command::Press(k) => vec![(Action::Press, k)],
+            Command::Release(k) => {
+                self.held.retain(|&h| h != k);
+                vec![(Action::Release, k)]
+            }
+            Command::Tap(k) => vec![(Action::Press, k), (Action::Release, k)],
+            Command::Step(_) | Command::Quit => Vec::new(),
+        }
+    }
+}
+
+fn understood(line: &str) -> Option<Command> {
+    match parse(line) {
+        Ok(command) => command,
+        Err(e) => {
+            warn!("The bot said \"{}\": {}", line.trim(), e);
+            None
+        }
+    }
+}
+
+// Every line from `from` down `to`, until one end or the other goes away.
+// Whether the game's still listening.
+fn pass_on<R: BufRead>(from: R, to: &Sender<String>) -> bool {
+    for line in from.lines() {
+        match line {
+            Ok(line) => if to.send(line).is_err() { return false },
+            Err(_) => break,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_the_bot_says() {
+        assert_eq!(parse("move up Right"),
+                   Ok(Some(Command::Move(vec![keyboard::Up, keyboard::Right]))));
+        assert_eq!(parse("  # thinking"), Ok(None));
+        assert_eq!(parse("expand"), Ok(Some(Command::Tap(keyboard::Space))));
+        assert_eq!(parse("press G"), Ok(Some(Command::Press(keyboard::G))));
+        assert_eq!(parse("step 30"), Ok(Some(Command::Step(30))));
+        assert!(parse("move sideways").is_err());
+        assert!(parse("press Hyper").is_err());
+        assert!(parse("step lots").is_err());
+        assert!(parse("dance").is_err());
+        assert_eq!(Source::parse("stdin"), Some(Source::Stdin));
+        assert_eq!(Source::parse("7779"), Some(Source::Port(7779)));
+        assert_eq!(Source::parse("somewhere"), None);
+    }
+
+    #[test]
+    fn moving_only_changes_what_it_has_to() {
+        let (send, lines) = mpsc::channel();
+        let mut bot = Bot::new(lines);
+        send.send("move right".to_string()).unwrap();
+        send.send("nonsense".to_string()).unwrap();
+        send.send("move up right".to_string()).unwrap();
+        let commands = bot.received();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(bot.keys(&commands[0]), vec![(Action::Press, keyboard::Right)]);
+        assert_eq!(bot.keys(&commands[1]), vec![(Action::Press, keyboard::Up)]);
+        assert_eq!(bot.keys(&Command::Move(vec![keyboard::Up])),
+                   vec![(Action::Release, keyboard::Right)]);
+        drop(send);
+        assert_eq!(bot.wait(), None);
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * Bounce mode turns the player into a pinball: it keeps whatever velocity
+ * it has, and when it hits the edge of the arena it ricochets off. The arrow
+ * keys only nudge the velocity, so you steer rather than drive.
+ *
+ * Restitution is the physics word for "how bouncy". At 1.0 a bounce gives
+ * back every bit of speed that went into it; at 0.5 the square comes away
+ * at half the speed it arrived at, and fairly soon stops bouncing at all.
+ */
+use collision::{Aabb, Hits};
+
+/*
+ * Bounce a single velocity component off something: flip its sign and take
+ * off whatever the restitution says. Anything slower than `rest_speed`
+ * after the bounce just stops, otherwise a square with restitution below 1
+ * would sit against the wall making ever-tinier bounces forever.
+ */
+fn bounce_off(v: f64, restitution: f64, rest_speed: f64) -> f64 {
+    let v = -v * restitution;
+    if v.abs() < rest_speed { 0.0 } else { v }
+}
+
+// Reflect a velocity off whichever sides were hit, as reported by the
+// collision code.
+pub fn reflect(vel: (f64, f64), hits: Hits, restitution: f64, rest_speed: f64) -> (f64, f64) {
+    let (mut vx, mut vy) = vel;
+    if (hits.left && vx < 0.0) || (hits.right && vx > 0.0) {
+        vx = bounce_off(vx, restitution, rest_speed);
+    }
+    if (hits.top && vy < 0.0) || (hits.bottom && vy > 0.0) {
+        vy = bounce_off(vy, restitution, rest_speed);
+    }
+    (vx, vy)
+}
+
+/*
+ * Keep `body` inside `arena`. If it's poked out of a side, push it back so
+ * it's flush with the edge and note which side that was. Both axes are
+ * checked every time, so flying into a corner bounces off both walls in the
+ * same frame rather than getting wedged.
+ */
+pub fn confine(mut body: Aabb, arena: Aabb) -> (Aabb, Hits) {
+    let mut hits = Hits::default();
+    if body.x < arena.x {
+        body.x = arena.x;
+        hits.left = true;
+    } else if body.right() > arena.right() {
+        body.x = arena.right() - body.w;
+        hits.right = true;
+    }
+    if body.y < arena.y {
+        body.y = arena.y;
+        hits.top = true;
+    } else if body.bottom() > arena.bottom() {
+        body.y = arena.bottom() - body.h;
+        hits.bottom = true;
+    }
+    (body, hits)
+}
+
+// One step of bounce physics for a body in an empty arena: move along the
+// velocity, then bounce off any edges we went past.
+pub fn step(body: Aabb,
+            vel: (f64, f64),
+            dt: f64,
+            arena: Aabb,
+            restitution: f64,
+            rest_speed: f64) -> (Aabb, (f64, f64)) {
+    let moved = Aabb { x: body.x + vel.0 * dt, y: body.y + vel.1 * dt, ..body };
+    let (confined, hits) = confine(moved, arena);
+    (confined, reflect(vel, hits, restitution, rest_speed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::Aabb;
+
+    fn arena() -> Aabb { Aabb::new(0.0, 0.0, 100.0, 100.0) }
+
+    #[test]
+    fn bounces_back_and_forth() {
+        // 100 px/s, 10 px per step, in a 100 px wide box with a 10 px body.
+        let mut body = Aabb::new(50.0, 50.0, 10.0, 10.0);
+        let mut vel = (100.0, 0.0);
+        let mut xs = Vec::new();
+        for _ in 0..12 {
+            let (b, v) = step(body, vel, 0.1, arena(), 1.0, 1.0);
+            body = b;
+            vel = v;
+            xs.push(body.x.round());
+        }
+        // Out to the right wall, back across to the left, and off it again.
+        assert_eq!(xs, vec![60.0, 70.0, 80.0, 90.0, 90.0, 80.0,
+                            70.0, 60.0, 50.0, 40.0, 30.0, 20.0]);
+        assert_eq!(vel, (-100.0, 0.0));
+        for _ in 0..3 {
+            let (b, v) = step(body, vel, 0.1, arena(), 1.0, 1.0);
+            body = b;
+            vel = v;
+        }
+        assert_eq!(body.x.round(), 0.0);
+        assert_eq!(vel, (100.0, 0.0));
+    }
+
+    #[test]
+    fn corner_hit_reflects_both() {
+        let body = Aabb::new(85.0, 85.0, 10.0, 10.0);
+        let (b, v) = step(body, (100.0, 100.0), 0.1, arena(), 1.0, 1.0);
+        assert_eq!((b.x, b.y), (90.0, 90.0));
+        assert_eq!(v, (-100.0, -100.0));
+        // And it comes cleanly back out rather than sticking.
+        let (b, _) = step(b, v, 0.1, arena(), 1.0, 1.0);
+        assert!(arena().contains(&b));
+        assert_eq!((b.x.round(), b.y.round()), (80.0, 80.0));
+    }
+
+    #[test]
+    fn restitution_loses_speed_then_settles() {
+        let mut body = Aabb::new(85.0, 0.0, 10.0, 10.0);
+        let mut vel = (80.0, 0.0);
+        // Each bounce halves the speed: 80 -> 40 -> 20 -> 10, and then the
+        // next would be 5, which is under the rest speed, so it stops.
+        let mut speeds = Vec::new();
+        for _ in 0..400 {
+            let (b, v) = step(body, vel, 0.05, arena(), 0.5, 6.0);
+            if v.0.abs() != vel.0.abs() { speeds.push(v.0.abs()); }
+            body = b;
+            vel = v;
+        }
+        assert_eq!(speeds, vec![40.0, 20.0, 10.0, 0.0]);
+        assert_eq!(vel, (0.0, 0.0));
+        assert!(arena().contains(&body));
+    }
+}
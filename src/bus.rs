@@ -0,0 +1,164 @@
+/*
+ * The event bus: how the parts of the game hear about what's happened in
+ * it, without App::update having to call each of them. When something
+ * happens that the rest of the game might care about (someone moved, a
+ * pickup got picked up, an enemy got knocked out, the level was won or
+ * lost) App publishes a GameEvent, and says nothing about who's
+ * listening. Then at the end of the tick everything published gets
+ * delivered to every subscriber, in the order they subscribed:
+ *
+ *     app.bus.subscribe(audio::hear);
+ *     app.bus.publish(GameEvent::EnemyKilled { at: (120.0, 80.0) });
+ *     bus::deliver(&mut app);
+ *
+ * So the sound of a pickup is audio.rs's business, the achievements'
+ * bookkeeping is achievements.rs's, and so on, each in a `hear` of its own
+ * next to the rest of what it does; and adding another one (a stats
+ * screen, say) means subscribing it, not finding every place in App::update
+ * it would need telling.
+ *
+ * A subscriber is a plain function, given the event and whatever the bus
+ * belongs to, which for App's is App. That's the same catch as Timers (see
+ * timers.rs): the bus lives in App, and App's lent out to the subscribers.
+ * Functions can be copied, though, so deliver copies the list out first,
+ * and there's nothing to put back. Subscribers can publish too, and what
+ * they publish gets delivered straight after, up to MAX_ROUNDS rounds, so
+ * two that set each other off can't go on forever.
+ */
+use std::mem;
+
+use app::LevelState;
+
+// How many times deliver goes round for what subscribers publish.
+pub const MAX_ROUNDS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    // Player `player` went from `from` to `to` this tick.
+    PlayerMoved { player: usize, from: (f64, f64), to: (f64, f64) },
+    // A pickup's gone, from where its middle was. A power-up gives a power
+    // rather than a point.
+    PickupCollected { at: (f64, f64), power_up: bool },
+    // An enemy was knocked out where its middle was.
+    EnemyKilled { at: (f64, f64) },
+    // The level's been won, or lost, or started again. Only when it's a
+    // different state: the victory lap's timer going up doesn't count.
+    StateChanged { from: LevelState, to: LevelState },
+}
+
+pub type Subscriber<C> = fn(&GameEvent, &mut C);
+
+pub struct Bus<C> {
+    queue: Vec<GameEvent>,
+    subscribers: Vec<Subscriber<C>>,
+}
+
+impl<C> Bus<C> {
+    pub fn new() -> Bus<C> {
+        Bus { queue: Vec::new(), subscribers: Vec::new() }
+    }
+
+    pub fn subscribe(&mut self, subscriber: Subscriber<C>) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn publish(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+
+    // What's been published and not delivered yet.
+    pub fn pending(&self) -> &[GameEvent] {
+        &self.queue
+    }
+
+    // Forget anything not delivered yet, for starting afresh.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+impl<C> Default for Bus<C> {
+    fn default() -> Bus<C> {
+        Bus::new()
+    }
+}
+
+// Anything with a bus of its own, for deliver.
+pub trait HasBus: Sized {
+    fn bus(&mut self) -> &mut Bus<Self>;
+}
+
+// Hand everything published on `context`'s bus to each of its
+// subscribers. What they publish meanwhile goes round again.
+pub fn deliver<C: HasBus>(context: &mut C) {
+    let subscribers = context.bus().subscribers.clone();
+    for _ in 0..MAX_ROUNDS {
+        let events = mem::replace(&mut context.bus().queue, Vec::new());
+        if events.is_empty() { return }
+        for event in events.iter() {
+            for subscriber in subscribers.iter() {
+                subscriber(event, context);
+            }
+        }
+    }
+    let left = context.bus().queue.len();
+    if left > 0 {
+        warn!("{} game events were still being published after {} rounds", left, MAX_ROUNDS);
+        context.bus().clear();
+    }
+}
+
+/*
+ * Which of the two states is the other, as far as StateChanged goes:
+ * Complete with one timer is the same as Complete with another.
+ */
+pub fn same_state(a: LevelState, b: LevelState) -> bool {
+    mem::discriminant(&a) == mem::discriminant(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Something to deliver to: it writes down what it heard, and a kill
+    // sets off one more.
+    #[derive(Default)]
+    struct Listener {
+        bus: Bus<Listener>,
+        heard: Vec<GameEvent>,
+    }
+
+    impl HasBus for Listener {
+        fn bus(&mut self) -> &mut Bus<Listener> {
+            &mut self.bus
+        }
+    }
+
+    fn write_down(event: &GameEvent, listener: &mut Listener) {
+        listener.heard.push(*event);
+    }
+
+    fn echo(event: &GameEvent, listener: &mut Listener) {
+        if let GameEvent::EnemyKilled { at } = *event {
+            listener.bus.publish(GameEvent::PickupCollected { at: at, power_up: false });
+        }
+    }
+
+    #[test]
+    fn everyone_hears_everything_in_order() {
+        let mut listener = Listener::default();
+        listener.bus.subscribe(write_down);
+        listener.bus.subscribe(echo);
+        let killed = GameEvent::EnemyKilled { at: (1.0, 2.0) };
+        listener.bus.publish(killed);
+        assert_eq!(listener.bus.pending(), &[killed]);
+        deliver(&mut listener);
+        assert_eq!(listener.heard,
+                   vec![killed, GameEvent::PickupCollected { at: (1.0, 2.0), power_up: false }]);
+        assert!(listener.bus.pending().is_empty());
+        let (won, still_won) = (LevelState::Complete { timer: 0.0 },
+                                LevelState::Complete { timer: 1.0 });
+        assert!(same_state(won, still_won));
+        assert!(!same_state(LevelState::Playing, LevelState::Lost));
+    }
+}
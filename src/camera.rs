@@ -0,0 +1,252 @@
+/*
+ * The camera is the window's view onto the world. The world can be bigger
+ * than the window now, so something has to decide which bit of it you're
+ * looking at, and that something follows the player about.
+ *
+ * Two things stop it feeling like the world is glued to the player's nose.
+ * The deadzone is a box in the middle of the view that the player can walk
+ * around in without the camera moving at all; only when they push against
+ * its edge does the camera follow. And smoothing means the camera doesn't
+ * jump straight to where it wants to be, but glides there, covering a
+ * fraction of the remaining distance every second.
+ *
+ * Drawing through the camera is just a translation: everything in the world
+ * gets shifted left and up by wherever the camera is. Then there's zoom,
+ * which scales everything up (or down) on top of that. Zoomed in to 2, the
+ * window only has room for half as much world each way, so a lot of the
+ * sums below use view_width and view_height, the size of the view in world
+ * units, rather than the window's size. to_world and to_screen are the
+ * camera's part in getting from the screen to the world and back (see
+ * units.rs): everything else goes through those.
+ */
+use units::{ScreenPos, WorldPos};
+
+// How far in and out you're allowed to zoom.
+pub const MIN_ZOOM: f64 = 0.5;
+pub const MAX_ZOOM: f64 = 4.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera {
+    // The top-left corner of the view, in world coordinates.
+    pub x: f64,
+    pub y: f64,
+    // How big the view is, i.e. how big the window is.
+    pub width: f64,
+    pub height: f64,
+    // How many of the screen's points one world unit takes up.
+    pub zoom: f64,
+}
+
+impl Camera {
+    pub fn new(width: f64, height: f64) -> Camera {
+        Camera { x: 0.0, y: 0.0, width: width, height: height, zoom: 1.0 }
+    }
+
+    pub fn view_width(&self) -> f64 { self.width / self.zoom }
+    pub fn view_height(&self) -> f64 { self.height / self.zoom }
+
+    /*
+     * `corner`, moved to the nearest spot that lines the view up with the
+     * screen's pixels, with `draw_scale` of them to each of the window's
+     * points (see App). Drawn from anywhere in between, the edges of every
+     * tile and picture fall part-way across a pixel and come out blurred.
+     * On a HiDPI screen a pixel is half a point, so it can stop at half
+     * points rather than whole ones, which moves that much more smoothly.
+     */
+    pub fn snap(&self, corner: (f64, f64), draw_scale: f64) -> (f64, f64) {
+        let step = self.zoom * draw_scale;
+        ((corner.0 * step).round() / step, (corner.1 * step).round() / step)
+    }
+
+    // The top-left corner of the view, in the world.
+    pub fn corner(&self) -> WorldPos {
+        WorldPos::new(self.x, self.y)
+    }
+
+    // Move so that `at`, in the world, is seen at `on`, on the screen.
+    pub fn put(&mut self, at: WorldPos, on: ScreenPos) {
+        self.x = at.x - on.x / self.zoom;
+        self.y = at.y - on.y / self.zoom;
+    }
+
+    // Jump straight to having `target` in the middle of the view.
+    pub fn centre_on(&mut self, target: WorldPos) {
+        let middle = ScreenPos::new(self.width / 2.0, self.height / 2.0);
+        self.put(target, middle);
+    }
+
+    /*
+     * Change the zoom, keeping `anchor` (a spot in the world, usually the
+     * player) exactly where it was on screen. Without this, zooming would
+     * scale everything towards the top-left corner of the window, which is
+     * where the camera's position is measured from.
+     */
+    pub fn set_zoom(&mut self, zoom: f64, anchor: WorldPos) {
+        let on = self.to_screen(anchor);
+        self.zoom = zoom.max(MIN_ZOOM).min(MAX_ZOOM);
+        self.put(anchor, on);
+    }
+
+    /*
+     * Follow `target` (the middle of the player). `deadzone` is the size of
+     * the box in the middle of the view they can move around in freely.
+     * `smoothing` is how quickly we catch up: the bigger, the snappier, and
+     * zero means no gliding at all. The catching up uses 1 - e^(-smoothing
+     * * dt) rather than just smoothing * dt so that it comes out the same
+     * whatever dt is; two half-length updates get exactly as far as one
+     * full-length one.
+     */
+    pub fn follow(&mut self, target: (f64, f64), deadzone: (f64, f64), smoothing: f64, dt: f64) {
+        let (vw, vh) = (self.view_width(), self.view_height());
+        let wanted_x = self.x + push(target.0 - (self.x + vw / 2.0), deadzone.0 / 2.0);
+        let wanted_y = self.y + push(target.1 - (self.y + vh / 2.0), deadzone.1 / 2.0);
+        let t = if smoothing > 0.0 { 1.0 - (-smoothing * dt).exp() } else { 1.0 };
+        self.x += (wanted_x - self.x) * t;
+        self.y += (wanted_y - self.y) * t;
+    }
+
+    // Where a spot on the screen (the mouse, say) is in the world: the
+    // other way round to drawing.
+    pub fn to_world(&self, at: ScreenPos) -> WorldPos {
+        WorldPos::new(self.x + at.x / self.zoom, self.y + at.y / self.zoom)
+    }
+
+    // And the other way: where a spot in the world is on the screen.
+    pub fn to_screen(&self, at: WorldPos) -> ScreenPos {
+        ScreenPos::new((at.x - self.x) * self.zoom, (at.y - self.y) * self.zoom)
+    }
+
+    /*
+     * Don't look past the edge of the world. If the world is smaller than
+     * the view along some axis there's no way to fill the view, so it's
+     * centred instead.
+     */
+    pub fn clamp_to(&mut self, world_width: f64, world_height: f64) {
+        let clamp = |v: f64, view: f64, world: f64| {
+            if world <= view { (world - view) / 2.0 } else { v.max(0.0).min(world - view) }
+        };
+        self.x = clamp(self.x, self.view_width(), world_width);
+        self.y = clamp(self.y, self.view_height(), world_height);
+    }
+}
+
+/*
+ * Letterboxing: fitting a view of one shape into a window of another
+ * without stretching it. We scale the view up (or down) as far as it'll go
+ * while still fitting both ways, and centre it, which leaves black bars
+ * either side or above and below. Returns where the view's corner goes in
+ * the window, and the scale.
+ */
+pub fn letterbox(window: (f64, f64), view: (f64, f64)) -> (f64, f64, f64) {
+    let scale = (window.0 / view.0).min(window.1 / view.1);
+    ((window.0 - view.0 * scale) / 2.0, (window.1 - view.1 * scale) / 2.0, scale)
+}
+
+// How far something `offset` from the middle of the view is past the edge
+// of a deadzone reaching `half` either way. Inside it, that's nothing.
+fn push(offset: f64, half: f64) -> f64 {
+    if offset > half {
+        offset - half
+    } else if offset < -half {
+        offset + half
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_fits_without_stretching() {
+        // A 4:3 view in a 16:9 window gets bars down the sides...
+        assert_eq!(letterbox((1920.0, 1080.0), (640.0, 480.0)), (240.0, 0.0, 2.25));
+        // ...and in a tall window, bars top and bottom.
+        assert_eq!(letterbox((640.0, 960.0), (640.0, 480.0)), (0.0, 240.0, 1.0));
+    }
+
+    #[test]
+    fn screen_spots_are_found_in_the_world() {
+        let mut camera = Camera::new(640.0, 480.0);
+        camera.x = 100.0;
+        camera.y = 50.0;
+        camera.zoom = 2.0;
+        assert_eq!(camera.to_world(ScreenPos::new(0.0, 0.0)), WorldPos::new(100.0, 50.0));
+        assert_eq!(camera.to_world(ScreenPos::new(640.0, 480.0)), WorldPos::new(420.0, 290.0));
+        assert_eq!(camera.to_screen(WorldPos::new(420.0, 290.0)), ScreenPos::new(640.0, 480.0));
+        // Put somewhere on the screen, a spot's seen right there.
+        camera.put(WorldPos::new(500.0, 500.0), ScreenPos::new(40.0, 20.0));
+        assert_eq!(camera.to_screen(WorldPos::new(500.0, 500.0)), ScreenPos::new(40.0, 20.0));
+    }
+
+    #[test]
+    fn snapping_stops_on_the_screens_pixels() {
+        let mut camera = Camera::new(640.0, 480.0);
+        assert_eq!(camera.snap((10.3, 20.8), 1.0), (10.0, 21.0));
+        assert_eq!(camera.snap((10.3, 20.8), 2.0), (10.5, 21.0));
+        camera.zoom = 2.0;
+        assert_eq!(camera.snap((10.3, 20.8), 2.0), (10.25, 20.75));
+    }
+
+    #[test]
+    fn deadzone_holds_still() {
+        let mut camera = Camera::new(640.0, 480.0);
+        // The middle of the view is (320, 240); 50 off is inside a 200x100
+        // deadzone.
+        camera.follow((370.0, 280.0), (200.0, 100.0), 0.0, 1.0 / 60.0);
+        assert_eq!((camera.x, camera.y), (0.0, 0.0));
+    }
+
+    #[test]
+    fn pushing_the_deadzone_moves_the_camera() {
+        let mut camera = Camera::new(640.0, 480.0);
+        // 130 right of the middle is 30 past the deadzone's edge.
+        camera.follow((450.0, 240.0), (200.0, 100.0), 0.0, 1.0 / 60.0);
+        assert_eq!((camera.x, camera.y), (30.0, 0.0));
+    }
+
+    #[test]
+    fn smoothing_is_the_same_at_any_update_rate() {
+        let run = |steps: u32| {
+            let mut camera = Camera::new(640.0, 480.0);
+            for _ in 0..steps {
+                camera.follow((1000.0, 240.0), (0.0, 0.0), 5.0, 0.5 / steps as f64);
+            }
+            camera.x
+        };
+        let (slow, fast) = (run(30), run(144));
+        assert!((slow - fast).abs() < 1e-9);
+        // Glided some of the way, but not all of it.
+        assert!(slow > 0.0 && slow < 680.0);
+    }
+
+    #[test]
+    fn stays_inside_the_world() {
+        let mut camera = Camera::new(640.0, 480.0);
+        camera.centre_on(WorldPos::new(10.0, 10.0));
+        camera.clamp_to(1280.0, 960.0);
+        assert_eq!((camera.x, camera.y), (0.0, 0.0));
+        camera.centre_on(WorldPos::new(1270.0, 950.0));
+        camera.clamp_to(1280.0, 960.0);
+        assert_eq!((camera.x, camera.y), (640.0, 480.0));
+        // A world narrower than the window sits in the middle of it.
+        camera.clamp_to(320.0, 960.0);
+        assert_eq!(camera.x, -160.0);
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor_in_place() {
+        let mut camera = Camera::new(640.0, 480.0);
+        camera.x = 100.0;
+        camera.y = 100.0;
+        let anchor = WorldPos::new(300.0, 200.0);
+        let before = camera.to_screen(anchor);
+        camera.set_zoom(2.0, anchor);
+        assert_eq!(camera.to_screen(anchor), before);
+        assert_eq!(camera.view_width(), 320.0);
+        // And there are limits.
+        camera.set_zoom(100.0, anchor);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+    }
+}
@@ -0,0 +1,430 @@
+/*
+ * Screenshots, and clips. F12 saves whatever's in the window as a PNG in
+ * screenshots/, named after when it was taken, like
+ * screenshot-2014-11-02_21-04-33.png, so they sort in order and never
+ * overwrite each other. (Two in the same second get a -2, -3 and so on on
+ * the end.)
+ *
+ * Getting the picture means asking OpenGL for it back: glReadPixels copies
+ * what's been drawn so far this frame off the graphics card, as four bytes
+ * a pixel, red, green, blue and alpha. That has to happen after the frame's
+ * been drawn but before the window shows it, which is why game::run does it
+ * straight after rendering.
+ *
+ * OpenGL counts rows from the bottom of the window up, the way graphs do,
+ * and pictures count them from the top down, so the rows get turned over
+ * on the way out. The PNG itself is written by the png crate.
+ *
+ * A clip is the last few seconds of the game as an animated GIF, for
+ * showing people. The game can't know in advance which bit you'll want,
+ * so it's always keeping the last CLIP_SECONDS: every so often (CLIP_FPS
+ * times a second, which is plenty for a GIF and far less work than every
+ * frame) the window gets read back, shrunk to about CLIP_WIDTH pixels
+ * across, and put in a ring buffer - a queue where, once it's full, each
+ * new frame pushes out the oldest. F2 saves what's in it to clips/.
+ *
+ * Turning a few seconds of frames into a GIF is slow, because a GIF can
+ * only have 256 colors in each frame and picking the best 256 takes some
+ * thinking about. Doing that while the game waits would freeze it for a
+ * second or two, so it happens on a thread of its own, and the game
+ * carries on. The gif crate does the encoding.
+ */
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gif;
+use gl;
+use png;
+
+// How much a clip holds, how many frames a second it keeps, and about how
+// wide they are.
+pub const CLIP_SECONDS: f64 = 5.0;
+pub const CLIP_FPS: u32 = 15;
+pub const CLIP_WIDTH: u32 = 320;
+
+// A picture as four bytes a pixel, top row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    // Rows the way OpenGL hands them over, bottom row first.
+    pub fn from_gl_rows(width: u32, height: u32, rows: &[u8]) -> Image {
+        let stride = width as usize * 4;
+        let mut pixels = Vec::with_capacity(rows.len());
+        for row in rows.chunks(stride).rev() {
+            pixels.extend_from_slice(row);
+        }
+        Image { width: width, height: height, pixels: pixels }
+    }
+
+    /*
+     * A smaller copy, `factor` times narrower and shorter. Each pixel of
+     * the copy is the average of the factor by factor square it stands for,
+     * which looks much better than picking one of them: thin lines don't
+     * flicker in and out as they move. Anything left over at the right or
+     * the bottom edge that doesn't make a whole square is dropped.
+     */
+    pub fn shrink(&self, factor: u32) -> Image {
+        let factor = factor.max(1) as usize;
+        let (width, height) = (self.width as usize / factor, self.height as usize / factor);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 4];
+                for dy in 0..factor {
+                    let row = (y * factor + dy) * self.width as usize;
+                    for dx in 0..factor {
+                        let i = (row + x * factor + dx) * 4;
+                        for c in 0..4 {
+                            sum[c] += self.pixels[i + c] as u32;
+                        }
+                    }
+                }
+                let n = (factor * factor) as u32;
+                pixels.extend(sum.iter().map(|&s| (s / n) as u8));
+            }
+        }
+        Image { width: width as u32, height: height as u32, pixels: pixels }
+    }
+}
+
+// Where screenshots go.
+pub fn default_dir() -> PathBuf {
+    PathBuf::from("screenshots")
+}
+
+/*
+ * Read back what's been drawn in the window, which is `width` by `height`
+ * pixels. This is one of the few bits of the game that talk to OpenGL
+ * directly, rather than through opengl_graphics (see shader.rs too),
+ * and calling into OpenGL is unsafe: Rust can't check what C does with the
+ * pointer we give it. What makes it all right is that the buffer is
+ * exactly big enough for what we ask for, and that PACK_ALIGNMENT 1 means
+ * OpenGL doesn't pad the rows out.
+ */
+pub fn read_window(width: u32, height: u32) -> Image {
+    let mut rows = vec![0u8; width as usize * height as usize * 4];
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE,
+                       rows.as_mut_ptr() as *mut _);
+    }
+    Image::from_gl_rows(width, height, &rows)
+}
+
+pub fn write_png<W: Write>(image: &Image, out: W) -> Result<(), String> {
+    let mut encoder = png::Encoder::new(out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .map_err(|e| e.to_string())
+}
+
+pub fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let file = File::create(path)
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+    write_png(image, BufWriter::new(file))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+/*
+ * `seconds` since 1970 as a date and time, like 2014-11-02_21-04-33, in UTC
+ * (working out the local time zone is a whole other job). The days-to-date
+ * part is Howard Hinnant's civil_from_days, which counts in 400-year eras
+ * starting from the 1st of March, so leap days fall at the end of a year.
+ */
+pub fn timestamp(seconds: u64) -> String {
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+                       - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+            year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+// A name in `dir` for a file taken at `now` that isn't there yet.
+pub fn unused_path(dir: &Path, prefix: &str, extension: &str, now: SystemTime) -> PathBuf {
+    let seconds = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let stem = format!("{}-{}", prefix, timestamp(seconds));
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, n, extension));
+        n += 1;
+    }
+    path
+}
+
+/*
+ * Write `frames` out as a GIF that loops forever, each frame shown for
+ * `delay` hundredths of a second (GIFs count in hundredths). The frames
+ * all have to be the same size as the first; a window resized mid-clip
+ * leaves some that aren't, and those get left out.
+ */
+pub fn write_gif<W: Write>(frames: &[Image], delay: u16, out: W) -> Result<(), String> {
+    let (width, height) = match frames.first() {
+        Some(first) => (first.width, first.height),
+        None => return Err("there's nothing in the clip yet".to_string()),
+    };
+    let mut encoder = gif::Encoder::new(out, width as u16, height as u16, &[])
+        .map_err(|e| e.to_string())?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+    for image in frames.iter().filter(|i| (i.width, i.height) == (width, height)) {
+        // from_rgba_speed picks the 256 colors. 10 is its middling speed:
+        // 1 is best and slowest, 30 fastest and worst.
+        let mut pixels = image.pixels.clone();
+        let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut pixels, 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub fn save_gif(frames: &[Image], delay: u16, path: &Path) -> Result<(), String> {
+    let file = File::create(path)
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+    write_gif(frames, delay, BufWriter::new(file))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+/*
+ * The ring buffer of recent frames, and the clip being saved, if there is
+ * one. tick says when it's time for another frame; the one doing the
+ * drawing (game::run) then reads the window back and hands it to push.
+ * Game owns it, so it keeps recording from one screen to the next.
+ */
+pub struct ClipRecorder {
+    frames: VecDeque<Image>,
+    capacity: usize,
+    // Seconds between frames, and since the last one.
+    interval: f64,
+    waited: f64,
+    // The thread saving a clip says how it went down here.
+    saving: Option<Receiver<Result<PathBuf, String>>>,
+}
+
+impl ClipRecorder {
+    pub fn new() -> ClipRecorder {
+        let capacity = (CLIP_SECONDS * CLIP_FPS as f64).ceil() as usize;
+        ClipRecorder {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            interval: 1.0 / CLIP_FPS as f64,
+            waited: 0.0,
+            saving: None,
+        }
+    }
+
+    // `seconds` more have gone by: whether it's time for another frame.
+    pub fn tick(&mut self, seconds: f64) -> bool {
+        self.waited += seconds;
+        if self.waited < self.interval { return false }
+        // Not back to zero: then a frame that comes a bit late doesn't
+        // push all the rest later too. But never more than one frame
+        // behind, or a long pause would mean a burst of them after.
+        self.waited = (self.waited - self.interval).min(self.interval);
+        true
+    }
+
+    // A frame the size the window is, for shrinking down to size and
+    // keeping.
+    pub fn push(&mut self, image: &Image) {
+        let factor = (image.width + CLIP_WIDTH - 1) / CLIP_WIDTH;
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(image.shrink(factor));
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /*
+     * F2: save what's in the buffer to a GIF in `dir`, off on its own
+     * thread. The frames are copied out for it, so recording carries on
+     * meanwhile. One clip at a time: asking again while one's being saved
+     * is turned down.
+     */
+    pub fn save(&mut self, dir: &Path) -> Result<(), String> {
+        if self.saving.is_some() {
+            return Err("still saving the last clip".to_string());
+        }
+        if self.frames.is_empty() {
+            return Err("there's nothing in the clip yet".to_string());
+        }
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("couldn't make {}: {}", dir.display(), e))?;
+        let path = unused_path(dir, "clip", "gif", SystemTime::now());
+        let frames: Vec<Image> = self.frames.iter().cloned().collect();
+        let delay = (100 / CLIP_FPS) as u16;
+        let (done, result) = mpsc::channel();
+        thread::spawn(move || {
+            let saved = save_gif(&frames, delay, &path).map(|_| path);
+            // If nobody's listening any more, the game's gone; never mind.
+            let _ = done.send(saved);
+        });
+        self.saving = Some(result);
+        Ok(())
+    }
+
+    // Whether the clip being saved has finished, and how it went. Only
+    // says so once.
+    pub fn finished(&mut self) -> Option<Result<PathBuf, String>> {
+        let result = match self.saving {
+            Some(ref saving) => match saving.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err("the thread saving it died".to_string()),
+            },
+            None => return None,
+        };
+        self.saving = None;
+        Some(result)
+    }
+}
+
+impl Default for ClipRecorder {
+    fn default() -> ClipRecorder {
+        ClipRecorder::new()
+    }
+}
+
+// Where clips go.
+pub fn clips_dir() -> PathBuf {
+    PathBuf::from("clips")
+}
+
+// F12: save the window as it is now, and say where it went.
+pub fn screenshot(width: u32, height: u32) -> Result<PathBuf, String> {
+    let dir = default_dir();
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("couldn't make {}: {}", dir.display(), e))?;
+    let path = unused_path(&dir, "screenshot", "png", SystemTime::now());
+    save_png(&read_window(width, height), &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Read;
+    use std::time::Duration;
+
+    #[test]
+    fn rows_are_turned_the_right_way_up() {
+        // Two pixels across, two down: OpenGL gives the bottom row first.
+        let rows = [1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4];
+        let image = Image::from_gl_rows(2, 2, &rows);
+        assert_eq!(image.pixels, vec![3, 3, 3, 3, 4, 4, 4, 4, 1, 1, 1, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn shrinking_averages_squares() {
+        // Four pixels across and two down, shrunk by two: two pixels, each
+        // the average of a two by two square.
+        let mut pixels = Vec::new();
+        for &v in [0u8, 100, 200, 40, 20, 120, 220, 60].iter() {
+            pixels.extend_from_slice(&[v, v, v, 255]);
+        }
+        let image = Image { width: 4, height: 2, pixels: pixels };
+        let small = image.shrink(2);
+        assert_eq!((small.width, small.height), (2, 1));
+        assert_eq!(small.pixels, vec![60, 60, 60, 255, 130, 130, 130, 255]);
+        assert_eq!(image.shrink(1), image);
+    }
+
+    #[test]
+    fn the_clip_keeps_the_last_few_seconds() {
+        let mut clip = ClipRecorder::new();
+        let frame = Image { width: 640, height: 480, pixels: vec![0; 640 * 480 * 4] };
+        // A minute at 60 frames a second, of which CLIP_FPS a second get
+        // kept, until there are CLIP_SECONDS' worth.
+        let mut kept = 0;
+        for _ in 0..3600 {
+            if clip.tick(1.0 / 60.0) {
+                clip.push(&frame);
+                kept += 1;
+            }
+        }
+        assert!((kept as i32 - 60 * CLIP_FPS as i32).abs() <= 1);
+        assert_eq!(clip.len(), (CLIP_SECONDS * CLIP_FPS as f64) as usize);
+        // Shrunk down to CLIP_WIDTH across.
+        assert_eq!(clip.frames[0].width, CLIP_WIDTH);
+    }
+
+    #[test]
+    fn clips_are_saved_in_the_background() {
+        let dir = env::temp_dir().join(format!("jumpy-clips-{}", ::std::process::id()));
+        let mut clip = ClipRecorder::new();
+        assert!(clip.save(&dir).is_err());
+        for shade in 0..3 {
+            clip.push(&Image { width: 8, height: 8, pixels: vec![shade * 100; 8 * 8 * 4] });
+        }
+        clip.save(&dir).unwrap();
+        assert!(clip.save(&dir).is_err());
+        let mut result = None;
+        for _ in 0..500 {
+            result = clip.finished();
+            if result.is_some() { break }
+            thread::sleep(::std::time::Duration::from_millis(10));
+        }
+        let path = result.unwrap().unwrap();
+        let mut contents = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+        assert_eq!(&contents[..6], b"GIF89a");
+        assert!(clip.finished().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn timestamps_are_dates() {
+        assert_eq!(timestamp(0), "1970-01-01_00-00-00");
+        assert_eq!(timestamp(1414962273), "2014-11-02_21-04-33");
+        // The day after a leap day.
+        assert_eq!(timestamp(951868800), "2000-03-01_00-00-00");
+    }
+
+    #[test]
+    fn pngs_start_like_pngs() {
+        let image = Image { width: 1, height: 1, pixels: vec![255, 0, 0, 255] };
+        let mut out = Vec::new();
+        write_png(&image, &mut out).unwrap();
+        assert_eq!(&out[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn screenshots_do_not_overwrite_each_other() {
+        let dir = env::temp_dir().join(format!("jumpy-capture-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let when = UNIX_EPOCH + Duration::from_secs(1414962273);
+        let first = unused_path(&dir, "screenshot", "png", when);
+        assert_eq!(first, dir.join("screenshot-2014-11-02_21-04-33.png"));
+        File::create(&first).unwrap();
+        assert_eq!(unused_path(&dir, "screenshot", "png", when),
+                   dir.join("screenshot-2014-11-02_21-04-33-2.png"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
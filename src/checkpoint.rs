@@ -0,0 +1,172 @@
+/*
+ * Checkpoints: flags partway through a level. Touch one and it lights up,
+ * and the game as it is right then is kept, so that losing a life puts you
+ * back there rather than all the way back at the spawn. A level lists
+ * where its flags stand, by their middles:
+ *
+ *     "checkpoints": [[300.0, 360.0], [700.0, 120.0]]
+ *
+ * What's kept is a save (see save.rs), the same snapshot F5 writes out,
+ * turned into JSON the same way, only held on to instead of written to a
+ * file. Dying loads it back, the way F9 does, so everything goes back to
+ * how it was when you touched the flag: the score, the clock, the pickups
+ * and enemies and doors, and what you were carrying. Except the lives, or
+ * dying would cost you nothing, and the health, which comes back in full
+ * the way it always has.
+ *
+ * It's the last flag you touched that counts, whichever it was, and it
+ * lasts until the level starts over (a reset, or the next level). Touching
+ * the one you're already on again doesn't save again, so standing on it
+ * doesn't keep you saving sixty times a second.
+ */
+use graphics::Context;
+
+use app::App;
+use audio::Sound;
+use collision::Aabb;
+use entity::{Entity, Status, WorldInfo};
+use renderer::Renderer;
+use save;
+
+// How big a flag is: a thin pole, and the flag up the top of it.
+pub const FLAG_WIDTH: f64 = 16.0;
+pub const FLAG_HEIGHT: f64 = 32.0;
+const POLE_WIDTH: f64 = 3.0;
+
+// The checkpoint you're on: where its flag is, and the game as it was when
+// you touched it, as JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub at: (f64, f64),
+    pub state: String,
+}
+
+/*
+ * A flag, in the level's entities. You walk through it, like an NPC. It
+ * works out for itself whether it's lit, from which checkpoint WorldInfo
+ * says you're on, so nothing has to go round telling the others they've
+ * gone out when you touch a new one.
+ */
+pub struct Flag {
+    pub bounds: Aabb,
+    pub at: (f64, f64),
+    pub color: [f32; 4],
+    pub lit_color: [f32; 4],
+    pub lit: bool,
+}
+
+impl Flag {
+    pub fn at(x: f64, y: f64, color: [f32; 4], lit_color: [f32; 4]) -> Flag {
+        Flag {
+            bounds: Aabb::new(x - FLAG_WIDTH / 2.0, y - FLAG_HEIGHT / 2.0, FLAG_WIDTH, FLAG_HEIGHT),
+            at: (x, y),
+            color: color,
+            lit_color: lit_color,
+            lit: false,
+        }
+    }
+}
+
+impl Entity for Flag {
+    fn update(&mut self, _: f64, world: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        self.lit = world.checkpoint == Some(self.at);
+        Status::Alive
+    }
+
+    // The pole down the left, and the flag off it, which is only in color
+    // once it's lit.
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.rect(context, b.x, b.y, POLE_WIDTH, b.h, self.color);
+        let flag = if self.lit { self.lit_color } else { self.color };
+        r.rect(context, b.x + POLE_WIDTH, b.y, b.w - POLE_WIDTH, b.h / 2.0, flag);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn sprite(&self) -> Option<&'static str> {
+        Some(if self.lit { "checkpoint_lit" } else { "checkpoint" })
+    }
+
+    fn checkpoint(&self) -> Option<(f64, f64)> { Some(self.at) }
+}
+
+// Once a tick: if either player's touching a flag that isn't the one
+// you're on, save the game there.
+pub fn reach(app: &mut App) {
+    let boxes = app.local_boxes();
+    let touched = app.entities.iter()
+        .filter(|e| boxes.iter().any(|b| b.intersects(&e.bounds())))
+        .filter_map(|e| e.checkpoint())
+        .next();
+    let at = match touched {
+        Some(at) => at,
+        None => return,
+    };
+    if app.checkpoint.as_ref().map_or(false, |c| c.at == at) { return }
+    match save::to_json(&app.snapshot()) {
+        Ok(state) => {
+            info!("Checkpoint! Losing a life comes back to here");
+            app.checkpoint = Some(Checkpoint { at: at, state: state });
+            app.play(Sound::Pickup);
+        }
+        Err(e) => error!("Couldn't save at the checkpoint: {}", e),
+    }
+}
+
+/*
+ * Back to the checkpoint you're on, having lost a life, keeping the lives
+ * you've got left. Whether there was one to go back to: if there wasn't,
+ * or it won't load (which it always should, having come from this game),
+ * the game's as it was and it's back to the spawn as usual.
+ */
+pub fn respawn(app: &mut App) -> bool {
+    // Loading starts the level over, which would forget the checkpoint it's
+    // loading, so it comes out for the duration. It would set the ticks
+    // back to 0 as well, and a replay goes by those, so they're kept too,
+    // and the waves would start over from the first, so they are as well.
+    let checkpoint = match app.checkpoint.take() {
+        Some(checkpoint) => checkpoint,
+        None => return false,
+    };
+    let (lives, ticks, waves) = (app.lives, app.ticks, app.waves);
+    let loaded = save::from_json(&checkpoint.state).and_then(|snapshot| app.restore(&snapshot));
+    app.checkpoint = Some(checkpoint);
+    app.ticks = ticks;
+    match loaded {
+        Ok(()) => {
+            app.lives = lives;
+            app.waves = waves;
+            true
+        }
+        Err(e) => {
+            error!("Couldn't go back to the checkpoint: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::Recorder;
+
+    #[test]
+    fn flags_light_up_for_the_checkpoint_youre_on() {
+        let world = |checkpoint| WorldInfo {
+            player: Aabb::new(0.0, 0.0, 10.0, 10.0), width: 640.0, height: 480.0,
+            checkpoint: checkpoint,
+        };
+        let mut flag = Flag::at(100.0, 50.0, [0.5; 4], [1.0; 4]);
+        assert_eq!(flag.bounds, Aabb::new(92.0, 34.0, 16.0, 32.0));
+        flag.update(0.1, &world(Some((100.0, 50.0))), &mut Vec::new());
+        assert!(flag.lit);
+        let context = Context::new_abs(640.0, 480.0);
+        let mut r = Recorder::new();
+        flag.draw(&context, &mut r);
+        assert_eq!(r.rects_in([1.0; 4]), vec![[95.0, 34.0, 13.0, 16.0]]);
+        // Touching another one puts this one out.
+        flag.update(0.1, &world(Some((300.0, 50.0))), &mut Vec::new());
+        assert!(!flag.lit);
+    }
+}
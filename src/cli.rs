@@ -0,0 +1,372 @@
+/*
+ * Command line flags. There are crates that'll do this for you with all the
+ * bells and whistles, but we only have a couple of flags, and walking over
+ * the arguments by hand shows there's no magic involved.
+ */
+use std::path::PathBuf;
+
+use bot::Source;
+use dump::Destination;
+use generate::{MAX_DIFFICULTY, MIN_DIFFICULTY};
+use log::{self, Level, Target};
+
+pub const USAGE: &'static str = "\
+Usage: game [options]
+
+Options:
+    --ups <n>        Updates (simulation steps) per second [default: ups in
+                     config.json]
+    --max-fps <n>    Cap on frames drawn per second [default: max_fps in
+                     config.json]
+    --log-level <l>  How chatty to be: error, warn, info, debug or trace
+                     [default: info]. Parts of the game can have their
+                     own, like info,input=debug,net=trace; the parts are
+                     input, render, collision and net
+    --log-file <f>   Also write the log to this file
+    --level <f>      Play just this level file, rather than everything in
+                     levels/
+    --generate <d>   Play levels made up from the seed instead, starting on
+                     difficulty d, from 1 to 10; see src/generate.rs
+    --export <dir>   With --generate, write the levels into this directory
+                     as level files too
+    --width <n>      Window width in pixels [default: from config.json]
+    --height <n>     Window height in pixels [default: from config.json]
+    --fullscreen     Fill the screen instead of opening a window
+    --seed <n>       Seed for the random numbers, so runs can be repeated
+    --headless       No window at all: play a few seconds of the game with
+                     nobody at the controls, log how it went, and stop
+    --script <f>     With --headless, press keys when this file says to
+    --bench <n>      No window either: run n updates as fast as they'll
+                     go, and print how long each part of them took
+    --bench-render   With --bench, draw a frame after every update too
+    --replay <f>     Play back a recorded game (replay.txt is the last
+                     one) instead of listening to the keyboard
+    --coop           Two players on one keyboard: the second one moves
+                     with WASD and expands with E
+    --host           Wait for a second player to join over the network
+                     (on port 7777)
+    --connect <a>    Join a game being hosted at this address, e.g.
+                     192.168.1.5 or 192.168.1.5:7777
+    --spectate       Let people watch the game over a WebSocket (on
+                     port 7778); see spectate.html
+    --lua <f>        Run this Lua script alongside the game (needs the lua
+                     feature); see example.lua
+    --dump-state <f> Write the whole game's state as JSON, a line at a
+                     time, to this file (or to stdout, given stdout)
+    --dump-interval <n>
+                     With --dump-state, only every n ticks [default: 1]
+    --bot <s>        Take commands like \"move up\" and \"expand\" a line at
+                     a time from another program, on stdin (given stdin)
+                     or from a TCP port on this computer; see src/bot.rs
+    --inspector      Open a second window listing everything in the level,
+                     where it is and how fast it's going, to change as it
+                     plays; see src/inspector.rs";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    // None means go with the config file, for both of these.
+    pub ups: Option<u64>,
+    pub max_fps: Option<u64>,
+    pub log_level: Level,
+    // Parts of the game with a log level of their own.
+    pub log_targets: Vec<(Target, Level)>,
+    pub log_file: Option<PathBuf>,
+    pub level: Option<PathBuf>,
+    // The difficulty --generate starts on, and where --export writes.
+    pub generate: Option<u32>,
+    pub export: Option<PathBuf>,
+    // These two are None unless given, so the config file gets a say.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub script: Option<PathBuf>,
+    // How many updates --bench runs, and whether it draws as well.
+    pub bench: Option<u64>,
+    pub bench_render: bool,
+    pub replay: Option<PathBuf>,
+    pub coop: bool,
+    pub host: bool,
+    pub connect: Option<String>,
+    pub spectate: bool,
+    pub lua: Option<PathBuf>,
+    // Where --dump-state writes, and how often, if it's been given.
+    pub dump_state: Option<Destination>,
+    pub dump_interval: Option<u64>,
+    pub bot: Option<Source>,
+    pub inspector: bool,
+}
+
+impl Default for Options {
+    // Nothing given, so nearly everything's up to the config file.
+    fn default() -> Options {
+        Options {
+            ups: None,
+            max_fps: None,
+            log_level: Level::Info,
+            log_targets: Vec::new(),
+            log_file: None,
+            level: None,
+            generate: None,
+            export: None,
+            width: None,
+            height: None,
+            fullscreen: false,
+            seed: None,
+            headless: false,
+            script: None,
+            bench: None,
+            bench_render: false,
+            replay: None,
+            coop: false,
+            host: false,
+            connect: None,
+            spectate: false,
+            lua: None,
+            dump_state: None,
+            dump_interval: None,
+            bot: None,
+            inspector: false,
+        }
+    }
+}
+
+/*
+ * Takes the arguments *without* the program name (so std::env::args()
+ * .skip(1)), which also makes it easy to feed in a made-up list in tests.
+ */
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--ups" => options.ups = Some(positive(&arg, args.next())?),
+            "--max-fps" => options.max_fps = Some(positive(&arg, args.next())?),
+            "--log-level" => {
+                let (level, targets) = levels(&arg, args.next())?;
+                options.log_level = level;
+                options.log_targets = targets;
+            }
+            "--log-file" => match args.next() {
+                Some(path) => options.log_file = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a file name", arg)),
+            },
+            "--level" => match args.next() {
+                Some(path) => options.level = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a file name", arg)),
+            },
+            "--generate" => match args.next().as_ref().and_then(|v| v.parse().ok()) {
+                Some(d) if d >= MIN_DIFFICULTY && d <= MAX_DIFFICULTY => {
+                    options.generate = Some(d)
+                }
+                _ => return Err(format!("{} needs a difficulty from {} to {}",
+                                        arg, MIN_DIFFICULTY, MAX_DIFFICULTY)),
+            },
+            "--export" => match args.next() {
+                Some(path) => options.export = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a directory", arg)),
+            },
+            "--width" => options.width = Some(pixels(&arg, args.next())?),
+            "--height" => options.height = Some(pixels(&arg, args.next())?),
+            "--fullscreen" => options.fullscreen = true,
+            // Any number will do for a seed, even 0.
+            "--seed" => match args.next().as_ref().and_then(|v| v.parse().ok()) {
+                Some(seed) => options.seed = Some(seed),
+                None => return Err(format!("{} needs a number", arg)),
+            },
+            "--headless" => options.headless = true,
+            "--script" => match args.next() {
+                Some(path) => options.script = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a file name", arg)),
+            },
+            "--bench" => options.bench = Some(positive(&arg, args.next())?),
+            "--bench-render" => options.bench_render = true,
+            "--replay" => match args.next() {
+                Some(path) => options.replay = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a file name", arg)),
+            },
+            "--coop" => options.coop = true,
+            "--host" => options.host = true,
+            "--connect" => match args.next() {
+                Some(address) => options.connect = Some(address),
+                None => return Err(format!("{} needs an address", arg)),
+            },
+            "--spectate" => options.spectate = true,
+            "--lua" => match args.next() {
+                Some(path) => options.lua = Some(PathBuf::from(path)),
+                None => return Err(format!("{} needs a file name", arg)),
+            },
+            "--dump-state" => match args.next() {
+                Some(to) => options.dump_state = Some(Destination::parse(&to)),
+                None => return Err(format!("{} needs a file name, or stdout", arg)),
+            },
+            "--dump-interval" => options.dump_interval = Some(positive(&arg, args.next())?),
+            "--bot" => match args.next().as_ref().and_then(|s| Source::parse(s)) {
+                Some(source) => options.bot = Some(source),
+                None => return Err(format!("{} needs stdin or a port number", arg)),
+            },
+            "--inspector" => options.inspector = true,
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    // There's only room for one second player.
+    let seconds = [options.coop, options.host, options.connect.is_some()];
+    if seconds.iter().filter(|&&s| s).count() > 1 {
+        return Err("pick one of --coop, --host and --connect".to_string());
+    }
+    if options.generate.is_some() && options.level.is_some() {
+        return Err("pick one of --level and --generate".to_string());
+    }
+    if options.export.is_some() && options.generate.is_none() {
+        return Err("--export needs --generate to make something to export".to_string());
+    }
+    if options.dump_interval.is_some() && options.dump_state.is_none() {
+        return Err("--dump-interval needs --dump-state to say where to".to_string());
+    }
+    Ok(options)
+}
+
+// The value following a flag, which has to be a whole number above zero;
+// zero updates per second would be a very boring game.
+fn positive(flag: &str, value: Option<String>) -> Result<u64, String> {
+    match value.as_ref().and_then(|v| v.parse().ok()) {
+        Some(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} needs a number greater than zero", flag)),
+    }
+}
+
+// A window size: like positive, but it has to fit a u32 as well.
+fn pixels(flag: &str, value: Option<String>) -> Result<u32, String> {
+    match value.as_ref().and_then(|v| v.parse().ok()) {
+        Some(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} needs a number of pixels", flag)),
+    }
+}
+
+fn levels(flag: &str, value: Option<String>) -> Result<(Level, Vec<(Target, Level)>), String> {
+    match value.as_ref().and_then(|v| log::parse_levels(v)) {
+        Some(levels) => Ok(levels),
+        None => Err(format!("{} needs one of error, warn, info, debug or trace, or a list \
+                             like info,net=debug", flag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> ::std::vec::IntoIter<String> {
+        list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn no_flags_gives_defaults() {
+        assert_eq!(parse(args(&[])), Ok(Options::default()));
+    }
+
+    #[test]
+    fn reads_event_loop_options() {
+        let options = parse(args(&["--ups", "30", "--max-fps", "144"])).unwrap();
+        assert_eq!(options, Options { ups: Some(30), max_fps: Some(144), ..Options::default() });
+        let options = parse(args(&["--bench", "1000", "--bench-render"])).unwrap();
+        assert_eq!((options.bench, options.bench_render), (Some(1000), true));
+    }
+
+    #[test]
+    fn reads_log_options() {
+        let options = parse(args(&["--log-level", "debug", "--log-file", "game.log"])).unwrap();
+        assert_eq!(options.log_level, Level::Debug);
+        assert_eq!(options.log_file, Some(PathBuf::from("game.log")));
+        let options = parse(args(&["--log-level", "warn,input=debug"])).unwrap();
+        assert_eq!((options.log_level, options.log_targets),
+                   (Level::Warn, vec![(Target::Input, Level::Debug)]));
+    }
+
+    #[test]
+    fn reads_window_and_game_options() {
+        let options = parse(args(&["--level", "levels/level2.json", "--width", "800",
+                                   "--height", "600", "--fullscreen", "--seed", "0",
+                                   "--headless", "--script", "walk.txt",
+                                   "--replay", "replay.txt", "--coop",
+                                   "--inspector"])).unwrap();
+        assert_eq!(options, Options {
+            level: Some(PathBuf::from("levels/level2.json")),
+            width: Some(800),
+            height: Some(600),
+            fullscreen: true,
+            seed: Some(0),
+            headless: true,
+            script: Some(PathBuf::from("walk.txt")),
+            replay: Some(PathBuf::from("replay.txt")),
+            coop: true,
+            inspector: true,
+            ..Options::default()
+        });
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert!(parse(args(&["--ups"])).is_err());
+        assert!(parse(args(&["--ups", "0"])).is_err());
+        assert!(parse(args(&["--bench", "0"])).is_err());
+        assert!(parse(args(&["--max-fps", "lots"])).is_err());
+        assert!(parse(args(&["--fast"])).is_err());
+        assert!(parse(args(&["--log-level", "loud"])).is_err());
+        assert!(parse(args(&["--log-file"])).is_err());
+        assert!(parse(args(&["--level"])).is_err());
+        assert!(parse(args(&["--width", "0"])).is_err());
+        assert!(parse(args(&["--height", "-600"])).is_err());
+        assert!(parse(args(&["--seed", "random"])).is_err());
+        assert!(parse(args(&["--replay"])).is_err());
+        assert!(parse(args(&["--connect"])).is_err());
+    }
+
+    #[test]
+    fn reads_what_to_generate() {
+        let options = parse(args(&["--generate", "3", "--export", "made"])).unwrap();
+        assert_eq!((options.generate, options.export), (Some(3), Some(PathBuf::from("made"))));
+        assert!(parse(args(&["--generate", "0"])).is_err());
+        assert!(parse(args(&["--generate", "11"])).is_err());
+        assert!(parse(args(&["--generate", "2", "--level", "levels/level1.json"])).is_err());
+        assert!(parse(args(&["--export", "made"])).is_err());
+    }
+
+    #[test]
+    fn reads_network_options() {
+        assert!(parse(args(&["--host"])).unwrap().host);
+        let options = parse(args(&["--connect", "192.168.1.5"])).unwrap();
+        assert_eq!(options.connect, Some("192.168.1.5".to_string()));
+        // Only one second player at a time.
+        assert!(parse(args(&["--host", "--connect", "192.168.1.5"])).is_err());
+        assert!(parse(args(&["--coop", "--host"])).is_err());
+        // Watching goes with anything.
+        assert!(parse(args(&["--host", "--spectate"])).unwrap().spectate);
+    }
+
+    #[test]
+    fn reads_a_lua_script() {
+        let options = parse(args(&["--lua", "example.lua"])).unwrap();
+        assert_eq!(options.lua, Some(PathBuf::from("example.lua")));
+        assert!(parse(args(&["--lua"])).is_err());
+    }
+
+    #[test]
+    fn reads_where_to_dump_the_state() {
+        let options = parse(args(&["--dump-state", "stdout", "--dump-interval", "60"])).unwrap();
+        assert_eq!((options.dump_state, options.dump_interval),
+                   (Some(Destination::Stdout), Some(60)));
+        let options = parse(args(&["--dump-state", "states.jsonl"])).unwrap();
+        assert_eq!(options.dump_state, Some(Destination::File(PathBuf::from("states.jsonl"))));
+        assert!(parse(args(&["--dump-state"])).is_err());
+        assert!(parse(args(&["--dump-state", "-", "--dump-interval", "0"])).is_err());
+        assert!(parse(args(&["--dump-interval", "60"])).is_err());
+    }
+
+    #[test]
+    fn reads_where_the_bot_is() {
+        assert_eq!(parse(args(&["--bot", "stdin"])).unwrap().bot, Some(Source::Stdin));
+        assert_eq!(parse(args(&["--bot", "7779"])).unwrap().bot, Some(Source::Port(7779)));
+        assert!(parse(args(&["--bot", "over there"])).is_err());
+        assert!(parse(args(&["--bot"])).is_err());
+    }
+}
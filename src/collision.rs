@@ -0,0 +1,556 @@
+/*
+ * Collision detection between axis-aligned boxes. "Axis-aligned" just means
+ * the box is never rotated, so its edges always run parallel to the x and y
+ * axes. That makes checking whether two of them overlap really cheap: they
+ * overlap if and only if they overlap on both axes at once.
+ *
+ * The thing doing the moving can be round instead: the circle that fits in
+ * its box (see Body). Walls are always boxes, so that's circle against box,
+ * which is nearly as cheap. The closest point of a box to anything is found
+ * by clamping the thing's x and y to the box's edges, and a circle overlaps
+ * the box if that point is closer to its centre than its radius.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl Aabb {
+    pub fn new(x: f64, y: f64, w: f64, h: f64) -> Aabb {
+        Aabb { x: x, y: y, w: w, h: h }
+    }
+
+    pub fn right(&self) -> f64 { self.x + self.w }
+    pub fn bottom(&self) -> f64 { self.y + self.h }
+
+    // Strict inequalities on purpose: two boxes that are merely touching
+    // (one's right edge exactly on the other's left edge) don't overlap.
+    // Otherwise a player resting flush against a wall would count as stuck
+    // inside it.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.x < other.right() && self.right() > other.x &&
+        self.y < other.bottom() && self.bottom() > other.y
+    }
+
+    // Whether `other` sits entirely inside this box. Unlike intersects this
+    // one is inclusive, so a box filling us right up to our edges counts.
+    pub fn contains(&self, other: &Aabb) -> bool {
+        other.x >= self.x && other.right() <= self.right() &&
+        other.y >= self.y && other.bottom() <= self.bottom()
+    }
+}
+
+/*
+ * What shape the moving thing is, as far as bumping into boxes goes: its
+ * whole box, or the circle that fits inside its box. A round player gets
+ * closer to walls diagonally, and slips past corners a square one would
+ * catch on.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Body {
+    Square,
+    Round,
+}
+
+// A circle's overlap gets this much leeway, squared. Sliding a circle up to
+// a box uses a square root, which can land a hair short of touching; without
+// this, the next move would count that as already overlapping and let us
+// straight through (see sweep).
+const ROUND_SLACK: f64 = 1e-9;
+
+// The circle in `b`: its centre and radius.
+fn circle(b: &Aabb) -> (f64, f64, f64) {
+    (b.x + b.w / 2.0, b.y + b.h / 2.0, b.w.min(b.h) / 2.0)
+}
+
+// How far `v` is outside `low..high`, or 0 inside it.
+fn outside(v: f64, low: f64, high: f64) -> f64 {
+    if v < low { low - v } else if v > high { v - high } else { 0.0 }
+}
+
+// Whether a `body` shaped `mover` overlaps `other`. Touching still isn't
+// overlapping, like Aabb::intersects.
+pub fn overlaps(body: Body, mover: &Aabb, other: &Aabb) -> bool {
+    match body {
+        Body::Square => mover.intersects(other),
+        Body::Round => {
+            if !mover.intersects(other) { return false }
+            let (cx, cy, r) = circle(mover);
+            let dx = outside(cx, other.x, other.right());
+            let dy = outside(cy, other.y, other.bottom());
+            dx * dx + dy * dy < r * r - ROUND_SLACK
+        }
+    }
+}
+
+/*
+ * Which sides of the moving box bumped into something on this move. `right`
+ * means our right-hand edge hit a wall, i.e. we were moving right. Walking
+ * (App::update_walking) stops dead along whichever way it hit, platforming
+ * (App::update_platformer) stops falling on landing and counts as standing
+ * on the ground while the bottom's hit, and bouncing (see bounce.rs) turns
+ * the velocity round off whichever side it hit. Any hit at all sets off
+ * the bump sound too (see App::move_player).
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hits {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+
+impl Hits {
+    pub fn any(&self) -> bool {
+        self.left || self.right || self.top || self.bottom
+    }
+}
+
+/*
+ * The biggest distance we can move in one go without any risk of hopping
+ * clean over an obstacle: half of the thinnest thing in the way. If there's
+ * nothing in the way at all we can move as far as we like.
+ */
+fn max_step(obstacles: &[Aabb]) -> f64 {
+    obstacles.iter()
+        .map(|o| o.w.min(o.h) / 2.0)
+        .filter(|s| *s > 0.0)
+        .fold(::std::f64::INFINITY, |a, b| a.min(b))
+}
+
+/*
+ * Move `mover` by (dx, dy), stopping flush against anything in `obstacles`.
+ *
+ * The naive approach - add dx and dy, then check for overlap - breaks down as
+ * soon as something moves quickly. Move 200 pixels in one frame and you can
+ * start on one side of a 5 pixel wall and end up on the other side, never
+ * having overlapped it at all. This is called tunneling.
+ *
+ * So instead we chop the movement into little sub-steps, each no bigger than
+ * half the thinnest obstacle, and check after every one. We also do the x
+ * axis and then the y axis separately; that way, running diagonally into a
+ * wall stops you on one axis but lets you slide along it on the other.
+ */
+pub fn move_and_collide(mover: Aabb, dx: f64, dy: f64, obstacles: &[Aabb]) -> (Aabb, Hits) {
+    move_body(Body::Square, mover, dx, dy, obstacles)
+}
+
+// The same, for something that might be round.
+pub fn move_body(body: Body, mover: Aabb, dx: f64, dy: f64, obstacles: &[Aabb])
+                 -> (Aabb, Hits) {
+    let mut hits = Hits::default();
+    let step = max_step(obstacles);
+    let mover = sweep(body, mover, dx, true, step, obstacles, &mut hits);
+    let mover = sweep(body, mover, dy, false, step, obstacles, &mut hits);
+    (mover, hits)
+}
+
+// Sweep along a single axis; `horizontal` picks which one.
+fn sweep(body: Body,
+         mut mover: Aabb,
+         delta: f64,
+         horizontal: bool,
+         step: f64,
+         obstacles: &[Aabb],
+         hits: &mut Hits) -> Aabb {
+    if delta == 0.0 { return mover }
+
+    // If we're already overlapping something (say, expand just grew us into
+    // a wall) we ignore it, so the player can always walk back out.
+    let blocking: Vec<&Aabb> = obstacles.iter()
+        .filter(|o| !overlaps(body, &mover, o))
+        .collect();
+
+    let steps = (delta.abs() / step).ceil().max(1.0) as usize;
+    let increment = delta / steps as f64;
+
+    for _ in 0..steps {
+        if horizontal { mover.x += increment } else { mover.y += increment }
+
+        for o in blocking.iter() {
+            if !overlaps(body, &mover, o) { continue }
+            // We've gone into something: push back out so we're exactly
+            // touching the face we came in through, and note which side hit.
+            // A circle can come in round a corner, so it can stop short of
+            // the face; `gap` is by how much.
+            let gap = match body {
+                Body::Square => 0.0,
+                Body::Round => round_gap(&mover, o, horizontal),
+            };
+            match (horizontal, increment > 0.0) {
+                (true, true) => { mover.x = o.x - mover.w + gap; hits.right = true }
+                (true, false) => { mover.x = o.right() - gap; hits.left = true }
+                (false, true) => { mover.y = o.y - mover.h + gap; hits.bottom = true }
+                (false, false) => { mover.y = o.bottom() - gap; hits.top = true }
+            }
+            return mover;
+        }
+    }
+    mover
+}
+
+/*
+ * How far short of flush against `o` a circle in `mover` stops, moving
+ * along one axis. Lined up with the face it comes in through, it's flush,
+ * the same as a square. Off to one side, it's the corner it meets: the
+ * circle's centre is `off` past the corner across the way we're going, so
+ * along the way we're going it's Pythagoras' sqrt(r² - off²) from it, not
+ * the whole radius.
+ */
+fn round_gap(mover: &Aabb, o: &Aabb, horizontal: bool) -> f64 {
+    let (cx, cy, r) = circle(mover);
+    let off = if horizontal { outside(cy, o.y, o.bottom()) } else { outside(cx, o.x, o.right()) };
+    r - (r * r - off * off).max(0.0).sqrt()
+}
+
+/*
+ * Shove `mover` out of anything in `obstacles` it's overlapping, and say
+ * which of its sides got shoved. This is for when something other than
+ * moving put us inside a wall - expand growing the player's hitbox into one,
+ * say - since move_and_collide only stops us *going* into things.
+ *
+ * Each overlap is undone whichever way is shortest: a box that's poked 2
+ * pixels into a wall's left face and 30 into its top goes back out the left,
+ * 2 pixels, rather than being teleported over the top.
+ */
+pub fn push_out(mover: Aabb, obstacles: &[Aabb]) -> (Aabb, Hits) {
+    push_body_out(Body::Square, mover, obstacles)
+}
+
+/*
+ * The same, for something that might be round. A circle with its centre
+ * off past a box's corner goes straight away from the corner, far enough
+ * to just touch it; otherwise, it's the same as a square, since the circle
+ * touches the same faces its box does.
+ */
+pub fn push_body_out(body: Body, mut mover: Aabb, obstacles: &[Aabb]) -> (Aabb, Hits) {
+    let mut hits = Hits::default();
+    for o in obstacles.iter() {
+        if !overlaps(body, &mover, o) { continue }
+        let (cx, cy, r) = circle(&mover);
+        let (dx, dy) = (outside(cx, o.x, o.right()), outside(cy, o.y, o.bottom()));
+        if body == Body::Round && dx > 0.0 && dy > 0.0 {
+            let distance = (dx * dx + dy * dy).sqrt();
+            let (sx, sy) = (if cx < o.x { -1.0 } else { 1.0 }, if cy < o.y { -1.0 } else { 1.0 });
+            mover.x += sx * dx / distance * (r - distance);
+            mover.y += sy * dy / distance * (r - distance);
+            if sx < 0.0 { hits.right = true } else { hits.left = true }
+            if sy < 0.0 { hits.bottom = true } else { hits.top = true }
+            continue;
+        }
+        let left = mover.right() - o.x;
+        let right = o.right() - mover.x;
+        let up = mover.bottom() - o.y;
+        let down = o.bottom() - mover.y;
+        let shortest = left.min(right).min(up).min(down);
+        if shortest == left {
+            mover.x -= left;
+            hits.right = true;
+        } else if shortest == right {
+            mover.x += right;
+            hits.left = true;
+        } else if shortest == up {
+            mover.y -= up;
+            hits.bottom = true;
+        } else {
+            mover.y += down;
+            hits.top = true;
+        }
+    }
+    (mover, hits)
+}
+
+/*
+ * A quadtree, for finding what's near a box without checking everything.
+ * Checking every shot against every enemy is fine for a handful of each,
+ * but it's shots times enemies checks a tick, which gets out of hand
+ * quickly with a level full of them.
+ *
+ * The tree starts as one cell round all the boxes. Once a cell has more
+ * than QUADTREE_CAPACITY boxes in it, it's cut into four quarters, and
+ * each box that fits wholly inside a quarter goes down into it; one lying
+ * across the lines stays where it is. Asking what's touching a box then
+ * only has to look in the cells that box reaches into, and skips whole
+ * quarters of the level at a time. Plenty of boxes in exactly the same
+ * place could go on being cut up forever, so after QUADTREE_DEPTH cuts a
+ * cell just takes however many it's given.
+ *
+ * Things move, so nothing's ever taken out of a tree; it's cheap enough to
+ * build that App makes a new one every tick instead.
+ */
+const QUADTREE_CAPACITY: usize = 4;
+const QUADTREE_DEPTH: u32 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quadtree {
+    boxes: Vec<Aabb>,
+    root: Cell,
+}
+
+// A cell of the tree: its area, the boxes it keeps itself (as places in
+// the tree's list of boxes), and either no quarters or all four.
+#[derive(Clone, Debug, PartialEq)]
+struct Cell {
+    area: Aabb,
+    boxes: Vec<usize>,
+    quarters: Vec<Cell>,
+}
+
+impl Quadtree {
+    pub fn new(boxes: Vec<Aabb>) -> Quadtree {
+        let area = match boxes.split_first() {
+            Some((first, rest)) => rest.iter().fold(*first, |a, b| span(a, *b)),
+            None => Aabb::new(0.0, 0.0, 0.0, 0.0),
+        };
+        let mut root = Cell { area: area, boxes: Vec::new(), quarters: Vec::new() };
+        for i in 0..boxes.len() {
+            root.insert(&boxes, i, 0);
+        }
+        Quadtree { boxes: boxes, root: root }
+    }
+
+    pub fn len(&self) -> usize {
+        self.boxes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    // Where in the list the tree was made from every box overlapping
+    // `area` is, in the list's order.
+    pub fn query(&self, area: &Aabb) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.root.query(&self.boxes, area, &mut found);
+        found.sort();
+        found
+    }
+
+    // The boxes overlapping `area` themselves.
+    pub fn near(&self, area: &Aabb) -> Vec<Aabb> {
+        self.query(area).into_iter().map(|i| self.boxes[i]).collect()
+    }
+
+    // Every cell's area, biggest first, for F4 to draw. An empty tree has
+    // nothing to show.
+    pub fn cells(&self) -> Vec<Aabb> {
+        let mut cells = Vec::new();
+        if !self.is_empty() {
+            self.root.cells(&mut cells);
+        }
+        cells
+    }
+}
+
+impl Cell {
+    fn insert(&mut self, boxes: &[Aabb], i: usize, depth: u32) {
+        if let Some(quarter) = self.quarters.iter_mut().find(|q| q.area.contains(&boxes[i])) {
+            quarter.insert(boxes, i, depth + 1);
+            return;
+        }
+        self.boxes.push(i);
+        if self.quarters.is_empty() && self.boxes.len() > QUADTREE_CAPACITY &&
+           depth < QUADTREE_DEPTH {
+            let a = self.area;
+            let (w, h) = (a.w / 2.0, a.h / 2.0);
+            self.quarters = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)].iter()
+                .map(|&(x, y)| Cell { area: Aabb::new(a.x + x, a.y + y, w, h),
+                                      boxes: Vec::new(), quarters: Vec::new() })
+                .collect();
+            for i in self.boxes.split_off(0) {
+                self.insert(boxes, i, depth);
+            }
+        }
+    }
+
+    // A box overlapping `area` overlaps every cell it's in too, so a cell
+    // `area` doesn't reach into can't have anything for us, and nor can
+    // its quarters.
+    fn query(&self, boxes: &[Aabb], area: &Aabb, found: &mut Vec<usize>) {
+        if !self.area.intersects(area) { return }
+        found.extend(self.boxes.iter().filter(|&&i| boxes[i].intersects(area)));
+        for quarter in self.quarters.iter() {
+            quarter.query(boxes, area, found);
+        }
+    }
+
+    fn cells(&self, cells: &mut Vec<Aabb>) {
+        cells.push(self.area);
+        for quarter in self.quarters.iter() {
+            quarter.cells(cells);
+        }
+    }
+}
+
+// The smallest box containing both a and b.
+pub fn span(a: Aabb, b: Aabb) -> Aabb {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    Aabb::new(x, y, a.right().max(b.right()) - x, a.bottom().max(b.bottom()) - y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 10x10 player and a 5 pixel wide (or tall) wall 200 pixels away.
+    fn player() -> Aabb { Aabb::new(0.0, 0.0, 10.0, 10.0) }
+
+    #[test]
+    fn moving_right_stops_at_wall() {
+        let wall = Aabb::new(100.0, -50.0, 5.0, 100.0);
+        let (b, hits) = move_and_collide(player(), 200.0, 0.0, &[wall]);
+        assert_eq!(b.right(), wall.x);
+        assert_eq!(b.y, 0.0);
+        assert_eq!(hits, Hits { right: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn moving_left_stops_at_wall() {
+        let wall = Aabb::new(-100.0, -50.0, 5.0, 100.0);
+        let (b, hits) = move_and_collide(player(), -200.0, 0.0, &[wall]);
+        assert_eq!(b.x, wall.right());
+        assert_eq!(hits, Hits { left: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn moving_down_stops_at_wall() {
+        let wall = Aabb::new(-50.0, 100.0, 100.0, 5.0);
+        let (b, hits) = move_and_collide(player(), 0.0, 200.0, &[wall]);
+        assert_eq!(b.bottom(), wall.y);
+        assert_eq!(hits, Hits { bottom: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn moving_up_stops_at_wall() {
+        let wall = Aabb::new(-50.0, -100.0, 100.0, 5.0);
+        let (b, hits) = move_and_collide(player(), 0.0, -200.0, &[wall]);
+        assert_eq!(b.y, wall.bottom());
+        assert_eq!(hits, Hits { top: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn diagonal_slides_along_wall() {
+        // Running down-right into a tall wall: x stops at the wall, y keeps
+        // on going the full distance.
+        let wall = Aabb::new(100.0, -500.0, 5.0, 1000.0);
+        let (b, hits) = move_and_collide(player(), 200.0, 200.0, &[wall]);
+        assert_eq!(b.right(), wall.x);
+        assert_eq!(b.y, 200.0);
+        assert_eq!(hits, Hits { right: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn diagonal_into_corner_hits_both_sides() {
+        let wall = Aabb::new(100.0, -500.0, 5.0, 1000.0);
+        let floor = Aabb::new(-500.0, 100.0, 1000.0, 5.0);
+        let (b, hits) = move_and_collide(player(), 200.0, 200.0, &[wall, floor]);
+        assert_eq!(b.right(), wall.x);
+        assert_eq!(b.bottom(), floor.y);
+        assert_eq!(hits, Hits { right: true, bottom: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn touching_is_not_overlapping() {
+        let a = Aabb::new(0.0, 0.0, 10.0, 10.0);
+        let b = Aabb::new(10.0, 0.0, 10.0, 10.0);
+        assert!(!a.intersects(&b));
+    }
+
+    #[test]
+    fn containment() {
+        let goal = Aabb::new(100.0, 100.0, 40.0, 40.0);
+        // Sitting right next to the goal, touching its left edge: not in.
+        assert!(!goal.contains(&Aabb::new(90.0, 110.0, 10.0, 10.0)));
+        // Half in, half out: still not in.
+        assert!(!goal.contains(&Aabb::new(95.0, 110.0, 10.0, 10.0)));
+        // All the way in, even if flush against the inside edge: in.
+        assert!(goal.contains(&Aabb::new(100.0, 110.0, 10.0, 10.0)));
+        assert!(goal.contains(&Aabb::new(115.0, 115.0, 10.0, 10.0)));
+        // Bigger than the goal can't ever fit.
+        assert!(!goal.contains(&Aabb::new(95.0, 95.0, 50.0, 50.0)));
+    }
+
+    #[test]
+    fn overlaps_are_pushed_out_the_shortest_way() {
+        // Poking 3 pixels into the left of a tall wall goes back out left.
+        let wall = Aabb::new(7.0, -50.0, 5.0, 100.0);
+        let (b, hits) = push_out(player(), &[wall]);
+        assert_eq!(b, Aabb::new(-3.0, 0.0, 10.0, 10.0));
+        assert_eq!(hits, Hits { right: true, ..Hits::default() });
+        // Sitting 2 pixels down into a floor goes back up.
+        let floor = Aabb::new(-50.0, 8.0, 100.0, 5.0);
+        let (b, hits) = push_out(player(), &[floor]);
+        assert_eq!(b.bottom(), floor.y);
+        assert_eq!(hits, Hits { bottom: true, ..Hits::default() });
+        // And nothing in the way means nothing happens.
+        assert_eq!(push_out(player(), &[]), (player(), Hits::default()));
+    }
+
+    #[test]
+    fn round_bodies_catch_less_on_corners() {
+        let wall = Aabb::new(100.0, 8.0, 5.0, 100.0);
+        // A square moving right catches the top of the wall (its bottom is
+        // below the wall's top)...
+        let (b, _) = move_and_collide(player(), 200.0, 0.0, &[wall]);
+        assert_eq!(b.right(), wall.x);
+        // ...but a circle with its centre above the corner stops at the
+        // corner: 3 up from its centre, so 4 along, which is 1 short.
+        let (b, hits) = move_body(Body::Round, player(), 200.0, 0.0, &[wall]);
+        assert!((b.right() - (wall.x + 1.0)).abs() < 1e-9, "{:?}", b);
+        assert_eq!(hits, Hits { right: true, ..Hits::default() });
+        // And one that's only overlapping the wall's box in a corner gets
+        // by altogether.
+        let wall = Aabb::new(9.0, 9.0, 5.0, 5.0);
+        assert!(player().intersects(&wall) && !overlaps(Body::Round, &player(), &wall));
+        // Straight on, a circle stops flush, like a square.
+        let wall = Aabb::new(100.0, -50.0, 5.0, 100.0);
+        let (b, _) = move_body(Body::Round, player(), 200.0, 0.0, &[wall]);
+        assert_eq!(b.right(), wall.x);
+    }
+
+    #[test]
+    fn round_bodies_are_pushed_away_from_corners() {
+        // The circle's centre is at (5, 5), 3 above and 3 left of the
+        // wall's corner: 4.24 away, which is inside its radius of 5.
+        let wall = Aabb::new(8.0, 8.0, 10.0, 10.0);
+        let (b, hits) = push_body_out(Body::Round, player(), &[wall]);
+        assert!(!overlaps(Body::Round, &b, &wall));
+        assert!(b.x < 0.0 && b.y < 0.0 && (b.x - b.y).abs() < 1e-9);
+        assert_eq!(hits, Hits { right: true, bottom: true, ..Hits::default() });
+    }
+
+    #[test]
+    fn can_walk_out_of_an_overlap() {
+        let wall = Aabb::new(5.0, -50.0, 5.0, 100.0);
+        let (b, hits) = move_and_collide(player(), -20.0, 0.0, &[wall]);
+        assert_eq!(b.x, -20.0);
+        assert!(!hits.any());
+    }
+
+    #[test]
+    fn the_quadtree_finds_the_same_as_checking_everything() {
+        // A row of 10x10 boxes along the top, and a big one across the
+        // middle that won't fit in any quarter.
+        let mut boxes: Vec<Aabb> = (0..20).map(|i| Aabb::new(i as f64 * 20.0, 0.0, 10.0, 10.0))
+                                          .collect();
+        boxes.push(Aabb::new(150.0, 150.0, 100.0, 100.0));
+        boxes.push(Aabb::new(0.0, 390.0, 10.0, 10.0));
+        let tree = Quadtree::new(boxes.clone());
+        assert_eq!(tree.len(), 22);
+        assert_eq!(tree.cells()[0], Aabb::new(0.0, 0.0, 390.0, 400.0));
+        assert!(tree.cells().len() > 1);
+        let areas = [Aabb::new(15.0, -5.0, 30.0, 10.0), Aabb::new(200.0, 0.0, 100.0, 400.0),
+                     Aabb::new(-10.0, -10.0, 500.0, 500.0), Aabb::new(10.0, 10.0, 5.0, 5.0)];
+        for area in areas.iter() {
+            let everything: Vec<usize> = (0..boxes.len()).filter(|&i| boxes[i].intersects(area))
+                                                          .collect();
+            assert_eq!(tree.query(area), everything);
+        }
+        assert_eq!(tree.near(&Aabb::new(25.0, 5.0, 1.0, 1.0)), vec![boxes[1]]);
+        assert!(Quadtree::new(Vec::new()).cells().is_empty());
+    }
+}
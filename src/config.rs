@@ -0,0 +1,778 @@
+/*
+ * All the numbers that decide how the game *feels* live here rather than
+ * being scattered around the code as magic literals. They're read from a
+ * JSON file at startup, and - the fun part - re-read while the game is
+ * running whenever the file changes (or when you press F10), so you can
+ * nudge the move speed, save, and see the difference without restarting.
+ * The key bindings are in here too, so they come along with everything
+ * else.
+ *
+ * The constants that are left in the code are the ones that aren't a
+ * matter of taste: how long a tick is, how big the sprites are drawn (the
+ * pictures are made that size), how the menus are laid out.
+ *
+ * If there's no file at all, we write one out with the defaults in it, so
+ * there's always something to open up and edit.
+ */
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use input::keyboard;
+use engine::OpenGL;
+
+use audio::AudioSettings;
+use keys;
+use macros;
+use palette::{self, Palette};
+use player::{Shape, PLAYER_SIZE};
+use schema::{self, Format};
+use shader::ShaderTarget;
+use transition::Style;
+use tween::Easing;
+use watch::FileWatcher;
+
+/*
+ * #[serde(default)] means any field missing from the file is filled in from
+ * Config::default(), so a config file only needs to mention the things you
+ * actually want to change.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // The window is created once at startup, so these need a restart to
+    // take effect. The OpenGL version is written like "3.2"; if the graphics
+    // card won't do it, the game falls back to 3.2 and then 2.1, which
+    // older cards (and some virtual machines) need. Everything below them
+    // can be changed live.
+    pub window_width: u32,
+    pub window_height: u32,
+    pub window_title: String,
+    pub opengl_version: String,
+    // A fragment shader of your own to draw the game through (see
+    // shader.rs): the file it's in, or "" for none, and whether it gets
+    // the whole "scene" or just the "player". Like the window, they count
+    // from the next time it opens.
+    pub shader: String,
+    pub shader_target: ShaderTarget,
+    // How the event loop ticks, which can be changed on the options screen
+    // too. max_fps is the most frames to draw in a second, and ups how many
+    // updates to run (--max-fps and --ups beat these). With vsync on, a
+    // frame waits for the screen to be ready for it, which stops tearing;
+    // that counts from the next time the window opens. bench_mode stops
+    // the loop waiting between frames and updates at all, so they go as
+    // fast as they can, which is only any use for seeing how fast that is.
+    pub max_fps: u64,
+    pub ups: u64,
+    pub vsync: bool,
+    pub bench_mode: bool,
+    // Whether the window starts fullscreen. F11 flips it while the game's
+    // running, and so does the options screen, which writes it back here.
+    pub fullscreen: bool,
+    // Letterboxing keeps the window_width by window_height shape whatever
+    // size the window is stretched to, with black bars to fill the rest.
+    // Without it, a bigger window just shows more of the world.
+    // Letterboxed, the game's drawn at view_width by view_height and scaled
+    // to fit the window, so it looks the same on any screen, its own size
+    // included; 0, the default, is the window's size from above. Unlike the
+    // window's, these can be changed while the game's running.
+    pub letterbox: bool,
+    pub view_width: u32,
+    pub view_height: u32,
+    // Where the player starts every level, instead of the level's own spawn
+    // point. Handy for trying out one particular corner over and over;
+    // leave it null to use the levels' spawn points.
+    pub start_position: Option<[f64; 2]>,
+    // The camera. The deadzone is a box in the middle of the window the
+    // player can move around in without the camera following, and
+    // smoothing is how quickly it catches up (0 means instantly).
+    pub camera_deadzone_width: f64,
+    pub camera_deadzone_height: f64,
+    pub camera_smoothing: f64,
+    // How much one press of + or -, or one notch of the mouse wheel, zooms
+    // by.
+    pub zoom_step: f64,
+    // How fast the player moves while an arrow key is held, in world units
+    // per second. Diagonals are the same speed, not faster. Holding a key
+    // speeds the player up to that at acceleration world units per second
+    // per second, and letting go slows them down at friction. 0 for either
+    // means straight away. (See movement.rs.)
+    pub move_speed: f64,
+    pub acceleration: f64,
+    pub friction: f64,
+    // How much a press of Space grows the player, how quickly (in world
+    // units per second, on average) it shrinks back, and how it eases down
+    // (see tween.rs): "ease_out" starts off quick and slows as it gets
+    // there, and "linear" is the old steady shrink.
+    pub expand_step: f64,
+    pub expand_decay: f64,
+    pub expand_easing: Easing,
+    // How much the player squashes and stretches, as a fraction of their
+    // size: stretched out along the way they're going, going flat out, and
+    // wobbling like a jelly after puffing up. 0 for a player who's always
+    // square. Only the look changes: the hitbox stays the same.
+    pub squash_stretch: f64,
+    // The screen shake when expand fires: how far the view jumps about, in
+    // world units, and for how many seconds. Set the amplitude to 0 to turn
+    // it off.
+    pub shake_amplitude: f64,
+    pub shake_duration: f64,
+    // The confetti that flies off when expand fires, or an enemy's knocked
+    // out (see particles.rs): between particle_min_count and
+    // particle_max_count bits a burst, going particle_min_speed to
+    // particle_max_speed world units per second, each particle_size across
+    // and in particle_color, for particle_lifetime seconds. There are never
+    // more than particle_max_live at once, so mashing Space can't make the
+    // list of them grow without bound.
+    pub particle_min_count: usize,
+    pub particle_max_count: usize,
+    pub particle_min_speed: f64,
+    pub particle_max_speed: f64,
+    pub particle_lifetime: f64,
+    pub particle_size: f64,
+    pub particle_max_live: usize,
+    pub particle_color: [f32; 4],
+    // The post-processing (see postprocess.rs). bloom is how much the
+    // bright bits glow, 0 for not at all, and bloom_threshold how bright
+    // counts as bright, from 0 to 1. The flash when expand fires starts
+    // flash_strength of the way to white (0 for no flash) and fades out
+    // over flash_seconds; low_distraction turns it off too.
+    pub bloom: f64,
+    pub bloom_threshold: f64,
+    pub flash_strength: f64,
+    pub flash_seconds: f64,
+    // The trail behind the player: how many ticks' worth of it (0 for
+    // none), and how much fainter each copy is than the one in front, from
+    // 0 to 1.
+    pub trail_length: usize,
+    pub trail_fade: f32,
+    // Shift dashes: for dash_duration seconds the player shoots off at
+    // dash_speed world units per second, and then can't dash again for
+    // dash_cooldown seconds. While they're dashing, the player gets
+    // painted over with dash_flash_color.
+    pub dash_speed: f64,
+    pub dash_duration: f64,
+    pub dash_cooldown: f64,
+    pub dash_flash_color: [f32; 4],
+    // Q and E turn the player, at rotate_speed degrees a second. With
+    // tank_controls on, Left and Right turn them too, and Up and Down go
+    // forwards and backwards the way they're pointing, rather than up and
+    // down the screen.
+    pub rotate_speed: f64,
+    pub tank_controls: bool,
+    // Platformer mode. Gravity pulls down at this many world units per second
+    // per second, up to max_fall_speed; a jump starts at jump_speed
+    // upwards; and letting go of Space before the top of a jump cuts what's
+    // left of its upward speed down to jump_cut of itself, so a tap is a
+    // hop and holding on is a leap.
+    pub gravity: f64,
+    pub max_fall_speed: f64,
+    pub jump_speed: f64,
+    pub jump_cut: f64,
+    // Enemies chase the player at enemy_speed world units per second. Each
+    // one that catches you takes enemy_damage of your player_health, and
+    // when you're out of health it's game over. After being caught, you
+    // can't be caught again for hurt_seconds. Enemies have enemy_health
+    // themselves, and each shot takes projectile_damage off it.
+    pub enemy_speed: f64,
+    // Every enemy_path_seconds, each enemy with a wall between it and the
+    // player works out a way round (see pathfinding.rs). 0 leaves them
+    // heading straight for you, walls or no walls. Like enemy_spawn_seconds,
+    // a change only counts from the next reset.
+    pub enemy_path_seconds: f64,
+    pub player_health: u32,
+    pub hurt_seconds: f64,
+    // Running out of health loses one of your lives, and puts you back at
+    // the level's spawn point, blinking, where nothing can hurt you for
+    // respawn_seconds. When the last life goes, it's game over.
+    pub lives: u32,
+    pub respawn_seconds: f64,
+    pub enemy_damage: u32,
+    pub enemy_health: u32,
+    pub projectile_damage: u32,
+    // Every enemy_spawn_seconds, another enemy turns up somewhere in the
+    // world. 0, the default, is never. A change only counts from the next
+    // reset.
+    pub enemy_spawn_seconds: f64,
+    // Or every wave_seconds, a whole wave of them: wave_size in the first,
+    // wave_growth more each wave after that, each wave wave_speed_growth of
+    // enemy_speed faster, and the first wave_grace_seconds in (see
+    // waves.rs). 0, the default, is no waves.
+    pub wave_seconds: f64,
+    pub wave_size: u32,
+    pub wave_growth: f64,
+    pub wave_speed_growth: f64,
+    pub wave_grace_seconds: f64,
+    // Power-ups (see powerup.rs) last power_up_seconds. Speed makes you
+    // speed_boost times as fast, and size size_boost times as big.
+    pub power_up_seconds: f64,
+    pub speed_boost: f64,
+    pub size_boost: f64,
+    // How long the goal flashes for, once you're there, before it's on to
+    // the next level.
+    pub level_complete_seconds: f64,
+    // Shooting: how fast projectiles fly, in world units per second, and how
+    // many seconds they last if they don't hit anything.
+    pub projectile_speed: f64,
+    pub projectile_lifetime: f64,
+    // Which palette to draw everything in: "default", "deuteranopia" or
+    // "high_contrast" (see palette.rs). The colors from here down to
+    // hud_color are the default palette's; the others have their own.
+    pub palette: String,
+    pub background_color: [f32; 4],
+    // What the players look like, which can be changed on the options
+    // screen too: player one's color, how many world units across they both
+    // are, and whether they're a "square", a "hollow" square, a "circle" or
+    // a "polygon" (see player::Shape). The size and the shape are what
+    // walls and enemies go by as well, not just how they look. A polygon
+    // has player_sides sides, three or more.
+    pub player_color: [f32; 4],
+    pub player_size: f64,
+    pub player_shape: Shape,
+    pub player_sides: u32,
+    // Player two's color, in co-op, and how far to the right of player one
+    // they start.
+    pub player_two_color: [f32; 4],
+    pub player_two_offset: f64,
+    pub wall_color: [f32; 4],
+    pub goal_color: [f32; 4],
+    pub pickup_color: [f32; 4],
+    pub enemy_color: [f32; 4],
+    pub projectile_color: [f32; 4],
+    // The power-ups, and you while you've got one.
+    pub speed_color: [f32; 4],
+    pub size_color: [f32; 4],
+    pub invulnerable_color: [f32; 4],
+    // The people you can talk to (see dialogue.rs), and how many letters
+    // a second what they say comes up at (0 for a page at a time).
+    pub npc_color: [f32; 4],
+    pub dialogue_chars_per_second: f64,
+    // The HUD: which font to write it in (any .ttf file; no font, no HUD),
+    // how big, and what color.
+    pub font_path: String,
+    pub hud_font_size: u32,
+    pub hud_color: [f32; 4],
+    // Other fonts, by name, for the things that ask for one by name (see
+    // text.rs). So far that's only "console"; without it, it's font_path.
+    pub fonts: BTreeMap<String, String>,
+    // Which language the words on the screen are in: "en" or "fr" (see
+    // locale.rs).
+    pub language: String,
+    // Whether a level too big to fit on the screen gets a minimap in the
+    // bottom-right corner (see minimap.rs).
+    pub minimap: bool,
+    // A plain, still background instead of the moving one (see
+    // background.rs), and no flash when expand fires, for anyone who finds
+    // them getting in the way. It's on the options screen too.
+    pub low_distraction: bool,
+    // How one screen turns into the next (see transition.rs): a "fade" or
+    // a "wipe", over transition_seconds (0 for a straight cut), going as
+    // transition_easing says.
+    pub transition: Style,
+    pub transition_seconds: f64,
+    pub transition_easing: Easing,
+    // Whether the left mouse button is a finger on a touch screen: drag on
+    // the left half to move, tap the right to expand, with a D-pad drawn
+    // to aim at (see touch.rs). F6 flips it while playing.
+    pub touch_controls: bool,
+    // Key names as understood by keys::parse_key, e.g. "R" or "Escape".
+    // (Escape and P are taken already: they pause. So are Q and E, which
+    // turn the player; quitting used to be Q, before they did.)
+    pub reset_key: String,
+    pub quit_key: String,
+    pub help_key: String,
+    // Held, it runs time backwards, as far back as rewind_seconds (0 turns
+    // rewinding off; see rewind.rs).
+    pub rewind_key: String,
+    pub rewind_seconds: f64,
+    // Macros: keys that press a few others for you, each a key's name and
+    // the lines of a script to play when it's pressed, like
+    // "0.1 press Space". macro_key records a new one from what you press.
+    // A turbo key, held, goes on pressing itself turbo_per_second times a
+    // second. See macros.rs.
+    pub macros: BTreeMap<String, Vec<String>>,
+    pub macro_key: String,
+    pub turbo_keys: Vec<String>,
+    pub turbo_per_second: f64,
+    // Every autosave_seconds of play the game's saved, going round
+    // autosave_slots files in the autosave directory, and the title screen
+    // offers to resume the last one (see autosave.rs). 0 is never.
+    pub autosave_seconds: f64,
+    pub autosave_slots: u32,
+    // Bounce mode. Restitution is how much speed survives a bounce (1.0 is
+    // all of it), nudge is how hard the arrow keys push in world units per
+    // second per second, and anything slower than rest_speed after a bounce
+    // stops. Each world unit of expand adds boost to the speed multiplier,
+    // so Space gives a burst of speed.
+    pub bounce_restitution: f64,
+    pub bounce_nudge: f64,
+    pub bounce_rest_speed: f64,
+    pub bounce_boost: f64,
+    // Sound effects: whether they're muted, and how loud they are if not.
+    pub audio: AudioSettings,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            window_width: 640,
+            window_height: 480,
+            window_title: "jumpy".to_string(),
+            opengl_version: "3.2".to_string(),
+            shader: String::new(),
+            shader_target: ShaderTarget::Scene,
+            max_fps: 60,
+            ups: 120,
+            vsync: false,
+            bench_mode: false,
+            fullscreen: false,
+            letterbox: false,
+            view_width: 0,
+            view_height: 0,
+            start_position: None,
+            camera_deadzone_width: 160.0,
+            camera_deadzone_height: 120.0,
+            camera_smoothing: 6.0,
+            zoom_step: 1.25,
+            move_speed: 200.0,
+            acceleration: 2400.0,
+            friction: 1600.0,
+            expand_step: 10.0,
+            expand_decay: 60.0,
+            expand_easing: Easing::EaseOut,
+            squash_stretch: 0.25,
+            shake_amplitude: 6.0,
+            shake_duration: 0.3,
+            particle_min_count: 20,
+            particle_max_count: 40,
+            particle_min_speed: 40.0,
+            particle_max_speed: 160.0,
+            particle_lifetime: 0.75,
+            particle_size: 2.0,
+            particle_max_live: 400,
+            particle_color: [1.0, 0.8, 0.2, 1.0],
+            bloom: 0.5,
+            bloom_threshold: 0.7,
+            flash_strength: 0.35,
+            flash_seconds: 0.25,
+            trail_length: 12,
+            trail_fade: 0.7,
+            dash_speed: 900.0,
+            dash_duration: 0.12,
+            dash_cooldown: 0.8,
+            dash_flash_color: [1.0, 1.0, 1.0, 0.7],
+            rotate_speed: 180.0,
+            tank_controls: false,
+            gravity: 1800.0,
+            max_fall_speed: 900.0,
+            jump_speed: 650.0,
+            jump_cut: 0.4,
+            enemy_speed: 80.0,
+            enemy_path_seconds: 0.5,
+            player_health: 3,
+            hurt_seconds: 1.0,
+            lives: 3,
+            respawn_seconds: 2.0,
+            enemy_damage: 1,
+            enemy_health: 1,
+            projectile_damage: 1,
+            enemy_spawn_seconds: 0.0,
+            wave_seconds: 0.0,
+            wave_size: 3,
+            wave_growth: 1.0,
+            wave_speed_growth: 0.1,
+            wave_grace_seconds: 3.0,
+            power_up_seconds: 6.0,
+            speed_boost: 1.6,
+            size_boost: 2.0,
+            level_complete_seconds: 2.0,
+            projectile_speed: 400.0,
+            projectile_lifetime: 1.0,
+            palette: "default".to_string(),
+            background_color: [0.6, 0.6, 0.6, 1.0],
+            player_color: [1.0, 0.0, 0.0, 1.0],
+            player_size: PLAYER_SIZE,
+            player_shape: Shape::Square,
+            player_sides: 6,
+            player_two_color: [0.9, 0.4, 1.0, 1.0],
+            player_two_offset: 20.0,
+            wall_color: [0.2, 0.2, 0.2, 1.0],
+            goal_color: [0.0, 0.8, 0.0, 1.0],
+            pickup_color: [1.0, 0.8, 0.1, 1.0],
+            enemy_color: [0.1, 0.3, 0.9, 1.0],
+            projectile_color: [1.0, 1.0, 0.6, 1.0],
+            speed_color: [0.2, 0.9, 0.9, 1.0],
+            size_color: [1.0, 0.5, 0.0, 1.0],
+            invulnerable_color: [1.0, 1.0, 1.0, 1.0],
+            npc_color: [0.85, 0.55, 0.85, 1.0],
+            dialogue_chars_per_second: 40.0,
+            font_path: "assets/font.ttf".to_string(),
+            hud_font_size: 16,
+            hud_color: [1.0, 1.0, 1.0, 1.0],
+            fonts: BTreeMap::new(),
+            language: "en".to_string(),
+            minimap: true,
+            low_distraction: false,
+            transition: Style::Fade,
+            transition_seconds: 0.4,
+            transition_easing: Easing::EaseInOut,
+            touch_controls: false,
+            reset_key: "R".to_string(),
+            quit_key: "X".to_string(),
+            help_key: "H".to_string(),
+            rewind_key: "Z".to_string(),
+            rewind_seconds: 5.0,
+            macros: BTreeMap::new(),
+            macro_key: "C".to_string(),
+            turbo_keys: Vec::new(),
+            turbo_per_second: 10.0,
+            autosave_seconds: 30.0,
+            autosave_slots: 3,
+            bounce_restitution: 0.9,
+            bounce_nudge: 400.0,
+            bounce_rest_speed: 5.0,
+            bounce_boost: 0.05,
+            audio: AudioSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    // How big the game is drawn, letterboxed: view_width by view_height,
+    // with the window's size standing in for either one that's 0.
+    pub fn view_size(&self) -> (f64, f64) {
+        let or = |size: u32, window: u32| (if size == 0 { window } else { size }) as f64;
+        (or(self.view_width, self.window_width), or(self.view_height, self.window_height))
+    }
+
+    // The colors to draw with. A palette we've never heard of gets a
+    // warning (see warnings) and the default one.
+    pub fn palette(&self) -> Palette {
+        Palette::named(&self.palette, self).unwrap_or_else(|| Palette::from_config(self))
+    }
+}
+
+// Read and parse a config file, bringing an old one up to date (see
+// schema.rs). Errors come back as a plain string, since the only thing we
+// ever do with one is print it.
+pub fn load(path: &Path) -> Result<Config, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    schema::from_json(Format::Config, &contents)
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))
+}
+
+// Write `config` out as JSON, laid out one setting per line so it's easy
+// to edit by hand.
+pub fn save(path: &Path, config: &Config) -> Result<(), String> {
+    let json = schema::to_json(Format::Config, config)?;
+    File::create(path)
+        .and_then(|mut f| f.write_all(json.as_bytes()))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+// What main calls at startup. A missing file gets written with the
+// defaults first, then read like any other.
+pub fn load_or_create(path: &Path) -> Result<Config, String> {
+    if !path.exists() {
+        save(path, &Config::default())?;
+        info!("Wrote the default settings to {}", path.display());
+    }
+    load(path)
+}
+
+// "3.2" to OpenGL::V3_2, and so on for the versions the graphics crate knows.
+pub fn opengl_version(name: &str) -> Option<OpenGL> {
+    Some(match name.trim() {
+        "2.1" => OpenGL::V2_1,
+        "3.0" => OpenGL::V3_0,
+        "3.1" => OpenGL::V3_1,
+        "3.2" => OpenGL::V3_2,
+        "3.3" => OpenGL::V3_3,
+        "4.0" => OpenGL::V4_0,
+        "4.1" => OpenGL::V4_1,
+        "4.2" => OpenGL::V4_2,
+        "4.3" => OpenGL::V4_3,
+        "4.4" => OpenGL::V4_4,
+        "4.5" => OpenGL::V4_5,
+        _ => return None,
+    })
+}
+
+/*
+ * Things in a config that parse fine but probably aren't what you meant:
+ * key names we don't recognise, two actions fighting over the same key, and
+ * an action on one of the keys that turn the player.
+ * These are only warnings - the game still runs, those keys just won't do
+ * what you hoped.
+ */
+pub fn warnings(config: &Config) -> Vec<String> {
+    let named = [("reset_key", &config.reset_key),
+                 ("quit_key", &config.quit_key),
+                 ("help_key", &config.help_key),
+                 ("rewind_key", &config.rewind_key),
+                 ("macro_key", &config.macro_key)];
+    let mut warnings = Vec::new();
+    if opengl_version(&config.opengl_version).is_none() {
+        warnings.push(format!("opengl_version: there's no OpenGL \"{}\" (we'll try 3.2, then 2.1)",
+                              config.opengl_version));
+    }
+    if !palette::NAMES.contains(&config.palette.as_str()) {
+        warnings.push(format!("palette: there's no palette called \"{}\" (it can be {})",
+                              config.palette, palette::NAMES.join(", ")));
+    }
+    if config.player_size < 1.0 {
+        warnings.push(format!("player_size: {} is too small to see (we'll use 1)",
+                              config.player_size));
+    }
+    if config.player_sides < 3 {
+        warnings.push(format!("player_sides: a polygon needs 3 sides at least, not {}",
+                              config.player_sides));
+    }
+    for (i, &(field, name)) in named.iter().enumerate() {
+        let key = match keys::parse_key(name) {
+            Some(key) => key,
+            None => {
+                warnings.push(format!("{}: there's no key called \"{}\"", field, name));
+                continue;
+            }
+        };
+        if key == keyboard::Q || key == keyboard::E {
+            warnings.push(format!("{}: {} turns the player too, but it'll only do this",
+                                  field, keys::key_name(key)));
+        }
+        for &(other, other_name) in named[..i].iter() {
+            if keys::parse_key(other_name) == Some(key) {
+                warnings.push(format!("{} and {} are both bound to {}",
+                                      other, field, keys::key_name(key)));
+            }
+        }
+    }
+    // A macro on one of those keys would get in its way, and one that
+    // won't parse can't be played.
+    for (name, lines) in config.macros.iter() {
+        let key = match keys::parse_key(name) {
+            Some(key) => key,
+            None => {
+                warnings.push(format!("macros: there's no key called \"{}\"", name));
+                continue;
+            }
+        };
+        if let Some(&(field, _)) = named.iter().find(|&&(_, n)| keys::parse_key(n) == Some(key)) {
+            warnings.push(format!("macros: {} is {} already, so its macro won't play",
+                                  keys::key_name(key), field));
+        }
+        if let Err(e) = macros::parse(lines) {
+            warnings.push(format!("macros: {}'s {}", keys::key_name(key), e));
+        }
+    }
+    warnings
+}
+
+/*
+ * The watcher keeps an eye on the config file (file is a FileWatcher, see
+ * watch.rs, which only looks about once a second) and reads it again when
+ * it changes.
+ */
+pub struct ConfigWatcher {
+    pub file: FileWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> ConfigWatcher {
+        ConfigWatcher { file: FileWatcher::new(path) }
+    }
+
+    // Call this every update. Hands back a fresh config if the file changed
+    // and parsed cleanly, and None otherwise.
+    pub fn update(&mut self, dt: f64, current: &Config) -> Option<Config> {
+        if !self.file.update(dt) { return None }
+        self.reload(current)
+    }
+
+    // The same, but looking at the file right now.
+    pub fn check(&mut self, current: &Config) -> Option<Config> {
+        if !self.file.changed() { return None }
+        self.reload(current)
+    }
+
+    /*
+     * Read the file again, whether it's changed or not; this is what F10
+     * does. A broken file never replaces the config we already have; we log
+     * what went wrong and carry on with the last good one. The watcher
+     * still takes note of the change, though, otherwise we'd log the same
+     * error every second until it's fixed.
+     */
+    pub fn reload(&mut self, current: &Config) -> Option<Config> {
+        self.file.changed();
+        let path = self.file.path().to_path_buf();
+        match load(&path) {
+            Ok(mut config) => {
+                if config.window_width != current.window_width ||
+                   config.window_height != current.window_height ||
+                   config.window_title != current.window_title ||
+                   config.opengl_version != current.opengl_version {
+                    warn!("Window settings changed in {}; those need a restart \
+                           to take effect.", path.display());
+                    config.window_width = current.window_width;
+                    config.window_height = current.window_height;
+                    config.window_title = current.window_title.clone();
+                    config.opengl_version = current.opengl_version.clone();
+                }
+                info!("Reloaded {}", path.display());
+                for warning in warnings(&config).iter() {
+                    warn!("{}", warning);
+                }
+                Some(config)
+            }
+            Err(e) => {
+                error!("{} (keeping the previous settings)", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    // Write `contents` to a file in the temp directory and push its mtime
+    // `bump` seconds into the future, so back-to-back writes in a test don't
+    // land on the same timestamp.
+    fn write(path: &PathBuf, contents: &str, bump: u64) {
+        let mut f = File::create(path).unwrap();
+        f.write_all(contents.as_bytes()).unwrap();
+        f.set_modified(SystemTime::now() + Duration::from_secs(bump)).unwrap();
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("jumpy-{}-{}.json", name, ::std::process::id()))
+    }
+
+    #[test]
+    fn reloads_when_mtime_changes() {
+        let path = temp_path("reload");
+        write(&path, r#"{ "move_speed": 10.0 }"#, 0);
+        let mut watcher = ConfigWatcher::new(&path);
+        let current = load(&path).unwrap();
+
+        // Nothing changed yet, so nothing to reload.
+        assert_eq!(watcher.check(&current), None);
+
+        write(&path, r#"{ "move_speed": 25.0 }"#, 10);
+        let reloaded = watcher.check(&current).unwrap();
+        assert_eq!(reloaded.move_speed, 25.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_old_config_on_parse_failure() {
+        let path = temp_path("broken");
+        write(&path, r#"{ "move_speed": 10.0 }"#, 0);
+        let mut watcher = ConfigWatcher::new(&path);
+        let current = load(&path).unwrap();
+
+        write(&path, r#"{ "move_speed": oops"#, 10);
+        assert_eq!(watcher.check(&current), None);
+        // ...and we don't keep retrying the same broken file.
+        assert_eq!(watcher.check(&current), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignores_window_size_on_reload() {
+        let path = temp_path("window");
+        write(&path, r#"{ "window_width": 640 }"#, 0);
+        let mut watcher = ConfigWatcher::new(&path);
+        let current = load(&path).unwrap();
+
+        write(&path, r#"{ "window_width": 1920, "move_speed": 3.0 }"#, 10);
+        let reloaded = watcher.check(&current).unwrap();
+        assert_eq!(reloaded.window_width, 640);
+        assert_eq!(reloaded.move_speed, 3.0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn warns_about_bad_and_clashing_keys() {
+        let config = Config {
+            reset_key: "X".to_string(),
+            help_key: "Hepl".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(warnings(&config), vec![
+            "reset_key and quit_key are both bound to X".to_string(),
+            "help_key: there's no key called \"Hepl\"".to_string(),
+        ]);
+        assert!(warnings(&Config::default()).is_empty());
+        // Quitting was Q, once, so there are config files that still say so.
+        let config = Config { quit_key: "Q".to_string(), ..Config::default() };
+        assert_eq!(warnings(&config), vec![
+            "quit_key: Q turns the player too, but it'll only do this".to_string(),
+        ]);
+        let mut config = Config::default();
+        config.macros.insert("R".to_string(), vec!["0 press Space".to_string()]);
+        config.macros.insert("V".to_string(), vec!["soon press Space".to_string()]);
+        assert_eq!(warnings(&config), vec![
+            "macros: R is reset_key already, so its macro won't play".to_string(),
+            "macros: V's line 1: the time should be seconds from the start".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn writes_the_defaults_when_there_is_no_file() {
+        let path = temp_path("create");
+        let _ = fs::remove_file(&path);
+        assert_eq!(load_or_create(&path), Ok(Config::default()));
+        assert!(path.exists());
+        // And the second time round it's just read.
+        assert_eq!(load_or_create(&path), Ok(Config::default()));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn knows_opengl_versions() {
+        assert!(opengl_version("3.2") == Some(OpenGL::V3_2));
+        assert!(opengl_version(" 2.1 ") == Some(OpenGL::V2_1));
+        assert!(opengl_version("9.9").is_none());
+        let config = Config { opengl_version: "three".to_string(), ..Config::default() };
+        assert_eq!(warnings(&config).len(), 1);
+    }
+
+    #[test]
+    fn unknown_palettes_are_the_default_one() {
+        let config = Config { palette: "sepia".to_string(), ..Config::default() };
+        assert_eq!(warnings(&config).len(), 1);
+        assert_eq!(config.palette(), Palette::from_config(&config));
+    }
+
+    #[test]
+    fn warns_about_tiny_players() {
+        let config = Config { player_size: 0.0, ..Config::default() };
+        assert_eq!(warnings(&config).len(), 1);
+        let config = Config { player_sides: 2, ..Config::default() };
+        assert_eq!(warnings(&config).len(), 1);
+    }
+
+    #[test]
+    fn only_checks_once_per_interval() {
+        let path = temp_path("interval");
+        write(&path, "{}", 0);
+        let mut watcher = ConfigWatcher::new(&path);
+        let current = load(&path).unwrap();
+
+        write(&path, r#"{ "expand_step": 4.0 }"#, 10);
+        assert_eq!(watcher.update(0.5, &current), None);
+        assert!(watcher.update(0.5, &current).is_some());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+/*
+ * The debug console: press ` (the key above Tab) while playing and a strip
+ * drops down from the top of the window that you can type commands into.
+ * It's for poking at the game while it runs, without a config file or a
+ * script:
+ *
+ *     tp 100 200            put player one at (100, 200)
+ *     set expand 50         change a number: expand, x, y, health or score
+ *                           for the game in progress, or anything numeric
+ *                           in the config, like move_speed
+ *     spawn enemy           make an enemy (or a pickup) next to player one,
+ *     spawn pickup 300 400  or at x, y
+ *     save state.json       save the game to a file of your choosing
+ *     load state.json       and load it again
+ *     help                  list all that
+ *
+ * Return runs what you've typed, Backspace rubs out, and ` or Escape closes
+ * the console again. The game holds still while it's open, like it does
+ * for the help, and every other key goes into the console rather than the
+ * game, so typing "quit" doesn't quit. What the commands say back goes in
+ * the strip above the line you're typing on, and in the log.
+ *
+ * The letters themselves don't come from key presses: the window sends a
+ * separate text event for what a key press types, which already knows
+ * about shift and the keyboard layout. So Console keeps the line being
+ * edited, and knows nothing about keys; App decides which keys mean Return
+ * and Backspace (see handlers.rs).
+ *
+ * A config changed with set only lasts until the config file is next
+ * reloaded, and nothing typed in here goes into a replay.
+ */
+use std::path::PathBuf;
+
+use serde_json;
+
+use app::App;
+use entity::Pickup;
+use lua::Thing;
+use save;
+
+// How many old lines the console hangs on to.
+const SCROLLBACK: usize = 8;
+
+// What a line typed into the console asks for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Teleport(f64, f64),
+    Set(String, f64),
+    // Where, or None for next to player one.
+    Spawn(Thing, Option<(f64, f64)>),
+    Save(PathBuf),
+    Load(PathBuf),
+    Help,
+}
+
+// The commands, ready to show to a person, for help to print.
+const HELP: &'static [&'static str] = &[
+    "tp <x> <y>",
+    "set <expand|x|y|health|score|config setting> <number>",
+    "spawn <enemy|pickup> [<x> <y>]",
+    "save <file>",
+    "load <file>",
+    "help",
+];
+
+fn number(word: Option<&str>, what: &str) -> Result<f64, String> {
+    let word = word.ok_or_else(|| format!("{} is missing", what))?;
+    word.parse().map_err(|_| format!("{} should be a number, not {}", what, word))
+}
+
+// Turn a line into a Command, or say what's wrong with it.
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Err("type help to see the commands".to_string()),
+    };
+    let command = match name {
+        "tp" => Command::Teleport(number(words.next(), "x")?, number(words.next(), "y")?),
+        "set" => {
+            let setting = words.next().ok_or("set what?")?;
+            Command::Set(setting.to_string(), number(words.next(), "the value")?)
+        }
+        "spawn" => {
+            let thing = match words.next() {
+                Some("enemy") => Thing::Enemy,
+                Some("pickup") => Thing::Pickup,
+                Some(other) => return Err(format!("can't spawn a {}", other)),
+                None => return Err("spawn what? An enemy or a pickup".to_string()),
+            };
+            let at = match words.next() {
+                Some(x) => Some((number(Some(x), "x")?, number(words.next(), "y")?)),
+                None => None,
+            };
+            Command::Spawn(thing, at)
+        }
+        "save" | "load" => {
+            let path = PathBuf::from(words.next().ok_or("which file?")?);
+            if name == "save" { Command::Save(path) } else { Command::Load(path) }
+        }
+        "help" => Command::Help,
+        _ => return Err(format!("no such command: {}", name)),
+    };
+    match words.next() {
+        Some(extra) => Err(format!("didn't expect {} on the end", extra)),
+        None => Ok(command),
+    }
+}
+
+// The console's line being typed, and what it's said lately.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub output: Vec<String>,
+}
+
+impl Console {
+    // Typed text, from a text event. The ` that opened the console types
+    // a ` too, so that's left out, along with anything else that isn't
+    // something you could read.
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.chars().filter(|&c| c != '`' && !c.is_control()));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    // Return: take the line, and leave a copy of it in the output so you
+    // can see what you asked for above what it did.
+    pub fn submit(&mut self) -> String {
+        let line = self.input.split_off(0);
+        self.say(format!("> {}", line));
+        line
+    }
+
+    pub fn say(&mut self, line: String) {
+        self.output.push(line);
+        if self.output.len() > SCROLLBACK {
+            let extra = self.output.len() - SCROLLBACK;
+            self.output.drain(..extra);
+        }
+    }
+
+    // What a font would show: the output, then the line being typed with
+    // a cursor on the end.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = self.output.clone();
+        lines.push(format!("> {}_", self.input));
+        lines
+    }
+}
+
+/*
+ * Running commands is App's business, since it's App they change, so it
+ * gets another `impl App` here, like render.rs's.
+ */
+impl App {
+    /*
+     * Open or close the console. Like opening the help, opening it forgets
+     * any held arrow keys, or the player would set off again the moment it
+     * closed.
+     */
+    pub fn toggle_console(&mut self) {
+        self.console.open = !self.console.open;
+        debug!("Console {}", if self.console.open { "opened" } else { "closed" });
+        if self.console.open {
+            self.forget_held_keys();
+        }
+    }
+
+    // Run whatever's been typed into the console.
+    pub fn submit_console(&mut self) {
+        let line = self.console.submit();
+        if line.trim().is_empty() { return }
+        let reply = match parse(&line).and_then(|command| self.run_command(command)) {
+            Ok(reply) => { info!("{}: {}", line, reply); reply }
+            Err(e) => { warn!("{}: {}", line, e); e }
+        };
+        self.console.say(reply);
+    }
+
+    // Do what `command` says, and say what happened.
+    pub fn run_command(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Teleport(x, y) => {
+                {
+                    let player = &mut self.players[0];
+                    player.x = x;
+                    player.y = y;
+                    player.previous = (x, y);
+                }
+                // Into a wall is no good, so walls push back, like always.
+                self.push_out_of_walls(0);
+                Ok(format!("player one is at ({:.0}, {:.0})", self.players[0].x,
+                           self.players[0].y))
+            }
+            Command::Set(name, value) => self.set(&name, value),
+            Command::Spawn(thing, at) => {
+                let (x, y) = at.unwrap_or((self.players[0].x + 100.0, self.players[0].y));
+                match thing {
+                    Thing::Pickup => {
+                        let color = self.config.palette().pickup;
+                        self.entities.push(Box::new(Pickup::at(x, y, color)));
+                    }
+                    _ => self.spawn_enemy(x, y),
+                }
+                Ok(format!("made a {} at ({:.0}, {:.0})", thing.name(), x, y))
+            }
+            Command::Save(path) => {
+                save::save(&path, &self.snapshot())?;
+                Ok(format!("saved to {}", path.display()))
+            }
+            Command::Load(path) => {
+                save::load(&path).and_then(|snapshot| self.restore(&snapshot))?;
+                Ok(format!("loaded {}", path.display()))
+            }
+            Command::Help => Ok(HELP.join(", ")),
+        }
+    }
+
+    /*
+     * set. A handful of names are about the game in progress; anything
+     * else has to be one of the config's numbers. The config's fields
+     * aren't something we can look up by name in Rust, but they are in
+     * JSON, so the config goes through serde_json and back with the one
+     * number changed.
+     */
+    fn set(&mut self, name: &str, value: f64) -> Result<String, String> {
+        match name {
+            "expand" => self.players[0].expand = value.max(0.0),
+            "x" => self.players[0].x = value,
+            "y" => self.players[0].y = value,
+            "health" => self.health = value.max(0.0) as u32,
+            "score" => self.score = value.max(0.0) as u32,
+            _ => {
+                let mut json = serde_json::to_value(&self.config).map_err(|e| e.to_string())?;
+                let number = match json.get(name) {
+                    // Whole-number settings, like the font size, won't
+                    // take a number with a point in it.
+                    Some(old) if old.is_u64() => serde_json::Value::from(value.max(0.0) as u64),
+                    Some(old) if old.is_number() => serde_json::Value::from(value),
+                    Some(_) => return Err(format!("{} isn't a number", name)),
+                    None => return Err(format!("there's no setting called {}", name)),
+                };
+                json[name] = number;
+                let config = serde_json::from_value(json).map_err(|e| e.to_string())?;
+                self.apply_config(config);
+            }
+        }
+        Ok(format!("{} is now {}", name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_commands() {
+        assert_eq!(parse("tp 100 200"), Ok(Command::Teleport(100.0, 200.0)));
+        assert_eq!(parse("  set   expand 50 "), Ok(Command::Set("expand".to_string(), 50.0)));
+        assert_eq!(parse("spawn enemy"), Ok(Command::Spawn(Thing::Enemy, None)));
+        assert_eq!(parse("spawn pickup 3 4"), Ok(Command::Spawn(Thing::Pickup, Some((3.0, 4.0)))));
+        assert_eq!(parse("save state.json"), Ok(Command::Save(PathBuf::from("state.json"))));
+        assert_eq!(parse("load state.json"), Ok(Command::Load(PathBuf::from("state.json"))));
+        assert_eq!(parse("help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn says_what_is_wrong_with_a_command() {
+        assert_eq!(parse("tp 100"), Err("y is missing".to_string()));
+        assert_eq!(parse("tp 100 up"), Err("y should be a number, not up".to_string()));
+        assert_eq!(parse("spawn dragon"), Err("can't spawn a dragon".to_string()));
+        assert_eq!(parse("spawn enemy 5"), Err("y is missing".to_string()));
+        assert_eq!(parse("help me"), Err("didn't expect me on the end".to_string()));
+        assert_eq!(parse("fly"), Err("no such command: fly".to_string()));
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn editing_the_line() {
+        let mut console = Console::default();
+        console.type_text("`tp 1");
+        console.type_text("\u{8}0");
+        console.backspace();
+        console.type_text("2 3");
+        assert_eq!(console.lines(), vec!["> tp 12 3_"]);
+        assert_eq!(console.submit(), "tp 12 3");
+        assert_eq!(console.lines(), vec!["> tp 12 3", "> _"]);
+        // Only the last few lines are kept.
+        for i in 0..20 {
+            console.say(i.to_string());
+        }
+        assert_eq!(console.output.len(), SCROLLBACK);
+        assert_eq!(console.output.last(), Some(&"19".to_string()));
+    }
+}
@@ -0,0 +1,386 @@
+/*
+ * Which movement keys are currently held down, and which way that points the
+ * player. Piston tells us when a key goes down (a press) and when it comes
+ * back up (a release); by remembering everything in between we know what's
+ * held at any moment, which is what smooth movement needs.
+ *
+ * A controller's analog stick gets remembered here too. Unlike a key it's
+ * not just on or off: it reports how far it's pushed, from -1 to 1 on each
+ * axis, so pushing it halfway walks at half speed.
+ *
+ * Q and E turn the player round, anticlockwise and clockwise. Normally that
+ * only changes how they look, but with tank controls it's how you steer:
+ * Left and Right turn you as well, and Up and Down go forwards and
+ * backwards, whichever way you're pointing (see tank_direction).
+ */
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use input::keyboard::{self, Key};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputState {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub rotate_left: bool,
+    pub rotate_right: bool,
+    // The stick's raw (x, y) position, straight from the controller.
+    pub stick: (f64, f64),
+    // And a finger dragged about on a touch screen, which works like
+    // another stick (see touch.rs).
+    pub touch: (f64, f64),
+}
+
+/*
+ * Sticks never sit perfectly at (0, 0) when you let go; worn ones can rest
+ * a good way off centre. Anything closer to the middle than this counts as
+ * not being pushed at all, otherwise the player would creep about on its
+ * own.
+ */
+pub const STICK_DEAD_ZONE: f64 = 0.2;
+
+impl InputState {
+    // Returns whether the key was one of ours, so the caller knows whether
+    // to bother looking at it any further.
+    pub fn press(&mut self, key: Key) -> bool {
+        self.set(key, true)
+    }
+
+    pub fn release(&mut self, key: Key) -> bool {
+        self.set(key, false)
+    }
+
+    fn set(&mut self, key: Key, held: bool) -> bool {
+        match key {
+            keyboard::Up => self.up = held,
+            keyboard::Down => self.down = held,
+            keyboard::Left => self.left = held,
+            keyboard::Right => self.right = held,
+            keyboard::Q => self.rotate_left = held,
+            keyboard::E => self.rotate_right = held,
+            _ => return false,
+        }
+        true
+    }
+
+    // One axis of the stick has moved; `horizontal` says which. Which of the
+    // controller's axes count as which is up to the ControllerMap.
+    pub fn move_stick(&mut self, horizontal: bool, position: f64) {
+        let position = position.max(-1.0).min(1.0);
+        if horizontal { self.stick.0 = position } else { self.stick.1 = position }
+    }
+
+    /*
+     * The direction the held keys point in, as an (x, y) pair of length 1,
+     * or (0, 0) if they don't point anywhere. Holding opposite keys cancels
+     * out, so Up + Down is no vertical movement at all.
+     *
+     * The normalising is the interesting bit. Up + Right naively gives
+     * (1, -1), but that arrow is √2 ≈ 1.41 long - so moving diagonally
+     * would be 41% faster than moving straight. Shrinking it by 1/√2 brings
+     * it back to length 1.
+     *
+     * If no keys are held, the stick gets its say instead (see stick_direction
+     * below). Keys win when both are going, since a key is a much more
+     * deliberate thing than a stick that's been knocked.
+     */
+    pub fn direction(&self) -> (f64, f64) {
+        let axis = |neg: bool, pos: bool| (pos as i32 - neg as i32) as f64;
+        let (dx, dy) = (axis(self.left, self.right), axis(self.up, self.down));
+        if dx != 0.0 && dy != 0.0 {
+            (dx * FRAC_1_SQRT_2, dy * FRAC_1_SQRT_2)
+        } else if dx != 0.0 || dy != 0.0 {
+            (dx, dy)
+        } else {
+            self.analog()
+        }
+    }
+
+    // Where the sticks point: a finger on the screen, if there is one,
+    // since nobody drags one about by accident, and otherwise the
+    // controller's.
+    fn analog(&self) -> (f64, f64) {
+        let touch = stick_direction(self.touch);
+        if touch != (0.0, 0.0) { touch } else { stick_direction(self.stick) }
+    }
+
+    /*
+     * Which way the held keys turn the player: 1 is clockwise at full
+     * speed, -1 anticlockwise, and 0 not at all. That's Q and E, and with
+     * `tank` controls Left, Right and the stick's x as well. Turning both
+     * ways at once cancels out, like moving does.
+     */
+    pub fn turning(&self, tank: bool) -> f64 {
+        let axis = |neg: bool, pos: bool| (pos as i32 - neg as i32) as f64;
+        let keys = axis(self.rotate_left, self.rotate_right);
+        if !tank { return keys }
+        let arrows = axis(self.left, self.right);
+        if keys != 0.0 || arrows != 0.0 {
+            (keys + arrows).max(-1.0).min(1.0)
+        } else {
+            self.analog().0
+        }
+    }
+
+    /*
+     * Tank controls' movement: Up goes forwards, the way `angle` points,
+     * and Down backwards. The stick's y does the same (pushing it up is
+     * negative, remember, so it's flipped). Left and Right don't move you
+     * at all; they're for turning.
+     */
+    pub fn tank_direction(&self, angle: f64) -> (f64, f64) {
+        let keys = (self.up as i32 - self.down as i32) as f64;
+        let forward = if keys != 0.0 { keys } else { -self.analog().1 };
+        let (x, y) = angle_vector(angle);
+        (x * forward, y * forward)
+    }
+}
+
+/*
+ * The player's angle is in radians, clockwise from straight up, since
+ * that's the way a player that hasn't turned is pointing, and clockwise is
+ * the way rotation goes on a screen where y grows downwards. This is the
+ * unit-length (x, y) it points along.
+ */
+pub fn angle_vector(angle: f64) -> (f64, f64) {
+    (angle.sin(), -angle.cos())
+}
+
+/*
+ * Turn a raw stick position into a direction. Inside the dead zone that's
+ * nothing at all. Outside it, we measure from the edge of the dead zone
+ * rather than from the middle, so speed starts from zero as the stick
+ * leaves the dead zone instead of jumping straight to 20%. Pushed all the
+ * way, diagonals get trimmed back to length 1 just like the keys do; most
+ * sticks can reach the corners of their square, which would be √2 again.
+ */
+pub fn stick_direction((x, y): (f64, f64)) -> (f64, f64) {
+    let length = (x * x + y * y).sqrt();
+    if length <= STICK_DEAD_ZONE { return (0.0, 0.0) }
+    let scaled = ((length - STICK_DEAD_ZONE) / (1.0 - STICK_DEAD_ZONE)).min(1.0);
+    (x / length * scaled, y / length * scaled)
+}
+
+/*
+ * The eight ways the player can face. Remember y grows downwards on screen,
+ * so Up is negative y.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Facing {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Facing {
+    /*
+     * Which way a movement direction faces, if it faces anywhere at all.
+     * The keys only ever point exactly along one of the eight directions,
+     * but a stick can point anywhere, so we take the angle and round it to
+     * the nearest eighth of a turn. atan2 measures from the positive x axis,
+     * and since y grows downwards a quarter turn clockwise is Down.
+     */
+    pub fn from_direction(dx: f64, dy: f64) -> Option<Facing> {
+        if dx == 0.0 && dy == 0.0 { return None }
+        let eighth = ((dy.atan2(dx) / (PI / 4.0)).round() as i32 + 8) % 8;
+        Some(match eighth {
+            0 => Facing::Right,
+            1 => Facing::DownRight,
+            2 => Facing::Down,
+            3 => Facing::DownLeft,
+            4 => Facing::Left,
+            5 => Facing::UpLeft,
+            6 => Facing::Up,
+            _ => Facing::UpRight,
+        })
+    }
+
+    // The nearest of the eight to an angle, like the player's.
+    pub fn from_angle(angle: f64) -> Facing {
+        let (x, y) = angle_vector(angle);
+        Facing::from_direction(x, y).unwrap_or(Facing::Up)
+    }
+
+    // A unit-length (x, y) pointing the way we face. Handy for anything that
+    // gets fired out of the front of the player.
+    pub fn vector(&self) -> (f64, f64) {
+        let d = FRAC_1_SQRT_2;
+        match *self {
+            Facing::Up => (0.0, -1.0),
+            Facing::UpRight => (d, -d),
+            Facing::Right => (1.0, 0.0),
+            Facing::DownRight => (d, d),
+            Facing::Down => (0.0, 1.0),
+            Facing::DownLeft => (-d, d),
+            Facing::Left => (-1.0, 0.0),
+            Facing::UpLeft => (-d, -d),
+        }
+    }
+}
+
+// The facing after this update: wherever the keys point, or if they don't
+// point anywhere, wherever we were already facing.
+pub fn next_facing(current: Facing, input: &InputState) -> Facing {
+    let (dx, dy) = input.direction();
+    Facing::from_direction(dx, dy).unwrap_or(current)
+}
+
+/*
+ * In co-op, player two has the other side of the keyboard: WASD to move,
+ * and E to expand. Rather than teach everything else a second set of keys,
+ * we turn each of theirs into the one it stands in for - W is player two's
+ * Up, E is their Space - and keep track of whose it was alongside.
+ */
+pub fn player_two_key(key: Key) -> Option<Key> {
+    match key {
+        keyboard::W => Some(keyboard::Up),
+        keyboard::A => Some(keyboard::Left),
+        keyboard::S => Some(keyboard::Down),
+        keyboard::D => Some(keyboard::Right),
+        keyboard::E => Some(keyboard::Space),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::keyboard;
+
+    fn held(keys: &[Key]) -> InputState {
+        let mut input = InputState::default();
+        for &k in keys.iter() { input.press(k); }
+        input
+    }
+
+    fn length((x, y): (f64, f64)) -> f64 { (x * x + y * y).sqrt() }
+
+    #[test]
+    fn all_eight_directions() {
+        let cases = [
+            (vec![keyboard::Up], Facing::Up),
+            (vec![keyboard::Up, keyboard::Right], Facing::UpRight),
+            (vec![keyboard::Right], Facing::Right),
+            (vec![keyboard::Down, keyboard::Right], Facing::DownRight),
+            (vec![keyboard::Down], Facing::Down),
+            (vec![keyboard::Down, keyboard::Left], Facing::DownLeft),
+            (vec![keyboard::Left], Facing::Left),
+            (vec![keyboard::Up, keyboard::Left], Facing::UpLeft),
+        ];
+        for &(ref keys, facing) in cases.iter() {
+            let dir = held(keys).direction();
+            // Every direction moves at exactly the same speed...
+            assert!((length(dir) - 1.0).abs() < 1e-12);
+            // ...and faces the way you'd expect.
+            assert_eq!(Facing::from_direction(dir.0, dir.1), Some(facing));
+            assert_eq!(facing.vector(), dir);
+        }
+    }
+
+    #[test]
+    fn opposites_cancel_out() {
+        assert_eq!(held(&[keyboard::Up, keyboard::Down]).direction(), (0.0, 0.0));
+        assert_eq!(held(&[keyboard::Left, keyboard::Right]).direction(), (0.0, 0.0));
+        // Cancelling one axis leaves the other at full speed, not 1/√2.
+        assert_eq!(held(&[keyboard::Up, keyboard::Down, keyboard::Right]).direction(),
+                   (1.0, 0.0));
+    }
+
+    #[test]
+    fn facing_persists_after_release() {
+        let mut input = held(&[keyboard::Up, keyboard::Left]);
+        let facing = next_facing(Facing::Down, &input);
+        assert_eq!(facing, Facing::UpLeft);
+
+        input.release(keyboard::Up);
+        input.release(keyboard::Left);
+        assert_eq!(next_facing(facing, &input), Facing::UpLeft);
+    }
+
+    #[test]
+    fn stick_dead_zone_and_scaling() {
+        let mut input = InputState::default();
+        // A resting stick that's drifted a little off centre does nothing.
+        input.move_stick(true, 0.1);
+        input.move_stick(false, -0.1);
+        assert_eq!(input.direction(), (0.0, 0.0));
+        assert_eq!(Facing::from_direction(0.0, 0.0), None);
+
+        // Pushed all the way right is full speed right.
+        input.move_stick(true, 1.0);
+        input.move_stick(false, 0.0);
+        assert_eq!(input.direction(), (1.0, 0.0));
+
+        // Halfway between the dead zone and the edge is half speed.
+        input.move_stick(true, 0.6);
+        assert!((length(input.direction()) - 0.5).abs() < 1e-12);
+
+        // Jammed into a corner is no faster than straight along.
+        input.move_stick(true, 1.0);
+        input.move_stick(false, 1.0);
+        assert!((length(input.direction()) - 1.0).abs() < 1e-12);
+        assert_eq!(next_facing(Facing::Up, &input), Facing::DownRight);
+    }
+
+    #[test]
+    fn stick_facing_rounds_to_nearest_direction() {
+        // Mostly right and a little bit up is still Right...
+        assert_eq!(Facing::from_direction(0.9, -0.2), Some(Facing::Right));
+        // ...but a proper diagonal is a diagonal.
+        assert_eq!(Facing::from_direction(0.6, -0.5), Some(Facing::UpRight));
+        assert_eq!(Facing::from_direction(-0.1, 0.8), Some(Facing::Down));
+    }
+
+    #[test]
+    fn keys_beat_the_stick() {
+        let mut input = held(&[keyboard::Left]);
+        input.move_stick(true, 1.0);
+        assert_eq!(input.direction(), (-1.0, 0.0));
+        // And a finger on the screen beats the stick.
+        let mut input = InputState::default();
+        input.move_stick(true, 1.0);
+        input.touch = (0.0, -1.0);
+        assert_eq!(input.direction(), (0.0, -1.0));
+    }
+
+    #[test]
+    fn wasd_stands_in_for_the_arrows() {
+        let mut input = InputState::default();
+        for &k in [keyboard::W, keyboard::D].iter() {
+            assert!(input.press(player_two_key(k).unwrap()));
+        }
+        assert_eq!(input.direction(), (FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+        assert_eq!(player_two_key(keyboard::Up), None);
+    }
+
+    #[test]
+    fn tank_controls_steer() {
+        let input = held(&[keyboard::Q, keyboard::Up]);
+        assert_eq!(input.turning(false), -1.0);
+        let input = held(&[keyboard::Right, keyboard::Up]);
+        // Without tank controls, Right is just walking.
+        assert_eq!(input.turning(false), 0.0);
+        assert_eq!(input.turning(true), 1.0);
+        // Pointing right, Up goes right...
+        let (x, y) = input.tank_direction(PI / 2.0);
+        assert!((x - 1.0).abs() < 1e-12 && y.abs() < 1e-12);
+        assert_eq!(Facing::from_angle(PI / 2.0), Facing::Right);
+        // ...and pointing down, Down goes up.
+        let (x, y) = held(&[keyboard::Down]).tank_direction(PI);
+        assert!(x.abs() < 1e-12 && (y + 1.0).abs() < 1e-12);
+        assert_eq!(Facing::from_angle(0.0), Facing::Up);
+    }
+
+    #[test]
+    fn other_keys_are_not_ours() {
+        let mut input = InputState::default();
+        assert!(!input.press(keyboard::Space));
+        assert_eq!(input, InputState::default());
+    }
+}
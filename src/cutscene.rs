@@ -0,0 +1,271 @@
+/*
+ * Cutscenes: a little scripted scene at the start of a level, before you
+ * get to play. The player walks somewhere, an enemy moves into place, the
+ * camera goes off to show you the goal, some words come up. It's not much,
+ * but it's enough to set a level up.
+ *
+ * A level file names one with "cutscene" (a file next to the level file,
+ * like its tilemap), and it's a list of steps, taken one after another:
+ *
+ *     [
+ *         { "say": { "text": "Where's everyone gone?", "seconds": 1.5 } },
+ *         { "walk": { "to": [200.0, 120.0], "seconds": 1.5 } },
+ *         { "move_enemy": { "enemy": 0, "to": [700.0, 150.0], "seconds": 1.0 } },
+ *         { "pan": { "to": [1210.0, 890.0], "seconds": 2.0 } },
+ *         { "wait": { "seconds": 0.5 } }
+ *     ]
+ *
+ * - walk: a player (`player` 0, which you can leave out, or 1 in co-op)
+ *   walks in a straight line to `to`, taking `seconds` about it. Walls
+ *   don't get in the way: the cutscene goes where it's told.
+ * - move_enemy: the same, for an enemy, numbered the way they are in the
+ *   level file from 0.
+ * - pan: the camera glides over until `to` is in the middle of the view,
+ *   and stays there until the next pan. Until the first one, and after the
+ *   cutscene, it follows the player as usual.
+ * - say: `text` comes up across the bottom of the screen, and stays there
+ *   until the next say (an empty one just takes it away) or the end. The
+ *   text can be one of the locale's keys, to come out in your language.
+ *   `seconds` is how long before the next step starts.
+ * - wait: nothing happens, for `seconds`.
+ *
+ * While a cutscene's on, it has the level to itself: App::tick hands it
+ * each tick instead of doing anything else, so the enemies don't chase,
+ * the timers don't run, and the keys don't do anything (handleKey sees to
+ * that). The exceptions are pausing, and Return, which skips the rest,
+ * putting everything where the cutscene would have left it. It all goes in
+ * ticks, like the rest of the game, so a replay plays it back just the same.
+ */
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde_json;
+
+use app::App;
+use animation::AnimationState;
+use controls::Facing;
+use ecs::{Kind, Position};
+use tween::Easing;
+
+// How much of the screen's height each of the black bars across the top
+// and bottom takes up while a cutscene's on, so it looks like one.
+pub const BARS: f64 = 0.1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Walk {
+        #[serde(default)]
+        player: usize,
+        to: [f64; 2],
+        seconds: f64,
+    },
+    MoveEnemy { enemy: usize, to: [f64; 2], seconds: f64 },
+    Pan { to: [f64; 2], seconds: f64 },
+    Say { text: String, seconds: f64 },
+    Wait { seconds: f64 },
+}
+
+impl Step {
+    pub fn seconds(&self) -> f64 {
+        let seconds = match *self {
+            Step::Walk { seconds, .. } | Step::MoveEnemy { seconds, .. } => seconds,
+            Step::Pan { seconds, .. } | Step::Say { seconds, .. } => seconds,
+            Step::Wait { seconds } => seconds,
+        };
+        seconds.max(0.0)
+    }
+}
+
+pub fn parse(json: &str) -> Result<Vec<Step>, String> {
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Step>, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cutscene {
+    pub steps: Vec<Step>,
+    // Which step we're on, and how far into it.
+    pub index: usize,
+    pub elapsed: f64,
+    // Where whatever the step moves was when it started, or None if the
+    // step hasn't started yet.
+    from: Option<(f64, f64)>,
+    // What the last say said, and where the last pan left the middle of
+    // the view.
+    pub text: Option<String>,
+    pub camera: Option<(f64, f64)>,
+}
+
+impl Cutscene {
+    pub fn new(steps: Vec<Step>) -> Cutscene {
+        Cutscene { steps: steps, index: 0, elapsed: 0.0, from: None, text: None, camera: None }
+    }
+
+    pub fn done(&self) -> bool {
+        self.index >= self.steps.len()
+    }
+
+    /*
+     * Move on by `dt`. A step that finishes partway through hands what's
+     * left of `dt` on to the next one, so a run of short steps takes just
+     * as long as it says, whatever size the ticks are.
+     */
+    pub fn update(&mut self, app: &mut App, dt: f64) {
+        let mut left = dt;
+        let mut walking = None;
+        while let Some(step) = self.steps.get(self.index).cloned() {
+            let from = self.start(&step, app);
+            let seconds = step.seconds();
+            let spent = left.min(seconds - self.elapsed).max(0.0);
+            self.elapsed += spent;
+            left -= spent;
+            let t = if seconds > 0.0 { self.elapsed / seconds } else { 1.0 };
+            if let Some(facing) = self.place(&step, from, t, app) {
+                walking = Some(facing);
+            }
+            // The allowance is for rounding: ticks of 1/120 don't quite add
+            // up to a second.
+            if self.elapsed < seconds - 1e-9 { break }
+            self.index += 1;
+            self.elapsed = 0.0;
+            self.from = None;
+        }
+        let state = walking.map_or(AnimationState::Idle, AnimationState::walking);
+        app.animator.update(dt, state);
+        self.aim_camera(app, dt);
+    }
+
+    // Do all the rest of it at once, finishing every step there's left.
+    pub fn skip(&mut self, app: &mut App) {
+        while let Some(step) = self.steps.get(self.index).cloned() {
+            let from = self.start(&step, app);
+            self.place(&step, from, 1.0, app);
+            self.index += 1;
+            self.from = None;
+        }
+        self.elapsed = 0.0;
+    }
+
+    // Where `step` starts from, noting it down if the step's just begun.
+    fn start(&mut self, step: &Step, app: &App) -> (f64, f64) {
+        if let Some(from) = self.from { return from }
+        let from = match *step {
+            Step::Walk { player, .. } => app.players.get(player).map_or((0.0, 0.0), |p| (p.x, p.y)),
+            Step::MoveEnemy { enemy, .. } => enemy_position(app, enemy).unwrap_or((0.0, 0.0)),
+            Step::Pan { .. } => camera_centre(app),
+            Step::Say { ref text, .. } => {
+                self.text = if text.is_empty() { None } else { Some(text.clone()) };
+                (0.0, 0.0)
+            }
+            Step::Wait { .. } => (0.0, 0.0),
+        };
+        self.from = Some(from);
+        from
+    }
+
+    // Put whatever `step` moves `t` of the way along, from 0 to 1. Player
+    // one walking says which way they're facing, for the animation.
+    fn place(&mut self, step: &Step, from: (f64, f64), t: f64, app: &mut App)
+             -> Option<Facing> {
+        match *step {
+            Step::Walk { player, to, .. } => {
+                let at = between(from, to, t);
+                let p = match app.players.get_mut(player) {
+                    Some(p) => p,
+                    None => return None,
+                };
+                p.x = at.0;
+                p.y = at.1;
+                let facing = Facing::from_direction(to[0] - from.0, to[1] - from.1);
+                if let Some(facing) = facing { p.facing = facing }
+                if player == 0 && t < 1.0 { facing } else { None }
+            }
+            Step::MoveEnemy { enemy, to, .. } => {
+                let at = between(from, to, t);
+                if let Some(id) = app.scene.of_kind(Kind::Enemy).get(enemy) {
+                    app.scene.positions[id.index] = Some(Position { x: at.0, y: at.1 });
+                }
+                None
+            }
+            // The camera doesn't walk, it glides, so it eases in and out.
+            Step::Pan { to, .. } => {
+                self.camera = Some(between(from, to, Easing::EaseInOut.apply(t)));
+                None
+            }
+            Step::Say { .. } | Step::Wait { .. } => None,
+        }
+    }
+
+    // Where the last pan left the camera, or if there hasn't been one,
+    // after the player like always.
+    fn aim_camera(&self, app: &mut App, dt: f64) {
+        match self.camera {
+            Some(centre) => {
+                app.camera.centre_on(centre.into());
+                let (width, height) = app.world_size();
+                app.camera.clamp_to(width, height);
+            }
+            None => app.update_camera(dt),
+        }
+    }
+}
+
+// Run the app's cutscene on by `dt`, and get rid of it once it's over. It
+// comes out of App for the duration, so it can move App's things about.
+pub fn run(app: &mut App, dt: f64) {
+    let mut cutscene = match app.cutscene.take() {
+        Some(cutscene) => cutscene,
+        None => return,
+    };
+    cutscene.update(app, dt);
+    if cutscene.done() {
+        info!("Cutscene over, on with the level");
+    } else {
+        app.cutscene = Some(cutscene);
+    }
+}
+
+fn between(from: (f64, f64), to: [f64; 2], t: f64) -> (f64, f64) {
+    let t = t.max(0.0).min(1.0);
+    (from.0 + (to[0] - from.0) * t, from.1 + (to[1] - from.1) * t)
+}
+
+fn enemy_position(app: &App, enemy: usize) -> Option<(f64, f64)> {
+    let id = app.scene.of_kind(Kind::Enemy).get(enemy).cloned()?;
+    app.scene.positions[id.index].map(|p| (p.x, p.y))
+}
+
+fn camera_centre(app: &App) -> (f64, f64) {
+    let camera = &app.camera;
+    (camera.x + camera.view_width() / 2.0, camera.y + camera.view_height() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_cutscene() {
+        let steps = parse(r#"[
+            { "walk": { "to": [200.0, 120.0], "seconds": 1.5 } },
+            { "move_enemy": { "enemy": 1, "to": [700.0, 150.0], "seconds": 1.0 } },
+            { "say": { "text": "Hello", "seconds": -1.0 } },
+            { "wait": { "seconds": 0.5 } }
+        ]"#).unwrap();
+        assert_eq!(steps[0], Step::Walk { player: 0, to: [200.0, 120.0], seconds: 1.5 });
+        assert_eq!(steps[1], Step::MoveEnemy { enemy: 1, to: [700.0, 150.0], seconds: 1.0 });
+        assert_eq!(steps[2].seconds(), 0.0);
+        assert_eq!(steps[3].seconds(), 0.5);
+        assert!(parse(r#"[{ "dance": { "seconds": 1.0 } }]"#).is_err());
+        assert_eq!(between((0.0, 0.0), [10.0, 20.0], 0.5), (5.0, 10.0));
+    }
+}
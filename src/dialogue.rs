@@ -0,0 +1,265 @@
+/*
+ * People to talk to. A level can have NPCs ("non-player characters", the
+ * people in a game who aren't you) standing about, listed in the level file
+ * under `npcs`, each with where they stand and the file with what they've
+ * got to say in it, next to the level file like the tilemap:
+ *
+ *     "npcs": [{ "at": [400.0, 300.0], "dialogue": "hermit.txt" }]
+ *
+ * Walk into one and press Return, and a box comes up across the bottom of
+ * the screen with what they say in it. The file's plain text, a page of
+ * the box at a time, with a blank line between pages:
+ *
+ *     You're not from round here, are you?
+ *
+ *     The way out is down in the far corner. Mind the blue ones. They bite.
+ *
+ * Each page comes up a letter at a time, the way a typewriter would,
+ * `dialogue_chars_per_second` of them a second (0 for all at once). Return
+ * shows the rest of the page straight away, if it's still coming, or turns
+ * over to the next one once it's all there, and after the last page it
+ * closes the box. The lines in the file are just for you: a page is run
+ * together and broken up again to fit the box, which has room for
+ * BOX_LINES lines, so keep pages short. While the box is open, the
+ * game holds still underneath, the same as it does for the help.
+ */
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use graphics::Context;
+
+use collision::Aabb;
+use entity::{Entity, Status, WorldInfo};
+use hud::{self, Hud};
+use palette::faded;
+use renderer::Renderer;
+use text::{Align, Text};
+
+// How big an NPC is, each way.
+pub const NPC_SIZE: f64 = 20.0;
+
+// How many lines of text the box has room for, and how see-through its
+// backing is.
+pub const BOX_LINES: usize = 3;
+const BOX_ALPHA: f32 = 0.85;
+
+// Where an NPC stands, by its middle, and which file it talks from. The
+// pages are read in from the file by level::load, not written in the level
+// file, hence the skip.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NpcSpot {
+    pub at: [f64; 2],
+    pub dialogue: String,
+    #[serde(skip)]
+    pub pages: Vec<String>,
+}
+
+/*
+ * The NPC itself, in the level's entities. It doesn't do much: it stands
+ * there, you can walk through it (you have to be touching it to talk), and
+ * it knows what it's going to say.
+ */
+pub struct Npc {
+    pub bounds: Aabb,
+    pub color: [f32; 4],
+    pub pages: Vec<String>,
+}
+
+impl Npc {
+    pub fn at(x: f64, y: f64, color: [f32; 4], pages: Vec<String>) -> Npc {
+        let half = NPC_SIZE / 2.0;
+        Npc { bounds: Aabb::new(x - half, y - half, NPC_SIZE, NPC_SIZE), color: color,
+              pages: pages }
+    }
+}
+
+impl Entity for Npc {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    // A square with a head on, so it's not mistaken for a pickup.
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        let head = b.w / 2.0;
+        r.rect(context, b.x, b.y + head / 2.0, b.w, b.h - head / 2.0, self.color);
+        r.rect(context, b.x + head / 2.0, b.y - head / 2.0, head, head, self.color);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn sprite(&self) -> Option<&'static str> { Some("npc") }
+
+    fn dialogue(&self) -> Option<&[String]> { Some(&self.pages[..]) }
+}
+
+// A dialogue file's pages: blank lines between them, and each one's lines
+// (and any runs of spaces) run together with single spaces.
+pub fn parse(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|page| page.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|page| !page.is_empty())
+        .collect()
+}
+
+pub fn load(path: &Path) -> Result<Vec<String>, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    Ok(parse(&contents.replace("\r\n", "\n")))
+}
+
+/*
+ * `text` broken into lines no wider than `width` in `style`, at the spaces.
+ * A word too long for a line on its own gets a line to itself anyway,
+ * sticking out. Every break takes the place of one space, which reveal
+ * relies on.
+ */
+pub fn wrap(text: &str, style: &Text, width: f64) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let longer = if line.is_empty() { word.to_string() }
+                     else { format!("{} {}", line, word) };
+        if !line.is_empty() && style.width(&longer) > width {
+            lines.push(line);
+            line = word.to_string();
+        } else {
+            line = longer;
+        }
+    }
+    if !line.is_empty() { lines.push(line) }
+    lines
+}
+
+// The first `count` characters of `lines`, as lines, counting each break
+// between them as one (it was a space, before wrap).
+pub fn reveal(lines: &[String], count: usize) -> Vec<String> {
+    let mut left = count;
+    let mut shown = Vec::new();
+    for line in lines.iter() {
+        let length = line.chars().count();
+        shown.push(line.chars().take(left).collect());
+        if left <= length { break }
+        left -= length + 1;
+    }
+    shown
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dialogue {
+    pub pages: Vec<String>,
+    pub page: usize,
+    // How many of the page's characters are showing so far. A fraction,
+    // since an update can be too short for a whole one.
+    pub shown: f64,
+}
+
+impl Dialogue {
+    pub fn new(pages: Vec<String>) -> Dialogue {
+        Dialogue { pages: pages, page: 0, shown: 0.0 }
+    }
+
+    fn length(&self) -> f64 {
+        self.pages.get(self.page).map_or(0, |p| p.chars().count()) as f64
+    }
+
+    // The typewriter, `chars_per_second` fast.
+    pub fn update(&mut self, dt: f64, chars_per_second: f64) {
+        self.shown = if chars_per_second <= 0.0 {
+            self.length()
+        } else {
+            (self.shown + dt * chars_per_second).min(self.length())
+        };
+    }
+
+    // Whether the whole page is showing.
+    pub fn revealed(&self) -> bool {
+        self.shown >= self.length()
+    }
+
+    /*
+     * Return: the rest of the page if it's still coming, or the next page
+     * if it's all there. False once there are no more pages, meaning it's
+     * time the box went away.
+     */
+    pub fn advance(&mut self) -> bool {
+        if !self.revealed() {
+            self.shown = self.length();
+            return true;
+        }
+        self.page += 1;
+        self.shown = 0.0;
+        self.page < self.pages.len()
+    }
+
+    /*
+     * The box, across the bottom of a window `size`, with as much of the
+     * page in it as is showing, in `style`, on a backing of `shade`. Once
+     * it's all there, `more` goes in the bottom corner, to say Return moves
+     * it on.
+     */
+    pub fn draw(&self, size: (f64, f64), style: &Text, shade: [f32; 4], more: &str,
+                hud: &mut Hud, context: &Context, r: &mut dyn Renderer) {
+        let (w, h) = size;
+        let padding = hud::MARGIN;
+        let line_height = hud::line_height(style.size);
+        let height = line_height * BOX_LINES as f64 + padding * 2.0;
+        let (x, y, width) = (hud::MARGIN, h - hud::MARGIN - height, w - hud::MARGIN * 2.0);
+        r.rect(context, x, y, width, height, faded(shade, BOX_ALPHA));
+        r.outline(context, [x, y, width, height], 2.0, style.color);
+
+        let page = self.pages.get(self.page).map_or("", |p| &p[..]);
+        let lines = wrap(page, style, width - padding * 2.0);
+        let shown = reveal(&lines, self.shown as usize);
+        hud.draw_lines(&shown, style, x + padding, y + padding, context, r);
+        if self.revealed() {
+            let corner = style.clone().align(Align::Right);
+            hud.write(&corner, more, x + width - padding, y + height - padding, context, r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_come_up_a_letter_at_a_time() {
+        let pages = parse("You're not\nfrom round here.\n\n\n   Mind the   blue ones.\n");
+        assert_eq!(pages, vec!["You're not from round here.", "Mind the blue ones."]);
+
+        let mut dialogue = Dialogue::new(pages);
+        dialogue.update(0.1, 40.0);
+        assert_eq!(dialogue.shown, 4.0);
+        assert!(!dialogue.revealed());
+        // Return while it's coming out shows the rest...
+        assert!(dialogue.advance());
+        assert!(dialogue.revealed() && dialogue.page == 0);
+        // ...then turns over...
+        assert!(dialogue.advance());
+        assert_eq!((dialogue.page, dialogue.shown), (1, 0.0));
+        // ...and with no typewriter it's all there at once.
+        dialogue.update(0.0, 0.0);
+        assert!(dialogue.revealed());
+        assert!(!dialogue.advance());
+    }
+
+    #[test]
+    fn pages_fit_the_box() {
+        // Six pixels a letter, so sixty is ten letters.
+        let style = Text::new(16, [1.0; 4]);
+        let lines = wrap("one two three extraordinary", &Text::new(10, [1.0; 4]), 60.0);
+        assert_eq!(lines, vec!["one two", "three", "extraordinary"]);
+        let lines = wrap("a b c d e f", &style, 60.0);
+        assert!(lines.iter().all(|l| style.width(l) <= 60.0));
+        assert_eq!(lines.join(" "), "a b c d e f");
+
+        let lines = vec!["one two".to_string(), "three".to_string()];
+        assert_eq!(reveal(&lines, 3), vec!["one"]);
+        assert_eq!(reveal(&lines, 9), vec!["one two", "t"]);
+        assert_eq!(reveal(&lines, 100), lines);
+    }
+}
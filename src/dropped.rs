@@ -0,0 +1,94 @@
+/*
+ * Files dropped on the window. Drag a level or a save out of wherever it
+ * is and let go of it over the game, and it's played, with no need to go
+ * anywhere near the command line: a level starts a game on it there and
+ * then, and a save picks up where it left off. Which is handy for trying
+ * out a level someone's sent you, or showing them how far you got.
+ *
+ * Which it is goes by the name first. A file called something.level.json
+ * is a level. Anything else is tried as a save, since a save says exactly
+ * what's in it and a level wouldn't pass for one, and if it isn't one, as
+ * a level after all (the ones in levels/ are just level1.json and so on).
+ * A level's tilemap, cutscene and dialogue are looked for next to it, the
+ * same as for --level.
+ *
+ * A dropped level goes in with the rest, in place of the one with the same
+ * name if there is one, so that dropping a level you're editing again
+ * plays the new version, or on the end if not. It's only in this run's
+ * levels, not in levels/, so it's gone again next time they're all loaded
+ * from there (trying again after a game over, for one). A save has to be
+ * of one of those levels, the same as with F9.
+ *
+ * None of it goes in a recording, since it isn't a key, so a replay of a
+ * game that had something dropped on it won't come out the same.
+ */
+use std::path::Path;
+
+use level::{self, Level};
+use save::{self, Snapshot};
+
+pub enum Dropped {
+    Level(Level),
+    Save(Snapshot),
+}
+
+// What the file at `path` is, and what's in it.
+pub fn open(path: &Path) -> Result<Dropped, String> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if name.ends_with(".level.json") {
+        return level::load(path).map(Dropped::Level);
+    }
+    match save::load(path) {
+        Ok(snapshot) => Ok(Dropped::Save(snapshot)),
+        Err(not_a_save) => level::load(path).map(Dropped::Level).map_err(|not_a_level| {
+            format!("{} isn't a save or a level ({}; {})", path.display(), not_a_save,
+                    not_a_level)
+        }),
+    }
+}
+
+// Put `level` in `levels`, over the one with the same name, if any. Where
+// it went.
+pub fn add_level(levels: &mut Vec<Level>, level: Level) -> usize {
+    match levels.iter().position(|l| l.name == level.name) {
+        Some(i) => {
+            levels[i] = level;
+            i
+        }
+        None => {
+            levels.push(level);
+            levels.len() - 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn levels_go_in_over_the_one_with_their_name() {
+        let path = env::temp_dir().join(format!("jumpy-dropped-{}.level.json",
+                                                ::std::process::id()));
+        let mut level = Level::fallback();
+        level.name = "Dropped in".to_string();
+        level::save(&path, &level).unwrap();
+        let opened = open(&path);
+        fs::remove_file(&path).unwrap();
+        let opened = match opened {
+            Ok(Dropped::Level(level)) => level,
+            _ => panic!("that was a level"),
+        };
+        assert_eq!(opened.name, "Dropped in");
+
+        let mut levels = vec![Level::fallback()];
+        assert_eq!(add_level(&mut levels, opened.clone()), 1);
+        assert_eq!(add_level(&mut levels, opened), 1);
+        assert_eq!(levels.len(), 2);
+
+        let nothing = env::temp_dir().join("jumpy-dropped-nothing-here.json");
+        assert!(open(&nothing).is_err());
+    }
+}
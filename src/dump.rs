@@ -0,0 +1,136 @@
+/*
+ * The game's state, written down every so often for something else to
+ * read. With --dump-state, every --dump-interval ticks (every tick, unless
+ * you say otherwise) the whole of the game goes out as a line of JSON,
+ * either into a file or to stdout:
+ *
+ *     game --headless --dump-state stdout --dump-interval 60 | my-bot
+ *
+ * So a bot, a test, or a script drawing graphs of how a game went can know
+ * everything the game does without being built into it, or even being in
+ * Rust. It's one JSON object a line ("JSON Lines"), so a reader can take
+ * them one at a time as they come, and a file can be cut off anywhere
+ * without spoiling the lines before.
+ *
+ * A line is a State, which is two things we already had: the spectators'
+ * Frame (see spectate.rs), which has where everything is to draw it, and a
+ * save's Snapshot (see save.rs), which has how the player's doing - their
+ * speed, the power-ups they've got - and is what F9 can load, so a line
+ * from here can be pasted into save.json and played from.
+ *
+ * The log goes to stderr, so stdout has nothing but the lines on it. A
+ * file is started afresh each run, and written to across every game
+ * played in it, the way the spectators are told about each one.
+ */
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde_json;
+
+use save::Snapshot;
+use spectate::Frame;
+
+// What --dump-state is given to mean stdout rather than a file.
+pub const STDOUT: &'static str = "stdout";
+
+// How often a state goes out if --dump-interval doesn't say: every tick.
+pub const DEFAULT_INTERVAL: u64 = 1;
+
+// Where the states go.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    Stdout,
+    File(PathBuf),
+}
+
+impl Destination {
+    // "stdout" (or "-", which is what a lot of tools say) is stdout, and
+    // anything else is a file.
+    pub fn parse(arg: &str) -> Destination {
+        match arg {
+            STDOUT | "-" => Destination::Stdout,
+            path => Destination::File(PathBuf::from(path)),
+        }
+    }
+}
+
+// One line of the dump.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct State {
+    pub frame: Frame,
+    pub save: Snapshot,
+}
+
+impl State {
+    pub fn to_json(&self) -> String {
+        // Nothing in a State can fail to turn into JSON, the same as Frame.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+pub struct Dump {
+    pub interval: u64,
+    out: Box<dyn Write>,
+    // How many states have gone out, and whether writing has gone wrong,
+    // in which case we stop rather than complain every tick.
+    pub written: u64,
+    pub stopped: bool,
+}
+
+impl Dump {
+    pub fn new(out: Box<dyn Write>, interval: u64) -> Dump {
+        Dump { interval: interval.max(1), out: out, written: 0, stopped: false }
+    }
+
+    pub fn open(destination: &Destination, interval: u64) -> Result<Dump, String> {
+        let out: Box<dyn Write> = match *destination {
+            Destination::Stdout => Box::new(io::stdout()),
+            Destination::File(ref path) => Box::new(File::create(path)
+                .map_err(|e| format!("couldn't write {}: {}", path.display(), e))?),
+        };
+        Ok(Dump::new(out, interval))
+    }
+
+    // Whether tick number `tick` is one that gets written down.
+    pub fn due(&self, tick: u64) -> bool {
+        !self.stopped && tick % self.interval == 0
+    }
+
+    /*
+     * Write `state` down, a line of its own. Each one's flushed straight
+     * away, so whoever's reading gets it now rather than when a buffer
+     * somewhere fills up. If that fails (the reader's gone away, say, or
+     * the disk is full) that's it for this dump.
+     */
+    pub fn write(&mut self, state: &State) -> Result<(), String> {
+        let result = writeln!(self.out, "{}", state.to_json()).and_then(|_| self.out.flush());
+        match result {
+            Ok(()) => {
+                self.written += 1;
+                Ok(())
+            }
+            Err(e) => {
+                self.stopped = true;
+                Err(format!("couldn't dump the game's state, so that's the last: {}", e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stdout_is_a_name_for_stdout() {
+        assert_eq!(Destination::parse("stdout"), Destination::Stdout);
+        assert_eq!(Destination::parse("-"), Destination::Stdout);
+        assert_eq!(Destination::parse("states.jsonl"),
+                   Destination::File(PathBuf::from("states.jsonl")));
+        let dump = Dump::new(Box::new(io::sink()), 30);
+        assert!(dump.due(0) && dump.due(60) && !dump.due(45));
+        // Every 0 ticks would be never, which isn't what anyone means.
+        assert_eq!(Dump::new(Box::new(io::sink()), 0).interval, 1);
+    }
+}
@@ -0,0 +1,708 @@
+/*
+ * A very small entity-component system. App used to have a field for every
+ * kind of thing that moves about - a list of enemies, a list of
+ * projectiles - and every new kind meant another field, another loop in
+ * render, another in update, and another special case in the save. Here
+ * instead a thing (an "entity") is nothing but a number, its Id, and what
+ * it's like is down to which components it has: a Position says where it
+ * is, a Velocity that it moves, a Renderable that it gets drawn, and so
+ * on. A projectile is a Position, a Velocity, a Renderable, a Collider and
+ * a Lifetime; an enemy swaps the Velocity and Lifetime for Chase and
+ * Health.
+ *
+ * The behaviour lives in systems: plain functions that go through every
+ * entity that has the components they care about and do their one job.
+ * movement moves anything with a Position and a Velocity, whatever it is;
+ * render draws anything with a Position and a Renderable. A new kind of
+ * thing is mostly a new mix of the components we've already got.
+ *
+ * The components are kept column by column: one Vec per kind of component,
+ * all as long as each other, with an entity's components at the same index
+ * (its slot) in every one, and None where it hasn't got that component. It's
+ * not the fastest way there is, or the cleverest, but it's easy to follow,
+ * and rust is happy with it, because a system borrowing the positions
+ * mutably while it reads the velocities is borrowing two different Vecs.
+ *
+ * Walls and pickups still go in App's list of Box<dyn Entity> (see
+ * entity.rs), and the players are still players; those could move in here
+ * too, but they're not the ones that kept needing new fields.
+ */
+use std::collections::BTreeSet;
+use std::fmt;
+
+use graphics::Context;
+
+use assets::Assets;
+use collision::Aabb;
+use renderer::Renderer;
+
+/*
+ * Which entity. index is where its components are in the columns, and
+ * generation tells apart the different entities that have had that index
+ * over time. A slot gets used again once its entity's gone, but with the
+ * generation one higher, so an Id kept hold of after its entity has gone
+ * doesn't find some other entity sitting where it used to be: Scene just
+ * says it isn't there any more.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Id {
+    pub index: usize,
+    generation: u32,
+}
+
+// Where it is: the top-left corner, like everything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+// How fast it's going, in world units per second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub vx: f64,
+    pub vy: f64,
+}
+
+// That it gets drawn, and as what: a flat rectangle this big.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Renderable {
+    pub w: f64,
+    pub h: f64,
+    pub color: [f32; 4],
+}
+
+// That it can bump into things: its hitbox, from its Position, and what
+// sort of thing it is as far as the bumping goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collider {
+    pub w: f64,
+    pub h: f64,
+    pub kind: Kind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Enemy,
+    Shot,
+}
+
+impl Kind {
+    // Its picture's name in the atlas, if there's an atlas (see assets.rs).
+    pub fn sprite(&self) -> &'static str {
+        match *self {
+            Kind::Enemy => "enemy",
+            Kind::Shot => "shot",
+        }
+    }
+}
+
+// That it comes after the player. How fast is up to the config, which can
+// change while it's going, so that's not in here; see enemy::chase_one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chase;
+
+// That it takes more than one hit to get rid of: how many hit points it's
+// got left, out of how many it started with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub points: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn full(max: u32) -> Health {
+        Health { points: max, max: max }
+    }
+}
+
+// That it only lasts so long: gone once age gets to limit, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lifetime {
+    pub age: f64,
+    pub limit: f64,
+}
+
+/*
+ * The components for a new entity, all in one go. Anything left out is
+ * None, so making something is a matter of saying what it has:
+ * Parts { position: Some(...), chase: Some(Chase), ..Parts::default() }.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Parts {
+    pub position: Option<Position>,
+    pub velocity: Option<Velocity>,
+    pub renderable: Option<Renderable>,
+    pub collider: Option<Collider>,
+    pub chase: Option<Chase>,
+    pub health: Option<Health>,
+    pub lifetime: Option<Lifetime>,
+}
+
+/*
+ * A pool: a list whose slots get used again. Things like shots and
+ * particles come and go every few frames, and making room for each new one
+ * and tidying up after each old one keeps the allocator busy for nothing.
+ * So rather than going from the list, something that's done with is only
+ * marked free, and the next thing to come along goes in its place; the
+ * list only grows when every slot is full, and that soon stops happening.
+ * Since nothing ever moves up to fill a gap, where a thing is in the pool
+ * stays the same for as long as it's there, which is what Scene's Ids are
+ * built on.
+ *
+ * A free slot keeps whatever was in it last. insert writes over that, but
+ * claim leaves it be, for when what's left behind is worth having: Scene
+ * keeps each slot's generation in a Pool, and the generation has to carry
+ * on from the last entity's. The first free slot is always the one used,
+ * so the same game always fills the same slots.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pool<T> {
+    items: Vec<T>,
+    live: Vec<bool>,
+    free: BTreeSet<usize>,
+}
+
+// How full a pool is: how many of its slots are in use, out of how many
+// there are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolStats {
+    pub live: usize,
+    pub slots: usize,
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.live, self.slots)
+    }
+}
+
+// Written out, because the derive would want a T that has a default too.
+impl<T> Default for Pool<T> {
+    fn default() -> Pool<T> {
+        Pool::new()
+    }
+}
+
+impl<T> Pool<T> {
+    pub fn new() -> Pool<T> {
+        Pool { items: Vec::new(), live: Vec::new(), free: BTreeSet::new() }
+    }
+
+    // Put `item` in the first free slot (or a new one), and say which.
+    pub fn insert(&mut self, item: T) -> usize {
+        match self.take_free() {
+            Some(i) => { self.items[i] = item; i }
+            None => self.push(item),
+        }
+    }
+
+    // Take the first free slot, with whatever was left in it, or a new one
+    // with a fresh T.
+    pub fn claim(&mut self) -> usize where T: Default {
+        match self.take_free() {
+            Some(i) => i,
+            None => self.push(T::default()),
+        }
+    }
+
+    fn take_free(&mut self) -> Option<usize> {
+        let first = self.free.iter().next().cloned();
+        if let Some(i) = first {
+            self.free.remove(&i);
+            self.live[i] = true;
+        }
+        first
+    }
+
+    fn push(&mut self, item: T) -> usize {
+        self.items.push(item);
+        self.live.push(true);
+        self.items.len() - 1
+    }
+
+    // Free slot `i`. Freeing one that's already free does nothing.
+    pub fn remove(&mut self, i: usize) {
+        if self.is_live(i) {
+            self.live[i] = false;
+            self.free.insert(i);
+        }
+    }
+
+    pub fn is_live(&self, i: usize) -> bool {
+        i < self.live.len() && self.live[i]
+    }
+
+    // What's in slot `i`, if it's in use.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        if self.is_live(i) { Some(&self.items[i]) } else { None }
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if self.is_live(i) { Some(&mut self.items[i]) } else { None }
+    }
+
+    // What's in slot `i` whether it's in use or not, for the likes of
+    // Scene's generations.
+    pub fn slot(&self, i: usize) -> &T {
+        &self.items[i]
+    }
+
+    pub fn slot_mut(&mut self, i: usize) -> &mut T {
+        &mut self.items[i]
+    }
+
+    // Everything in use, in slot order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a {
+        self.items.iter().zip(self.live.iter()).filter(|&(_, &live)| live)
+            .map(|(item, _)| item)
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut T> + 'a {
+        self.items.iter_mut().zip(self.live.iter()).filter(|&(_, &live)| live)
+            .map(|(item, _)| item)
+    }
+
+    // Free everything in use that `keep` says no to.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) {
+        let (live, free) = (&mut self.live, &mut self.free);
+        for (i, item) in self.items.iter().enumerate() {
+            if live[i] && !keep(item) {
+                live[i] = false;
+                free.insert(i);
+            }
+        }
+    }
+
+    // Free everything. The slots stay, ready for next time.
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+
+    // How many slots are in use, and how many there are, used or not.
+    pub fn len(&self) -> usize {
+        self.items.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn slots(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats { live: self.len(), slots: self.slots() }
+    }
+}
+
+/*
+ * The entities and their components. Besides keeping the columns, this is
+ * what hands out the Ids, and what lets a system get rid of things (or make
+ * them) while it's going through them. Despawning there and then is fine
+ * for a system that only looks at each entity once, but if it's going to
+ * look at the others again - every shot looking through every enemy, say -
+ * an enemy it's already knocked out mustn't be knocked out twice. So it can
+ * ask for it *later* instead: despawn_later marks the entity as on its way
+ * out, which hides it from ids and everything built on it, and the actual
+ * despawning waits until flush, once all the systems are done. spawn_later
+ * is the same for making things.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    // Which slots have an entity in at the moment, and which generation
+    // each slot is on. The columns are as long as this is.
+    slots: Pool<u32>,
+    pub positions: Vec<Option<Position>>,
+    pub velocities: Vec<Option<Velocity>>,
+    pub renderables: Vec<Option<Renderable>>,
+    pub colliders: Vec<Option<Collider>>,
+    pub chasers: Vec<Option<Chase>>,
+    pub healths: Vec<Option<Health>>,
+    pub lifetimes: Vec<Option<Lifetime>>,
+    // What's been asked for with spawn_later and despawn_later.
+    spawning: Vec<Parts>,
+    despawning: Vec<Id>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    /*
+     * Make a new entity, reusing the first free slot if there is one.
+     * Always the first, rather than whichever was freed most recently, so
+     * that the same game always hands out the same Ids; replays and the
+     * determinism tests compare whole Scenes. That's the Pool's doing; only
+     * a brand new slot needs room making for it in the columns.
+     */
+    pub fn spawn(&mut self, parts: Parts) -> Id {
+        let index = self.slots.claim();
+        if index == self.positions.len() {
+            self.positions.push(None);
+            self.velocities.push(None);
+            self.renderables.push(None);
+            self.colliders.push(None);
+            self.chasers.push(None);
+            self.healths.push(None);
+            self.lifetimes.push(None);
+        }
+        self.positions[index] = parts.position;
+        self.velocities[index] = parts.velocity;
+        self.renderables[index] = parts.renderable;
+        self.colliders[index] = parts.collider;
+        self.chasers[index] = parts.chase;
+        self.healths[index] = parts.health;
+        self.lifetimes[index] = parts.lifetime;
+        Id { index: index, generation: *self.slots.slot(index) }
+    }
+
+    // Get rid of an entity, and all its components with it. An entity
+    // that's already gone stays gone.
+    pub fn despawn(&mut self, id: Id) {
+        if !self.exists(id) { return }
+        let i = id.index;
+        *self.slots.slot_mut(i) += 1;
+        self.slots.remove(i);
+        self.positions[i] = None;
+        self.velocities[i] = None;
+        self.renderables[i] = None;
+        self.colliders[i] = None;
+        self.chasers[i] = None;
+        self.healths[i] = None;
+        self.lifetimes[i] = None;
+    }
+
+    // Make an entity once the systems are done, at the next flush.
+    pub fn spawn_later(&mut self, parts: Parts) {
+        self.spawning.push(parts);
+    }
+
+    // Get rid of an entity at the next flush. Until then it's still there,
+    // but ids (and everything else that goes looking) skips over it.
+    pub fn despawn_later(&mut self, id: Id) {
+        if self.alive(id) {
+            self.despawning.push(id);
+        }
+    }
+
+    // Do everything that's been put off. The despawns go first, so the
+    // spawns can have their slots.
+    pub fn flush(&mut self) {
+        for id in self.despawning.split_off(0) {
+            self.despawn(id);
+        }
+        for parts in self.spawning.split_off(0) {
+            self.spawn(parts);
+        }
+    }
+
+    // Whether `id`'s entity is still in the scene, even if it's on its
+    // way out.
+    fn exists(&self, id: Id) -> bool {
+        self.slots.get(id.index) == Some(&id.generation)
+    }
+
+    // Whether `id`'s entity is still in the scene, and not on its way out.
+    pub fn alive(&self, id: Id) -> bool {
+        self.exists(id) && !self.despawning.contains(&id)
+    }
+
+    // Every entity that's alive, in slot order. This is a Vec rather than
+    // an iterator so a system can change the scene while it goes through
+    // it.
+    pub fn ids(&self) -> Vec<Id> {
+        (0..self.slots.slots())
+            .map(|i| Id { index: i, generation: *self.slots.slot(i) })
+            .filter(|&id| self.alive(id))
+            .collect()
+    }
+
+    // The ones whose Collider says they're `kind`.
+    pub fn of_kind(&self, kind: Kind) -> Vec<Id> {
+        self.ids().into_iter().filter(|&id| self.kind(id) == Some(kind)).collect()
+    }
+
+    pub fn kind(&self, id: Id) -> Option<Kind> {
+        if !self.alive(id) { return None }
+        self.colliders[id.index].map(|c| c.kind)
+    }
+
+    pub fn count(&self, kind: Kind) -> usize {
+        self.of_kind(kind).len()
+    }
+
+    // How many of the scene's slots have something in them, for F4.
+    pub fn stats(&self) -> PoolStats {
+        self.slots.stats()
+    }
+
+    // An entity's hitbox, if it's alive and has a Position and a Collider.
+    // The collision systems find out where everything is through this.
+    pub fn bounds(&self, id: Id) -> Option<Aabb> {
+        if !self.alive(id) { return None }
+        match (self.positions[id.index], self.colliders[id.index]) {
+            (Some(p), Some(c)) => Some(Aabb::new(p.x, p.y, c.w, c.h)),
+            _ => None,
+        }
+    }
+
+    // The hitboxes of everything that's `kind`.
+    pub fn boxes(&self, kind: Kind) -> Vec<Aabb> {
+        self.of_kind(kind).into_iter().filter_map(|id| self.bounds(id)).collect()
+    }
+
+    pub fn despawn_all(&mut self, kind: Kind) {
+        for id in self.of_kind(kind) {
+            self.despawn(id);
+        }
+    }
+
+    /*
+     * Knock `points` off an entity's Health, and get rid of it (at the
+     * next flush) if that's the last of them. Something with no Health at
+     * all goes at the first hit. Returns whether it's been knocked out.
+     */
+    pub fn damage(&mut self, id: Id, points: u32) -> bool {
+        if !self.alive(id) { return false }
+        let knocked_out = match self.healths[id.index] {
+            Some(ref mut health) => {
+                health.points = health.points.saturating_sub(points);
+                health.points == 0
+            }
+            None => true,
+        };
+        if knocked_out {
+            self.despawn_later(id);
+        }
+        knocked_out
+    }
+
+    // Draw everything that's `kind` in `color` from now on, for when the
+    // config changes.
+    pub fn repaint(&mut self, kind: Kind, color: [f32; 4]) {
+        for id in self.of_kind(kind) {
+            if let Some(ref mut r) = self.renderables[id.index] {
+                r.color = color;
+            }
+        }
+    }
+}
+
+/*
+ * The systems that don't care what anything is. The ones that do - enemies
+ * chasing, shots hitting things - are in enemy.rs and projectile.rs.
+ */
+
+// Move everything that has a velocity along by it.
+pub fn movement(scene: &mut Scene, dt: f64) {
+    for (position, velocity) in scene.positions.iter_mut().zip(scene.velocities.iter()) {
+        if let (&mut Some(ref mut p), &Some(v)) = (position, velocity) {
+            p.x += v.vx * dt;
+            p.y += v.vy * dt;
+        }
+    }
+}
+
+// Age everything that has a Lifetime, and get rid of whatever's had its
+// time (at the next flush).
+pub fn ageing(scene: &mut Scene, dt: f64) {
+    for id in scene.ids() {
+        let expired = match scene.lifetimes[id.index] {
+            Some(ref mut lifetime) => {
+                lifetime.age += dt;
+                lifetime.age >= lifetime.limit
+            }
+            None => false,
+        };
+        if expired {
+            scene.despawn_later(id);
+        }
+    }
+}
+
+// Draw everything that has a Renderable, in slot order, apart from the
+// enemies: they're game objects, and draw themselves (see object.rs).
+pub fn render(scene: &Scene, assets: &Assets, context: &Context, r: &mut dyn Renderer) {
+    for id in scene.ids() {
+        if scene.kind(id) != Some(Kind::Enemy) {
+            render_one(scene, id, assets, context, r);
+        }
+    }
+}
+
+// Draw just the one, if it's alive and has a Position and a Renderable: as
+// its kind's picture from the atlas, if it has a kind and the atlas has a
+// picture for it, or a rectangle otherwise.
+pub fn render_one(scene: &Scene, id: Id, assets: &Assets, context: &Context, r: &mut dyn Renderer) {
+    if !scene.alive(id) { return }
+    if let (Some(p), Some(look)) = (scene.positions[id.index], scene.renderables[id.index]) {
+        let b = [p.x, p.y, look.w, look.h];
+        let collider = scene.colliders[id.index];
+        let drawn = collider.map_or(false, |c| assets.sprite(c.kind.sprite(), b, context, r));
+        if !drawn {
+            r.rect(context, p.x, p.y, look.w, look.h, look.color);
+        }
+    }
+}
+
+/*
+ * A little bar over anything that's been hurt but is still going, so you
+ * can see a shot did something: the red part is how much it's got left.
+ * Anything at full health doesn't get one. Enemies are the only things
+ * with Health, and each draws its own (see object::EnemyObject).
+ */
+pub const HEALTH_BAR_HEIGHT: f64 = 3.0;
+const HEALTH_BAR_GAP: f64 = 2.0;
+const HEALTH_BAR_BACK: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const HEALTH_BAR_LEFT: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+
+pub fn health_bar(scene: &Scene, id: Id, context: &Context, r: &mut dyn Renderer) {
+    let (b, health) = match (scene.bounds(id), scene.healths[id.index]) {
+        (Some(b), Some(health)) if health.points < health.max => (b, health),
+        _ => return,
+    };
+    let y = b.y - HEALTH_BAR_GAP - HEALTH_BAR_HEIGHT;
+    let left = b.w * health.points as f64 / health.max as f64;
+    r.rect(context, b.x, y, b.w, HEALTH_BAR_HEIGHT, HEALTH_BAR_BACK);
+    r.rect(context, b.x, y, left, HEALTH_BAR_HEIGHT, HEALTH_BAR_LEFT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::Context;
+    use renderer::{Call, Recorder};
+
+    fn mover(x: f64) -> Parts {
+        Parts {
+            position: Some(Position { x: x, y: 0.0 }),
+            velocity: Some(Velocity { vx: 10.0, vy: 0.0 }),
+            renderable: Some(Renderable { w: 4.0, h: 4.0, color: [1.0; 4] }),
+            collider: Some(Collider { w: 4.0, h: 4.0, kind: Kind::Shot }),
+            ..Parts::default()
+        }
+    }
+
+    #[test]
+    fn pools_fill_the_first_gap() {
+        let mut pool = Pool::new();
+        let (a, b, c) = (pool.insert('a'), pool.insert('b'), pool.insert('c'));
+        assert_eq!((a, b, c), (0, 1, 2));
+        pool.remove(c);
+        pool.remove(a);
+        assert_eq!(pool.get(a), None);
+        assert_eq!(pool.stats(), PoolStats { live: 1, slots: 3 });
+        assert_eq!(pool.insert('d'), a);
+        // What was left in a slot is still there for claim.
+        assert_eq!(*pool.slot(c), 'c');
+        assert_eq!(pool.iter().cloned().collect::<String>(), "db");
+        pool.retain(|&item| item != 'b');
+        assert_eq!(pool.stats().to_string(), "1/3");
+        pool.clear();
+        assert!(pool.is_empty());
+        assert_eq!(pool.slots(), 3);
+    }
+
+    #[test]
+    fn slots_get_used_again_but_ids_do_not() {
+        let mut scene = Scene::new();
+        let (a, b) = (scene.spawn(mover(0.0)), scene.spawn(mover(1.0)));
+        assert!(a != b);
+        scene.despawn(a);
+        assert_eq!(scene.ids(), vec![b]);
+        assert_eq!(scene.positions[a.index], None);
+        let c = scene.spawn(mover(2.0));
+        assert_eq!(c.index, a.index);
+        // The old Id doesn't get the new entity.
+        assert!(c != a && !scene.alive(a) && scene.alive(c));
+        assert_eq!(scene.bounds(a), None);
+        scene.despawn(a);
+        assert!(scene.alive(c));
+    }
+
+    #[test]
+    fn later_means_at_the_next_flush() {
+        let mut scene = Scene::new();
+        let a = scene.spawn(mover(0.0));
+        scene.despawn_later(a);
+        scene.spawn_later(mover(5.0));
+        // On its way out, so nothing finds it, but it's not gone yet.
+        assert!(scene.ids().is_empty() && scene.bounds(a).is_none());
+        assert!(scene.positions[a.index].is_some());
+        scene.flush();
+        let ids = scene.ids();
+        assert_eq!(ids.len(), 1);
+        assert!(ids[0] != a);
+        assert_eq!(scene.bounds(ids[0]).map(|b| b.x), Some(5.0));
+    }
+
+    #[test]
+    fn only_things_with_velocity_move() {
+        let mut scene = Scene::new();
+        let moving = scene.spawn(mover(0.0));
+        let still = scene.spawn(Parts { velocity: None, ..mover(50.0) });
+        movement(&mut scene, 0.5);
+        assert_eq!(scene.positions[moving.index], Some(Position { x: 5.0, y: 0.0 }));
+        assert_eq!(scene.positions[still.index], Some(Position { x: 50.0, y: 0.0 }));
+        assert_eq!(scene.bounds(moving), Some(Aabb::new(5.0, 0.0, 4.0, 4.0)));
+    }
+
+    #[test]
+    fn lifetimes_run_out() {
+        let mut scene = Scene::new();
+        let lifetime = Some(Lifetime { age: 0.0, limit: 1.0 });
+        let id = scene.spawn(Parts { lifetime: lifetime, ..mover(0.0) });
+        ageing(&mut scene, 0.5);
+        scene.flush();
+        assert_eq!(scene.ids(), vec![id]);
+        ageing(&mut scene, 0.5);
+        scene.flush();
+        assert!(scene.ids().is_empty());
+    }
+
+    #[test]
+    fn damage_wears_health_down() {
+        let mut scene = Scene::new();
+        let tough = scene.spawn(Parts { health: Some(Health::full(3)), ..mover(0.0) });
+        let flimsy = scene.spawn(mover(20.0));
+        assert!(!scene.damage(tough, 2));
+        assert_eq!(scene.healths[tough.index], Some(Health { points: 1, max: 3 }));
+        // Only hurt things get a bar: a third of the width left.
+        let mut r = Recorder::new();
+        health_bar(&scene, tough, &Context::new_abs(640.0, 480.0), &mut r);
+        health_bar(&scene, flimsy, &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.rects_in(HEALTH_BAR_LEFT), vec![[0.0, -5.0, 4.0 / 3.0, 3.0]]);
+        assert!(scene.damage(tough, 5));
+        assert!(scene.damage(flimsy, 1));
+        // Once it's on its way out, there's nothing left to hit.
+        assert!(!scene.damage(flimsy, 1));
+        scene.flush();
+        assert!(scene.ids().is_empty());
+    }
+
+    #[test]
+    fn renderables_get_drawn_and_repainted() {
+        let mut scene = Scene::new();
+        scene.spawn(mover(0.0));
+        scene.spawn(Parts { renderable: None, ..mover(20.0) });
+        scene.repaint(Kind::Shot, [0.0, 1.0, 0.0, 1.0]);
+        let mut r = Recorder::new();
+        render(&scene, &Assets::none(), &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls, vec![Call::Rect([0.0, 0.0, 4.0, 4.0], [0.0, 1.0, 0.0, 1.0])]);
+
+        // An enemy is left to draw itself.
+        let enemy = scene.spawn(Parts {
+            collider: Some(Collider { w: 4.0, h: 4.0, kind: Kind::Enemy }),
+            ..mover(40.0)
+        });
+        let mut r = Recorder::new();
+        render(&scene, &Assets::none(), &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls.len(), 1);
+        render_one(&scene, enemy, &Assets::none(), &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls[1], Call::Rect([40.0, 0.0, 4.0, 4.0], [1.0; 4]));
+    }
+}
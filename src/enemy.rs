@@ -0,0 +1,133 @@
+/*
+ * Enemies. For now there's only one kind, and it isn't very bright: every
+ * update it turns to face the player and heads straight for them. Walls
+ * still stop it, but it used to be you could lose one by putting something
+ * in the way. Now, if it's been given a route round (see pathfinding.rs),
+ * it heads for the next waypoint on that instead.
+ *
+ * An enemy is an entity in the Scene (see ecs.rs) with a Position, a
+ * Renderable, a Collider of Kind::Enemy, Health, and Chase, which is what
+ * marks it out as something that comes after you. Anything else given
+ * Chase would come after you too. How many hits it takes is its Health;
+ * enemy_health in the config is how many App's enemies get.
+ */
+use collision::{self, Aabb, Quadtree};
+use ecs::{Chase, Collider, Health, Id, Kind, Parts, Position, Renderable, Scene};
+use pathfinding::Paths;
+
+// How big an enemy is, each way.
+pub const ENEMY_SIZE: f64 = 16.0;
+
+// An enemy centred on (x, y), which is how levels say where they go. It
+// goes down in one hit.
+pub fn spawn(scene: &mut Scene, x: f64, y: f64, color: [f32; 4]) -> Id {
+    spawn_with_health(scene, x, y, color, 1)
+}
+
+// One that takes `health` hits' worth to knock out.
+pub fn spawn_with_health(scene: &mut Scene, x: f64, y: f64, color: [f32; 4], health: u32) -> Id {
+    scene.spawn(Parts {
+        position: Some(Position { x: x - ENEMY_SIZE / 2.0, y: y - ENEMY_SIZE / 2.0 }),
+        renderable: Some(Renderable { w: ENEMY_SIZE, h: ENEMY_SIZE, color: color }),
+        collider: Some(Collider { w: ENEMY_SIZE, h: ENEMY_SIZE, kind: Kind::Enemy }),
+        chase: Some(Chase),
+        health: Some(Health::full(health.max(1))),
+        ..Parts::default()
+    })
+}
+
+/*
+ * Where something at `b` ends up after heading `speed` world units per second
+ * towards `target` (the middle of the player), bumping into walls on the
+ * way just like the player does. We never go further than the distance
+ * left, otherwise an enemy that's caught up would overshoot and jitter back
+ * and forth on the spot.
+ */
+pub fn chase(b: Aabb, target: (f64, f64), speed: f64, dt: f64, obstacles: &[Aabb]) -> Aabb {
+    let (cx, cy) = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+    let (dx, dy) = (target.0 - cx, target.1 - cy);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance < 1e-9 { return b }
+    let step = (speed * dt).min(distance);
+    let (moved, _) = collision::move_and_collide(
+        b, dx / distance * step, dy / distance * step, obstacles);
+    moved
+}
+
+// The chasing system, for one entity at a time: if it's got Chase, it
+// takes a step towards `target`, or along its route to it, if `paths` has
+// one for it. Every enemy does this on its own turn (see object.rs).
+pub fn chase_one(scene: &mut Scene, id: Id, target: (f64, f64), speed: f64, dt: f64,
+                 walls: &Quadtree, paths: &mut Paths) {
+    if !scene.alive(id) || scene.chasers[id.index].is_none() { return }
+    if let Some(b) = scene.bounds(id) {
+        // Nothing gets further than this in one go, so only the walls this
+        // close could be in the way. Those are all chase gets to see.
+        let reach = speed * dt;
+        let near = walls.near(&Aabb::new(b.x - reach, b.y - reach,
+                                         b.w + reach * 2.0, b.h + reach * 2.0));
+        let middle = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+        let heading = paths.next(id, middle).unwrap_or(target);
+        let moved = chase(b, heading, speed, dt, &near);
+        scene.positions[id.index] = Some(Position { x: moved.x, y: moved.y });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::Aabb;
+    use ecs::{Kind, Scene};
+
+    // An enemy's box, centred on (x, y).
+    fn at(x: f64, y: f64) -> Aabb {
+        Aabb::new(x - ENEMY_SIZE / 2.0, y - ENEMY_SIZE / 2.0, ENEMY_SIZE, ENEMY_SIZE)
+    }
+
+    #[test]
+    fn heads_for_the_target() {
+        assert_eq!(chase(at(0.0, 0.0), (100.0, 0.0), 50.0, 1.0, &[]), at(50.0, 0.0));
+        // Diagonally it goes the same distance, not further.
+        let b = chase(at(0.0, 0.0), (300.0, 400.0), 50.0, 1.0, &[]);
+        assert!((b.x - at(30.0, 40.0).x).abs() < 1e-9);
+        assert!((b.y - at(30.0, 40.0).y).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stops_on_the_target_rather_than_overshooting() {
+        let b = chase(at(0.0, 0.0), (10.0, 0.0), 50.0, 1.0, &[]);
+        assert_eq!(b, at(10.0, 0.0));
+        assert_eq!(chase(b, (10.0, 0.0), 50.0, 1.0, &[]), at(10.0, 0.0));
+    }
+
+    #[test]
+    fn walls_get_in_the_way() {
+        let wall = Aabb::new(20.0, -50.0, 5.0, 100.0);
+        assert_eq!(chase(at(0.0, 0.0), (100.0, 0.0), 200.0, 1.0, &[wall]).right(), 20.0);
+    }
+
+    #[test]
+    fn everything_that_chases_gives_chase() {
+        let mut scene = Scene::new();
+        let enemy = spawn(&mut scene, 0.0, 0.0, [0.0, 0.0, 1.0, 1.0]);
+        let mut paths = Paths::new();
+        let walls = Quadtree::new(Vec::new());
+        chase_one(&mut scene, enemy, (100.0, 0.0), 50.0, 1.0, &walls, &mut paths);
+        assert_eq!(scene.bounds(enemy), Some(at(50.0, 0.0)));
+        assert_eq!(scene.count(Kind::Enemy), 1);
+    }
+
+    #[test]
+    fn a_route_goes_round_first() {
+        let mut scene = Scene::new();
+        let enemy = spawn(&mut scene, 0.0, 0.0, [0.0; 4]);
+        let mut paths = Paths::new();
+        paths.set(enemy, vec![(0.0, 30.0), (100.0, 30.0)]);
+        let walls = Quadtree::new(Vec::new());
+        chase_one(&mut scene, enemy, (100.0, 0.0), 50.0, 1.0, &walls, &mut paths);
+        assert_eq!(scene.bounds(enemy), Some(at(0.0, 30.0)));
+        // There, it's on to the next.
+        chase_one(&mut scene, enemy, (100.0, 0.0), 50.0, 1.0, &walls, &mut paths);
+        assert_eq!(scene.bounds(enemy), Some(at(50.0, 30.0)));
+    }
+}
@@ -0,0 +1,240 @@
+/*
+ * Piston, from the game's side. Everything the game needs from the engine
+ * (its input types, the event loop, OpenGL and the things it draws with)
+ * comes in through here, so this is the one file that knows which piston
+ * it's talking to.
+ *
+ * That mattered once already. The game started out on pre-1.0 piston,
+ * which came as a pile of little crates (input, event, shader_version) and
+ * handed out events to closures: `e.press(|key| ...)`, `e.render(|r| ...)`,
+ * one after another, each closure run if the event was that sort. piston
+ * 1.0 folded those crates into one, and events became plain values you
+ * `match` on, which is how most Rust is written and easier to follow: one
+ * arm for each sort of event, and the compiler checking you meant all of
+ * them. Porting meant changing the names of nearly everything, so rather
+ * than scatter piston's new names all over the game, they're gathered
+ * here, and the rest of the game mostly didn't notice.
+ *
+ * There are three parts to it:
+ *
+ * - `input`, which holds the names the game has always used for keys and
+ *   buttons (keyboard::Up, Keyboard(k), mouse::Left), so handlers.rs and
+ *   keys.rs read the same as before. lib.rs makes it the crate's `input`.
+ *
+ * - Event, our own list of the events the game cares about, which is what
+ *   run's loop matches on. piston's Event is more general than we need (it
+ *   has touch, focus, custom events from other libraries...) and nested
+ *   two deep, so `event` turns one of those into one of ours.
+ *
+ * - The EventLoop trait, for anything that hands Events out one at a time.
+ *   The real one is PistonLoop, over piston's Events, which knows when it's
+ *   time for an update and a frame. Scripted just hands out a list it was
+ *   given, so a test can put a game through a run of events with no
+ *   window; and anything else that wanted to drive the game (a different
+ *   engine, say) would only have to be another EventLoop.
+ */
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use piston::event_loop::{EventLoop as PistonEventLoop, EventSettings, Events};
+use piston::input::{
+    ButtonEvent,
+    ButtonState,
+    CloseEvent,
+    ControllerAxisEvent,
+    FileDrag,
+    Input,
+    MouseCursorEvent,
+    MouseScrollEvent,
+    RenderEvent,
+    ResizeEvent,
+    TextEvent,
+    UpdateEvent,
+};
+use piston::window::Window;
+
+// Gl is what the window draws on itself with. piston calls it GlGraphics
+// now; it's still Gl to us.
+pub use opengl_graphics::GlGraphics as Gl;
+// Which OpenGL to ask for: OpenGL::V3_2 and so on.
+pub use opengl_graphics::OpenGL;
+// Fonts. A GlyphCache can borrow its font's bytes for as long as it lives,
+// but ours are always loaded from files, so they're its own: 'static.
+pub type GlyphCache = ::opengl_graphics::GlyphCache<'static>;
+
+/*
+ * The input names. Old piston had keyboard::Up where new piston has
+ * Key::Up, and its Button's variants were in scope on their own; `pub use`
+ * of an enum's variants puts them back where they were.
+ */
+pub mod input {
+    pub use piston::input::{Button, ControllerAxisArgs, RenderArgs, UpdateArgs};
+    pub use piston::input::Button::{Controller, Hat, Keyboard, Mouse};
+
+    pub mod keyboard {
+        pub use piston::input::Key;
+        pub use piston::input::Key::*;
+    }
+
+    pub mod mouse {
+        pub use piston::input::MouseButton;
+        pub use piston::input::MouseButton::*;
+    }
+}
+
+use self::input::{Button, ControllerAxisArgs, RenderArgs, UpdateArgs};
+
+// How often the loop updates the game and draws it: updates a second, the
+// most frames a second, and whether to do both as fast as it can instead
+// (bench mode). See Game::pace.
+pub type Pace = (u64, u64, bool);
+
+/*
+ * What can happen, as far as the game's concerned.
+ *
+ * Render comes once a frame, and is where the drawing happens; Update
+ * comes `ups` times a second, and is where the game moves on. Press and
+ * Release are a key or a button going down and coming back up, and Text
+ * comes with a press that types something, and says what. Axis is a
+ * controller's stick (or trigger) moving, Cursor the mouse moving over the
+ * window, and Scroll its wheel turning. Resize is the window changing size,
+ * and Drop a file being dragged onto it and let go of (see dropped.rs).
+ * Anything else (the window getting the focus, say) is Other, and ignored.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Render(RenderArgs),
+    Update(UpdateArgs),
+    Press(Button),
+    Release(Button),
+    Text(String),
+    Axis(ControllerAxisArgs),
+    Cursor(f64, f64),
+    Scroll(f64, f64),
+    Resize(f64, f64),
+    Drop(PathBuf),
+    Close,
+    Other,
+}
+
+// One of piston's events, as one of ours.
+pub fn event(e: &::piston::input::Event) -> Event {
+    if let Some(r) = e.render_args() { return Event::Render(r) }
+    if let Some(u) = e.update_args() { return Event::Update(u) }
+    if let Some(b) = e.button_args() {
+        return match b.state {
+            ButtonState::Press => Event::Press(b.button),
+            ButtonState::Release => Event::Release(b.button),
+        };
+    }
+    if let Some(text) = e.text_args() { return Event::Text(text) }
+    if let Some(args) = e.controller_axis_args() { return Event::Axis(args) }
+    if let Some([x, y]) = e.mouse_cursor_args() { return Event::Cursor(x, y) }
+    if let Some([dx, dy]) = e.mouse_scroll_args() { return Event::Scroll(dx, dy) }
+    if let Some(args) = e.resize_args() {
+        return Event::Resize(args.window_size[0], args.window_size[1]);
+    }
+    if e.close_args().is_some() { return Event::Close }
+    // There's no FileDragEvent to ask, so this one's dug out by hand.
+    if let ::piston::input::Event::Input(Input::FileDrag(FileDrag::Drop(ref path)), _) = *e {
+        return Event::Drop(path.clone());
+    }
+    Event::Other
+}
+
+// Something that hands out events, one at a time, off the window `W`, until
+// there aren't any more.
+pub trait EventLoop<W> {
+    fn next(&mut self, window: &mut W) -> Option<Event>;
+
+    // Go at a different pace from now on.
+    fn set_pace(&mut self, pace: Pace);
+}
+
+// The real thing: piston's loop, which waits for the window's events, and
+// makes up the updates and frames in between at the right times.
+pub struct PistonLoop {
+    events: Events,
+}
+
+impl PistonLoop {
+    pub fn new(pace: Pace) -> PistonLoop {
+        let mut piston_loop = PistonLoop { events: Events::new(EventSettings::new()) };
+        piston_loop.set_pace(pace);
+        piston_loop
+    }
+
+    // The same as EventLoop's, but without having to say which window it's
+    // for, which it doesn't need.
+    pub fn set_pace(&mut self, pace: Pace) {
+        self.events.set_ups(pace.0);
+        self.events.set_max_fps(pace.1);
+        self.events.set_bench_mode(pace.2);
+    }
+}
+
+impl<W: Window> EventLoop<W> for PistonLoop {
+    fn next(&mut self, window: &mut W) -> Option<Event> {
+        self.events.next(window).map(|e| event(&e))
+    }
+
+    fn set_pace(&mut self, pace: Pace) {
+        PistonLoop::set_pace(self, pace);
+    }
+}
+
+// A list of events, handed out in order, whatever the window. The pace
+// doesn't come into it; the list is what happens.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scripted {
+    pub events: VecDeque<Event>,
+}
+
+impl Scripted {
+    pub fn new(events: Vec<Event>) -> Scripted {
+        Scripted { events: events.into_iter().collect() }
+    }
+}
+
+impl<W> EventLoop<W> for Scripted {
+    fn next(&mut self, _: &mut W) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn set_pace(&mut self, _: Pace) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::input::keyboard;
+    use piston::input::{ButtonArgs, Loop, Motion};
+
+    #[test]
+    fn pistons_events_become_ours() {
+        let press = Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Keyboard(keyboard::Space),
+            scancode: None,
+        });
+        assert_eq!(event(&press.into()), Event::Press(input::Keyboard(keyboard::Space)));
+        let update = ::piston::input::Event::Loop(Loop::Update(UpdateArgs { dt: 0.5 }));
+        assert_eq!(event(&update), Event::Update(UpdateArgs { dt: 0.5 }));
+        let moved: ::piston::input::Event = Input::Move(Motion::MouseCursor([3.0, 4.0])).into();
+        assert_eq!(event(&moved), Event::Cursor(3.0, 4.0));
+        let focus: ::piston::input::Event = Input::Focus(true).into();
+        assert_eq!(event(&focus), Event::Other);
+        let path = PathBuf::from("level3.json");
+        let dropped: ::piston::input::Event = Input::FileDrag(FileDrag::Drop(path.clone())).into();
+        assert_eq!(event(&dropped), Event::Drop(path));
+    }
+
+    #[test]
+    fn scripted_loops_hand_out_their_list() {
+        let mut script = Scripted::new(vec![Event::Resize(640.0, 480.0), Event::Close]);
+        EventLoop::<()>::set_pace(&mut script, (60, 60, false));
+        assert_eq!(script.next(&mut ()), Some(Event::Resize(640.0, 480.0)));
+        assert_eq!(script.next(&mut ()), Some(Event::Close));
+        assert_eq!(script.next(&mut ()), None);
+    }
+}
@@ -0,0 +1,279 @@
+/*
+ * An entity is anything in the world that wants a turn every update and gets
+ * drawn every frame: walls, pickups, and whatever comes next. Rather than
+ * App::render and App::update growing a new hand-written block every time we
+ * invent a new kind of thing, each kind implements the Entity trait and App
+ * just loops over a big list of them.
+ *
+ * This is where rust's traits shine. The list is a Vec<Box<dyn Entity>>: a
+ * box is a pointer to something on the heap, and Box<dyn Entity> means "a
+ * pointer to some type, I don't know which, that implements Entity" (the dyn
+ * is what says it's a trait being looked up as it runs, rather than a type).
+ * That lets one vector hold walls and pickups side by side. (Particles used
+ * to be in here too, but there are so many of them that they got a list of
+ * their own; see particles.rs.)
+ *
+ * The pickups are in here too, but they're game objects as well, along
+ * with the players and the enemies (see object.rs), so they're drawn with
+ * them rather than by draw_all.
+ */
+use graphics::Context;
+
+use renderer::Renderer;
+
+use assets::Assets;
+use collision::Aabb;
+use powerup::Effect;
+
+/*
+ * The bits of the wider world an entity might want to know about while it
+ * updates. Entities only get to look at this, not change it, which keeps
+ * them from treading on each other's toes.
+ */
+pub struct WorldInfo {
+    pub player: Aabb,
+    pub width: f64,
+    pub height: f64,
+    // Where the flag of the checkpoint you're on is, if you're on one.
+    pub checkpoint: Option<(f64, f64)>,
+}
+
+// What an entity tells us after its update: keep me around, or bin me.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Alive,
+    Dead,
+}
+
+pub trait Entity {
+    /*
+     * dt is the seconds since last update, as usual. If the entity wants to
+     * create new entities (a projectile throwing off sparks, say) it pushes
+     * them onto `spawn`; they join the world once everyone has had their
+     * turn this update.
+     */
+    fn update(&mut self, dt: f64, world: &WorldInfo, spawn: &mut Vec<Box<dyn Entity>>) -> Status;
+
+    // Drawing goes through a Renderer, so it works with or without a
+    // window; see renderer.rs.
+    fn draw(&self, context: &Context, r: &mut dyn Renderer);
+
+    // Where the entity is and how big, for collisions.
+    fn bounds(&self) -> Aabb;
+
+    // Whether the player should bump into this thing. Most things aren't
+    // solid, so that's the default; walls override it.
+    fn solid(&self) -> bool { false }
+
+    // Whether the player picks this thing up by touching it. Again most
+    // things aren't; pickups are.
+    fn collectible(&self) -> bool { false }
+
+    // What power picking it up gives, if it's a power-up (see powerup.rs).
+    // Anything else collectible, that isn't an item, is worth a point.
+    fn power_up(&self) -> Option<Effect> { None }
+
+    // Which item it is, if it's one you carry about instead (see
+    // inventory.rs).
+    fn item(&self) -> Option<&str> { None }
+
+    // What it takes to open, and how many, if it's a door.
+    fn lock(&self) -> Option<(&str, u32)> { None }
+
+    // Which of the atlas's pictures it's drawn with, if the atlas has one
+    // by that name (see assets.rs). Nothing, to always draw it the usual way.
+    fn sprite(&self) -> Option<&'static str> { None }
+
+    // What it says when you talk to it, a page at a time, if it's someone
+    // you can talk to (see dialogue.rs).
+    fn dialogue(&self) -> Option<&[String]> { None }
+
+    // Where it is, if it's a checkpoint's flag (see checkpoint.rs).
+    fn checkpoint(&self) -> Option<(f64, f64)> { None }
+}
+
+/*
+ * Give every entity its update in list order, throw away the ones that say
+ * they're dead, then tack anything newly spawned onto the end. Spawned
+ * entities don't get updated until the next time around, so something that
+ * spawns a copy of itself every update can't send us into an infinite loop.
+ */
+pub fn update_all(entities: &mut Vec<Box<dyn Entity>>, dt: f64, world: &WorldInfo) {
+    let mut spawned = Vec::new();
+    let mut alive = Vec::with_capacity(entities.len());
+    for mut e in entities.drain(..) {
+        if e.update(dt, world, &mut spawned) == Status::Alive {
+            alive.push(e);
+        }
+    }
+    alive.extend(spawned.into_iter());
+    *entities = alive;
+}
+
+// Draw every entity, in list order, so later ones end up on top. Not the
+// collectible ones, though: those are game objects (see object.rs).
+pub fn draw_all(entities: &[Box<dyn Entity>], assets: &Assets, context: &Context,
+                r: &mut dyn Renderer) {
+    for e in entities.iter().filter(|e| !e.collectible()) {
+        draw_one(&**e, assets, context, r);
+    }
+}
+
+// Draw just the one: with its picture from the atlas, if there is one, or
+// however it draws itself otherwise.
+pub fn draw_one(e: &dyn Entity, assets: &Assets, context: &Context, r: &mut dyn Renderer) {
+    let b = e.bounds();
+    let drawn = e.sprite().map_or(false, |name| {
+        assets.sprite(name, [b.x, b.y, b.w, b.h], context, r)
+    });
+    if !drawn {
+        e.draw(context, r);
+    }
+}
+
+/*
+ * Walls are the simplest entity there is: they sit there, they're solid,
+ * and they're drawn as a flat rectangle.
+ */
+pub struct Wall {
+    pub bounds: Aabb,
+    pub color: [f32; 4],
+}
+
+impl Entity for Wall {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.rect(context, b.x, b.y, b.w, b.h, self.color);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn solid(&self) -> bool { true }
+
+    fn sprite(&self) -> Option<&'static str> { Some("wall") }
+}
+
+/*
+ * Pickups are the things dotted about a level for you to collect. They're
+ * not solid - you walk over them, not into them - and they're collectible,
+ * so App takes them away (and gives you a point) when you touch one. Other
+ * than that they're just a small golden square.
+ */
+pub struct Pickup {
+    pub bounds: Aabb,
+    pub color: [f32; 4],
+}
+
+// How big a pickup is, each way.
+pub const PICKUP_SIZE: f64 = 12.0;
+
+impl Pickup {
+    // A pickup centred on (x, y), which is how levels say where they go.
+    pub fn at(x: f64, y: f64, color: [f32; 4]) -> Pickup {
+        let half = PICKUP_SIZE / 2.0;
+        Pickup { bounds: Aabb::new(x - half, y - half, PICKUP_SIZE, PICKUP_SIZE), color: color }
+    }
+}
+
+impl Entity for Pickup {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.rect(context, b.x, b.y, b.w, b.h, self.color);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn collectible(&self) -> bool { true }
+
+    fn sprite(&self) -> Option<&'static str> { Some("pickup") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use graphics::Context;
+    use collision::Aabb;
+    use renderer::Renderer;
+
+    /*
+     * A mock entity: it writes its id into a shared log every time it's
+     * updated, dies after `lives` updates, and optionally spawns a child on
+     * its first update. Rc<RefCell<..>> lets all the mocks share one log.
+     */
+    struct Mock {
+        id: u32,
+        lives: u32,
+        spawns: Option<u32>,
+        log: Rc<RefCell<Vec<u32>>>,
+    }
+
+    impl Entity for Mock {
+        fn update(&mut self, _: f64, _: &WorldInfo, spawn: &mut Vec<Box<dyn Entity>>) -> Status {
+            self.log.borrow_mut().push(self.id);
+            match self.spawns.take() {
+                Some(id) => spawn.push(Box::new(Mock {
+                    id: id, lives: 1, spawns: None, log: self.log.clone()
+                })),
+                None => {}
+            }
+            self.lives -= 1;
+            if self.lives == 0 { Status::Dead } else { Status::Alive }
+        }
+        fn draw(&self, _: &Context, _: &mut dyn Renderer) {}
+        fn bounds(&self) -> Aabb { Aabb::new(0.0, 0.0, 0.0, 0.0) }
+    }
+
+    fn world() -> WorldInfo {
+        WorldInfo { player: Aabb::new(0.0, 0.0, 10.0, 10.0), width: 640.0, height: 480.0,
+                    checkpoint: None }
+    }
+
+    fn mock(id: u32, lives: u32, log: &Rc<RefCell<Vec<u32>>>) -> Box<dyn Entity> {
+        Box::new(Mock { id: id, lives: lives, spawns: None, log: log.clone() })
+    }
+
+    #[test]
+    fn updates_in_list_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut entities = vec![mock(1, 5, &log), mock(2, 5, &log), mock(3, 5, &log)];
+        update_all(&mut entities, 0.1, &world());
+        assert_eq!(*log.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn dead_entities_are_removed() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut entities = vec![mock(1, 1, &log), mock(2, 2, &log), mock(3, 1, &log)];
+        update_all(&mut entities, 0.1, &world());
+        assert_eq!(entities.len(), 1);
+        update_all(&mut entities, 0.1, &world());
+        assert!(entities.is_empty());
+        assert_eq!(*log.borrow(), vec![1, 2, 3, 2]);
+    }
+
+    #[test]
+    fn spawned_entities_join_after_the_update() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut entities: Vec<Box<dyn Entity>> = vec![
+            Box::new(Mock { id: 1, lives: 2, spawns: Some(10), log: log.clone() }),
+            mock(2, 2, &log),
+        ];
+        update_all(&mut entities, 0.1, &world());
+        // The child didn't get a turn on the update it was born...
+        assert_eq!(*log.borrow(), vec![1, 2]);
+        assert_eq!(entities.len(), 3);
+        // ...but it does on the next one, after everything already there.
+        update_all(&mut entities, 0.1, &world());
+        assert_eq!(*log.borrow(), vec![1, 2, 1, 2, 10]);
+    }
+}
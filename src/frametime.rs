@@ -0,0 +1,247 @@
+/*
+ * How long frames are taking to draw. Frames per second is the number
+ * everyone quotes, but it's an average, and averages hide stutter: a game
+ * drawing 59 frames in 10ms and one in 400ms still averages out at a
+ * respectable-sounding 60 or so, and feels awful. So alongside the average
+ * we keep the 99th percentile, the time that 99 frames in 100 come in under.
+ * If that's far above the average, something is making the odd frame slow.
+ *
+ * We only remember the last few seconds' worth of frames, so the numbers
+ * follow what's happening now rather than what happened at startup.
+ *
+ * Even the 99th percentile is only a number, though, and a graph is better
+ * still: FrameGraph, further down, keeps how long each of the last few
+ * seconds' frames spent updating and drawing, and turns them into bars.
+ */
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// A frame's worth of time at 60 frames a second, the usual screen's rate.
+// A frame that takes longer than this has missed its turn on the screen.
+pub const BUDGET: f64 = 1.0 / 60.0;
+// How many frames the graph shows: three seconds' worth at 60 a second.
+pub const GRAPH_FRAMES: usize = 180;
+
+// The graph's colors: the updating part of a bar is the darker one. A frame
+// over budget is red instead of green.
+const UPDATE_COLOR: [f32; 4] = [0.0, 0.5, 0.0, 0.9];
+const RENDER_COLOR: [f32; 4] = [0.2, 0.9, 0.2, 0.9];
+const SLOW_UPDATE_COLOR: [f32; 4] = [0.6, 0.0, 0.0, 0.9];
+const SLOW_RENDER_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 0.9];
+const BUDGET_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+
+// A Duration as seconds, which is what all our timing is in.
+pub fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 * 1e-9
+}
+
+/*
+ * How long a couple of App's systems have spent working, all told, in
+ * seconds: the enemies chasing and catching, and the collision checks that
+ * aren't part of moving about (shots hitting things, pickups being picked
+ * up). Walls stopping you are part of moving, so they don't count here.
+ * Nothing shows these but --bench (see bench.rs), which watches them go up.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SystemTimes {
+    pub enemies: f64,
+    pub collision: f64,
+}
+
+pub struct FrameTimes {
+    // Seconds per frame, oldest first.
+    samples: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl FrameTimes {
+    // Remember the last `capacity` frames.
+    pub fn new(capacity: usize) -> FrameTimes {
+        FrameTimes { samples: VecDeque::with_capacity(capacity), capacity: capacity }
+    }
+
+    // A frame took `seconds`. If that's one too many to remember, the
+    // oldest one is forgotten.
+    pub fn record(&mut self, seconds: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(seconds);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // All the frames' times added up.
+    pub fn total(&self) -> f64 {
+        self.samples.iter().fold(0.0, |a, b| a + b)
+    }
+
+    // The average frame time in seconds, or 0 if there haven't been any.
+    pub fn average(&self) -> f64 {
+        if self.samples.is_empty() { return 0.0 }
+        self.samples.iter().fold(0.0, |a, b| a + b) / self.samples.len() as f64
+    }
+
+    pub fn fps(&self) -> f64 {
+        let average = self.average();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    /*
+     * The frame time that `p` (between 0 and 1) of frames come in at or
+     * under. This is the simple "nearest rank" way of doing it: sort the
+     * times, and take the one `p` of the way along.
+     */
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.samples.is_empty() { return 0.0 }
+        let mut sorted: Vec<f64> = self.samples.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let rank = (p * sorted.len() as f64).ceil().max(1.0) as usize;
+        sorted[rank.min(sorted.len()) - 1]
+    }
+
+    // Everything in one line, ready to show someone.
+    pub fn summary(&self) -> String {
+        format!("{:.0} fps, {:.1} ms avg, {:.1} ms p99",
+                self.fps(), self.average() * 1000.0, self.percentile(0.99) * 1000.0)
+    }
+}
+
+/*
+ * The last GRAPH_FRAMES frames, as how long each spent in update and how
+ * long in render. The event loop times both (see game::run). There can be
+ * any number of updates between two frames, or none, so update times add
+ * up until the next render comes along and finishes the frame off.
+ */
+#[derive(Default)]
+pub struct FrameGraph {
+    // (updating, drawing) in seconds, oldest first.
+    samples: VecDeque<(f64, f64)>,
+    updating: f64,
+}
+
+impl FrameGraph {
+    pub fn new() -> FrameGraph {
+        FrameGraph { samples: VecDeque::with_capacity(GRAPH_FRAMES), updating: 0.0 }
+    }
+
+    pub fn update_took(&mut self, seconds: f64) {
+        self.updating += seconds;
+    }
+
+    pub fn render_took(&mut self, seconds: f64) {
+        if self.samples.len() == GRAPH_FRAMES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((self.updating, seconds));
+        self.updating = 0.0;
+    }
+
+    /*
+     * The graph as [x, y, w, h] rectangles, ready for Renderer::rects. The
+     * bars stand on `bottom`, `bar_width` wide, left to right from the
+     * oldest frame at `left`; once the graph's full, every new frame pushes
+     * the lot one bar to the left, which is the scrolling. A bar is
+     * `scale` pixels high for every second of its frame, stopping at
+     * `max_height` so one enormous frame doesn't cover the screen, with
+     * updating at the bottom and drawing on top of it. Across it all goes a
+     * thin line at the height of BUDGET.
+     */
+    pub fn bars(&self, left: f64, bottom: f64, bar_width: f64, scale: f64, max_height: f64)
+                -> Vec<([f64; 4], [f32; 4])> {
+        let mut bars = Vec::with_capacity(self.samples.len() * 2 + 1);
+        for (i, &(updating, drawing)) in self.samples.iter().enumerate() {
+            let x = left + i as f64 * bar_width;
+            let (update_color, render_color) = if updating + drawing > BUDGET {
+                (SLOW_UPDATE_COLOR, SLOW_RENDER_COLOR)
+            } else {
+                (UPDATE_COLOR, RENDER_COLOR)
+            };
+            let update_height = (updating * scale).min(max_height);
+            let render_height = (drawing * scale).min(max_height - update_height);
+            bars.push(([x, bottom - update_height, bar_width, update_height], update_color));
+            bars.push(([x, bottom - update_height - render_height, bar_width, render_height],
+                       render_color));
+        }
+        let budget = (BUDGET * scale).min(max_height);
+        bars.push(([left, bottom - budget, GRAPH_FRAMES as f64 * bar_width, 1.0], BUDGET_COLOR));
+        bars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_recorded_is_all_zeroes() {
+        let times = FrameTimes::new(10);
+        assert_eq!((times.average(), times.fps(), times.percentile(0.99)), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn averages_and_percentiles() {
+        let mut times = FrameTimes::new(100);
+        // 99 frames at 1/128 of a second (about 8ms) and one awful one at
+        // 101/128 (about 790ms). Sums of 128ths of a second come out exact,
+        // so the average is exactly 1/64, with nothing rounded off.
+        for _ in 0..99 {
+            times.record(1.0 / 128.0);
+        }
+        times.record(101.0 / 128.0);
+        assert_eq!(times.average(), 1.0 / 64.0);
+        assert_eq!(times.fps(), 64.0);
+        assert_eq!(times.percentile(0.99), 1.0 / 128.0);
+        assert_eq!(times.percentile(1.0), 101.0 / 128.0);
+    }
+
+    #[test]
+    fn only_recent_frames_count() {
+        let mut times = FrameTimes::new(4);
+        times.record(1.0);
+        for _ in 0..4 {
+            times.record(0.25);
+        }
+        assert_eq!(times.average(), 0.25);
+        assert_eq!(times.summary(), "4 fps, 250.0 ms avg, 250.0 ms p99");
+    }
+
+    #[test]
+    fn the_graph_stacks_updates_under_drawing() {
+        let mut graph = FrameGraph::new();
+        // Two updates and a quick draw, then one slow draw on its own.
+        // (Times in 1024ths of a second, at a pixel each, so the sums come
+        // out exact.)
+        graph.update_took(2.0 / 1024.0);
+        graph.update_took(2.0 / 1024.0);
+        graph.render_took(4.0 / 1024.0);
+        graph.render_took(32.0 / 1024.0);
+        let bars = graph.bars(0.0, 100.0, 2.0, 1024.0, 50.0);
+        assert_eq!(bars.len(), 5);
+        assert_eq!(bars[0], ([0.0, 96.0, 2.0, 4.0], UPDATE_COLOR));
+        assert_eq!(bars[1], ([0.0, 92.0, 2.0, 4.0], RENDER_COLOR));
+        assert_eq!(bars[2], ([2.0, 100.0, 2.0, 0.0], SLOW_UPDATE_COLOR));
+        assert_eq!(bars[3], ([2.0, 68.0, 2.0, 32.0], SLOW_RENDER_COLOR));
+        assert_eq!(bars[4].1, BUDGET_COLOR);
+    }
+
+    #[test]
+    fn the_graph_scrolls_and_stays_short() {
+        let mut graph = FrameGraph::new();
+        for _ in 0..GRAPH_FRAMES {
+            graph.render_took(0.001);
+        }
+        graph.render_took(1.0);
+        let bars = graph.bars(0.0, 100.0, 2.0, 1000.0, 50.0);
+        assert_eq!(bars.len(), GRAPH_FRAMES * 2 + 1);
+        // The enormous frame is the newest, on the right, cut down to size.
+        let newest = bars[bars.len() - 2].0;
+        assert_eq!(newest, [(GRAPH_FRAMES - 1) as f64 * 2.0, 50.0, 2.0, 50.0]);
+    }
+}
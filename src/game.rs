@@ -0,0 +1,1821 @@
+/*
+ * The screens around the game, and the event loop that drives them. run
+ * is what used to be main: it reads the command line and the config, opens
+ * the window and loops until it's time to stop. main.rs just calls it.
+ */
+use std::cell::RefCell;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+
+// The window is where we draw all our stuff. Which sort of window it is
+// depends how we were built; see window_backend.rs. In a browser, there's
+// no window and no run either; the page drives Game itself (see web.rs).
+#[cfg(not(target_arch = "wasm32"))]
+use window_backend::{self, GameWindow};
+use input::{self, Button, Keyboard, Controller, Mouse, RenderArgs, UpdateArgs};
+use graphics::{Context, Graphics};
+// AdvancedWindow is what a window can do beyond the basics, like
+// changing its title.
+use piston::window::AdvancedWindow;
+/*
+ * engine provides our event loop, and Gl, the way the window draws on
+ * itself. An Event is one of the things that can happen: a frame to
+ * render, an update to do, a key going down or up, the mouse moving, and
+ * so on (see engine.rs for the whole list). PistonLoop hands them out one
+ * at a time, and knows how often to make the updates and the frames: its
+ * pace is the updates a second, the most frames a second, and whether to
+ * do them as fast as it can instead (bench mode).
+ */
+use engine::{Event, Gl};
+#[cfg(not(target_arch = "wasm32"))]
+use engine::{EventLoop, PistonLoop};
+#[cfg(not(target_arch = "wasm32"))]
+use piston::window::Window;
+// And our own bits and bobs
+use achievements::{self, Achievements, Progress, Toast};
+use app::{App, LevelState, Settings, SIM_DT};
+use assets::{self, Assets};
+use audio::{self, Audio, Music, Track};
+use autosave::{self, Autosave};
+use bot::{Bot, Command as BotCommand};
+use capture::{self, ClipRecorder};
+use cli;
+use dropped::{self, Dropped};
+use collision::Aabb;
+use config::{self, Config};
+use dump::{self, Dump};
+use frametime::seconds;
+use generate;
+use handlers::{handleKey, handleRelease};
+use hud::Hud;
+#[cfg(not(target_arch = "wasm32"))]
+use inspector::InspectorWindow;
+use keys;
+use level;
+use log;
+use lua::Lua;
+use menu::{Choice, Menu};
+use options::{self, Options, Setting};
+use net::{self, Link};
+use postprocess::{Effects, Pipeline};
+use renderer::{Batcher, Recorder, Renderer};
+use save::Snapshot;
+use scores::{self, Entry, HighScores};
+use script::{Action, Script};
+use shader::{self, Shader, Uniforms};
+use spectate::{self, Spectators};
+use transition::{self, Transition};
+use ui::Ui;
+
+// How much of the game --headless plays before stopping, in seconds.
+pub const HEADLESS_SECONDS: f64 = 10.0;
+
+/*
+ * The game goes through a few different screens: the title screen with its
+ * menu, the options screen, the game itself, and the game-over screen. Each
+ * one is a variant of GameState, and Game hands every event to whichever
+ * one we're on. The options screen has which of its settings is picked in
+ * it, and a game over good enough for the high-score table has a name
+ * in it, being typed in, until Enter puts it in the table. Only
+ * Playing has an App in it. A fresh App gets made every time a game starts,
+ * so each game begins from scratch, without reset having to be sure it's
+ * put every last thing back.
+ */
+pub enum GameState {
+    Menu(Menu),
+    Options(Options),
+    Playing(App),
+    GameOver { score: u32, level: usize, name: Option<String> },
+}
+
+impl GameState {
+    // What the screen's called, for the log.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameState::Menu(_) => "the menu",
+            GameState::Options(_) => "the options",
+            GameState::Playing(_) => "playing",
+            GameState::GameOver { .. } => "game over",
+        }
+    }
+}
+
+/*
+ * What lasts longer than any one game: the settings each App is made from,
+ * the pictures and the font (which take a while to load, and need OpenGL
+ * to do it), the sounds, and what we last put in the window's title bar.
+ * quit is set when it's time to stop, whatever screen we're on.
+ */
+pub struct Game {
+    pub settings: Settings,
+    pub state: GameState,
+    pub assets: Assets,
+    pub hud: Hud,
+    pub audio: Audio,
+    pub music: Music,
+    pub title: String,
+    pub quit: bool,
+    // Whether we want the window fullscreen. run keeps the real window in
+    // step with it.
+    pub fullscreen: bool,
+    // Whether to open the inspector's window beside the game's, for
+    // --inspector; see inspector.rs.
+    pub inspect: bool,
+    // The most frames a second the event loop should draw: the config's
+    // max_fps, unless --max-fps said otherwise.
+    pub max_fps: u64,
+    // How big the window is, in points, and how many pixels across a
+    // point is; see App.
+    pub window_size: (f64, f64),
+    pub draw_scale: f64,
+    // Where the mouse is, so a new game knows before it's next moved.
+    pub cursor: Option<(f64, f64)>,
+    // F12 asks for a screenshot, and run takes it once the next frame's
+    // been drawn.
+    pub screenshot_requested: bool,
+    // The last few seconds, ready for F2 to save as a GIF.
+    pub clip: ClipRecorder,
+    // The new screen coming in, if it's still coming (see transition.rs),
+    // and whether the game was paused the last we looked, since that's the
+    // App's business and we're not told.
+    pub transition: Option<Transition>,
+    pub paused: bool,
+    // The post-processing, if the window can do it (see postprocess.rs),
+    // and the config's shader, if it has one and it's loaded (shader.rs).
+    pub pipeline: Option<Pipeline>,
+    pub shader: Option<Shader>,
+    // The best games so far, and the file they're kept in, if they're
+    // being kept anywhere.
+    pub high_scores: HighScores,
+    pub high_scores_path: Option<PathBuf>,
+    // The same for the achievements, and the toasts saying you've just
+    // got one.
+    pub achievements: Achievements,
+    pub achievements_path: Option<PathBuf>,
+    pub toasts: Vec<Toast>,
+    // Another program at the controls, from --bot, and how many ticks
+    // it's asked for that haven't gone by yet (see bot.rs).
+    pub bot: Option<Bot>,
+    pub bot_ticks: u64,
+    // The game that was paused to go to the options from, which carries on
+    // when you come back.
+    pub suspended: Option<App>,
+    // The autosaves, if they're being kept (see autosave.rs), and the game
+    // Resume on the title screen carries on with, if there's one to.
+    pub autosave: Option<Autosave>,
+    pub resume: Option<Snapshot>,
+}
+
+impl Game {
+    pub fn new(settings: Settings) -> Game {
+        let window_size = (settings.config.window_width as f64,
+                           settings.config.window_height as f64);
+        Game {
+            settings: settings,
+            state: GameState::Menu(Menu::main()),
+            assets: Assets::none(),
+            hud: Hud::none(),
+            audio: Audio::none(),
+            music: Music::none(),
+            title: String::new(),
+            quit: false,
+            fullscreen: false,
+            inspect: false,
+            max_fps: settings.config.max_fps,
+            window_size: window_size,
+            draw_scale: 1.0,
+            cursor: None,
+            screenshot_requested: false,
+            clip: ClipRecorder::new(),
+            transition: None,
+            paused: false,
+            pipeline: None,
+            shader: None,
+            high_scores: HighScores::default(),
+            high_scores_path: None,
+            achievements: Achievements::default(),
+            achievements_path: None,
+            toasts: Vec::new(),
+            bot: None,
+            bot_ticks: 0,
+            suspended: None,
+            autosave: None,
+            resume: None,
+        }
+    }
+
+    // A replay is only played back once: the first game takes it, and any
+    // game after that is played for real.
+    pub fn start(&mut self) {
+        info!("Starting a new game");
+        let mut app = self.new_app();
+        app.replay = self.settings.replay.take();
+        if app.replaying() {
+            info!("Playing back a replay");
+        }
+        self.set_state(GameState::Playing(app));
+    }
+
+    // A game made from the settings, sized and scaled for the window.
+    fn new_app(&self) -> App {
+        let mut app = App::new(self.settings.clone());
+        app.resize_window(self.window_size.0, self.window_size.1);
+        app.draw_scale = self.draw_scale;
+        app.cursor = self.cursor;
+        app
+    }
+
+    /*
+     * A file's been dropped on the window (see dropped.rs). A level's
+     * played straight away, from the start. A save goes into the game
+     * going, if there is one, or a new one if not, which is only kept if
+     * the save loads, so a bad one leaves you where you were. Not in a
+     * replay, though, which has to be left to play as it was.
+     */
+    pub fn drop_file(&mut self, path: &Path) {
+        if let GameState::Playing(ref app) = self.state {
+            if app.replaying() {
+                warn!("Not while a replay's playing (ignoring {})", path.display());
+                return;
+            }
+        }
+        let snapshot = match dropped::open(path) {
+            Ok(Dropped::Level(level)) => {
+                info!("Playing the level \"{}\" from {}", level.name, path.display());
+                let index = dropped::add_level(&mut self.settings.levels, level);
+                let mut app = self.new_app();
+                app.level_index = index;
+                app.reset();
+                self.suspended = None;
+                self.set_state(GameState::Playing(app));
+                return;
+            }
+            Ok(Dropped::Save(snapshot)) => snapshot,
+            Err(e) => {
+                error!("Couldn't open what was dropped: {}", e);
+                return;
+            }
+        };
+        let restored = match self.state {
+            GameState::Playing(ref mut app) => Some(app.restore(&snapshot)),
+            _ => None,
+        };
+        let loaded = match restored {
+            Some(result) => result,
+            None => {
+                let mut app = self.new_app();
+                let result = app.restore(&snapshot);
+                if result.is_ok() {
+                    self.suspended = None;
+                    self.set_state(GameState::Playing(app));
+                }
+                result
+            }
+        };
+        match loaded {
+            Ok(()) => info!("Loaded {}", path.display()),
+            Err(e) => error!("Couldn't load {}: {}", path.display(), e),
+        }
+    }
+
+    // Over to another screen. Every change of screen goes through here, so
+    // the log has the lot of them, and they all get a transition.
+    pub fn set_state(&mut self, state: GameState) {
+        info!("From {} to {}", self.state.name(), state.name());
+        // Once there's a new game going, last time's is left behind.
+        if let GameState::Playing(_) = state {
+            self.resume = None;
+        }
+        self.state = state;
+        self.paused = false;
+        self.begin_transition(1.0);
+    }
+
+    // Start the config's transition, with the screen `from` covered.
+    pub fn begin_transition(&mut self, from: f64) {
+        let config = &self.settings.config;
+        self.transition = if config.transition_seconds > 0.0 {
+            Some(Transition::new(config.transition, config.transition_easing,
+                                 config.transition_seconds, from))
+        } else {
+            None
+        };
+    }
+
+    /*
+     * Write down the game in progress, so it can be played back with
+     * --replay. The seed goes in with it, so the replay gets the same random
+     * numbers; the top of the file says which level it started on too,
+     * because a replay only comes out the same going through the same
+     * levels. Playing back a replay doesn't write one; it'd only be
+     * the same again, or the first part of it if you quit early.
+     */
+    pub fn write_replay(&self) {
+        let app = match self.state {
+            GameState::Playing(ref app) if !app.replaying() => app,
+            _ => return,
+        };
+        let path = match self.settings.record_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let header = format!("jumpy replay, starting on {}\nplay it back with: game --replay {}",
+                             app.level().name, path.display());
+        let mut recording = Script::from_steps(app.recording.clone());
+        recording.seed = Some(self.settings.seed);
+        match recording.save(path, &header) {
+            Ok(()) => info!("Wrote the replay to {}", path.display()),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    // The window changed size. The App needs to know, and so does any App
+    // we start later.
+    pub fn resize(&mut self, width: f64, height: f64) {
+        self.window_size = (width, height);
+        if let GameState::Playing(ref mut app) = self.state {
+            app.resize_window(width, height);
+        }
+    }
+
+    // The window's pixels are now `scale` to a point: it's been dragged to
+    // a HiDPI screen, say, or off one.
+    pub fn set_draw_scale(&mut self, scale: f64) {
+        if scale == self.draw_scale || !(scale > 0.0) { return }
+        info!(target: Render, "{} pixels to a point", scale);
+        self.draw_scale = scale;
+        if let GameState::Playing(ref mut app) = self.state {
+            app.draw_scale = scale;
+        }
+    }
+
+    /*
+     * F10. The pictures, the font, the sounds and the shader are ours to
+     * load again; the config and the levels are the App's, if there's a
+     * game going. Pictures need an OpenGL context to load into, but F10
+     * only ever comes from a window's keyboard, so there's always one
+     * about. It's the same window, so the old shader's for throwing away.
+     */
+    pub fn reload_files(&mut self) {
+        let config = match self.state {
+            GameState::Playing(ref mut app) => {
+                app.reload_files();
+                app.config.clone()
+            }
+            _ => self.settings.config.clone(),
+        };
+        self.assets.load_pictures(&assets::default_dir());
+        self.hud = self.assets.fonts(&config);
+        self.audio = self.assets.sounds(&audio::default_dir());
+        if let Some(shader) = self.shader.take() {
+            shader.delete();
+        }
+        self.load_shader();
+    }
+
+    /*
+     * Try again after a game over: load the levels again (so if you've been
+     * editing the one that beat you, you get the new version), and start
+     * afresh on the level you got to. If the levels won't load any more we
+     * keep the ones we had rather than having nothing to play.
+     */
+    pub fn restart(&mut self, level: usize) {
+        if let Some(ref path) = self.settings.levels_path {
+            let levels = self.assets.levels(path);
+            if levels.is_empty() {
+                warn!("Couldn't reload the levels, playing the ones we had");
+            } else {
+                self.settings.levels = levels;
+            }
+        }
+        info!("Trying level {} again", level + 1);
+        let mut app = self.new_app();
+        if level < app.levels.len() {
+            app.level_index = level;
+            app.reset();
+        }
+        self.set_state(GameState::Playing(app));
+    }
+
+    /*
+     * The game's over. Hang on to any config changes and the update rate
+     * from the App that's finishing, so the next game starts with them
+     * rather than whatever we started with, then show the score. If it's
+     * made the high-score table, the game-over screen asks for a name
+     * first; a replay's score is someone else's game, so that doesn't count.
+     */
+    pub fn finish(&mut self) {
+        self.write_replay();
+        let (score, level, replayed) = match self.state {
+            GameState::Playing(ref app) => {
+                self.settings.config = app.config.clone();
+                self.settings.ups = app.ups;
+                (app.score, app.level_index, app.replaying())
+            }
+            _ => return,
+        };
+        let name = if !replayed && self.high_scores.qualifies(score) {
+            info!("A high score!");
+            Some(String::new())
+        } else {
+            None
+        };
+        // There's nothing to resume now.
+        if let Some(ref mut autosave) = self.autosave {
+            autosave.forget();
+        }
+        self.set_state(GameState::GameOver { score: score, level: level, name: name });
+    }
+
+    /*
+     * Enter, with a name typed in: the game goes in the high-score table,
+     * and the table goes back in its file. If the file won't write, the
+     * score's still on the screen, it just won't be there next time.
+     */
+    pub fn enter_high_score(&mut self) {
+        let entry = match self.state {
+            GameState::GameOver { score, level, ref mut name } => match name.take() {
+                Some(name) => Entry::now(&name, score, level),
+                None => return,
+            },
+            _ => return,
+        };
+        info!("{} scored {} on level {}", entry.name, entry.score, entry.level + 1);
+        self.high_scores.add(entry);
+        if let Some(ref path) = self.high_scores_path {
+            if let Err(e) = scores::save(path, &self.high_scores) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /*
+     * --headless: play `seconds` of the game as fast as we can, with no
+     * window, pressing keys when `script` says to, and say how it went.
+     * It's the same Game as ever, it's just that the events come from the
+     * script and the clock instead of from a window. Each update gets drawn
+     * too, onto a Recorder rather than a screen, so the drawing code gets a
+     * run as well. With an empty script nobody's at the controls and the
+     * player stands on the spawn point, which is still a fair check that the
+     * levels load and the enemies do what they ought to. Returns the score
+     * and the level we got to.
+     */
+    pub fn run_headless(&mut self, seconds: f64, script: &mut Script) -> (u32, usize) {
+        self.start();
+        // A bot counts in ticks, so with one each update is exactly one.
+        // It's the bot that says when to stop, too, not the clock.
+        let dt = if self.bot.is_some() { SIM_DT } else { 1.0 / self.ups() as f64 };
+        let (w, h) = self.window_size;
+        let mut renderer = Recorder::new();
+        let mut t = 0.0;
+        while t < seconds || self.bot.is_some() {
+            for step in script.due(t).iter() {
+                match step.action {
+                    Action::Press => self.handle_key(Keyboard(step.key)),
+                    Action::Release => self.handle_release(Keyboard(step.key)),
+                }
+            }
+            if !self.wait_for_bot() { break }
+            self.update(&UpdateArgs { dt: dt });
+            renderer.calls.clear();
+            self.draw(w, h, &mut renderer);
+            t += dt;
+            if self.quit { break }
+            if let GameState::GameOver { .. } = self.state { break }
+        }
+        let (score, level) = match self.state {
+            GameState::Playing(ref app) => (app.score, app.level_index),
+            GameState::GameOver { score, level, .. } => (score, level),
+            _ => (0, 0),
+        };
+        info!("After {:.1}s: level {}, score {}{}", t, level + 1, score,
+              if let GameState::GameOver { .. } = self.state { ", game over" } else { "" });
+        (score, level)
+    }
+
+    // Do what the bot's said since the last update, with a window, where
+    // it plays in real time.
+    pub fn obey_bot(&mut self) {
+        let commands = match self.bot {
+            Some(ref mut bot) => bot.received(),
+            None => return,
+        };
+        for command in commands.iter() {
+            self.obey(command);
+        }
+        // There's a real clock to keep up with, so steps don't count.
+        self.bot_ticks = 0;
+    }
+
+    /*
+     * Headless, with a bot, wait until it asks for a tick, doing whatever
+     * else it says meanwhile. Without one there's nothing to wait for.
+     * Whether to carry on: not if the bot's gone, or said to quit.
+     */
+    pub fn wait_for_bot(&mut self) -> bool {
+        while self.bot_ticks == 0 {
+            let command = match self.bot {
+                Some(ref mut bot) => bot.wait(),
+                None => return true,
+            };
+            match command {
+                Some(command) => self.obey(&command),
+                None => {
+                    info!("The bot's finished");
+                    return false;
+                }
+            }
+            if self.quit { return false }
+        }
+        self.bot_ticks -= 1;
+        true
+    }
+
+    // One of the bot's commands. Keys are pressed the same way as
+    // anyone's, so they go through handle_key.
+    pub fn obey(&mut self, command: &BotCommand) {
+        let keys = match self.bot {
+            Some(ref mut bot) => bot.keys(command),
+            None => Vec::new(),
+        };
+        for (action, key) in keys {
+            match action {
+                Action::Press => self.handle_key(Keyboard(key)),
+                Action::Release => self.handle_release(Keyboard(key)),
+            }
+        }
+        match *command {
+            BotCommand::Step(n) => self.bot_ticks += n,
+            BotCommand::Quit => self.quit = true,
+            _ => {}
+        }
+    }
+
+    // How many updates a second the event loop should run.
+    pub fn ups(&self) -> u64 {
+        match self.state {
+            GameState::Playing(ref app) => app.ups,
+            _ => self.settings.ups,
+        }
+    }
+
+    // Everything the event loop needs to know to tick the way we want:
+    // updates a second, frames a second, and bench mode. run makes a new
+    // loop whenever this changes.
+    pub fn pace(&self) -> (u64, u64, bool) {
+        (self.ups(), self.max_fps, self.settings.config.bench_mode)
+    }
+
+    /*
+     * Key presses. Playing, handleKey takes care of everything, same as
+     * it always has, unless a replay is at the controls, in which case
+     * the only key that does anything is quit. On the other screens
+     * there's hardly anything to do:
+     * Enter (or the controller's face button) moves on, or picks whatever's
+     * highlighted in the menu; Up and Down (or the D-pad) move the
+     * highlight; Left and Right change whatever's picked on the options
+     * screen, and Enter on one of the keys there waits for the new key; O
+     * while paused goes to the options and back; and the quit key quits.
+     * After a game over, the reset key tries again. While a name's being
+     * typed for the high-score table, though, letters are for the name, so
+     * neither of those does anything: Backspace rubs out, Enter puts the
+     * name in the table, and Escape leaves the game out of it.
+     */
+    pub fn handle_key(&mut self, key: Button) {
+        // A key being set on the options screen gets every key there is,
+        // even F11, except Escape, which leaves it as it was.
+        if let GameState::Options(ref mut options) = self.state {
+            if options.capturing {
+                match key {
+                    Keyboard(input::keyboard::Escape) => options.capturing = false,
+                    Keyboard(k) if options.capture(&mut self.settings.config, k) => {
+                        info!("{}", options.line(&self.settings.config, &self.settings.locale));
+                        for warning in config::warnings(&self.settings.config) {
+                            warn!("{}", warning);
+                        }
+                    }
+                    _ => info!("{} can't be one of the keys", keys::button_name(key)),
+                }
+                return;
+            }
+        }
+        // A click on a button is that button's key (see click).
+        if key == Mouse(input::mouse::Left) && self.click() {
+            return;
+        }
+        // F11 goes fullscreen (or back) whatever screen we're on.
+        if key == Keyboard(input::keyboard::F11) {
+            self.fullscreen = !self.fullscreen;
+            self.settings.config.fullscreen = self.fullscreen;
+            info!("Fullscreen {}", if self.fullscreen { "on" } else { "off" });
+            return;
+        }
+        // F12 takes a screenshot, whatever screen we're on.
+        if key == Keyboard(input::keyboard::F12) {
+            self.screenshot_requested = true;
+            return;
+        }
+        // F2 saves the last few seconds as a GIF, whatever screen we're on.
+        if key == Keyboard(input::keyboard::F2) {
+            match self.clip.save(&capture::clips_dir()) {
+                Ok(()) => info!("Saving the last {} seconds as a GIF", capture::CLIP_SECONDS),
+                Err(e) => warn!("Couldn't save a clip: {}", e),
+            }
+            return;
+        }
+        // F10 reads everything in again, whatever screen we're on.
+        if key == Keyboard(input::keyboard::F10) {
+            self.reload_files();
+            return;
+        }
+        let go = match key {
+            Keyboard(input::keyboard::Return) => true,
+            Controller(b) => b.button == 0,
+            _ => false,
+        };
+        let (up, down) = match key {
+            Keyboard(input::keyboard::Up) => (true, false),
+            Keyboard(input::keyboard::Down) => (false, true),
+            Controller(b) => (b.button == 11, b.button == 12),
+            _ => (false, false),
+        };
+        let (left, right) = match key {
+            Keyboard(input::keyboard::Left) => (true, false),
+            Keyboard(input::keyboard::Right) => (false, true),
+            Controller(b) => (b.button == 13, b.button == 14),
+            _ => (false, false),
+        };
+        let (quit, retry) = match key {
+            Keyboard(k) => (keys::is_key(k, &self.settings.config.quit_key),
+                            keys::is_key(k, &self.settings.config.reset_key)),
+            _ => (false, false),
+        };
+        match self.state {
+            GameState::Playing(ref app) if app.replaying() => {
+                if quit { self.quit = true }
+            }
+            // O, paused, is the options.
+            GameState::Playing(ref app)
+                if app.paused && !app.console.open && key == Keyboard(input::keyboard::O) => {
+                self.options_from_pause();
+            }
+            GameState::Playing(ref mut app) => {
+                handleKey(key, app);
+                self.quit = app.quit;
+            }
+            GameState::Menu(ref mut menu) if up => menu.up(),
+            GameState::Menu(ref mut menu) if down => menu.down(),
+            GameState::Menu(ref menu) if go => match menu.selected() {
+                Choice::Resume => self.resume_game(),
+                Choice::Start => self.start(),
+                Choice::Options => self.set_state(GameState::Options(Options::default())),
+                Choice::Quit => self.quit = true,
+            },
+            GameState::Options(ref mut options) if up => options.up(),
+            GameState::Options(ref mut options) if down => options.down(),
+            // The event loop's settings count straight away, for the menu
+            // as much as the game, so they're passed on as well as being
+            // changed in the config. So does the language, so you can see
+            // what you've picked.
+            GameState::Options(ref mut options) if left || right => {
+                options.change(&mut self.settings.config, right);
+                match options.selected() {
+                    Setting::MaxFps => self.max_fps = self.settings.config.max_fps,
+                    Setting::Ups => self.settings.ups = self.settings.config.ups,
+                    Setting::Language => {
+                        self.settings.locale = self.assets.locale(&self.settings.config.language);
+                    }
+                    Setting::Fullscreen => self.fullscreen = self.settings.config.fullscreen,
+                    _ => {}
+                }
+                info!("{}", options.line(&self.settings.config, &self.settings.locale));
+            }
+            // Enter on a key waits for the new one.
+            GameState::Options(ref mut options) if go && options.start_capture() => {
+                info!("{}", options.line(&self.settings.config, &self.settings.locale));
+            }
+            // Escape goes back from the options too, since that's what
+            // everyone presses to get out of an options screen.
+            GameState::Options(_) if go || key == Keyboard(input::keyboard::Escape) => {
+                self.leave_options();
+            }
+            GameState::GameOver { name: Some(ref mut name), .. }
+                if key == Keyboard(input::keyboard::Backspace) => { name.pop(); }
+            GameState::GameOver { ref mut name, .. }
+                if name.is_some() && key == Keyboard(input::keyboard::Escape) => *name = None,
+            GameState::GameOver { name: Some(_), .. } if go => self.enter_high_score(),
+            GameState::GameOver { name: Some(_), .. } => {}
+            GameState::GameOver { .. } if go => self.set_state(GameState::Menu(Menu::main())),
+            GameState::GameOver { level, .. } if retry => self.restart(level),
+            _ if quit => self.quit = true,
+            _ => debug!(target: Input, "Ignored {}: not on this screen", keys::button_name(key)),
+        }
+    }
+
+    /*
+     * The options, from the pause. The game goes to one side, in
+     * `suspended`, rather than going away with the screen, so it can carry
+     * on afterwards (see leave_options).
+     */
+    pub fn options_from_pause(&mut self) {
+        let options = GameState::Options(Options::default());
+        if let GameState::Playing(app) = mem::replace(&mut self.state, options) {
+            self.suspended = Some(app);
+        }
+        info!("From the pause to the options");
+        self.paused = false;
+        self.begin_transition(1.0);
+    }
+
+    /*
+     * Back from the options: to the game they were opened from, if they
+     * were, with the choices made, and still paused; or to the title
+     * screen, with Options picked, since that's where you came from.
+     * The window may have been resized or moved to another screen while
+     * the options were up, and only a game that's playing hears about
+     * that, so the one coming back gets told now.
+     */
+    pub fn leave_options(&mut self) {
+        self.save_options();
+        match self.suspended.take() {
+            Some(mut app) => {
+                let mut config = app.config.clone();
+                options::copy_choices(&self.settings.config, &mut config);
+                app.apply_config(config);
+                app.resize_window(self.window_size.0, self.window_size.1);
+                app.draw_scale = self.draw_scale;
+                app.settings.locale = self.settings.locale.clone();
+                app.ups = self.settings.ups;
+                self.set_state(GameState::Playing(app));
+            }
+            None => {
+                let mut menu = if self.resume.is_some() { Menu::resuming() } else { Menu::main() };
+                menu.selected = menu.choices.iter().position(|&c| c == Choice::Options)
+                    .unwrap_or(0);
+                self.set_state(GameState::Menu(menu));
+            }
+        }
+    }
+
+    // Resume, on the title screen: carry on from the last autosave. If it
+    // won't load after all, there's only the title screen without it.
+    pub fn resume_game(&mut self) {
+        let snapshot = match self.resume.take() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let mut app = self.new_app();
+        match app.restore(&snapshot) {
+            Ok(()) => {
+                info!("Resuming on \"{}\", with {} points", snapshot.level_name, snapshot.score);
+                self.set_state(GameState::Playing(app));
+            }
+            Err(e) => {
+                error!("Couldn't resume: {}", e);
+                self.set_state(GameState::Menu(Menu::main()));
+            }
+        }
+    }
+
+    /*
+     * The game's quitting. A game in progress (or waiting behind the
+     * options) gets one last autosave, so that Resume picks up right here,
+     * and the session says it was quit, not crashed.
+     */
+    pub fn end_session(&mut self) {
+        let autosave = match self.autosave {
+            Some(ref mut autosave) => autosave,
+            None => return,
+        };
+        let app = match self.state {
+            GameState::Playing(ref app) => Some(app),
+            _ => self.suspended.as_ref(),
+        };
+        if let Some(app) = app {
+            autosave.write_game(app);
+        }
+        if let Err(e) = autosave.finish() {
+            warn!("{}", e);
+        }
+    }
+
+    /*
+     * Leaving the options screen: if anything was changed there, it goes
+     * into config.json, so it's still that way next time. If that doesn't
+     * work, the changes still count for as long as the game's running.
+     */
+    pub fn save_options(&mut self) {
+        if let GameState::Options(ref options) = self.state {
+            if !options.changed { return }
+            let path = &self.settings.config_path;
+            match options::save_choices(path, &self.settings.config) {
+                Ok(()) => info!("Saved the options to {}", path.display()),
+                Err(e) => warn!("Couldn't save the options: {}", e),
+            }
+        }
+    }
+
+    pub fn handle_release(&mut self, key: Button) {
+        if let GameState::Playing(ref mut app) = self.state {
+            if !app.replaying() {
+                handleRelease(key, app);
+            }
+        }
+    }
+
+    // Typing, which the debug console wants, and so does the high-score
+    // table when it's asking for your name.
+    pub fn handle_text(&mut self, text: &str) {
+        match self.state {
+            GameState::Playing(ref mut app) if app.console.open => app.console.type_text(text),
+            GameState::GameOver { name: Some(ref mut name), .. } => {
+                for c in text.chars().filter(|c| !c.is_control()) {
+                    if name.chars().count() < scores::NAME_LENGTH {
+                        name.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /*
+     * A replay can press quit as well as a person can, so the App's quit
+     * gets passed along from here too. Then any sounds the App wanted, this
+     * update or from keys pressed since the last one, get played.
+     * The music goes on whatever screen we're on: the game's track while
+     * playing, and the menu's everywhere else. Playing, it's the App's
+     * config that says how loud, since that's the one M mutes.
+     */
+    pub fn update(&mut self, args: &UpdateArgs) {
+        match self.state {
+            GameState::Playing(ref app) => {
+                self.music.play(Track::Game);
+                self.music.update(args.dt, &app.config.audio);
+            }
+            _ => {
+                self.music.play(Track::Menu);
+                self.music.update(args.dt, &self.settings.config.audio);
+            }
+        }
+        achievements::tick_toasts(&mut self.toasts, args.dt);
+        if let Some(ref mut transition) = self.transition {
+            transition.update(args.dt);
+        }
+        if self.transition.map_or(false, |t| t.done()) {
+            self.transition = None;
+        }
+        let (lost, progress) = match self.state {
+            GameState::Playing(ref mut app) => {
+                app.update(args);
+                self.quit = self.quit || app.quit;
+                for sound in app.sounds.drain(..) {
+                    self.audio.play(sound, &app.config.audio);
+                }
+                let progress: Vec<Progress> = app.progress.drain(..).collect();
+                (app.level_state == LevelState::Lost,
+                 if app.replaying() { Vec::new() } else { progress })
+            }
+            _ => (false, Vec::new()),
+        };
+        self.record_progress(&progress);
+        // Every so often, while playing, an autosave.
+        if let (Some(autosave), &GameState::Playing(ref app)) = (self.autosave.as_mut(),
+                                                                 &self.state) {
+            if !app.paused && autosave.due(args.dt) {
+                autosave.write_game(app);
+            }
+        }
+        if lost { self.finish() }
+
+        // Pausing, or unpausing, is a bit of a change of screen too.
+        let paused = match self.state {
+            GameState::Playing(ref app) => app.paused,
+            _ => false,
+        };
+        if paused != self.paused {
+            self.paused = paused;
+            self.begin_transition(transition::PAUSE_COVER);
+        }
+    }
+
+    // Count up what's happened towards the achievements, toast any that
+    // unlock, and write them down straight away so they're kept.
+    pub fn record_progress(&mut self, progress: &[Progress]) {
+        let mut unlocked = false;
+        for &p in progress.iter() {
+            if let Some(achievement) = self.achievements.record(p) {
+                info!("Achievement unlocked: {}", achievement.name());
+                self.toasts.push(Toast::unlocked(achievement, &self.settings.locale));
+                unlocked = true;
+            }
+        }
+        if unlocked {
+            self.save_achievements();
+        }
+    }
+
+    /*
+     * There's a window now, so everything that was waiting for one can be
+     * done. With an OpenGL context about (`pictures`), we can load
+     * pictures, and the font for the HUD; a front end drawing some other
+     * way goes without, as if their files weren't there. That's everything,
+     * so now's the time to say what wasn't there.
+     */
+    pub fn opened(&mut self, pictures: bool) {
+        if pictures {
+            self.assets.load_pictures(&assets::default_dir());
+            self.hud = self.assets.fonts(&self.settings.config);
+            self.load_effects();
+        }
+        self.audio = self.assets.sounds(&audio::default_dir());
+        self.music = self.assets.music(&self.settings.config.audio);
+        self.assets.report();
+        // The autosaves, and whether there's a game to resume, which there
+        // isn't while a replay's watched: it's starting anyway.
+        if self.settings.config.autosave_seconds > 0.0 {
+            let mut autosave = Autosave::new(&autosave::default_dir(),
+                                             self.settings.config.autosave_seconds,
+                                             self.settings.config.autosave_slots);
+            if self.settings.replay.is_none() {
+                self.resume = autosave.find();
+                if self.resume.is_some() {
+                    self.state = GameState::Menu(Menu::resuming());
+                }
+            }
+            self.autosave = Some(autosave);
+        }
+        // There's no point making someone pick Start to watch a replay.
+        if self.settings.replay.is_some() {
+            self.start();
+        }
+
+        // The achievements. Like the high scores, a broken file is left
+        // alone rather than written over. --headless never gets this far,
+        // so a script's games don't count towards them.
+        let achievements_path = achievements::default_path();
+        match achievements::load(&achievements_path) {
+            Ok(achievements) => {
+                self.achievements = achievements;
+                self.achievements_path = Some(achievements_path);
+            }
+            Err(e) => warn!("{} (achievements won't be kept)", e),
+        }
+    }
+
+    /*
+     * Set up the post-processing (see postprocess.rs) in the window that's
+     * just opened, and load the config's shader into it. Either one not
+     * working is only a warning; the game looks the same as ever without.
+     * Any old ones were the old window's, and went with it.
+     */
+    pub fn load_effects(&mut self) {
+        self.pipeline = match Pipeline::new() {
+            Ok(pipeline) => Some(pipeline),
+            Err(e) => {
+                warn!(target: Render, "No post-processing: {}", e);
+                None
+            }
+        };
+        self.shader = None;
+        self.load_shader();
+    }
+
+    // Load the config's shader, if it has one. It's drawn by the
+    // post-processing, so without that there's no shader either.
+    pub fn load_shader(&mut self) {
+        let config = &self.settings.config;
+        self.shader = None;
+        if config.shader.is_empty() { return }
+        if self.pipeline.is_none() {
+            warn!(target: Render, "There's no post-processing, so no shader");
+            return;
+        }
+        match Shader::load(Path::new(&config.shader), config.shader_target) {
+            Ok(shader) => {
+                info!(target: Render, "Drawing through the shader in {}", config.shader);
+                self.shader = Some(shader);
+            }
+            Err(e) => warn!(target: Render, "{} (so there's no shader)", e),
+        }
+    }
+
+    // How much of each effect there is this frame: the game's own config,
+    // and its flash, if there's one going.
+    pub fn effects(&self) -> Effects {
+        match self.state {
+            GameState::Playing(ref app) => Effects::new(&app.config, app.flash.amount),
+            _ => Effects::new(&self.settings.config, 0.0),
+        }
+    }
+
+    /*
+     * Before a frame `pixels` big is drawn: if there are effects to put on
+     * it, it gets drawn into the post-processing's texture rather than the
+     * window. If it can't be (the graphics card won't draw into a texture
+     * that big, say), that's the end of the post-processing.
+     */
+    pub fn begin_frame(&mut self, pixels: [u32; 2]) {
+        let effects = self.effects();
+        let shader = self.shader.is_some();
+        let failed = match self.pipeline {
+            Some(ref mut pipeline) => pipeline.begin(pixels, &effects, shader).err(),
+            None => return,
+        };
+        if let Some(e) = failed {
+            warn!(target: Render, "No more post-processing: {}", e);
+            if let Some(pipeline) = self.pipeline.take() {
+                pipeline.delete();
+            }
+            if let Some(shader) = self.shader.take() {
+                shader.delete();
+            }
+        }
+    }
+
+    /*
+     * Once it's drawn, put the effects on and get it onto the window. The
+     * shader, aimed at the player, goes round player one, so it's only
+     * there in a game. Then, since the passes had OpenGL to themselves for
+     * a moment, gl is told to forget what it thought its program and its
+     * blending were, and set them up again next time it draws.
+     */
+    pub fn finish_frame(&mut self, pixels: [u32; 2], gl: &mut Gl) {
+        let effects = self.effects();
+        let pipeline = match self.pipeline {
+            Some(ref mut pipeline) => pipeline,
+            None => return,
+        };
+        let window = self.window_size;
+        let (w, h) = window;
+        if w <= 0.0 || h <= 0.0 { return }
+        let (player, expand) = match self.state {
+            GameState::Playing(ref app) => (Some(app.player_on_window()), app.players[0].expand),
+            _ => (None, 0.0),
+        };
+        let around = player.map(|b| {
+            let m = shader::PLAYER_MARGIN;
+            shader::corners(Aabb::new(b.x - m, b.y - m, b.w + m * 2.0, b.h + m * 2.0), window)
+        });
+        let uniforms = Uniforms::new(window, pixels[0] as f64 / w, player, expand);
+        pipeline.finish(&effects, self.shader.as_ref(), &uniforms, around);
+        gl.clear_program();
+        gl.clear_draw_state();
+    }
+
+    pub fn save_achievements(&self) {
+        if let Some(ref path) = self.achievements_path {
+            if let Err(e) = achievements::save(path, &self.achievements) {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    /*
+     * The window's title bar doubles as a second HUD for anyone without a
+     * font, so it says what screen we're on. Only touch it when it changes;
+     * there's no need to bother the window sixty times a second with the
+     * same string.
+     *
+     * On a HiDPI screen the window has more pixels to draw on (its draw
+     * size) than points to measure it in (its size, which is what the
+     * mouse and the resize events go by), twice as many across, usually.
+     * All our drawing is in points, so everything's the size it should be
+     * and the mouse lands where it ought to; OpenGL gets told to spread
+     * that across every pixel, so it comes out sharp rather than drawn on a
+     * quarter of the window, or blown up and blurry.
+     *
+     * The drawing goes through a Batcher, so the rectangles go to OpenGL
+     * in bulk (see renderer.rs); flushing it at the end draws the last of
+     * them.
+     */
+    pub fn render<W: AdvancedWindow>(&mut self, window: &mut W, args: &RenderArgs,
+                                     gl: &mut Gl) {
+        self.set_title(window);
+        // draw sets OpenGL up for the frame, with the viewport across
+        // every pixel, and hands us a Gl to draw it with.
+        gl.draw(args.viewport(), |_, gl| self.render_on(args, gl));
+    }
+
+    // The same, on any graphics back end that's a Renderer too, once it's
+    // ready to be drawn on. Gl is one; piston_window's G2d is another (see
+    // piston_front.rs), which gets here its own way.
+    pub fn render_on<G: Graphics + Renderer>(&mut self, args: &RenderArgs, g: &mut G) {
+        let [w, h] = args.window_size;
+        if w > 0.0 {
+            self.set_draw_scale(args.draw_size[0] as f64 / w);
+        }
+        let mut batcher = Batcher::new(g);
+        self.draw(w, h, &mut batcher);
+        batcher.flush();
+    }
+
+    pub fn set_title<W: AdvancedWindow>(&mut self, window: &mut W) {
+        let name = &self.settings.config.window_title;
+        let title = match self.state {
+            GameState::Menu(ref menu) => {
+                format!("{} - {}", name, self.settings.locale.get(menu.selected().key()))
+            }
+            GameState::Options(_) => format!("{} - options", name),
+            GameState::Playing(ref app) => app.title_text(),
+            GameState::GameOver { score, .. } => format!("{} - game over - score {}", name, score),
+        };
+        if title != self.title {
+            window.set_title(title.clone());
+            self.title = title;
+        }
+    }
+
+    /*
+     * Draw whichever screen we're on, `w` by `h` pixels, on `r`, with the
+     * transition into it if it's still coming, and any achievement toasts
+     * on top. This is the half of rendering that doesn't
+     * need a window, so --headless and the tests can do it too.
+     */
+    pub fn draw(&mut self, w: f64, h: f64, r: &mut dyn Renderer) {
+        self.draw_screen(w, h, r);
+        if let Some(ref transition) = self.transition {
+            transition.draw(w, h, &Context::new_abs(w, h), r);
+        }
+        let config = &self.settings.config;
+        achievements::draw_toasts(&self.toasts, &mut self.hud, config.hud_font_size,
+                                  config.palette().text, w, &Context::new_abs(w, h), r);
+    }
+
+    fn draw_screen(&mut self, w: f64, h: f64, r: &mut dyn Renderer) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.render(r, &self.assets, &mut self.hud);
+            return;
+        }
+        let context = &Context::new_abs(w, h);
+        r.clear(context, self.settings.config.palette().background);
+        if let Some(ui) = self.screen_ui(w, h) {
+            ui.draw(&mut self.hud, context, r);
+        }
+    }
+
+    /*
+     * What's on the screen we're on, `w` by `h`, as widgets (see ui.rs),
+     * for drawing it and for working out what the mouse is on. The game
+     * has one of its own while it's paused, and otherwise none.
+     */
+    pub fn screen_ui(&self, w: f64, h: f64) -> Option<Ui> {
+        let config = &self.settings.config;
+        let locale = &self.settings.locale;
+        let palette = config.palette();
+        let size = config.hud_font_size;
+        match self.state {
+            GameState::Playing(ref app) if app.paused => Some(app.pause_menu()),
+            GameState::Playing(_) => None,
+            GameState::Menu(ref menu) => {
+                let mut ui = Ui::new(w, h, size, &palette, Some(menu.selected));
+                ui.y = h / 4.0;
+                ui.title(&config.window_title);
+                menu.layout(&mut ui, locale);
+                Some(ui)
+            }
+            // The settings you can change here (see options.rs), and a
+            // pointer to the file for everything else. The rest of the
+            // keys are on the help, in the game.
+            GameState::Options(ref options) => {
+                let focus = options.selected.checked_sub(options.first);
+                let mut ui = Ui::new(w, h, size, &palette, focus);
+                ui.centre(options.rows() + 3);
+                ui.title(locale.get("options.title"));
+                options.layout(&mut ui, config, locale);
+                ui.label(locale.get("options.how"));
+                ui.label(&locale.text("options.everything_else",
+                                      &[&self.settings.config_path.display()]));
+                ui.label(locale.get("options.back"));
+                Some(ui)
+            }
+            // Trying again, the title screen and quitting are buttons, in
+            // that order, unless there's a name being typed.
+            GameState::GameOver { score, level, ref name } => {
+                let key_name = |key: &str| keys::parse_key(key).map(keys::key_name)
+                                                               .unwrap_or(key.to_string());
+                let scores = self.high_scores.lines(locale);
+                let extra = if scores.is_empty() { 0 } else { scores.len() + 2 };
+                let mut ui = Ui::new(w, h, size, &palette, None);
+                ui.centre(4 + extra);
+                ui.title(locale.get("game_over.title"));
+                ui.label(&locale.text("game_over.score", &[&score, &(level + 1)]));
+                match *name {
+                    Some(ref name) => {
+                        ui.label(&locale.text("game_over.name", &[name]));
+                        ui.label(locale.get("game_over.name_keys"));
+                    }
+                    None => {
+                        ui.button(&locale.text("game_over.try_again",
+                                               &[&key_name(&config.reset_key)]));
+                        ui.button(locale.get("game_over.menu"));
+                        ui.button(&locale.text("game_over.quit", &[&key_name(&config.quit_key)]));
+                    }
+                }
+                if !scores.is_empty() {
+                    ui.label("");
+                    ui.label(locale.get("game_over.high_scores"));
+                    for line in scores.iter() {
+                        ui.label(line);
+                    }
+                }
+                Some(ui)
+            }
+        }
+    }
+
+    pub fn record_frame(&mut self, seconds: f64) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.record_frame(seconds);
+        }
+    }
+
+    // How long the last update, and the last render, took to do, for F3's
+    // graph.
+    pub fn record_update_time(&mut self, seconds: f64) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.frame_graph.update_took(seconds);
+        }
+    }
+
+    pub fn record_render_time(&mut self, seconds: f64) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.frame_graph.render_took(seconds);
+        }
+    }
+
+    pub fn controller_axis(&mut self, axis: u8, position: f64) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.controller_axis(axis, position);
+        }
+    }
+
+    pub fn mouse_moved(&mut self, x: f64, y: f64) {
+        self.cursor = Some((x, y));
+        if let GameState::Playing(ref mut app) = self.state {
+            app.mouse_moved(x, y);
+            return;
+        }
+        // Over a button on the menus, the mouse picks it, the same as Up
+        // and Down would.
+        let (w, h) = self.window_size;
+        let over = match self.screen_ui(w, h) {
+            Some(ui) => ui.hit(x, y),
+            None => None,
+        };
+        match (over, &mut self.state) {
+            (Some(i), &mut GameState::Menu(ref mut menu)) => menu.selected = i,
+            (Some(i), &mut GameState::Options(ref mut options)) if !options.capturing => {
+                options.selected = options.first + i;
+            }
+            _ => {}
+        }
+    }
+
+    /*
+     * The left mouse button, on the screen's widgets: whichever key does
+     * what the one it's on does, after picking it. A slider's set to the
+     * point along it that's clicked, which no key quite does. Whether it
+     * was on one at all; if it wasn't, it's just the mouse button.
+     */
+    pub fn click(&mut self) -> bool {
+        let (w, h) = self.window_size;
+        let (ui, (x, y)) = match self.state {
+            GameState::Playing(ref app) if app.paused => match app.cursor_on_screen() {
+                Some(at) => (app.pause_menu(), at.tuple()),
+                None => return false,
+            },
+            _ => match (self.screen_ui(w, h), self.cursor) {
+                (Some(ui), Some(at)) => (ui, at),
+                _ => return false,
+            },
+        };
+        let hit = match ui.hit(x, y) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let by_name = |name: &str| keys::parse_key(name).map(Keyboard);
+        let key = match self.state {
+            GameState::Playing(_) => [Keyboard(input::keyboard::P),
+                                      Keyboard(input::keyboard::O)].get(hit).cloned(),
+            GameState::Menu(ref mut menu) => {
+                menu.selected = hit;
+                Some(Keyboard(input::keyboard::Return))
+            }
+            GameState::Options(ref mut options) => {
+                options.selected = options.first + hit;
+                match ui.along(hit, x) {
+                    Some(along) => {
+                        options.set_volume(&mut self.settings.config, along);
+                        info!("{}", options.line(&self.settings.config, &self.settings.locale));
+                        None
+                    }
+                    None if options::is_key(options.selected()) => {
+                        Some(Keyboard(input::keyboard::Return))
+                    }
+                    None => Some(Keyboard(input::keyboard::Right)),
+                }
+            }
+            GameState::GameOver { .. } => match hit {
+                0 => by_name(&self.settings.config.reset_key),
+                1 => Some(Keyboard(input::keyboard::Return)),
+                _ => by_name(&self.settings.config.quit_key),
+            },
+        };
+        if let Some(key) = key {
+            self.handle_key(key);
+        }
+        true
+    }
+
+    pub fn scroll_zoom(&mut self, notches: f64) {
+        if let GameState::Playing(ref mut app) = self.state {
+            app.scroll_zoom(notches);
+        }
+    }
+
+    /*
+     * Whatever just happened, from the event loop, passed on to wherever
+     * it's going. Everything but drawing: a frame needs the window and a
+     * Gl, which only run has, so Render is run's to deal with, and is
+     * ignored here. That leaves the rest free to be driven by any
+     * EventLoop, a Scripted one in the tests included.
+     */
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            // Was there a key pressed? Handle that!
+            Event::Press(key) => self.handle_key(key),
+            // Was one let go? Handle that too!
+            Event::Release(key) => self.handle_release(key),
+            // Did it type something? Hand that over for the console!
+            Event::Text(ref text) => self.handle_text(text),
+            // Did a controller stick move? Note down where to!
+            Event::Axis(args) => self.controller_axis(args.axis, args.position),
+            // Did the mouse move? Note down where to, for aiming!
+            Event::Cursor(x, y) => self.mouse_moved(x, y),
+            // Did the mouse wheel turn? Zoom!
+            Event::Scroll(_, dy) => self.scroll_zoom(dy),
+            // Did the window change size? Tell everyone who draws!
+            Event::Resize(w, h) => self.resize(w, h),
+            // Was a file dropped on it? Play it!
+            Event::Drop(ref path) => self.drop_file(path),
+            // Is it time to move the world on? Update that! (And time it.)
+            Event::Update(u) => {
+                let started = Instant::now();
+                self.obey_bot();
+                self.update(&u);
+                self.record_update_time(seconds(started.elapsed()));
+            }
+            // The window's closing; piston's loop stops by itself after
+            // this, so there's nothing to do but let it.
+            Event::Render(_) | Event::Close | Event::Other => {}
+        }
+    }
+}
+
+/*
+ * Everything before the window: the command line, the config, the levels
+ * and the rest, and a Game made out of them. None means there's nothing
+ * more to do, either because something that was asked for couldn't be had
+ * (which has been said) or because it's been done already: --bench and
+ * --headless need no window, so they play here. Otherwise it's over to
+ * whichever front end it is to open a window and play: run, or
+ * piston_front::run.
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn prepare() -> Option<Game> {
+    // First up, the command line. A bad flag gets you the usage text rather
+    // than a game.
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::USAGE);
+            return None;
+        }
+    };
+    // Then the logger, so everything after this can log.
+    let log_file = options.log_file.as_ref().map(|p| p.as_path());
+    if let Err(e) = log::init(options.log_level, &options.log_targets, log_file) {
+        println!("{}", e);
+        return None;
+    }
+
+    // If config.json is missing we write one with the defaults in. If it's
+    // broken (or we can't write it) we grumble and use the defaults; there's
+    // no reason that should stop anyone playing.
+    let config_path = PathBuf::from("config.json");
+    let mut config = match config::load_or_create(&config_path) {
+        Ok(config) => {
+            info!("Loaded {}", config_path.display());
+            for warning in config::warnings(&config).iter() {
+                warn!("{}", warning);
+            }
+            config
+        }
+        Err(e) => {
+            error!("{} (using default settings)", e);
+            Config::default()
+        }
+    };
+
+    // Flags on the command line win over the config file.
+    config.window_width = options.width.unwrap_or(config.window_width);
+    config.window_height = options.height.unwrap_or(config.window_height);
+    let max_fps = options.max_fps.unwrap_or(config.max_fps);
+    let ups = options.ups.unwrap_or(config.ups);
+
+    // A replay to play back, if we've been given one. It's read now so a
+    // file that's missing or broken stops us before a window opens.
+    let replay = match options.replay {
+        Some(ref path) => match Script::load(path) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        },
+        None => None,
+    };
+    let replay_length = replay.as_ref().map(|r| r.length()).unwrap_or(0.0);
+
+    // The seed: --seed if there is one, or else the one the replay was
+    // played with. Overriding a replay's seed is allowed, but it won't play
+    // out the same, so we say so.
+    let replay_seed = replay.as_ref().and_then(|r| r.seed);
+    if let (Some(seed), Some(recorded)) = (options.seed, replay_seed) {
+        if seed != recorded {
+            warn!("The replay was played with seed {}, so with {} it won't go the same way",
+                  recorded, seed);
+        }
+    }
+    let seed = options.seed.or(replay_seed).unwrap_or(0x5EED);
+    info!("Random numbers from seed {}", seed);
+
+    /*
+     * The levels. --level means just that one, and if it won't load that's
+     * the end of it: you asked for it specifically. --generate makes some up
+     * from the seed (which is why they're sorted out after it), and writes
+     * them out too with --export. Otherwise it's everything in levels/, and
+     * if there aren't any we can still play the old empty field, so that's
+     * a warning rather than the end of the world.
+     *
+     * Like everything else read from a file, they come through the asset
+     * manager, which doesn't need a window for anything but pictures, so
+     * it's made now and handed to Game once there's a Game to hand it to.
+     */
+    let mut assets = Assets::none();
+    let (levels, levels_path) = match (&options.level, options.generate) {
+        (&Some(ref path), _) => match assets.level(path) {
+            Ok(level) => (vec![level], Some(path.clone())),
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        },
+        (&None, Some(difficulty)) => {
+            let levels = generate::levels(seed, difficulty);
+            info!("Made up {} levels, starting on difficulty {}", levels.len(), difficulty);
+            if let Some(ref dir) = options.export {
+                if let Err(e) = generate::export(dir, &levels) {
+                    error!("{}", e);
+                    return None;
+                }
+                info!("Wrote them to {}", dir.display());
+            }
+            (levels, None)
+        }
+        (&None, None) => {
+            let mut levels = assets.levels(Path::new("levels"));
+            if levels.is_empty() {
+                warn!("No levels found in levels/, using the built-in one");
+                levels.push(level::Level::fallback());
+            }
+            (levels, Some(PathBuf::from("levels")))
+        }
+    };
+
+    // The network, if we're playing over it. Like the levels, asking for it
+    // and not getting it is the end: there's no point playing on your own
+    // when you meant to play with someone.
+    let link = if options.host {
+        Some(Link::host(net::DEFAULT_PORT))
+    } else {
+        options.connect.as_ref().map(|address| Link::connect(address))
+    };
+    let link = match link {
+        Some(Ok(link)) => {
+            match options.connect {
+                Some(ref address) => info!(target: Net, "Playing with {}", address),
+                None => info!(target: Net, "Waiting for someone to join on port {}",
+                              net::DEFAULT_PORT),
+            }
+            Some(Rc::new(RefCell::new(link)))
+        }
+        Some(Err(e)) => {
+            error!("{}", e);
+            return None;
+        }
+        None => None,
+    };
+
+    // Spectators, on the other hand, are a nice extra. If we can't have
+    // them, say so and carry on.
+    let spectators = if options.spectate {
+        match Spectators::listen(spectate::DEFAULT_PORT) {
+            Ok(spectators) => {
+                info!(target: Net, "Spectators can watch on port {}", spectate::DEFAULT_PORT);
+                Some(Rc::new(RefCell::new(spectators)))
+            }
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // A broken (or missing) script is the same: grumble and play without.
+    let lua = match options.lua {
+        Some(ref path) => match Lua::load(path) {
+            Ok(lua) => Some(Rc::new(RefCell::new(lua))),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // The same goes for dumping the state: if the file won't open, the
+    // game's still worth playing.
+    let dump = match options.dump_state {
+        Some(ref destination) => {
+            let interval = options.dump_interval.unwrap_or(dump::DEFAULT_INTERVAL);
+            match Dump::open(destination, interval) {
+                Ok(dump) => Some(Rc::new(RefCell::new(dump))),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let locale = assets.locale(&config.language);
+    let mut game = Game::new(Settings {
+        config: config,
+        config_path: config_path,
+        ups: ups,
+        seed: seed,
+        levels: levels,
+        levels_path: levels_path,
+        replay: replay,
+        record_path: Some(PathBuf::from("replay.txt")),
+        coop: options.coop,
+        link: link,
+        spectators: spectators,
+        lua: lua,
+        dump: dump,
+        locale: locale,
+    });
+    game.assets = assets;
+    game.max_fps = max_fps;
+
+    // A bot, like a second player, is something that was asked for, so
+    // not getting it is the end: there'd be nobody at the controls.
+    if let Some(ref source) = options.bot {
+        match Bot::listen(source) {
+            Ok(bot) => game.bot = Some(bot),
+            Err(e) => {
+                error!("{}", e);
+                return None;
+            }
+        }
+    }
+
+    // The high scores. If scores.json is broken we say so and leave it be:
+    // this time's games don't get kept, but the old ones don't get lost.
+    let scores_path = scores::default_path();
+    match scores::load(&scores_path) {
+        Ok(high_scores) => {
+            game.high_scores = high_scores;
+            game.high_scores_path = Some(scores_path);
+        }
+        Err(e) => warn!("{} (high scores won't be kept)", e),
+    }
+
+    // --bench doesn't need a window either. What it's found goes to the
+    // terminal whatever the log level, since that's what it's for.
+    if let Some(updates) = options.bench {
+        let report = game.run_bench(updates as usize, options.bench_render);
+        for line in report.lines() {
+            println!("{}", line);
+        }
+        return None;
+    }
+
+    // With no window there's nothing more to set up; just play, for ten
+    // seconds or until the end of the script (or the replay) if that's
+    // longer.
+    if options.headless {
+        let mut script = match options.script {
+            Some(ref path) => match Script::load(path) {
+                Ok(script) => script,
+                Err(e) => {
+                    error!("{}", e);
+                    return None;
+                }
+            },
+            None => Script::empty(),
+        };
+        let seconds = HEADLESS_SECONDS.max(script.length() + 1.0).max(replay_length + 1.0);
+        game.run_headless(seconds, &mut script);
+        game.write_replay();
+        return None;
+    }
+    game.fullscreen = options.fullscreen || game.settings.config.fullscreen;
+    game.inspect = options.inspector;
+    Some(game)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run() {
+    /*
+     * I love this bit because it's all lets and then a loop.
+     * Let there be settings (and everything else in prepare)
+     * Let there be a window
+     * Let there be a player
+     * Let there be a world
+     * For now we LOOP
+     */
+    let mut game = match prepare() {
+        Some(game) => game,
+        None => return,
+    };
+
+    /*
+     * The window, and which OpenGL it draws with: the config's version if
+     * that works, or failing that one that does (see window_backend.rs).
+     * If there's no window to be had at all, there's no game, but that's
+     * still no reason to crash: we say what went wrong, and what might
+     * help, and stop.
+     */
+    info!(target: Render, "Opening a window with {}", window_backend::NAME);
+    let (mut window, mut gl, opengl): (GameWindow, Gl, _) =
+        match window_backend::open_any(&game.settings.config, game.fullscreen) {
+            Ok(opened) => opened,
+            Err(e) => {
+                error!(target: Render, "{}", e);
+                return;
+            }
+        };
+    // Fullscreen, the window won't be the size we asked for.
+    let size = window.size();
+    info!(target: Render, "The window's open, {} by {}", size.width, size.height);
+    game.resize(size.width, size.height);
+    game.opened(true);
+    // And the inspector's, if it's wanted. Not getting one isn't worth
+    // stopping for: the game's still there to play.
+    let mut inspector = if game.inspect {
+        match InspectorWindow::open(&mut game, opengl, &mut window) {
+            Ok(inspector) => Some(inspector),
+            Err(e) => {
+                warn!(target: Render, "Couldn't open the inspector: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    /*
+     * The event loop. Each time round, events.next waits for the next thing
+     * to happen, and we match on what it was and pass it on to game, which
+     * passes it on to whichever screen we're on. Piston's loop takes the
+     * window each time, rather than keeping hold of it, so the window stays
+     * ours to swap for another (see F11, below) whenever we like.
+     *
+     * The loop is `while let` rather than a plain `for`, so that when F7/F8
+     * change the update rate (or the options screen changes that, the frame
+     * cap or bench mode) we can tell the loop to go at the new pace before
+     * it hands out the next event.
+     */
+    let mut pace = game.pace();
+    let mut fullscreen = game.fullscreen;
+    let mut events = PistonLoop::new(pace);
+    // When the last frame started, for timing them.
+    let mut last_frame = Instant::now();
+    while let Some(e) = events.next(&mut window) {
+        match e {
+            // Is it time for a new frame? Time the last one, and render that!
+            // And time the rendering too, for the graph.
+            Event::Render(r) => {
+                let now = Instant::now();
+                let frame = seconds(now.duration_since(last_frame));
+                game.record_frame(frame);
+                last_frame = now;
+                game.begin_frame(r.draw_size);
+                game.render(&mut window, &r, &mut gl);
+                // The frame's drawn and not yet on the screen, so it's the
+                // moment for the effects, and then to read it all back for
+                // a screenshot.
+                game.finish_frame(r.draw_size, &mut gl);
+                game.record_render_time(seconds(now.elapsed()));
+                let [pixels_w, pixels_h] = r.draw_size;
+                if game.screenshot_requested {
+                    game.screenshot_requested = false;
+                    match capture::screenshot(pixels_w, pixels_h) {
+                        Ok(path) => info!("Saved a screenshot to {}", path.display()),
+                        Err(e) => error!("Couldn't take a screenshot: {}", e),
+                    }
+                }
+                // And every so often, for the clip.
+                if game.clip.tick(frame) {
+                    game.clip.push(&capture::read_window(pixels_w, pixels_h));
+                }
+                match game.clip.finished() {
+                    Some(Ok(path)) => info!("Saved a clip to {}", path.display()),
+                    Some(Err(e)) => error!("Couldn't save a clip: {}", e),
+                    None => {}
+                }
+                // The inspector's drawn whenever the game is, so it's never
+                // behind, and after the reading back, which is the game's.
+                if let Some(ref mut inspector) = inspector {
+                    inspector.draw(&game, &mut window);
+                }
+            }
+            // Anything else is a key, the mouse, an update and so on,
+            // which Game knows what to do with.
+            e => game.handle_event(&e),
+        }
+        // Whatever's happened to the inspector's window since. Closing it
+        // closes only it; the game carries on.
+        let open = inspector.as_mut().map_or(true, |inspector| inspector.service(&mut game));
+        if !open {
+            info!("Closed the inspector");
+            inspector = None;
+        }
+
+        if game.quit { break }
+        if game.pace() != pace {
+            pace = game.pace();
+            info!(target: Render, "Now {} updates and up to {} frames a second{}", pace.0,
+                  pace.1, if pace.2 { ", in bench mode" } else { "" });
+            events.set_pace(pace);
+        }
+        /*
+         * Going fullscreen (or back) means a whole new window: SDL (and
+         * friends) can switch an existing one, but piston's Window trait
+         * doesn't let us at that, so we make another with the other setting and drop the old
+         * one. The new window brings a new OpenGL context along with it,
+         * and pictures loaded into the old one don't carry over, so the
+         * pictures, the font and the effects get loaded again as well. Then we tell the
+         * game the new size, and App::resize_view keeps the player where
+         * they were on screen.
+         */
+        if game.fullscreen != fullscreen {
+            // If the new window won't open we keep the one we've got, and
+            // forget about going fullscreen (or back). It's the same OpenGL
+            // as before, so its Gl ought to be fine, but if not there's
+            // nothing left to draw with.
+            match window_backend::open(opengl, &game.settings.config, game.fullscreen) {
+                Ok(new_window) => window = new_window,
+                Err(e) => {
+                    error!(target: Render, "Couldn't open the window again: {}", e);
+                    game.fullscreen = fullscreen;
+                    continue;
+                }
+            }
+            fullscreen = game.fullscreen;
+            gl = match window_backend::gl(opengl) {
+                Ok(gl) => gl,
+                Err(e) => {
+                    error!(target: Render, "Couldn't draw on the new window: {}", e);
+                    break;
+                }
+            };
+            game.assets.load_pictures(&assets::default_dir());
+            game.hud = game.assets.fonts(&game.settings.config);
+            game.load_effects();
+            // A new window has the default title, so make sure we set ours.
+            game.title = String::new();
+            // And it's (probably) a new size, without there having been a
+            // resize event to say so.
+            let size = window.size();
+            info!(target: Render, "Opened the window again, {} by {}", size.width, size.height);
+            game.resize(size.width, size.height);
+        }
+    }
+    // Quitting mid-game still leaves a replay of it behind, whatever you got
+    // towards the achievements gets kept, and there's an autosave to pick it
+    // up again from. Then falling out of the loop
+    // drops the window, which closes it, and that's us done.
+    game.write_replay();
+    game.save_achievements();
+    game.end_session();
+    info!("Bye!");
+}
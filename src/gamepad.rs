@@ -0,0 +1,131 @@
+/*
+ * Controllers. Piston hands us controller buttons and axes as plain numbers,
+ * and which number is which depends on the controller, so this is where we
+ * decide what they mean. The defaults follow SDL's game controller layout,
+ * which most pads get translated into: button 0 is the bottom face button
+ * (A on an Xbox pad, cross on a PlayStation one), 1 is the one to the right
+ * of it (B, or circle), 11 to 14 are the D-pad,
+ * and axes 0 and 1 are the left stick.
+ *
+ * The D-pad just pretends to be the arrow keys, and the stick feeds the
+ * analog part of InputState (see controls.rs), so once a controller event
+ * has been through here nothing else needs to know it wasn't the keyboard.
+ */
+use controls::InputState;
+
+// The things a controller button can do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Expand,
+    Fire,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ControllerMap {
+    pub buttons: Vec<(u8, Action)>,
+    // Which axes are the stick we walk with.
+    pub move_x: u8,
+    pub move_y: u8,
+}
+
+impl Default for ControllerMap {
+    fn default() -> ControllerMap {
+        ControllerMap {
+            buttons: vec![
+                (0, Action::Expand),
+                (1, Action::Fire),
+                (11, Action::Up),
+                (12, Action::Down),
+                (13, Action::Left),
+                (14, Action::Right),
+            ],
+            move_x: 0,
+            move_y: 1,
+        }
+    }
+}
+
+impl ControllerMap {
+    // What a button does, if anything.
+    pub fn action(&self, button: u8) -> Option<Action> {
+        self.buttons.iter()
+            .find(|&&(b, _)| b == button)
+            .map(|&(_, action)| action)
+    }
+
+    // Like InputState::press: note down a D-pad button as held, and say
+    // whether it was one, so the caller knows whether to look any further.
+    pub fn press(&self, input: &mut InputState, button: u8) -> bool {
+        self.set(input, button, true)
+    }
+
+    pub fn release(&self, input: &mut InputState, button: u8) -> bool {
+        self.set(input, button, false)
+    }
+
+    fn set(&self, input: &mut InputState, button: u8, held: bool) -> bool {
+        match self.action(button) {
+            Some(Action::Up) => input.up = held,
+            Some(Action::Down) => input.down = held,
+            Some(Action::Left) => input.left = held,
+            Some(Action::Right) => input.right = held,
+            _ => return false,
+        }
+        true
+    }
+
+    // An axis moved. Returns whether it was one of the stick's; other axes
+    // (the right stick, the triggers) don't do anything yet.
+    pub fn axis(&self, input: &mut InputState, axis: u8, position: f64) -> bool {
+        if axis == self.move_x {
+            input.move_stick(true, position);
+        } else if axis == self.move_y {
+            input.move_stick(false, position);
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpad_holds_like_the_arrows() {
+        let map = ControllerMap::default();
+        let mut input = InputState::default();
+        assert!(map.press(&mut input, 14));
+        assert_eq!(input.direction(), (1.0, 0.0));
+        assert!(map.release(&mut input, 14));
+        assert_eq!(input.direction(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn face_buttons_expand_and_fire() {
+        let map = ControllerMap::default();
+        let mut input = InputState::default();
+        assert_eq!(map.action(0), Some(Action::Expand));
+        assert_eq!(map.action(1), Some(Action::Fire));
+        // Neither is something you hold, so they're not ours to note down.
+        assert!(!map.press(&mut input, 0));
+        assert!(!map.press(&mut input, 1));
+        assert_eq!(map.action(7), None);
+    }
+
+    #[test]
+    fn left_stick_moves() {
+        let map = ControllerMap::default();
+        let mut input = InputState::default();
+        assert!(map.axis(&mut input, 1, -1.0));
+        assert_eq!(input.direction(), (0.0, -1.0));
+        // The right stick's x axis is number 2, which isn't ours.
+        assert!(!map.axis(&mut input, 2, 1.0));
+        assert_eq!(input.direction(), (0.0, -1.0));
+    }
+}
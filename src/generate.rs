@@ -0,0 +1,195 @@
+/*
+ * Levels made up on the spot. With --generate 3, say, instead of the level
+ * files there's a run of LEVELS levels built from the random numbers, on
+ * difficulty 3 to start with and one harder each level after (up to
+ * MAX_DIFFICULTY). The harder, the bigger the world, and the more walls and
+ * enemies there are in it. They come from --seed like everything else
+ * random, so the same seed and difficulty make the same levels every time,
+ * and --export writes them out as level files, in the usual format, to play
+ * again with --level or to tidy up by hand into a real one.
+ *
+ * Random walls can shut you in, and a level you can't finish is no good,
+ * so every level is made solvable as it goes. You always start in the top
+ * left and the goal's somewhere in the bottom right, and there's room kept
+ * clear round both. Walls are thrown down one at a time, and any one that
+ * would cut the goal off from the start gets picked up again. "Cut off" is
+ * worked out the way the enemies find their way about (see pathfinding.rs),
+ * on a grid of cells bigger than the player, so anywhere that's reachable
+ * is reachable with room to spare. Then the things to pick up, and the
+ * enemies, only go where you could get to them, and the enemies not too
+ * near the start.
+ */
+use std::fs;
+use std::path::Path;
+
+use collision::Aabb;
+use level::{self, Level};
+use pathfinding::Grid;
+use powerup::{Effect, Spot};
+use rng::Rng;
+use tilemap::Tilemap;
+
+// How many levels --generate makes, and the difficulties it goes between.
+pub const LEVELS: usize = 5;
+pub const MIN_DIFFICULTY: u32 = 1;
+pub const MAX_DIFFICULTY: u32 = 10;
+
+// The cells reachability is worked out on, and how far round the start and
+// the goal walls keep off.
+const CELL: f64 = 32.0;
+const CLEARING: f64 = 80.0;
+// Where the player starts, and how big the goal is.
+const SPAWN: [f64; 2] = [50.0, 50.0];
+const GOAL_SIZE: f64 = 40.0;
+// Enemies start at least this far from the player.
+const ENEMY_DISTANCE: f64 = 400.0;
+// How many goes anything gets at finding somewhere to go before it's
+// left out.
+const TRIES: usize = 50;
+
+// The run of levels --generate plays.
+pub fn levels(seed: u64, difficulty: u32) -> Vec<Level> {
+    let mut rng = Rng::new(seed);
+    (0..LEVELS as u32)
+        .map(|n| level(&mut rng, n as usize + 1, (difficulty + n).min(MAX_DIFFICULTY)))
+        .collect()
+}
+
+// Level number `number`, on `difficulty` (who's going to stop at 10?), with
+// the coming random numbers.
+pub fn level(rng: &mut Rng, number: usize, difficulty: u32) -> Level {
+    let d = difficulty.max(MIN_DIFFICULTY).min(MAX_DIFFICULTY) as f64;
+    let (width, height) = (1280.0 + 128.0 * (d - 1.0), 960.0 + 96.0 * (d - 1.0));
+    let goal = [rng.range(width * 0.75, width - GOAL_SIZE - 10.0).round(),
+                rng.range(height * 0.75, height - GOAL_SIZE - 10.0).round(),
+                GOAL_SIZE, GOAL_SIZE];
+    let start = (SPAWN[0], SPAWN[1]);
+    let end = (goal[0] + GOAL_SIZE / 2.0, goal[1] + GOAL_SIZE / 2.0);
+    let clearings = [Aabb::new(start.0 - CLEARING, start.1 - CLEARING,
+                               CLEARING * 2.0, CLEARING * 2.0),
+                     Aabb::new(goal[0] - CLEARING, goal[1] - CLEARING,
+                               GOAL_SIZE + CLEARING * 2.0, GOAL_SIZE + CLEARING * 2.0)];
+
+    // The walls: long thin ones, across or down, more and longer the harder
+    // it is.
+    let mut walls: Vec<Aabb> = Vec::new();
+    for _ in 0..(3.0 + 2.0 * d) as usize {
+        let length = rng.range(100.0, 300.0 + 20.0 * d).round();
+        let thickness = rng.range(10.0, 20.0).round();
+        let (w, h) = if rng.next_f64() < 0.5 { (length, thickness) } else { (thickness, length) };
+        let (x, y) = (rng.range(0.0, width - w).round(), rng.range(0.0, height - h).round());
+        let wall = Aabb::new(x, y, w, h);
+        if clearings.iter().any(|c| c.intersects(&wall)) { continue }
+        walls.push(wall);
+        if Grid::new(width, height, CELL, &walls).route(start, end).is_none() {
+            walls.pop();
+        }
+    }
+
+    // Anywhere you could get to, somewhere in `area`, and not in a wall.
+    let grid = Grid::new(width, height, CELL, &walls);
+    let somewhere = |rng: &mut Rng, area: Aabb| -> Option<[f64; 2]> {
+        for _ in 0..TRIES {
+            let at = (rng.range(area.x, area.right()).round(),
+                      rng.range(area.y, area.bottom()).round());
+            let (column, row) = grid.cell_of(at);
+            if !grid.blocked(column, row) && grid.route(start, at).is_some() {
+                return Some([at.0, at.1]);
+            }
+        }
+        None
+    };
+    let world = Aabb::new(20.0, 20.0, width - 40.0, height - 40.0);
+    let collectibles = (0..4 + d as usize).filter_map(|_| somewhere(rng, world)).collect();
+    let effects = [Effect::Speed, Effect::Size, Effect::Invulnerable];
+    let power_ups = (0..if d <= 5.0 { 2 } else { 1 })
+        .filter_map(|_| {
+            let effect = effects[rng.range_usize(0, effects.len() - 1)];
+            somewhere(rng, world).map(|at| Spot { effect: effect, at: at })
+        })
+        .collect();
+    // The enemies keep their distance to begin with: from the start, out.
+    let far = Aabb::new(ENEMY_DISTANCE, ENEMY_DISTANCE,
+                        width - ENEMY_DISTANCE - 20.0, height - ENEMY_DISTANCE - 20.0);
+    let enemies = (0..d as usize).filter_map(|_| somewhere(rng, far)).collect();
+
+    Level {
+        name: format!("Generated {} (difficulty {})", number, difficulty),
+        world_width: width,
+        world_height: height,
+        spawn: SPAWN,
+        goal: goal,
+        walls: walls.iter().map(|b| [b.x, b.y, b.w, b.h]).collect(),
+        collectibles: collectibles,
+        enemies: enemies,
+        power_ups: power_ups,
+        items: Vec::new(),
+        doors: Vec::new(),
+        npcs: Vec::new(),
+        checkpoints: Vec::new(),
+        tilemap: None,
+        ground: Tilemap::empty(),
+        cutscene: None,
+        cutscene_steps: Vec::new(),
+    }
+}
+
+// Write `levels` into `dir` as level1.json, level2.json and so on, which
+// is just what --level (or level::load_all) wants to find there.
+pub fn export(dir: &Path, levels: &[Level]) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("couldn't make {}: {}", dir.display(), e))?;
+    for (i, level) in levels.iter().enumerate() {
+        level::save(&dir.join(format!("level{}.json", i + 1)), level)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn the_same_seed_makes_the_same_levels() {
+        assert_eq!(levels(7, 2), levels(7, 2));
+        assert!(levels(7, 2) != levels(8, 2));
+        let run = levels(7, 9);
+        assert_eq!(run.len(), LEVELS);
+        assert!(run[0].name.ends_with("(difficulty 9)"));
+        assert!(run[4].name.ends_with("(difficulty 10)"));
+    }
+
+    #[test]
+    fn every_level_can_be_finished() {
+        for seed in 0..10 {
+            for &difficulty in [MIN_DIFFICULTY, 5, MAX_DIFFICULTY].iter() {
+                let level = level(&mut Rng::new(seed), 1, difficulty);
+                let walls: Vec<Aabb> =
+                    level.walls.iter().map(|w| Aabb::new(w[0], w[1], w[2], w[3])).collect();
+                let grid = Grid::new(level.world_width, level.world_height, CELL, &walls);
+                let g = level.goal;
+                let start = (level.spawn[0], level.spawn[1]);
+                assert!(grid.route(start, (g[0] + g[2] / 2.0, g[1] + g[3] / 2.0)).is_some());
+                for p in level.collectibles.iter().chain(level.enemies.iter()) {
+                    assert!(grid.route(start, (p[0], p[1])).is_some());
+                }
+                assert!(level.enemies.len() > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_levels_are_ordinary_level_files() {
+        let generated = level(&mut Rng::new(3), 1, 4);
+        let json = level::to_json(&generated).unwrap();
+        assert_eq!(level::parse(&json), Ok(generated));
+        assert!(!json.contains("tilemap"));
+
+        // And a run of them goes where level::load_all will find them.
+        let dir = env::temp_dir().join(format!("jumpy-generated-{}", ::std::process::id()));
+        let run = levels(3, 4);
+        export(&dir, &run).unwrap();
+        assert_eq!(level::load_all(&dir), run);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ * What the keys do while you're playing. Keys on the title screen and the
+ * game-over screen are Game's to deal with (see game.rs); once there's an
+ * App, every press and release ends up here.
+ *
+ * This would be called input.rs, but there's already a crate called input
+ * (the one Button and Keyboard come from), and a module with the same name
+ * as a crate is asking for trouble.
+ */
+use input::{self, Button, Keyboard, Controller, Mouse};
+use app::{App, LevelState};
+use audio::Sound;
+use config::Config;
+use controls;
+use gamepad;
+use keys;
+use macros;
+use script::Action;
+
+/*
+ * Here is our keyhandler. We'd probably benefit in minor ways from having
+ * this be a method on the app, but I wanted to experiment with passing
+ * bare functions as arguments. Then it turned out that I needed a closure
+ * to do partial application anyway, and I had to do partial application
+ * in order to take the right set of arguments from the event handler.
+ * C'est la vie.
+ */
+pub fn handleKey(key: Button, app: &mut App) { 
+    debug!(target: Input, "Pressed {}", keys::button_name(key));
+    /*
+     * The debug console comes before everything, even quit: while it's
+     * open, keys are for typing with, and they aren't the game's to record
+     * or tell the script about. The letters typed arrive separately, as
+     * text (see Console), so all that's left for the keys to do here is
+     * run the line, rub out, and close.
+     */
+    if key == Keyboard(input::keyboard::Backquote) {
+        app.toggle_console();
+        return;
+    }
+    if app.console.open {
+        match key {
+            Keyboard(input::keyboard::Return) => app.submit_console(),
+            Keyboard(input::keyboard::Backspace) => app.console.backspace(),
+            Keyboard(input::keyboard::Escape) => app.toggle_console(),
+            _ => {}
+        }
+        return;
+    }
+    app.record(key, Action::Press);
+    if let Keyboard(k) = key {
+        macros::heard(app, k, Action::Press);
+    }
+    app.tell_lua(key);
+    /*
+     * Reset and quit come first, before anything that might ignore the key,
+     * because they should work whatever the game is up to. Their keys come
+     * from the config rather than being baked in, so a match arm on a fixed
+     * key won't do; instead we ask keys::is_key whether this is the key the
+     * config names.
+     */
+    match key {
+        Keyboard(k) if keys::is_key(k, &app.config.reset_key) => {
+            app.reset();
+            return;
+        }
+        Keyboard(k) if keys::is_key(k, &app.config.quit_key) => {
+            app.quit = true;
+            return;
+        }
+        // The help key (or F1, which is where everyone looks for help)
+        Keyboard(input::keyboard::F1) => {
+            app.toggle_help();
+            return;
+        }
+        Keyboard(k) if keys::is_key(k, &app.config.help_key) => {
+            app.toggle_help();
+            return;
+        }
+        _ => {}
+    }
+
+    // With help open, everything else gets swallowed so nobody goes
+    // wandering about behind the overlay.
+    if app.help_open {
+        debug!(target: Input, "Ignored {}: the help is open", keys::button_name(key));
+        return;
+    }
+    // The same goes for the dialogue box, where Return is the only key
+    // that does anything: it moves what they're saying on.
+    if app.dialogue.is_some() {
+        if key == Keyboard(input::keyboard::Return) {
+            app.advance_dialogue();
+        } else {
+            debug!(target: Input, "Ignored {}: someone's talking", keys::button_name(key));
+        }
+        return;
+    }
+    // The rewind key is held rather than pressed, like the arrow keys, and
+    // tick does the rewinding for as long as it's down (see rewind.rs).
+    if let Keyboard(k) = key {
+        if keys::is_key(k, &app.config.rewind_key) {
+            app.rewinding = true;
+            return;
+        }
+    }
+
+    // A macro's key plays the macro instead, and the macro's keys come
+    // back round through here (see macros.rs).
+    if let Keyboard(k) = key {
+        if macros::key_down(app, k) { return }
+    }
+
+    // From here on, the key might be player two's. If so it's been swapped
+    // for the one it stands in for: see whose_key.
+    let (player, key) = whose_key(key, app);
+
+    // The arrow keys don't move anything themselves any more; they just get
+    // noted down as held, and update does the moving. Q and E, which turn,
+    // are the same. (E only in single player: in co-op it's player two's
+    // expand.) We note them even when the game isn't taking input, otherwise
+    // a key held through the level-complete flash would be forgotten about.
+    // Setting off makes a little noise, as long as we really are.
+    let moving = match key {
+        Keyboard(k) => app.players[player].input.press(k),
+        Controller(b) => app.controller_map.press(&mut app.players[0].input, b.button),
+        // With touch controls, a finger down on the left half of the
+        // screen is for moving with (see touch.rs).
+        Mouse(input::mouse::Left) if app.touch.enabled => app.touch_press(),
+        _ => false,
+    };
+    if moving {
+        if app.level_state == LevelState::Playing && !app.paused && app.cutscene.is_none() {
+            app.play(Sound::Move);
+        }
+        return;
+    }
+
+    // No wandering off while the level-complete flash is going (or after
+    // it's all over).
+    if app.level_state != LevelState::Playing {
+        debug!(target: Input, "Ignored {}: the level is over", keys::button_name(key));
+        return;
+    }
+    // Nor while there's a cutscene on, which the keys can only skip (with
+    // Return) or pause.
+    let pausing = key == Keyboard(input::keyboard::P) || key == Keyboard(input::keyboard::Escape);
+    if app.cutscene.is_some() && !pausing {
+        if key == Keyboard(input::keyboard::Return) {
+            app.skip_cutscene();
+        } else {
+            debug!(target: Input, "Ignored {}: there's a cutscene on", keys::button_name(key));
+        }
+        return;
+    }
+    // Player two can walk (that's been done, above) and expand, and that's
+    // all.
+    if player > 0 {
+        if let Keyboard(input::keyboard::Space) = key {
+            app.trigger_expand(player);
+        }
+        return;
+    }
+    match key {
+        // Space, or the controller's face button, fires off expand; or in
+        // platformer mode, jumps.
+        Keyboard(input::keyboard::Space) if app.platformer => { app.jump() }
+        Keyboard(input::keyboard::Space) => { app.trigger_expand(0) }
+        Controller(b) if app.controller_map.action(b.button) == Some(gamepad::Action::Expand) => {
+            if app.platformer { app.jump() } else { app.trigger_expand(0) }
+        }
+        // F, or the controller's other face button, fires. So does the
+        // left mouse button, at wherever the mouse is pointing.
+        Keyboard(input::keyboard::F) => { app.fire() }
+        // Unless touch controls have the mouse, in which case one on the
+        // right half is Space.
+        Mouse(input::mouse::Left) if app.touch.enabled => {
+            if app.platformer { app.jump() } else { app.trigger_expand(0) }
+        }
+        Mouse(input::mouse::Left) => { app.fire_at_cursor() }
+        // Shift, either one, dashes.
+        Keyboard(input::keyboard::LShift) | Keyboard(input::keyboard::RShift) => { app.dash() }
+        Controller(b) if app.controller_map.action(b.button) == Some(gamepad::Action::Fire) => {
+            app.fire()
+        }
+        // P or Escape pauses, and . steps a single tick forward while paused.
+        Keyboard(input::keyboard::P) | Keyboard(input::keyboard::Escape) => {
+            app.paused = !app.paused;
+            info!("{}", if app.paused { "Paused" } else { "Unpaused" });
+        }
+        Keyboard(input::keyboard::Period) => { app.step_requested = app.paused }
+        // Return talks to whoever you're standing on.
+        Keyboard(input::keyboard::Return) => { app.talk() }
+        // B flips between walking about and bouncing about.
+        Keyboard(input::keyboard::B) => { app.toggle_bounce() }
+        // G turns gravity on: platformer mode.
+        Keyboard(input::keyboard::G) => { app.toggle_platformer() }
+        // W makes the edges of the screen wrap round, or stop doing so. (Not
+        // in co-op, where it's player two's Up.)
+        Keyboard(input::keyboard::W) => { app.toggle_world_mode() }
+        // N skips to the next level.
+        Keyboard(input::keyboard::N) => { app.next_level() }
+        // + and - zoom. The + is really the = key, since that's where + is
+        // without holding shift.
+        Keyboard(input::keyboard::Equals) => {
+            let step = app.config.zoom_step;
+            app.zoom_by(step)
+        }
+        Keyboard(input::keyboard::Minus) => {
+            let step = app.config.zoom_step;
+            app.zoom_by(1.0 / step)
+        }
+        // F3 shows how long frames are taking to draw.
+        Keyboard(input::keyboard::F3) => { app.toggle_frame_stats() }
+        // F4 shows the hitboxes, a grid, and where everything's off to.
+        Keyboard(input::keyboard::F4) => { app.toggle_debug() }
+        // M mutes the sound.
+        Keyboard(input::keyboard::M) => { app.toggle_mute() }
+        // F5 saves, F9 loads, like in every PC game since about 1995.
+        Keyboard(input::keyboard::F5) => { app.save_game() }
+        Keyboard(input::keyboard::F9) => { app.load_game() }
+        // F7 and F8 step the update rate down and up, so you can see for
+        // yourself that the game plays the same at any of them. (F11, for
+        // fullscreen, is dealt with by Game, since it works on every
+        // screen.)
+        Keyboard(input::keyboard::F7) => { app.cycle_ups(false) }
+        Keyboard(input::keyboard::F8) => { app.cycle_ups(true) }
+        // F6 turns the touch controls on and off.
+        Keyboard(input::keyboard::F6) => { app.toggle_touch() }
+        /* Rust makes you match all possibilities and doesn't have nil.
+         * Aww yiss.
+         * When matching, _ is basically else.
+         * Here, it's everything we don't have a use for, which is worth a
+         * line in the debug log when you're wondering why a key did nothing.
+         */
+        _ => debug!(target: Input, "Ignored {}: not bound to anything", keys::button_name(key))
+    }
+}
+
+// And when a key (or D-pad button) comes back up, it's no longer held.
+// Letting go of Space mid-jump makes it a short one.
+pub fn handleRelease(key: Button, app: &mut App) {
+    app.record(key, Action::Release);
+    if let Keyboard(k) = key {
+        macros::heard(app, k, Action::Release);
+        if keys::is_key(k, &app.config.rewind_key) {
+            app.rewinding = false;
+            return;
+        }
+        if macros::key_up(app, k) { return }
+    }
+    match whose_key(key, app) {
+        (0, Keyboard(input::keyboard::Space)) => { app.end_jump(); }
+        (player, Keyboard(k)) => { app.players[player].input.release(k); }
+        (_, Controller(b)) => {
+            if app.controller_map.action(b.button) == Some(gamepad::Action::Expand) {
+                app.end_jump();
+            }
+            app.controller_map.release(&mut app.players[0].input, b.button);
+        }
+        // A finger coming off the screen lets go of the drag, or if it
+        // wasn't dragging, it was a tap, and that ends a jump like Space.
+        (_, Mouse(input::mouse::Left)) => {
+            let dragging = app.touch.drag.is_some();
+            app.touch_release();
+            if app.touch.enabled && !dragging { app.end_jump(); }
+        }
+        _ => {}
+    }
+}
+
+// Whose key is this, and which key is it really? Everything's player one's
+// unless there's a player two, in which case WASD and E are theirs. See
+// controls::player_two_key.
+fn whose_key(key: Button, app: &App) -> (usize, Button) {
+    match key {
+        Keyboard(k) if app.settings.coop => match controls::player_two_key(k) {
+            Some(k) => (1, Keyboard(k)),
+            None => (0, key),
+        },
+        _ => (0, key),
+    }
+}
+
+/*
+ * Every key binding, as (key, what it does) pairs ready to show to a
+ * person. The configurable ones are read from the config, so this always
+ * reflects what the keys actually do right now. Keep it in step with
+ * handleKey!
+ */
+pub fn bindings(config: &Config) -> Vec<(String, &'static str)> {
+    use input::keyboard;
+    let configured = |name: &str| match keys::parse_key(name) {
+        Some(key) => keys::key_name(key),
+        None => format!("{} (unknown key)", name),
+    };
+    let mut all = vec![
+        (keys::key_name(keyboard::Up), "Move up"),
+        (keys::key_name(keyboard::Down), "Move down"),
+        (keys::key_name(keyboard::Left), "Move left"),
+        (keys::key_name(keyboard::Right), "Move right"),
+        (keys::key_name(keyboard::Space), "Expand"),
+        (keys::key_name(keyboard::F), "Fire"),
+        ("Left Mouse Button".to_string(), "Fire at the mouse (move and expand with F6)"),
+        (format!("{} / {}", keys::key_name(keyboard::Q), keys::key_name(keyboard::E)),
+         "Turn (E not in co-op)"),
+        ("Shift".to_string(), "Dash"),
+        ("D-pad / Left Stick".to_string(), "Move (controller)"),
+        ("Controller Button 0".to_string(), "Expand (controller)"),
+        ("Controller Button 1".to_string(), "Fire (controller)"),
+        (keys::key_name(keyboard::B), "Toggle bounce mode"),
+        (keys::key_name(keyboard::G), "Toggle platformer mode (Space jumps)"),
+        (keys::key_name(keyboard::W), "Toggle screen wrap (not in co-op)"),
+        ("W / A / S / D".to_string(), "Move player two (co-op)"),
+        (keys::key_name(keyboard::E), "Expand player two (co-op)"),
+        (keys::key_name(keyboard::N), "Skip to the next level"),
+        ("+ / - / Mouse Wheel".to_string(), "Zoom in and out"),
+        (format!("{} / {}", keys::key_name(keyboard::P), keys::key_name(keyboard::Escape)),
+         "Pause"),
+        (".".to_string(), "Step one tick (while paused)"),
+        (keys::key_name(keyboard::Return), "Talk, and skip a cutscene"),
+        (configured(&config.rewind_key), "Rewind time (hold)"),
+        (configured(&config.macro_key), "Record a macro, then pick its key"),
+        (format!("{} / {}", configured(&config.help_key), keys::key_name(keyboard::F1)),
+         "Show or hide this help"),
+        (keys::key_name(keyboard::F3), "Show or hide frame times"),
+        (keys::key_name(keyboard::F4), "Show or hide hitboxes and the grid"),
+        (keys::key_name(keyboard::Backquote), "Open or close the debug console"),
+        (keys::key_name(keyboard::M), "Sound on and off"),
+        (keys::key_name(keyboard::F5), "Save the game"),
+        (keys::key_name(keyboard::F9), "Load the saved game"),
+        (keys::key_name(keyboard::F7), "Fewer updates per second"),
+        (keys::key_name(keyboard::F8), "More updates per second"),
+        (keys::key_name(keyboard::F6), "Touch controls on and off"),
+        (keys::key_name(keyboard::F10), "Reload the config, levels and pictures"),
+        (keys::key_name(keyboard::F11), "Fullscreen on and off"),
+        (keys::key_name(keyboard::F12), "Take a screenshot"),
+        (keys::key_name(keyboard::F2), "Save the last few seconds as a GIF"),
+        (configured(&config.reset_key), "Reset the game"),
+        (configured(&config.quit_key), "Quit"),
+    ];
+    // And whatever keys the config's put macros or turbo on.
+    for name in config.macros.keys() {
+        all.push((configured(name), "Play a macro"));
+    }
+    for name in config.turbo_keys.iter() {
+        all.push((configured(name), "Turbo (hold)"));
+    }
+    all
+}
@@ -0,0 +1,267 @@
+/*
+ * The HUD (heads-up display): a few lines of text in the top-left corner of
+ * the window saying how you're getting on. Drawing text needs a font, and
+ * drawing it quickly needs a glyph cache: the first time we draw a letter it
+ * gets rendered from the font into a little texture, and every time after
+ * that we just draw the texture.
+ *
+ * No font ships with the game, so (like the sprites in assets.rs) this is
+ * optional. Point font_path in the config at any .ttf file to get a HUD;
+ * without one, the numbers only show up in the log.
+ *
+ * Hud has the fonts for everyone else too - the menus, the console, the
+ * toasts - by name, as the asset manager registers them. Everything gets
+ * written through write, in a Text style (see text.rs), which picks the
+ * font; one that isn't there is written in the default one instead, and
+ * with no default one, nothing is written at all.
+ */
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use graphics::{
+    Context,
+    Transformed,
+};
+use opengl_graphics::TextureSettings;
+
+use app::App;
+use bus::GameEvent;
+use engine::GlyphCache;
+use locale::Locale;
+use powerup::Active;
+use renderer::Renderer;
+use text::{Align, Text, DEFAULT_FONT};
+
+// How far the text sits in from the corner of the window, in pixels.
+pub const MARGIN: f64 = 10.0;
+
+// The health bar: a square for every point of health, full ones red and
+// lost ones dark, along the top right of the window.
+pub const HEALTH_SQUARE: f64 = 12.0;
+const HEALTH_GAP: f64 = 4.0;
+pub const HEALTH_FULL: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+pub const HEALTH_LOST: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+
+// What the HUD shows, gathered up by App each frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub score: u32,
+    pub health: u32,
+    // What health starts at, for the health bar.
+    pub max_health: u32,
+    pub lives: u32,
+    // How many enemies have been knocked out this game.
+    pub kills: u32,
+    // Which wave of enemies it is, 0 if there aren't any waves.
+    pub wave: u32,
+    pub position: (f64, f64),
+    pub expand: f64,
+    // The power-ups in effect, and how long they've got left.
+    pub effects: Vec<Active>,
+    // How quickly frames are being drawn, if F3 has asked to see it.
+    pub frame_stats: Option<String>,
+    // What's happening with the macro being recorded, if one is; see
+    // macros.rs.
+    pub macro_line: Option<String>,
+}
+
+pub struct Hud {
+    fonts: BTreeMap<String, GlyphCache>,
+}
+
+impl Hud {
+    pub fn none() -> Hud {
+        Hud { fonts: BTreeMap::new() }
+    }
+
+    // Write in `glyphs` whatever asks for the font called `name`.
+    pub fn register(&mut self, name: &str, glyphs: GlyphCache) {
+        self.fonts.insert(name.to_string(), glyphs);
+    }
+
+    pub fn has_font(&self, name: &str) -> bool {
+        self.fonts.contains_key(name)
+    }
+
+    /*
+     * One line of `text`, in `style`, lined up on `x` the way the style
+     * says, and sitting on `baseline` - the line the letters sit on, which
+     * is how text is positioned. The shadow goes first, so the text's on
+     * top of it.
+     */
+    pub fn write(&mut self, style: &Text, text: &str, x: f64, baseline: f64,
+                 context: &Context, r: &mut dyn Renderer) {
+        let name = if self.fonts.contains_key(&style.font) { &style.font[..] }
+                   else { DEFAULT_FONT };
+        let glyphs = match self.fonts.get_mut(name) {
+            Some(glyphs) => glyphs,
+            None => return,
+        };
+        let x = style.left(text, x);
+        if let Some(shadow) = style.shadow {
+            let offset = style.shadow_offset();
+            r.text(&context.trans(x + offset, baseline + offset), text, style.size, shadow,
+                   glyphs);
+        }
+        r.text(&context.trans(x, baseline), text, style.size, style.color, glyphs);
+    }
+
+    /*
+     * Draw the status, one line per thing, in `style`, in the corner, and
+     * the health bar in the other corner of a `width` pixel wide window.
+     * The bar is only rectangles, so unlike the rest it's there even
+     * without a font.
+     */
+    pub fn draw(&mut self, status: &Status, locale: &Locale, style: &Text, width: f64,
+                context: &Context, r: &mut dyn Renderer) {
+        for (x, full) in health_bar(status.health, status.max_health, width) {
+            r.rect(context, x, MARGIN, HEALTH_SQUARE, HEALTH_SQUARE,
+                   if full { HEALTH_FULL } else { HEALTH_LOST });
+        }
+        self.draw_lines(&lines(status, locale), style, MARGIN, MARGIN, context, r);
+    }
+
+    /*
+     * Lines of text, one under the other, with the top of the first at
+     * height `y`, lined up on `x`. Since text goes by its baseline, each
+     * line goes a whole line height further down than the top of where we
+     * want it.
+     */
+    pub fn draw_lines(&mut self, lines: &[String], style: &Text, x: f64, y: f64,
+                      context: &Context, r: &mut dyn Renderer) {
+        let line_height = line_height(style.size);
+        for (i, line) in lines.iter().enumerate() {
+            let y = y + line_height * (i + 1) as f64;
+            self.write(style, line, x, y, context, r);
+        }
+    }
+}
+
+// The font at `path`. A missing or broken font is a warning, and None;
+// whatever wanted it goes without, or makes do with another.
+pub fn load_font(path: &Path) -> Option<GlyphCache> {
+    if !path.exists() {
+        info!("No font at {}", path.display());
+        return None;
+    }
+    match GlyphCache::new(path, (), TextureSettings::new()) {
+        Ok(glyphs) => {
+            info!("Loaded font {}", path.display());
+            Some(glyphs)
+        }
+        Err(e) => {
+            warn!("Couldn't load font {}: {:?}", path.display(), e);
+            None
+        }
+    }
+}
+
+// How far apart lines of `size` pixel text go, with a little gap between.
+pub fn line_height(size: u32) -> f64 {
+    size as f64 * 1.25
+}
+
+impl Hud {
+    /*
+     * A line of text centred across a `width` pixel wide window, with its
+     * middle at height `y`, like PAUSED in the middle of the screen,
+     * whichever way `style` lines up otherwise.
+     */
+    pub fn draw_banner(&mut self, text: &str, style: &Text, width: f64, y: f64,
+                       context: &Context, r: &mut dyn Renderer) {
+        let centred = style.clone().align(Align::Center);
+        self.write(&centred, text, width / 2.0, y + style.size as f64 / 2.0, context, r);
+    }
+}
+
+// Where each of the health bar's squares go across, and whether it's a
+// point you've still got. Health set higher than usual (from the console,
+// say) gets more squares rather than running off the end.
+pub fn health_bar(health: u32, max_health: u32, width: f64) -> Vec<(f64, bool)> {
+    let squares = health.max(max_health);
+    let step = HEALTH_SQUARE + HEALTH_GAP;
+    let left = width - MARGIN - squares as f64 * step + HEALTH_GAP;
+    (0..squares).map(|i| (left + i as f64 * step, i < health)).collect()
+}
+
+// The HUD keeps its own count of the knockouts, off App's bus (see bus.rs).
+pub fn hear(event: &GameEvent, app: &mut App) {
+    if let GameEvent::EnemyKilled { .. } = *event {
+        app.knocked_out += 1;
+    }
+}
+
+// The HUD's text, in `locale`'s language, worked out separately from
+// drawing it so it can be tested without a window.
+pub fn lines(status: &Status, locale: &Locale) -> Vec<String> {
+    let (x, y) = (format!("{:.0}", status.position.0), format!("{:.0}", status.position.1));
+    let mut lines = vec![
+        locale.text("hud.score", &[&status.score]),
+        locale.text("hud.health", &[&status.health]),
+        locale.text("hud.lives", &[&status.lives]),
+        locale.text("hud.position", &[&x, &y]),
+        locale.text("hud.expand", &[&format!("{:.1}", status.expand)]),
+    ];
+    // Nobody needs telling they haven't knocked anything out yet.
+    if status.kills > 0 {
+        lines.push(locale.text("hud.kills", &[&status.kills]));
+    }
+    if status.wave > 0 {
+        lines.push(locale.text("hud.wave", &[&status.wave]));
+    }
+    for a in status.effects.iter() {
+        let name = locale.get(&format!("power_up.{}", a.effect.name())).to_string();
+        lines.push(locale.text("hud.power_up", &[&name, &a.left.ceil()]));
+    }
+    if let Some(ref stats) = status.frame_stats {
+        lines.push(stats.clone());
+    }
+    if let Some(ref line) = status.macro_line {
+        lines.push(line.clone());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use powerup::Effect;
+    use renderer::Recorder;
+
+    #[test]
+    fn shows_how_you_are_doing() {
+        let mut status = Status { score: 3, health: 2, max_health: 3, lives: 1, kills: 0,
+                                  wave: 0, position: (120.4, 86.6), expand: 12.34,
+                                  effects: Vec::new(), frame_stats: None, macro_line: None };
+        let english = Locale::english();
+        assert_eq!(lines(&status, &english), vec!["Score: 3", "Health: 2", "Lives: 1",
+                                                  "Position: 120, 87", "Expand: 12.3"]);
+        status.kills = 2;
+        assert_eq!(lines(&status, &english).last(), Some(&"Knocked out: 2".to_string()));
+        status.wave = 4;
+        assert_eq!(lines(&status, &english).last(), Some(&"Wave: 4".to_string()));
+        status.effects.push(Active { effect: Effect::Speed, left: 2.2 });
+        assert_eq!(lines(&status, &english).last(), Some(&"Power-up: speed (3s)".to_string()));
+        status.frame_stats = Some("60 fps".to_string());
+        assert_eq!(lines(&status, &english).last(), Some(&"60 fps".to_string()));
+    }
+
+    #[test]
+    fn the_health_bar_fills_in_from_the_left() {
+        // Three squares ending MARGIN in from the right of a 640 wide window.
+        assert_eq!(health_bar(2, 3, 640.0), vec![(586.0, true), (602.0, true), (618.0, false)]);
+        assert_eq!(health_bar(4, 3, 640.0).len(), 4);
+        assert!(health_bar(0, 3, 640.0).iter().all(|&(_, full)| !full));
+    }
+
+    #[test]
+    fn missing_font_means_no_hud() {
+        assert!(load_font(Path::new("no-such-font.ttf")).is_none());
+        let mut hud = Hud::none();
+        let mut r = Recorder::new();
+        hud.write(&Text::new(16, [1.0; 4]).font("console"), "hi", 0.0, 0.0,
+                  &Context::new_abs(640.0, 480.0), &mut r);
+        assert!(r.calls.is_empty());
+    }
+}
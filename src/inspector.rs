@@ -0,0 +1,356 @@
+/*
+ * The inspector: a second window, beside the game's, listing everything in
+ * the level as it plays, with where it is and how fast it's going, and
+ * letting you change those as it goes. Ask for it with --inspector. It's
+ * for finding out why something's where it is, or putting something where
+ * you want it to see what happens, without stopping to type the numbers
+ * into the console.
+ *
+ * In the inspector's window, Up and Down pick a row, and Left and Right
+ * pick which of its numbers: x, y, and the speed it's going each way, vx
+ * and vy. + and - nudge it by NUDGE, or Enter lets you type a number in
+ * and Enter again puts it in (Escape thinks better of it). The players
+ * have all four; the enemies and shots have a place, and the shots a
+ * speed; anything else (the pickups, the items and so on) is only shown,
+ * because entity.rs's things can say where they are but not be moved.
+ * Walls are left out altogether, since there are a lot of them and none of
+ * them ever go anywhere.
+ *
+ * All that is the Inspector, which doesn't need a window, so the tests can
+ * put it through its paces. InspectorWindow is the window round it, and
+ * the only part that's particular about what sort of window it's in.
+ *
+ * Two windows means the event loop looking after two of them. The game's
+ * window still gets its events from PistonLoop, which says when to update
+ * and when to draw; but every time round, run also asks the inspector's
+ * window for whatever's happened to it (poll_event, which doesn't wait),
+ * and gives it to the inspector, and every time the game's drawn, so is
+ * the inspector. Each window has an OpenGL context of its own, and the
+ * drawing goes to whichever's current, so the inspector's is made current
+ * to draw on it, its frame is put on the screen, and the game's is made
+ * current again straight after, before anything notices. Its font is its
+ * own too, since a font's letters are kept in the context they were first
+ * drawn in.
+ */
+use engine::Event;
+use input::{keyboard, Keyboard};
+use app::App;
+use ecs::{Id, Kind, Position, Velocity};
+
+// What + and - change a number by.
+pub const NUDGE: f64 = 10.0;
+
+// The numbers each row can have, in order.
+pub const FIELDS: [&'static str; 4] = ["x", "y", "vx", "vy"];
+
+// What a row's about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Player(usize),
+    Thing(Id),
+    // One of App's entities, by where it is in the list.
+    Entity(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub target: Target,
+    pub name: String,
+    // x, y, vx and vy, where it has them.
+    pub values: [Option<f64>; 4],
+    pub editable: bool,
+}
+
+// A row for everything in `app` worth a row: the players, then the
+// scene's enemies and shots, then the level's other entities.
+pub fn rows(app: &App) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (i, p) in app.players.iter().enumerate() {
+        rows.push(Row {
+            target: Target::Player(i),
+            name: format!("player {}", i + 1),
+            values: [Some(p.x), Some(p.y), Some(p.vx), Some(p.vy)],
+            editable: true,
+        });
+    }
+    for id in app.scene.ids() {
+        let name = match app.scene.kind(id) {
+            Some(Kind::Enemy) => "enemy",
+            Some(Kind::Shot) => "shot",
+            None => "thing",
+        };
+        let position = app.scene.positions[id.index];
+        let velocity = app.scene.velocities[id.index];
+        rows.push(Row {
+            target: Target::Thing(id),
+            name: format!("{} {}", name, id.index),
+            values: [position.map(|p| p.x), position.map(|p| p.y),
+                     velocity.map(|v| v.vx), velocity.map(|v| v.vy)],
+            editable: true,
+        });
+    }
+    for (i, e) in app.entities.iter().enumerate().filter(|&(_, e)| !e.solid()) {
+        let b = e.bounds();
+        rows.push(Row {
+            target: Target::Entity(i),
+            name: format!("{} {}", e.item().or(e.sprite()).unwrap_or("entity"), i),
+            values: [Some(b.x), Some(b.y), None, None],
+            editable: false,
+        });
+    }
+    rows
+}
+
+// Set number `field` of `target` to `value`. Whether it had that number
+// to set.
+pub fn set(app: &mut App, target: Target, field: usize, value: f64) -> bool {
+    match target {
+        Target::Player(i) if i < app.players.len() => {
+            let player = &mut app.players[i];
+            match field {
+                0 => player.x = value,
+                1 => player.y = value,
+                2 => player.vx = value,
+                3 => player.vy = value,
+                _ => return false,
+            }
+            true
+        }
+        Target::Thing(id) if app.scene.alive(id) => {
+            let scene = &mut app.scene;
+            match (field, scene.positions[id.index], scene.velocities[id.index]) {
+                (0, Some(p), _) => scene.positions[id.index] = Some(Position { x: value, ..p }),
+                (1, Some(p), _) => scene.positions[id.index] = Some(Position { y: value, ..p }),
+                (2, _, Some(v)) => scene.velocities[id.index] = Some(Velocity { vx: value, ..v }),
+                (3, _, Some(v)) => scene.velocities[id.index] = Some(Velocity { vy: value, ..v }),
+                _ => return false,
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+// Which row and number are picked, and what's been typed, if anything's
+// being typed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inspector {
+    pub selected: usize,
+    pub field: usize,
+    pub typing: Option<String>,
+}
+
+impl Inspector {
+    pub fn new() -> Inspector {
+        Inspector::default()
+    }
+
+    // Something's happened in the inspector's window. There's only
+    // anything to inspect while there's a game going.
+    pub fn handle_event(&mut self, event: &Event, app: Option<&mut App>) {
+        let app = match app {
+            Some(app) => app,
+            None => return,
+        };
+        match *event {
+            Event::Press(Keyboard(key)) => self.handle_key(key, app),
+            Event::Text(ref text) => {
+                if let Some(ref mut typing) = self.typing {
+                    let number = |c: &char| c.is_digit(10) || *c == '.' || *c == '-';
+                    typing.extend(text.chars().filter(number));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_key(&mut self, key: keyboard::Key, app: &mut App) {
+        let rows = rows(app);
+        if rows.is_empty() { return }
+        self.selected = self.selected.min(rows.len() - 1);
+        let row = &rows[self.selected];
+        if self.typing.is_some() {
+            match key {
+                keyboard::Return => {
+                    let typed = self.typing.take().unwrap_or_default();
+                    match typed.parse() {
+                        Ok(value) => self.change(app, row, |_| value),
+                        Err(_) => info!("\"{}\" isn't a number", typed),
+                    }
+                }
+                keyboard::Backspace => { self.typing.as_mut().map(|t| t.pop()); }
+                keyboard::Escape => self.typing = None,
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            keyboard::Up => self.selected = (self.selected + rows.len() - 1) % rows.len(),
+            keyboard::Down => self.selected = (self.selected + 1) % rows.len(),
+            keyboard::Left => self.field = (self.field + FIELDS.len() - 1) % FIELDS.len(),
+            keyboard::Right => self.field = (self.field + 1) % FIELDS.len(),
+            keyboard::Equals | keyboard::NumPadPlus => self.change(app, row, |v| v + NUDGE),
+            keyboard::Minus | keyboard::NumPadMinus => self.change(app, row, |v| v - NUDGE),
+            keyboard::Return if row.editable && row.values[self.field].is_some() => {
+                self.typing = Some(String::new());
+            }
+            _ => {}
+        }
+    }
+
+    // The picked number of `row`, made into whatever `to` makes of it.
+    fn change<F: Fn(f64) -> f64>(&self, app: &mut App, row: &Row, to: F) {
+        if !row.editable { return }
+        if let Some(value) = row.values[self.field] {
+            let value = to(value);
+            if set(app, row.target, self.field, value) {
+                info!("Inspector: {} {} is now {:.1}", row.name, FIELDS[self.field], value);
+            }
+        }
+    }
+
+    /*
+     * What the window says, a line a row, starting from whichever row puts
+     * the picked one on the screen when only `fit` lines do. The picked
+     * row has an arrow, and its picked number's in brackets (or what's
+     * being typed for it is). A number a row hasn't got is a dash.
+     */
+    pub fn lines(&self, rows: &[Row], fit: usize) -> Vec<String> {
+        let fit = fit.max(1);
+        let first = (self.selected + 1).saturating_sub(fit);
+        rows.iter().enumerate().skip(first).take(fit).map(|(i, row)| {
+            let picked = i == self.selected;
+            let numbers: Vec<String> = row.values.iter().enumerate().map(|(f, value)| {
+                let number = match *value {
+                    Some(v) => format!("{:.1}", v),
+                    None => "-".to_string(),
+                };
+                let number = match self.typing {
+                    Some(ref typed) if picked && f == self.field => format!("{}_", typed),
+                    _ => number,
+                };
+                if picked && f == self.field {
+                    format!("{} [{}]", FIELDS[f], number)
+                } else {
+                    format!("{} {}", FIELDS[f], number)
+                }
+            }).collect();
+            format!("{} {:<10} {}", if picked { ">" } else { " " }, row.name, numbers.join("  "))
+        }).collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::window::InspectorWindow;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod window {
+    use graphics::{Context, Viewport};
+    use piston::window::{OpenGLWindow, Window, WindowSettings};
+
+    use engine::{self, Gl, OpenGL};
+    use game::{Game, GameState};
+    use hud::{self, Hud};
+    use renderer::{Batcher, Renderer};
+    use text::Text;
+    use window_backend::{self, GameWindow};
+    use super::{rows, Inspector};
+
+    // How big the inspector's window is to start with.
+    const WIDTH: u32 = 560;
+    const HEIGHT: u32 = 400;
+
+    pub struct InspectorWindow {
+        pub window: GameWindow,
+        pub gl: Gl,
+        pub hud: Hud,
+        pub inspector: Inspector,
+    }
+
+    impl InspectorWindow {
+        /*
+         * The inspector's window, drawing with the same OpenGL as the
+         * game's, so it's one we know works. Opening it leaves its context
+         * current, so the font's loaded into that, and then it's the
+         * game's window's turn again.
+         */
+        pub fn open<W: OpenGLWindow>(game: &mut Game, opengl: OpenGL, main: &mut W)
+                                     -> Result<InspectorWindow, String> {
+            let title = format!("{} - inspector", game.settings.config.window_title);
+            let settings = WindowSettings::new(title, [WIDTH, HEIGHT])
+                .vsync(false)
+                .graphics_api(opengl);
+            let mut window: GameWindow = settings.build().map_err(|e| e.to_string())?;
+            window.make_current();
+            let gl = window_backend::gl(opengl)?;
+            let hud = game.assets.fonts(&game.settings.config);
+            main.make_current();
+            Ok(InspectorWindow { window: window, gl: gl, hud: hud, inspector: Inspector::new() })
+        }
+
+        // Everything that's happened to the inspector's window since last
+        // time, to the inspector. Whether it's still open.
+        pub fn service(&mut self, game: &mut Game) -> bool {
+            while let Some(e) = self.window.poll_event() {
+                let e: ::piston::input::Event = e.into();
+                let event = engine::event(&e);
+                if event == engine::Event::Close { return false }
+                let app = match game.state {
+                    GameState::Playing(ref mut app) => Some(app),
+                    _ => None,
+                };
+                self.inspector.handle_event(&event, app);
+            }
+            !self.window.should_close()
+        }
+
+        // Draw the inspector and put it on the screen, then give the
+        // drawing back to the game's window, `main`.
+        pub fn draw<W: OpenGLWindow>(&mut self, game: &Game, main: &mut W) {
+            let size = self.window.size();
+            let draw_size = self.window.draw_size();
+            let viewport = Viewport {
+                rect: [0, 0, draw_size.width as i32, draw_size.height as i32],
+                draw_size: [draw_size.width as u32, draw_size.height as u32],
+                window_size: [size.width, size.height],
+            };
+            self.window.make_current();
+            let config = &game.settings.config;
+            let palette = config.palette();
+            let style = Text::new(config.hud_font_size, palette.text);
+            let fit = ((size.height - hud::MARGIN * 2.0) / hud::line_height(style.size)) as usize;
+            let lines = match game.state {
+                GameState::Playing(ref app) => self.inspector.lines(&rows(app), fit),
+                _ => vec!["Nothing to inspect until there's a game going".to_string()],
+            };
+            let hud = &mut self.hud;
+            self.gl.draw(viewport, |_, g| {
+                let context = Context::new_abs(size.width, size.height);
+                let mut batcher = Batcher::new(g);
+                batcher.clear(&context, palette.background);
+                hud.draw_lines(&lines, &style, hud::MARGIN, hud::MARGIN, &context, &mut batcher);
+                batcher.flush();
+            });
+            self.window.swap_buffers();
+            main.make_current();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_picked_row_stays_on_the_screen() {
+        let rows: Vec<Row> = (0..10).map(|i| Row {
+            target: Target::Entity(i), name: format!("pickup {}", i),
+            values: [Some(1.0), Some(2.0), None, None], editable: false,
+        }).collect();
+        let inspector = Inspector { selected: 7, ..Inspector::new() };
+        let lines = inspector.lines(&rows, 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with("> pickup 7"));
+        assert!(lines[2].ends_with("x [1.0]  y 2.0  vx -  vy -"));
+    }
+}
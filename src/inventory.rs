@@ -0,0 +1,244 @@
+/*
+ * Things you carry about. Some of what's lying around a level isn't worth
+ * a point, it's worth keeping: a key, or a power cell. Walk over one and
+ * it goes in your inventory, shown in a bar along the bottom of the screen,
+ * and what it's for is doors. A door is a wall with a lock on, and it
+ * stays shut until you walk into it with what it needs, when it takes that
+ * off you and opens (goes away, like a pickup). In co-op, the inventory is
+ * shared like the health, so it doesn't matter who's carrying the key.
+ *
+ * The level says what sorts of item there are, what color each is, and
+ * where they're lying, and where the doors are and what each one needs,
+ * so one level's keys can be another's power cells:
+ *
+ *     "items": [
+ *         { "name": "key", "color": [1.0, 0.85, 0.2, 1.0], "at": [[200.0, 800.0]] },
+ *         { "name": "power cell", "color": [0.3, 1.0, 0.6, 1.0],
+ *           "at": [[700.0, 100.0], [1100.0, 150.0]] }
+ *     ],
+ *     "doors": [
+ *         { "bounds": [600.0, 380.0, 10.0, 180.0], "needs": "key" },
+ *         { "bounds": [900.0, 310.0, 10.0, 200.0], "needs": "power cell", "count": 2 }
+ *     ]
+ *
+ * A door needs one of what it needs unless it says otherwise with `count`.
+ * The lock's drawn in the middle of the door in the color of the item, a
+ * dot for each one it wants, so you can tell what you're looking for.
+ */
+use std::collections::BTreeMap;
+
+use graphics::Context;
+
+use collision::Aabb;
+use entity::{Entity, Status, WorldInfo};
+use renderer::Renderer;
+
+// How big an item is, each way, and how close you need to be to a door to
+// open it: you can't get inside one, so it's near enough touching.
+pub const ITEM_SIZE: f64 = 12.0;
+pub const DOOR_REACH: f64 = 2.0;
+
+// The inventory bar's squares: how big, and how far apart.
+pub const SLOT_SIZE: f64 = 24.0;
+pub const SLOT_GAP: f64 = 6.0;
+
+// A sort of item, and everywhere one's lying in the level.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemKind {
+    pub name: String,
+    pub color: [f32; 4],
+    #[serde(default)]
+    pub at: Vec<[f64; 2]>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DoorSpot {
+    pub bounds: [f64; 4],
+    pub needs: String,
+    #[serde(default = "one")]
+    pub count: u32,
+}
+
+fn one() -> u32 { 1 }
+
+// What you're carrying: how many of each item, by name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    items: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory { items: BTreeMap::new() }
+    }
+
+    pub fn add(&mut self, name: &str) {
+        *self.items.entry(name.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn count(&self, name: &str) -> u32 {
+        self.items.get(name).cloned().unwrap_or(0)
+    }
+
+    // Take `count` of `name` away, if there are that many. Whether there
+    // were.
+    pub fn take(&mut self, name: &str, count: u32) -> bool {
+        let have = self.count(name);
+        if have < count { return false }
+        if have == count {
+            self.items.remove(name);
+        } else {
+            self.items.insert(name.to_string(), have - count);
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/*
+ * The inventory bar: a square for each sort of item in `kinds` you've got
+ * any of, in the order the level lists them, in a row across the middle
+ * of `x`, with their tops at `y`. Returns where each square goes, the
+ * item's color, and how many there are, so whoever's drawing can write
+ * the number on.
+ */
+pub fn bar(inventory: &Inventory, kinds: &[ItemKind], x: f64, y: f64)
+           -> Vec<([f64; 4], [f32; 4], u32)> {
+    let held: Vec<&ItemKind> = kinds.iter()
+        .filter(|kind| inventory.count(&kind.name) > 0)
+        .collect();
+    let width = held.len() as f64 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP;
+    held.iter()
+        .enumerate()
+        .map(|(i, kind)| {
+            let left = x - width / 2.0 + i as f64 * (SLOT_SIZE + SLOT_GAP);
+            ([left, y, SLOT_SIZE, SLOT_SIZE], kind.color, inventory.count(&kind.name))
+        })
+        .collect()
+}
+
+// An item lying about, waiting to be picked up. It's round, so it doesn't
+// look like a pickup.
+pub struct Item {
+    pub bounds: Aabb,
+    pub name: String,
+    pub color: [f32; 4],
+}
+
+impl Item {
+    // Centred on (x, y), like a pickup.
+    pub fn at(x: f64, y: f64, name: &str, color: [f32; 4]) -> Item {
+        let half = ITEM_SIZE / 2.0;
+        Item { bounds: Aabb::new(x - half, y - half, ITEM_SIZE, ITEM_SIZE),
+               name: name.to_string(), color: color }
+    }
+}
+
+impl Entity for Item {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.ellipse(context, [b.x, b.y, b.w, b.h], self.color);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn collectible(&self) -> bool { true }
+
+    fn item(&self) -> Option<&str> { Some(&self.name[..]) }
+}
+
+// A door: a wall, in the wall's color, with a lock in the middle in the
+// color of what it needs.
+pub struct Door {
+    pub bounds: Aabb,
+    pub color: [f32; 4],
+    pub needs: String,
+    pub count: u32,
+    pub lock_color: [f32; 4],
+}
+
+impl Entity for Door {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.rect(context, b.x, b.y, b.w, b.h, self.color);
+        // The dots go along the door's long side, as many as fit.
+        let dot = (b.w.min(b.h) * 0.6).min(ITEM_SIZE / 2.0).max(2.0);
+        let across = b.w >= b.h;
+        let length = if across { b.w } else { b.h };
+        let fits = ((length / (dot * 2.0)).floor() as u32).max(1);
+        let dots = self.count.max(1).min(fits);
+        let (cx, cy) = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+        for i in 0..dots {
+            let along = (i as f64 - (dots - 1) as f64 / 2.0) * dot * 2.0;
+            let (x, y) = if across { (cx + along, cy) } else { (cx, cy + along) };
+            r.ellipse(context, [x - dot / 2.0, y - dot / 2.0, dot, dot], self.lock_color);
+        }
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn solid(&self) -> bool { true }
+
+    fn lock(&self) -> Option<(&str, u32)> { Some((&self.needs[..], self.count)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn doors_take_what_they_need() {
+        let mut inventory = Inventory::new();
+        inventory.add("power cell");
+        assert!(!inventory.take("power cell", 2));
+        inventory.add("power cell");
+        inventory.add("key");
+        assert_eq!(inventory.count("power cell"), 2);
+        assert!(inventory.take("power cell", 2));
+        assert_eq!(inventory.count("power cell"), 0);
+        assert!(!inventory.is_empty());
+        assert!(inventory.take("key", 1));
+        assert!(inventory.is_empty());
+
+        let door: DoorSpot = serde_json::from_str(
+            r#"{ "bounds": [0.0, 0.0, 10.0, 100.0], "needs": "key" }"#).unwrap();
+        assert_eq!(door.count, 1);
+    }
+
+    #[test]
+    fn the_bar_shows_what_you_have() {
+        let kinds = vec![
+            ItemKind { name: "key".to_string(), color: [1.0, 0.8, 0.2, 1.0], at: Vec::new() },
+            ItemKind { name: "cell".to_string(), color: [0.3, 1.0, 0.6, 1.0], at: Vec::new() },
+        ];
+        let mut inventory = Inventory::new();
+        assert!(bar(&inventory, &kinds, 320.0, 400.0).is_empty());
+        inventory.add("cell");
+        inventory.add("cell");
+        let slots = bar(&inventory, &kinds, 320.0, 400.0);
+        let left = 320.0 - SLOT_SIZE / 2.0;
+        assert_eq!(slots, vec![([left, 400.0, SLOT_SIZE, SLOT_SIZE], kinds[1].color, 2)]);
+        // The key's first, since the level lists it first, and the two
+        // of them are still in the middle.
+        inventory.add("key");
+        let slots = bar(&inventory, &kinds, 320.0, 400.0);
+        assert_eq!((slots[0].1, slots[0].2), (kinds[0].color, 1));
+        assert_eq!(slots[0].0[0] + slots[1].0[0] + SLOT_SIZE, 640.0);
+    }
+}
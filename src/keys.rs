@@ -0,0 +1,131 @@
+/*
+ * Keys have names, so that the config file can say "R" or "Escape" instead
+ * of some number only the keyboard driver understands. This is the table
+ * that maps between the two. It also works the other way round, turning a
+ * key into something nice to show a human, for the help screen and for
+ * complaining about the config.
+ */
+use input::{
+    Button,
+    Keyboard,
+    Mouse,
+    Controller,
+    Hat,
+};
+use input::keyboard::{self, Key};
+
+const NAMES: &'static [(Key, &'static str)] = &[
+    (keyboard::Up, "Up"),
+    (keyboard::Down, "Down"),
+    (keyboard::Left, "Left"),
+    (keyboard::Right, "Right"),
+    (keyboard::Space, "Space"),
+    (keyboard::Return, "Return"),
+    (keyboard::Escape, "Escape"),
+    (keyboard::Tab, "Tab"),
+    (keyboard::Backspace, "Backspace"),
+    (keyboard::Backquote, "`"),
+    (keyboard::Period, "Period"),
+    (keyboard::Equals, "Equals"),
+    (keyboard::Minus, "Minus"),
+    (keyboard::LShift, "LShift"),
+    (keyboard::RShift, "RShift"),
+    (keyboard::A, "A"), (keyboard::B, "B"), (keyboard::C, "C"),
+    (keyboard::D, "D"), (keyboard::E, "E"), (keyboard::F, "F"),
+    (keyboard::G, "G"), (keyboard::H, "H"), (keyboard::I, "I"),
+    (keyboard::J, "J"), (keyboard::K, "K"), (keyboard::L, "L"),
+    (keyboard::M, "M"), (keyboard::N, "N"), (keyboard::O, "O"),
+    (keyboard::P, "P"), (keyboard::Q, "Q"), (keyboard::R, "R"),
+    (keyboard::S, "S"), (keyboard::T, "T"), (keyboard::U, "U"),
+    (keyboard::V, "V"), (keyboard::W, "W"), (keyboard::X, "X"),
+    (keyboard::Y, "Y"), (keyboard::Z, "Z"),
+    (keyboard::F1, "F1"), (keyboard::F2, "F2"), (keyboard::F3, "F3"),
+    (keyboard::F4, "F4"), (keyboard::F5, "F5"), (keyboard::F6, "F6"),
+    (keyboard::F7, "F7"), (keyboard::F8, "F8"), (keyboard::F9, "F9"),
+    (keyboard::F10, "F10"), (keyboard::F11, "F11"), (keyboard::F12, "F12"),
+];
+
+// Look a key up by name. Case doesn't matter, so "escape" works too.
+pub fn parse_key(name: &str) -> Option<Key> {
+    NAMES.iter()
+        .find(|&&(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|&(k, _)| k)
+}
+
+// The other way round: the name parse_key would want for `key`, if it has
+// one. Unlike key_name, this is for writing to files, not showing people.
+pub fn short_name(key: Key) -> Option<&'static str> {
+    NAMES.iter().find(|&&(k, _)| k == key).map(|&(_, n)| n)
+}
+
+// Whether `key` is the one named by `name`. An unknown name matches nothing.
+pub fn is_key(key: Key, name: &str) -> bool {
+    parse_key(name) == Some(key)
+}
+
+/*
+ * A key's name as a person would say it. Mostly that's just the name from
+ * the table, but "Left" on its own is a bit ambiguous on a help screen, so
+ * the arrows get spelled out. Anything that isn't in the table falls back
+ * to rust's debug formatting, which is ugly but at least tells you what it
+ * is.
+ */
+pub fn key_name(key: Key) -> String {
+    match key {
+        keyboard::Up => "Up Arrow".to_string(),
+        keyboard::Down => "Down Arrow".to_string(),
+        keyboard::Left => "Left Arrow".to_string(),
+        keyboard::Right => "Right Arrow".to_string(),
+        _ => match NAMES.iter().find(|&&(k, _)| k == key) {
+            Some(&(_, name)) => name.to_string(),
+            None => format!("{:?}", key),
+        }
+    }
+}
+
+// The same, for any button at all, mouse and controller buttons included.
+// Controllers only tell us a button's number, so that's what you get. A
+// hat is the D-pad on controllers that don't call it buttons.
+pub fn button_name(button: Button) -> String {
+    match button {
+        Keyboard(key) => key_name(key),
+        Mouse(mouse) => format!("{:?} Mouse Button", mouse),
+        Controller(b) => format!("Controller Button {}", b.button),
+        Hat(hat) => format!("Controller Hat {:?}", hat.state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::{keyboard, Keyboard};
+
+    #[test]
+    fn display_names() {
+        assert_eq!(button_name(Keyboard(keyboard::Left)), "Left Arrow");
+        assert_eq!(button_name(Keyboard(keyboard::Space)), "Space");
+        assert_eq!(button_name(Keyboard(keyboard::F1)), "F1");
+        assert_eq!(key_name(keyboard::Escape), "Escape");
+    }
+
+    #[test]
+    fn display_names_of_named_keys_parse_back() {
+        for name in ["R", "Escape", "Space", "F11"].iter() {
+            assert_eq!(key_name(parse_key(name).unwrap()), *name);
+        }
+    }
+
+    #[test]
+    fn short_names_parse_back() {
+        assert_eq!(short_name(keyboard::Left), Some("Left"));
+        assert_eq!(parse_key(short_name(keyboard::Period).unwrap()), Some(keyboard::Period));
+    }
+
+    #[test]
+    fn parses_names_in_any_case() {
+        assert_eq!(parse_key("R"), Some(keyboard::R));
+        assert_eq!(parse_key("escape"), Some(keyboard::Escape));
+        assert_eq!(parse_key("F11"), Some(keyboard::F11));
+        assert_eq!(parse_key("NotAKey"), None);
+    }
+}
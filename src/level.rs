@@ -0,0 +1,276 @@
+/*
+ * Levels. Everything that makes one level different from another - how big
+ * the world is, where you start, where the goal and walls are, what there is
+ * to pick up, and what the ground looks like - lives in a JSON file in the
+ * levels directory rather than in the code. That way making a new level is
+ * a matter of writing a file, not recompiling.
+ *
+ * A level file looks like this. Boxes are [x, y, width, height], points are
+ * [x, y], and the tilemap is the name of a map file (see tilemap.rs) next to
+ * the level file. So is the cutscene, which plays before the level starts
+ * (see cutscene.rs), and so are the files the npcs talk from (see
+ * dialogue.rs). The items and doors are in inventory.rs, and the
+ * checkpoints in checkpoint.rs. walls, collectibles, enemies, power_ups
+ * (see powerup.rs), items, doors, npcs, checkpoints, tilemap and cutscene
+ * can all be left out, and so can the version, for a level from before
+ * there were versions (see schema.rs).
+ *
+ *     {
+ *         "version": 1,
+ *         "name": "First steps",
+ *         "world_width": 1280.0,
+ *         "world_height": 960.0,
+ *         "spawn": [50.0, 50.0],
+ *         "goal": [1190.0, 870.0, 40.0, 40.0],
+ *         "walls": [[300.0, 100.0, 5.0, 200.0]],
+ *         "collectibles": [[200.0, 400.0]],
+ *         "enemies": [[900.0, 600.0]],
+ *         "power_ups": [{ "effect": "speed", "at": [640.0, 480.0] }],
+ *         "items": [{ "name": "key", "color": [1.0, 0.85, 0.2, 1.0], "at": [[200.0, 800.0]] }],
+ *         "doors": [{ "bounds": [600.0, 380.0, 10.0, 180.0], "needs": "key" }],
+ *         "npcs": [{ "at": [400.0, 300.0], "dialogue": "hermit.txt" }],
+ *         "checkpoints": [[640.0, 200.0]],
+ *         "tilemap": "ground.json",
+ *         "cutscene": "intro1.json"
+ *     }
+ */
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use cutscene::{self, Step};
+use dialogue::{self, NpcSpot};
+use inventory::{DoorSpot, ItemKind};
+use powerup::Spot;
+use schema::{self, Format};
+use tilemap::Tilemap;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    pub world_width: f64,
+    pub world_height: f64,
+    pub spawn: [f64; 2],
+    pub goal: [f64; 4],
+    #[serde(default)]
+    pub walls: Vec<[f64; 4]>,
+    #[serde(default)]
+    pub collectibles: Vec<[f64; 2]>,
+    #[serde(default)]
+    pub enemies: Vec<[f64; 2]>,
+    #[serde(default)]
+    pub power_ups: Vec<Spot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<ItemKind>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub doors: Vec<DoorSpot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub npcs: Vec<NpcSpot>,
+    // Where the checkpoints' flags stand, by their middles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checkpoints: Vec<[f64; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tilemap: Option<String>,
+    // The tilemap itself, loaded by `load` once the level file has been
+    // read. It's not in the JSON, hence the skip.
+    #[serde(skip)]
+    pub ground: Tilemap,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cutscene: Option<String>,
+    // And the cutscene's steps, the same way.
+    #[serde(skip)]
+    pub cutscene_steps: Vec<Step>,
+}
+
+impl Level {
+    /*
+     * The level you get when there aren't any level files: the same one
+     * the game had before levels were a thing. A big empty field with a
+     * single wall, and the goal in the far corner.
+     */
+    pub fn fallback() -> Level {
+        Level {
+            name: "Empty field".to_string(),
+            world_width: 1280.0,
+            world_height: 960.0,
+            spawn: [50.0, 50.0],
+            goal: [1190.0, 870.0, 40.0, 40.0],
+            walls: vec![[300.0, 100.0, 5.0, 200.0]],
+            collectibles: Vec::new(),
+            enemies: Vec::new(),
+            power_ups: Vec::new(),
+            items: Vec::new(),
+            doors: Vec::new(),
+            npcs: Vec::new(),
+            checkpoints: Vec::new(),
+            tilemap: None,
+            ground: Tilemap::empty(),
+            cutscene: None,
+            cutscene_steps: Vec::new(),
+        }
+    }
+}
+
+/*
+ * Parse a level, from any version of the format (see schema.rs), and check
+ * it's playable. A world with no size can't hold anything, and a spawn
+ * point or goal outside the world would leave the player stuck against the
+ * edge, or with nowhere to go.
+ */
+pub fn parse(json: &str) -> Result<Level, String> {
+    let level: Level = schema::from_json(Format::Level, json)?;
+    if level.world_width <= 0.0 || level.world_height <= 0.0 {
+        return Err("the world has to be bigger than nothing".to_string());
+    }
+    let inside = |x: f64, y: f64| {
+        x >= 0.0 && y >= 0.0 && x <= level.world_width && y <= level.world_height
+    };
+    if !inside(level.spawn[0], level.spawn[1]) {
+        return Err("the spawn point is outside the world".to_string());
+    }
+    let g = level.goal;
+    if !inside(g[0], g[1]) || !inside(g[0] + g[2], g[1] + g[3]) {
+        return Err("the goal is outside the world".to_string());
+    }
+    Ok(level)
+}
+
+// A level as a level file would have it: the other way from parse. The
+// ground's left out, since that lives in its own file, if anywhere.
+pub fn to_json(level: &Level) -> Result<String, String> {
+    schema::to_json(Format::Level, level)
+}
+
+pub fn save(path: &Path, level: &Level) -> Result<(), String> {
+    let json = to_json(level)?;
+    File::create(path)
+        .and_then(|mut f| f.write_all(json.as_bytes()))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+// Read a level file, and the tilemap, cutscene and dialogue files it names,
+// if any. A missing or broken tilemap only costs you the ground, not the
+// whole level, a cutscene only the cutscene, and so on.
+pub fn load(path: &Path) -> Result<Level, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    let mut level = parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Some(ref name) = level.tilemap {
+        let map_path = path.parent().unwrap_or(Path::new(".")).join(name);
+        match Tilemap::load(&map_path) {
+            Ok(ground) => level.ground = ground,
+            Err(e) => warn!("{} (the level will have no ground)", e),
+        }
+    }
+    if let Some(ref name) = level.cutscene {
+        let scene_path = path.parent().unwrap_or(Path::new(".")).join(name);
+        match cutscene::load(&scene_path) {
+            Ok(steps) => level.cutscene_steps = steps,
+            Err(e) => warn!("{} (the level will start without it)", e),
+        }
+    }
+    for npc in level.npcs.iter_mut() {
+        let dialogue_path = path.parent().unwrap_or(Path::new(".")).join(&npc.dialogue);
+        match dialogue::load(&dialogue_path) {
+            Ok(pages) => npc.pages = pages,
+            Err(e) => warn!("{} (they'll have nothing to say)", e),
+        }
+    }
+    Ok(level)
+}
+
+/*
+ * Every level in `dir`, in order. Levels are the files called level
+ * something .json, sorted by name, so level1.json comes before level2.json.
+ * (Sorted as text, mind: level10.json would come before level2.json.) Broken
+ * ones are logged and skipped.
+ */
+pub fn load_all(dir: &Path) -> Vec<Level> {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                name.starts_with("level") && name.ends_with(".json")
+            })
+            .collect(),
+        Err(e) => {
+            warn!("couldn't read {}: {}", dir.display(), e);
+            Vec::new()
+        }
+    };
+    paths.sort();
+
+    let mut levels = Vec::new();
+    for path in paths.iter() {
+        match load(path) {
+            Ok(level) => {
+                info!("Loaded level \"{}\" from {}", level.name, path.display());
+                levels.push(level);
+            }
+            Err(e) => error!("{} (skipping it)", e),
+        }
+    }
+    levels
+}
+
+// What `path` has in it: every level, if it's a directory like levels/, or
+// just the one if it's a file. Nothing at all if it won't load.
+pub fn load_from(path: &Path) -> Vec<Level> {
+    if path.is_dir() {
+        return load_all(path);
+    }
+    match load(path) {
+        Ok(level) => vec![level],
+        Err(e) => {
+            error!("{}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn parses_a_level() {
+        let level = parse(r#"{
+            "name": "Test",
+            "world_width": 100.0, "world_height": 100.0,
+            "spawn": [10.0, 10.0],
+            "goal": [80.0, 80.0, 10.0, 10.0],
+            "collectibles": [[50.0, 50.0]]
+        }"#).unwrap();
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.walls.len(), 0);
+        assert_eq!(level.collectibles, vec![[50.0, 50.0]]);
+        assert_eq!(level.tilemap, None);
+    }
+
+    #[test]
+    fn rejects_unplayable_levels() {
+        let with = |spawn: &str, goal: &str| parse(&format!(r#"{{
+            "name": "Test", "world_width": 100.0, "world_height": 100.0,
+            "spawn": {}, "goal": {} }}"#, spawn, goal));
+        assert!(with("[10.0, 10.0]", "[80.0, 80.0, 10.0, 10.0]").is_ok());
+        assert!(with("[-10.0, 10.0]", "[80.0, 80.0, 10.0, 10.0]").is_err());
+        assert!(with("[10.0, 10.0]", "[95.0, 80.0, 10.0, 10.0]").is_err());
+        // And anything missing altogether.
+        assert!(parse(r#"{ "name": "Test" }"#).is_err());
+    }
+
+    #[test]
+    fn demo_levels_load() {
+        let levels = load_all(Path::new("levels"));
+        assert!(levels.len() >= 2);
+        for level in levels.iter() {
+            assert!(level.ground.rows() > 0, "{} has no ground", level.name);
+        }
+        assert!(!levels[0].cutscene_steps.is_empty());
+    }
+}
@@ -1,3 +1,258 @@
-#[test]
-fn it_works() {
-}
+/*
+ * This is a super simple example toy in Piston. It renders a square that can
+ * be controlled by the arrow keys. This is to demonstrate getting to the
+ * point where we can begin to focus on game development rather than fighting
+ * with the libraries. This heavily commented version of the code seeks to
+ * make jumping in and playing relatively easy.
+ *
+ * This file is the library: every part of the game, as a module. main.rs,
+ * the game you actually run, is only a few lines that call game::run. Having
+ * it all in a library means other programs (an editor for the levels, say,
+ * or a bot that plays) can `extern crate jumpy` and use App and the rest
+ * themselves, and means `cargo test` tests all of it in one go.
+ */
+
+/* 
+ * This is how we import external libraries in rust. Crates represent 
+ * repositories of rust code that will compiled and linked when we run
+ * `cargo build`. This set of crates gives us a nice baseline set of tools
+ * for our first forays into game dev
+ */
+// graphics deals, predictably, with drawing to the screen
+extern crate graphics;
+// piston is the core engine. It used to come in pieces, with input and the
+// event loop as crates of their own; now it's all in here. See engine.rs.
+extern crate piston;
+/*
+ * Piston attempts to modularize as much code as possible, including the
+ * rendering engine. SDL2 is a well known and widely used set of libraries
+ * for game development, typically used with C++. This crate lets us use SDL2
+ * to deliver our game. Conceptually, with the way piston has been designed,
+ * we should be able to swap this out underneath an existing game, making 
+ * porting woes a thing of the past! Of course, nothing is actually this easy
+ * in software, but we can dream.
+ * Or we can try it: glutin and GLFW can stand in for SDL2, picked with a
+ * cargo feature. #[cfg(feature = ...)] means the line only exists when that
+ * feature is switched on. See window_backend.rs.
+ */
+#[cfg(feature = "sdl2")]
+extern crate sdl2_window;
+#[cfg(feature = "glutin")]
+extern crate glutin_window;
+#[cfg(feature = "glfw")]
+extern crate glfw_window;
+// Or all of it at once, window, drawing and loop. See piston_front.rs.
+#[cfg(feature = "piston_front")]
+extern crate piston_window;
+// SDL itself supports multiple renderers; here we elect to use OpenGL.
+// This also says which version of OpenGL we use.
+extern crate opengl_graphics;
+// And this plays sounds, if we're built with them. See audio.rs.
+#[cfg(feature = "sound")]
+extern crate ears;
+// And this lets spectators watch over a WebSocket. See spectate.rs.
+#[cfg(feature = "spectate")]
+extern crate tungstenite;
+// And this runs Lua scripts, if asked. See lua.rs.
+#[cfg(feature = "lua")]
+extern crate mlua;
+// And these take screenshots and clips: OpenGL to read the window back,
+// and PNG and GIF to write it out. See capture.rs.
+extern crate gif;
+extern crate gl;
+extern crate png;
+/*
+ * serde is the go-to rust library for turning structs into text and back
+ * again. The derive crate gives us #[derive(Serialize, Deserialize)], and
+ * serde_json does the actual JSON reading and writing.
+ */
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+/*
+ * These are our own modules. `mod rng;` tells rust to go look for a file
+ * called rng.rs next to this one and treat its contents as a module named
+ * rng. Splitting things out like this keeps each file readable. They're
+ * all `pub`, so that anything using the library can get at them.
+ */
+// Logging, with levels. #[macro_use] makes its macros (info! and friends)
+// available to everything declared after it, so it has to go first.
+#[macro_use]
+pub mod log;
+/*
+ * Everything we use from piston, in one place: the input types, the event
+ * loop, and what draws on the window. The keys and buttons have been
+ * called input::keyboard::Up and so on for as long as there's been a game,
+ * so engine's copy of those stands in for the old input crate, and the
+ * rest of the game uses them from here without having to know.
+ */
+pub mod engine;
+use engine::input;
+// A small, seedable random number generator
+pub mod rng;
+// The confetti that flies off the player when expand fires
+pub mod particles;
+// Which version each file format is at, and bringing old files up to date
+pub mod schema;
+// Tunable settings, loaded from (and live-reloaded from) config.json
+pub mod config;
+// Boxes, and what happens when they bump into each other
+pub mod collision;
+// The Entity trait that everything in the world implements
+pub mod entity;
+// The players, enemies and pickups, each one a GameObject in the one list
+pub mod object;
+// Command line flags
+pub mod cli;
+// Turning key names from the config into actual keys
+pub mod keys;
+// Which arrow keys are held, and which way the player faces
+pub mod controls;
+// Pinball physics for bounce mode
+pub mod bounce;
+// What controller buttons and sticks do
+pub mod gamepad;
+// What happens at the edges of the world
+pub mod world;
+// Pictures loaded from disk
+pub mod assets;
+// Flipbook animation for the player's sprite sheet
+pub mod animation;
+// World positions and screen positions, and getting from one to the other
+pub mod units;
+// Which part of the world the window is looking at
+pub mod camera;
+// Making the view jiggle when something big happens
+pub mod shake;
+// The ground, as a grid of tiles
+pub mod tilemap;
+// Level files: where everything goes
+pub mod level;
+// Scripted scenes at the start of a level
+pub mod cutscene;
+// People to talk to, and the box what they say comes up in
+pub mod dialogue;
+// Keys and the doors they open
+pub mod inventory;
+// Flags that save your progress partway through a level
+pub mod checkpoint;
+// Holding a key to run the last few seconds backwards
+pub mod rewind;
+// Keys that press a few others, recorded or written down, and turbo keys
+pub mod macros;
+// Levels made up from the random numbers
+pub mod generate;
+// Things made of components, and the systems that run them
+pub mod ecs;
+// Things that chase you
+pub mod enemy;
+// Enemies coming in waves, more and faster each time
+pub mod waves;
+// How enemies find their way round walls
+pub mod pathfinding;
+// Things that make you faster, bigger or unbeatable for a while
+pub mod powerup;
+// Things you shoot at the things that chase you
+pub mod projectile;
+// Text in the corner saying how you're doing
+pub mod hud;
+// How text looks: font, size, color, lining up and shadows
+pub mod text;
+// The words on the screen, in whichever language
+pub mod locale;
+// A map of the whole level, in the corner
+pub mod minimap;
+// The colors everything's drawn in
+pub mod palette;
+// How long frames take to draw
+pub mod frametime;
+// Timing each part of the game as fast as it'll go, for --bench
+pub mod bench;
+// Buttons, labels, sliders and panels for the screens around the game
+pub mod ui;
+// The title screen's menu
+pub mod menu;
+// The options screen's settings
+pub mod options;
+// Saving a game in progress and picking it up again
+pub mod save;
+// Saving every so often, and resuming after
+pub mod autosave;
+// Levels and saves dropped on the window
+pub mod dropped;
+// The high-score table
+pub mod scores;
+// Achievements, and the toasts that say you've got one
+pub mod achievements;
+// Which window library we're built with. A browser doesn't have one.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod window_backend;
+// The game in a web page, instead of a window
+pub mod web;
+// Drawing, with or without a window to draw in
+pub mod renderer;
+// Key presses written down ahead of time, for --headless
+pub mod script;
+// Sound effects
+pub mod audio;
+// The streak left behind the player
+pub mod trail;
+// Getting up to speed and slowing down again
+pub mod movement;
+// Playing against someone on another computer
+pub mod net;
+// Letting people watch, from a web browser
+pub mod spectate;
+// Writing the game's state down every so often, for other programs to read
+pub mod dump;
+// And another program playing, with commands a line at a time
+pub mod bot;
+// Trying out new behaviour in Lua, without recompiling
+pub mod lua;
+// Noticing when files change
+pub mod watch;
+// Screenshots
+pub mod capture;
+// Things that happen in so many seconds
+pub mod timers;
+// Numbers that change over time, and how they ease from one to the next
+pub mod tween;
+// How the game's parts hear about what happens in it
+pub mod bus;
+/*
+ * And these used to all be in main.rs, before it got too long to read.
+ * They're the heart of the thing: the game, the player in it, what the keys
+ * do, how it all gets drawn, and the screens and the loop around it.
+ */
+// App, which is the game itself
+pub mod app;
+// The player, or two of them
+pub mod player;
+// What the keys do while playing
+pub mod handlers;
+// Drawing a frame of App
+pub mod render;
+// Fading (or wiping) from one screen to the next
+pub mod transition;
+// The slowly moving background behind the world
+pub mod background;
+// Fragment shaders of your own, drawn over the game
+pub mod shader;
+// Drawing the frame into a texture first, for bloom and the expand flash
+pub mod postprocess;
+// The debug console, and the commands you can type into it
+pub mod console;
+// A second window, for looking at and changing what's in the level
+pub mod inspector;
+// Playing with a finger: dragging to move, tapping to expand
+pub mod touch;
+// The title screen and friends, and the event loop
+pub mod game;
+// A game with no window, for the tests to play
+pub mod simulation;
+// The same game on piston_window, to show it doesn't mind. Only with
+// --features piston_front.
+#[cfg(feature = "piston_front")]
+pub mod piston_front;
@@ -0,0 +1,153 @@
+/*
+ * The words on the screen, in whichever language `language` in config.json
+ * says (or Left and Right on the options screen). Nothing's written into
+ * the code in English any more; it asks for a string by its key, like
+ * "hud.score", and the Locale looks it up in a table read from
+ * locales/<language>.json:
+ *
+ *     { "hud.score": "Score: {0}", "menu.start": "Start", ... }
+ *
+ * {0}, {1} and so on are where the numbers and names go, in the order
+ * they're handed over (see Locale::text). Languages don't all put things
+ * in the same order, so a table can move them about, or leave one out.
+ *
+ * English is the one everything's written in first, so it's built into
+ * the game from locales/en.json, and it's what a table that's missing a
+ * string falls back on; a key that isn't even in English comes out as
+ * itself, which is ugly enough that someone notices. There's French too,
+ * in locales/fr.json, and another language is another file, and another
+ * name in LANGUAGES.
+ *
+ * The list of keys on the options screen is still in English, since it's
+ * printed to the terminal for the help as well, and so are the window's
+ * title, the log and the console: they're for whoever's working on the
+ * game rather than whoever's playing it.
+ */
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+// Every language there's a table for, in the order Left and Right go
+// through them.
+pub const LANGUAGES: &'static [&'static str] = &["en", "fr"];
+
+const ENGLISH: &'static str = include_str!("../locales/en.json");
+
+pub type Strings = BTreeMap<String, String>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Locale {
+    pub language: String,
+    strings: Strings,
+    english: Strings,
+}
+
+impl Locale {
+    // The built-in English.
+    pub fn english() -> Locale {
+        let english = parse(ENGLISH).expect("locales/en.json should be a table of strings");
+        Locale { language: "en".to_string(), strings: english.clone(), english: english }
+    }
+
+    // `language` from `strings`, with English for anything it hasn't got.
+    pub fn new(language: &str, strings: Strings) -> Locale {
+        Locale { language: language.to_string(), strings: strings, ..Locale::english() }
+    }
+
+    // The string for `key`.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).or_else(|| self.english.get(key)).map_or(key, |s| &s[..])
+    }
+
+    // The string for `key`, with `args` where its {0}, {1} and so on are.
+    pub fn text(&self, key: &str, args: &[&Display]) -> String {
+        let mut text = self.get(key).to_string();
+        for (i, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+        }
+        text
+    }
+
+    // The keys English has that this hasn't, for a translator to fill in.
+    pub fn missing(&self) -> Vec<&str> {
+        self.english.keys().filter(|k| !self.strings.contains_key(*k)).map(|k| &k[..])
+            .collect()
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale::english()
+    }
+}
+
+pub fn parse(json: &str) -> Result<Strings, String> {
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+pub fn default_dir() -> PathBuf {
+    PathBuf::from("locales")
+}
+
+pub fn path(dir: &Path, language: &str) -> PathBuf {
+    dir.join(format!("{}.json", language))
+}
+
+// The language after `language`, going back round to the first after the
+// last, or before it. One we don't know goes to the first.
+pub fn step(language: &str, forward: bool) -> &'static str {
+    let n = LANGUAGES.len();
+    match LANGUAGES.iter().position(|&l| l == language) {
+        Some(i) if forward => LANGUAGES[(i + 1) % n],
+        Some(i) => LANGUAGES[(i + n - 1) % n],
+        None => LANGUAGES[0],
+    }
+}
+
+// `language`'s table from `dir`. English doesn't need one, since it's
+// built in, but one that's there wins, so it can be changed without a
+// rebuild.
+pub fn load(dir: &Path, language: &str) -> Result<Locale, String> {
+    let path = path(dir, language);
+    if language == "en" && !path.exists() {
+        return Ok(Locale::english());
+    }
+    let mut contents = String::new();
+    File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    let strings = parse(&contents)
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))?;
+    Ok(Locale::new(language, strings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_filled_in_and_fall_back_to_english() {
+        let mut strings = Strings::new();
+        strings.insert("hud.position".to_string(), "{1} down, {0} across".to_string());
+        let locale = Locale::new("xx", strings);
+        assert_eq!(locale.text("hud.position", &[&3, &"4"]), "4 down, 3 across");
+        assert_eq!(locale.text("hud.score", &[&12]), "Score: 12");
+        assert_eq!(locale.get("no.such.key"), "no.such.key");
+        assert!(locale.missing().contains(&"hud.score"));
+    }
+
+    #[test]
+    fn every_language_has_every_string() {
+        for &language in LANGUAGES.iter() {
+            let locale = load(&default_dir(), language).unwrap();
+            assert_eq!(locale.missing(), Vec::<&str>::new(), "{}", language);
+        }
+        assert_eq!(step("fr", true), "en");
+        assert_eq!(step("en", false), "fr");
+        assert!(load(&default_dir(), "xx").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+/*
+ * A small logger, so debugging doesn't have to mean sprinkling println!s
+ * about and then hunting them all down again afterwards. Every line says
+ * when it happened (in game seconds, since that's the clock everything else
+ * runs on) and how important it is:
+ *
+ *     [   12.345 INFO ] Level complete! Time: 11.20s, score: 0
+ *
+ * The levels go from "something broke" down to "here's every little thing",
+ * and you pick how far down you want to see with --log-level. Anything
+ * below that gets thrown away before it's even formatted, which matters
+ * because some of these calls happen every single update.
+ *
+ * Some messages are about one part of the game in particular - the keys,
+ * the drawing, the collisions, the network - and say so. Those are the
+ * ones you often want to see more of, without drowning in everything else
+ * at that level, so each part can have a level of its own:
+ *
+ *     --log-level info,input=debug,net=trace
+ *
+ * and lines from one of them say which:
+ *
+ *     [    3.210 DEBUG] input: Pressed Space
+ *
+ * The macros (error!, warn!, info!, debug! and trace!) work just like
+ * println!, with `target: Input,` (say) in front for a message about one
+ * part. They're defined in here, and lib.rs pulls this module in with
+ * #[macro_use] before any of the others so that all of them can use them.
+ */
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+// Every level, most important first.
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    // For --log-level. Case doesn't matter, so "debug" and "DEBUG" both work.
+    pub fn parse(name: &str) -> Option<Level> {
+        LEVELS.iter().cloned().find(|l| l.name().eq_ignore_ascii_case(name))
+    }
+
+    /*
+     * Whether a message at `message` level gets through when we're set to
+     * this one. The enum is declared most important first, so "lower" means
+     * "more important": at Info, Error/Warn/Info get through, and Debug and
+     * Trace don't.
+     */
+    pub fn allows(&self, message: Level) -> bool {
+        message <= *self
+    }
+}
+
+// Which part of the game a message is about. Game is everything that
+// doesn't say.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Game,
+    Input,
+    Render,
+    Collision,
+    Net,
+}
+
+const TARGETS: [Target; 5] =
+    [Target::Game, Target::Input, Target::Render, Target::Collision, Target::Net];
+
+impl Target {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Target::Game => "game",
+            Target::Input => "input",
+            Target::Render => "render",
+            Target::Collision => "collision",
+            Target::Net => "net",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Target> {
+        TARGETS.iter().cloned().find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/*
+ * What --log-level says: a level on its own is for everything, and
+ * target=level is for just that part of the game. They're separated by
+ * commas, and can come in any order; without a plain level, it's info.
+ */
+pub fn parse_levels(spec: &str) -> Option<(Level, Vec<(Target, Level)>)> {
+    let mut level = Level::Info;
+    let mut targets = Vec::new();
+    for part in spec.split(',') {
+        let mut halves = part.splitn(2, '=');
+        match (halves.next(), halves.next()) {
+            (Some(name), None) => level = Level::parse(name)?,
+            (Some(target), Some(name)) => targets.push((Target::parse(target)?,
+                                                        Level::parse(name)?)),
+            _ => return None,
+        }
+    }
+    Some((level, targets))
+}
+
+/*
+ * Write one log line to `out`. This is the one place the line format lives,
+ * and it writes straight through rather than building a String first, so a
+ * message costs no allocations on its way out.
+ */
+pub fn write_line<W: Write>(out: &mut W,
+                            time: f64,
+                            level: Level,
+                            target: Target,
+                            args: fmt::Arguments) -> io::Result<()> {
+    match target {
+        Target::Game => writeln!(out, "[{:9.3} {:<5}] {}", time, level.name(), args),
+        _ => writeln!(out, "[{:9.3} {:<5}] {}: {}", time, level.name(), target.name(), args),
+    }
+}
+
+struct Logger {
+    level: Level,
+    // The parts of the game with a level of their own.
+    targets: Vec<(Target, Level)>,
+    time: f64,
+    file: Option<File>,
+}
+
+impl Logger {
+    fn level_for(&self, target: Target) -> Level {
+        self.targets.iter()
+            .find(|&&(t, _)| t == target)
+            .map_or(self.level, |&(_, level)| level)
+    }
+}
+
+/*
+ * The logger lives in a thread local rather than being handed about, so that
+ * anything anywhere can log without every function growing an extra
+ * argument. We only have the one thread, so there's only ever one of it.
+ */
+thread_local!(static LOGGER: RefCell<Logger> = RefCell::new(Logger {
+    level: Level::Info,
+    targets: Vec::new(),
+    time: 0.0,
+    file: None,
+}));
+
+// Set the level, the levels for any parts of the game that want their own,
+// and optionally a file that gets a copy of everything.
+pub fn init(level: Level, targets: &[(Target, Level)], path: Option<&Path>)
+            -> Result<(), String> {
+    let file = match path {
+        Some(path) => Some(File::create(path)
+            .map_err(|e| format!("Couldn't open log file {}: {}", path.display(), e))?),
+        None => None,
+    };
+    LOGGER.with(|l| {
+        let mut logger = l.borrow_mut();
+        logger.level = level;
+        logger.targets = targets.to_vec();
+        logger.file = file;
+    });
+    Ok(())
+}
+
+// The game clock calls this every update so log lines know what time it is.
+pub fn set_time(time: f64) {
+    LOGGER.with(|l| l.borrow_mut().time = time);
+}
+
+// Whether a message at this level, about `target`, would be shown. The
+// macros check this before doing anything else.
+pub fn enabled(level: Level, target: Target) -> bool {
+    LOGGER.with(|l| l.borrow().level_for(target).allows(level))
+}
+
+/*
+ * Where the macros end up. Everything goes to stderr, so it doesn't get
+ * mixed up with the help text and such on stdout, and to the log file if
+ * there is one. If writing fails there's not much we can do about it -
+ * we can hardly log the error - so it's ignored.
+ */
+pub fn log(level: Level, target: Target, args: fmt::Arguments) {
+    LOGGER.with(|l| {
+        let mut logger = l.borrow_mut();
+        let time = logger.time;
+        let stderr = io::stderr();
+        let _ = write_line(&mut stderr.lock(), time, level, target, args);
+        if let Some(ref mut file) = logger.file {
+            let _ = write_line(file, time, level, target, args);
+        }
+    });
+}
+
+/*
+ * Some things are worth logging but happen every update: where the player
+ * is, say, or that they're leaning on a wall. At 120 updates a second that's
+ * a lot of lines nobody will read. A RateLimit lets one through, then says no
+ * until `interval` seconds have gone by.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub interval: f64,
+    last: Option<f64>,
+}
+
+impl RateLimit {
+    pub fn new(interval: f64) -> RateLimit {
+        RateLimit { interval: interval, last: None }
+    }
+
+    // Whether something may be logged at time `now`. Saying yes counts as
+    // using up the turn, so ask only when you're going to log.
+    pub fn ready(&mut self, now: f64) -> bool {
+        match self.last {
+            Some(last) if now - last < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/*
+ * The macros themselves. The checking happens *outside* format_args!, so a
+ * filtered-out message never gets its arguments evaluated, let alone
+ * formatted - `trace!("{}", some_expensive_string())` costs nothing at all
+ * unless we're tracing. A message that starts with `target: Something,` is
+ * about Target::Something; the rest are about the game in general. (The
+ * first rule has to come first: a format string never looks like
+ * `target:`, so anything that isn't one falls through to the second.)
+ */
+macro_rules! log_at {
+    ($level:expr, target: $target:ident, $($arg:tt)+) => {
+        if $crate::log::enabled($level, $crate::log::Target::$target) {
+            $crate::log::log($level, $crate::log::Target::$target, format_args!($($arg)+));
+        }
+    };
+    ($level:expr, $($arg:tt)+) => {
+        log_at!($level, target: Game, $($arg)+)
+    };
+}
+
+macro_rules! error { ($($arg:tt)+) => { log_at!($crate::log::Level::Error, $($arg)+) } }
+macro_rules! warn { ($($arg:tt)+) => { log_at!($crate::log::Level::Warn, $($arg)+) } }
+macro_rules! info { ($($arg:tt)+) => { log_at!($crate::log::Level::Info, $($arg)+) } }
+macro_rules! debug { ($($arg:tt)+) => { log_at!($crate::log::Level::Debug, $($arg)+) } }
+macro_rules! trace { ($($arg:tt)+) => { log_at!($crate::log::Level::Trace, $($arg)+) } }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn line(time: f64, level: Level, args: fmt::Arguments) -> String {
+        let mut out = Vec::new();
+        write_line(&mut out, time, level, Target::Game, args).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lines_have_time_and_level() {
+        assert_eq!(line(12.3456, Level::Info, format_args!("hello {}", 42)),
+                   "[   12.346 INFO ] hello 42\n");
+        assert_eq!(line(0.0, Level::Error, format_args!("oops")),
+                   "[    0.000 ERROR] oops\n");
+        assert_eq!(line(1.5, Level::Trace, format_args!("x={:.1}", 3.0)),
+                   "[    1.500 TRACE] x=3.0\n");
+    }
+
+    #[test]
+    fn lines_about_one_part_of_the_game_say_so() {
+        let mut out = Vec::new();
+        write_line(&mut out, 3.21, Level::Debug, Target::Input, format_args!("Pressed Space"))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[    3.210 DEBUG] input: Pressed Space\n");
+    }
+
+    #[test]
+    fn levels_filter_less_important_messages() {
+        assert!(Level::Info.allows(Level::Error));
+        assert!(Level::Info.allows(Level::Info));
+        assert!(!Level::Info.allows(Level::Debug));
+        assert!(!Level::Error.allows(Level::Warn));
+        for &level in LEVELS.iter() {
+            assert!(Level::Trace.allows(level));
+        }
+    }
+
+    #[test]
+    fn parses_level_names() {
+        assert_eq!(Level::parse("debug"), Some(Level::Debug));
+        assert_eq!(Level::parse("WARN"), Some(Level::Warn));
+        assert_eq!(Level::parse("loud"), None);
+        for &level in LEVELS.iter() {
+            assert_eq!(Level::parse(level.name()), Some(level));
+        }
+    }
+
+    #[test]
+    fn parses_levels_for_parts_of_the_game() {
+        assert_eq!(parse_levels("debug"), Some((Level::Debug, Vec::new())));
+        assert_eq!(parse_levels("net=trace,warn,input=debug"),
+                   Some((Level::Warn, vec![(Target::Net, Level::Trace),
+                                           (Target::Input, Level::Debug)])));
+        assert_eq!(parse_levels("collision=debug"),
+                   Some((Level::Info, vec![(Target::Collision, Level::Debug)])));
+        assert_eq!(parse_levels("sound=debug"), None);
+        assert_eq!(parse_levels("net=loud"), None);
+        assert_eq!(parse_levels(""), None);
+    }
+
+    #[test]
+    fn filtered_messages_are_not_evaluated() {
+        init(Level::Warn, &[(Target::Net, Level::Trace)], None).unwrap();
+        let evaluated = Cell::new(0);
+        let expensive = || { evaluated.set(evaluated.get() + 1); 1 };
+        debug!("{}", expensive());
+        debug!(target: Input, "{}", expensive());
+        assert_eq!(evaluated.get(), 0);
+        // But net's been turned all the way up.
+        assert!(enabled(Level::Trace, Target::Net));
+        assert!(!enabled(Level::Info, Target::Render));
+    }
+
+    #[test]
+    fn rate_limit_lets_one_through_per_interval() {
+        let mut limit = RateLimit::new(1.0);
+        let allowed: Vec<f64> = (0..30)
+            .map(|i| i as f64 * 0.1)
+            .filter(|&t| limit.ready(t))
+            .collect();
+        assert_eq!(allowed.len(), 3);
+        assert_eq!(allowed[0], 0.0);
+    }
+}
@@ -0,0 +1,319 @@
+/*
+ * Trying out new ideas without recompiling. With --lua some_file.lua the
+ * game runs that file when it starts, and then calls two functions from it,
+ * if it defines them:
+ *
+ *     on_update(entity, dt)  every tick, once for each player and once for
+ *                            each enemy, dt seconds after the last
+ *     on_key(action)         whenever a key goes down, with the key's name
+ *                            ("Space", "K", "F5") as the action
+ *
+ * entity is a table with kind ("player" or "enemy"), number (which player,
+ * or which enemy, counting from 1), x, y and expand in it. A script moves
+ * things about by changing those, and the game picks the changes up once
+ * on_update returns. For the look of the thing there are functions too:
+ *
+ *     position(entity)           x, y
+ *     set_position(entity, x, y)
+ *     expand(entity)             how puffed up the player is (enemies are 0)
+ *     set_expand(entity, e)      which does nothing to an enemy
+ *     spawn(kind, x, y)          make an "enemy" or a "pickup" at x, y
+ *
+ * example.lua, next to the README, has a go at all of them. Walls still
+ * stop a player a script has moved, and what a script spawns turns up at
+ * the end of the tick.
+ *
+ * The Lua itself comes from the mlua crate, behind the "lua" cargo feature
+ * (cargo run --features lua), which builds a whole Lua from source along
+ * with the game. Built without it, --lua is still understood, but all it
+ * gets you is a warning.
+ *
+ * A script that goes wrong gets its error logged, and then that function
+ * isn't called again, so one typo doesn't fill the log 120 times a second.
+ * Scripts don't get written into replays, either: a replay of a game with
+ * a script only plays back right with the same script running.
+ */
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[cfg(feature = "lua")]
+use mlua;
+
+// What a script can be handed, or asked to make.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thing {
+    Player,
+    Enemy,
+    Pickup,
+}
+
+impl Thing {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Thing::Player => "player",
+            Thing::Enemy => "enemy",
+            Thing::Pickup => "pickup",
+        }
+    }
+}
+
+// One thing in the world, as on_update sees it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scripted {
+    pub thing: Thing,
+    pub number: usize,
+    pub x: f64,
+    pub y: f64,
+    pub expand: f64,
+}
+
+// Something a script has asked spawn for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub thing: Thing,
+    pub x: f64,
+    pub y: f64,
+}
+
+/*
+ * The Lua that runs the script. Without the lua feature there's no such
+ * thing, the same trick as audio.rs's Clip: an enum with nothing in it,
+ * which can be named but never made.
+ */
+#[cfg(feature = "lua")]
+type Engine = mlua::Lua;
+#[cfg(not(feature = "lua"))]
+enum Engine {}
+
+pub struct Lua {
+    engine: Engine,
+    path: PathBuf,
+    // What spawn has been asked for since App last looked. spawn itself
+    // lives inside the Lua, so this is shared with it.
+    spawned: Rc<RefCell<Vec<Spawn>>>,
+    // The callbacks that have gone wrong, and won't be called again.
+    broken: Vec<&'static str>,
+}
+
+impl Lua {
+    // Read the script at `path` and run it, which is when it defines its
+    // functions.
+    pub fn load(path: &Path) -> Result<Lua, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let spawned = Rc::new(RefCell::new(Vec::new()));
+        let engine = start(&source, path, spawned.clone())?;
+        info!("Running {}", path.display());
+        Ok(Lua { engine: engine, path: path.to_path_buf(), spawned: spawned, broken: Vec::new() })
+    }
+
+    // Call on_update for `entity`, and keep whatever it did to it.
+    pub fn update(&mut self, entity: &mut Scripted, dt: f64) {
+        if self.broken.contains(&"on_update") { return }
+        if let Err(e) = call_update(&self.engine, entity, dt) {
+            self.give_up("on_update", e);
+        }
+    }
+
+    // Call on_key, for a key called `action`.
+    pub fn key(&mut self, action: &str) {
+        if self.broken.contains(&"on_key") { return }
+        if let Err(e) = call_key(&self.engine, action) {
+            self.give_up("on_key", e);
+        }
+    }
+
+    // Everything spawn's been asked for since last time, oldest first.
+    pub fn spawned(&mut self) -> Vec<Spawn> {
+        self.spawned.borrow_mut().drain(..).collect()
+    }
+
+    fn give_up(&mut self, callback: &'static str, e: String) {
+        warn!("{} in {} went wrong, so it's off for now: {}", callback, self.path.display(), e);
+        self.broken.push(callback);
+    }
+}
+
+/*
+ * Make a Lua, give it our functions, and run the script in it. The
+ * functions only ever see the entity table they're given, which is what
+ * keeps App out of the Lua: it hands over a table, and reads it back.
+ */
+#[cfg(feature = "lua")]
+fn start(source: &str, path: &Path, spawned: Rc<RefCell<Vec<Spawn>>>) -> Result<Engine, String> {
+    let lua = mlua::Lua::new();
+    api(&lua, spawned).map_err(|e| e.to_string())?;
+    lua.load(source).set_name(path.display().to_string()).exec()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(lua)
+}
+
+#[cfg(feature = "lua")]
+fn api(lua: &mlua::Lua, spawned: Rc<RefCell<Vec<Spawn>>>) -> mlua::Result<()> {
+    let globals = lua.globals();
+    globals.set("position", lua.create_function(|_, e: mlua::Table| {
+        Ok((e.get::<_, f64>("x")?, e.get::<_, f64>("y")?))
+    })?)?;
+    globals.set("set_position", lua.create_function(|_, (e, x, y): (mlua::Table, f64, f64)| {
+        e.set("x", x)?;
+        e.set("y", y)
+    })?)?;
+    globals.set("expand", lua.create_function(|_, e: mlua::Table| {
+        e.get::<_, f64>("expand")
+    })?)?;
+    globals.set("set_expand", lua.create_function(|_, (e, expand): (mlua::Table, f64)| {
+        e.set("expand", expand.max(0.0))
+    })?)?;
+    globals.set("spawn", lua.create_function(move |_, (kind, x, y): (String, f64, f64)| {
+        let thing = match &kind[..] {
+            "enemy" => Thing::Enemy,
+            "pickup" => Thing::Pickup,
+            _ => return Err(mlua::Error::RuntimeError(format!("can't spawn a {}", kind))),
+        };
+        spawned.borrow_mut().push(Spawn { thing: thing, x: x, y: y });
+        Ok(())
+    })?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "lua"))]
+fn start(_: &str, path: &Path, _: Rc<RefCell<Vec<Spawn>>>) -> Result<Engine, String> {
+    Err(format!("can't run {}: this game was built without the lua feature", path.display()))
+}
+
+// A script that doesn't have on_update (or on_key) is fine; it just
+// doesn't get called.
+#[cfg(feature = "lua")]
+fn call_update(lua: &Engine, entity: &mut Scripted, dt: f64) -> Result<(), String> {
+    let mut call = || -> mlua::Result<()> {
+        let on_update = match lua.globals().get::<_, Option<mlua::Function>>("on_update")? {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        let table = lua.create_table()?;
+        table.set("kind", entity.thing.name())?;
+        table.set("number", entity.number)?;
+        table.set("x", entity.x)?;
+        table.set("y", entity.y)?;
+        table.set("expand", entity.expand)?;
+        on_update.call::<_, ()>((table.clone(), dt))?;
+        entity.x = table.get("x")?;
+        entity.y = table.get("y")?;
+        entity.expand = table.get("expand")?;
+        Ok(())
+    };
+    call().map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "lua")]
+fn call_key(lua: &Engine, action: &str) -> Result<(), String> {
+    let call = || -> mlua::Result<()> {
+        match lua.globals().get::<_, Option<mlua::Function>>("on_key")? {
+            Some(on_key) => on_key.call::<_, ()>(action),
+            None => Ok(()),
+        }
+    };
+    call().map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "lua"))]
+fn call_update(lua: &Engine, _: &mut Scripted, _: f64) -> Result<(), String> {
+    match *lua {}
+}
+
+#[cfg(not(feature = "lua"))]
+fn call_key(lua: &Engine, _: &str) -> Result<(), String> {
+    match *lua {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    // Write `source` to a file of its own and load it.
+    fn script(name: &str, source: &str) -> Result<Lua, String> {
+        let path = env::temp_dir().join(format!("jumpy-{}-{}.lua", name, ::std::process::id()));
+        fs::write(&path, source).unwrap();
+        let lua = Lua::load(&path);
+        let _ = fs::remove_file(&path);
+        lua
+    }
+
+    fn player() -> Scripted {
+        Scripted { thing: Thing::Player, number: 1, x: 50.0, y: 50.0, expand: 0.0 }
+    }
+
+    #[test]
+    fn missing_scripts_say_so() {
+        assert!(Lua::load(Path::new("no-such-script.lua")).is_err());
+    }
+
+    #[cfg(not(feature = "lua"))]
+    #[test]
+    fn without_the_feature_scripts_do_not_run() {
+        let e = script("featureless", "").err().unwrap();
+        assert!(e.contains("lua feature"));
+    }
+
+    #[cfg(feature = "lua")]
+    #[test]
+    fn on_update_moves_things() {
+        let mut lua = script("update", "
+            function on_update(entity, dt)
+                local x, y = position(entity)
+                set_position(entity, x + 100 * dt, y)
+                if entity.kind == 'player' then set_expand(entity, 5) end
+            end").unwrap();
+        let mut p = player();
+        lua.update(&mut p, 0.5);
+        assert_eq!((p.x, p.y, p.expand), (100.0, 50.0, 5.0));
+        let mut e = Scripted { thing: Thing::Enemy, ..player() };
+        lua.update(&mut e, 0.5);
+        assert_eq!((e.x, e.expand), (100.0, 0.0));
+    }
+
+    #[cfg(feature = "lua")]
+    #[test]
+    fn keys_can_spawn_things() {
+        let mut lua = script("keys", "
+            function on_key(action)
+                if action == 'K' then spawn('enemy', 10, 20) end
+                if action == 'P' then spawn('pickup', 30, 40) end
+            end").unwrap();
+        lua.key("K");
+        lua.key("Space");
+        lua.key("P");
+        assert_eq!(lua.spawned(), vec![Spawn { thing: Thing::Enemy, x: 10.0, y: 20.0 },
+                                       Spawn { thing: Thing::Pickup, x: 30.0, y: 40.0 }]);
+        assert!(lua.spawned().is_empty());
+    }
+
+    #[cfg(feature = "lua")]
+    #[test]
+    fn scripts_that_go_wrong_stop_being_called() {
+        let mut lua = script("broken", "
+            function on_update(entity, dt)
+                spawn('dragon', 0, 0)
+            end").unwrap();
+        let mut p = player();
+        lua.update(&mut p, 0.1);
+        lua.update(&mut p, 0.1);
+        assert_eq!(lua.broken, vec!["on_update"]);
+        assert_eq!(p, player());
+        // And a script with nothing to call is no trouble at all.
+        let mut quiet = script("quiet", "x = 1").unwrap();
+        quiet.update(&mut p, 0.1);
+        quiet.key("Space");
+        assert!(quiet.broken.is_empty());
+    }
+
+    #[cfg(feature = "lua")]
+    #[test]
+    fn scripts_that_do_not_run_do_not_load() {
+        assert!(script("syntax", "function on_update(").is_err());
+    }
+}
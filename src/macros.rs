@@ -0,0 +1,322 @@
+/*
+ * Macros: one key that presses a few others for you. Put them in
+ * config.json with the rest of the keys, each the name of a key and the
+ * lines of a script (see script.rs) to play when it's pressed, timed from
+ * the moment it is:
+ *
+ *     "macros": {
+ *         "V": ["0 press LShift", "0 release LShift",
+ *               "0.15 press Space", "0.15 release Space"]
+ *     }
+ *
+ * That's a dash, and then an expand at the end of it. The keys go in
+ * through handleKey and handleRelease, the same as if you'd pressed them
+ * yourself, a tick at a time, so a macro can do anything you can and
+ * nothing you can't. Holding the macro's key doesn't do anything more;
+ * it's the steps that say how long things are held for.
+ *
+ * Or record one instead of writing it: press C (macro_key), play the keys
+ * you want, press C again, and then the key to put it on. It's played
+ * from then on, and written into config.json, so it's there next time.
+ * C twice, or Escape instead of a key, forgets about it. Anything still
+ * held when you stop gets let go of at the end, so a macro can't leave a
+ * key held down.
+ *
+ * Turbo keys are the other sort of shortcut: hold one and it goes on
+ * pressing itself, turbo_per_second times a second, for Space or F
+ * mostly.
+ *
+ *     "turbo_keys": ["F"], "turbo_per_second": 10.0
+ *
+ * A replay has the keys a macro pressed in it already, as ordinary keys,
+ * so none of this happens while one's playing, or it would all happen
+ * twice.
+ */
+use std::path::Path;
+
+use input::keyboard::{self, Key};
+use input::Keyboard;
+
+use app::{App, SIM_DT};
+use config::{self, Config};
+use handlers::{handleKey, handleRelease};
+use keys;
+use locale::Locale;
+use script::{Action, Script, Step};
+
+// A macro that's going: the tick it started on, and its steps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Playing {
+    pub started: u64,
+    pub script: Script,
+}
+
+// Recording a macro: the keys so far, and then, once macro_key's been
+// pressed again, waiting for the key to put them on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Capture {
+    Recording { started: u64, steps: Vec<Step> },
+    Binding(Vec<Step>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Macros {
+    pub playing: Vec<Playing>,
+    pub capture: Option<Capture>,
+    // The turbo keys held down, and the tick each goes again on.
+    pub turbo: Vec<(Key, u64)>,
+    // Whether the keys going through handleKey and handleRelease right now
+    // are a macro's, which aren't to set off any more macros.
+    pub expanding: bool,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros::default()
+    }
+}
+
+// A macro's lines, as steps. It's a script, only without a seed, which
+// wouldn't mean anything here.
+pub fn parse(lines: &[String]) -> Result<Vec<Step>, String> {
+    let script = Script::parse(&lines.join("\n"))?;
+    if script.seed.is_some() {
+        return Err("a macro can't have a seed".to_string());
+    }
+    Ok(script.steps().to_vec())
+}
+
+// And back, for config.json.
+pub fn to_lines(steps: &[Step]) -> Vec<String> {
+    Script::from_steps(steps.to_vec()).to_text("").lines().map(|l| l.to_string()).collect()
+}
+
+// The macro on `key`, if there's one (that parses; config::warnings has
+// told you about any that don't).
+pub fn bound(config: &Config, key: Key) -> Option<Vec<Step>> {
+    config.macros.iter()
+        .find(|&(name, _)| keys::is_key(key, name))
+        .and_then(|(_, lines)| parse(lines).ok())
+}
+
+fn is_turbo(config: &Config, key: Key) -> bool {
+    config.turbo_per_second > 0.0 && config.turbo_keys.iter().any(|name| keys::is_key(key, name))
+}
+
+// How many ticks apart a turbo key's presses are. Never less than one.
+pub fn turbo_ticks(config: &Config) -> u64 {
+    (1.0 / (config.turbo_per_second * SIM_DT)).round().max(1.0) as u64
+}
+
+// Whether to leave it to the rest of the game: not while a macro's own
+// keys are going in, nor in a replay, which has those keys in already.
+fn listening(app: &App) -> bool {
+    !app.macros.expanding && !app.replaying()
+}
+
+/*
+ * A key going down, before handleKey does anything with it. Whether it's
+ * been dealt with here, which it has if it's macro_key, the key to put a
+ * new macro on, or a macro's key. A turbo key starts going again and
+ * again, but the first press is still the game's.
+ */
+pub fn key_down(app: &mut App, key: Key) -> bool {
+    if !listening(app) { return false }
+    if keys::is_key(key, &app.config.macro_key) {
+        capture(app);
+        return true;
+    }
+    if let Some(Capture::Binding(_)) = app.macros.capture {
+        bind(app, key);
+        return true;
+    }
+    if let Some(steps) = bound(&app.config, key) {
+        debug!(target: Input, "Playing the macro on {}", keys::key_name(key));
+        app.macros.playing.push(Playing { started: app.ticks, script: Script::from_steps(steps) });
+        // Whatever it does straight away, it does straight away.
+        run(app);
+        return true;
+    }
+    if is_turbo(&app.config, key) {
+        let next = app.ticks + turbo_ticks(&app.config);
+        app.macros.turbo.retain(|&(k, _)| k != key);
+        app.macros.turbo.push((key, next));
+    }
+    false
+}
+
+// A key coming up. The macros' keys' releases are nobody's business, and
+// a turbo key stops.
+pub fn key_up(app: &mut App, key: Key) -> bool {
+    if !listening(app) { return false }
+    app.macros.turbo.retain(|&(k, _)| k != key);
+    keys::is_key(key, &app.config.macro_key) || bound(&app.config, key).is_some()
+}
+
+// Every key the game hears, for the macro being recorded, if there is one.
+pub fn heard(app: &mut App, key: Key, action: Action) {
+    if keys::is_key(key, &app.config.macro_key) { return }
+    let ticks = app.ticks;
+    if let Some(Capture::Recording { started, ref mut steps }) = app.macros.capture {
+        steps.push(Step { at: (ticks - started) as f64 * SIM_DT, action: action, key: key });
+    }
+}
+
+// macro_key: start recording, or stop and ask for a key, or think better
+// of it.
+fn capture(app: &mut App) {
+    let key = app.config.macro_key.clone();
+    app.macros.capture = match app.macros.capture.take() {
+        None => {
+            info!("Recording a macro: play it, then press {} again", key);
+            Some(Capture::Recording { started: app.ticks, steps: Vec::new() })
+        }
+        Some(Capture::Recording { steps, .. }) => {
+            if steps.is_empty() {
+                info!("No macro: nothing was pressed");
+                None
+            } else {
+                info!("Now press the key to put the macro on, or {} to forget it", key);
+                Some(Capture::Binding(let_go(steps)))
+            }
+        }
+        Some(Capture::Binding(_)) => {
+            info!("Forgot about the macro");
+            None
+        }
+    };
+}
+
+/*
+ * The steps, with a release at the end for anything pressed and not let go
+ * of. And the other way round: a key already held when the recording
+ * started being let go of is left out.
+ */
+pub fn let_go(steps: Vec<Step>) -> Vec<Step> {
+    let end = steps.last().map_or(0.0, |s| s.at);
+    let mut held: Vec<Key> = Vec::new();
+    let mut kept = Vec::new();
+    for step in steps {
+        match step.action {
+            Action::Press => {
+                if !held.contains(&step.key) { held.push(step.key) }
+            }
+            Action::Release => {
+                if !held.contains(&step.key) { continue }
+                held.retain(|&k| k != step.key);
+            }
+        }
+        kept.push(step);
+    }
+    for key in held {
+        kept.push(Step { at: end, action: Action::Release, key: key });
+    }
+    kept
+}
+
+// The key for the macro that's been recorded: it's played from now on,
+// and written into config.json as well, if it'll go.
+fn bind(app: &mut App, key: Key) {
+    let steps = match app.macros.capture.take() {
+        Some(Capture::Binding(steps)) => steps,
+        other => {
+            app.macros.capture = other;
+            return;
+        }
+    };
+    let name = match keys::short_name(key) {
+        Some(name) if key != keyboard::Escape => name,
+        _ => {
+            info!("Forgot about the macro");
+            return;
+        }
+    };
+    let lines = to_lines(&steps);
+    info!("{} plays the macro now ({} steps)", keys::key_name(key), steps.len());
+    app.config.macros.insert(name.to_string(), lines.clone());
+    if let Err(e) = save(&app.settings.config_path, name, lines) {
+        warn!("Couldn't keep the macro for next time: {}", e);
+    }
+}
+
+/*
+ * Put one macro into the config file at `path`, leaving the rest of it as
+ * it was, the way the options screen does (see options::save_choices). No
+ * file, or one that won't read, and it's left alone.
+ */
+pub fn save(path: &Path, name: &str, lines: Vec<String>) -> Result<(), String> {
+    let mut config = config::load(path)?;
+    config.macros.insert(name.to_string(), lines);
+    config::save(path, &config)
+}
+
+// Once a tick, before it: the steps of the macros that are due, and the
+// turbo keys that are, in through the same door as the keyboard.
+pub fn run(app: &mut App) {
+    if app.replaying() { return }
+    let ticks = app.ticks;
+    let mut due = Vec::new();
+    for playing in app.macros.playing.iter_mut() {
+        due.extend(playing.script.due((ticks - playing.started) as f64 * SIM_DT));
+    }
+    app.macros.playing.retain(|p| !p.script.finished());
+    let every = turbo_ticks(&app.config);
+    for &mut (key, ref mut next) in app.macros.turbo.iter_mut() {
+        if ticks < *next { continue }
+        *next = ticks + every;
+        due.push(Step { at: 0.0, action: Action::Release, key: key });
+        due.push(Step { at: 0.0, action: Action::Press, key: key });
+    }
+    if due.is_empty() { return }
+    app.macros.expanding = true;
+    for step in due {
+        match step.action {
+            Action::Press => handleKey(Keyboard(step.key), app),
+            Action::Release => handleRelease(Keyboard(step.key), app),
+        }
+    }
+    app.macros.expanding = false;
+}
+
+// What the HUD says while a macro's being recorded.
+pub fn status(macros: &Macros, config: &Config, locale: &Locale) -> Option<String> {
+    let key = match keys::parse_key(&config.macro_key) {
+        Some(key) => keys::key_name(key),
+        None => config.macro_key.clone(),
+    };
+    match macros.capture {
+        Some(Capture::Recording { .. }) => Some(locale.text("hud.macro_recording", &[&key])),
+        Some(Capture::Binding(_)) => Some(locale.text("hud.macro_binding", &[&key])),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(at: f64, action: Action, key: Key) -> Step {
+        Step { at: at, action: action, key: key }
+    }
+
+    #[test]
+    fn a_recording_lets_go_of_what_it_pressed() {
+        let steps = vec![
+            step(0.0, Action::Release, keyboard::Up),
+            step(0.0, Action::Press, keyboard::Right),
+            step(0.2, Action::Press, keyboard::Space),
+            step(0.25, Action::Release, keyboard::Space),
+        ];
+        assert_eq!(let_go(steps), vec![
+            step(0.0, Action::Press, keyboard::Right),
+            step(0.2, Action::Press, keyboard::Space),
+            step(0.25, Action::Release, keyboard::Space),
+            step(0.25, Action::Release, keyboard::Right),
+        ]);
+
+        let lines = to_lines(&[step(0.5, Action::Press, keyboard::LShift)]);
+        assert_eq!(lines, vec!["0.5 press LShift".to_string()]);
+        assert_eq!(parse(&lines).unwrap(), vec![step(0.5, Action::Press, keyboard::LShift)]);
+        assert!(parse(&["seed 4".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+/*
+ * The title screen's menu: a short list of choices stacked down the middle
+ * of the window, one of them highlighted. Up and Down move the highlight
+ * (round from the bottom to the top, and back), and Enter picks whatever's
+ * highlighted. What picking something actually does is up to Game.
+ * There's Resume at the top as well, when there's an autosave to resume
+ * (see autosave.rs).
+ *
+ * Each choice is a button (see ui.rs), a box with its name written in it.
+ * The boxes work without a font, so even with no HUD you can see where the
+ * highlight is.
+ */
+use collision::Aabb;
+use locale::Locale;
+use ui::{Ui, BUTTON_GAP, BUTTON_HEIGHT, BUTTON_WIDTH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Choice {
+    Resume,
+    Start,
+    Options,
+    Quit,
+}
+
+impl Choice {
+    // Its name's key in the string tables (see locale.rs).
+    pub fn key(&self) -> &'static str {
+        match *self {
+            Choice::Resume => "menu.resume",
+            Choice::Start => "menu.start",
+            Choice::Options => "menu.options",
+            Choice::Quit => "menu.quit",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Menu {
+    pub choices: Vec<Choice>,
+    pub selected: usize,
+}
+
+impl Menu {
+    // The title screen's menu, with Start highlighted.
+    pub fn main() -> Menu {
+        Menu { choices: vec![Choice::Start, Choice::Options, Choice::Quit], selected: 0 }
+    }
+
+    // The same, with Resume first, and highlighted.
+    pub fn resuming() -> Menu {
+        let mut menu = Menu::main();
+        menu.choices.insert(0, Choice::Resume);
+        menu
+    }
+
+    pub fn up(&mut self) {
+        let n = self.choices.len();
+        self.selected = (self.selected + n - 1) % n;
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.choices.len();
+    }
+
+    pub fn selected(&self) -> Choice {
+        self.choices[self.selected]
+    }
+
+    // Where choice number `index` goes in a `width` by `height` window: the
+    // whole stack is centred, a little below the middle so there's room for
+    // the title above it.
+    pub fn item_box(&self, index: usize, width: f64, height: f64) -> Aabb {
+        let n = self.choices.len() as f64;
+        let stack = n * BUTTON_HEIGHT + (n - 1.0) * BUTTON_GAP;
+        let top = (height - stack) / 2.0 + BUTTON_HEIGHT;
+        Aabb::new((width - BUTTON_WIDTH) / 2.0,
+                  top + index as f64 * (BUTTON_HEIGHT + BUTTON_GAP),
+                  BUTTON_WIDTH,
+                  BUTTON_HEIGHT)
+    }
+
+    // The choices, as buttons on `ui`, numbered the way they're listed.
+    // The Ui's focus should be `selected`, so the highlight's in the
+    // right place.
+    pub fn layout(&self, ui: &mut Ui, locale: &Locale) {
+        let (width, height) = (ui.width, ui.height);
+        for (i, choice) in self.choices.iter().enumerate() {
+            ui.button_in(self.item_box(i, width, height), locale.get(choice.key()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_the_highlight_wraps_round() {
+        let mut menu = Menu::main();
+        assert_eq!(menu.selected(), Choice::Start);
+        menu.up();
+        assert_eq!(menu.selected(), Choice::Quit);
+        menu.down();
+        menu.down();
+        assert_eq!(menu.selected(), Choice::Options);
+        let mut menu = Menu::resuming();
+        assert_eq!(menu.selected(), Choice::Resume);
+        menu.down();
+        assert_eq!(menu.selected(), Choice::Start);
+    }
+
+    #[test]
+    fn choices_are_stacked_down_the_middle() {
+        let menu = Menu::main();
+        let (first, second) = (menu.item_box(0, 640.0, 480.0), menu.item_box(1, 640.0, 480.0));
+        assert_eq!(first.x + first.w / 2.0, 320.0);
+        assert_eq!(second.x, first.x);
+        assert_eq!(second.y, first.bottom() + BUTTON_GAP);
+        assert!(!first.intersects(&second));
+    }
+}
@@ -0,0 +1,105 @@
+/*
+ * The minimap: the whole level shrunk down into the bottom-right corner of
+ * the window, so that once a level's bigger than the screen you can still
+ * see where everything is. The level's edges are a box, the players,
+ * enemies, pickups and the goal are dots inside it in their own colors,
+ * and the part the camera can see has an outline round it, which moves
+ * (and, with the zoom, grows and shrinks) as the camera does.
+ *
+ * A level that fits on the screen is all there to see already, so it
+ * doesn't get one, and `minimap` in config.json turns it off altogether.
+ *
+ * Minimap only knows how to squash the world into its corner; what goes on
+ * it is App's business (see render.rs).
+ */
+use graphics::Context;
+
+use collision::Aabb;
+use hud;
+use renderer::Renderer;
+
+// How many pixels the longer side of the map is, whatever shape the level.
+pub const SIZE: f64 = 160.0;
+// How big a dot is, however big the thing it's standing in for.
+pub const DOT: f64 = 4.0;
+// The dots are a touch see-through, so one on top of another still shows.
+pub const DOT_ALPHA: f32 = 0.9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Minimap {
+    // Where the map is in the window, and how many of its pixels one of
+    // the world's is.
+    pub area: Aabb,
+    pub scale: f64,
+}
+
+// Whether a `world` sized level needs a map, with the camera seeing `seen`.
+pub fn needed(world: (f64, f64), seen: Aabb) -> bool {
+    world.0 > seen.w || world.1 > seen.h
+}
+
+impl Minimap {
+    // The map of a `world` sized level, in the corner of a `view` sized
+    // window, MARGIN in from the edges like the HUD.
+    pub fn new(world: (f64, f64), view: (f64, f64)) -> Minimap {
+        let scale = SIZE / world.0.max(world.1).max(1.0);
+        let (w, h) = (world.0 * scale, world.1 * scale);
+        Minimap {
+            area: Aabb::new(view.0 - hud::MARGIN - w, view.1 - hud::MARGIN - h, w, h),
+            scale: scale,
+        }
+    }
+
+    // Where the world's `at` is on the map.
+    pub fn to_map(&self, at: (f64, f64)) -> (f64, f64) {
+        (self.area.x + at.0 * self.scale, self.area.y + at.1 * self.scale)
+    }
+
+    // A dot for `b`, on its middle, kept inside the map.
+    pub fn dot(&self, b: Aabb, color: [f32; 4]) -> ([f64; 4], [f32; 4]) {
+        let (x, y) = self.to_map((b.x + b.w / 2.0, b.y + b.h / 2.0));
+        let a = self.area;
+        let x = x.max(a.x + DOT / 2.0).min(a.x + a.w - DOT / 2.0);
+        let y = y.max(a.y + DOT / 2.0).min(a.y + a.h - DOT / 2.0);
+        ([x - DOT / 2.0, y - DOT / 2.0, DOT, DOT], color)
+    }
+
+    /*
+     * The map itself: a `backing` to make it stand out from the game, the
+     * level's edges in `edge`, the camera's `seen` box in `outline`, and the
+     * `dots` on top. The camera can see past the edges of the world, so its
+     * box is cut down to the part that's on the map.
+     */
+    pub fn draw(&self, dots: &[([f64; 4], [f32; 4])], seen: Aabb, backing: [f32; 4],
+                edge: [f32; 4], outline: [f32; 4], context: &Context, r: &mut dyn Renderer) {
+        let a = self.area;
+        r.rect(context, a.x, a.y, a.w, a.h, backing);
+        r.outline(context, [a.x, a.y, a.w, a.h], 1.0, edge);
+        let (x, y) = self.to_map((seen.x, seen.y));
+        let (right, bottom) = self.to_map((seen.x + seen.w, seen.y + seen.h));
+        let (x, y) = (x.max(a.x), y.max(a.y));
+        let (right, bottom) = (right.min(a.x + a.w), bottom.min(a.y + a.h));
+        if right > x && bottom > y {
+            r.outline(context, [x, y, right - x, bottom - y], 1.0, outline);
+        }
+        r.rects(context, dots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_world_is_squashed_into_the_corner() {
+        let map = Minimap::new((1280.0, 960.0), (640.0, 480.0));
+        assert_eq!(map.scale, 0.125);
+        assert_eq!(map.area, Aabb::new(470.0, 350.0, 160.0, 120.0));
+        assert_eq!(map.to_map((640.0, 480.0)), (550.0, 410.0));
+        // Something off the edge of the world is still on the map.
+        let (b, _) = map.dot(Aabb::new(-100.0, 0.0, 10.0, 10.0), [1.0; 4]);
+        assert_eq!(b, [470.0, 350.0, DOT, DOT]);
+        assert!(needed((1280.0, 960.0), Aabb::new(0.0, 0.0, 640.0, 480.0)));
+        assert!(!needed((640.0, 480.0), Aabb::new(0.0, 0.0, 640.0, 480.0)));
+    }
+}
@@ -0,0 +1,79 @@
+/*
+ * How the player gets up to speed, and back down again. Setting the
+ * velocity straight to "full speed that way" the moment a key goes down is
+ * easy, but it feels like steering a cursor rather than a thing with any
+ * weight to it. Instead, the keys pick a velocity we'd *like* to be going
+ * at, and every tick the real velocity moves a little closer to it:
+ * `acceleration` world units per second per second while a key's held, and
+ * `friction` once they're all let go. Either can be 0, which means "get
+ * there at once" - set both to 0 and you're back to the old instant start
+ * and stop.
+ *
+ * These three numbers are most of what people mean by "game feel". High
+ * acceleration and friction is snappy; low is floaty, like walking on ice.
+ * They're all in config.json, so have a play while the game's running.
+ */
+
+/*
+ * Move `velocity` towards `target` by at most `rate * dt`, treating the two
+ * as arrows and moving the tip of one towards the tip of the other. Doing
+ * it as a whole arrow, rather than x and y separately, means a diagonal
+ * gets up to speed in the same time as a straight line does.
+ */
+pub fn approach(velocity: (f64, f64), target: (f64, f64), rate: f64, dt: f64) -> (f64, f64) {
+    let (gap_x, gap_y) = (target.0 - velocity.0, target.1 - velocity.1);
+    let gap = (gap_x * gap_x + gap_y * gap_y).sqrt();
+    let step = rate * dt;
+    if rate <= 0.0 || gap <= step {
+        return target;
+    }
+    (velocity.0 + gap_x / gap * step, velocity.1 + gap_y / gap * step)
+}
+
+/*
+ * One tick of walking: `direction` is what the held keys add up to (one
+ * unit long, or nothing), and we head for `max_speed` that way. Turning
+ * round counts as accelerating, not as friction, so reversing is as quick
+ * as setting off.
+ */
+pub fn walk(velocity: (f64, f64), direction: (f64, f64), max_speed: f64,
+            acceleration: f64, friction: f64, dt: f64) -> (f64, f64) {
+    let target = (direction.0 * max_speed, direction.1 * max_speed);
+    let held = direction != (0.0, 0.0);
+    approach(velocity, target, if held { acceleration } else { friction }, dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speeds_up_then_stops_speeding_up() {
+        let mut v = (0.0, 0.0);
+        v = walk(v, (1.0, 0.0), 200.0, 1000.0, 500.0, 0.1);
+        assert_eq!(v, (100.0, 0.0));
+        v = walk(v, (1.0, 0.0), 200.0, 1000.0, 500.0, 0.5);
+        assert_eq!(v, (200.0, 0.0));
+    }
+
+    #[test]
+    fn friction_slows_down_to_a_stop() {
+        let mut v = walk((200.0, 0.0), (0.0, 0.0), 200.0, 1000.0, 500.0, 0.1);
+        assert_eq!(v, (150.0, 0.0));
+        v = walk(v, (0.0, 0.0), 200.0, 1000.0, 500.0, 1.0);
+        assert_eq!(v, (0.0, 0.0));
+    }
+
+    #[test]
+    fn zero_means_at_once() {
+        assert_eq!(walk((0.0, 0.0), (0.0, -1.0), 200.0, 0.0, 0.0, 0.01), (0.0, -200.0));
+        assert_eq!(walk((0.0, -200.0), (0.0, 0.0), 200.0, 0.0, 0.0, 0.01), (0.0, 0.0));
+    }
+
+    #[test]
+    fn diagonals_are_no_quicker() {
+        let d = 0.5f64.sqrt();
+        let (vx, vy) = walk((0.0, 0.0), (d, d), 200.0, 1000.0, 500.0, 0.1);
+        assert!(((vx * vx + vy * vy).sqrt() - 100.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * Playing over the network, two of you, one on each computer. One of you
+ * hosts (--host), which just means waiting for the other to turn up; the
+ * other connects to them (--connect 192.168.1.5:7777). After that the two
+ * ends are the same: every tick, each one sends the other a message saying
+ * which keys its player is holding and where that player is, and draws the
+ * other player wherever the last message from them said. Each of you walks
+ * your own square about on your own computer, so there's no waiting for
+ * the network to say where you've gone; only the other square can lag.
+ *
+ * The messages are JSON, like everything else this game writes down, sent
+ * as UDP datagrams. UDP is the "send it and hope" way of using the
+ * network: a datagram either turns up whole or not at all, and not
+ * necessarily in the order they were sent. That's fine here. Every message
+ * says everything, so losing one doesn't matter, the next one will do; and
+ * each one is numbered, so one that turns up late can be spotted and
+ * thrown away.
+ *
+ * What isn't shared is everything else: pickups, enemies and the rest all
+ * happen separately at each end. This is a demo of keeping two squares in
+ * step, not a whole networked game - that's a much bigger job.
+ */
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use serde_json;
+
+use controls::{Facing, InputState};
+
+// Where --host listens, and where --connect goes if it's not told a port.
+pub const DEFAULT_PORT: u16 = 7777;
+
+// Plenty for a message. Anything bigger that turns up isn't one of ours.
+const MAX_DATAGRAM: usize = 1024;
+
+// Which keys a player is holding, in a form that can go over the network.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Held {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl Held {
+    pub fn from_input(input: &InputState) -> Held {
+        Held { up: input.up, down: input.down, left: input.left, right: input.right }
+    }
+
+    pub fn to_input(&self) -> InputState {
+        InputState { up: self.up, down: self.down, left: self.left, right: self.right,
+                     ..InputState::default() }
+    }
+}
+
+// Where a player is and how they're doing: the state half of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Remote {
+    pub x: f64,
+    pub y: f64,
+    pub expand: f64,
+    pub facing: Facing,
+}
+
+// One tick's worth, from one end to the other. The number is how many
+// messages were sent before this one; see Link::send.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub number: u64,
+    pub held: Held,
+    pub player: Remote,
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        // Nothing in a Message can fail to turn into JSON.
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, String> {
+        serde_json::from_slice(bytes).map_err(|e| format!("not a message: {}", e))
+    }
+}
+
+/*
+ * One end of the connection. The host doesn't know where the other end is
+ * until something arrives, so peer starts off as None and gets filled in
+ * by the first message. After that, anything from anywhere else is
+ * ignored, so a stray datagram can't take over the other player.
+ */
+pub struct Link {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>,
+    // How many messages we've sent, and the newest number we've had from
+    // the other end, to catch stragglers. These count from when the link
+    // was made, not from the start of the level, so they keep going up
+    // when either end starts a level again.
+    sent: u64,
+    newest: Option<u64>,
+}
+
+impl Link {
+    // Wait for somebody to connect on `port`.
+    pub fn host(port: u16) -> Result<Link, String> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))
+            .map_err(|e| format!("couldn't listen on port {}: {}", port, e))?;
+        Link::new(socket, None)
+    }
+
+    // Connect to a host at `address`, which is "host:port" or just "host".
+    pub fn connect(address: &str) -> Result<Link, String> {
+        let with_port = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, DEFAULT_PORT)
+        };
+        let peer = with_port.to_socket_addrs().ok().and_then(|mut a| a.next())
+            .ok_or_else(|| format!("couldn't find {}", address))?;
+        let local = if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).map_err(|e| format!("couldn't open a socket: {}", e))?;
+        Link::new(socket, Some(peer))
+    }
+
+    /*
+     * The game can't sit and wait for a datagram that may never come, so
+     * the socket's put into non-blocking mode: asking for a datagram when
+     * there isn't one gives back a WouldBlock error straight away, rather
+     * than waiting.
+     */
+    fn new(socket: UdpSocket, peer: Option<SocketAddr>) -> Result<Link, String> {
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Link { socket: socket, peer: peer, sent: 0, newest: None })
+    }
+
+    // Where we are, for the log (and the tests).
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.local_addr().ok()
+    }
+
+    // Whether there's anyone at the other end yet.
+    pub fn connected(&self) -> bool {
+        self.peer.is_some()
+    }
+
+    // Send the other end where our player is and what they're holding, if
+    // there is another end. A datagram that doesn't go is no great loss;
+    // there'll be another along next tick.
+    pub fn send(&mut self, held: Held, player: Remote) {
+        let peer = match self.peer {
+            Some(peer) => peer,
+            None => return,
+        };
+        let message = Message { number: self.sent, held: held, player: player };
+        self.sent += 1;
+        if let Err(e) = self.socket.send_to(&message.encode(), peer) {
+            debug!(target: Net, "Couldn't send to {}: {}", peer, e);
+        }
+    }
+
+    /*
+     * Everything that's arrived since last time, boiled down to the newest
+     * message, if there is one. Only the newest matters: each one has the
+     * whole story, so older ones have nothing to add.
+     */
+    pub fn receive(&mut self) -> Option<Message> {
+        let mut newest = None;
+        let mut buffer = [0; MAX_DATAGRAM];
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(got) => got,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    // On some systems a host that isn't there yet shows
+                    // up as an error here. Try again next tick.
+                    debug!(target: Net, "Couldn't receive: {}", e);
+                    break;
+                }
+            };
+            match self.peer {
+                Some(peer) if peer != from => {
+                    debug!(target: Net, "Ignored a datagram from {}", from);
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    info!(target: Net, "{} has joined", from);
+                    self.peer = Some(from);
+                }
+            }
+            match Message::decode(&buffer[..size]) {
+                Ok(message) => {
+                    if self.newest.map_or(true, |n| message.number > n) {
+                        self.newest = Some(message.number);
+                        newest = Some(message);
+                    }
+                }
+                Err(e) => debug!(target: Net, "Ignored a datagram from {}: {}", from, e),
+            }
+        }
+        newest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use controls::Facing;
+
+    fn message(number: u64) -> Message {
+        Message {
+            number: number,
+            held: Held { up: false, down: true, left: false, right: true },
+            player: Remote { x: 120.0, y: 80.5, expand: 10.0, facing: Facing::DownRight },
+        }
+    }
+
+    #[test]
+    fn messages_survive_the_trip() {
+        assert_eq!(Message::decode(&message(3).encode()), Ok(message(3)));
+        assert!(Message::decode(b"{ \"number\": 1 }").is_err());
+    }
+
+    #[test]
+    fn held_keys_go_both_ways() {
+        let held = message(0).held;
+        assert_eq!(Held::from_input(&held.to_input()), held);
+    }
+
+    // Wait a little for datagrams to arrive. Over the loopback they're
+    // very quick, but not instant.
+    fn receive(link: &mut Link) -> Option<Message> {
+        for _ in 0..100 {
+            if let Some(m) = link.receive() { return Some(m) }
+            thread::sleep(Duration::from_millis(5));
+        }
+        None
+    }
+
+    #[test]
+    fn host_and_client_talk_to_each_other() {
+        // Port 0 means any free port, so the test doesn't fight with a
+        // game that's running.
+        let mut host = Link::host(0).unwrap();
+        let port = host.local_addr().unwrap().port();
+        let mut client = Link::connect(&format!("127.0.0.1:{}", port)).unwrap();
+        assert!(!host.connected() && client.connected());
+
+        let sent = message(0);
+        client.send(sent.held, sent.player);
+        assert_eq!(receive(&mut host), Some(sent));
+        assert!(host.connected());
+
+        // Stragglers from the past get thrown away. (These go straight out
+        // of the socket, since send numbers them itself.)
+        let to = client.local_addr().unwrap();
+        let to = SocketAddr::new("127.0.0.1".parse().unwrap(), to.port());
+        host.socket.send_to(&message(5).encode(), to).unwrap();
+        host.socket.send_to(&message(4).encode(), to).unwrap();
+        assert_eq!(receive(&mut client).map(|m| m.number), Some(5));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(client.receive(), None);
+    }
+}
@@ -0,0 +1,153 @@
+/*
+ * Game objects: the things in the world that have a turn every tick and get
+ * drawn every frame, each one of them a GameObject, all in the one list.
+ * Every player is one, and so is every enemy and every pickup. A tick goes
+ * through the lot of them, giving each its update in turn, and a frame goes
+ * through them again, asking each to draw itself. That's one loop for all
+ * three, rather than a block of App::advance for the players, another for
+ * the enemies and another for the pickups, and the same again in render.
+ *
+ * What a game object holds, though, is which player or enemy or pickup it
+ * is, not the player or the enemy itself. The players are read all over
+ * App (the camera follows them, the HUD shows their expand, the keys steer
+ * them), the enemies are in the Scene where the projectiles can find them
+ * (see ecs.rs), and the pickups are in the list of entities along with the
+ * walls (see entity.rs), and each of those is the right place for them. So
+ * a PlayerObject is a player's number, an EnemyObject an enemy's Id, and a
+ * PickupObject where the pickup is in the entities. That's also why update
+ * and render get the App: it's where the player actually is, and the walls
+ * they bump into, and the player an enemy's after.
+ *
+ * Who's in the world changes all the time (an enemy gets shot, a pickup
+ * gets picked up), so the list is gathered afresh at the start of every
+ * tick and every frame. Everyone updates in the order they're gathered in:
+ * the players move, then the enemies go after them, then the pickups see
+ * who's touching them. Drawing wants a different order, with the players
+ * on top of everything, so each says which layer it goes on, and lower
+ * layers are drawn first.
+ */
+use std::time::Instant;
+
+use graphics::Context;
+
+use app::App;
+use assets::Assets;
+use ecs::{self, Id, Kind};
+use entity;
+use frametime;
+use renderer::Renderer;
+
+pub trait GameObject {
+    // One tick of `dt` seconds.
+    fn update(&mut self, app: &mut App, dt: f64);
+
+    // Which layer it's drawn on: lower goes underneath.
+    fn layer(&self) -> u32;
+
+    // Drawing, through the camera: `world` is the world's context.
+    fn render(&self, app: &App, assets: &Assets, world: &Context, r: &mut dyn Renderer);
+}
+
+// Everyone in the world right now, in the order they update.
+pub fn gather(app: &App) -> Vec<Box<dyn GameObject>> {
+    let mut objects: Vec<Box<dyn GameObject>> = Vec::new();
+    for i in 0..app.players.len() {
+        objects.push(Box::new(PlayerObject { index: i }));
+    }
+    for id in app.scene.of_kind(Kind::Enemy) {
+        objects.push(Box::new(EnemyObject { id: id }));
+    }
+    for (i, e) in app.entities.iter().enumerate() {
+        if e.collectible() {
+            objects.push(Box::new(PickupObject { index: i }));
+        }
+    }
+    objects
+}
+
+// A tick's worth: everyone's update, in order, with the App to themselves
+// while they have it. The list's kept on App afterwards.
+pub fn update_all(app: &mut App, dt: f64) {
+    let mut objects = gather(app);
+    for object in objects.iter_mut() {
+        object.update(app, dt);
+    }
+    app.objects = objects;
+}
+
+// And a frame's worth, a layer at a time. Objects on the same layer are
+// drawn in the order they were gathered in.
+pub fn render_all(app: &mut App, assets: &Assets, world: &Context, r: &mut dyn Renderer) {
+    app.objects = gather(app);
+    let app: &App = app;
+    let mut objects: Vec<&Box<dyn GameObject>> = app.objects.iter().collect();
+    objects.sort_by_key(|o| o.layer());
+    for object in objects {
+        object.render(app, assets, world, r);
+    }
+}
+
+// A player, by their number in app.players. Someone playing from the other
+// end of the network moves about on their own computer, so theirs is only
+// drawn here (see App::exchange).
+pub struct PlayerObject {
+    pub index: usize,
+}
+
+impl GameObject for PlayerObject {
+    fn update(&mut self, app: &mut App, dt: f64) {
+        if self.index < app.local_players() {
+            app.update_player(self.index, dt);
+        }
+    }
+
+    fn layer(&self) -> u32 { 2 }
+
+    fn render(&self, app: &App, assets: &Assets, world: &Context, r: &mut dyn Renderer) {
+        app.draw_player(self.index, assets, world, r);
+    }
+}
+
+// An enemy, by its Id in the scene.
+pub struct EnemyObject {
+    pub id: Id,
+}
+
+impl GameObject for EnemyObject {
+    // Timed, for --bench, along with all the other enemies' turns.
+    fn update(&mut self, app: &mut App, dt: f64) {
+        let started = Instant::now();
+        app.update_enemy(self.id, dt);
+        app.system_times.enemies += frametime::seconds(started.elapsed());
+    }
+
+    fn layer(&self) -> u32 { 1 }
+
+    // A plain blue square, from its Renderable (or the atlas's picture of
+    // an enemy), and a health bar over it once it's been shot but not
+    // knocked out.
+    fn render(&self, app: &App, assets: &Assets, world: &Context, r: &mut dyn Renderer) {
+        ecs::render_one(&app.scene, self.id, assets, world, r);
+        ecs::health_bar(&app.scene, self.id, world, r);
+    }
+}
+
+// A pickup, by where it is in app.entities.
+pub struct PickupObject {
+    pub index: usize,
+}
+
+impl GameObject for PickupObject {
+    // Picking things up is collision, as far as --bench is concerned.
+    fn update(&mut self, app: &mut App, _: f64) {
+        let started = Instant::now();
+        app.touch_pickup(self.index);
+        app.system_times.collision += frametime::seconds(started.elapsed());
+    }
+
+    fn layer(&self) -> u32 { 0 }
+
+    fn render(&self, app: &App, assets: &Assets, world: &Context, r: &mut dyn Renderer) {
+        entity::draw_one(&*app.entities[self.index], assets, world, r);
+    }
+}
@@ -0,0 +1,579 @@
+/*
+ * The options screen. Most of what you can change lives in config.json (and
+ * is picked up while the game runs), but a few things are nice to be able
+ * to change without a text editor: the palette, what player one looks like,
+ * how fast the game ticks over, whether the background moves, which
+ * language it's in, how loud it is, whether it's fullscreen, and the keys
+ * for resetting, quitting, the help and rewinding. Those are a list down
+ * the screen, one of them picked, like the title screen's menu: Up and
+ * Down pick one, and Left and Right change it. The list's longer than the
+ * screen, so it scrolls, SHOWN at a time.
+ *
+ * The volumes are sliders, going up and down a tenth at a time, and the
+ * keys are a bit different again: Enter on one, and the next key you press
+ * is the new one (or Escape, to leave it as it was). That's a key-capture
+ * field, in the jargon. The rest go round a list, or flip on and off.
+ *
+ * You can get here from the title screen, or from the pause (O, while
+ * paused), in which case Game keeps the game you were playing to one side
+ * and carries on with it when you leave, with your changes. From the title
+ * screen, changes count from the next game. Either way, when you leave the
+ * screen they're written back into config.json, so they're still that way
+ * next time (see save_choices). The frame cap, the update rate and bench
+ * mode don't wait for a game, since Game passes them straight on to the
+ * event loop, and nor do the language, which Game loads the moment it
+ * changes, or fullscreen, but vsync has to wait for the next window (after
+ * F11, say).
+ */
+use std::path::Path;
+
+use input::keyboard::Key;
+
+use app::UPS_STEPS;
+use config::{self, Config};
+use keys;
+use locale::{self, Locale};
+use palette;
+use player::SHAPES;
+use ui::Ui;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Setting {
+    Palette,
+    PlayerColor,
+    PlayerSize,
+    PlayerShape,
+    MaxFps,
+    Ups,
+    Vsync,
+    BenchMode,
+    LowDistraction,
+    Language,
+    Volume,
+    MusicVolume,
+    Fullscreen,
+    ResetKey,
+    QuitKey,
+    HelpKey,
+    RewindKey,
+}
+
+pub const SETTINGS: &'static [Setting] = &[
+    Setting::Palette,
+    Setting::PlayerColor,
+    Setting::PlayerSize,
+    Setting::PlayerShape,
+    Setting::MaxFps,
+    Setting::Ups,
+    Setting::Vsync,
+    Setting::BenchMode,
+    Setting::LowDistraction,
+    Setting::Language,
+    Setting::Volume,
+    Setting::MusicVolume,
+    Setting::Fullscreen,
+    Setting::ResetKey,
+    Setting::QuitKey,
+    Setting::HelpKey,
+    Setting::RewindKey,
+];
+
+// How many of them are on the screen at once.
+pub const SHOWN: usize = 10;
+
+// The colors Left and Right go through for the player, with their names'
+// keys in the string tables. Blue's missing, since that's the enemies.
+pub const COLORS: &'static [(&'static str, [f32; 4])] = &[
+    ("color.red", [1.0, 0.0, 0.0, 1.0]),
+    ("color.orange", [1.0, 0.5, 0.0, 1.0]),
+    ("color.yellow", [1.0, 0.9, 0.0, 1.0]),
+    ("color.green", [0.1, 0.8, 0.1, 1.0]),
+    ("color.purple", [0.6, 0.2, 0.9, 1.0]),
+    ("color.pink", [1.0, 0.4, 0.7, 1.0]),
+    ("color.white", [1.0, 1.0, 1.0, 1.0]),
+];
+
+// How much Left and Right change the size by, and how small and big it goes.
+pub const SIZE_STEP: f64 = 2.0;
+pub const MIN_SIZE: f64 = 4.0;
+pub const MAX_SIZE: f64 = 40.0;
+
+// The frame caps Left and Right go through: the usual screens' refresh
+// rates, and a couple either side.
+pub const FRAME_CAPS: &'static [u64] = &[30, 60, 75, 120, 144, 240];
+
+// How much Left and Right change a volume by, and how many notches its
+// slider has.
+pub const VOLUME_STEP: f32 = 0.1;
+const NOTCHES: usize = 10;
+
+// Which setting's picked, whether any of them have been changed, whether
+// the picked one's a key waiting for you to press the new one, and which
+// is the first on the screen, with the list scrolled.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    pub selected: usize,
+    pub changed: bool,
+    pub capturing: bool,
+    pub first: usize,
+}
+
+impl Options {
+    pub fn up(&mut self) {
+        let n = SETTINGS.len();
+        self.selected = (self.selected + n - 1) % n;
+        self.scroll();
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % SETTINGS.len();
+        self.scroll();
+    }
+
+    // Just far enough to have the picked one on the screen. The mouse
+    // only ever picks one that's on it already, so pointing at the ones
+    // at the ends doesn't move the list out from under it.
+    fn scroll(&mut self) {
+        if self.selected < self.first {
+            self.first = self.selected;
+        } else if self.selected >= self.first + SHOWN {
+            self.first = self.selected + 1 - SHOWN;
+        }
+    }
+
+    pub fn selected(&self) -> Setting {
+        SETTINGS[self.selected]
+    }
+
+    // Left (`forward` false) or Right on the picked setting.
+    pub fn change(&mut self, config: &mut Config, forward: bool) {
+        change(self.selected(), config, forward);
+        self.changed = true;
+    }
+
+    // Enter on a key: the next one pressed is the new one. Whether the
+    // picked setting is a key, so Enter's been taken care of.
+    pub fn start_capture(&mut self) -> bool {
+        self.capturing = is_key(self.selected());
+        self.capturing
+    }
+
+    // The key pressed while capturing. Whether it took: keys without a
+    // name for the config don't, and it goes on waiting.
+    pub fn capture(&mut self, config: &mut Config, key: Key) -> bool {
+        if !bind(self.selected(), config, key) { return false }
+        self.capturing = false;
+        self.changed = true;
+        true
+    }
+
+    // The picked setting, ready to show in `locale`'s language, with an
+    // arrow next to it.
+    pub fn line(&self, config: &Config, locale: &Locale) -> String {
+        format!("> {}", self.text(self.selected, config, locale))
+    }
+
+    // Setting number `i` and what it's set to, or for a key that's being
+    // set, that it's waiting.
+    fn text(&self, i: usize, config: &Config, locale: &Locale) -> String {
+        let setting = SETTINGS[i];
+        let value = if self.capturing && i == self.selected {
+            locale.get("options.press_a_key").to_string()
+        } else {
+            value(setting, config, locale)
+        };
+        format!("{}: {}", locale.get(label(setting)), value)
+    }
+
+    /*
+     * The part of the list that's on the screen, ready to show, with an
+     * arrow next to the one that's picked. It's SHOWN of them, from
+     * `first`, which scrolls along as Up and Down go past the ends. "..."
+     * above or below means there's more that way.
+     */
+    pub fn lines(&self, config: &Config, locale: &Locale) -> Vec<String> {
+        let first = self.first;
+        let mut lines = Vec::new();
+        if first > 0 { lines.push("...".to_string()) }
+        for i in first..(first + SHOWN).min(SETTINGS.len()) {
+            lines.push(if i == self.selected { self.line(config, locale) }
+                       else { self.text(i, config, locale) });
+        }
+        if first + SHOWN < SETTINGS.len() { lines.push("...".to_string()) }
+        lines
+    }
+
+    /*
+     * The same list, as widgets on `ui` (see ui.rs): the volumes are
+     * sliders, and everything else is a button. The Ui's numbers start at
+     * `first`, so its focus should be selected less that, and a number it
+     * gives back wants that adding on again.
+     */
+    pub fn layout(&self, ui: &mut Ui, config: &Config, locale: &Locale) {
+        let first = self.first;
+        if first > 0 { ui.label("...") }
+        for i in first..(first + SHOWN).min(SETTINGS.len()) {
+            let text = self.text(i, config, locale);
+            match SETTINGS[i] {
+                Setting::Volume => ui.slider(&text, config.audio.volume),
+                Setting::MusicVolume => ui.slider(&text, config.audio.music_volume),
+                _ => ui.button(&text),
+            };
+        }
+        if first + SHOWN < SETTINGS.len() { ui.label("...") }
+    }
+
+    // How many lines layout takes up, for centring them.
+    pub fn rows(&self) -> usize {
+        let first = self.first;
+        SHOWN.min(SETTINGS.len()) + (first > 0) as usize
+            + (first + SHOWN < SETTINGS.len()) as usize
+    }
+
+    /*
+     * The volume being picked, set to a point along its slider from 0 to 1
+     * by clicking on it, to the nearest step. False if it isn't a volume
+     * being picked.
+     */
+    pub fn set_volume(&mut self, config: &mut Config, along: f32) -> bool {
+        let volume = (along * 10.0).round() / 10.0;
+        match self.selected() {
+            Setting::Volume => config.audio.volume = volume,
+            Setting::MusicVolume => config.audio.music_volume = volume,
+            _ => return false,
+        }
+        self.changed = true;
+        true
+    }
+}
+
+// The key for `setting`'s name in the string tables.
+pub fn label(setting: Setting) -> &'static str {
+    match setting {
+        Setting::Palette => "options.palette",
+        Setting::PlayerColor => "options.player_color",
+        Setting::PlayerSize => "options.player_size",
+        Setting::PlayerShape => "options.player_shape",
+        Setting::MaxFps => "options.max_fps",
+        Setting::Ups => "options.ups",
+        Setting::Vsync => "options.vsync",
+        Setting::BenchMode => "options.bench_mode",
+        Setting::LowDistraction => "options.low_distraction",
+        Setting::Language => "options.language",
+        Setting::Volume => "options.volume",
+        Setting::MusicVolume => "options.music_volume",
+        Setting::Fullscreen => "options.fullscreen",
+        Setting::ResetKey => "options.reset_key",
+        Setting::QuitKey => "options.quit_key",
+        Setting::HelpKey => "options.help_key",
+        Setting::RewindKey => "options.rewind_key",
+    }
+}
+
+// Whether `setting` is one of the keys, which Enter sets rather than Left
+// and Right.
+pub fn is_key(setting: Setting) -> bool {
+    match setting {
+        Setting::ResetKey | Setting::QuitKey | Setting::HelpKey | Setting::RewindKey => true,
+        _ => false,
+    }
+}
+
+/*
+ * What `setting` is set to in `config`, in `locale`'s words. The player's
+ * color is only the default palette's, so with another one it says it
+ * isn't being used. A language is always called by its own name, so you
+ * can find yours whatever it's in now.
+ */
+pub fn value(setting: Setting, config: &Config, locale: &Locale) -> String {
+    match setting {
+        Setting::Palette => locale.get(&format!("palette.{}", config.palette)).to_string(),
+        Setting::PlayerColor => {
+            let name = COLORS.iter().find(|&&(_, c)| c == config.player_color)
+                                    .map_or("options.own_color", |&(name, _)| name);
+            if config.palette == "default" {
+                locale.get(name).to_string()
+            } else {
+                locale.text("options.default_palette_only", &[&locale.get(name)])
+            }
+        }
+        Setting::PlayerSize => format!("{:.0}", config.player_size),
+        Setting::PlayerShape => {
+            locale.get(&format!("shape.{}", config.player_shape.name())).to_string()
+        }
+        Setting::MaxFps => locale.text("options.a_second", &[&config.max_fps]),
+        Setting::Ups => config.ups.to_string(),
+        Setting::Vsync => on_off(config.vsync, locale),
+        Setting::BenchMode => on_off(config.bench_mode, locale),
+        Setting::LowDistraction => on_off(config.low_distraction, locale),
+        // Game loads the new language as soon as it's picked, so it's
+        // only not the one in `locale` if it wouldn't load.
+        Setting::Language if locale.language == config.language => {
+            locale.get("language").to_string()
+        }
+        Setting::Language => config.language.clone(),
+        Setting::Volume => slider(config.audio.volume),
+        Setting::MusicVolume => slider(config.audio.music_volume),
+        Setting::Fullscreen => on_off(config.fullscreen, locale),
+        Setting::ResetKey => key_value(&config.reset_key),
+        Setting::QuitKey => key_value(&config.quit_key),
+        Setting::HelpKey => key_value(&config.help_key),
+        Setting::RewindKey => key_value(&config.rewind_key),
+    }
+}
+
+fn on_off(on: bool, locale: &Locale) -> String {
+    locale.get(if on { "options.on" } else { "options.off" }).to_string()
+}
+
+// A volume, from 0 to 1, as a slider with a notch for every step.
+fn slider(volume: f32) -> String {
+    let filled = ((volume * NOTCHES as f32).round() as usize).min(NOTCHES);
+    format!("[{}{}] {:.0}%", "#".repeat(filled), "-".repeat(NOTCHES - filled), volume * 100.0)
+}
+
+// A key from the config by the name a person would know it by, or as the
+// config has it if it isn't a key at all.
+fn key_value(name: &str) -> String {
+    keys::parse_key(name).map_or(name.to_string(), keys::key_name)
+}
+
+// A volume a step up or down, kept to whole tenths so that going up and
+// back down again ends up where it started.
+fn step_volume(volume: f32, forward: bool) -> f32 {
+    let by = if forward { VOLUME_STEP } else { -VOLUME_STEP };
+    ((volume + by) * 10.0).round().max(0.0).min(10.0) / 10.0
+}
+
+// Make `key` the one for `setting`, if it's one of the keys and `key` has
+// a name to write in the config. Whether it did.
+pub fn bind(setting: Setting, config: &mut Config, key: Key) -> bool {
+    let name = match keys::short_name(key) {
+        Some(name) => name.to_string(),
+        None => return false,
+    };
+    match setting {
+        Setting::ResetKey => config.reset_key = name,
+        Setting::QuitKey => config.quit_key = name,
+        Setting::HelpKey => config.help_key = name,
+        Setting::RewindKey => config.rewind_key = name,
+        _ => return false,
+    }
+    true
+}
+
+// One along from where `current` is in a list of `n`, going round at the
+// ends. Something that isn't in the list at all goes to the first.
+fn step(current: Option<usize>, n: usize, forward: bool) -> usize {
+    match current {
+        Some(i) if forward => (i + 1) % n,
+        Some(i) => (i + n - 1) % n,
+        None => 0,
+    }
+}
+
+pub fn change(setting: Setting, config: &mut Config, forward: bool) {
+    match setting {
+        Setting::Palette => {
+            let name = if forward { palette::next(&config.palette) }
+                       else { palette::previous(&config.palette) };
+            config.palette = name.to_string();
+        }
+        Setting::PlayerColor => {
+            let current = COLORS.iter().position(|&(_, c)| c == config.player_color);
+            config.player_color = COLORS[step(current, COLORS.len(), forward)].1;
+        }
+        Setting::PlayerSize => {
+            let by = if forward { SIZE_STEP } else { -SIZE_STEP };
+            config.player_size = (config.player_size + by).max(MIN_SIZE).min(MAX_SIZE);
+        }
+        Setting::PlayerShape => {
+            let current = SHAPES.iter().position(|&s| s == config.player_shape);
+            config.player_shape = SHAPES[step(current, SHAPES.len(), forward)];
+        }
+        Setting::MaxFps => {
+            let current = FRAME_CAPS.iter().position(|&fps| fps == config.max_fps);
+            config.max_fps = FRAME_CAPS[step(current, FRAME_CAPS.len(), forward)];
+        }
+        Setting::Ups => {
+            let current = UPS_STEPS.iter().position(|&ups| ups == config.ups);
+            config.ups = UPS_STEPS[step(current, UPS_STEPS.len(), forward)];
+        }
+        // Either way flips these.
+        Setting::Vsync => config.vsync = !config.vsync,
+        Setting::BenchMode => config.bench_mode = !config.bench_mode,
+        Setting::LowDistraction => config.low_distraction = !config.low_distraction,
+        Setting::Language => {
+            config.language = locale::step(&config.language, forward).to_string();
+        }
+        Setting::Volume => config.audio.volume = step_volume(config.audio.volume, forward),
+        Setting::MusicVolume => {
+            config.audio.music_volume = step_volume(config.audio.music_volume, forward);
+        }
+        Setting::Fullscreen => config.fullscreen = !config.fullscreen,
+        // The keys are set with Enter instead (see bind).
+        Setting::ResetKey | Setting::QuitKey | Setting::HelpKey | Setting::RewindKey => {}
+    }
+}
+
+/*
+ * Write what's been chosen here into the config file at `path`. It's read
+ * first and only these settings are changed, so everything else in it stays
+ * as you left it; in particular, anything changed mid-game (with the
+ * console, say) isn't written. A file that won't read is an error, and is
+ * left alone: either there isn't one for some reason, or it's broken, and
+ * writing over a broken one would throw away whatever you'd been typing.
+ */
+pub fn save_choices(path: &Path, chosen: &Config) -> Result<(), String> {
+    let mut config = config::load(path)?;
+    copy_choices(chosen, &mut config);
+    config::save(path, &config)
+}
+
+// The settings on this screen, from `chosen` into `config`, leaving the
+// rest of it as it is. The same goes for a game carrying on after the
+// options, from the pause.
+pub fn copy_choices(chosen: &Config, config: &mut Config) {
+    config.palette = chosen.palette.clone();
+    config.player_color = chosen.player_color;
+    config.player_size = chosen.player_size;
+    config.player_shape = chosen.player_shape;
+    config.max_fps = chosen.max_fps;
+    config.ups = chosen.ups;
+    config.vsync = chosen.vsync;
+    config.bench_mode = chosen.bench_mode;
+    config.low_distraction = chosen.low_distraction;
+    config.language = chosen.language.clone();
+    config.audio.volume = chosen.audio.volume;
+    config.audio.music_volume = chosen.audio.music_volume;
+    config.fullscreen = chosen.fullscreen;
+    config.reset_key = chosen.reset_key.clone();
+    config.quit_key = chosen.quit_key.clone();
+    config.help_key = chosen.help_key.clone();
+    config.rewind_key = chosen.rewind_key.clone();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use player::Shape;
+
+    #[test]
+    fn left_and_right_change_the_picked_setting() {
+        let mut options = Options::default();
+        let mut config = Config::default();
+        let english = Locale::english();
+        options.down();
+        options.change(&mut config, false);
+        assert_eq!(value(Setting::PlayerColor, &config, &english), "white");
+        options.change(&mut config, true);
+        options.change(&mut config, true);
+        assert_eq!(config.player_color, COLORS[1].1);
+        options.down();
+        for _ in 0..40 {
+            options.change(&mut config, true);
+        }
+        assert_eq!(config.player_size, MAX_SIZE);
+        options.down();
+        options.change(&mut config, true);
+        assert_eq!(config.player_shape, Shape::Hollow);
+        assert!(options.changed);
+        assert_eq!(options.lines(&config, &english)[3], "> Player shape: hollow");
+    }
+
+    #[test]
+    fn the_language_is_called_by_its_own_name() {
+        let mut config = Config::default();
+        let english = Locale::english();
+        assert_eq!(value(Setting::Language, &config, &english), "English");
+        change(Setting::Language, &mut config, true);
+        assert_eq!(config.language, "fr");
+        let french = locale::load(&locale::default_dir(), "fr").unwrap();
+        assert_eq!(value(Setting::Language, &config, &french), "Français");
+        assert_eq!(value(Setting::Vsync, &config, &french), "non");
+        assert_eq!(Options::default().lines(&config, &french)[0], "> Palette: par défaut");
+    }
+
+    #[test]
+    fn the_event_loop_settings_go_round_too() {
+        let mut config = Config::default();
+        let english = Locale::english();
+        change(Setting::MaxFps, &mut config, true);
+        assert_eq!(value(Setting::MaxFps, &config, &english), "75 a second");
+        change(Setting::Ups, &mut config, true);
+        assert_eq!(config.ups, 30);
+        change(Setting::Ups, &mut config, false);
+        assert_eq!(config.ups, 120);
+        change(Setting::Vsync, &mut config, false);
+        assert_eq!(value(Setting::Vsync, &config, &english), "on");
+        change(Setting::LowDistraction, &mut config, true);
+        assert_eq!(value(Setting::LowDistraction, &config, &english), "on");
+    }
+
+    #[test]
+    fn the_volumes_are_sliders() {
+        let mut config = Config::default();
+        let english = Locale::english();
+        assert_eq!(value(Setting::Volume, &config, &english), "[########--] 80%");
+        for _ in 0..5 {
+            change(Setting::Volume, &mut config, true);
+        }
+        assert_eq!(config.audio.volume, 1.0);
+        change(Setting::MusicVolume, &mut config, false);
+        change(Setting::MusicVolume, &mut config, true);
+        assert_eq!(config.audio.music_volume, 0.5);
+        change(Setting::Fullscreen, &mut config, true);
+        assert_eq!(value(Setting::Fullscreen, &config, &english), "on");
+    }
+
+    #[test]
+    fn enter_on_a_key_waits_for_the_new_one() {
+        let mut options = Options::default();
+        let mut config = Config::default();
+        let english = Locale::english();
+        assert!(!options.start_capture());
+        options.selected = SETTINGS.iter().position(|&s| s == Setting::RewindKey).unwrap();
+        assert!(options.start_capture());
+        assert_eq!(options.line(&config, &english),
+                   "> Rewind key: press a key (Escape to leave it)");
+        // Left and Right don't do anything to a key...
+        options.change(&mut config, true);
+        assert_eq!(config.rewind_key, "Z");
+        // ...but pressing one does.
+        assert!(options.capture(&mut config, Key::B));
+        assert!(!options.capturing && options.changed);
+        assert_eq!(config.rewind_key, "B");
+        assert_eq!(options.line(&config, &english), "> Rewind key: B");
+    }
+
+    #[test]
+    fn the_list_scrolls() {
+        let mut options = Options::default();
+        let config = Config::default();
+        let english = Locale::english();
+        let lines = options.lines(&config, &english);
+        assert_eq!(lines.len(), SHOWN + 1);
+        assert_eq!(lines[SHOWN], "...");
+        options.up();
+        let lines = options.lines(&config, &english);
+        assert_eq!(lines[0], "...");
+        assert_eq!(lines[SHOWN], "> Rewind key: Z");
+    }
+
+    #[test]
+    fn only_the_choices_are_written_back() {
+        let path = env::temp_dir().join(format!("jumpy-options-test-{}.json",
+                                                ::std::process::id()));
+        assert!(save_choices(&path, &Config::default()).is_err());
+        fs::write(&path, r#"{ "move_speed": 3.0 }"#).unwrap();
+        let mut chosen = Config::default();
+        chosen.player_size = 16.0;
+        chosen.move_speed = 99.0;
+        save_choices(&path, &chosen).unwrap();
+        let saved = config::load(&path).unwrap();
+        assert_eq!((saved.player_size, saved.move_speed), (16.0, 3.0));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+/*
+ * Palettes: every color the game is drawn in, all in one place. The game
+ * used to be red squares on grey, which is fine unless red and grey are
+ * hard to tell apart for you, as they are for a lot of people with red-green
+ * colorblindness, and it's blue enemies on the same grey that catch you. So
+ * there are a few to pick from, with `palette` in config.json or Left and
+ * Right on the options screen:
+ *
+ *     default        the colors in config.json (player_color and friends),
+ *                    which are the game's usual ones unless you've changed
+ *                    them
+ *     deuteranopia   the Okabe-Ito colors, which are picked to stay apart
+ *                    for all the common kinds of colorblindness, on a dark
+ *                    background
+ *     high_contrast  bright, flat colors on black
+ *
+ * The two presets are fixed, so the colors in config.json only count with
+ * the default one.
+ *
+ * Besides the colors of things, there's what the window gets painted round
+ * a letterboxed view, and shade, which is what's laid over the game to dim
+ * it (pausing, the help, the console) at however see-through each of them
+ * wants it.
+ */
+use config::Config;
+use powerup::Effect;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub background: [f32; 4],
+    pub player: [f32; 4],
+    pub player_two: [f32; 4],
+    pub wall: [f32; 4],
+    pub goal: [f32; 4],
+    pub pickup: [f32; 4],
+    pub enemy: [f32; 4],
+    pub projectile: [f32; 4],
+    pub speed: [f32; 4],
+    pub size: [f32; 4],
+    pub invulnerable: [f32; 4],
+    pub npc: [f32; 4],
+    pub text: [f32; 4],
+    pub dash_flash: [f32; 4],
+    pub particle: [f32; 4],
+    pub letterbox: [f32; 4],
+    pub shade: [f32; 4],
+}
+
+// Every palette there is, in the order the options screen goes through them.
+pub const NAMES: &'static [&'static str] = &["default", "deuteranopia", "high_contrast"];
+
+const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+impl Palette {
+    pub fn from_config(config: &Config) -> Palette {
+        Palette {
+            background: config.background_color,
+            player: config.player_color,
+            player_two: config.player_two_color,
+            wall: config.wall_color,
+            goal: config.goal_color,
+            pickup: config.pickup_color,
+            enemy: config.enemy_color,
+            projectile: config.projectile_color,
+            speed: config.speed_color,
+            size: config.size_color,
+            invulnerable: config.invulnerable_color,
+            npc: config.npc_color,
+            text: config.hud_color,
+            dash_flash: config.dash_flash_color,
+            particle: config.particle_color,
+            letterbox: BLACK,
+            shade: BLACK,
+        }
+    }
+
+    pub fn deuteranopia() -> Palette {
+        Palette {
+            background: [0.15, 0.15, 0.2, 1.0],
+            player: [0.9, 0.62, 0.0, 1.0],
+            player_two: [0.34, 0.71, 0.91, 1.0],
+            wall: [0.75, 0.75, 0.75, 1.0],
+            goal: [0.0, 0.62, 0.45, 1.0],
+            pickup: [0.94, 0.89, 0.26, 1.0],
+            enemy: [0.0, 0.45, 0.7, 1.0],
+            projectile: [1.0, 1.0, 0.8, 1.0],
+            speed: [0.8, 0.47, 0.65, 1.0],
+            size: [0.84, 0.37, 0.0, 1.0],
+            invulnerable: WHITE,
+            npc: [0.7, 0.6, 0.9, 1.0],
+            text: WHITE,
+            dash_flash: [1.0, 1.0, 1.0, 0.7],
+            particle: [0.94, 0.89, 0.26, 1.0],
+            letterbox: BLACK,
+            shade: BLACK,
+        }
+    }
+
+    pub fn high_contrast() -> Palette {
+        Palette {
+            background: BLACK,
+            player: [1.0, 1.0, 0.0, 1.0],
+            player_two: [0.0, 1.0, 1.0, 1.0],
+            wall: WHITE,
+            goal: [0.0, 1.0, 0.0, 1.0],
+            pickup: [1.0, 0.0, 1.0, 1.0],
+            enemy: [1.0, 0.2, 0.2, 1.0],
+            projectile: WHITE,
+            speed: [0.4, 0.6, 1.0, 1.0],
+            size: [1.0, 0.5, 0.0, 1.0],
+            invulnerable: WHITE,
+            npc: [1.0, 0.7, 0.7, 1.0],
+            text: WHITE,
+            dash_flash: [1.0, 1.0, 1.0, 0.7],
+            particle: [1.0, 0.5, 0.0, 1.0],
+            // The bars round a letterbox would vanish into a black
+            // background, so they're grey to show where the game stops.
+            letterbox: [0.3, 0.3, 0.3, 1.0],
+            shade: BLACK,
+        }
+    }
+
+    // The palette called `name`, which for the default one means the
+    // colors in `config`. None if there's no such palette.
+    pub fn named(name: &str, config: &Config) -> Option<Palette> {
+        match name {
+            "default" => Some(Palette::from_config(config)),
+            "deuteranopia" => Some(Palette::deuteranopia()),
+            "high_contrast" => Some(Palette::high_contrast()),
+            _ => None,
+        }
+    }
+
+    // A power-up, and you while you've got it.
+    pub fn power_up(&self, effect: Effect) -> [f32; 4] {
+        match effect {
+            Effect::Speed => self.speed,
+            Effect::Size => self.size,
+            Effect::Invulnerable => self.invulnerable,
+        }
+    }
+}
+
+// The palette after `name`, going back round to the first after the last.
+// One we don't know is followed by the first.
+pub fn next(name: &str) -> &'static str {
+    let i = NAMES.iter().position(|&n| n == name).map(|i| i + 1).unwrap_or(0);
+    NAMES[i % NAMES.len()]
+}
+
+// And the one before it.
+pub fn previous(name: &str) -> &'static str {
+    let i = NAMES.iter().position(|&n| n == name).unwrap_or(0);
+    NAMES[(i + NAMES.len() - 1) % NAMES.len()]
+}
+
+// `color`, only `alpha` see-through, for the shade.
+pub fn faded(color: [f32; 4], alpha: f32) -> [f32; 4] {
+    [color[0], color[1], color[2], alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Roughly how bright a color looks, from 0 for black to 1 for white.
+    fn brightness(c: [f32; 4]) -> f32 {
+        0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+    }
+
+    #[test]
+    fn the_default_palette_is_the_config() {
+        let mut config = Config::default();
+        config.player_color = [0.5, 0.5, 0.5, 1.0];
+        let palette = Palette::named("default", &config).unwrap();
+        assert_eq!(palette.player, [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(palette.power_up(Effect::Size), config.size_color);
+        assert_eq!(Palette::named("sepia", &config), None);
+    }
+
+    #[test]
+    fn the_player_and_the_enemies_stand_out_in_every_palette() {
+        let config = Config::default();
+        for name in NAMES.iter() {
+            let p = Palette::named(name, &config).unwrap();
+            let against = brightness(p.background);
+            assert!((brightness(p.player) - against).abs() > 0.2, "{}: player", name);
+            assert!((brightness(p.enemy) - against).abs() > 0.2, "{}: enemy", name);
+        }
+    }
+
+    #[test]
+    fn palettes_go_round() {
+        assert_eq!(next("default"), "deuteranopia");
+        assert_eq!(next("high_contrast"), "default");
+        assert_eq!(next("sepia"), "default");
+        assert_eq!(previous("default"), "high_contrast");
+        assert_eq!(previous("deuteranopia"), "default");
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * Particles are the little bits of confetti that fly off the player when the
+ * expand power fires. Each one is dirt simple: it has a position, a velocity,
+ * and a clock telling it how long it has left to live. Every update we move
+ * it along its velocity, age it, and throw it away once its time is up.
+ *
+ * They used to be entities like everything else, but there are hundreds of
+ * them, they never touch anything, and they're all drawn the same way, so
+ * now they live in an Emitter of their own instead: one plain list of
+ * particles that gets updated in one go and drawn in one batch, without a
+ * Box apiece or asking each one how to draw itself. The list is a Pool
+ * (see ecs.rs), so a burst's particles go into the slots the last burst's
+ * left behind, rather than the list growing and shrinking all the time.
+ */
+use std::f64::consts::PI;
+
+use graphics::Context;
+
+use app::App;
+use bus::GameEvent;
+use collision::Aabb;
+use config::Config;
+use ecs::Pool;
+use renderer::Renderer;
+use rng::Rng;
+
+pub struct Particle {
+    pub x: f64,
+    pub y: f64,
+    pub vx: f64,
+    pub vy: f64,
+    // How long this particle has been alive, and how long it gets in total.
+    // Both are in seconds, same as UpdateArgs.dt.
+    pub age: f64,
+    pub lifetime: f64,
+    pub size: f64,
+    pub color: [f32; 4],
+}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    // 1.0 when freshly spawned, sliding down to 0.0 as it dies. We use it as
+    // the alpha when drawing, so old particles fade out rather than pop.
+    pub fn remaining(&self) -> f64 {
+        if self.lifetime <= 0.0 { return 0.0 }
+        (1.0 - self.age / self.lifetime).max(0.0)
+    }
+
+    // Move along the velocity and get a little older.
+    pub fn update(&mut self, dt: f64) {
+        self.x += self.vx * dt;
+        self.y += self.vy * dt;
+        self.age += dt;
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(self.x - self.size / 2.0, self.y - self.size / 2.0,
+                  self.size, self.size)
+    }
+
+    // Its color, with the alpha turned down by however much of its life
+    // it's used up, so it fades out rather than vanishing.
+    pub fn faded_color(&self) -> [f32; 4] {
+        let alpha = self.color[3] * self.remaining() as f32;
+        [self.color[0], self.color[1], self.color[2], alpha]
+    }
+}
+
+/*
+ * All the knobs for a burst in one place. They come from the config (the
+ * particle_ ones, see config.rs), and the color from its palette, so App
+ * makes a fresh set whenever the config changes.
+ */
+pub struct ParticleSettings {
+    pub min_count: usize,
+    pub max_count: usize,
+    // Speeds are in world units per second.
+    pub min_speed: f64,
+    pub max_speed: f64,
+    pub lifetime: f64,
+    pub size: f64,
+    // A hard cap on how many particles there can be at once, so mashing
+    // Space can't make the list grow without bound.
+    pub max_live: usize,
+    pub color: [f32; 4],
+}
+
+impl ParticleSettings {
+    pub fn from_config(config: &Config) -> ParticleSettings {
+        ParticleSettings {
+            min_count: config.particle_min_count,
+            max_count: config.particle_max_count,
+            min_speed: config.particle_min_speed,
+            max_speed: config.particle_max_speed,
+            lifetime: config.particle_lifetime,
+            size: config.particle_size,
+            max_live: config.particle_max_live,
+            color: config.palette().particle,
+        }
+    }
+}
+
+// The ones from the default config.
+impl Default for ParticleSettings {
+    fn default() -> ParticleSettings {
+        ParticleSettings::from_config(&Config::default())
+    }
+}
+
+// Where particles come from, and where they live until they fade away.
+pub struct Emitter {
+    pub particles: Pool<Particle>,
+    pub settings: ParticleSettings,
+}
+
+impl Emitter {
+    pub fn new(settings: ParticleSettings) -> Emitter {
+        Emitter { particles: Pool::new(), settings: settings }
+    }
+
+    // Spray a burst of particles out from (x, y) in random directions. Once
+    // we hit the cap we just stop spawning; the burst is a little thinner,
+    // but nobody's going to count.
+    pub fn burst(&mut self, x: f64, y: f64, rng: &mut Rng) {
+        let settings = &self.settings;
+        let count = rng.range_usize(settings.min_count, settings.max_count);
+        for _ in 0..count {
+            if self.particles.len() >= settings.max_live { break }
+            // Pick a random angle and speed, then turn that into x and y
+            // components with a bit of trigonometry.
+            let angle = rng.range(0.0, 2.0 * PI);
+            let speed = rng.range(settings.min_speed, settings.max_speed);
+            self.particles.insert(Particle {
+                x: x,
+                y: y,
+                vx: angle.cos() * speed,
+                vy: angle.sin() * speed,
+                age: 0.0,
+                lifetime: settings.lifetime,
+                size: settings.size,
+                color: settings.color,
+            });
+        }
+    }
+
+    // Move every particle along, and let go of the ones that are done.
+    pub fn update(&mut self, dt: f64) {
+        for p in self.particles.iter_mut() {
+            p.update(dt);
+        }
+        self.particles.retain(|p| p.is_alive());
+    }
+
+    // Every particle is a tiny square, so they all go to the renderer
+    // together, in one batch.
+    pub fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let batch: Vec<([f64; 4], [f32; 4])> = self.particles.iter()
+            .map(|p| {
+                let b = p.bounds();
+                ([b.x, b.y, b.w, b.h], p.faded_color())
+            })
+            .collect();
+        r.rects(context, &batch);
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+}
+
+// An enemy going out goes out with a bang, off App's bus (see bus.rs).
+pub fn hear(event: &GameEvent, app: &mut App) {
+    if let GameEvent::EnemyKilled { at: (x, y) } = *event {
+        app.particles.burst(x, y, &mut app.rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::Context;
+    use renderer::{Call, Recorder};
+    use rng::Rng;
+
+    #[test]
+    fn burst_expires_within_lifetime() {
+        let mut emitter = Emitter::new(ParticleSettings::default());
+        let mut rng = Rng::new(1234);
+        emitter.burst(100.0, 100.0, &mut rng);
+        let count = emitter.particles.len();
+        assert!(count >= emitter.settings.min_count);
+        assert!(count <= emitter.settings.max_count);
+
+        // Step a 60 fps clock until just past the lifetime; nothing should
+        // survive that.
+        let dt = 1.0 / 60.0;
+        let mut elapsed = 0.0;
+        while elapsed < emitter.settings.lifetime + dt {
+            emitter.update(dt);
+            elapsed += dt;
+        }
+        assert!(emitter.particles.is_empty());
+    }
+
+    #[test]
+    fn burst_respects_cap() {
+        let mut emitter = Emitter::new(ParticleSettings { max_live: 50,
+                                                          ..ParticleSettings::default() });
+        let mut rng = Rng::new(99);
+        for _ in 0..10 {
+            emitter.burst(0.0, 0.0, &mut rng);
+        }
+        assert_eq!(emitter.particles.len(), 50);
+    }
+
+    #[test]
+    fn same_seed_same_burst() {
+        let mut a = Emitter::new(ParticleSettings::default());
+        let mut b = Emitter::new(ParticleSettings::default());
+        a.burst(0.0, 0.0, &mut Rng::new(5));
+        b.burst(0.0, 0.0, &mut Rng::new(5));
+        assert_eq!(a.particles.len(), b.particles.len());
+        // Step both once; the same velocities mean the same positions.
+        a.update(0.5);
+        b.update(0.5);
+        for (pa, pb) in a.particles.iter().zip(b.particles.iter()) {
+            assert_eq!(pa.bounds(), pb.bounds());
+        }
+    }
+
+    #[test]
+    fn the_next_burst_goes_where_the_last_one_was() {
+        let mut emitter = Emitter::new(ParticleSettings::default());
+        let mut rng = Rng::new(7);
+        emitter.burst(0.0, 0.0, &mut rng);
+        let slots = emitter.particles.slots();
+        emitter.update(emitter.settings.lifetime);
+        assert!(emitter.particles.is_empty());
+        emitter.burst(0.0, 0.0, &mut rng);
+        // Only a bigger burst than before needs any more room.
+        assert_eq!(emitter.particles.slots(), slots.max(emitter.particles.len()));
+    }
+
+    #[test]
+    fn old_particles_are_drawn_fainter() {
+        let mut emitter = Emitter::new(ParticleSettings { min_count: 1, max_count: 1,
+                                                          ..ParticleSettings::default() });
+        emitter.burst(0.0, 0.0, &mut Rng::new(3));
+        emitter.update(emitter.settings.lifetime / 2.0);
+        let mut r = Recorder::new();
+        emitter.draw(&Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls.len(), 1);
+        match r.calls[0] {
+            Call::Rect(_, color) => assert_eq!(color[3], 0.5),
+            ref other => panic!("expected a rectangle, got {:?}", other),
+        }
+    }
+}
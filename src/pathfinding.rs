@@ -0,0 +1,314 @@
+/*
+ * Finding a way round the walls. Left to themselves, enemies head straight
+ * for the player (see enemy.rs), which is fine out in the open and hopeless
+ * behind a wall: they just lean on it. So every so often App works out a
+ * route for each enemy that can't see the player, and the enemy follows
+ * that instead, one waypoint at a time, until it's round the corner and
+ * can go straight for them again. The player doesn't hold still, so a route
+ * only lasts until the next time, enemy_path_seconds later (see
+ * App::plan_paths).
+ *
+ * The routes are worked out on a grid over the world, the same size as the
+ * tilemap's tiles, with a cell blocked if anything solid is in it at all.
+ * That's coarser than the walls themselves, but it means an enemy (which is
+ * smaller than a cell) in the middle of a free cell can't be touching
+ * anything, so following the middles of free cells always gets it through.
+ *
+ * The searching is A*, which is the usual way to find the shortest way
+ * across a grid. It's a breadth-first search that tries the most promising
+ * cells first: each cell's promise is how far it's taken to get there plus
+ * a guess at how far is left, and as long as the guess is never too much,
+ * the first route to reach the end is as short as they come. The guess here
+ * is how far it'd be with nothing in the way, going diagonally as much as
+ * it could. Diagonal steps are allowed, but not past a corner, where the
+ * enemy would clip the wall.
+ *
+ * A route from cell to cell zigzags, so it's smoothed out afterwards: any
+ * waypoint that can be skipped, because there's a clear line past it to
+ * the next, is.
+ */
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+
+use collision::{Aabb, Quadtree};
+use ecs::Id;
+
+// How close to a waypoint counts as there.
+pub const ARRIVED: f64 = 1.0;
+
+// The grid: which cells have something solid in them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub cell: f64,
+    pub columns: usize,
+    pub rows: usize,
+    blocked: Vec<bool>,
+}
+
+// A cell waiting to be looked at, and how promising it is. BinaryHeap
+// hands out the biggest first, so the ordering's backwards: the lowest
+// cost is the "biggest". Ties go to the first cell, to keep it the same
+// every time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Open {
+    cost: f64,
+    index: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Open) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Grid {
+    // A grid over a world `width` by `height`, in cells `cell` across, with
+    // the ones `obstacles` get into blocked.
+    pub fn new(width: f64, height: f64, cell: f64, obstacles: &[Aabb]) -> Grid {
+        let columns = (width / cell).ceil().max(1.0) as usize;
+        let rows = (height / cell).ceil().max(1.0) as usize;
+        let mut grid = Grid { cell: cell, columns: columns, rows: rows,
+                              blocked: vec![false; columns * rows] };
+        // The first cell something starts in, and the one after the last
+        // it ends in: a wall that only touches a cell's edge isn't in it.
+        let first = |v: f64, max: usize| (v / cell).floor().max(0.0).min(max as f64) as usize;
+        let after = |v: f64, max: usize| (v / cell).ceil().max(0.0).min(max as f64) as usize;
+        for b in obstacles.iter() {
+            let (c0, r0) = (first(b.x, columns), first(b.y, rows));
+            let (c1, r1) = (after(b.right(), columns), after(b.bottom(), rows));
+            for row in r0..r1 {
+                for column in c0..c1 {
+                    grid.blocked[row * columns + column] = true;
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn blocked(&self, column: usize, row: usize) -> bool {
+        self.blocked[row * self.columns + column]
+    }
+
+    // The cell (x, y) is in. Anything off the edge is in the nearest one.
+    pub fn cell_of(&self, at: (f64, f64)) -> (usize, usize) {
+        let clamp = |v: f64, max: usize| (v / self.cell).floor().max(0.0).min(max as f64 - 1.0);
+        (clamp(at.0, self.columns) as usize, clamp(at.1, self.rows) as usize)
+    }
+
+    pub fn middle(&self, column: usize, row: usize) -> (f64, f64) {
+        ((column as f64 + 0.5) * self.cell, (row as f64 + 0.5) * self.cell)
+    }
+
+    /*
+     * The waypoints from `from` to `to`, not counting where it starts, and
+     * ending at `to` itself. None if there's no way there. The cells at
+     * either end count as free whatever's in them: something up against a
+     * wall can be in a cell the wall's in too.
+     */
+    pub fn route(&self, from: (f64, f64), to: (f64, f64)) -> Option<Vec<(f64, f64)>> {
+        let (start, end) = (self.cell_of(from), self.cell_of(to));
+        let index = |(c, r): (usize, usize)| r * self.columns + c;
+        let (start, end) = (index(start), index(end));
+        let free = |c: isize, r: isize| {
+            c >= 0 && r >= 0 && (c as usize) < self.columns && (r as usize) < self.rows && {
+                let i = r as usize * self.columns + c as usize;
+                i == start || i == end || !self.blocked[i]
+            }
+        };
+        let guess = |i: usize| {
+            let (c, r) = (i % self.columns, i / self.columns);
+            let (ec, er) = (end % self.columns, end / self.columns);
+            let (dx, dy) = ((c as f64 - ec as f64).abs(), (r as f64 - er as f64).abs());
+            dx + dy + (SQRT_2 - 2.0) * dx.min(dy)
+        };
+
+        let mut so_far = vec![::std::f64::INFINITY; self.blocked.len()];
+        let mut came_from = vec![None; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        so_far[start] = 0.0;
+        open.push(Open { cost: guess(start), index: start });
+        while let Some(Open { cost, index: i }) = open.pop() {
+            if i == end { break }
+            // Already got here a shorter way since this went on the heap.
+            if cost > so_far[i] + guess(i) { continue }
+            let (c, r) = ((i % self.columns) as isize, (i / self.columns) as isize);
+            for dr in -1..2 {
+                for dc in -1..2 {
+                    if (dc, dr) == (0, 0) || !free(c + dc, r + dr) { continue }
+                    // No cutting corners.
+                    if dc != 0 && dr != 0 && !(free(c + dc, r) && free(c, r + dr)) { continue }
+                    let next = (r + dr) as usize * self.columns + (c + dc) as usize;
+                    let step = if dc != 0 && dr != 0 { SQRT_2 } else { 1.0 };
+                    if so_far[i] + step < so_far[next] {
+                        so_far[next] = so_far[i] + step;
+                        came_from[next] = Some(i);
+                        open.push(Open { cost: so_far[next] + guess(next), index: next });
+                    }
+                }
+            }
+        }
+        if start != end && came_from[end].is_none() { return None }
+
+        let mut cells = Vec::new();
+        let mut at = end;
+        while at != start {
+            cells.push(at);
+            at = came_from[at]?;
+        }
+        let mut waypoints: Vec<(f64, f64)> = cells.into_iter().rev()
+            .map(|i| self.middle(i % self.columns, i / self.columns))
+            .collect();
+        waypoints.pop();
+        waypoints.push(to);
+        Some(waypoints)
+    }
+}
+
+/*
+ * Whether something `size` across could go in a straight line from `from`
+ * to `to` (its middle, that is) without bumping into any of `walls`. It's
+ * checked every half a size along the way, which is close enough together
+ * that no wall fits in between.
+ */
+pub fn line_clear(from: (f64, f64), to: (f64, f64), size: f64, walls: &Quadtree) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = (dx.hypot(dy) / (size / 2.0)).ceil().max(1.0) as usize;
+    (0..steps + 1).all(|n| {
+        let t = n as f64 / steps as f64;
+        let (x, y) = (from.0 + dx * t, from.1 + dy * t);
+        walls.query(&Aabb::new(x - size / 2.0, y - size / 2.0, size, size)).is_empty()
+    })
+}
+
+// `route` from `from`, with every waypoint that can be seen past taken out.
+pub fn smooth(from: (f64, f64), route: Vec<(f64, f64)>, size: f64,
+              walls: &Quadtree) -> Vec<(f64, f64)> {
+    let mut smoothed = Vec::new();
+    let mut at = from;
+    let mut i = 0;
+    while i < route.len() {
+        // The furthest one we can see from here: there's always the next.
+        let furthest = (i + 1..route.len()).rev()
+            .find(|&j| line_clear(at, route[j], size, walls))
+            .unwrap_or(i);
+        smoothed.push(route[furthest]);
+        at = route[furthest];
+        i = furthest + 1;
+    }
+    smoothed
+}
+
+// The route each enemy that has one is following.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Paths {
+    routes: Vec<(Id, Vec<(f64, f64)>)>,
+}
+
+impl Paths {
+    pub fn new() -> Paths {
+        Paths::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.routes.clear();
+    }
+
+    pub fn set(&mut self, id: Id, route: Vec<(f64, f64)>) {
+        self.routes.retain(|&(other, _)| other != id);
+        self.routes.push((id, route));
+    }
+
+    // The waypoints `id` has still to get to, if it's following a route.
+    pub fn route(&self, id: Id) -> Option<&[(f64, f64)]> {
+        self.routes.iter().find(|&&(other, _)| other == id).map(|&(_, ref route)| &route[..])
+    }
+
+    pub fn all(&self) -> &[(Id, Vec<(f64, f64)>)] {
+        &self.routes
+    }
+
+    // Where `id`, now `at`, should head next: the first waypoint it's not at
+    // yet. None once it's been all the way along, or hasn't got a route.
+    pub fn next(&mut self, id: Id, at: (f64, f64)) -> Option<(f64, f64)> {
+        let position = self.routes.iter().position(|&(other, _)| other == id)?;
+        let route = &mut self.routes[position].1;
+        while let Some(&(x, y)) = route.first() {
+            if (x - at.0).hypot(y - at.1) > ARRIVED { return Some((x, y)) }
+            route.remove(0);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::{Collider, Kind, Parts, Position, Scene};
+
+    // A wall down the middle of a 10 by 10 grid of 10s, with a gap at the
+    // bottom.
+    fn walled() -> (Grid, Vec<Aabb>) {
+        let wall = vec![Aabb::new(50.0, 0.0, 10.0, 80.0)];
+        (Grid::new(100.0, 100.0, 10.0, &wall), wall)
+    }
+
+    #[test]
+    fn the_grid_knows_where_the_walls_are() {
+        let (grid, _) = walled();
+        assert_eq!((grid.columns, grid.rows), (10, 10));
+        assert!(grid.blocked(5, 0) && grid.blocked(5, 7));
+        // Touching isn't being in.
+        assert!(!grid.blocked(4, 0) && !grid.blocked(6, 0) && !grid.blocked(5, 8));
+        assert_eq!(grid.cell_of((-5.0, 1000.0)), (0, 9));
+    }
+
+    #[test]
+    fn goes_round_the_wall() {
+        let (grid, wall) = walled();
+        let route = grid.route((15.0, 15.0), (85.0, 15.0)).unwrap();
+        assert_eq!(route.last(), Some(&(85.0, 15.0)));
+        // Down through the gap, and never through the wall.
+        assert!(route.iter().any(|&(_, y)| y > 80.0));
+        assert!(route.iter().all(|&(x, y)| !wall[0].intersects(&Aabb::new(x, y, 0.1, 0.1))));
+        // Smoothed, it's just the corners and the end.
+        let smooth = smooth((15.0, 15.0), route.clone(), 4.0, &Quadtree::new(wall));
+        assert!(smooth.len() < route.len());
+        assert_eq!(smooth.last(), Some(&(85.0, 15.0)));
+    }
+
+    #[test]
+    fn no_way_through_is_no_route() {
+        let wall = vec![Aabb::new(50.0, 0.0, 10.0, 100.0)];
+        let grid = Grid::new(100.0, 100.0, 10.0, &wall);
+        assert_eq!(grid.route((15.0, 15.0), (85.0, 15.0)), None);
+        assert_eq!(grid.route((15.0, 15.0), (16.0, 17.0)), Some(vec![(16.0, 17.0)]));
+        assert!(!line_clear((15.0, 15.0), (85.0, 15.0), 4.0, &Quadtree::new(wall.clone())));
+        assert!(line_clear((15.0, 15.0), (15.0, 85.0), 4.0, &Quadtree::new(wall)));
+    }
+
+    #[test]
+    fn waypoints_get_ticked_off() {
+        let mut scene = Scene::new();
+        let id = scene.spawn(Parts {
+            position: Some(Position { x: 0.0, y: 0.0 }),
+            collider: Some(Collider { w: 1.0, h: 1.0, kind: Kind::Enemy }),
+            ..Parts::default()
+        });
+        let mut paths = Paths::new();
+        paths.set(id, vec![(10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!(paths.next(id, (0.0, 0.0)), Some((10.0, 0.0)));
+        assert_eq!(paths.next(id, (10.0, 0.5)), Some((10.0, 10.0)));
+        assert_eq!(paths.route(id), Some(&[(10.0, 10.0)][..]));
+        assert_eq!(paths.next(id, (10.0, 10.0)), None);
+    }
+}
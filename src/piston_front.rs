@@ -0,0 +1,122 @@
+/*
+ * The game again, on piston_window. piston_window is piston's "batteries
+ * included" crate: a window (glutin's), OpenGL drawing through gfx rather
+ * than opengl_graphics, and the event loop, all in one PistonWindow, which
+ * is how most piston examples you'll find are written. Build with
+ *
+ *     cargo run --features piston_front
+ *
+ * and main.rs calls this run instead of game::run.
+ *
+ * It's here to show off how little of the game cares what it's running
+ * on. prepare does all the setting up, the same as ever, and then this
+ * opens a window its own way and feeds the same Game the same Events
+ * (engine::event turns piston_window's into ours just like game::run's),
+ * so App, Player and the rest are exactly the code the usual game runs.
+ * Drawing goes through Game::render_on, which takes any graphics back end
+ * that's a Renderer too, so all it needs from us is a Renderer for G2d,
+ * piston_window's back end, below.
+ *
+ * What it goes without: our pictures and fonts are opengl_graphics's, and
+ * G2d can't draw those, so there are no pictures or HUD text (the title
+ * bar still says how you're doing), like in a browser. Screenshots, clips
+ * and the post-processing talk to OpenGL directly, which gfx keeps to itself, so
+ * those are out too, and so is F11: going fullscreen means a new window,
+ * and a PistonWindow is made to last.
+ */
+use graphics::{self, Context};
+use opengl_graphics::Texture;
+use piston_window::{EventLoop, G2d, PistonWindow, Window, WindowSettings};
+
+use engine::{self, Event, GlyphCache};
+use game;
+use renderer::Renderer;
+
+// The shapes go the same way as on a Gl (see renderer.rs); pictures and
+// text are left out, as above.
+impl<'a> Renderer for G2d<'a> {
+    fn clear(&mut self, _: &Context, color: [f32; 4]) {
+        graphics::clear(color, self);
+    }
+
+    fn rect(&mut self, context: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]) {
+        graphics::rectangle(color, [x, y, w, h], context.transform, self);
+    }
+
+    fn ellipse(&mut self, context: &Context, b: [f64; 4], color: [f32; 4]) {
+        graphics::ellipse(color, b, context.transform, self);
+    }
+
+    fn polygon(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        graphics::polygon(color, points, context.transform, self);
+    }
+
+    fn image(&mut self, _: &Context, _: &Texture, _: Option<[i32; 4]>) {}
+
+    fn text(&mut self, _: &Context, _: &str, _: u32, _: [f32; 4], _: &mut GlyphCache) {}
+}
+
+// Set the window's loop going at `pace`: see Game::pace.
+fn set_pace(window: &mut PistonWindow, pace: engine::Pace) {
+    window.set_ups(pace.0);
+    window.set_max_fps(pace.1);
+    window.set_bench_mode(pace.2);
+}
+
+pub fn run() {
+    let mut game = match game::prepare() {
+        Some(game) => game,
+        None => return,
+    };
+
+    let config = game.settings.config.clone();
+    info!(target: Render, "Opening a window with piston_window");
+    let settings = WindowSettings::new(config.window_title.clone(),
+                                       [config.window_width, config.window_height])
+        .fullscreen(game.fullscreen)
+        .vsync(config.vsync);
+    let mut window: PistonWindow = match settings.build() {
+        Ok(window) => window,
+        Err(e) => {
+            error!(target: Render, "Couldn't open a window: {}", e);
+            return;
+        }
+    };
+    let size = window.size();
+    info!(target: Render, "The window's open, {} by {}", size.width, size.height);
+    game.resize(size.width, size.height);
+    game.opened(false);
+
+    let mut pace = game.pace();
+    let fullscreen = game.fullscreen;
+    set_pace(&mut window, pace);
+    // Much like game::run's loop, but a frame is drawn with draw_2d, which
+    // gets G2d ready and hands it over.
+    while let Some(e) = window.next() {
+        match engine::event(&e) {
+            Event::Render(r) => {
+                game.set_title(&mut window);
+                window.draw_2d(&e, |_, g, _| game.render_on(&r, g));
+                if game.screenshot_requested {
+                    game.screenshot_requested = false;
+                    warn!("There are no screenshots with piston_window");
+                }
+            }
+            event => game.handle_event(&event),
+        }
+
+        if game.quit { break }
+        if game.pace() != pace {
+            pace = game.pace();
+            set_pace(&mut window, pace);
+        }
+        if game.fullscreen != fullscreen {
+            warn!(target: Render, "piston_window's window can't go fullscreen (or back)");
+            game.fullscreen = fullscreen;
+        }
+    }
+    game.write_replay();
+    game.save_achievements();
+    game.end_session();
+    info!("Bye!");
+}
@@ -0,0 +1,193 @@
+/*
+ * The player: where they are, how fast they're going, how big their expand
+ * is and which keys they're holding. There can be two of them (see --coop
+ * and --host), so this is everything one of them has that the other
+ * doesn't. What they get up to each tick is App's business, in app.rs,
+ * since that needs to know about the walls and the enemies and everything
+ * else as well.
+ */
+use collision::{Aabb, Body};
+use controls::{Facing, InputState};
+use tween::{Easing, Tween};
+
+// Here's the player struct declaration. Everything in it is pub, since
+// App over in app.rs is forever reaching in to move the player about; in
+// rust, fields are private to their module unless we say otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub x: f64,
+    pub y: f64,
+    // How fast the player is going, in world units per second. Every bit of
+    // movement happens by way of these, so nothing moves by more in a
+    // second at 144 updates per second than it does at 30.
+    pub vx: f64,
+    pub vy: f64,
+    // Which way the player last moved. It sticks when you let go of the
+    // keys, so you never face "nowhere".
+    pub facing: Facing,
+    // How far Q and E have turned them, in radians clockwise, from the way
+    // they're drawn: pointing straight up. See controls::angle_vector.
+    pub angle: f64,
+    // How puffed up they are: the expand power. Space for player one. The
+    // tween is it shrinking back down again (see shrink_expand), and
+    // squash is the wobble after puffing up (see squash_stretch).
+    pub expand: f64,
+    pub expand_tween: Option<Tween>,
+    pub squash: Option<Tween>,
+    // How big their square is, each way, before any growing: the config's
+    // player_size. And how big they are besides, as so many times that:
+    // the size power-up (see powerup.rs) makes it more than 1.
+    pub base_size: f64,
+    pub size: f64,
+    // Where they were before the last tick, for drawing in between.
+    pub previous: (f64, f64),
+    // Which of their keys are held, and (for player one) where the
+    // controller's stick is.
+    pub input: InputState,
+}
+
+impl Player {
+    // A player standing still at (x, y), facing the screen.
+    pub fn at(x: f64, y: f64) -> Player {
+        Player { x: x, y: y, vx: 0.0, vy: 0.0, facing: Facing::Down, angle: 0.0, expand: 0.0,
+                 expand_tween: None, squash: None, base_size: PLAYER_SIZE, size: 1.0,
+                 previous: (x, y), input: InputState::default() }
+    }
+
+    // Back to (x, y), as if they'd just arrived. What keys are held is
+    // left alone: they're still held, whatever happened to the level. So
+    // is how big they are, which is the config's business, not the level's.
+    pub fn respawn(&mut self, x: f64, y: f64) {
+        *self = Player { input: self.input, base_size: self.base_size, ..Player::at(x, y) };
+    }
+}
+
+// How big the player's square is, each way, when they haven't grown, unless
+// the config says otherwise.
+pub const PLAYER_SIZE: f64 = 10.0;
+
+/*
+ * What the player looks like, when there isn't a picture of them: "square"
+ * is the good old solid square, and "hollow" is just its edges. "circle" is
+ * the circle that fits in the square, and "polygon" a regular polygon with
+ * its corners on that circle, with however many sides the config says.
+ *
+ * It's not only the look, either. The square ones bump into things as a
+ * square, and the other two as the circle (see collision::Body): round
+ * players can get that bit closer to walls on the diagonal, and slip past
+ * corners a square one would catch on. A polygon's flat sides stop a little
+ * short of the circle, but with a few sides that's hard to see.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Square,
+    Hollow,
+    Circle,
+    Polygon,
+}
+
+impl Shape {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Shape::Square => "square",
+            Shape::Hollow => "hollow",
+            Shape::Circle => "circle",
+            Shape::Polygon => "polygon",
+        }
+    }
+
+    pub fn body(&self) -> Body {
+        match *self {
+            Shape::Square | Shape::Hollow => Body::Square,
+            Shape::Circle | Shape::Polygon => Body::Round,
+        }
+    }
+}
+
+// Every shape, in the order the options screen goes through them.
+pub const SHAPES: &'static [Shape] = &[Shape::Square, Shape::Hollow, Shape::Circle, Shape::Polygon];
+
+// How much bigger than that they are: their expand, and however much the
+// size power-up adds.
+pub fn grown_by(player: &Player) -> f64 {
+    player.base_size * (player.size - 1.0) + player.expand
+}
+
+// The player's square, grown and kept centred on the same spot.
+pub fn player_bounds(player: &Player) -> Aabb {
+    let grown = grown_by(player);
+    Aabb::new(player.x - (grown / 2.0),
+              player.y - (grown / 2.0),
+              grown + player.base_size,
+              grown + player.base_size)
+}
+
+/*
+ * Shrink expand back to zero at `decay` units per second, by way of a
+ * tween that takes expand / decay seconds to get there, `easing` the way
+ * down. Lots of things set expand besides the tween: Space, the console, a
+ * script, loading a game. So whenever expand isn't where the tween left it, it's been puffed up
+ * (or let down) since, and a fresh tween starts from wherever it is now.
+ * This is its own function so we can test it without a window.
+ */
+pub fn shrink_expand(player: &mut Player, decay: f64, easing: Easing, dt: f64) {
+    if player.expand_tween.map(|t| t.value()) != Some(player.expand) {
+        player.expand_tween = if player.expand > 0.0 && decay > 0.0 {
+            Some(Tween::new(player.expand, 0.0, player.expand / decay).eased(easing))
+        } else {
+            None
+        };
+    }
+    if let Some(ref mut tween) = player.expand_tween {
+        tween.update(dt);
+        player.expand = tween.value();
+    }
+}
+
+// How long the wobble after puffing up goes on for.
+pub const SQUASH_SECONDS: f64 = 0.6;
+
+// Puffing up: start the wobble, from the top. It's an elastic tween from 1
+// down to 0, so it goes past 0 and back a few times on the way, which is
+// the wobble.
+pub fn boing(player: &mut Player) {
+    player.squash = Some(Tween::new(1.0, 0.0, SQUASH_SECONDS).eased(Easing::Elastic));
+}
+
+// The wobble, moved on by `dt`, and gone once it's over.
+pub fn settle(player: &mut Player, dt: f64) {
+    if let Some(mut tween) = player.squash {
+        tween.update(dt);
+        player.squash = if tween.done() { None } else { Some(tween) };
+    }
+}
+
+/*
+ * How much wider and taller to draw the player than their box, as two
+ * scales, for squash and stretch: the old animators' trick for making
+ * things look soft and alive. They stretch out along the way they're going,
+ * by as much as `amount` at `top_speed`, and they wobble after puffing up,
+ * wider and flatter and then taller and thinner. Whichever it is, what it
+ * adds one way it takes off the other, so they stay about the same size.
+ * The scales go the way the player's drawn, which is turned by their angle,
+ * so the going is turned to match.
+ */
+pub fn squash_stretch(player: &Player, top_speed: f64, amount: f64) -> (f64, f64) {
+    if !(amount > 0.0) { return (1.0, 1.0) }
+    let wobble = 1.0 + player.squash.map_or(0.0, |t| t.value()) * amount;
+    let (mut wide, mut tall) = (wobble, 1.0 / wobble);
+    if top_speed > 0.0 {
+        let stretch = 1.0 + (player.vx.hypot(player.vy) / top_speed).min(1.0) * amount;
+        let (sin, cos) = (player.angle.sin(), player.angle.cos());
+        let (across, down) = (player.vx * cos + player.vy * sin, player.vy * cos - player.vx * sin);
+        if across.abs() >= down.abs() {
+            wide *= stretch;
+            tall /= stretch;
+        } else {
+            tall *= stretch;
+            wide /= stretch;
+        }
+    }
+    (wide, tall)
+}
@@ -0,0 +1,371 @@
+/*
+ * Post-processing: doing things to the frame once it's been drawn. Rather
+ * than straight onto the window, the game's drawn into a texture (an
+ * offscreen framebuffer, OpenGL calls somewhere to draw that isn't the
+ * window), and then that's drawn onto the window through a run of shaders,
+ * passes, each one taking what the one before it made. There are three
+ * effects, every one of them optional, and they go in this order:
+ *
+ * - Bloom, which makes the bright bits glow: the first pass keeps only
+ *   what's brighter than bloom_threshold and smears it sideways, the
+ *   second smears that up and down, and adds it back onto the frame as it
+ *   was drawn. Blurring across and then down is the same as blurring round
+ *   in a circle, for a lot fewer sums.
+ *
+ * - The flash, which whites the screen out when expand fires and fades
+ *   back over flash_seconds. App keeps how white it is now, as a Flash.
+ *
+ * - The user's own shader, if config.json has one (see shader.rs).
+ *
+ * Each pass draws into one of two more textures, turn and turn about,
+ * except the last, which draws onto the window. So adding another effect
+ * is another Step and another shader, and it fits in with the rest. If
+ * none of them have anything to do this frame, the game's drawn straight
+ * onto the window as it always was, and none of this happens.
+ *
+ * The textures are the size of the window in pixels, so they're made again
+ * the first frame after it changes size. Like shader.rs, this needs
+ * OpenGL 3.2; without it, there's a warning, and no effects.
+ */
+use std::ptr;
+
+use gl;
+use gl::types::{GLint, GLuint};
+
+use config::Config;
+use shader::{self, Program, Shader, ShaderTarget, Uniforms};
+
+// How far apart the pixels the blurs mix are, in pixels. Each one mixes
+// nine, the middle one most.
+const BLUR_SPACING: f32 = 2.0;
+
+const GLOW: &'static str = "uniform float threshold;
+uniform vec2 direction;
+const float weights[5] = float[](0.227, 0.195, 0.122, 0.054, 0.016);
+vec3 bright(vec2 at) {
+    vec3 c = texture(scene, at).rgb;
+    return max(c - vec3(threshold), vec3(0.0)) / max(1.0 - threshold, 0.001);
+}
+void main() {
+    vec3 sum = bright(uv) * weights[0];
+    for (int i = 1; i < 5; i++) {
+        vec2 along = direction * float(i);
+        sum += (bright(uv + along) + bright(uv - along)) * weights[i];
+    }
+    color = vec4(sum, 1.0);
+}
+";
+
+const BLOOM: &'static str = "uniform sampler2D original;
+uniform float amount;
+uniform vec2 direction;
+const float weights[5] = float[](0.227, 0.195, 0.122, 0.054, 0.016);
+void main() {
+    vec3 sum = texture(scene, uv).rgb * weights[0];
+    for (int i = 1; i < 5; i++) {
+        vec2 along = direction * float(i);
+        sum += (texture(scene, uv + along).rgb + texture(scene, uv - along).rgb) * weights[i];
+    }
+    color = vec4(texture(original, uv).rgb + sum * amount, 1.0);
+}
+";
+
+const FLASH: &'static str = "uniform float amount;
+void main() {
+    color = vec4(mix(texture(scene, uv).rgb, vec3(1.0), amount), 1.0);
+}
+";
+
+// What's underneath a shader that's only over the player.
+const COPY: &'static str = "void main() {
+    color = texture(scene, uv);
+}
+";
+
+/*
+ * The flash from expand. `amount` is how far to white the screen is, 0 to
+ * 1, and it goes down by the same amount every second, so it's gone in
+ * however many seconds it was fired with.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flash {
+    pub amount: f64,
+    fade: f64,
+}
+
+impl Flash {
+    pub fn fire(&mut self, strength: f64, seconds: f64) {
+        if strength <= 0.0 || seconds <= 0.0 { return }
+        self.amount = strength.min(1.0);
+        self.fade = self.amount / seconds;
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.amount = (self.amount - self.fade * dt).max(0.0);
+    }
+}
+
+// How much of each effect there is this frame; 0 is none.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Effects {
+    pub bloom: f32,
+    pub threshold: f32,
+    pub flash: f32,
+}
+
+impl Effects {
+    // From `config`, with the flash `flash` bright. Low distraction goes
+    // without the flash, which is the most distracting thing there is.
+    pub fn new(config: &Config, flash: f64) -> Effects {
+        Effects {
+            bloom: config.bloom.max(0.0) as f32,
+            threshold: config.bloom_threshold.max(0.0).min(1.0) as f32,
+            flash: if config.low_distraction { 0.0 } else { flash as f32 },
+        }
+    }
+}
+
+// The passes, as far as planning them goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    // Bloom's two halves.
+    Glow,
+    Bloom,
+    Flash,
+    // The user's shader.
+    Own,
+}
+
+// Which passes there are to do this frame, in order: nothing if none of
+// the effects are on.
+pub fn plan(effects: &Effects, shader: bool) -> Vec<Step> {
+    let mut steps = Vec::new();
+    if effects.bloom > 0.0 {
+        steps.push(Step::Glow);
+        steps.push(Step::Bloom);
+    }
+    if effects.flash > 0.0 {
+        steps.push(Step::Flash);
+    }
+    if shader {
+        steps.push(Step::Own);
+    }
+    steps
+}
+
+// Somewhere to draw that isn't the window: a framebuffer, with a texture
+// to draw into, which the next pass can then read.
+struct Target {
+    framebuffer: GLuint,
+    texture: GLuint,
+    size: (i32, i32),
+}
+
+impl Target {
+    fn new() -> Target {
+        let (mut framebuffer, mut texture) = (0, 0);
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            for &(parameter, value) in [(gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+                                        (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                                        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                                        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE)].iter() {
+                gl::TexParameteri(gl::TEXTURE_2D, parameter, value as GLint);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        Target { framebuffer: framebuffer, texture: texture, size: (0, 0) }
+    }
+
+    // Make it `size` pixels, if it isn't already. The graphics card gets
+    // a say in whether it can be drawn into, and if not, that's an error.
+    fn resize(&mut self, size: (i32, i32)) -> Result<(), String> {
+        if self.size == size { return Ok(()) }
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA8 as GLint, size.0, size.1, 0, gl::RGBA,
+                           gl::UNSIGNED_BYTE, ptr::null());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D,
+                                     self.texture, 0);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("can't draw into a {} by {} texture (status {:#x})",
+                                   size.0, size.1, status));
+            }
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    fn delete(self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+/*
+ * The lot: the texture the game's drawn into, the two the passes take
+ * turns with, and the passes' shaders. Like a Program, it belongs to the
+ * window it was made in.
+ */
+pub struct Pipeline {
+    scene: Target,
+    front: Target,
+    back: Target,
+    glow: Program,
+    bloom: Program,
+    flash: Program,
+    copy: Program,
+    // What begin planned for this frame, for finish to do.
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    pub fn new() -> Result<Pipeline, String> {
+        let make = |name: &str, text: &str| {
+            Program::new(&shader::source(text)).map_err(|e| format!("{}: {}", name, e))
+        };
+        Ok(Pipeline {
+            glow: make("glow", GLOW)?,
+            bloom: make("bloom", BLOOM)?,
+            flash: make("flash", FLASH)?,
+            copy: make("copy", COPY)?,
+            scene: Target::new(),
+            front: Target::new(),
+            back: Target::new(),
+            steps: Vec::new(),
+        })
+    }
+
+    /*
+     * Get ready for a frame `pixels` big, with `effects`, and the user's
+     * shader if `shader`. If there's anything to do, the game gets drawn
+     * into the scene texture from here on, until finish; if not, it's the
+     * window as usual.
+     */
+    pub fn begin(&mut self, pixels: [u32; 2], effects: &Effects, shader: bool)
+                 -> Result<(), String> {
+        self.steps = plan(effects, shader);
+        if self.steps.is_empty() { return Ok(()) }
+        let size = (pixels[0] as i32, pixels[1] as i32);
+        for target in [&mut self.scene, &mut self.front, &mut self.back].iter_mut() {
+            if let Err(e) = target.resize(size) {
+                self.steps.clear();
+                return Err(e);
+            }
+        }
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.scene.framebuffer) }
+        Ok(())
+    }
+
+    /*
+     * Do the passes begin planned, onto the window in the end. `uniforms`
+     * are for the user's shader, and `player` is the corners of the box
+     * round the player, if there's one, for a shader that's only for them.
+     */
+    pub fn finish(&mut self, effects: &Effects, shader: Option<&Shader>, uniforms: &Uniforms,
+                  player: Option<[[f32; 2]; 4]>) {
+        if self.steps.is_empty() { return }
+        let everywhere = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]];
+        let (w, h) = self.scene.size;
+        let mut input = self.scene.texture;
+        for (i, &step) in self.steps.iter().enumerate() {
+            let output = if i + 1 == self.steps.len() { None }
+                         else if i % 2 == 0 { Some(&self.front) }
+                         else { Some(&self.back) };
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, output.map_or(0, |t| t.framebuffer));
+                gl::Viewport(0, 0, w, h);
+            }
+            match step {
+                Step::Glow => {
+                    self.glow.bind();
+                    self.glow.texture("scene", 0, input);
+                    self.glow.float("threshold", effects.threshold);
+                    self.glow.vec2("direction", [BLUR_SPACING / w as f32, 0.0]);
+                    self.glow.draw(&everywhere);
+                }
+                Step::Bloom => {
+                    self.bloom.bind();
+                    self.bloom.texture("scene", 0, input);
+                    self.bloom.texture("original", 1, self.scene.texture);
+                    self.bloom.float("amount", effects.bloom);
+                    self.bloom.vec2("direction", [0.0, BLUR_SPACING / h as f32]);
+                    self.bloom.draw(&everywhere);
+                }
+                Step::Flash => {
+                    self.flash.bind();
+                    self.flash.texture("scene", 0, input);
+                    self.flash.float("amount", effects.flash);
+                    self.flash.draw(&everywhere);
+                }
+                Step::Own => {
+                    let shader = match shader {
+                        Some(shader) => shader,
+                        None => continue,
+                    };
+                    let corners = match (shader.target, player) {
+                        (ShaderTarget::Scene, _) => Some(everywhere),
+                        (ShaderTarget::Player, corners) => {
+                            self.copy.bind();
+                            self.copy.texture("scene", 0, input);
+                            self.copy.draw(&everywhere);
+                            corners
+                        }
+                    };
+                    if let Some(corners) = corners {
+                        shader.apply(uniforms, &corners, input);
+                    }
+                }
+            }
+            input = output.map_or(0, |t| t.texture);
+        }
+        self.steps.clear();
+    }
+
+    pub fn delete(self) {
+        for program in vec![self.glow, self.bloom, self.flash, self.copy] {
+            program.delete();
+        }
+        for target in vec![self.scene, self.front, self.back] {
+            target.delete();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_flash_fades_out() {
+        let mut flash = Flash::default();
+        flash.fire(0.5, 0.25);
+        assert_eq!(flash.amount, 0.5);
+        flash.update(0.125);
+        assert_eq!(flash.amount, 0.25);
+        flash.update(1.0);
+        assert_eq!(flash.amount, 0.0);
+        // Nothing to fire with, nothing happens.
+        flash.fire(0.5, 0.0);
+        assert_eq!(flash.amount, 0.0);
+    }
+
+    #[test]
+    fn only_the_effects_that_are_on_get_a_pass() {
+        let mut config = Config::default();
+        config.bloom = 0.0;
+        assert!(plan(&Effects::new(&config, 0.0), false).is_empty());
+        assert_eq!(plan(&Effects::new(&config, 0.3), true), vec![Step::Flash, Step::Own]);
+        config.bloom = 0.5;
+        config.low_distraction = true;
+        assert_eq!(plan(&Effects::new(&config, 0.3), false), vec![Step::Glow, Step::Bloom]);
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Power-ups. Expand was the first thing that changed the player for a
+ * while and then wore off; these are the rest of them. They're dotted
+ * about a level like pickups, but instead of a point, touching one gives
+ * you a power for power_up_seconds (see config.rs):
+ *
+ *     speed         you walk (and bounce) speed_boost times as fast
+ *     size          you're size_boost times as big, which is handy for
+ *                   reaching the goal and less so for squeezing past walls
+ *     invulnerable  enemies can't hurt you
+ *
+ * While one's on, you're drawn in its color, the same color as the
+ * power-up itself, and it blinks for the last second so you know it's
+ * about to run out. The HUD counts down how long each has left.
+ *
+ * The powers in effect are a list on App, shared by both players in
+ * co-op the way health and score are (someone picking up invulnerable is
+ * no use if their friend still gets eaten). Picking up one you've already
+ * got doesn't stack; it starts the clock again.
+ *
+ * In a level file they go in "power_ups", like this:
+ *
+ *     "power_ups": [{ "effect": "speed", "at": [400.0, 300.0] }]
+ */
+use graphics::Context;
+
+use collision::Aabb;
+use entity::{Entity, Status, WorldInfo, PICKUP_SIZE};
+use renderer::Renderer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Speed,
+    Size,
+    Invulnerable,
+}
+
+impl Effect {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Effect::Speed => "speed",
+            Effect::Size => "size",
+            Effect::Invulnerable => "invulnerable",
+        }
+    }
+}
+
+// A power-up in a level file, or in a save.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spot {
+    pub effect: Effect,
+    pub at: [f64; 2],
+}
+
+// A power in effect, and how many seconds it has left.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Active {
+    pub effect: Effect,
+    pub left: f64,
+}
+
+// Give `effect` for `seconds`: to the end of the list, as it's the newest,
+// and instead of any of the same effect that's already there.
+pub fn grant(active: &mut Vec<Active>, effect: Effect, seconds: f64) {
+    active.retain(|a| a.effect != effect);
+    active.push(Active { effect: effect, left: seconds });
+}
+
+// `dt` seconds go by. Hands back the powers that have just worn off.
+pub fn tick(active: &mut Vec<Active>, dt: f64) -> Vec<Effect> {
+    let mut ended = Vec::new();
+    for a in active.iter_mut() {
+        a.left -= dt;
+        if a.left <= 0.0 {
+            ended.push(a.effect);
+        }
+    }
+    active.retain(|a| a.left > 0.0);
+    ended
+}
+
+pub fn has(active: &[Active], effect: Effect) -> bool {
+    active.iter().any(|a| a.effect == effect)
+}
+
+/*
+ * The one on the map. It's a pickup as far as App's concerned (collectible,
+ * not solid, and gone once touched), only it says which power it gives,
+ * which is how App knows not to give a point for it. It's drawn as a square
+ * the size of a pickup with a smaller one cut out of the middle, so it
+ * doesn't look like one.
+ */
+pub struct PowerUp {
+    pub bounds: Aabb,
+    pub effect: Effect,
+    pub color: [f32; 4],
+}
+
+impl PowerUp {
+    // Centred on (x, y), like a pickup.
+    pub fn at(x: f64, y: f64, effect: Effect, color: [f32; 4]) -> PowerUp {
+        let half = PICKUP_SIZE / 2.0;
+        PowerUp {
+            bounds: Aabb::new(x - half, y - half, PICKUP_SIZE, PICKUP_SIZE),
+            effect: effect,
+            color: color,
+        }
+    }
+}
+
+impl Entity for PowerUp {
+    fn update(&mut self, _: f64, _: &WorldInfo, _: &mut Vec<Box<dyn Entity>>) -> Status {
+        Status::Alive
+    }
+
+    fn draw(&self, context: &Context, r: &mut dyn Renderer) {
+        let b = self.bounds;
+        r.outline(context, [b.x, b.y, b.w, b.h], 3.0, self.color);
+    }
+
+    fn bounds(&self) -> Aabb { self.bounds }
+
+    fn collectible(&self) -> bool { true }
+
+    fn power_up(&self) -> Option<Effect> { Some(self.effect) }
+
+    fn sprite(&self) -> Option<&'static str> { Some(self.effect.name()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn powers_wear_off() {
+        let mut active = Vec::new();
+        grant(&mut active, Effect::Speed, 1.0);
+        grant(&mut active, Effect::Size, 2.0);
+        assert_eq!(tick(&mut active, 0.5), vec![]);
+        assert_eq!(tick(&mut active, 0.5), vec![Effect::Speed]);
+        assert!(!has(&active, Effect::Speed) && has(&active, Effect::Size));
+        // Getting it again starts it over, rather than there being two.
+        grant(&mut active, Effect::Size, 2.0);
+        assert_eq!(active, vec![Active { effect: Effect::Size, left: 2.0 }]);
+    }
+
+    #[test]
+    fn spots_are_read_from_levels() {
+        let spot: Spot = serde_json::from_str(r#"{ "effect": "invulnerable", "at": [4, 5] }"#)
+            .unwrap();
+        assert_eq!(spot, Spot { effect: Effect::Invulnerable, at: [4.0, 5.0] });
+    }
+}
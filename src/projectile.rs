@@ -0,0 +1,157 @@
+/*
+ * Things the player shoots. A projectile flies in a straight line at a
+ * steady speed until it hits something or runs out of time: a wall just
+ * stops it, and an enemy stops it and loses some health, which is the end
+ * of it if that was the last of its health.
+ *
+ * A projectile is an entity in the Scene (see ecs.rs) with a Position, a
+ * Velocity, a Renderable, a Collider of Kind::Shot and a Lifetime. The
+ * movement and ageing systems take care of the flying and the running out;
+ * all that's left for here is the hitting.
+ */
+use collision::{span, Aabb, Quadtree};
+use ecs::{Collider, Id, Kind, Lifetime, Parts, Position, Renderable, Scene, Velocity};
+
+// How big a projectile is, each way.
+pub const PROJECTILE_SIZE: f64 = 4.0;
+
+// A projectile centred on `from` heading along `direction` (one unit long)
+// at `speed`.
+pub fn fire(scene: &mut Scene, from: (f64, f64), direction: (f64, f64), speed: f64,
+            lifetime: f64, color: [f32; 4]) -> Id {
+    scene.spawn(Parts {
+        position: Some(Position { x: from.0 - PROJECTILE_SIZE / 2.0,
+                                  y: from.1 - PROJECTILE_SIZE / 2.0 }),
+        velocity: Some(Velocity { vx: direction.0 * speed, vy: direction.1 * speed }),
+        renderable: Some(Renderable { w: PROJECTILE_SIZE, h: PROJECTILE_SIZE, color: color }),
+        collider: Some(Collider { w: PROJECTILE_SIZE, h: PROJECTILE_SIZE, kind: Kind::Shot }),
+        lifetime: Some(Lifetime { age: 0.0, limit: lifetime }),
+        ..Parts::default()
+    })
+}
+
+/*
+ * The hitting system: sort out what every projectile is about to hit, and
+ * get rid of it, taking `damage` off any enemy it hits. Returns where the
+ * enemies that got knocked out were, so the caller can make a fuss about
+ * them (App puts an EnemyKilled on the bus for each; see bus.rs). This
+ * goes just before movement, so it looks along the path each one's about
+ * to fly this tick.
+ *
+ * Projectiles are small and fast, so checking where one ends up isn't
+ * enough; it could skip clean over a thin wall between one update and the
+ * next (see collision.rs). Instead we check the box covering everywhere it
+ * goes this update, from where it is to where it'll be. For a projectile
+ * going diagonally that box is a bit bigger than the path it really takes,
+ * but at four pixels across nobody's going to notice.
+ *
+ * The enemies go in a quadtree first, and so do the `walls` (App makes
+ * theirs every tick), so a shot only gets checked against what's near it.
+ * The quadtree hands back what it finds in the order we gave it them,
+ * which is slot order, so a shot that could hit two enemies at once picks
+ * the same one it always did.
+ */
+pub fn hit_all(scene: &mut Scene, walls: &Quadtree, damage: u32, dt: f64) -> Vec<Aabb> {
+    let enemies: Vec<(Id, Aabb)> = scene.of_kind(Kind::Enemy).into_iter()
+        .filter_map(|e| scene.bounds(e).map(|b| (e, b)))
+        .collect();
+    let tree = Quadtree::new(enemies.iter().map(|&(_, b)| b).collect());
+    let mut knocked_out = Vec::new();
+    for shot in scene.of_kind(Kind::Shot) {
+        let (b, v) = match (scene.bounds(shot), scene.velocities[shot.index]) {
+            (Some(b), Some(v)) => (b, v),
+            _ => continue,
+        };
+        let path = span(b, Aabb::new(b.x + v.vx * dt, b.y + v.vy * dt, b.w, b.h));
+
+        // One that's been knocked out already this tick isn't alive.
+        let target = tree.query(&path).into_iter().map(|i| enemies[i])
+            .find(|&(e, _)| scene.alive(e));
+        if let Some((enemy, where_it_was)) = target {
+            // Knocked out enemies go later, so that the next shot along
+            // doesn't hit it too.
+            if scene.damage(enemy, damage) {
+                knocked_out.push(where_it_was);
+            }
+            scene.despawn_later(shot);
+        } else if !walls.query(&path).is_empty() {
+            scene.despawn_later(shot);
+        }
+    }
+    knocked_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::{Aabb, Quadtree};
+    use ecs::{self, Kind, Scene};
+    use enemy;
+
+    fn shot(scene: &mut Scene) -> Id {
+        fire(scene, (0.0, 0.0), (1.0, 0.0), 100.0, 1.0, [1.0; 4])
+    }
+
+    // One tick of the systems a projectile cares about, in the order App
+    // runs them.
+    fn tick(scene: &mut Scene, obstacles: &[Aabb], dt: f64) -> usize {
+        let hit = hit_all(scene, &Quadtree::new(obstacles.to_vec()), 1, dt).len();
+        ecs::movement(scene, dt);
+        ecs::ageing(scene, dt);
+        scene.flush();
+        hit
+    }
+
+    #[test]
+    fn flies_then_runs_out() {
+        let mut scene = Scene::new();
+        let id = shot(&mut scene);
+        tick(&mut scene, &[], 0.5);
+        assert_eq!(scene.bounds(id).map(|b| b.x), Some(48.0));
+        tick(&mut scene, &[], 0.5);
+        assert_eq!(scene.count(Kind::Shot), 0);
+    }
+
+    #[test]
+    fn cannot_skip_over_a_thin_wall() {
+        let wall = Aabb::new(30.0, -50.0, 2.0, 100.0);
+        let mut scene = Scene::new();
+        shot(&mut scene);
+        // One update takes it from x = -2 to x = 48, right over the wall.
+        tick(&mut scene, &[wall], 0.5);
+        assert_eq!(scene.count(Kind::Shot), 0);
+    }
+
+    #[test]
+    fn two_shots_at_one_enemy_only_get_it_once() {
+        let mut scene = Scene::new();
+        shot(&mut scene);
+        let second = shot(&mut scene);
+        enemy::spawn(&mut scene, 40.0, 0.0, [0.0; 4]);
+        assert_eq!(tick(&mut scene, &[], 0.5), 1);
+        // The second sails on through where the enemy was.
+        assert_eq!(scene.of_kind(Kind::Shot), vec![second]);
+    }
+
+    #[test]
+    fn hitting_an_enemy_gets_rid_of_both() {
+        let mut scene = Scene::new();
+        shot(&mut scene);
+        enemy::spawn(&mut scene, 40.0, 0.0, [0.0; 4]);
+        let missed = enemy::spawn(&mut scene, 40.0, 200.0, [0.0; 4]);
+        assert_eq!(tick(&mut scene, &[], 0.5), 1);
+        assert_eq!(scene.count(Kind::Shot), 0);
+        assert_eq!(scene.of_kind(Kind::Enemy), vec![missed]);
+    }
+
+    #[test]
+    fn tough_enemies_take_more_than_one_shot() {
+        let mut scene = Scene::new();
+        shot(&mut scene);
+        shot(&mut scene);
+        enemy::spawn_with_health(&mut scene, 40.0, 0.0, [0.0; 4], 2);
+        // Both shots hit it this time, and it takes both.
+        assert_eq!(tick(&mut scene, &[], 0.5), 1);
+        assert_eq!(scene.count(Kind::Shot) + scene.count(Kind::Enemy), 0);
+    }
+}
@@ -0,0 +1,697 @@
+/*
+ * Drawing a frame. This used to be the middle of main.rs, and it's still a
+ * method on App: `impl App` can be split over as many files as we like, as
+ * long as they're all in the same crate, so this one gets a file of its own
+ * without App having to know.
+ */
+/*
+ * Context is the in memory representation of our drawable area. I might call
+ * this 'canvas' in future work to help my brain analogize it to HTML5
+ * or Processing style canvases.
+ * Transformed lets us move and scale it about, and ImageSize tells
+ * us how big a picture is to begin with. The actual drawing of rectangles
+ * and pictures on it goes through our own Renderer trait (see renderer.rs),
+ * which is what does the work of drawing our beautiful masterpieces.
+ */
+use graphics::{
+    Context,
+    Transformed,
+    ImageSize,
+};
+use renderer::{regular_polygon, Renderer};
+use animation;
+use app::{App, LevelState};
+use assets::Assets;
+use collision::{Aabb, Body};
+use cutscene;
+use ecs;
+use entity;
+use frametime;
+use handlers;
+use hud::{self, Hud};
+use inventory;
+use macros;
+use minimap::{self, Minimap};
+use object;
+use palette::faded;
+use player::{self, Shape};
+use text::{Align, Text};
+use ui::Ui;
+use units::WorldPos;
+use world::{self, WorldMode};
+
+// How far apart the lines of the debug grid are, in world units.
+const DEBUG_GRID: f64 = 100.0;
+// And what color everything in the debug drawing is: see draw_debug.
+const DEBUG_GRID_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+const DEBUG_SOLID_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+const DEBUG_PICKUP_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const DEBUG_ENEMY_COLOR: [f32; 4] = [1.0, 0.5, 0.0, 1.0];
+const DEBUG_PLAYER_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+const DEBUG_GOAL_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const DEBUG_QUADTREE_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 0.5];
+const DEBUG_PATH_COLOR: [f32; 4] = [1.0, 0.5, 0.0, 0.6];
+const DEBUG_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// How far apart the dots of the mouse's aiming line are, in world pixels,
+// and how see-through the line and its cross are.
+const AIM_DOT_SPACING: f64 = 12.0;
+const AIM_ALPHA: f32 = 0.5;
+
+impl App {
+    /*
+     * Paused, the game is dimmed behind a see-through black sheet, and
+     * says so across the middle, with buttons under it to carry on or go to
+     * the options (which P and O do too). It's still all there to look at,
+     * which is the point of step mode.
+     */
+    pub fn pause_menu(&self) -> Ui {
+        let (w, h) = self.view_size;
+        let locale = &self.settings.locale;
+        let mut ui = Ui::new(w, h, self.config.hud_font_size, &self.config.palette(), None);
+        ui.panel(Aabb::new(0.0, 0.0, w, h), 0.5);
+        ui.y = h / 2.0;
+        ui.title(locale.get("hud.paused"));
+        ui.button(locale.get("pause.carry_on"));
+        ui.button(locale.get("pause.options"));
+        ui
+    }
+
+    // The render method is what we call to draw a frame. If I understand
+    // correctly, the event loop ensures that this is done 60 times per
+    // second.
+    // The pictures and the font come from Game, which keeps them from one
+    // App to the next. It all gets drawn on `r`, which is the window's Gl
+    // when there's a window; see renderer.rs.
+    // How big to draw comes from the last resize event (see resize_window)
+    // rather than from the RenderArgs, so everything agrees on the size:
+    // the drawing here, the camera, and the clamping in update.
+    // Every color comes from the palette (see palette.rs), so one setting
+    // changes the lot.
+    pub fn render(&mut self, r: &mut dyn Renderer, assets: &Assets, hud: &mut Hud) {
+        self.frames += 1;
+        let (w, h) = self.view_size;
+        let palette = self.config.palette();
+
+        /*
+         * We create a local variable to represent our canvas. Letterboxed,
+         * the whole window gets painted the palette's letterbox color
+         * (black, usually) for the bars, and then the canvas is moved and
+         * scaled to the box in the middle, so everything after this can
+         * carry on as if the window was exactly view_size.
+         */
+        let screen = Context::new_abs(self.window_size.0, self.window_size.1);
+        let context = &if self.config.letterbox {
+            r.clear(&screen, palette.letterbox);
+            let letterbox = self.letterboxing();
+            screen.trans(letterbox.x, letterbox.y).zoom(letterbox.scale)
+        } else {
+            screen
+        };
+        /*
+         * This part is kinda cool; The context struct carries a set of fluent
+         * methods; that is, methods that return an object of the same type.
+         * We're able to chain methods on the context to pull together a 
+         * representation of what we want to put on the canvas. Then we can
+         * call draw, passing in a mutable representation of the window to
+         * actually draw on.
+         */
+        /*
+         * Everything in the world gets drawn through the camera: world is
+         * the canvas shifted so that the camera's corner lands on the
+         * window's corner. Only the help overlay, which belongs to the
+         * window rather than the world, still uses the plain context.
+         * The screen shake goes in here too, since shaking is just the
+         * camera being knocked a little off where it should be. Reading
+         * the transforms right to left: shift the world so the camera's
+         * corner is at the origin, blow it up by the zoom, then give it a
+         * shake. The corner's snapped to the screen's pixels, so the world
+         * stays crisp as the camera glides about (see Camera::snap).
+         */
+        let (sx, sy) = self.shake.offset();
+        let (_, corner) = self.interpolated();
+        let (cx, cy) = self.camera.snap(corner, self.draw_scale);
+        let world = &context
+            .trans(sx, sy)
+            .zoom(self.camera.zoom)
+            .trans(-cx, -cy);
+
+        // Behind everything, the background. With low_distraction we simply
+        // paint the whole thing grey; the actual shade comes from the
+        // palette, so feel free to go wild. It's a rectangle the size of the
+        // view, rather than no shape at all (which paints the whole window),
+        // so the letterbox bars stay black. Otherwise it's the same color,
+        // but on the move (see background.rs), with the shapes going by
+        // slower than the world does as the camera moves.
+        let bg = palette.background;
+        if self.config.low_distraction {
+            r.rect(context, 0.0, 0.0, w, h, bg);
+        } else {
+            self.background.draw(context, r, (w, h), (cx, cy), bg);
+        }
+
+        // The ground goes down first of all, so everything else stands on
+        // it. Only the tiles the camera can see get drawn.
+        let view = Aabb::new(cx, cy, self.camera.view_width(), self.camera.view_height());
+        self.level().ground.draw(world, r, view);
+
+        // The goal goes next, so everything else is drawn over it. Once
+        // the level is complete it flashes - bright for a fifth of a second,
+        // dim for a fifth - which is `timer / 0.2` rounded down being even or
+        // odd. A picture of the goal can't be dimmed, so that flashes by
+        // not being there for the dim part.
+        let mut goal_color = palette.goal;
+        let dim = match self.level_state {
+            LevelState::Complete { timer } => ((timer / 0.2) as u32) % 2 == 1,
+            _ => false,
+        };
+        if dim {
+            goal_color[3] *= 0.3;
+        }
+        let g = self.goal;
+        if !assets.has_sprite("goal") {
+            r.rect(world, g.x, g.y, g.w, g.h, goal_color);
+        } else if !dim {
+            assets.sprite("goal", [g.x, g.y, g.w, g.h], world, r);
+        }
+
+        // Every entity knows how to draw itself, so we just ask them all,
+        // unless the atlas has a picture of it. The pickups are game
+        // objects, though (see below).
+        entity::draw_all(&self.entities, assets, world, r);
+        self.particles.draw(world, r);
+
+        // The projectiles are plain squares, in the scene. The enemies are
+        // plain blue squares too, but they're game objects.
+        ecs::render(&self.scene, assets, world, r);
+
+        // And then everyone that's a game object: the pickups, then the
+        // enemies, and the players on top of them all (see object.rs).
+        object::render_all(self, assets, world, r);
+
+        // The aim goes over everyone, so it doesn't get lost behind them.
+        let aim = faded(palette.text, AIM_ALPHA);
+        self.draw_aim(world, WorldPos::new(cx, cy), aim, r);
+
+        // Standing on someone with something to say, it says over their head
+        // how to get them to say it.
+        if let (Some(npc), None) = (self.talker(), self.dialogue.as_ref()) {
+            let b = npc.bounds();
+            let style = Text::new((self.config.hud_font_size * 3 / 4).max(8), palette.text)
+                            .align(Align::Center)
+                            .shadow(faded(palette.shade, 0.6));
+            let prompt = self.settings.locale.get("dialogue.talk");
+            hud.write(&style, prompt, b.x + b.w / 2.0, b.y - b.h / 2.0 - 4.0, world, r);
+        }
+
+        // F4's debug drawing goes over the top of the world, but under the
+        // HUD.
+        if self.show_debug {
+            self.draw_debug(world, view, r, hud);
+        }
+
+        // The HUD goes on top of the world, but it belongs to the window, so
+        // it's drawn on the plain context and stays put when the camera
+        // moves.
+        let status = hud::Status {
+            score: self.score,
+            health: self.health,
+            max_health: self.config.player_health,
+            lives: self.lives,
+            kills: self.knocked_out,
+            wave: self.waves.number,
+            position: (self.players[0].x, self.players[0].y),
+            expand: self.players[0].expand,
+            effects: self.effects.clone(),
+            frame_stats: if self.show_frame_stats { Some(self.frame_stats.clone()) } else { None },
+            macro_line: macros::status(&self.macros, &self.config, &self.settings.locale),
+        };
+        // It's shadowed, since the game behind it could be any color.
+        let style = Text::new(self.config.hud_font_size, palette.text)
+                        .shadow(faded(palette.shade, 0.6));
+        hud.draw(&status, &self.settings.locale, &style, w, context, r);
+
+        // F4 also says how full the pools are, over in the top-right corner.
+        if self.show_debug {
+            let style = Text::new((self.config.hud_font_size / 2).max(8), DEBUG_TEXT_COLOR)
+                            .align(Align::Right);
+            hud.draw_lines(&self.pool_stats(), &style, w - hud::MARGIN, hud::MARGIN, context, r);
+        }
+
+        // What you're carrying goes along the bottom, in the middle: each
+        // sort of item in a square, with how many, if it's more than one.
+        let style = Text::new((self.config.hud_font_size * 3 / 4).max(8), palette.text)
+                        .align(Align::Right)
+                        .shadow(faded(palette.shade, 0.6));
+        let y = h - hud::MARGIN - inventory::SLOT_SIZE;
+        for (b, color, count) in inventory::bar(&self.inventory, &self.level().items, w / 2.0, y) {
+            r.rect(context, b[0], b[1], b[2], b[3], faded(palette.shade, 0.5));
+            r.ellipse(context, [b[0] + 4.0, b[1] + 4.0, b[2] - 8.0, b[3] - 8.0], color);
+            if count > 1 {
+                hud.write(&style, &count.to_string(), b[0] + b[2] - 2.0, b[1] + b[3] - 2.0,
+                          context, r);
+            }
+        }
+
+        // Then the minimap, down in the other corner, if the level's too big
+        // to see all of at once.
+        if self.config.minimap && minimap::needed(self.world_size(), view) {
+            self.draw_minimap(view, context, r);
+        }
+
+        // The touch controls' D-pad and button, if they're on, in the
+        // HUD's color, so they show up on the same things it does.
+        if self.touch.enabled {
+            self.touch.draw(self.view_size, palette.text, context, r);
+        }
+
+        // A cutscene gets bars across the top and bottom, like in the
+        // pictures, in the letterbox color, and whatever it's saying goes in
+        // the bottom one.
+        if let Some(ref cutscene) = self.cutscene {
+            let bar = h * cutscene::BARS;
+            r.rect(context, 0.0, 0.0, w, bar, palette.letterbox);
+            r.rect(context, 0.0, h - bar, w, bar, palette.letterbox);
+            if let Some(ref text) = cutscene.text {
+                let style = Text::new(self.config.hud_font_size * 2, palette.text);
+                let text = self.settings.locale.get(text);
+                hud.draw_banner(text, &style, w, h - bar / 2.0, context, r);
+            }
+        }
+
+        // Then the dialogue box, if someone's talking.
+        if let Some(ref dialogue) = self.dialogue {
+            let style = Text::new(self.config.hud_font_size, palette.text);
+            let more = self.settings.locale.get("dialogue.more");
+            dialogue.draw((w, h), &style, palette.shade, more, hud, context, r);
+        }
+
+        // Rewinding, it says so, with a bar along the top for how much
+        // further back there is to go.
+        if self.rewinding && self.cutscene.is_none() {
+            let left = w * self.rewind.fullness();
+            r.rect(context, 0.0, 0.0, left, hud::MARGIN / 2.0, palette.text);
+            let style = Text::new(self.config.hud_font_size * 2, palette.text);
+            let text = self.settings.locale.get("rewind.rewinding");
+            hud.draw_banner(text, &style, w, h / 4.0, context, r);
+        }
+
+        /*
+         * With F3's frame stats, there's a graph of the last few seconds'
+         * frames in the bottom-left corner too, on a dark backing so it
+         * shows up on anything. A bar a frame, two pixels a millisecond,
+         * and anything over the line is a frame that missed its turn on the
+         * screen; see frametime.rs. (This frame isn't in it yet, of course:
+         * it's still being drawn.)
+         */
+        if self.show_frame_stats {
+            let (bar_width, scale, height) = (2.0, 2000.0, 80.0);
+            let width = frametime::GRAPH_FRAMES as f64 * bar_width;
+            let bottom = h - hud::MARGIN;
+            r.rect(context, hud::MARGIN, bottom - height, width, height, faded(palette.shade, 0.5));
+            r.rects(context, &self.frame_graph.bars(hud::MARGIN, bottom, bar_width, scale, height));
+        }
+
+        // Paused, the pause menu goes over the top (see pause_menu).
+        if self.paused {
+            self.pause_menu().draw(hud, context, r);
+        }
+
+        // Last of all, so it covers everything else: the help overlay. It's
+        // a dark, see-through panel with a border of game peeking out round
+        // the edges, and every key and what it does written on it, in the
+        // console's font so the keys line up in a column. They come from
+        // the config, so a key you've rebound shows up as what it is now.
+        if self.help_open {
+            let margin = 40.0;
+            r.rect(context, margin, margin, w - 2.0 * margin, h - 2.0 * margin,
+                   faded(palette.shade, 0.75));
+            let mut lines = vec![self.settings.locale.get("help.controls").to_string()];
+            for &(ref key, action) in handlers::bindings(&self.config).iter() {
+                lines.push(format!("  {:<14} {}", key, action));
+            }
+            let style = Text::new(self.config.hud_font_size, palette.text).font("console");
+            let inside = margin + hud::MARGIN;
+            hud.draw_lines(&lines, &style, inside, inside, context, r);
+        }
+
+        // And over even that, the debug console, dropped down from the top
+        // and just tall enough for what it's got to say. Without a font it
+        // still shows up, as an empty strip, so you know your typing's
+        // going somewhere; what it says goes to the log as well.
+        if self.console.open {
+            let size = self.config.hud_font_size;
+            let lines = self.console.lines();
+            let height = hud::line_height(size) * lines.len() as f64 + hud::MARGIN * 2.0;
+            r.rect(context, 0.0, 0.0, w, height, faded(palette.shade, 0.85));
+            let style = Text::new(size, palette.text).font("console");
+            hud.draw_lines(&lines, &style, hud::MARGIN, hud::MARGIN, context, r);
+        }
+
+        // Nothing stops the world being drawn past the edges of the view,
+        // so letterboxed, the bars go back over the top of anything that
+        // strayed into them.
+        if self.config.letterbox {
+            let (ww, wh) = self.window_size;
+            let letterbox = self.letterboxing();
+            let (x, y) = (letterbox.x, letterbox.y);
+            let bars = palette.letterbox;
+            r.rect(&screen, 0.0, 0.0, x, wh, bars);
+            r.rect(&screen, ww - x, 0.0, x, wh, bars);
+            r.rect(&screen, 0.0, 0.0, ww, y, bars);
+            r.rect(&screen, 0.0, wh - y, ww, y, bars);
+        }
+    }
+
+    /*
+     * Here we build the rectangle that represents our player.
+     * rect takes four arguments; the x and y coordinates of the top
+     * left corner of the rectangle, followed by the width and height.
+     * player_box works those out from the Player struct we defined
+     * earlier and the 'expand' attribute we tacked on to the state
+     * above: it adds expand to the width and height to grow the box, and
+     * subtracts half of it from the coordinates so that the expansion is
+     * evenly distributed rather than emitting exclusively right and down
+     * from the shape. Finally, we assign it a color (red, unless the
+     * config or the options screen says otherwise) and draw it to the
+     * canvas, in whichever shape player_shape says; the round ones fill
+     * the same box (see fill_shape).
+     * If there's a player sprite we draw that instead, scaled to fill
+     * the same box, so expand stretches it just like the square. A
+     * sprite sheet beats a still sprite; from that we cut out whichever
+     * frame the animator says we're on.
+     *
+     * In wrap mode a player hanging off one edge should poke in at the
+     * other, so it may get drawn more than once; see world.rs. And just
+     * after being hurt they blink, a tenth of a second on and a tenth off,
+     * which is drawn as not being drawn at all.
+     * Where the box goes is interpolated: see App::update. Player one's
+     * trail goes underneath them, so it's drawn first.
+     * In co-op each player is drawn like that, in their own colors (see
+     * object::PlayerObject).
+     */
+    pub fn draw_player(&self, i: usize, assets: &Assets, world: &Context, r: &mut dyn Renderer) {
+        let palette = self.config.palette();
+        if i == 0 {
+            self.trail.draw(palette.player, self.config.trail_fade, world, r);
+        }
+        let (world_width, world_height) = self.world_size();
+        let blinking = self.hurt_timer > 0.0 && ((self.hurt_timer / 0.1) as u32) % 2 == 1;
+        let color = self.player_color(i);
+        let player_at = self.drawn_at(i);
+        let mut b = self.player_box_of(i);
+        b.x += player_at.0 - self.players[i].x;
+        b.y += player_at.1 - self.players[i].y;
+        let offsets = match self.world_mode {
+            _ if blinking => Vec::new(),
+            WorldMode::Wrap => world::wrap_offsets(b, world_width, world_height),
+            WorldMode::Bounded => vec![(0.0, 0.0)],
+        };
+        // The pictures are player one's; player two is always a square.
+        let pictures = if i == 0 {
+            (assets.player_sheet(), assets.player())
+        } else {
+            (None, None)
+        };
+        /*
+         * The player's turned by their angle (Q and E), so rather than
+         * being drawn where they are, they're drawn round (0, 0) in a
+         * context that's been moved to their middle and turned: `turned`,
+         * with `local` as their box in it. Only the look turns; the
+         * hitbox stays straight, as F4 shows. The same goes for the
+         * squash and stretch (see player::squash_stretch): it's only
+         * `local` that's squashed.
+         */
+        let angle = self.players[i].angle;
+        let (wide, tall) = player::squash_stretch(&self.players[i], self.move_speed(),
+                                                  self.config.squash_stretch);
+        let (lw, lh) = (b.w * wide, b.h * tall);
+        let local = [-lw / 2.0, -lh / 2.0, lw, lh];
+        for &(ox, oy) in offsets.iter() {
+            let turned = world.trans(b.x + ox + b.w / 2.0, b.y + oy + b.h / 2.0)
+                              .rot_rad(angle);
+            match pictures {
+                (Some(sheet), _) => {
+                    // Frames are square, ROWS of them stacked top to bottom.
+                    let (_, th) = sheet.get_size();
+                    let size = th / animation::ROWS;
+                    let (col, row) = self.animator.cell(&self.animations);
+                    let cell = [(col * size) as i32, (row * size) as i32,
+                                size as i32, size as i32];
+                    r.image(&turned.trans(local[0], local[1])
+                                   .scale(lw / size as f64, lh / size as f64),
+                            sheet, Some(cell));
+                }
+                (None, Some(texture)) => {
+                    let (tw, th) = texture.get_size();
+                    r.image(&turned.trans(local[0], local[1])
+                                   .scale(lw / tw as f64, lh / th as f64),
+                            texture, None);
+                }
+                // Without either, player one can still have a picture in
+                // the atlas.
+                (None, None) if i == 0 && assets.has_sprite("player") => {
+                    assets.sprite("player", local, &turned, r);
+                }
+                (None, None) => match self.config.player_shape {
+                    Shape::Hollow => r.outline(&turned, local, (b.w / 5.0).max(1.0), color),
+                    shape => fill_shape(r, &turned, shape, self.config.player_sides, local,
+                                        color),
+                },
+            }
+
+            /*
+             * A little notch on whichever edge (or corner) the player faces,
+             * so you can tell which way is forward. edge turns each part of
+             * the facing vector into -1, 0 or 1, which picks the
+             * left/middle/right (and top/middle/bottom) of the square. The
+             * notch is darker than the player and centred on the edge, so
+             * half of it pokes out. A round player has no corners, so
+             * theirs goes on the circle, straight out along facing.
+             * The notch goes on the turned player, so facing gets turned
+             * back the other way first, into `turned`'s directions. It
+             * takes more than half of one to pick an edge, or a facing
+             * that's only a little off from straight (with tank controls,
+             * say, where facing is the angle rounded) would get a corner.
+             */
+            let (fx, fy) = self.players[i].facing.vector();
+            let (fx, fy) = (fx * angle.cos() + fy * angle.sin(),
+                            fy * angle.cos() - fx * angle.sin());
+            let edge = |v: f64| if v > 0.5 { 1.0 } else if v < -0.5 { -1.0 } else { 0.0 };
+            let (nx, ny) = if self.player_body() == Body::Round {
+                let length = (fx * fx + fy * fy).sqrt().max(1e-9);
+                (lw / 2.0 * fx / length, lh / 2.0 * fy / length)
+            } else {
+                (lw / 2.0 * edge(fx), lh / 2.0 * edge(fy))
+            };
+            r.rect(&turned, nx - 2.0, ny - 2.0, 4.0, 4.0,
+                   [color[0] * 0.5, color[1] * 0.5, color[2] * 0.5, color[3]]);
+
+            // Mid-dash the player flashes white, sprite or no sprite, in
+            // their shape (a hollow one's flash fills it in).
+            if i == 0 && self.dash_timer > 0.0 {
+                fill_shape(r, &turned, self.config.player_shape, self.config.player_sides,
+                           local, palette.dash_flash);
+            }
+        }
+    }
+
+    /*
+     * Where a click would shoot: a dotted line from the middle of player
+     * one to the mouse, and a little cross where it's pointing. It's only
+     * there once the mouse has been over the window, so if you're playing
+     * with the keys, the game looks the same as ever. `corner` is where
+     * the camera's being drawn from, so the cross is right under the mouse
+     * (see App::cursor_seen_from), and the line starts where player one is
+     * drawn rather than where they are as of the last tick.
+     */
+    fn draw_aim(&self, world: &Context, corner: WorldPos, color: [f32; 4], r: &mut dyn Renderer) {
+        let (mx, my) = match self.cursor_seen_from(corner) {
+            Some(at) => at.tuple(),
+            None => return,
+        };
+        let b = self.player_box();
+        let (x, y) = self.drawn_at(0);
+        let from_x = b.x + b.w / 2.0 + x - self.players[0].x;
+        let from_y = b.y + b.h / 2.0 + y - self.players[0].y;
+        r.rects(world, &dotted((from_x, from_y), (mx, my), AIM_DOT_SPACING, color));
+        r.rect(world, mx - 5.0, my - 1.0, 10.0, 2.0, color);
+        r.rect(world, mx - 1.0, my - 5.0, 2.0, 10.0, color);
+    }
+
+    /*
+     * The minimap (see minimap.rs), in the bottom-right corner of the
+     * view, with a dot for everyone and everything worth finding: the
+     * players, the enemies, whatever there is to pick up, and the goal.
+     * Power-ups are dotted in their own colors, like they're drawn. The
+     * players go on last, so they're never lost under an enemy, with player
+     * one on top. `view` is
+     * what the camera can see, for the outline.
+     */
+    fn draw_minimap(&self, view: Aabb, context: &Context, r: &mut dyn Renderer) {
+        let palette = self.config.palette();
+        let map = Minimap::new(self.world_size(), self.view_size);
+        let dot = |b: Aabb, color: [f32; 4]| map.dot(b, faded(color, minimap::DOT_ALPHA));
+        let mut dots = vec![dot(self.goal, palette.goal)];
+        for e in self.entities.iter().filter(|e| e.collectible()) {
+            let color = e.power_up().map_or(palette.pickup, |effect| palette.power_up(effect));
+            dots.push(dot(e.bounds(), color));
+        }
+        for b in self.scene.boxes(ecs::Kind::Enemy) {
+            dots.push(dot(b, palette.enemy));
+        }
+        // In their own colors, not a power-up's flashing.
+        let own = [palette.player, palette.player_two];
+        for i in (0..self.players.len()).rev() {
+            dots.push(dot(self.player_box_of(i), own[i.min(1)]));
+        }
+        map.draw(&dots, view, faded(palette.shade, 0.5), faded(palette.text, 0.8),
+                 faded(palette.text, 0.4), context, r);
+    }
+
+    /*
+     * F4's debug drawing, for seeing what the collision code sees. Every
+     * box anything collides with gets outlined, in a color saying what it
+     * is: red for walls (and solid tiles, when they count), yellow for
+     * pickups, orange for enemies and shots, cyan for the players and
+     * green for the goal. These are the real boxes, where things are as of
+     * the last tick, so a player mid-way between ticks is drawn a little
+     * ahead of their outline; that's the interpolation in update at work.
+     *
+     * Under all that there's a grid, a line every DEBUG_GRID pixels of the
+     * world, which makes it plain what the camera is doing: the grid moves
+     * when the camera does, and gets bigger when it zooms. Only the lines
+     * in `view` are drawn. The walls' quadtree has its cells outlined in
+     * see-through magenta, so you can watch it cut up a busy level and
+     * leave an empty one whole. Everything's drawn through `world`, the
+     * camera's canvas, so the outlines and labels line up with what they're
+     * outlining; the lines are a pixel thick at a zoom of 1.
+     */
+    fn draw_debug(&self, world: &Context, view: Aabb, r: &mut dyn Renderer, hud: &mut Hud) {
+        let thickness = 1.0 / self.camera.zoom;
+        let mut x = (view.x / DEBUG_GRID).floor() * DEBUG_GRID;
+        while x < view.x + view.w {
+            r.rect(world, x, view.y, thickness, view.h, DEBUG_GRID_COLOR);
+            x += DEBUG_GRID;
+        }
+        let mut y = (view.y / DEBUG_GRID).floor() * DEBUG_GRID;
+        while y < view.y + view.h {
+            r.rect(world, view.x, y, view.w, thickness, DEBUG_GRID_COLOR);
+            y += DEBUG_GRID;
+        }
+
+        let mut boxes: Vec<(Aabb, [f32; 4])> = Vec::new();
+        for e in self.entities.iter() {
+            if e.solid() {
+                boxes.push((e.bounds(), DEBUG_SOLID_COLOR));
+            } else if e.collectible() {
+                boxes.push((e.bounds(), DEBUG_PICKUP_COLOR));
+            }
+        }
+        if self.platformer {
+            boxes.extend(self.solid_tiles.iter().map(|&b| (b, DEBUG_SOLID_COLOR)));
+        }
+        for id in self.scene.ids() {
+            if let Some(b) = self.scene.bounds(id) {
+                boxes.push((b, DEBUG_ENEMY_COLOR));
+            }
+        }
+        for i in 0..self.players.len() {
+            boxes.push((self.player_box_of(i), DEBUG_PLAYER_COLOR));
+        }
+        boxes.push((self.goal, DEBUG_GOAL_COLOR));
+        boxes.extend(self.quadtree.cells().into_iter().map(|b| (b, DEBUG_QUADTREE_COLOR)));
+        for &(b, color) in boxes.iter() {
+            r.outline(world, [b.x, b.y, b.w, b.h], thickness, color);
+        }
+
+        // The way round the walls each enemy's following, if it's following
+        // one (see pathfinding.rs): dotted from where it is, with a square
+        // on each waypoint it's still to get to.
+        let mut dots = Vec::new();
+        for &(id, ref route) in self.paths.all().iter() {
+            let mut at = match self.scene.bounds(id) {
+                Some(b) => (b.x + b.w / 2.0, b.y + b.h / 2.0),
+                None => continue,
+            };
+            for &(x, y) in route.iter() {
+                dots.extend(dotted(at, (x, y), AIM_DOT_SPACING / 2.0, DEBUG_PATH_COLOR));
+                dots.push(([x - 3.0, y - 3.0, 6.0, 6.0], DEBUG_PATH_COLOR));
+                at = (x, y);
+            }
+        }
+        r.rects(world, &dots);
+
+        // The labels go just above what they're about, at half the HUD's
+        // size, which is about as small as text stays readable.
+        let style = Text::new((self.config.hud_font_size / 2).max(8), DEBUG_TEXT_COLOR);
+        for &(x, y, ref label) in self.debug_labels().iter() {
+            let top = y - hud::line_height(style.size);
+            hud.draw_lines(&[label.clone()], &style, x, top, world, r);
+        }
+    }
+
+    /*
+     * What draw_debug writes next to things, and where: the players and
+     * every enemy and shot, with where they are and how fast they're going,
+     * in world units per second. Pickups and walls don't move, so they just get
+     * where they are; they're outlined anyway. This is separate so a test
+     * can read it without a font.
+     */
+    pub fn debug_labels(&self) -> Vec<(f64, f64, String)> {
+        let mut labels = Vec::new();
+        for (i, player) in self.players.iter().enumerate() {
+            let b = self.player_box_of(i);
+            labels.push((b.x, b.y, format!("P{} ({:.0}, {:.0}) v ({:.0}, {:.0})",
+                                           i + 1, player.x, player.y, player.vx, player.vy)));
+        }
+        for id in self.scene.ids() {
+            let b = match self.scene.bounds(id) {
+                Some(b) => b,
+                None => continue,
+            };
+            let label = match self.scene.velocities[id.index] {
+                Some(ref v) => format!("({:.0}, {:.0}) v ({:.0}, {:.0})", b.x, b.y, v.vx, v.vy),
+                None => format!("({:.0}, {:.0})", b.x, b.y),
+            };
+            labels.push((b.x, b.y, label));
+        }
+        for e in self.entities.iter().filter(|e| e.collectible()) {
+            let b = e.bounds();
+            labels.push((b.x, b.y, format!("({:.0}, {:.0})", b.x, b.y)));
+        }
+        labels
+    }
+
+    /*
+     * How much of each Pool (see ecs.rs) is in use, for F4: the scene's
+     * slots, which shots and enemies share, and the particles'. A pool that
+     * has a lot more slots than it's using has had a busy moment, and is
+     * holding on to them for the next one.
+     */
+    pub fn pool_stats(&self) -> Vec<String> {
+        vec![format!("Scene: {} slots", self.scene.stats()),
+             format!("Particles: {} slots", self.particles.particles.stats())]
+    }
+}
+
+// `shape`, filled in, in the [x, y, w, h] box `b`. A hollow square gets
+// filled in too, since anything wanting it hollow uses outline.
+fn fill_shape(r: &mut dyn Renderer, context: &Context, shape: Shape, sides: u32, b: [f64; 4],
+              color: [f32; 4]) {
+    match shape {
+        Shape::Square | Shape::Hollow => r.rect(context, b[0], b[1], b[2], b[3], color),
+        Shape::Circle => r.ellipse(context, b, color),
+        Shape::Polygon => r.polygon(context, &regular_polygon(b, sides), color),
+    }
+}
+
+// Dots `spacing` apart from `from` to `to`, leaving out the two ends.
+fn dotted(from: (f64, f64), to: (f64, f64), spacing: f64,
+          color: [f32; 4]) -> Vec<([f64; 4], [f32; 4])> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+    (1..(length / spacing) as u32).map(|n| {
+        let t = n as f64 * spacing / length;
+        ([from.0 + dx * t - 1.0, from.1 + dy * t - 1.0, 2.0, 2.0], color)
+    }).collect()
+}
@@ -0,0 +1,384 @@
+/*
+ * Drawing, one step removed. Everything in the game draws through the
+ * Renderer trait rather than straight onto Gl, OpenGL's canvas. The real game
+ * hands them a Gl, which does the drawing like it always has. --headless,
+ * and the tests, hand them a Recorder instead, which just writes down what
+ * it was asked to draw. That means the drawing code can run on a machine
+ * with no screen and no graphics card, and a test can check that the enemy
+ * really did get drawn, and in blue.
+ *
+ * The trait only has the handful of things the game actually does:
+ * rectangles, ellipses and polygons, pictures and text. Where anything goes
+ * is still worked out with a graphics Context, transforms and all, so the
+ * drawing code reads the same as it did before.
+ *
+ * The real game's Gl goes behind a Batcher, which saves the rectangles up
+ * and draws them in bulk; see further down.
+ */
+use graphics::{
+    self,
+    Context,
+    DrawState,
+    Graphics,
+    Image,
+    Text,
+};
+use graphics::math::Matrix2d;
+use opengl_graphics::Texture;
+
+use engine::{Gl, GlyphCache};
+
+pub trait Renderer {
+    // Paint the whole window `color`, whatever the context's transform.
+    fn clear(&mut self, context: &Context, color: [f32; 4]);
+
+    fn rect(&mut self, context: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]);
+
+    // A whole batch of [x, y, w, h] rectangles, each in its own color. Gl
+    // and Recorder just take them one at a time, and Batcher saves up every
+    // rectangle anyway, but it means something drawing hundreds of them
+    // (the particles) says so in one go.
+    fn rects(&mut self, context: &Context, rects: &[([f64; 4], [f32; 4])]) {
+        for &(b, color) in rects.iter() {
+            self.rect(context, b[0], b[1], b[2], b[3], color);
+        }
+    }
+
+    // Just the edges of the [x, y, w, h] box, `thickness` thick and inside
+    // it, so a box drawn next to another doesn't look any bigger.
+    fn outline(&mut self, context: &Context, b: [f64; 4], thickness: f64, color: [f32; 4]) {
+        let t = thickness.min(b[2] / 2.0).min(b[3] / 2.0);
+        self.rects(context, &[([b[0], b[1], b[2], t], color),
+                              ([b[0], b[1] + b[3] - t, b[2], t], color),
+                              ([b[0], b[1] + t, t, b[3] - 2.0 * t], color),
+                              ([b[0] + b[2] - t, b[1] + t, t, b[3] - 2.0 * t], color)]);
+    }
+
+    // The ellipse that fills the [x, y, w, h] box; a circle, if it's square.
+    fn ellipse(&mut self, context: &Context, b: [f64; 4], color: [f32; 4]);
+
+    // A filled shape with corners at `points`, in order round its edge.
+    fn polygon(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]);
+
+    // A picture with its top-left corner at the context's origin; or, with
+    // `src`, just the [x, y, w, h] part of it.
+    fn image(&mut self, context: &Context, texture: &Texture, src: Option<[i32; 4]>);
+
+    // A line of text with its baseline at the context's origin.
+    fn text(&mut self, context: &Context, text: &str, size: u32, color: [f32; 4],
+            glyphs: &mut GlyphCache);
+}
+
+/*
+ * graphics has a function for each shape, which takes the color, where it
+ * goes, and the transform and the draw state (how it's blended, and
+ * clipped) from the context, and draws on whatever it's given.
+ */
+impl Renderer for Gl {
+    fn clear(&mut self, _: &Context, color: [f32; 4]) {
+        graphics::clear(color, self);
+    }
+
+    fn rect(&mut self, context: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]) {
+        graphics::rectangle(color, [x, y, w, h], context.transform, self);
+    }
+
+    fn ellipse(&mut self, context: &Context, b: [f64; 4], color: [f32; 4]) {
+        graphics::ellipse(color, b, context.transform, self);
+    }
+
+    fn polygon(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        graphics::polygon(color, points, context.transform, self);
+    }
+
+    fn image(&mut self, context: &Context, texture: &Texture, src: Option<[i32; 4]>) {
+        let image = match src {
+            Some(s) => Image::new().src_rect([s[0] as f64, s[1] as f64,
+                                              s[2] as f64, s[3] as f64]),
+            None => Image::new(),
+        };
+        image.draw(texture, &context.draw_state, context.transform, self);
+    }
+
+    // Text can fail, if a letter won't go into the font's texture, but
+    // there's nothing to be done about it halfway through a frame, and the
+    // rest of the line comes out anyway.
+    fn text(&mut self, context: &Context, text: &str, size: u32, color: [f32; 4],
+            glyphs: &mut GlyphCache) {
+        let _ = Text::new_color(color, size)
+            .draw(text, glyphs, &context.draw_state, context.transform, self);
+    }
+}
+
+// One thing a Recorder was asked to draw. Rectangles are written down as
+// they were asked for, before the context moved them about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    Clear([f32; 4]),
+    Rect([f64; 4], [f32; 4]),
+    Ellipse([f64; 4], [f32; 4]),
+    Polygon(Vec<[f64; 2]>, [f32; 4]),
+    Image,
+    Text(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recorder {
+    pub calls: Vec<Call>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder { calls: Vec::new() }
+    }
+
+    // The rectangles drawn in `color`, for checking something got drawn.
+    pub fn rects_in(&self, color: [f32; 4]) -> Vec<[f64; 4]> {
+        self.calls.iter().filter_map(|c| match *c {
+            Call::Rect(r, c) if c == color => Some(r),
+            _ => None,
+        }).collect()
+    }
+}
+
+impl Renderer for Recorder {
+    fn clear(&mut self, _: &Context, color: [f32; 4]) {
+        self.calls.push(Call::Clear(color));
+    }
+
+    fn rect(&mut self, _: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]) {
+        self.calls.push(Call::Rect([x, y, w, h], color));
+    }
+
+    fn ellipse(&mut self, _: &Context, b: [f64; 4], color: [f32; 4]) {
+        self.calls.push(Call::Ellipse(b, color));
+    }
+
+    fn polygon(&mut self, _: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        self.calls.push(Call::Polygon(points.to_vec(), color));
+    }
+
+    fn image(&mut self, _: &Context, _: &Texture, _: Option<[i32; 4]>) {
+        self.calls.push(Call::Image);
+    }
+
+    fn text(&mut self, _: &Context, text: &str, _: u32, _: [f32; 4], _: &mut GlyphCache) {
+        self.calls.push(Call::Text(text.to_string()));
+    }
+}
+
+/*
+ * Batching. A rectangle is drawn by sending OpenGL its two triangles and
+ * asking it to draw them, and the asking is what costs: a few hundred
+ * particles, tiles and enemies a frame is a few hundred trips to the
+ * graphics card for six corners each. So Batcher sits in front of a Gl
+ * and keeps the rectangles to itself, turned into triangles and colored
+ * corner by corner in a QuadBatch, until something that isn't a rectangle
+ * comes along; then everything it's kept goes over in one draw (or a few,
+ * for a really big pile; see CHUNK_QUADS), and the other thing gets drawn
+ * after them, so what's on top is still on top. A frame that's all
+ * rectangles, which is most of them, is a handful of draws altogether.
+ *
+ * Each corner's color goes with it, so rectangles of all different colors
+ * share a draw. The corners are put through the context's transform as
+ * they go in, since they're not all drawn through the same context (the
+ * camera's, the HUD's), and OpenGL only wants to know where they end up.
+ */
+// The most rectangles one draw takes: as many as fit in the corners
+// graphics lets a back end be handed at once, at six corners each.
+pub const CHUNK_QUADS: usize = graphics::BACK_END_MAX_VERTEX_COUNT / 6;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuadBatch {
+    // Where each corner is, x and y, six corners a rectangle, and each
+    // corner's color: red, green, blue and alpha.
+    vertices: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+}
+
+impl QuadBatch {
+    pub fn new() -> QuadBatch {
+        QuadBatch::default()
+    }
+
+    // The [x, y, w, h] rectangle `b` in `color`, through `transform`.
+    pub fn push(&mut self, transform: &Matrix2d, b: [f64; 4], color: [f32; 4]) {
+        let (x, y, right, bottom) = (b[0], b[1], b[0] + b[2], b[1] + b[3]);
+        let corners = [[x, y], [right, y], [x, bottom], [right, y], [right, bottom], [x, bottom]];
+        for corner in corners.iter() {
+            let [tx, ty] = transformed(transform, *corner);
+            self.vertices.push([tx as f32, ty as f32]);
+            self.colors.push(color);
+        }
+    }
+
+    // How many rectangles are in it.
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 6
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    // Empty it out. The Vecs hang on to their room, for the next lot.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.colors.clear();
+    }
+
+    // The corners and their colors, CHUNK_QUADS rectangles at a time.
+    pub fn chunks(&self) -> Vec<(&[[f32; 2]], &[[f32; 4]])> {
+        self.vertices.chunks(CHUNK_QUADS * 6).zip(self.colors.chunks(CHUNK_QUADS * 6)).collect()
+    }
+}
+
+// Where `at` ends up, through `m`.
+pub fn transformed(m: &Matrix2d, at: [f64; 2]) -> [f64; 2] {
+    [m[0][0] * at[0] + m[0][1] * at[1] + m[0][2],
+     m[1][0] * at[0] + m[1][1] * at[1] + m[1][2]]
+}
+
+/*
+ * A Gl with a QuadBatch in front of it: see above. Whoever's drawing has
+ * to flush it when they're done, or the last of the rectangles never get
+ * drawn; draws counts how many trips to OpenGL the rectangles took.
+ *
+ * It doesn't have to be a Gl. Any graphics back end can take triangles in
+ * bulk, so anything that's one of those and a Renderer as well will do:
+ * the piston_window front end's G2d is the other (see piston_front.rs).
+ */
+pub struct Batcher<'a, G: 'a> {
+    gl: &'a mut G,
+    batch: QuadBatch,
+    pub draws: usize,
+}
+
+impl<'a, G: Graphics + Renderer> Batcher<'a, G> {
+    pub fn new(gl: &'a mut G) -> Batcher<'a, G> {
+        Batcher { gl: gl, batch: QuadBatch::new(), draws: 0 }
+    }
+
+    // Draw everything that's been kept so far. Some of it's bound to be
+    // see-through, so blending's on for the lot. tri_list_c hands us a
+    // function to give the corners to, with their colors, as many times as
+    // we like.
+    pub fn flush(&mut self) {
+        if self.batch.is_empty() { return }
+        let chunks = self.batch.chunks();
+        self.gl.tri_list_c(&DrawState::new_alpha(), |f| {
+            for &(vertices, colors) in chunks.iter() {
+                f(vertices, colors);
+            }
+        });
+        self.draws += chunks.len();
+        self.batch.clear();
+    }
+}
+
+impl<'a, G: Graphics + Renderer> Renderer for Batcher<'a, G> {
+    // Anything not drawn yet would be painted over anyway.
+    fn clear(&mut self, context: &Context, color: [f32; 4]) {
+        self.batch.clear();
+        Renderer::clear(&mut *self.gl, context, color);
+    }
+
+    fn rect(&mut self, context: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]) {
+        self.batch.push(&context.transform, [x, y, w, h], color);
+    }
+
+    fn ellipse(&mut self, context: &Context, b: [f64; 4], color: [f32; 4]) {
+        self.flush();
+        Renderer::ellipse(&mut *self.gl, context, b, color);
+    }
+
+    fn polygon(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        self.flush();
+        Renderer::polygon(&mut *self.gl, context, points, color);
+    }
+
+    fn image(&mut self, context: &Context, texture: &Texture, src: Option<[i32; 4]>) {
+        self.flush();
+        Renderer::image(&mut *self.gl, context, texture, src);
+    }
+
+    fn text(&mut self, context: &Context, text: &str, size: u32, color: [f32; 4],
+            glyphs: &mut GlyphCache) {
+        self.flush();
+        Renderer::text(&mut *self.gl, context, text, size, color, glyphs);
+    }
+}
+
+/*
+ * The corners of a regular polygon with `sides` sides (three at least) that
+ * fits in the [x, y, w, h] box, with its corners on the ellipse that fills
+ * it. The first corner is straight up, so a triangle points up.
+ */
+pub fn regular_polygon(b: [f64; 4], sides: u32) -> Vec<[f64; 2]> {
+    let sides = sides.max(3);
+    let (cx, cy) = (b[0] + b[2] / 2.0, b[1] + b[3] / 2.0);
+    (0..sides).map(|i| {
+        let angle = i as f64 / sides as f64 * 2.0 * ::std::f64::consts::PI;
+        [cx + angle.sin() * b[2] / 2.0, cy - angle.cos() * b[3] / 2.0]
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::Context;
+
+    #[test]
+    fn records_what_it_is_asked_to_draw() {
+        let context = Context::new_abs(640.0, 480.0);
+        let mut r = Recorder::new();
+        r.clear(&context, [0.0, 0.0, 0.0, 1.0]);
+        r.rect(&context, 1.0, 2.0, 3.0, 4.0, [1.0, 0.0, 0.0, 1.0]);
+        r.rect(&context, 5.0, 6.0, 7.0, 8.0, [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(r.calls.len(), 3);
+        assert_eq!(r.rects_in([1.0, 0.0, 0.0, 1.0]), vec![[1.0, 2.0, 3.0, 4.0]]);
+    }
+
+    #[test]
+    fn outlines_are_four_thin_rectangles() {
+        let mut r = Recorder::new();
+        r.outline(&Context::new_abs(640.0, 480.0), [10.0, 20.0, 30.0, 40.0], 1.0, [1.0; 4]);
+        assert_eq!(r.rects_in([1.0; 4]), vec![[10.0, 20.0, 30.0, 1.0], [10.0, 59.0, 30.0, 1.0],
+                                              [10.0, 21.0, 1.0, 38.0], [39.0, 21.0, 1.0, 38.0]]);
+    }
+
+    #[test]
+    fn regular_polygons_have_their_corners_on_the_edge_of_the_box() {
+        let square = regular_polygon([0.0, 0.0, 10.0, 10.0], 4);
+        let expected = [[5.0, 0.0], [10.0, 5.0], [5.0, 10.0], [0.0, 5.0]];
+        assert_eq!(square.len(), 4);
+        for (p, e) in square.iter().zip(expected.iter()) {
+            assert!((p[0] - e[0]).abs() < 1e-9 && (p[1] - e[1]).abs() < 1e-9, "{:?}", p);
+        }
+        // Fewer than three sides isn't a shape, so it's a triangle.
+        assert_eq!(regular_polygon([0.0, 0.0, 10.0, 10.0], 1).len(), 3);
+    }
+
+    #[test]
+    fn batches_are_triangles_where_the_context_puts_them() {
+        // The whole window, which OpenGL calls -1 to 1 each way, with y up.
+        let mut batch = QuadBatch::new();
+        let red = [1.0, 0.0, 0.0, 0.5];
+        batch.push(&Context::new_abs(640.0, 480.0).transform, [0.0, 0.0, 640.0, 480.0], red);
+        assert_eq!(batch.len(), 1);
+        let (vertices, colors) = batch.chunks()[0];
+        assert_eq!(&vertices[..2], &[[-1.0, 1.0], [1.0, 1.0]]);
+        assert_eq!(vertices[4], [1.0, -1.0]);
+        assert_eq!(colors.len(), 6);
+        assert_eq!(colors[5], red);
+        // A big pile goes over a chunk at a time.
+        for _ in 0..CHUNK_QUADS {
+            batch.push(&Context::new_abs(640.0, 480.0).transform, [1.0, 2.0, 3.0, 4.0], red);
+        }
+        let chunks = batch.chunks();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[1].0.len(), chunks[1].1.len()), (6, 6));
+        batch.clear();
+        assert!(batch.is_empty());
+    }
+}
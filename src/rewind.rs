@@ -0,0 +1,152 @@
+/*
+ * Rewinding time. Hold the rewind key (Z, unless the config says otherwise;
+ * R would be the obvious one, but R is reset) and the game runs backwards,
+ * a tick of the past for every tick of holding it, for as far back as
+ * `rewind_seconds` goes. Let go and it carries on forwards from there, as
+ * though what you rewound through never happened.
+ *
+ * This is one of those things that sounds hard and turns out not to be,
+ * thanks to two things the game was doing already. One is the fixed
+ * timestep (see App::update): the game moves on in ticks of exactly
+ * SIM_DT, so "a tick ago" is a definite thing, the same size every time,
+ * and a tick's worth of going backwards is one step. The other is the
+ * save's Snapshot (see save.rs), which has everything about the level in
+ * progress in one plain value. So every tick, before anything moves, a
+ * snapshot of how things are goes in a ring buffer (a queue that throws
+ * the oldest away when it fills up), and rewinding takes them back out
+ * again, newest first, and puts each one back in turn.
+ *
+ * A snapshot's only a few hundred bytes, so five seconds of them at 120
+ * ticks a second is nothing to worry about. What isn't in one isn't
+ * rewound: shots in flight just go, timers keep their time, and the
+ * enemies are put back with their health full, the same as loading a
+ * save. Player two isn't in a save, so their whereabouts go in alongside.
+ * Neither a cutscene nor the dialogue box can be rewound through, since
+ * nothing's recorded while they're on.
+ */
+use std::collections::VecDeque;
+
+use app::{App, SIM_DT};
+use save::Snapshot;
+
+// A tick of the past: the snapshot, and where everyone but player one
+// was, and how fast they were going, as [x, y, vx, vy].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub snapshot: Snapshot,
+    pub others: Vec<[f64; 4]>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rewind {
+    frames: VecDeque<Frame>,
+    capacity: usize,
+}
+
+impl Rewind {
+    // Room for `seconds` of ticks. None, if it's 0, which turns it off.
+    pub fn new(seconds: f64) -> Rewind {
+        let capacity = (seconds.max(0.0) / SIM_DT).round() as usize;
+        Rewind { frames: VecDeque::with_capacity(capacity), capacity: capacity }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        if self.capacity == 0 { return }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    // The newest frame, taking it out.
+    pub fn pop(&mut self) -> Option<Frame> {
+        self.frames.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    // How full it is, from 0 to 1, for the bar along the top while
+    // rewinding.
+    pub fn fullness(&self) -> f64 {
+        if self.capacity == 0 { return 0.0 }
+        self.frames.len() as f64 / self.capacity as f64
+    }
+}
+
+// Once a tick, before anything moves: how things are, onto the end.
+pub fn record(app: &mut App) {
+    if !app.rewind.enabled() { return }
+    let frame = Frame {
+        snapshot: app.snapshot(),
+        others: app.players[1..].iter().map(|p| [p.x, p.y, p.vx, p.vy]).collect(),
+    };
+    app.rewind.push(frame);
+}
+
+/*
+ * A tick backwards, instead of one forwards: the newest frame goes back in.
+ * Once they've run out, everything holds still where the oldest one left
+ * it, until you let go. The camera follows along as it would going
+ * forwards.
+ */
+pub fn step_back(app: &mut App, dt: f64) {
+    if let Some(frame) = app.rewind.pop() {
+        app.put_back(&frame.snapshot);
+        for (player, other) in app.players[1..].iter_mut().zip(frame.others.iter()) {
+            player.x = other[0];
+            player.y = other[1];
+            player.vx = other[2];
+            player.vy = other[3];
+        }
+    }
+    app.update_camera(dt);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use controls::Facing;
+
+    fn frame(score: u32) -> Frame {
+        let snapshot = Snapshot {
+            level: 0, level_name: "Empty field".to_string(),
+            player: [50.0, 50.0], velocity: [0.0, 0.0], facing: Facing::Down, angle: 0.0,
+            expand: 0.0, score: score, health: 3, lives: Some(3), elapsed: 0.0,
+            pickups: Vec::new(), enemies: Vec::new(), power_ups: Vec::new(),
+            effects: Vec::new(), inventory: None, items: None, doors: None,
+        };
+        Frame { snapshot: snapshot, others: Vec::new() }
+    }
+
+    #[test]
+    fn the_oldest_frames_fall_off_the_end() {
+        let mut rewind = Rewind::new(3.0 * SIM_DT);
+        for score in 0..5 {
+            rewind.push(frame(score));
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.fullness(), 1.0);
+        let scores: Vec<u32> = (0..4).filter_map(|_| rewind.pop())
+            .map(|f| f.snapshot.score)
+            .collect();
+        assert_eq!(scores, vec![4, 3, 2]);
+
+        let mut off = Rewind::new(0.0);
+        off.push(frame(1));
+        assert!(!off.enabled() && off.is_empty());
+    }
+}
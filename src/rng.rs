@@ -0,0 +1,76 @@
+/*
+ * A tiny seedable random number generator. We could pull in the `rand`
+ * crate, but for a toy like this a xorshift generator is plenty random,
+ * only a few lines long, and - most importantly - completely predictable
+ * when you hand it the same seed. That last bit is what lets the tests
+ * check a particle burst without crossing their fingers.
+ *
+ * Each App owns one, seeded from --seed (or the seed a replay was recorded
+ * with), and reseeds it whenever the level resets. Anything in the game
+ * that wants to be random should take its numbers from that one, in update
+ * and not in render: then the same seed and the same keys always make the
+ * same game, which is all a replay is.
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // Xorshift falls over if its state is ever zero, so we quietly nudge a
+    // zero seed to something else.
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: if seed == 0 { 0x9E3779B97F4A7C15 } else { seed } }
+    }
+
+    // This is xorshift64*: shuffle the bits around, then multiply to smear
+    // the low bits a bit better.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // A float in [0, 1). We keep the top 53 bits because that's exactly how
+    // many bits of precision an f64 has.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // A float somewhere between lo (inclusive) and hi (exclusive).
+    pub fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.next_f64()
+    }
+
+    // A whole number between lo and hi, both inclusive.
+    pub fn range_usize(&mut self, lo: usize, hi: usize) -> usize {
+        lo + (self.next_u64() % ((hi - lo + 1) as u64)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let f = rng.range(-2.0, 3.0);
+            assert!(f >= -2.0 && f < 3.0);
+            let n = rng.range_usize(20, 40);
+            assert!(n >= 20 && n <= 40);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+/*
+ * Saving and loading. A save is a snapshot of everything about the game in
+ * progress that you'd be annoyed to lose: which level you're on, where the
+ * player is and how they're doing, and what's still left in the level. It's
+ * written as JSON, like the config and the levels, so you can open a save
+ * in a text editor and see (or fiddle with) exactly what's in it.
+ *
+ * Not everything goes in. The walls and the goal come from the level file,
+ * so there's no need to save them, and particles are just decoration, so
+ * they don't survive a load. The things that do change as you play -
+ * pickups being picked up, enemies being shot - are saved as the positions
+ * of the ones that are left, the same way a level file lists them. So are
+ * the power-ups, along with the powers you've got and how long they've got
+ * left, and the items and the doors, along with what you're carrying.
+ *
+ * A checkpoint (see checkpoint.rs) is a save too, one that's kept in memory
+ * rather than written out, but it's the same JSON, by way of to_json and
+ * from_json, so there's only the one idea of what a game in progress looks
+ * like.
+ *
+ * Saves are written atomically: all or nothing. Writing straight over the
+ * old file, a crash (or the power going) partway through would leave half
+ * a save, which is worse than an old one. So it goes into a file next to
+ * it first, and only once that's all safely on the disk is it renamed over
+ * the old one, which the file system does in one go. Whenever you look,
+ * there's either the old save or the new one, never a bit of each. The
+ * autosaves (see autosave.rs) count on that.
+ */
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use controls::Facing;
+use inventory::{Inventory, ItemKind};
+use powerup::{Active, Spot};
+use schema::{self, Format};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    // Which level, by its place in the list and by name. The name is there
+    // to catch a save being loaded after the levels have been shuffled.
+    pub level: usize,
+    pub level_name: String,
+    pub player: [f64; 2],
+    pub velocity: [f64; 2],
+    pub facing: Facing,
+    // Saves from before players could turn are the right way up.
+    #[serde(default)]
+    pub angle: f64,
+    pub expand: f64,
+    pub score: u32,
+    pub health: u32,
+    // Saves from before lives were a thing don't have this, and loading one
+    // leaves your lives as they are.
+    #[serde(default)]
+    pub lives: Option<u32>,
+    pub elapsed: f64,
+    // The middles of the pickups and enemies that are still about.
+    pub pickups: Vec<[f64; 2]>,
+    pub enemies: Vec<[f64; 2]>,
+    // Saves from before power-ups were a thing don't have these.
+    #[serde(default)]
+    pub power_ups: Vec<Spot>,
+    #[serde(default)]
+    pub effects: Vec<Active>,
+    // Saves from before there was anything to carry don't have these, and
+    // loading one puts the items and doors back the way the level has them.
+    // The items left are by kind, the way a level file lists them, and the
+    // doors are the ones still shut.
+    #[serde(default)]
+    pub inventory: Option<Inventory>,
+    #[serde(default)]
+    pub items: Option<Vec<ItemKind>>,
+    #[serde(default)]
+    pub doors: Option<Vec<[f64; 4]>>,
+}
+
+// Where F5 saves to and F9 loads from.
+pub fn default_path() -> PathBuf {
+    PathBuf::from("save.json")
+}
+
+pub fn to_json(snapshot: &Snapshot) -> Result<String, String> {
+    schema::to_json(Format::Save, snapshot)
+}
+
+// Saves from older builds are brought up to date first (see schema.rs).
+pub fn from_json(json: &str) -> Result<Snapshot, String> {
+    schema::from_json(Format::Save, json)
+}
+
+pub fn save(path: &Path, snapshot: &Snapshot) -> Result<(), String> {
+    write_atomically(path, to_json(snapshot)?.as_bytes())
+}
+
+// `contents` into the file at `path`, by way of path.tmp, so that it's
+// either all written or not at all.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".tmp");
+    let temporary = path.with_file_name(name);
+    File::create(&temporary)
+        .and_then(|mut f| f.write_all(contents).and_then(|()| f.sync_all()))
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temporary);
+            format!("couldn't write {}: {}", path.display(), e)
+        })
+}
+
+pub fn load(path: &Path) -> Result<Snapshot, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    from_json(&contents).map_err(|e| format!("couldn't parse {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use controls::Facing;
+    use powerup::Effect;
+
+    fn snapshot() -> Snapshot {
+        let mut inventory = Inventory::new();
+        inventory.add("key");
+        Snapshot {
+            level: 1,
+            level_name: "The moat".to_string(),
+            player: [120.0, 340.5],
+            velocity: [0.0, -200.0],
+            facing: Facing::UpLeft,
+            angle: 1.5,
+            expand: 12.5,
+            score: 3,
+            health: 2,
+            lives: Some(1),
+            elapsed: 17.25,
+            pickups: vec![[480.0, 60.0]],
+            enemies: Vec::new(),
+            power_ups: vec![Spot { effect: Effect::Size, at: [10.0, 20.0] }],
+            effects: vec![Active { effect: Effect::Speed, left: 2.5 }],
+            inventory: Some(inventory),
+            items: Some(Vec::new()),
+            doors: Some(vec![[200.0, 300.0, 10.0, 120.0]]),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let path = env::temp_dir().join(format!("jumpy-save-test-{}.json", ::std::process::id()));
+        save(&path, &snapshot()).unwrap();
+        assert_eq!(load(&path), Ok(snapshot()));
+        // The file it went into on the way has gone into this one.
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".tmp");
+        assert!(!Path::new(&temporary).exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_or_broken_saves_are_errors() {
+        assert!(load(Path::new("no-such-save.json")).is_err());
+        assert!(from_json(r#"{ "level": 0 }"#).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * Versions, for the files that get passed about: config.json, the levels,
+ * the saves and the replays. Each says at the top which version of its
+ * format it is,
+ *
+ *     {
+ *         "version": 1,
+ *         "name": "First steps",
+ *         ...
+ *
+ * (a replay's a line of its own, "version 1"; see script.rs), so that when
+ * a format changes, a file written by an older build of the game can be
+ * told apart from a new one, and brought up to date on the way in rather
+ * than turned away, or worse, read as meaning something it doesn't.
+ *
+ * Reading goes through upgrade: the JSON as it is in the file, before it's
+ * turned into a Config or a Level or whatever it is, gets moved on a
+ * version at a time until it's the current one, and only then handed to
+ * serde. Each step (see `step`) only has to know about the one version
+ * before it, so the steps never have to change once they're written, and
+ * a file from any build at all gets there in the end. A file from before
+ * there were versions doesn't say, and counts as version 0.
+ *
+ * Plenty of changes don't need a step at all: a new setting with a
+ * default, or a new list in a level that can be left out, reads fine from
+ * an old file as it is (that's what all the #[serde(default)]s are for). A
+ * step is for when the same JSON has to mean something different, like a
+ * setting being renamed, or its old value not making sense any more. A
+ * file from a newer build than this one can't be brought down, since
+ * there's no knowing what's changed, so that's an error, and one that
+ * says so, rather than whatever serde would have made of it.
+ *
+ * Writing puts the current version in, first, ahead of everything else.
+ */
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+
+// The file formats with versions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Config,
+    Level,
+    Save,
+    Replay,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Config => "config",
+            Format::Level => "level",
+            Format::Save => "save",
+            Format::Replay => "replay",
+        }
+    }
+
+    // The version this build writes, which is the newest it can read.
+    pub fn current(&self) -> u32 {
+        match *self {
+            Format::Config => 1,
+            Format::Level => 1,
+            Format::Save => 1,
+            Format::Replay => 1,
+        }
+    }
+}
+
+// A file being written: its version, and then the rest of it, as if
+// they'd all been the one struct.
+#[derive(Serialize)]
+struct Versioned<'a, T: 'a> {
+    version: u32,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+// `value` as JSON, laid out one field to a line, with `format`'s version.
+pub fn to_json<T: Serialize>(format: Format, value: &T) -> Result<String, String> {
+    let versioned = Versioned { version: format.current(), value: value };
+    serde_json::to_string_pretty(&versioned).map_err(|e| e.to_string())
+}
+
+// And back, from any version this build knows how to bring up to date.
+pub fn from_json<T: DeserializeOwned>(format: Format, json: &str) -> Result<T, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    serde_json::from_value(upgrade(format, value)?).map_err(|e| e.to_string())
+}
+
+// Whether this build can read version `version` of `format`, and if not,
+// why not.
+pub fn check(format: Format, version: u32) -> Result<(), String> {
+    if version <= format.current() { return Ok(()) }
+    Err(format!("this {} is version {}, from a newer build of the game; this one only \
+                 reads up to version {}", format.name(), version, format.current()))
+}
+
+/*
+ * A file's JSON, brought up to the current version, and without its
+ * version, which isn't part of what it's describing. Anything that isn't
+ * an object is left for serde to complain about.
+ */
+pub fn upgrade(format: Format, value: Value) -> Result<Value, String> {
+    let mut object = match value {
+        Value::Object(object) => object,
+        other => return Ok(other),
+    };
+    let version = match object.remove("version") {
+        None => 0,
+        Some(version) => match version.as_u64() {
+            Some(n) if n <= u32::max_value() as u64 => n as u32,
+            _ => return Err(format!("the version should be a whole number, not {}", version)),
+        },
+    };
+    check(format, version)?;
+    for from in version..format.current() {
+        object = step(format, from, object);
+    }
+    Ok(Value::Object(object))
+}
+
+// A version-`from` file of `format`, as the version after it would have it.
+fn step(format: Format, from: u32, mut object: Map<String, Value>) -> Map<String, Value> {
+    match (format, from) {
+        /*
+         * Quitting used to be Q, until Q and E turned the player, and a
+         * config.json written back then still says so: every turn to the
+         * left would quit. It's X now, like a new config.
+         */
+        (Format::Config, 0) => {
+            if object.get("quit_key").and_then(|key| key.as_str()) == Some("Q") {
+                info!("Quitting used to be Q, which turns the player now: it's X instead");
+                object.insert("quit_key".to_string(), Value::String("X".to_string()));
+            }
+        }
+        // Everything else from before there were versions reads as
+        // version 1 already.
+        _ => {}
+    }
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+
+    #[test]
+    fn old_files_are_brought_up_to_date() {
+        let old: Config = from_json(Format::Config, r#"{ "quit_key": "Q" }"#).unwrap();
+        assert_eq!(old.quit_key, "X");
+        // A version 1 config meant it.
+        let new: Config = from_json(Format::Config, r#"{ "version": 1, "quit_key": "Q" }"#)
+            .unwrap();
+        assert_eq!(new.quit_key, "Q");
+
+        let json = to_json(Format::Config, &new).unwrap();
+        assert!(json.starts_with("{\n  \"version\": 1,\n"));
+        assert_eq!(from_json::<Config>(Format::Config, &json).unwrap(), new);
+    }
+
+    #[test]
+    fn newer_files_are_turned_away() {
+        assert_eq!(from_json::<Config>(Format::Config, r#"{ "version": 7 }"#),
+                   Err("this config is version 7, from a newer build of the game; this one \
+                        only reads up to version 1".to_string()));
+        assert!(from_json::<Config>(Format::Config, r#"{ "version": "one" }"#).is_err());
+        assert!(from_json::<Config>(Format::Config, r#"{ "version": 1.5 }"#).is_err());
+        assert!(check(Format::Replay, 0).is_ok());
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * The high-score table: the ten best games played on this computer, kept in
+ * scores.json next to config.json so they're still there next time. Like
+ * everything else we write, it's JSON you can read (and, if you must, cheat
+ * in) with a text editor.
+ *
+ * A game that makes the table gets your name put next to it. The game-over
+ * screen asks for it (see game.rs), and the name is typed with the window's
+ * text events, the way the console's commands are. Each entry has when it
+ * was played too, as a date and time like the screenshots' names.
+ *
+ * A missing file is just an empty table; a broken one is an error, so it
+ * doesn't get written over by a table with one game in it.
+ */
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use capture;
+use locale::Locale;
+
+// How many games the table keeps, and how long a name can be.
+pub const TABLE_SIZE: usize = 10;
+pub const NAME_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    // Counting from 0, like level_index.
+    pub level: usize,
+    pub when: String,
+}
+
+impl Entry {
+    // An entry for a game that's just finished.
+    pub fn now(name: &str, score: u32, level: usize) -> Entry {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs())
+                                                                  .unwrap_or(0);
+        let name = name.trim();
+        Entry {
+            name: if name.is_empty() { "Anonymous".to_string() } else { name.to_string() },
+            score: score,
+            level: level,
+            when: capture::timestamp(seconds),
+        }
+    }
+}
+
+// Best first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    pub entries: Vec<Entry>,
+}
+
+impl HighScores {
+    /*
+     * Whether `score` would get into the table. A score of nothing never
+     * does, even with the table empty, or the first few games of anyone
+     * who's still finding the arrow keys would fill it. Matching the lowest
+     * score in a full table isn't enough either: the older one got there first.
+     */
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < TABLE_SIZE
+                      || self.entries.last().map_or(true, |e| score > e.score))
+    }
+
+    // Put `entry` in its place, and drop whatever falls off the bottom.
+    // Says where it went, counting from 0, if it stayed in.
+    pub fn add(&mut self, entry: Entry) -> Option<usize> {
+        let place = self.entries.iter().position(|e| entry.score > e.score)
+                                       .unwrap_or(self.entries.len());
+        self.entries.insert(place, entry);
+        self.entries.truncate(TABLE_SIZE);
+        if place < TABLE_SIZE { Some(place) } else { None }
+    }
+
+    // The table, ready to show to a person.
+    pub fn lines(&self, locale: &Locale) -> Vec<String> {
+        self.entries.iter().enumerate()
+            .map(|(i, e)| locale.text("game_over.entry", &[&(i + 1), &e.name, &e.score,
+                                                             &(e.level + 1)]))
+            .collect()
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    PathBuf::from("scores.json")
+}
+
+pub fn load(path: &Path) -> Result<HighScores, String> {
+    if !path.exists() {
+        return Ok(HighScores::default());
+    }
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))
+}
+
+pub fn save(path: &Path, scores: &HighScores) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(scores).map_err(|e| e.to_string())?;
+    File::create(path)
+        .and_then(|mut f| f.write_all(json.as_bytes()))
+        .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn entry(name: &str, score: u32) -> Entry {
+        Entry { name: name.to_string(), score: score, level: 0, when: String::new() }
+    }
+
+    #[test]
+    fn the_table_keeps_the_best_ten_in_order() {
+        let mut scores = HighScores::default();
+        assert!(!scores.qualifies(0) && scores.qualifies(1));
+        for i in 1..11 {
+            assert_eq!(scores.add(entry("old", i)), Some(0));
+        }
+        assert_eq!(scores.entries.len(), TABLE_SIZE);
+        // A tie goes below the one that got there first.
+        assert!(!scores.qualifies(1) && scores.qualifies(2));
+        assert_eq!(scores.add(entry("new", 5)), Some(6));
+        assert_eq!(scores.entries[5].name, "old");
+        assert_eq!(scores.entries.last().map(|e| e.score), Some(2));
+        assert_eq!(scores.add(entry("worst", 1)), None);
+        assert_eq!(scores.lines(&Locale::english())[0], "1. old - 10 (level 1)");
+    }
+
+    #[test]
+    fn blank_names_are_anonymous() {
+        assert_eq!(Entry::now("  ", 3, 1).name, "Anonymous");
+        assert_eq!(Entry::now(" Ada ", 3, 1).name, "Ada");
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let path = env::temp_dir().join(format!("jumpy-scores-test-{}.json", ::std::process::id()));
+        assert_eq!(load(&path), Ok(HighScores::default()));
+        let mut scores = HighScores::default();
+        scores.add(Entry::now("Ada", 7, 2));
+        save(&path, &scores).unwrap();
+        assert_eq!(load(&path), Ok(scores));
+        fs::write(&path, "[").unwrap();
+        assert!(load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * Input scripts: key presses written down ahead of time, for --headless to
+ * play back when there's nobody at the keyboard. One per line, a time in
+ * seconds from the start, then what happens:
+ *
+ *     # Walk right for half a second, then expand.
+ *     0.0 press Right
+ *     0.5 release Right
+ *     0.6 press Space
+ *
+ * Key names are the same ones the config uses (see keys.rs). Blank lines
+ * and anything after a # are ignored. The lines don't have to be in order;
+ * they're sorted by time, and ones with the same time keep the order they
+ * were written in.
+ *
+ * Replays are scripts too: the game writes down every key press as it
+ * happens, in this same format, and --replay plays them back. A replay also
+ * needs the random numbers to come out the same as they did the first
+ * time, so it has one more kind of line, saying which seed the game was
+ * played with:
+ *
+ *     seed 24301
+ *
+ * And a replay file starts with which version of all this it is (see
+ * schema.rs), so one from a newer build that this one can't play says so
+ * rather than going wrong partway through:
+ *
+ *     version 1
+ */
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use input::keyboard::Key;
+
+use keys;
+use schema::{self, Format};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Press,
+    Release,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub at: f64,
+    pub action: Action,
+    pub key: Key,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    steps: Vec<Step>,
+    // How many steps have been handed out by due already.
+    next: usize,
+    pub seed: Option<u64>,
+}
+
+impl Script {
+    // A script with nothing in it, for playing with nobody at the controls.
+    pub fn empty() -> Script {
+        Script { steps: Vec::new(), next: 0, seed: None }
+    }
+
+    // A script of steps that are already in order, like a recording.
+    pub fn from_steps(steps: Vec<Step>) -> Script {
+        Script { steps: steps, next: 0, seed: None }
+    }
+
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut steps = Vec::new();
+        let mut seed = None;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue }
+            let bad = |what: &str| format!("line {}: {}", i + 1, what);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() == 2 && words[0] == "seed" {
+                match words[1].parse() {
+                    Ok(n) => seed = Some(n),
+                    Err(_) => return Err(bad("the seed should be a whole number")),
+                }
+                continue;
+            }
+            if words.len() == 2 && words[0] == "version" {
+                match words[1].parse() {
+                    Ok(n) => schema::check(Format::Replay, n).map_err(|e| bad(&e))?,
+                    Err(_) => return Err(bad("the version should be a whole number")),
+                }
+                continue;
+            }
+            if words.len() != 3 {
+                return Err(bad("expected a time, press or release, and a key"));
+            }
+            let at = match words[0].parse::<f64>() {
+                Ok(at) if at >= 0.0 => at,
+                _ => return Err(bad("the time should be seconds from the start")),
+            };
+            let action = match words[1] {
+                "press" => Action::Press,
+                "release" => Action::Release,
+                _ => return Err(bad("expected press or release")),
+            };
+            let key = match keys::parse_key(words[2]) {
+                Some(key) => key,
+                None => return Err(bad(&format!("there's no key called \"{}\"", words[2]))),
+            };
+            steps.push(Step { at: at, action: action, key: key });
+        }
+        // sort_by is stable, so steps at the same time stay in file order.
+        steps.sort_by(|a, b| a.at.partial_cmp(&b.at).unwrap());
+        Ok(Script { steps: steps, next: 0, seed: seed })
+    }
+
+    pub fn load(path: &Path) -> Result<Script, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        Script::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /*
+     * The script as text that parse reads back exactly, with `header` as a
+     * comment at the top. Times are written with rust's {} formatting,
+     * which always writes the shortest number that reads back as the very
+     * same f64, so nothing gets rounded off on the way.
+     */
+    pub fn to_text(&self, header: &str) -> String {
+        let mut text = String::new();
+        for line in header.lines() {
+            text.push_str(&format!("# {}\n", line));
+        }
+        if let Some(seed) = self.seed {
+            text.push_str(&format!("seed {}\n", seed));
+        }
+        for step in self.steps.iter() {
+            let action = match step.action {
+                Action::Press => "press",
+                Action::Release => "release",
+            };
+            match keys::short_name(step.key) {
+                Some(name) => text.push_str(&format!("{} {} {}\n", step.at, action, name)),
+                // A key without a name can't be read back, and can't have
+                // done anything either, since the game only uses named ones.
+                None => {}
+            }
+        }
+        text
+    }
+
+    // Written to a file, it's a replay, so it says which version it is.
+    pub fn save(&self, path: &Path, header: &str) -> Result<(), String> {
+        let text = format!("version {}\n{}", Format::Replay.current(), self.to_text(header));
+        File::create(path)
+            .and_then(|mut f| f.write_all(text.as_bytes()))
+            .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+    }
+
+    // Everything due by `time` that hasn't been handed out yet, in order.
+    pub fn due(&mut self, time: f64) -> Vec<Step> {
+        let start = self.next;
+        while self.next < self.steps.len() && self.steps[self.next].at <= time {
+            self.next += 1;
+        }
+        self.steps[start..self.next].to_vec()
+    }
+
+    // All of it, handed out or not.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    // Whether every step has been handed out.
+    pub fn finished(&self) -> bool {
+        self.next >= self.steps.len()
+    }
+
+    // When the last step happens, or 0 for an empty script.
+    pub fn length(&self) -> f64 {
+        self.steps.last().map(|s| s.at).unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::keyboard;
+
+    #[test]
+    fn parses_and_sorts_steps() {
+        let mut script = Script::parse("
+            # Comments and blank lines are fine.
+            0.5 release Right
+            0.0 press Right   # so are comments at the end
+            0.5 press space
+        ").unwrap();
+        assert_eq!(script.length(), 0.5);
+        assert_eq!(script.due(0.1), vec![
+            Step { at: 0.0, action: Action::Press, key: keyboard::Right },
+        ]);
+        assert_eq!(script.due(0.2), vec![]);
+        assert_eq!(script.due(1.0), vec![
+            Step { at: 0.5, action: Action::Release, key: keyboard::Right },
+            Step { at: 0.5, action: Action::Press, key: keyboard::Space },
+        ]);
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let mut script = Script::from_steps(vec![
+            Step { at: 1.0 / 120.0, action: Action::Press, key: keyboard::Left },
+            Step { at: 0.25, action: Action::Release, key: keyboard::Left },
+        ]);
+        script.seed = Some(24301);
+        let text = script.to_text("A test\nof two lines");
+        assert!(text.starts_with("# A test\n# of two lines\n"));
+        assert_eq!(Script::parse(&text), Ok(script));
+    }
+
+    #[test]
+    fn says_which_line_is_wrong() {
+        assert_eq!(Script::parse("0 press Right\n1 press Nope"),
+                   Err("line 2: there's no key called \"Nope\"".to_string()));
+        assert!(Script::parse("soon press Right").is_err());
+        assert!(Script::parse("-1 press Right").is_err());
+        assert!(Script::parse("1 tap Right").is_err());
+        assert!(Script::parse("1 press").is_err());
+        assert!(Script::parse("seed lots").is_err());
+        assert!(Script::parse("version 1\n0 press Right").is_ok());
+        assert_eq!(Script::parse("version 99\n0 press Right"),
+                   Err("line 1: this replay is version 99, from a newer build of the game; \
+                        this one only reads up to version 1".to_string()));
+    }
+}
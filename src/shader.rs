@@ -0,0 +1,371 @@
+/*
+ * Shaders of your own. A fragment shader is a little program, written in
+ * GLSL, that the graphics card runs once for every pixel it draws, to say
+ * what color that pixel comes out; opengl_graphics has its own few, which
+ * just paint shapes the color they're asked for, and pictures as they are.
+ * Set `shader` in config.json to a file with one of yours in it, and the
+ * game gets drawn through that as well: every frame gets drawn into a
+ * texture rather than the window (see postprocess.rs), and after the
+ * game's own effects, your shader draws it onto the window however it
+ * likes. There are a couple to try in shaders/.
+ *
+ * It can have all of the view (`"shader_target": "scene"`, the default),
+ * or just a box round the player (`"player"`), and it gets told a few
+ * things to work with, as uniforms:
+ *
+ *     uniform sampler2D scene;  // what was drawn, to look things up in
+ *     uniform float time;       // seconds since the shader was loaded
+ *     uniform vec2 player;      // where player one's middle is, in pixels
+ *     uniform float expand;     // how puffed up player one is
+ *     uniform vec2 resolution;  // how big the window is, in pixels
+ *     in vec2 uv;               // where this pixel is, 0 to 1 across
+ *     out vec4 color;           // what to make it
+ *
+ * Those declarations go on the front of the file for you, along with the
+ * GLSL version, so a shader can be as short as
+ *
+ *     void main() { color = texture(scene, uv).bgra; }
+ *
+ * (which swaps red and blue over), unless the file starts with a
+ * `#version` of its own, in which case it's all yours, and declaring them
+ * is up to you. Pixels count from the
+ * window's bottom-left corner, the way OpenGL does, so `player` is too,
+ * and is in the same pixels as gl_FragCoord: `distance(gl_FragCoord.xy,
+ * player)` is how far you are from the player.
+ *
+ * The rest of the game draws through opengl_graphics, which keeps OpenGL's
+ * state to itself, so talking to OpenGL directly is done with care: what
+ * gets changed is put back (see Game::finish_frame), and only this,
+ * postprocess.rs and capture.rs do it at all. It's GLSL 1.50, which needs
+ * OpenGL 3.2 (the default); on anything older, or if the shader doesn't
+ * compile, there's a warning saying why and the game is drawn without it.
+ * It's read when the window opens, and again with F10.
+ */
+use std::ffi::CString;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+use std::time::Instant;
+
+use gl;
+use gl::types::{GLchar, GLenum, GLint, GLsizeiptr, GLuint};
+
+use collision::Aabb;
+use frametime::seconds;
+
+// What a shader's drawn over: all of the view, or just the player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShaderTarget {
+    Scene,
+    Player,
+}
+
+// How far round the player the "player" box reaches, in points, so a glow
+// has somewhere to go.
+pub const PLAYER_MARGIN: f64 = 16.0;
+
+// What goes on the front of a shader without a #version of its own.
+pub const PREAMBLE: &'static str = "#version 150
+uniform sampler2D scene;
+uniform float time;
+uniform vec2 player;
+uniform float expand;
+uniform vec2 resolution;
+in vec2 uv;
+out vec4 color;
+#line 1
+";
+
+// The vertex shader, which is always ours: it puts the corners where
+// they're asked for, and works out uv from where they end up.
+const VERTEX: &'static str = "#version 150
+in vec2 position;
+out vec2 uv;
+void main() {
+    uv = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+";
+
+// What the shader's told about the game this frame, but the time, which
+// Shader keeps itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Uniforms {
+    pub player: [f32; 2],
+    pub expand: f32,
+    pub resolution: [f32; 2],
+}
+
+impl Uniforms {
+    /*
+     * For a window `window` points across and down with `scale` pixels to
+     * the point, and the player's box in it (in points, from the top-left
+     * corner, as everything else is) if there's a game going. Without one,
+     * the "player" is in the middle. Flipping y over is what makes it count
+     * from the bottom instead.
+     */
+    pub fn new(window: (f64, f64), scale: f64, player: Option<Aabb>, expand: f64) -> Uniforms {
+        let (x, y) = match player {
+            Some(b) => (b.x + b.w / 2.0, b.y + b.h / 2.0),
+            None => (window.0 / 2.0, window.1 / 2.0),
+        };
+        Uniforms {
+            player: [(x * scale) as f32, ((window.1 - y) * scale) as f32],
+            expand: expand as f32,
+            resolution: [(window.0 * scale) as f32, (window.1 * scale) as f32],
+        }
+    }
+}
+
+/*
+ * The four corners of `area`, a box in a window `window` points big, in
+ * the order a triangle strip wants them: bottom left, bottom right, top
+ * left, top right. OpenGL wants them from -1 to 1 across the window, with
+ * y going up.
+ */
+pub fn corners(area: Aabb, window: (f64, f64)) -> [[f32; 2]; 4] {
+    let x = |x: f64| (x / window.0 * 2.0 - 1.0) as f32;
+    let y = |y: f64| (1.0 - y / window.1 * 2.0) as f32;
+    let (left, right) = (x(area.x), x(area.right()));
+    let (top, bottom) = (y(area.y), y(area.bottom()));
+    [[left, bottom], [right, bottom], [left, top], [right, top]]
+}
+
+// The whole fragment shader, from what was in the file.
+pub fn source(text: &str) -> String {
+    if text.trim_start().starts_with("#version") {
+        text.to_string()
+    } else {
+        format!("{}{}", PREAMBLE, text)
+    }
+}
+
+/*
+ * A compiled and linked pair of shaders, ours for the vertices and
+ * someone's for the pixels, with what it takes to draw a box with them:
+ * the corners go in a buffer, and a vertex array says how to read them.
+ * The user's shader is one of these, and so is every pass in
+ * postprocess.rs.
+ *
+ * What it's made of lives in the OpenGL of the window that was open when
+ * it was made, and goes when the window does, so there's no Drop: after
+ * F11, say, there's a new window, and the old numbers could be the new
+ * window's things by then. Done with one in the same window, delete it.
+ */
+pub struct Program {
+    id: GLuint,
+    vertex_array: GLuint,
+    buffer: GLuint,
+}
+
+impl Program {
+    /*
+     * Compile `fragment` (and our vertex shader) and link them. OpenGL's
+     * errors come back as its log, which says what's wrong and on which
+     * line. Calling OpenGL is unsafe for the same reason as in capture.rs;
+     * what makes it all right here is that every string it's given is a
+     * CString, so it ends where OpenGL thinks it does, and every number it
+     * hands back is kept, to give it back to it later.
+     */
+    pub fn new(fragment: &str) -> Result<Program, String> {
+        unsafe {
+            let vertex = compile(gl::VERTEX_SHADER, VERTEX)?;
+            let fragment = match compile(gl::FRAGMENT_SHADER, fragment) {
+                Ok(fragment) => fragment,
+                Err(e) => {
+                    gl::DeleteShader(vertex);
+                    return Err(e);
+                }
+            };
+            let id = gl::CreateProgram();
+            gl::AttachShader(id, vertex);
+            gl::AttachShader(id, fragment);
+            let (position, color) = (name("position"), name("color"));
+            gl::BindAttribLocation(id, 0, position.as_ptr());
+            gl::BindFragDataLocation(id, 0, color.as_ptr());
+            gl::LinkProgram(id);
+            gl::DeleteShader(vertex);
+            gl::DeleteShader(fragment);
+            let mut linked = 0;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut linked);
+            if linked == 0 {
+                let log = program_log(id);
+                gl::DeleteProgram(id);
+                return Err(format!("the shader didn't link: {}", log));
+            }
+            let (mut vertex_array, mut buffer) = (0, 0);
+            gl::GenVertexArrays(1, &mut vertex_array);
+            gl::GenBuffers(1, &mut buffer);
+            Ok(Program { id: id, vertex_array: vertex_array, buffer: buffer })
+        }
+    }
+
+    // Start using this one, so the uniforms can be set. draw stops again.
+    pub fn bind(&self) {
+        unsafe { gl::UseProgram(self.id) }
+    }
+
+    // Set the uniform `uniform`. A shader that doesn't have one has it at
+    // -1, which OpenGL quietly ignores setting.
+    pub fn float(&self, uniform: &str, value: f32) {
+        unsafe { gl::Uniform1f(self.uniform(uniform), value) }
+    }
+
+    pub fn vec2(&self, uniform: &str, value: [f32; 2]) {
+        unsafe { gl::Uniform2f(self.uniform(uniform), value[0], value[1]) }
+    }
+
+    // The texture `texture` as the sampler `uniform`, on texture unit `unit`.
+    pub fn texture(&self, uniform: &str, unit: u32, texture: GLuint) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::Uniform1i(self.uniform(uniform), unit as GLint);
+        }
+    }
+
+    // Everything the user's shader is told (see Uniforms), but the scene.
+    pub fn uniforms(&self, uniforms: &Uniforms, time: f64) {
+        self.float("time", time as f32);
+        self.vec2("player", uniforms.player);
+        self.float("expand", uniforms.expand);
+        self.vec2("resolution", uniforms.resolution);
+    }
+
+    /*
+     * Draw the box with `corners` (see corners) into whatever's being drawn
+     * into. Blending's off while it draws, so what the shader says is what
+     * you get, alpha and all.
+     */
+    pub fn draw(&self, corners: &[[f32; 2]; 4]) {
+        unsafe {
+            gl::BindVertexArray(self.vertex_array);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer);
+            gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(corners) as GLsizeiptr,
+                           corners.as_ptr() as *const _, gl::STREAM_DRAW);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::Disable(gl::BLEND);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::UseProgram(0);
+        }
+    }
+
+    pub fn delete(self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+            gl::DeleteVertexArrays(1, &self.vertex_array);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+
+    fn uniform(&self, uniform: &str) -> GLint {
+        unsafe { gl::GetUniformLocation(self.id, name(uniform).as_ptr()) }
+    }
+}
+
+// The user's shader: a Program, what it's aimed at, and when it started,
+// for `time`.
+pub struct Shader {
+    pub target: ShaderTarget,
+    program: Program,
+    started: Instant,
+}
+
+impl Shader {
+    pub fn load(path: &Path, target: ShaderTarget) -> Result<Shader, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let program = Program::new(&source(&text))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Shader { target: target, program: program, started: Instant::now() })
+    }
+
+    // Draw the shader over `corners`, with `scene` (a texture) as its
+    // scene. postprocess.rs says which texture, and where it's drawn to.
+    pub fn apply(&self, uniforms: &Uniforms, corners: &[[f32; 2]; 4], scene: GLuint) {
+        self.program.bind();
+        self.program.texture("scene", 0, scene);
+        self.program.uniforms(uniforms, seconds(self.started.elapsed()));
+        self.program.draw(corners);
+    }
+
+    pub fn delete(self) {
+        self.program.delete();
+    }
+}
+
+fn name(name: &str) -> CString {
+    CString::new(name).expect("uniform names don't have NULs in")
+}
+
+// Compile `text` as a shader of sort `kind`, or say what was wrong with it.
+unsafe fn compile(kind: GLenum, text: &str) -> Result<GLuint, String> {
+    let text = CString::new(text).map_err(|_| "the shader has a NUL in it".to_string())?;
+    let shader = gl::CreateShader(kind);
+    gl::ShaderSource(shader, 1, &text.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+    let mut compiled = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut compiled);
+    if compiled != 0 { return Ok(shader) }
+
+    let mut length = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+    let mut log = vec![0u8; length.max(1) as usize];
+    gl::GetShaderInfoLog(shader, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+    gl::DeleteShader(shader);
+    let which = if kind == gl::VERTEX_SHADER { "vertex" } else { "fragment" };
+    Err(format!("the {} shader didn't compile: {}", which, log_text(&log)))
+}
+
+unsafe fn program_log(program: GLuint) -> String {
+    let mut length = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+    let mut log = vec![0u8; length.max(1) as usize];
+    gl::GetProgramInfoLog(program, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+    log_text(&log)
+}
+
+// OpenGL's logs end with a NUL, and usually a new line before that.
+fn log_text(log: &[u8]) -> String {
+    String::from_utf8_lossy(log).trim_end_matches('\0').trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shaders_get_the_declarations_unless_they_have_their_own() {
+        let short = "void main() { color = texture(scene, uv); }";
+        assert!(source(short).starts_with("#version 150\n"));
+        assert!(source(short).contains("uniform vec2 player;\n"));
+        assert!(source(short).ends_with(short));
+        let own = "\n#version 330 core\nvoid main() {}";
+        assert_eq!(source(own), own);
+        assert_eq!(log_text(b"0:3(1): error: oops\n\0"), "0:3(1): error: oops");
+    }
+
+    #[test]
+    fn the_uniforms_count_pixels_from_the_bottom() {
+        let player = Aabb::new(100.0, 50.0, 20.0, 20.0);
+        let u = Uniforms::new((640.0, 480.0), 2.0, Some(player), 3.0);
+        assert_eq!(u.player, [220.0, 820.0]);
+        assert_eq!(u.resolution, [1280.0, 960.0]);
+        assert_eq!(u.expand, 3.0);
+        assert_eq!(Uniforms::new((640.0, 480.0), 1.0, None, 0.0).player, [320.0, 240.0]);
+
+        assert_eq!(corners(Aabb::new(0.0, 0.0, 640.0, 480.0), (640.0, 480.0)),
+                   [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]]);
+        assert_eq!(corners(Aabb::new(320.0, 0.0, 160.0, 240.0), (640.0, 480.0)),
+                   [[0.0, 0.0], [0.5, 0.0], [0.0, 1.0], [0.5, 1.0]]);
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * Screen shake: for a moment after something big happens, the whole view
+ * jiggles about. It's a cheap trick, but it makes a button press feel like
+ * it *did* something.
+ *
+ * Every update we pick a fresh random offset, no bigger than the current
+ * amplitude, and the drawing gets shifted by it. The amplitude starts at
+ * whatever the shake was started with and dies away to nothing over the
+ * shake's duration. It dies away along a curve rather than a straight line
+ * (the fraction left, squared), so the shake is mostly a sharp jolt with a
+ * quick tail, rather than a long wobble.
+ *
+ * The shake has its own random number generator rather than borrowing the
+ * game's, so that shaking or not shaking can never change where a particle
+ * goes.
+ */
+use rng::Rng;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenShake {
+    amplitude: f64,
+    duration: f64,
+    time_left: f64,
+    offset: (f64, f64),
+    rng: Rng,
+}
+
+impl ScreenShake {
+    pub fn new(seed: u64) -> ScreenShake {
+        ScreenShake {
+            amplitude: 0.0,
+            duration: 0.0,
+            time_left: 0.0,
+            offset: (0.0, 0.0),
+            rng: Rng::new(seed),
+        }
+    }
+
+    /*
+     * Start shaking, `amplitude` pixels at most, for `duration` seconds. If
+     * we're already shaking harder than that, carry on with the bigger
+     * shake; mashing Space shouldn't make the screen calm down.
+     */
+    pub fn start(&mut self, amplitude: f64, duration: f64) {
+        if duration <= 0.0 || amplitude <= 0.0 { return }
+        if amplitude >= self.current_amplitude() {
+            self.amplitude = amplitude;
+            self.duration = duration;
+            self.time_left = duration;
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.time_left = (self.time_left - dt).max(0.0);
+        let a = self.current_amplitude();
+        self.offset = if a > 0.0 {
+            (self.rng.range(-a, a), self.rng.range(-a, a))
+        } else {
+            (0.0, 0.0)
+        };
+    }
+
+    // How far to shift the drawing this frame.
+    pub fn offset(&self) -> (f64, f64) {
+        self.offset
+    }
+
+    fn current_amplitude(&self) -> f64 {
+        if self.duration <= 0.0 { return 0.0 }
+        let left = self.time_left / self.duration;
+        self.amplitude * left * left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn still_when_not_shaking() {
+        let mut shake = ScreenShake::new(1);
+        shake.update(0.1);
+        assert_eq!(shake.offset(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn shakes_within_amplitude_then_settles() {
+        let mut shake = ScreenShake::new(1);
+        shake.start(8.0, 0.5);
+        let mut moved = false;
+        for _ in 0..30 {
+            shake.update(1.0 / 60.0);
+            let (x, y) = shake.offset();
+            assert!(x.abs() <= 8.0 && y.abs() <= 8.0);
+            moved |= x != 0.0 || y != 0.0;
+        }
+        assert!(moved);
+        // Half a second has gone by, so it's over.
+        shake.update(1.0 / 60.0);
+        assert_eq!(shake.offset(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn a_smaller_shake_does_not_interrupt_a_bigger_one() {
+        let mut shake = ScreenShake::new(1);
+        shake.start(8.0, 1.0);
+        shake.start(2.0, 1.0);
+        assert_eq!(shake.current_amplitude(), 8.0);
+    }
+}
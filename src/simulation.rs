@@ -0,0 +1,301 @@
+/*
+ * A game without a window, to test with. A Simulation is an App and
+ * something to draw it on that only writes down what it was asked to draw
+ * (a Recorder; see renderer.rs), so there's no OpenGL anywhere, and a
+ * test can make one, press keys at it, run it on so many ticks, and then
+ * look at what happened, the way a player would but a lot quicker:
+ *
+ *     let mut sim = Simulation::new(Config::default());
+ *     sim.hold(keyboard::Right, 60);
+ *     assert!(sim.app.players[0].x > 50.0);
+ *
+ * Every step is one tick of SIM_DT, going in through App::update the same
+ * as a real update that happened to be exactly a tick long, so it's the
+ * very same game as with a window: the replay keys, the rewind, the dump,
+ * the lot. The ticks are the only clock (see App::update), which is why a
+ * test can say "sixty ticks" and mean it, and why two simulations given
+ * the same keys at the same ticks end up exactly the same, down to the
+ * last bit of the JSON.
+ *
+ * Keys and the rest go in as engine Events, the ones a window would have
+ * sent, or there's press, release and hold for the usual case. What's
+ * come of it is all in `app`, out in the open, and `state` has it as one
+ * value, the same as a line of --dump-state (see dump.rs), to compare,
+ * or write out.
+ */
+use std::path::PathBuf;
+
+use app::{App, Settings, SIM_DT};
+use assets::Assets;
+use config::Config;
+use dump::State;
+use engine::Event;
+use handlers::{handleKey, handleRelease};
+use hud::Hud;
+use input::{keyboard, Keyboard, UpdateArgs};
+use level::Level;
+use locale::Locale;
+use renderer::Recorder;
+
+// The seed every simulation starts from unless its Settings say otherwise,
+// so the same test goes the same way every time it's run.
+pub const SEED: u64 = 42;
+
+/*
+ * Settings for a game that's all in memory: the config you give it, the
+ * fallback level (one wall, and nothing else to get in the way), and
+ * nothing that reads or writes a file, or talks to anyone. Change the
+ * levels, or anything else, before making the Simulation.
+ */
+pub fn settings(config: Config) -> Settings {
+    Settings {
+        config: config,
+        config_path: PathBuf::from("no-such-config.json"),
+        ups: 120,
+        seed: SEED,
+        levels: vec![Level::fallback()],
+        levels_path: None,
+        replay: None,
+        record_path: None,
+        coop: false,
+        link: None,
+        spectators: None,
+        lua: None,
+        dump: None,
+        locale: Locale::english(),
+    }
+}
+
+pub struct Simulation {
+    pub app: App,
+    pub drawn: Recorder,
+    assets: Assets,
+    hud: Hud,
+}
+
+impl Simulation {
+    pub fn new(config: Config) -> Simulation {
+        Simulation::with_settings(settings(config))
+    }
+
+    pub fn with_settings(settings: Settings) -> Simulation {
+        Simulation {
+            app: App::new(settings),
+            drawn: Recorder::new(),
+            assets: Assets::none(),
+            hud: Hud::none(),
+        }
+    }
+
+    /*
+     * Something that could have come from a window. Keys go where a key
+     * pressed while playing would (see handlers.rs), and an Update moves
+     * time on by whatever it says, fixed timestep and all; step is the one
+     * to use for that, mostly. Frames, files and closing have nothing here
+     * to happen to.
+     */
+    pub fn event(&mut self, event: &Event) {
+        match *event {
+            Event::Press(button) => handleKey(button, &mut self.app),
+            Event::Release(button) => handleRelease(button, &mut self.app),
+            Event::Text(ref text) if self.app.console.open => self.app.console.type_text(text),
+            Event::Axis(args) => self.app.controller_axis(args.axis, args.position),
+            Event::Cursor(x, y) => self.app.mouse_moved(x, y),
+            Event::Resize(w, h) => self.app.resize_window(w, h),
+            Event::Update(ref args) => self.app.update(args),
+            Event::Text(_) | Event::Scroll(..) | Event::Render(_) | Event::Drop(_) |
+            Event::Close | Event::Other => {}
+        }
+    }
+
+    pub fn press(&mut self, key: keyboard::Key) {
+        self.event(&Event::Press(Keyboard(key)));
+    }
+
+    pub fn release(&mut self, key: keyboard::Key) {
+        self.event(&Event::Release(Keyboard(key)));
+    }
+
+    // Press `key`, keep it down for `ticks`, and let go.
+    pub fn hold(&mut self, key: keyboard::Key, ticks: u64) {
+        self.press(key);
+        self.step(ticks);
+        self.release(key);
+    }
+
+    // On by `ticks` ticks, one at a time.
+    pub fn step(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.app.update(&UpdateArgs { dt: SIM_DT });
+        }
+    }
+
+    // On by as many ticks as there are in `seconds`, near enough.
+    pub fn run_for(&mut self, seconds: f64) {
+        self.step((seconds / SIM_DT).round() as u64);
+    }
+
+    // Draw a frame, for looking through what got drawn.
+    pub fn draw(&mut self) -> &Recorder {
+        self.drawn.calls.clear();
+        self.app.render(&mut self.drawn, &self.assets, &mut self.hud);
+        &self.drawn
+    }
+
+    // Everything about the game as it is now.
+    pub fn state(&self) -> State {
+        State { frame: self.app.frame(), save: self.app.snapshot() }
+    }
+
+    pub fn to_json(&self) -> String {
+        self.state().to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use app::LevelState;
+    use ecs::Kind;
+    use enemy;
+    use tween::Easing;
+
+    // Going straight to full speed, and stopping dead, keeps the sums simple.
+    fn snappy() -> Config {
+        Config { acceleration: 0.0, friction: 0.0, ..Config::default() }
+    }
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn holding_a_key_walks_at_move_speed() {
+        let mut sim = Simulation::new(snappy());
+        let (x, y) = (sim.app.players[0].x, sim.app.players[0].y);
+        sim.hold(keyboard::Right, 60);
+        // Half a second at 200 pixels a second, and not a tick more after
+        // letting go.
+        assert!(near(sim.app.players[0].x, x + 100.0));
+        assert_eq!(sim.app.players[0].y, y);
+        sim.step(30);
+        assert!(near(sim.app.players[0].x, x + 100.0));
+        // Diagonally, it's no faster.
+        sim.press(keyboard::Down);
+        sim.hold(keyboard::Left, 60);
+        let gone = 100.0 / 2f64.sqrt();
+        assert!(near(sim.app.players[0].x, x + 100.0 - gone));
+        assert!(near(sim.app.players[0].y, y + gone));
+    }
+
+    #[test]
+    fn speeding_up_and_slowing_down_take_a_few_ticks() {
+        let mut sim = Simulation::new(Config::default());
+        sim.press(keyboard::Right);
+        sim.step(1);
+        let speed = sim.app.config.move_speed;
+        assert!(sim.app.players[0].vx > 0.0 && sim.app.players[0].vx < speed);
+        sim.run_for(speed / sim.app.config.acceleration);
+        assert_eq!(sim.app.players[0].vx, speed);
+        sim.release(keyboard::Right);
+        sim.step(1);
+        assert!(sim.app.players[0].vx > 0.0 && sim.app.players[0].vx < speed);
+        sim.run_for(speed / sim.app.config.friction);
+        assert_eq!(sim.app.players[0].vx, 0.0);
+    }
+
+    #[test]
+    fn the_edges_of_the_world_hold_the_player_in() {
+        let mut sim = Simulation::new(snappy());
+        sim.app.players[0].x = 1250.0;
+        sim.app.players[0].y = 930.0;
+        sim.press(keyboard::Right);
+        sim.hold(keyboard::Down, 60);
+        let (width, height) = sim.app.world_size();
+        let b = sim.app.player_box();
+        assert_eq!((b.right(), b.bottom()), (width, height));
+        assert_eq!((sim.app.players[0].vx, sim.app.players[0].vy), (0.0, 0.0));
+        // Puffing up in the corner grows it back into the world, not out.
+        sim.press(keyboard::Space);
+        sim.step(1);
+        let b = sim.app.player_box();
+        assert!(b.w > sim.app.players[0].base_size);
+        assert_eq!((b.right(), b.bottom()), (width, height));
+    }
+
+    #[test]
+    fn expand_goes_up_a_step_and_decays_back_to_nothing() {
+        let config = Config { expand_easing: Easing::Linear, ..snappy() };
+        let (step, decay) = (config.expand_step, config.expand_decay);
+        let mut sim = Simulation::new(config);
+        sim.press(keyboard::Space);
+        assert_eq!(sim.app.players[0].expand, step);
+        sim.step(6);
+        assert!(near(sim.app.players[0].expand, step - decay * 6.0 * SIM_DT));
+        // Another press goes on top of what's left.
+        sim.release(keyboard::Space);
+        sim.press(keyboard::Space);
+        let left = sim.app.players[0].expand;
+        assert!(near(left, step * 2.0 - decay * 6.0 * SIM_DT));
+        sim.run_for(left / decay + SIM_DT);
+        assert_eq!(sim.app.players[0].expand, 0.0);
+        sim.step(10);
+        assert_eq!(sim.app.players[0].expand, 0.0);
+    }
+
+    #[test]
+    fn walls_and_enemies_get_in_the_way() {
+        let mut sim = Simulation::new(snappy());
+        // Level with the wall at x = 300, and walking into it.
+        sim.app.players[0].y = 150.0;
+        sim.hold(keyboard::Right, 240);
+        assert_eq!(sim.app.player_box().right(), 300.0);
+        assert_eq!(sim.app.players[0].vx, 0.0);
+
+        // An enemy right on top of us bites once, and not again until
+        // hurt_seconds are up.
+        let mut sim = Simulation::new(snappy());
+        let health = sim.app.health;
+        let (x, y) = sim.app.player_centre();
+        let color = sim.app.config.palette().enemy;
+        enemy::spawn(&mut sim.app.scene, x, y, color);
+        sim.step(1);
+        assert_eq!(sim.app.health, health - sim.app.config.enemy_damage);
+        sim.run_for(sim.app.config.hurt_seconds / 2.0);
+        assert_eq!(sim.app.health, health - sim.app.config.enemy_damage);
+        sim.run_for(sim.app.config.hurt_seconds);
+        assert_eq!(sim.app.health, health - sim.app.config.enemy_damage * 2);
+        assert_eq!(sim.app.scene.boxes(Kind::Enemy).len(), 1);
+        assert_eq!(sim.app.level_state, LevelState::Playing);
+    }
+
+    #[test]
+    fn the_same_keys_make_the_same_game() {
+        let play = |keys: &[keyboard::Key]| {
+            let mut sim = Simulation::new(Config::default());
+            for &key in keys.iter() {
+                sim.hold(key, 30);
+                sim.event(&Event::Press(Keyboard(keyboard::Space)));
+                sim.step(5);
+            }
+            sim.to_json()
+        };
+        let keys = [keyboard::Right, keyboard::Down, keyboard::Right, keyboard::Up];
+        assert_eq!(play(&keys[..]), play(&keys[..]));
+        assert!(play(&keys[..]) != play(&keys[..3]));
+
+        let mut sim = Simulation::new(Config::default());
+        sim.hold(keyboard::Down, 30);
+        let state = sim.state();
+        assert_eq!(state.frame.tick, 30);
+        assert_eq!(state.save.player, [sim.app.players[0].x, sim.app.players[0].y]);
+    }
+
+    #[test]
+    fn drawing_needs_no_window() {
+        let mut sim = Simulation::new(Config::default());
+        let wall = sim.app.config.palette().wall;
+        let drawn = sim.draw();
+        assert!(drawn.rects_in(wall).contains(&[300.0, 100.0, 5.0, 200.0]));
+    }
+}
@@ -0,0 +1,275 @@
+/*
+ * Watching a game from somewhere else. With --spectate the game listens for
+ * WebSocket connections on port 7778, and every tick it sends everyone
+ * who's connected a JSON picture of what's going on: where the players,
+ * walls, pickups and enemies are, the score and so on. A web page can draw
+ * that (spectate.html, next to the README, is a small one that does), or a
+ * script can write it all down to pore over later.
+ *
+ * This is where keeping the game and the window apart pays off. App
+ * already knows everything there is to know about the game without any
+ * help from the window, so a Frame is just the interesting parts of App
+ * copied into a struct serde can write out.
+ *
+ * WebSocket is a way of keeping a connection open to a web browser and
+ * sending it messages, and it starts off as an ordinary web request that
+ * asks to be "upgraded". The tungstenite crate does the upgrading and the
+ * framing of the messages, behind the "spectate" cargo feature, which is off
+ * unless you ask for it (cargo run --features spectate). Built without it
+ * the game still listens, so nothing else has to know, but turns away
+ * anyone who connects.
+ */
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(feature = "spectate")]
+use std::time::Duration;
+
+use serde_json;
+
+#[cfg(feature = "spectate")]
+use tungstenite;
+
+use controls::Facing;
+
+// Where --spectate listens: one along from the network game's port.
+pub const DEFAULT_PORT: u16 = 7778;
+
+// How long someone connecting gets to finish saying hello. The game waits
+// for them, so it mustn't be long.
+#[cfg(feature = "spectate")]
+const HANDSHAKE_TIMEOUT_MS: u64 = 500;
+
+// One player, as a spectator sees them. Boxes are [x, y, width, height].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Shown {
+    pub bounds: [f64; 4],
+    pub facing: Facing,
+    pub color: [f32; 4],
+}
+
+// One tick of the game, as sent to the spectators.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Frame {
+    pub tick: u64,
+    pub level: usize,
+    pub level_name: String,
+    // Playing, Complete or Lost, as in LevelState.
+    pub state: String,
+    pub score: u32,
+    pub health: u32,
+    pub elapsed: f64,
+    pub world: [f64; 2],
+    // The top-left corner of what the window's looking at, and how much of
+    // the world it can see.
+    pub camera: [f64; 2],
+    pub view: [f64; 2],
+    pub players: Vec<Shown>,
+    pub walls: Vec<[f64; 4]>,
+    pub pickups: Vec<[f64; 4]>,
+    pub enemies: Vec<[f64; 4]>,
+    pub projectiles: Vec<[f64; 4]>,
+    pub goal: [f64; 4],
+}
+
+impl Frame {
+    pub fn to_json(&self) -> String {
+        // Nothing in a Frame can fail to turn into JSON.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/*
+ * A connected spectator. Without the spectate feature there's no such
+ * thing, the same trick as audio.rs's Clip: an enum with nothing in it,
+ * which can be named but never made.
+ */
+#[cfg(feature = "spectate")]
+type Client = tungstenite::WebSocket<TcpStream>;
+#[cfg(not(feature = "spectate"))]
+enum Client {}
+
+pub struct Spectators {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl Spectators {
+    // Listen for spectators on `port`. Like the network game's socket,
+    // this one's non-blocking, so checking for newcomers never waits.
+    pub fn listen(port: u16) -> Result<Spectators, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("couldn't listen for spectators on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Spectators { listener: listener, clients: Vec::new() })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    // Let in anyone who's turned up since last time.
+    pub fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, from)) => {
+                    if let Some(client) = accept_client(stream, from) {
+                        info!(target: Net, "{} is watching", from);
+                        self.clients.push(client);
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    debug!(target: Net, "Couldn't take a spectator: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    // How many people are watching. With nobody, there's no point making
+    // a Frame at all.
+    pub fn watching(&self) -> usize {
+        self.clients.len()
+    }
+
+    // Send `frame` to everyone watching. Anyone who's gone away gets
+    // forgotten about.
+    pub fn send(&mut self, frame: &Frame) {
+        let json = frame.to_json();
+        let mut still_here = Vec::with_capacity(self.clients.len());
+        for mut client in self.clients.drain(..) {
+            if send_to(&mut client, &json) {
+                still_here.push(client);
+            } else {
+                info!(target: Net, "A spectator has gone");
+            }
+        }
+        self.clients = still_here;
+    }
+}
+
+/*
+ * Upgrade a new connection to a WebSocket. That's a quick back and forth,
+ * which is easiest done with the stream blocking, so for the handshake it
+ * is, with a timeout so that someone who connects and says nothing can't
+ * hold the game up for long. After that it goes back to non-blocking, so
+ * sending never waits on a slow spectator: a message that can't go yet
+ * waits in the WebSocket's buffer until it can.
+ */
+#[cfg(feature = "spectate")]
+fn accept_client(stream: TcpStream, from: SocketAddr) -> Option<Client> {
+    let timeout = Some(Duration::from_millis(HANDSHAKE_TIMEOUT_MS));
+    let ready = stream.set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(timeout))
+        .and_then(|_| stream.set_write_timeout(timeout));
+    if let Err(e) = ready {
+        debug!(target: Net, "Couldn't set up {}: {}", from, e);
+        return None;
+    }
+    match tungstenite::accept(stream) {
+        Ok(client) => {
+            if let Err(e) = client.get_ref().set_nonblocking(true) {
+                debug!(target: Net, "Couldn't set up {}: {}", from, e);
+                return None;
+            }
+            Some(client)
+        }
+        Err(e) => {
+            debug!(target: Net, "{} didn't speak WebSocket: {}", from, e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "spectate"))]
+fn accept_client(_: TcpStream, from: SocketAddr) -> Option<Client> {
+    warn!(target: Net, "Turned {} away: this game was built without the spectate feature", from);
+    None
+}
+
+// Whether the client's still there. WouldBlock just means the message is
+// waiting in the buffer; anything else means they've gone.
+#[cfg(feature = "spectate")]
+fn send_to(client: &mut Client, json: &str) -> bool {
+    match client.send(tungstenite::Message::Text(json.to_string())) {
+        Ok(()) => true,
+        Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(feature = "spectate"))]
+fn send_to(client: &mut Client, _: &str) -> bool {
+    match *client {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use controls::Facing;
+
+    fn frame() -> Frame {
+        Frame {
+            tick: 12,
+            level: 0,
+            level_name: "First steps".to_string(),
+            state: "Playing".to_string(),
+            score: 2,
+            health: 3,
+            elapsed: 0.1,
+            world: [1280.0, 960.0],
+            camera: [0.0, 0.0],
+            view: [640.0, 480.0],
+            players: vec![Shown { bounds: [50.0, 50.0, 50.0, 50.0],
+                                  facing: Facing::Right,
+                                  color: [1.0, 0.0, 0.0, 1.0] }],
+            walls: vec![[300.0, 0.0, 20.0, 400.0]],
+            pickups: Vec::new(),
+            enemies: Vec::new(),
+            projectiles: Vec::new(),
+            goal: [600.0, 400.0, 40.0, 40.0],
+        }
+    }
+
+    #[test]
+    fn frames_are_json() {
+        let json: serde_json::Value = serde_json::from_str(&frame().to_json()).unwrap();
+        assert_eq!(json["tick"], 12);
+        assert_eq!(json["players"][0]["bounds"][0], 50.0);
+        assert_eq!(json["walls"].as_array().map(|w| w.len()), Some(1));
+    }
+
+    #[test]
+    fn nobody_watching_to_begin_with() {
+        // Port 0 means any free port, so the test doesn't fight with a game
+        // that's running.
+        let mut spectators = Spectators::listen(0).unwrap();
+        spectators.accept();
+        assert_eq!(spectators.watching(), 0);
+        spectators.send(&frame());
+    }
+
+    #[cfg(feature = "spectate")]
+    #[test]
+    fn spectators_get_sent_frames() {
+        use std::thread;
+        use std::time::Duration;
+        let mut spectators = Spectators::listen(0).unwrap();
+        let port = spectators.local_addr().unwrap().port();
+        // The spectator has to be off on its own thread: its end of the
+        // handshake waits for ours, and ours waits for it.
+        let watcher = thread::spawn(move || {
+            let url = format!("ws://127.0.0.1:{}/", port);
+            let (mut socket, _) = tungstenite::connect(url.as_str()).unwrap();
+            socket.read().unwrap().into_text().unwrap()
+        });
+        for _ in 0..200 {
+            spectators.accept();
+            if spectators.watching() > 0 { break }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(spectators.watching(), 1);
+        spectators.send(&frame());
+        assert_eq!(watcher.join().unwrap(), frame().to_json());
+    }
+}
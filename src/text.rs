@@ -0,0 +1,106 @@
+/*
+ * How a piece of text looks: which font, how big, what color, which way
+ * it lines up with where it's put, and whether it's got a drop shadow to
+ * stand out from whatever's behind it. The fonts themselves are Hud's (see
+ * hud.rs), registered by name through the asset manager, and Hud::write
+ * draws with a Text; this is only the description, so it can be made and
+ * passed about and tested without a font to hand.
+ *
+ * A Text is built up a bit at a time, starting from a size and a color:
+ *
+ *     Text::new(16, palette.text).align(Align::Right).shadow(palette.shade)
+ *
+ * The HUD has a shadow, since it's written over the game and the game can
+ * be any color; the menus and the console are on plain dark backgrounds,
+ * so they do without. The console asks for the "console" font, which is
+ * whatever `fonts` in config.json says, or the usual one if it doesn't say.
+ */
+
+// The font everything's written in unless it asks for another: the one at
+// font_path in config.json.
+pub const DEFAULT_FONT: &'static str = "default";
+
+// Which way text goes from where it's put: starting there, with its
+// middle there, or finishing there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub font: String,
+    pub size: u32,
+    pub color: [f32; 4],
+    pub align: Align,
+    pub shadow: Option<[f32; 4]>,
+}
+
+impl Text {
+    // `size` pixels high, in `color`, with the usual font, lined up on the
+    // left, without a shadow.
+    pub fn new(size: u32, color: [f32; 4]) -> Text {
+        Text { font: DEFAULT_FONT.to_string(), size: size, color: color, align: Align::Left,
+               shadow: None }
+    }
+
+    pub fn font(mut self, name: &str) -> Text {
+        self.font = name.to_string();
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Text {
+        self.align = align;
+        self
+    }
+
+    // A copy of the text, in `color`, a little down and to the right of it.
+    pub fn shadow(mut self, color: [f32; 4]) -> Text {
+        self.shadow = Some(color);
+        self
+    }
+
+    /*
+     * Roughly how wide `text` comes out. We don't ask the font how wide
+     * the text really is; letters are roughly six tenths as wide as they
+     * are tall, which is near enough to line things up by.
+     */
+    pub fn width(&self, text: &str) -> f64 {
+        text.chars().count() as f64 * self.size as f64 * 0.6
+    }
+
+    // Where `text` starts, put at `x`.
+    pub fn left(&self, text: &str, x: f64) -> f64 {
+        match self.align {
+            Align::Left => x,
+            Align::Center => x - self.width(text) / 2.0,
+            Align::Right => x - self.width(text),
+        }
+    }
+
+    // How far down and across the shadow is: a pixel for every sixteen of
+    // the text's size, so big writing gets a shadow that shows.
+    pub fn shadow_offset(&self) -> f64 {
+        (self.size as f64 / 16.0).round().max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_lines_up_on_either_side_or_the_middle() {
+        let text = Text::new(10, [1.0; 4]);
+        assert_eq!(text.width("abcd"), 24.0);
+        assert_eq!(text.left("abcd", 100.0), 100.0);
+        assert_eq!(text.clone().align(Align::Center).left("abcd", 100.0), 88.0);
+        assert_eq!(text.clone().align(Align::Right).left("abcd", 100.0), 76.0);
+        let big = Text::new(48, [1.0; 4]).shadow([0.0, 0.0, 0.0, 0.5]).font("console");
+        assert_eq!((big.shadow_offset(), text.shadow_offset()), (3.0, 1.0));
+        assert_eq!(big.font, "console");
+        assert_eq!(text.font, DEFAULT_FONT);
+    }
+}
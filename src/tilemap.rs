@@ -0,0 +1,199 @@
+/*
+ * The ground. A tilemap chops the world up into a grid of equally sized
+ * squares, tiles, and says what kind of ground each one is with a number.
+ * It's how nearly every 2D game from the 80s onwards builds its worlds: a
+ * whole level is just a small grid of numbers plus a handful of tile
+ * pictures, instead of one gigantic picture.
+ *
+ * We don't have tile pictures yet, so each kind of tile is a colored square
+ * for now (see tile_color). Tile 0 is "nothing here", and lets the
+ * background show through.
+ *
+ * Looking down on the map, as the game usually does, it's all just ground
+ * to walk over. Platformer mode looks at it side-on instead, and then stone
+ * is something solid to stand on (see is_solid).
+ *
+ * Maps are JSON files like this, one inner list per row, top to bottom:
+ *
+ *     { "tile_size": 32.0, "tiles": [[1, 2, 1], [2, 0, 2]] }
+ */
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use graphics::Context;
+use serde_json;
+
+use collision::Aabb;
+use renderer::Renderer;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Tilemap {
+    pub tile_size: f64,
+    pub tiles: Vec<Vec<u32>>,
+}
+
+// What each kind of tile looks like. Anything without a color here (0, or a
+// number we don't know) isn't drawn at all.
+pub fn tile_color(id: u32) -> Option<[f32; 4]> {
+    match id {
+        1 => Some([0.55, 0.62, 0.45, 1.0]), // grass
+        2 => Some([0.50, 0.57, 0.41, 1.0]), // slightly darker grass
+        3 => Some([0.70, 0.64, 0.50, 1.0]), // path
+        4 => Some([0.30, 0.45, 0.70, 1.0]), // water
+        5 => Some([0.40, 0.40, 0.42, 1.0]), // stone
+        _ => None,
+    }
+}
+
+// Which tiles platformer mode can stand on: just stone, for now.
+pub fn is_solid(id: u32) -> bool {
+    id == 5
+}
+
+// A level with no map file gets an empty one.
+impl Default for Tilemap {
+    fn default() -> Tilemap {
+        Tilemap::empty()
+    }
+}
+
+impl Tilemap {
+    // A map with nothing on it, for when there's no map file.
+    pub fn empty() -> Tilemap {
+        Tilemap { tile_size: 32.0, tiles: Vec::new() }
+    }
+
+    pub fn load(path: &Path) -> Result<Tilemap, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        Tilemap::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /*
+     * Parse a map and make sure it makes sense. Every row has to be the same
+     * length, otherwise "the tile at column 7" doesn't mean anything on the
+     * short rows; and tiles have to have some size, otherwise working out
+     * which tile a point is in would divide by zero.
+     */
+    pub fn parse(json: &str) -> Result<Tilemap, String> {
+        let map: Tilemap = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if map.tile_size <= 0.0 {
+            return Err("tile_size has to be bigger than zero".to_string());
+        }
+        if let Some(first) = map.tiles.first() {
+            if let Some(row) = map.tiles.iter().position(|r| r.len() != first.len()) {
+                return Err(format!("row {} is {} tiles long, but row 0 is {}",
+                                   row, map.tiles[row].len(), first.len()));
+            }
+        }
+        Ok(map)
+    }
+
+    // Size in tiles.
+    pub fn columns(&self) -> usize {
+        self.tiles.first().map(|r| r.len()).unwrap_or(0)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.tiles.len()
+    }
+
+    // The tile at (column, row), or None if that's off the map.
+    pub fn get(&self, column: usize, row: usize) -> Option<u32> {
+        self.tiles.get(row).and_then(|r| r.get(column)).cloned()
+    }
+
+    /*
+     * Which tiles overlap `area`, as (first column, first row, last column
+     * + 1, last row + 1). Clipped to the map, so it's always safe to index
+     * with. This is how we avoid drawing the whole map every frame when
+     * only a window's worth of it can be seen.
+     */
+    pub fn tiles_in(&self, area: Aabb) -> (usize, usize, usize, usize) {
+        let index = |v: f64, max: usize| {
+            (v / self.tile_size).floor().max(0.0).min(max as f64) as usize
+        };
+        (index(area.x, self.columns()),
+         index(area.y, self.rows()),
+         index(area.right() + self.tile_size - 1e-9, self.columns()),
+         index(area.bottom() + self.tile_size - 1e-9, self.rows()))
+    }
+
+    // A box for every solid tile on the map, in world coordinates, ready to
+    // be collided with.
+    pub fn solid_boxes(&self) -> Vec<Aabb> {
+        let s = self.tile_size;
+        let mut boxes = Vec::new();
+        for (row, tiles) in self.tiles.iter().enumerate() {
+            for (column, &id) in tiles.iter().enumerate() {
+                if is_solid(id) {
+                    boxes.push(Aabb::new(column as f64 * s, row as f64 * s, s, s));
+                }
+            }
+        }
+        boxes
+    }
+
+    // Draw the tiles that can be seen in `view` (in world coordinates).
+    pub fn draw(&self, context: &Context, r: &mut dyn Renderer, view: Aabb) {
+        let (c0, r0, c1, r1) = self.tiles_in(view);
+        let s = self.tile_size;
+        for row in r0..r1 {
+            for column in c0..c1 {
+                let color = match self.get(column, row).and_then(tile_color) {
+                    Some(color) => color,
+                    None => continue,
+                };
+                r.rect(context, column as f64 * s, row as f64 * s, s, s, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::Aabb;
+
+    fn map() -> Tilemap {
+        Tilemap::parse(r#"{ "tile_size": 10.0,
+                            "tiles": [[1, 2, 3, 4], [5, 0, 1, 2], [3, 4, 5, 0]] }"#).unwrap()
+    }
+
+    #[test]
+    fn parses_and_looks_up_tiles() {
+        let map = map();
+        assert_eq!((map.columns(), map.rows()), (4, 3));
+        assert_eq!(map.get(0, 0), Some(1));
+        assert_eq!(map.get(3, 1), Some(2));
+        assert_eq!(map.get(4, 0), None);
+        assert_eq!(map.get(0, 3), None);
+    }
+
+    #[test]
+    fn rejects_ragged_rows_and_zero_sized_tiles() {
+        assert!(Tilemap::parse(r#"{ "tile_size": 10.0, "tiles": [[1, 2], [1]] }"#).is_err());
+        assert!(Tilemap::parse(r#"{ "tile_size": 0.0, "tiles": [[1]] }"#).is_err());
+        assert!(Tilemap::parse(r#"{ "tile_size": 10.0, "tiles": [] }"#).is_ok());
+    }
+
+    #[test]
+    fn stone_is_solid() {
+        assert_eq!(map().solid_boxes(), vec![Aabb::new(0.0, 10.0, 10.0, 10.0),
+                                             Aabb::new(20.0, 20.0, 10.0, 10.0)]);
+    }
+
+    #[test]
+    fn only_visible_tiles_are_picked() {
+        let map = map();
+        // A view from the middle of tile (1, 0) to the middle of (2, 1).
+        assert_eq!(map.tiles_in(Aabb::new(15.0, 5.0, 10.0, 10.0)), (1, 0, 3, 2));
+        // Exactly one tile.
+        assert_eq!(map.tiles_in(Aabb::new(10.0, 10.0, 10.0, 10.0)), (1, 1, 2, 2));
+        // Hanging off the edges gets clipped to the map.
+        assert_eq!(map.tiles_in(Aabb::new(-50.0, -50.0, 500.0, 500.0)), (0, 0, 4, 3));
+    }
+}
@@ -0,0 +1,227 @@
+/*
+ * Timers: "do this in two seconds", or "do this every five seconds",
+ * without every one of them being its own f64 field on App that gets dt
+ * taken off it each tick. You give Timers a closure, it holds on to it, and
+ * update calls it once its time comes round:
+ *
+ *     app.timers.every(5.0, |app: &mut App| app.spawn_enemy_somewhere());
+ *     let id = app.timers.after(2.0, |app: &mut App| app.bounce_mode = false);
+ *     app.timers.cancel(id);
+ *
+ * Timers only move on when update says so, by however much dt it's given,
+ * which for App is once a tick in advance. So a paused game's timers are
+ * paused too, and a replay's go off on exactly the same ticks they did
+ * first time round.
+ *
+ * The closures get handed whatever Timers was told about when it was made,
+ * which is App for App's. There's a catch there: App's timers live in App,
+ * so while they're being run, App can't also be lent out to them. run
+ * (rather than Timers::update) gets round that, by taking the timers out of
+ * App for the duration, and putting them back afterwards along with any new
+ * ones the closures set up meanwhile.
+ *
+ * The other thing people use timers for, a number going smoothly from one
+ * value to another over so many seconds, is a Tween, in tween.rs.
+ */
+use std::mem;
+
+// What after and every hand back, for cancelling the timer with.
+pub type TimerId = u64;
+
+struct Timer<C> {
+    id: TimerId,
+    // Seconds until it goes off, and for a repeating timer how often it
+    // goes off after that.
+    left: f64,
+    every: Option<f64>,
+    callback: Box<dyn FnMut(&mut C)>,
+}
+
+pub struct Timers<C> {
+    timers: Vec<Timer<C>>,
+    next_id: TimerId,
+    // Timers cancelled that weren't here to cancel. They might be out
+    // running (see run), and get cancelled when they're back.
+    cancelled: Vec<TimerId>,
+}
+
+impl<C> Timers<C> {
+    pub fn new() -> Timers<C> {
+        Timers { timers: Vec::new(), next_id: 0, cancelled: Vec::new() }
+    }
+
+    // Call `callback` once, `seconds` from now.
+    pub fn after<F: FnMut(&mut C) + 'static>(&mut self, seconds: f64, callback: F) -> TimerId {
+        self.add(seconds, None, Box::new(callback))
+    }
+
+    /*
+     * Call `callback` every `seconds`, the first time `seconds` from now.
+     * Something that repeats faster than update is called just gets called
+     * more than once in an update, so it keeps up; but not more than once
+     * for every update if it's asked to repeat every 0 seconds, or it would
+     * never stop.
+     */
+    pub fn every<F: FnMut(&mut C) + 'static>(&mut self, seconds: f64, callback: F) -> TimerId {
+        self.add(seconds, Some(seconds), Box::new(callback))
+    }
+
+    fn add(&mut self, seconds: f64, every: Option<f64>, callback: Box<dyn FnMut(&mut C)>)
+           -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.push(Timer { id: id, left: seconds, every: every, callback: callback });
+        id
+    }
+
+    // Stop a timer going off (again). Cancelling one that's already gone
+    // off, or been cancelled, is fine, and does nothing.
+    pub fn cancel(&mut self, id: TimerId) {
+        let before = self.timers.len();
+        self.timers.retain(|t| t.id != id);
+        if self.timers.len() == before {
+            self.cancelled.push(id);
+        }
+    }
+
+    // Whether a timer is still going to go off.
+    pub fn pending(&self, id: TimerId) -> bool {
+        self.timers.iter().any(|t| t.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    /*
+     * `dt` more seconds have gone by: call everything that's due, in the
+     * order they were set up, and forget the one-shot ones once they've
+     * gone off.
+     */
+    pub fn update(&mut self, dt: f64, context: &mut C) {
+        for timer in self.timers.iter_mut() {
+            timer.left -= dt;
+            loop {
+                if timer.left > 0.0 { break }
+                (timer.callback)(context);
+                match timer.every {
+                    Some(every) if every > 0.0 => timer.left += every,
+                    Some(_) => { timer.left = 0.0; break }
+                    None => break,
+                }
+            }
+        }
+        self.timers.retain(|t| t.every.is_some() || t.left > 0.0);
+    }
+
+    // The same ids carry on from here, so a timer set up while these are
+    // out running can't get the same id as one of them.
+    fn stand_in(&self) -> Timers<C> {
+        Timers { timers: Vec::new(), next_id: self.next_id, cancelled: Vec::new() }
+    }
+
+    // Take on the timers set up, and cancelled, while these were out.
+    fn take_back(&mut self, stand_in: Timers<C>) {
+        for id in stand_in.cancelled {
+            self.cancel(id);
+        }
+        self.timers.extend(stand_in.timers);
+        self.next_id = stand_in.next_id;
+        self.cancelled.clear();
+    }
+}
+
+impl<C> Default for Timers<C> {
+    fn default() -> Timers<C> {
+        Timers::new()
+    }
+}
+
+// Anything with its own timers in it, for run.
+pub trait HasTimers: Sized {
+    fn timers(&mut self) -> &mut Timers<Self>;
+}
+
+// Move `context`'s own timers on by `dt`, handing it to the ones that go
+// off. See the top of the file for why this isn't just Timers::update.
+pub fn run<C: HasTimers>(context: &mut C, dt: f64) {
+    let stand_in = context.timers().stand_in();
+    let mut timers = mem::replace(context.timers(), stand_in);
+    timers.update(dt, context);
+    let stand_in = mem::replace(context.timers(), timers);
+    context.timers().take_back(stand_in);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_shot_timers_go_off_once() {
+        let mut timers = Timers::new();
+        timers.after(0.25, |fired: &mut Vec<u32>| fired.push(1));
+        let mut fired = Vec::new();
+        timers.update(0.2, &mut fired);
+        assert!(fired.is_empty());
+        timers.update(0.1, &mut fired);
+        timers.update(10.0, &mut fired);
+        assert_eq!(fired, vec![1]);
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn repeating_timers_keep_up() {
+        let mut timers = Timers::new();
+        let id = timers.every(0.5, |count: &mut u32| *count += 1);
+        let mut count = 0;
+        // Four seconds in steps of an eighth: eight goes.
+        for _ in 0..32 {
+            timers.update(0.125, &mut count);
+        }
+        assert_eq!(count, 8);
+        // Falling behind catches up all in one go.
+        timers.update(1.0, &mut count);
+        assert_eq!(count, 10);
+        timers.cancel(id);
+        timers.update(1.0, &mut count);
+        assert_eq!((count, timers.pending(id)), (10, false));
+    }
+
+    struct Counter {
+        count: u32,
+        timers: Timers<Counter>,
+    }
+
+    impl HasTimers for Counter {
+        fn timers(&mut self) -> &mut Timers<Counter> {
+            &mut self.timers
+        }
+    }
+
+    #[test]
+    fn timers_can_set_up_and_cancel_timers() {
+        let mut counter = Counter { count: 0, timers: Timers::new() };
+        let ticking = counter.timers.every(1.0, |c: &mut Counter| c.count += 1);
+        // In two seconds, add another tick that's worth ten, and stop the
+        // first one.
+        counter.timers.after(2.0, move |c: &mut Counter| {
+            c.timers.every(1.0, |c: &mut Counter| c.count += 10);
+            c.timers.cancel(ticking);
+        });
+        for _ in 0..4 {
+            run(&mut counter, 1.0);
+        }
+        // 1 and 2 from the first, then 10 and 10 from the second.
+        assert_eq!(counter.count, 22);
+        assert_eq!(counter.timers.len(), 1);
+        assert!(!counter.timers.pending(ticking));
+    }
+}
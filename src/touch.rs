@@ -0,0 +1,251 @@
+/*
+ * Playing with a finger, on a phone or a tablet, where there's no keyboard
+ * to hold arrows down on. The screen is split down the middle. Putting a
+ * finger down on the left half and dragging it about moves the player, as
+ * if it were a stick: the further you drag, the faster you go, up to
+ * TOUCH_RADIUS pixels, which is full speed. Tapping the right half expands
+ * (or jumps, in platformer mode), the same as Space.
+ *
+ * A finger can go down anywhere on the left; wherever that is, it's the
+ * middle of a stick that isn't there, and dragging is measured from it. So
+ * you don't have to look for the controls before you start. But it helps
+ * to have something to aim at, so there's an overlay in the bottom-left
+ * corner, a see-through D-pad, and a button on the right for expand. A
+ * finger put down on the D-pad measures from the pad's middle instead, so
+ * it works the way it looks: pressing an arm goes that way.
+ *
+ * Touches arrive as the mouse. Every browser and most desktop windows turn
+ * a finger into the left mouse button going down, the cursor following it
+ * about, and the button coming back up, which is all of this there is; it
+ * also means it can be tried out with a mouse. So `touch_controls` in
+ * config.json (or F6) turns it on, since it takes the left mouse button
+ * over: it stops firing at the mouse while touch is on. Only one finger at
+ * a time, then, which is enough to move and then tap.
+ *
+ * Touch knows nothing about App; App passes it where the finger is in the
+ * view (see below) and reads back which way to go, which ends up in the
+ * player's InputState next to the controller's stick (see controls.rs).
+ * The drag isn't a key, so like the mouse's aiming it isn't in recordings.
+ */
+use graphics::Context;
+
+use app::App;
+use collision::Aabb;
+use controls;
+use hud;
+use minimap;
+use palette::faded;
+use renderer::Renderer;
+use units::ScreenPos;
+
+// How far in from the window's edges the controls are.
+pub const TOUCH_MARGIN: f64 = 30.0;
+// How big the D-pad is across, and how wide each of its arms.
+pub const PAD: f64 = 120.0;
+pub const ARM: f64 = 40.0;
+// How far a drag goes for full speed: from the middle of the pad to the
+// end of an arm.
+pub const TOUCH_RADIUS: f64 = PAD / 2.0;
+// How big the expand button is.
+pub const BUTTON: f64 = 80.0;
+// How see-through the overlay is, and the arm that's being pressed.
+pub const OVERLAY_ALPHA: f32 = 0.25;
+pub const HELD_ALPHA: f32 = 0.5;
+
+// A finger held down: where it went down (or the pad's middle), and
+// where it's got to since.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Drag {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Touch {
+    pub enabled: bool,
+    pub drag: Option<Drag>,
+}
+
+// The middle of the D-pad, in a `view` sized window.
+pub fn pad_centre(view: (f64, f64)) -> (f64, f64) {
+    (TOUCH_MARGIN + PAD / 2.0, view.1 - TOUCH_MARGIN - PAD / 2.0)
+}
+
+// The D-pad's four arms, going right, down, left and up, the way a stick
+// pointing that way does.
+pub fn arms(view: (f64, f64)) -> [Aabb; 4] {
+    let (cx, cy) = pad_centre(view);
+    let (long, wide) = (PAD / 2.0 - ARM / 2.0, ARM);
+    [Aabb::new(cx + ARM / 2.0, cy - ARM / 2.0, long, wide),
+     Aabb::new(cx - ARM / 2.0, cy + ARM / 2.0, wide, long),
+     Aabb::new(cx - PAD / 2.0, cy - ARM / 2.0, long, wide),
+     Aabb::new(cx - ARM / 2.0, cy - PAD / 2.0, wide, long)]
+}
+
+// The expand button, on the right, but high enough to stay clear of the
+// minimap in the corner.
+pub fn expand_button(view: (f64, f64)) -> Aabb {
+    let bottom = view.1 - hud::MARGIN - minimap::SIZE - TOUCH_MARGIN;
+    Aabb::new(view.0 - TOUCH_MARGIN - BUTTON, bottom - BUTTON, BUTTON, BUTTON)
+}
+
+// Which of `arms` a stick pushed `stick` presses, if any: whichever way
+// it's pushed most.
+pub fn held_arm(stick: (f64, f64)) -> Option<usize> {
+    let (x, y) = controls::stick_direction(stick);
+    if x == 0.0 && y == 0.0 {
+        None
+    } else if x.abs() >= y.abs() {
+        Some(if x > 0.0 { 0 } else { 2 })
+    } else {
+        Some(if y > 0.0 { 1 } else { 3 })
+    }
+}
+
+impl Touch {
+    pub fn new(enabled: bool) -> Touch {
+        Touch { enabled: enabled, drag: None }
+    }
+
+    /*
+     * A finger went down at `at`, in a `view` sized window. On the left
+     * half that starts a drag, and this says so; on the right it's a tap,
+     * which is the caller's to do something with.
+     */
+    pub fn press(&mut self, at: (f64, f64), view: (f64, f64)) -> bool {
+        if at.0 >= view.0 / 2.0 {
+            return false;
+        }
+        let centre = pad_centre(view);
+        let on_pad = (at.0 - centre.0).hypot(at.1 - centre.1) <= PAD / 2.0;
+        self.drag = Some(Drag { from: if on_pad { centre } else { at }, to: at });
+        true
+    }
+
+    pub fn moved(&mut self, at: (f64, f64)) {
+        if let Some(ref mut drag) = self.drag {
+            drag.to = at;
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.drag = None;
+    }
+
+    // The drag as a stick's position, from -1 to 1 each way, no further
+    // than 1 from the middle whichever way it goes.
+    pub fn stick(&self) -> (f64, f64) {
+        let drag = match self.drag {
+            Some(drag) => drag,
+            None => return (0.0, 0.0),
+        };
+        let (x, y) = ((drag.to.0 - drag.from.0) / TOUCH_RADIUS,
+                      (drag.to.1 - drag.from.1) / TOUCH_RADIUS);
+        let length = x.hypot(y);
+        if length > 1.0 { (x / length, y / length) } else { (x, y) }
+    }
+
+    /*
+     * The overlay, in `color`, on a `view` sized window. The arm that's
+     * pressed is less see-through than the rest, and a drag that started
+     * off the pad gets a stick of its own drawn where it is, a ring and a
+     * knob, so you can see what you're doing.
+     */
+    pub fn draw(&self, view: (f64, f64), color: [f32; 4], context: &Context, r: &mut dyn Renderer) {
+        let (faint, held) = (faded(color, OVERLAY_ALPHA), faded(color, HELD_ALPHA));
+        let pressed = held_arm(self.stick());
+        let centre = pad_centre(view);
+        let from_pad = self.drag.map_or(true, |drag| drag.from == centre);
+        for (i, arm) in arms(view).iter().enumerate() {
+            let c = if from_pad && pressed == Some(i) { held } else { faint };
+            r.rect(context, arm.x, arm.y, arm.w, arm.h, c);
+        }
+        let b = expand_button(view);
+        r.ellipse(context, [b.x, b.y, b.w, b.h], faint);
+        if let Some(drag) = self.drag {
+            if !from_pad {
+                let (x, y) = drag.from;
+                r.ellipse(context, [x - TOUCH_RADIUS, y - TOUCH_RADIUS,
+                                    TOUCH_RADIUS * 2.0, TOUCH_RADIUS * 2.0], faint);
+                let (sx, sy) = self.stick();
+                let (kx, ky) = (x + sx * TOUCH_RADIUS, y + sy * TOUCH_RADIUS);
+                r.ellipse(context, [kx - ARM / 2.0, ky - ARM / 2.0, ARM, ARM], held);
+            }
+        }
+    }
+}
+
+/*
+ * And App's side of it: handing Touch the mouse, in the view's points
+ * rather than the window's, and passing on which way that says to go.
+ * handlers.rs decides when.
+ */
+impl App {
+    // F6: touch controls, and the overlay, on or off.
+    pub fn toggle_touch(&mut self) {
+        self.touch.enabled = !self.touch.enabled;
+        self.touch_release();
+        info!("Touch controls {}", if self.touch.enabled { "on" } else { "off" });
+    }
+
+    // The left mouse button went down. Whether that started a drag.
+    pub fn touch_press(&mut self) -> bool {
+        let at = match self.cursor_on_screen() {
+            Some(at) => at.tuple(),
+            None => return false,
+        };
+        let view = self.view_size;
+        let dragging = self.touch.press(at, view);
+        self.players[0].input.touch = self.touch.stick();
+        dragging
+    }
+
+    pub fn touch_moved(&mut self) {
+        if let Some(at) = self.cursor_on_screen() {
+            self.touch.moved(at.tuple());
+            self.players[0].input.touch = self.touch.stick();
+        }
+    }
+
+    pub fn touch_release(&mut self) {
+        self.touch.release();
+        self.players[0].input.touch = (0.0, 0.0);
+    }
+
+    // Where the mouse is on the screen, which letterboxed is moved and
+    // scaled inside the window. See cursor_seen_from. The pause menu's
+    // buttons go by this too.
+    pub fn cursor_on_screen(&self) -> Option<ScreenPos> {
+        let at = self.cursor?;
+        Some(self.letterboxing().to_screen(at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dragging_on_the_left_is_a_stick() {
+        let view = (800.0, 600.0);
+        let mut touch = Touch::new(true);
+        // The right half is a tap, not a drag.
+        assert!(!touch.press((600.0, 300.0), view));
+        assert_eq!(touch.drag, None);
+        // Off the pad, the drag's measured from where it started...
+        assert!(touch.press((200.0, 200.0), view));
+        touch.moved((230.0, 200.0));
+        assert_eq!(touch.stick(), (0.5, 0.0));
+        touch.moved((200.0, 0.0));
+        assert_eq!(touch.stick(), (0.0, -1.0));
+        assert_eq!(held_arm(touch.stick()), Some(3));
+        touch.release();
+        assert_eq!(touch.stick(), (0.0, 0.0));
+        // ...and on it, from the pad's middle.
+        let (cx, cy) = pad_centre(view);
+        assert!(touch.press((cx, cy + 50.0), view));
+        assert_eq!(touch.drag.unwrap().from, (cx, cy));
+        assert_eq!(held_arm(touch.stick()), Some(1));
+        assert!(arms(view)[1].contains(&Aabb::new(cx, cy + 50.0, 1.0, 1.0)));
+    }
+}
@@ -0,0 +1,84 @@
+/*
+ * A motion trail: faint copies of the player square left behind where it's
+ * been the last few ticks, each one fainter than the one before, so moving
+ * quickly leaves a streak. It's a ring buffer of the player's last few
+ * boxes. A VecDeque is rust's ring buffer: push on the front, and once
+ * it's full, the oldest drops off the back, without shuffling everything
+ * else along.
+ */
+use std::collections::VecDeque;
+
+use graphics::Context;
+
+use collision::Aabb;
+use renderer::Renderer;
+
+#[derive(Default)]
+pub struct Trail {
+    // Newest first.
+    boxes: VecDeque<Aabb>,
+}
+
+impl Trail {
+    pub fn new() -> Trail {
+        Trail { boxes: VecDeque::new() }
+    }
+
+    // The player was at `b` this tick. Keep the newest `length` of them;
+    // if the config's just made that shorter, the extras go now.
+    pub fn record(&mut self, b: Aabb, length: usize) {
+        self.boxes.push_front(b);
+        self.boxes.truncate(length);
+    }
+
+    // Forget everything, for when the player jumps somewhere rather than
+    // moving there. A trail all the way across the level would look daft.
+    pub fn clear(&mut self) {
+        self.boxes.clear();
+    }
+
+    /*
+     * Draw the trail, oldest first so newer copies go on top. The newest is
+     * drawn at `fade` of the color's alpha, the one before at fade times
+     * fade, and so on; so a fade of 0.5 halves each time, and one near 1
+     * makes a long, even streak. Copies sitting right on top of the player
+     * get covered up, so standing still there's no trail to see.
+     */
+    pub fn draw(&self, color: [f32; 4], fade: f32, context: &Context, r: &mut dyn Renderer) {
+        let mut alpha = color[3] * fade.powi(self.boxes.len() as i32);
+        for b in self.boxes.iter().rev() {
+            r.rect(context, b.x, b.y, b.w, b.h, [color[0], color[1], color[2], alpha]);
+            if fade > 0.0 { alpha /= fade }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::Aabb;
+    use graphics::Context;
+    use renderer::{Call, Recorder};
+
+    #[test]
+    fn keeps_only_the_newest() {
+        let mut trail = Trail::new();
+        for i in 0..10 {
+            trail.record(Aabb::new(i as f64, 0.0, 10.0, 10.0), 4);
+        }
+        assert_eq!(trail.boxes.len(), 4);
+        assert_eq!(trail.boxes[0].x, 9.0);
+        assert_eq!(trail.boxes[3].x, 6.0);
+    }
+
+    #[test]
+    fn older_copies_are_fainter() {
+        let mut trail = Trail::new();
+        trail.record(Aabb::new(0.0, 0.0, 10.0, 10.0), 3);
+        trail.record(Aabb::new(5.0, 0.0, 10.0, 10.0), 3);
+        let mut r = Recorder::new();
+        trail.draw([1.0, 0.0, 0.0, 1.0], 0.5, &Context::new_abs(640.0, 480.0), &mut r);
+        assert_eq!(r.calls, vec![Call::Rect([0.0, 0.0, 10.0, 10.0], [1.0, 0.0, 0.0, 0.25]),
+                                 Call::Rect([5.0, 0.0, 10.0, 10.0], [1.0, 0.0, 0.0, 0.5])]);
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Going from one screen to another (the menu to a game, a game to game
+ * over, and so on) used to be a cut: one frame the menu, the next the
+ * game. Now the new screen comes in from black. Every change of screen
+ * goes through Game::set_state, so that's where a Transition starts, and
+ * Game draws it over whatever's on the screen until it's done. There are
+ * two sorts, picked with `transition` in config.json:
+ *
+ * - "fade": the whole screen starts black and fades up.
+ * - "wipe": a black sheet slides off to the right, and the new screen's
+ *   underneath.
+ *
+ * How long it takes is `transition_seconds` (0 for the old straight cut),
+ * and how it gets there is `transition_easing` (see tween.rs for what
+ * they do): "ease_in_out", unless you say otherwise.
+ *
+ * Pausing is a change of screen too, but not much of one, so it starts
+ * half covered (PAUSE_COVER) rather than black. Nothing waits for a
+ * transition: the new screen's there, and taking keys, straight away; the
+ * sheet's only on top of it.
+ */
+use graphics::Context;
+
+use renderer::Renderer;
+use tween::Easing;
+
+// What a transition's drawn in.
+const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// How covered pausing and unpausing start.
+pub const PAUSE_COVER: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    Fade,
+    Wipe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub style: Style,
+    pub easing: Easing,
+    pub seconds: f64,
+    // How covered the screen is to begin with, from 0 to 1.
+    pub from: f64,
+    pub elapsed: f64,
+}
+
+impl Transition {
+    pub fn new(style: Style, easing: Easing, seconds: f64, from: f64) -> Transition {
+        Transition { style: style, easing: easing, seconds: seconds, from: from, elapsed: 0.0 }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.elapsed += dt;
+    }
+
+    pub fn done(&self) -> bool {
+        self.elapsed >= self.seconds
+    }
+
+    // How much of the screen's covered now: `from` at the start, down to
+    // nothing at the end.
+    pub fn cover(&self) -> f64 {
+        if self.seconds <= 0.0 { return 0.0 }
+        self.from * (1.0 - self.easing.apply(self.elapsed / self.seconds))
+    }
+
+    // Draw it over a screen `w` by `h`.
+    pub fn draw(&self, w: f64, h: f64, context: &Context, r: &mut dyn Renderer) {
+        let cover = self.cover();
+        if cover <= 0.0 { return }
+        match self.style {
+            Style::Fade => {
+                let mut color = BLACK;
+                color[3] = cover as f32;
+                r.rect(context, 0.0, 0.0, w, h, color);
+            }
+            Style::Wipe => r.rect(context, w * (1.0 - cover), 0.0, w * cover, h, BLACK),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::Recorder;
+
+    #[test]
+    fn the_sheet_comes_off() {
+        let context = Context::new_abs(640.0, 480.0);
+        let mut wipe = Transition::new(Style::Wipe, Easing::Linear, 0.5, 1.0);
+        wipe.update(0.125);
+        let mut r = Recorder::new();
+        wipe.draw(640.0, 480.0, &context, &mut r);
+        assert_eq!(r.rects_in(BLACK), vec![[160.0, 0.0, 480.0, 480.0]]);
+        wipe.update(0.375);
+        assert!(wipe.done());
+        r.calls.clear();
+        wipe.draw(640.0, 480.0, &context, &mut r);
+        assert!(r.calls.is_empty());
+
+        let mut fade = Transition::new(Style::Fade, Easing::Linear, 1.0, PAUSE_COVER);
+        fade.update(0.5);
+        assert_eq!(fade.cover(), 0.25);
+    }
+}
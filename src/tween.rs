@@ -0,0 +1,134 @@
+/*
+ * Tweening: a number going smoothly from one value to another over so many
+ * seconds. The word's from animation, where the "in-betweens" are the
+ * drawings between the ones that say where things start and end up. It's
+ * for anything that's a number and changes over time: the player's expand
+ * shrinking back to nothing, the player wobbling after puffing up (see
+ * player::squash_stretch), the camera gliding about in a cutscene, the
+ * sheet coming off the screen in a transition.
+ *
+ * How it gets from one to the other is its easing. Easing is what makes
+ * something look like it's moving rather than being moved: "linear" goes
+ * at the same speed all the way, which looks mechanical, "ease_in" starts
+ * slow and speeds up, "ease_out" starts fast and slows down at the end,
+ * "ease_in_out" does both, and "elastic" overshoots and springs back and
+ * forth a few times before it settles, like a weight on a spring.
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Elastic,
+}
+
+// How many times an elastic tween goes past where it's going. About three,
+// the way it's worked out: a wave with a period of a third, dying away.
+const ELASTIC_PERIOD: f64 = 1.0 / 3.0;
+
+impl Easing {
+    // How far along something easing this way is when `t` of the time's
+    // gone, both from 0 to 1. Elastic goes past 1 and back on the way.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            // Smoothstep: ease in for the first half, out for the second.
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            /*
+             * A sine wave, shrinking by half every tenth of the way, and
+             * shifted a quarter of a wave so that it starts at 0. It's
+             * only near enough to 1 at the end, so the end is made 1.
+             */
+            Easing::Elastic if t >= 1.0 => 1.0,
+            Easing::Elastic => {
+                let wave = (t - ELASTIC_PERIOD / 4.0) * 2.0 * ::std::f64::consts::PI
+                           / ELASTIC_PERIOD;
+                1.0 + 2f64.powf(-10.0 * t) * wave.sin()
+            }
+        }
+    }
+}
+
+/*
+ * A number going from `from` to `to` over `duration` seconds, eased, and
+ * then staying at `to`. update moves it on by dt, and value says where
+ * it's got to. It's linear unless it's been made otherwise with `eased`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tween {
+    pub from: f64,
+    pub to: f64,
+    pub duration: f64,
+    pub elapsed: f64,
+    pub easing: Easing,
+}
+
+impl Tween {
+    pub fn new(from: f64, to: f64, duration: f64) -> Tween {
+        Tween { from: from, to: to, duration: duration, elapsed: 0.0, easing: Easing::Linear }
+    }
+
+    pub fn eased(self, easing: Easing) -> Tween {
+        Tween { easing: easing, ..self }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+
+    pub fn value(&self) -> f64 {
+        if self.done() { return self.to }
+        self.from + (self.to - self.from) * self.easing.apply(self.elapsed / self.duration)
+    }
+
+    pub fn done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_gets_there_in_the_end() {
+        let all = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut,
+                   Easing::Elastic];
+        for &easing in all.iter() {
+            assert_eq!((easing.apply(0.0), easing.apply(1.0)), (0.0, 1.0));
+        }
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Linear.apply(2.0), 1.0);
+        // Elastic overshoots, and comes back, and has all but stopped by
+        // the end.
+        let springy: Vec<f64> = (1..20).map(|i| Easing::Elastic.apply(i as f64 / 20.0)).collect();
+        assert!(springy.iter().any(|&v| v > 1.1));
+        assert!(springy.iter().any(|&v| v < 1.0 && v > 0.5));
+        assert!((Easing::Elastic.apply(0.95) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn tweens_go_from_one_number_to_another() {
+        let mut tween = Tween::new(100.0, 0.0, 2.0);
+        tween.update(0.5);
+        assert_eq!(tween.value(), 75.0);
+        tween.update(1.0);
+        assert_eq!((tween.value(), tween.done()), (25.0, false));
+        tween.update(1.0);
+        assert_eq!((tween.value(), tween.done()), (0.0, true));
+        // One that takes no time at all is done already.
+        assert_eq!(Tween::new(5.0, 1.0, 0.0).value(), 1.0);
+
+        let mut eased = Tween::new(0.0, 10.0, 1.0).eased(Easing::EaseOut);
+        eased.update(0.5);
+        assert_eq!(eased.value(), 7.5);
+    }
+}
@@ -0,0 +1,271 @@
+/*
+ * The widgets the screens around the game are made of: the title screen's
+ * menu, the options, the pause and the game over screen. There are four
+ * sorts: a label (a line of writing across the middle), a button (writing
+ * in a box, which can be picked), a slider (a button with a bar along the
+ * bottom, filled as far as its value goes) and a panel (a see-through
+ * sheet to put the rest on).
+ *
+ * It's "immediate mode", which is the jargon for not keeping the widgets
+ * about. A screen is worked out again from scratch whenever it's wanted:
+ * make a Ui, say what's on it, in order, top to bottom, and then either
+ * draw it, or ask it what's under the mouse. Nothing has to be kept in
+ * step with anything else, since there's nothing kept. Which one's picked
+ * (has the focus) is still the screen's business, the selected in a Menu
+ * or Options, the same as it always was, and it says so when it makes the
+ * Ui. The buttons and sliders are numbered from 0 as they go in, labels
+ * and panels not counting, and that's the number the focus goes by, and
+ * the number `hit` gives back.
+ *
+ * So the keyboard moves the focus the way it always did, and the mouse
+ * does it too: Game asks the Ui what's under the mouse when it moves, and
+ * picks that (see Game::mouse_moved), and a click is whatever key does
+ * what that widget does (see Game::click).
+ */
+use graphics::Context;
+
+use collision::Aabb;
+use hud::Hud;
+use palette::{faded, Palette};
+use renderer::Renderer;
+use text::Text;
+
+// The title screen's buttons' size, and the gap between one and the next.
+pub const BUTTON_WIDTH: f64 = 200.0;
+pub const BUTTON_HEIGHT: f64 = 40.0;
+pub const BUTTON_GAP: f64 = 10.0;
+
+// How dark a button's box is, picked and not.
+const FOCUSED: f32 = 0.8;
+const UNFOCUSED: f32 = 0.3;
+
+// How far apart the lines of a list of them go, as many times their size.
+const LINE_SPACING: f64 = 1.5;
+
+// How tall a slider's bar is, and how much wider than its writing a row of
+// the lists' boxes are. A slider's never narrower than SLIDER_WIDTH, so
+// there's room to aim along it.
+const BAR_HEIGHT: f64 = 4.0;
+const PADDING: f64 = 12.0;
+const SLIDER_WIDTH: f64 = 240.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Widget {
+    Panel { bounds: Aabb, alpha: f32 },
+    // Centred across the screen, with its middle at `y`.
+    Label { text: String, size: u32, y: f64 },
+    Button { text: String, bounds: Aabb, focused: bool },
+    // `value` is from 0 to 1.
+    Slider { text: String, value: f32, bounds: Aabb, focused: bool },
+}
+
+impl Widget {
+    // Where you'd click to get it, if you can.
+    pub fn target(&self) -> Option<Aabb> {
+        match *self {
+            Widget::Button { bounds, .. } | Widget::Slider { bounds, .. } => Some(bounds),
+            Widget::Panel { .. } | Widget::Label { .. } => None,
+        }
+    }
+}
+
+pub struct Ui {
+    pub width: f64,
+    pub height: f64,
+    pub font_size: u32,
+    pub text: [f32; 4],
+    pub shade: [f32; 4],
+    pub focus: Option<usize>,
+    pub widgets: Vec<Widget>,
+    // Where the next widget in a list goes: the middle of its line.
+    pub y: f64,
+}
+
+impl Ui {
+    // A screen `width` by `height`, in `palette`'s colors, writing at
+    // `font_size` (with titles three times that), with `focus` picked.
+    pub fn new(width: f64, height: f64, font_size: u32, palette: &Palette,
+               focus: Option<usize>) -> Ui {
+        Ui {
+            width: width,
+            height: height,
+            font_size: font_size,
+            text: palette.text,
+            shade: palette.shade,
+            focus: focus,
+            widgets: Vec::new(),
+            y: 0.0,
+        }
+    }
+
+    /*
+     * Start a list of `lines` ordinary lines, and a title, far enough down
+     * that the lot's roughly in the middle, but no closer to the top than
+     * it has room for the title.
+     */
+    pub fn centre(&mut self, lines: usize) {
+        let size = self.font_size as f64;
+        let height = (lines as f64 + 3.0) * size * LINE_SPACING;
+        self.y = ((self.height - height) / 2.0).max(size * 2.0);
+    }
+
+    // How many buttons and sliders there are so far, which is the number
+    // the next one gets.
+    fn focusable(&self) -> usize {
+        self.widgets.iter().filter(|w| w.target().is_some()).count()
+    }
+
+    pub fn panel(&mut self, bounds: Aabb, alpha: f32) {
+        self.widgets.push(Widget::Panel { bounds: bounds, alpha: alpha });
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let size = self.font_size;
+        self.line(text, size);
+    }
+
+    pub fn title(&mut self, text: &str) {
+        let size = self.font_size * 3;
+        self.line(text, size);
+    }
+
+    fn line(&mut self, text: &str, size: u32) {
+        self.widgets.push(Widget::Label { text: text.to_string(), size: size, y: self.y });
+        self.y += size as f64 * LINE_SPACING;
+    }
+
+    // A button wherever you like. Its number.
+    pub fn button_in(&mut self, bounds: Aabb, text: &str) -> usize {
+        let number = self.focusable();
+        let focused = self.focus == Some(number);
+        self.widgets.push(Widget::Button { text: text.to_string(), bounds: bounds,
+                                           focused: focused });
+        number
+    }
+
+    // The next line of a list, as a button, in a box just big enough.
+    pub fn button(&mut self, text: &str) -> usize {
+        let width = Text::new(self.font_size, self.text).width(text) + PADDING * 2.0;
+        let bounds = self.row(width);
+        self.button_in(bounds, text)
+    }
+
+    // The next line of a list, as a slider.
+    pub fn slider(&mut self, text: &str, value: f32) -> usize {
+        let width = (Text::new(self.font_size, self.text).width(text) + PADDING * 2.0)
+            .max(SLIDER_WIDTH);
+        let bounds = self.row(width);
+        let number = self.focusable();
+        let focused = self.focus == Some(number);
+        let value = value.max(0.0).min(1.0);
+        self.widgets.push(Widget::Slider { text: text.to_string(), value: value, bounds: bounds,
+                                           focused: focused });
+        number
+    }
+
+    // A box `width` across the middle of the next line, moving on past it.
+    fn row(&mut self, width: f64) -> Aabb {
+        let size = self.font_size as f64;
+        let height = size * (LINE_SPACING - 0.2);
+        let bounds = Aabb::new((self.width - width) / 2.0, self.y - height / 2.0, width, height);
+        self.y += size * LINE_SPACING;
+        bounds
+    }
+
+    // The number of the button or slider at (x, y), if there is one.
+    pub fn hit(&self, x: f64, y: f64) -> Option<usize> {
+        self.widgets.iter()
+            .filter_map(|w| w.target())
+            .position(|b| x >= b.x && x < b.x + b.w && y >= b.y && y < b.y + b.h)
+    }
+
+    // Widget number `number`, counting only the ones that can be picked.
+    pub fn focusable_widget(&self, number: usize) -> Option<&Widget> {
+        self.widgets.iter().filter(|w| w.target().is_some()).nth(number)
+    }
+
+    // How far along slider number `number` `x` is, from 0 to 1, if it's a
+    // slider.
+    pub fn along(&self, number: usize, x: f64) -> Option<f32> {
+        match self.focusable_widget(number) {
+            Some(&Widget::Slider { bounds, .. }) => {
+                Some(((x - bounds.x) / bounds.w).max(0.0).min(1.0) as f32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn draw(&self, hud: &mut Hud, context: &Context, r: &mut dyn Renderer) {
+        for widget in self.widgets.iter() {
+            match *widget {
+                Widget::Panel { bounds: b, alpha } => {
+                    r.rect(context, b.x, b.y, b.w, b.h, faded(self.shade, alpha));
+                }
+                Widget::Label { ref text, size, y } => {
+                    let style = Text::new(size, self.text);
+                    hud.draw_banner(text, &style, self.width, y, context, r);
+                }
+                Widget::Button { ref text, bounds, focused } => {
+                    self.draw_box(text, bounds, focused, hud, context, r);
+                }
+                Widget::Slider { ref text, value, bounds: b, focused } => {
+                    self.draw_box(text, b, focused, hud, context, r);
+                    let (x, y, w) = (b.x + PADDING / 2.0, b.y + b.h - BAR_HEIGHT * 2.0,
+                                     b.w - PADDING);
+                    r.rect(context, x, y, w, BAR_HEIGHT, faded(self.text, 0.3));
+                    r.rect(context, x, y, w * value as f64, BAR_HEIGHT, self.text);
+                }
+            }
+        }
+    }
+
+    // A button's box, darker if it's picked, with its writing in the middle.
+    fn draw_box(&self, text: &str, b: Aabb, focused: bool, hud: &mut Hud, context: &Context,
+                r: &mut dyn Renderer) {
+        let shade = if focused { FOCUSED } else { UNFOCUSED };
+        r.rect(context, b.x, b.y, b.w, b.h, faded(self.shade, shade));
+        let style = Text::new(self.font_size, self.text);
+        hud.draw_banner(text, &style, self.width, b.y + b.h / 2.0, context, r);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::Recorder;
+
+    #[test]
+    fn only_buttons_and_sliders_are_numbered() {
+        let palette = Palette::high_contrast();
+        let mut ui = Ui::new(640.0, 480.0, 16, &palette, Some(1));
+        ui.y = 100.0;
+        ui.title("Options");
+        assert_eq!(ui.button("Palette: default"), 0);
+        ui.label("...");
+        assert_eq!(ui.slider("Volume", 0.5), 1);
+        assert_eq!(ui.y, 100.0 + 48.0 * 1.5 + 24.0 * 3.0);
+
+        let (x, y) = (320.0, 100.0 + 72.0 + 24.0 * 2.0);
+        assert_eq!(ui.hit(x, y), Some(1));
+        assert_eq!(ui.hit(x, 5.0), None);
+        assert_eq!(ui.along(1, x), Some(0.5));
+        assert_eq!(ui.along(0, x), None);
+        match ui.focusable_widget(1) {
+            Some(&Widget::Slider { focused, .. }) => assert!(focused),
+            other => panic!("not the slider: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_slider_is_filled_as_far_as_its_value() {
+        let palette = Palette::high_contrast();
+        let mut ui = Ui::new(640.0, 480.0, 16, &palette, None);
+        ui.y = 100.0;
+        ui.slider("Music", 0.25);
+        let mut r = Recorder::new();
+        ui.draw(&mut Hud::none(), &Context::new_abs(640.0, 480.0), &mut r);
+        let bar = r.rects_in(palette.text);
+        assert_eq!(bar.len(), 1);
+        assert_eq!(bar[0][2], (SLIDER_WIDTH - PADDING) * 0.25);
+    }
+}
@@ -0,0 +1,142 @@
+/*
+ * Where things are, and which "where" that is. There are three sorts of
+ * place in this game, and for a long time they were all just (f64, f64),
+ * which made it far too easy to hand one to something expecting another:
+ *
+ *   - The world, where the players, the walls and everything else are.
+ *     It's measured in world units, and so is everything that moves about
+ *     in it: move_speed is world units a second, gravity is world units a
+ *     second per second, a wall's 5 wide. That's all the game logic ever
+ *     deals in, and none of it needs to know how big anything's drawn.
+ *   - The screen: the game's view, what the camera shows, with the HUD and
+ *     the menus drawn on top. It's measured from its top-left corner in the
+ *     view's points (view_size of them). A world unit is a point of it at a
+ *     zoom of 1, and bigger or smaller zoomed in or out; the Camera's what
+ *     goes from one to the other (see Camera::to_world and to_screen).
+ *   - The window, which is where the mouse is, in the window's points.
+ *     Usually that's the screen, but letterboxed the screen is scaled to
+ *     fit and moved to the middle of it, and Letterbox is what goes from
+ *     one to the other.
+ *
+ * WorldPos and ScreenPos are the first two, so a function says which it
+ * means, and the compiler says so if it's given the other. The window's
+ * points are left as they come from the engine, since it's only the mouse
+ * and the shaders that care about them, on the way in and out.
+ */
+use camera;
+
+// A spot in the world, in world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldPos {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl WorldPos {
+    pub fn new(x: f64, y: f64) -> WorldPos {
+        WorldPos { x: x, y: y }
+    }
+
+    pub fn tuple(self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+
+    pub fn distance(self, to: WorldPos) -> f64 {
+        (to.x - self.x).hypot(to.y - self.y)
+    }
+
+    // The unit-length way from here to `to`, unless it's right here, where
+    // there's no way in particular.
+    pub fn towards(self, to: WorldPos) -> Option<(f64, f64)> {
+        let length = self.distance(to);
+        if length > 0.0 {
+            Some(((to.x - self.x) / length, (to.y - self.y) / length))
+        } else {
+            None
+        }
+    }
+}
+
+impl From<(f64, f64)> for WorldPos {
+    fn from(at: (f64, f64)) -> WorldPos {
+        WorldPos::new(at.0, at.1)
+    }
+}
+
+// A spot on the screen, in the view's points from its top-left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenPos {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl ScreenPos {
+    pub fn new(x: f64, y: f64) -> ScreenPos {
+        ScreenPos { x: x, y: y }
+    }
+
+    pub fn tuple(self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+}
+
+impl From<(f64, f64)> for ScreenPos {
+    fn from(at: (f64, f64)) -> ScreenPos {
+        ScreenPos::new(at.0, at.1)
+    }
+}
+
+/*
+ * Where the screen is in the window: its top-left corner, in the window's
+ * points, and how many of those one of its points takes up. Not
+ * letterboxed, it's the whole window, at 1 (see App::letterboxing).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Letterbox {
+    pub x: f64,
+    pub y: f64,
+    pub scale: f64,
+}
+
+impl Letterbox {
+    pub fn none() -> Letterbox {
+        Letterbox { x: 0.0, y: 0.0, scale: 1.0 }
+    }
+
+    // A view of `view` points fitted into a `window` (see camera::letterbox).
+    pub fn fit(window: (f64, f64), view: (f64, f64)) -> Letterbox {
+        let (x, y, scale) = camera::letterbox(window, view);
+        Letterbox { x: x, y: y, scale: scale }
+    }
+
+    // Where a spot in the window (the mouse, say) is on the screen.
+    pub fn to_screen(&self, at: (f64, f64)) -> ScreenPos {
+        ScreenPos::new((at.0 - self.x) / self.scale, (at.1 - self.y) / self.scale)
+    }
+
+    // And the other way.
+    pub fn to_window(&self, at: ScreenPos) -> (f64, f64) {
+        (self.x + at.x * self.scale, self.y + at.y * self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_window_and_the_screen_go_both_ways() {
+        // A 4:3 view in a 16:9 window, with bars down the sides.
+        let letterbox = Letterbox::fit((1920.0, 1080.0), (640.0, 480.0));
+        assert_eq!(letterbox.to_screen((240.0, 0.0)), ScreenPos::new(0.0, 0.0));
+        assert_eq!(letterbox.to_screen((960.0, 540.0)), ScreenPos::new(320.0, 240.0));
+        assert_eq!(letterbox.to_window(ScreenPos::new(640.0, 480.0)), (1680.0, 1080.0));
+        // Not letterboxed, the window is the screen.
+        assert_eq!(Letterbox::none().to_screen((12.0, 34.0)), ScreenPos::new(12.0, 34.0));
+
+        let from = WorldPos::new(10.0, 10.0);
+        assert_eq!(from.distance(WorldPos::new(13.0, 14.0)), 5.0);
+        assert_eq!(from.towards(WorldPos::new(13.0, 14.0)), Some((0.6, 0.8)));
+        assert_eq!(from.towards(from), None);
+    }
+}
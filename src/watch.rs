@@ -0,0 +1,142 @@
+/*
+ * Noticing when a file changes, so the game can pick up the new version
+ * while it's running. There are crates that get the operating system to
+ * tell you the moment it happens, but asking every so often is simpler and
+ * plenty quick enough for someone saving a file in an editor: every file
+ * remembers when it was last modified, and if that's different next time we
+ * look, it's changed.
+ *
+ * A watcher can watch a directory too, which is how the levels get watched.
+ * Saving a file in a directory doesn't always change the directory's own
+ * modified time, so for a directory it's every file in it that counts, and
+ * a file turning up or going away counts as a change as well.
+ */
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Every file we're watching, with when it was last modified (None if it
+// isn't there at all), in order by name so two looks can be compared.
+type Stamp = Vec<(PathBuf, Option<SystemTime>)>;
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn stamp(path: &Path) -> Stamp {
+    if !path.is_dir() {
+        return vec![(path.to_path_buf(), modified_time(path))];
+    }
+    let mut files: Stamp = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .map(|p| { let time = modified_time(&p); (p, time) })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+/*
+ * Rather than asking the OS every single frame, a watcher adds up the dt
+ * from each update and only looks again once `interval` seconds have gone
+ * by.
+ */
+pub struct FileWatcher {
+    path: PathBuf,
+    stamp: Stamp,
+    elapsed: f64,
+    pub interval: f64,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> FileWatcher {
+        FileWatcher { path: path.to_path_buf(), stamp: stamp(path), elapsed: 0.0, interval: 1.0 }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Call this every update: whether the file has changed, looking only
+    // once an interval.
+    pub fn update(&mut self, dt: f64) -> bool {
+        self.elapsed += dt;
+        if self.elapsed < self.interval { return false }
+        self.elapsed = 0.0;
+        self.changed()
+    }
+
+    // Whether the file has changed since last time, looking right now.
+    // Either way, what it looks like now is what the next look compares
+    // with, so each change is only reported once.
+    pub fn changed(&mut self) -> bool {
+        let now = stamp(&self.path);
+        if now == self.stamp { return false }
+        self.stamp = now;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::time::{Duration, SystemTime};
+
+    // Write `contents` to `path` and push its mtime `bump` seconds into the
+    // future, so back-to-back writes don't land on the same timestamp.
+    fn write(path: &Path, contents: &str, bump: u64) {
+        let mut f = File::create(path).unwrap();
+        f.write_all(contents.as_bytes()).unwrap();
+        f.set_modified(SystemTime::now() + Duration::from_secs(bump)).unwrap();
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("jumpy-watch-{}-{}", name, ::std::process::id()))
+    }
+
+    #[test]
+    fn notices_a_file_changing_once() {
+        let path = temp_path("file.json");
+        write(&path, "{}", 0);
+        let mut watcher = FileWatcher::new(&path);
+        assert!(!watcher.changed());
+        write(&path, "{ }", 10);
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        // Going away is a change too.
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+
+    #[test]
+    fn notices_files_changing_in_a_directory() {
+        let dir = temp_path("dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        write(&dir.join("level1.json"), "{}", 0);
+        let mut watcher = FileWatcher::new(&dir);
+        write(&dir.join("level1.json"), "{ }", 10);
+        assert!(watcher.changed());
+        write(&dir.join("level2.json"), "{}", 0);
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_looks_once_per_interval() {
+        let path = temp_path("interval.json");
+        write(&path, "{}", 0);
+        let mut watcher = FileWatcher::new(&path);
+        write(&path, "{ }", 10);
+        assert!(!watcher.update(0.5));
+        assert!(watcher.update(0.5));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+/*
+ * Waves of enemies. enemy_spawn_seconds has them trickle in one at a time;
+ * set wave_seconds in the config instead and they come in lots, every
+ * wave_seconds, each lot bigger than the last and the lot of them a bit
+ * faster. The first comes wave_grace_seconds after the level starts, so
+ * you've a moment to get your bearings, and knocking out everything in a
+ * wave brings the next one on early, but never sooner than that grace
+ * again: there's always a breather between one and the next. The HUD says
+ * which wave you're on.
+ *
+ * Wave n (counting from 1) is wave_size enemies, plus wave_growth more for
+ * every wave before it, rounded down, so with a growth of 1.5 it goes 3, 4,
+ * 6, 7, 9 and so on. The enemies go wave_speed_growth faster for every wave
+ * after the first, as a fraction of enemy_speed; this is all of them, not
+ * just the new ones, since they're all chasing you at the same speed. So
+ * the longer you last, the harder it gets, which is about as much as a
+ * difficulty curve needs to do. Both stop somewhere, or the game would
+ * turn into a screenful of enemies going faster than you can.
+ *
+ * Where each one turns up is up to spawn_enemy_somewhere, the same as the
+ * trickle, so they come from the game's random numbers, and a replay gets
+ * the same waves in the same places. Like the rest of the level they start
+ * over on a reset, but not on going back to a checkpoint (see
+ * checkpoint::respawn), or losing a life would make it easier.
+ */
+use app::App;
+use ecs::Kind;
+
+// No more than this many in one wave, and no faster than this many times
+// enemy_speed.
+pub const MAX_WAVE_SIZE: u32 = 40;
+pub const MAX_SPEED_UP: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waves {
+    // How many waves have come so far, 0 before the first one.
+    pub number: u32,
+    // Seconds until the next.
+    pub next: f64,
+}
+
+impl Waves {
+    // None yet, and the first in `grace` seconds.
+    pub fn new(grace: f64) -> Waves {
+        Waves { number: 0, next: grace }
+    }
+}
+
+// How many enemies wave `number` brings.
+pub fn size(number: u32, base: u32, growth: f64) -> u32 {
+    let more = (number.saturating_sub(1) as f64 * growth.max(0.0)).floor() as u32;
+    base.saturating_add(more).min(MAX_WAVE_SIZE)
+}
+
+// How many times enemy_speed the enemies go during wave `number`. Before the
+// first, or with waves off, that's their ordinary speed.
+pub fn speed_up(number: u32, growth: f64) -> f64 {
+    (1.0 + number.saturating_sub(1) as f64 * growth.max(0.0)).min(MAX_SPEED_UP)
+}
+
+// Once a tick: count down to the next wave, and if it's time, send it.
+pub fn update(app: &mut App, dt: f64) {
+    if app.config.wave_seconds <= 0.0 { return }
+    // Everything's knocked out, so the next one's no more than the grace
+    // away.
+    if app.waves.number > 0 && app.scene.count(Kind::Enemy) == 0 {
+        app.waves.next = app.waves.next.min(app.config.wave_grace_seconds);
+    }
+    app.waves.next -= dt;
+    if app.waves.next > 0.0 { return }
+    app.waves.number += 1;
+    app.waves.next += app.config.wave_seconds;
+    let count = size(app.waves.number, app.config.wave_size, app.config.wave_growth);
+    info!("Here comes wave {}: {} enemies", app.waves.number, count);
+    for _ in 0..count {
+        app.spawn_enemy_somewhere();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waves_get_bigger_and_faster() {
+        let sizes: Vec<u32> = (1..6).map(|n| size(n, 3, 1.5)).collect();
+        assert_eq!(sizes, vec![3, 4, 6, 7, 9]);
+        assert_eq!(size(1000, 3, 1.5), MAX_WAVE_SIZE);
+        assert_eq!(size(4, 2, -1.0), 2);
+        assert_eq!((speed_up(0, 0.1), speed_up(1, 0.1)), (1.0, 1.0));
+        assert!((speed_up(3, 0.1) - 1.2).abs() < 1e-9);
+        assert_eq!(speed_up(50, 0.1), MAX_SPEED_UP);
+    }
+}
@@ -0,0 +1,320 @@
+/*
+ * The game in a web page. A browser has no SDL2 and no window to open, but
+ * it does have a <canvas> to draw on and a keyboard to listen to, and it
+ * will run WebAssembly; so with `--features web`, built for
+ * wasm32-unknown-unknown, the game is a handful of functions the page's
+ * JavaScript calls (see web/index.html):
+ *
+ *     jumpy_start(width, height, seed,   make the game, the canvas's size,
+ *                 touch)                 and whether it's a touch screen
+ *     jumpy_resize(width, height)        the canvas changed size
+ *     jumpy_name(length)                 somewhere to write a key's name
+ *     jumpy_key(name, length, down)      that key went down or came up
+ *     jumpy_pointer(x, y, what)          a finger or the mouse moved there,
+ *                                        or went down or came up there
+ *     jumpy_frame(seconds)               time to update and draw, and how
+ *     jumpy_commands()                   much drawing there is, and where
+ *
+ * The browser runs the event loop, not us: it calls jumpy_frame once per
+ * requestAnimationFrame with how long it's been since the last one, and we
+ * hand that straight to Game::update, the same way piston's Events hands it
+ * the time in the desktop game. App's clock (see App::update) turns
+ * however much time that is into the right number of fixed ticks, so a
+ * 144Hz screen plays the same game as a 60Hz one.
+ *
+ * Everything's drawn through the Renderer trait, like always, with Canvas
+ * as the renderer: it doesn't draw anything itself, but writes each thing
+ * down as numbers, already turned into the canvas's pixels, for the
+ * JavaScript to read straight out of the WebAssembly memory and paint.
+ * Every shape is a filled polygon by then; see the comment over Canvas.
+ *
+ * It's not all the way there yet. Pictures and text go through
+ * opengl_graphics (Texture, and GlyphCache, which go straight to OpenGL), the
+ * sound effects through OpenAL and the screenshots through OpenGL, and none
+ * of those build for the browser; those parts of the game have to be kept
+ * out of a WebAssembly build before it'll link. Canvas skips pictures and
+ * text already, which the game is happy to go without, the same as when
+ * their files aren't there. Files aren't there: the page gets the built-in
+ * level and the built-in English, and the config's defaults.
+ */
+use std::path::PathBuf;
+
+use graphics::Context;
+use input::keyboard::{self, Key};
+use opengl_graphics::Texture;
+
+use app::Settings;
+use config::Config;
+use engine::GlyphCache;
+use keys;
+use level::Level;
+use locale::Locale;
+use renderer::{regular_polygon, transformed, Renderer};
+
+// What each thing Canvas writes down starts with: the color to clear to,
+// or a polygon to fill.
+pub const CLEAR: f64 = 0.0;
+pub const FILL: f64 = 1.0;
+
+// What jumpy_pointer is told the pointer did, besides moving.
+pub const POINTER_MOVE: f64 = 0.0;
+pub const POINTER_DOWN: f64 = 1.0;
+pub const POINTER_UP: f64 = 2.0;
+
+// How many corners an ellipse is drawn with.
+pub const ELLIPSE_CORNERS: u32 = 32;
+
+/*
+ * The drawing, written down for the JavaScript. Each piece starts with
+ * what it is:
+ *
+ *     CLEAR, r, g, b, a
+ *     FILL, n, x1, y1, ... xn, yn, r, g, b, a
+ *
+ * with colors from 0 to 1, like ours, and the corners in the canvas's own
+ * pixels, going down from the top left. A Context's transform takes us
+ * from where something is to OpenGL's -1 to 1 the other way up, so that's
+ * turned round again on the way out.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Canvas {
+    pub width: f64,
+    pub height: f64,
+    pub commands: Vec<f64>,
+}
+
+impl Canvas {
+    pub fn new(width: f64, height: f64) -> Canvas {
+        Canvas { width: width, height: height, commands: Vec::new() }
+    }
+
+    // Where OpenGL's `at` is on the canvas.
+    fn to_pixels(&self, at: [f64; 2]) -> [f64; 2] {
+        [(at[0] + 1.0) / 2.0 * self.width, (1.0 - at[1]) / 2.0 * self.height]
+    }
+
+    fn fill(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        self.commands.push(FILL);
+        self.commands.push(points.len() as f64);
+        for &p in points.iter() {
+            let on_canvas = self.to_pixels(transformed(&context.transform, p));
+            self.commands.extend_from_slice(&on_canvas);
+        }
+        self.commands.extend(color.iter().map(|&c| c as f64));
+    }
+}
+
+impl Renderer for Canvas {
+    fn clear(&mut self, _: &Context, color: [f32; 4]) {
+        self.commands.push(CLEAR);
+        self.commands.extend(color.iter().map(|&c| c as f64));
+    }
+
+    fn rect(&mut self, context: &Context, x: f64, y: f64, w: f64, h: f64, color: [f32; 4]) {
+        self.fill(context, &[[x, y], [x + w, y], [x + w, y + h], [x, y + h]], color);
+    }
+
+    fn ellipse(&mut self, context: &Context, b: [f64; 4], color: [f32; 4]) {
+        self.fill(context, &regular_polygon(b, ELLIPSE_CORNERS), color);
+    }
+
+    fn polygon(&mut self, context: &Context, points: &[[f64; 2]], color: [f32; 4]) {
+        self.fill(context, points, color);
+    }
+
+    // No pictures or text in a browser yet; see the top of the file.
+    fn image(&mut self, _: &Context, _: &Texture, _: Option<[i32; 4]>) {}
+
+    fn text(&mut self, _: &Context, _: &str, _: u32, _: [f32; 4], _: &mut GlyphCache) {}
+}
+
+/*
+ * The key a browser means by `name`, its KeyboardEvent.key. Letters and
+ * the F keys are called what keys.rs calls them (give or take the case),
+ * but the rest have names of their own.
+ */
+pub fn key(name: &str) -> Option<Key> {
+    match name {
+        "ArrowUp" => Some(keyboard::Up),
+        "ArrowDown" => Some(keyboard::Down),
+        "ArrowLeft" => Some(keyboard::Left),
+        "ArrowRight" => Some(keyboard::Right),
+        " " => Some(keyboard::Space),
+        "Enter" => Some(keyboard::Return),
+        "Shift" => Some(keyboard::LShift),
+        "." => Some(keyboard::Period),
+        "=" | "+" => Some(keyboard::Equals),
+        "-" => Some(keyboard::Minus),
+        _ => keys::parse_key(name),
+    }
+}
+
+// What the game gets started with in a page, where there are no files to
+// read: the config's defaults, the built-in level, and English. On a
+// `touch` screen the touch controls are on (see touch.rs).
+pub fn settings(seed: u64, touch: bool) -> Settings {
+    let mut config = Config::default();
+    config.touch_controls = touch;
+    Settings {
+        ups: config.ups,
+        config: config,
+        config_path: PathBuf::from("config.json"),
+        seed: seed,
+        levels: vec![Level::fallback()],
+        levels_path: None,
+        replay: None,
+        record_path: None,
+        coop: false,
+        link: None,
+        spectators: None,
+        lua: None,
+        dump: None,
+        locale: Locale::english(),
+    }
+}
+
+/*
+ * The functions the page calls, which only exist in a WebAssembly build.
+ * #[no_mangle] keeps their names as they are, so the JavaScript can find
+ * them, and extern "C" makes them take their arguments the plain way
+ * anything can call. WebAssembly has only the one thread, so the game
+ * lives in a thread_local, out of everyone's way between calls.
+ */
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+mod exports {
+    use std::cell::RefCell;
+    use std::slice;
+    use std::str;
+
+    use input::{mouse, Button, Keyboard, Mouse};
+    use piston::UpdateArgs;
+
+    use game::Game;
+    use super::{key, settings, Canvas, POINTER_DOWN, POINTER_UP};
+
+    thread_local! {
+        static GAME: RefCell<Option<(Game, Canvas)>> = RefCell::new(None);
+        // Where the page writes a key's name, for jumpy_key to read.
+        static NAME: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+    }
+
+    #[no_mangle]
+    pub extern "C" fn jumpy_start(width: f64, height: f64, seed: f64, touch: bool) {
+        let mut game = Game::new(settings(seed as u64, touch));
+        game.resize(width, height);
+        GAME.with(|g| *g.borrow_mut() = Some((game, Canvas::new(width, height))));
+    }
+
+    #[no_mangle]
+    pub extern "C" fn jumpy_resize(width: f64, height: f64) {
+        GAME.with(|g| if let Some((ref mut game, ref mut canvas)) = *g.borrow_mut() {
+            game.resize(width, height);
+            *canvas = Canvas::new(width, height);
+        });
+    }
+
+    // Somewhere `length` bytes long for the page to write a key's name.
+    #[no_mangle]
+    pub extern "C" fn jumpy_name(length: usize) -> *mut u8 {
+        NAME.with(|n| {
+            let mut n = n.borrow_mut();
+            n.resize(length, 0);
+            n.as_mut_ptr()
+        })
+    }
+
+    /*
+     * A key, named in the `length` bytes jumpy_name handed out. A key
+     * that types something types it too, for the console, the way a
+     * desktop window sends a text event along with the key press.
+     */
+    #[no_mangle]
+    pub extern "C" fn jumpy_key(name: *const u8, length: usize, down: bool) {
+        let bytes = unsafe { slice::from_raw_parts(name, length) };
+        let name = match str::from_utf8(bytes) {
+            Ok(name) => name,
+            Err(_) => return,
+        };
+        GAME.with(|g| if let Some((ref mut game, _)) = *g.borrow_mut() {
+            if let Some(k) = key(name) {
+                let button: Button = Keyboard(k);
+                if down { game.handle_key(button) } else { game.handle_release(button) }
+            }
+            if down && name.chars().count() == 1 {
+                game.handle_text(name);
+            }
+        });
+    }
+
+    // The pointer's at (x, y) on the canvas, and `what` says whether it's
+    // only moved there or gone down or come up as well. It's the left
+    // mouse button either way, the way a desktop window sends a touch.
+    #[no_mangle]
+    pub extern "C" fn jumpy_pointer(x: f64, y: f64, what: f64) {
+        GAME.with(|g| if let Some((ref mut game, _)) = *g.borrow_mut() {
+            game.mouse_moved(x, y);
+            let button: Button = Mouse(mouse::Left);
+            if what == POINTER_DOWN {
+                game.handle_key(button);
+            } else if what == POINTER_UP {
+                game.handle_release(button);
+            }
+        });
+    }
+
+    // `seconds` have gone by: update, and draw. What's drawn is left in
+    // the canvas's commands, for jumpy_commands; this says how many.
+    #[no_mangle]
+    pub extern "C" fn jumpy_frame(seconds: f64) -> usize {
+        GAME.with(|g| match *g.borrow_mut() {
+            Some((ref mut game, ref mut canvas)) => {
+                game.record_frame(seconds);
+                game.update(&UpdateArgs { dt: seconds });
+                canvas.commands.clear();
+                let (w, h) = (canvas.width, canvas.height);
+                game.draw(w, h, canvas);
+                canvas.commands.len()
+            }
+            None => 0,
+        })
+    }
+
+    #[no_mangle]
+    pub extern "C" fn jumpy_commands() -> *const f64 {
+        GAME.with(|g| match *g.borrow() {
+            Some((_, ref canvas)) => canvas.commands.as_ptr(),
+            None => ::std::ptr::null(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_canvas_gets_pixels_the_right_way_up() {
+        // A canvas with sides a power of two, so there's nothing for
+        // rounding to get at on the way there and back.
+        let mut canvas = Canvas::new(512.0, 256.0);
+        let context = Context::new_abs(512.0, 256.0);
+        canvas.clear(&context, [0.5; 4]);
+        canvas.rect(&context.trans(10.0, 20.0), 0.0, 0.0, 30.0, 40.0, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(canvas.commands, vec![CLEAR, 0.5, 0.5, 0.5, 0.5,
+                                         FILL, 4.0, 10.0, 20.0, 40.0, 20.0, 40.0, 60.0,
+                                         10.0, 60.0, 1.0, 0.0, 0.0, 1.0]);
+        canvas.commands.clear();
+        canvas.ellipse(&context, [0.0, 0.0, 10.0, 10.0], [1.0; 4]);
+        assert_eq!(canvas.commands[1], ELLIPSE_CORNERS as f64);
+    }
+
+    #[test]
+    fn browser_keys_are_our_keys() {
+        assert_eq!(key("ArrowLeft"), Some(keyboard::Left));
+        assert_eq!(key(" "), Some(keyboard::Space));
+        assert_eq!(key("r"), Some(keyboard::R));
+        assert_eq!(key("F4"), Some(keyboard::F4));
+        assert_eq!(key("Escape"), Some(keyboard::Escape));
+        assert_eq!(key("Dead"), None);
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * Which library opens the window. Up at the top of lib.rs there's a
+ * promise that piston lets you swap the window out from under a game
+ * without the game noticing; this is where we keep it. SDL2 is the
+ * default, and glutin or GLFW can stand in for it:
+ *
+ *     cargo run --no-default-features --features glutin
+ *     cargo run --no-default-features --features glfw
+ *
+ * Everything else in the game only ever talks to the window through
+ * piston's Window trait, so all that changes between them is the name of
+ * the struct and which crate it comes from. GameWindow is whichever one got
+ * built in. If more than one feature is on (say you forgot
+ * --no-default-features) SDL2 wins, then glutin.
+ *
+ * This is also where the window gets its OpenGL, which can go wrong: plenty
+ * of older graphics cards (and most virtual machines) stop at OpenGL 2.1,
+ * and asking them for 3.2 gets you nothing. So open_any tries the version
+ * the config asks for, then 3.2, then 2.1, and takes the first that works.
+ */
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use piston::window::WindowSettings;
+
+use config::{self, Config};
+use engine::{Gl, OpenGL};
+
+#[cfg(feature = "sdl2")]
+pub use sdl2_window::Sdl2Window as GameWindow;
+#[cfg(feature = "sdl2")]
+pub const NAME: &'static str = "SDL2";
+
+#[cfg(all(feature = "glutin", not(feature = "sdl2")))]
+pub use glutin_window::GlutinWindow as GameWindow;
+#[cfg(all(feature = "glutin", not(feature = "sdl2")))]
+pub const NAME: &'static str = "glutin";
+
+#[cfg(all(feature = "glfw", not(any(feature = "sdl2", feature = "glutin"))))]
+pub use glfw_window::GlfwWindow as GameWindow;
+#[cfg(all(feature = "glfw", not(any(feature = "sdl2", feature = "glutin"))))]
+pub const NAME: &'static str = "GLFW";
+
+// With none of them there's nothing to draw on, and it's better to hear
+// about that from the compiler than from a page of missing-name errors.
+#[cfg(not(any(feature = "sdl2", feature = "glutin", feature = "glfw")))]
+compile_error!("pick a window backend: --features sdl2, glutin or glfw");
+
+// The OpenGL versions worth trying, best first: what the config asks for,
+// then the two that nearly everything has, without trying any twice.
+pub const FALLBACK_VERSIONS: [&'static str; 2] = ["3.2", "2.1"];
+
+pub fn versions_to_try(wanted: &str) -> Vec<String> {
+    let mut versions: Vec<String> = Vec::new();
+    for name in Some(wanted.trim()).into_iter().chain(FALLBACK_VERSIONS.iter().cloned()) {
+        if config::opengl_version(name).is_some() && !versions.iter().any(|v| v == name) {
+            versions.push(name.to_string());
+        }
+    }
+    versions
+}
+
+/*
+ * The backends don't always say "no" politely, with an Err, when they
+ * can't make a window; sometimes they panic instead. A panic in Rust
+ * unwinds the stack, though, and catch_unwind can stop it on the way up
+ * and hand back what it said instead, which turns a crash into an Err.
+ * While we're trying, the usual "thread 'main' panicked at ..." message is
+ * turned off, since we'll be saying it better ourselves.
+ */
+fn without_panicking<T, F: FnOnce() -> T>(f: F) -> Result<T, String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result.map_err(|e| panic_message(&*e))
+}
+
+// What a panic said. It's usually a &str or a String, but could be anything.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => "it didn't say why".to_string(),
+        },
+    }
+}
+
+// A window set up the way the config says, fullscreen or not, and with
+// vsync or not. Fullscreen windows take the size of the screen, so the size
+// only matters when it's not. All three backends are made the same way,
+// which is the point.
+pub fn open(opengl: OpenGL, config: &Config, fullscreen: bool) -> Result<GameWindow, String> {
+    let settings = WindowSettings::new(config.window_title.clone(),
+                                       [config.window_width, config.window_height])
+        .fullscreen(fullscreen)
+        .vsync(config.vsync)
+        .graphics_api(opengl);
+    without_panicking(|| settings.build::<GameWindow>())?.map_err(|e| e.to_string())
+}
+
+// Something to draw on the window with. This one can fail too: it builds
+// the shaders for the version, which the graphics card may not understand.
+pub fn gl(opengl: OpenGL) -> Result<Gl, String> {
+    without_panicking(|| Gl::new(opengl))
+}
+
+/*
+ * A window, and a Gl for it, with the first OpenGL version that works out
+ * of versions_to_try. Along with them comes the version it was, since a
+ * window made again later (for fullscreen) should use the same one. If
+ * none of them work, the error says what each one said.
+ */
+pub fn open_any(config: &Config, fullscreen: bool) -> Result<(GameWindow, Gl, OpenGL), String> {
+    let mut failures = Vec::new();
+    for name in versions_to_try(&config.opengl_version) {
+        let opengl = match config::opengl_version(&name) {
+            Some(opengl) => opengl,
+            None => continue,
+        };
+        match open(opengl, config, fullscreen).and_then(|w| gl(opengl).map(|gl| (w, gl))) {
+            Ok((window, gl)) => {
+                info!(target: Render, "Drawing with OpenGL {}", name);
+                return Ok((window, gl, opengl));
+            }
+            Err(e) => {
+                warn!(target: Render, "OpenGL {} didn't work: {}", name, e);
+                failures.push(format!("OpenGL {}: {}", name, e));
+            }
+        }
+    }
+    Err(format!("Couldn't open a window with {}. ({})\n\
+                 It may help to update your graphics drivers, or to set opengl_version \
+                 in config.json to a version your graphics card has.",
+                NAME, failures.join("; ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tries_what_the_config_says_then_the_fallbacks() {
+        assert_eq!(versions_to_try("4.5"), vec!["4.5", "3.2", "2.1"]);
+        assert_eq!(versions_to_try(" 2.1"), vec!["2.1", "3.2"]);
+        assert_eq!(versions_to_try("three"), vec!["3.2", "2.1"]);
+    }
+
+    #[test]
+    fn panics_become_errors() {
+        assert_eq!(without_panicking(|| 1), Ok(1));
+        assert_eq!(without_panicking(|| -> u32 { panic!("no OpenGL 3.2 here") }),
+                   Err("no OpenGL 3.2 here".to_string()));
+        assert_eq!(without_panicking(|| -> u32 { panic!("version {}", 9) }),
+                   Err("version 9".to_string()));
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * What happens at the edge of the world. Normally the edges of the window
+ * are walls. In wrap mode they aren't there at all: go off the right-hand
+ * side and you come straight back in on the left, like in Asteroids.
+ * Mathematicians call that shape a torus, which is a doughnut - roll the
+ * screen up into a tube so the top meets the bottom, then bend the tube
+ * round so the ends meet.
+ */
+use collision::Aabb;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldMode {
+    Bounded,
+    Wrap,
+}
+
+impl WorldMode {
+    pub fn toggled(&self) -> WorldMode {
+        match *self {
+            WorldMode::Bounded => WorldMode::Wrap,
+            WorldMode::Wrap => WorldMode::Bounded,
+        }
+    }
+}
+
+// Bring v back into [0, size). Rust's % keeps the sign of the left-hand
+// side, so -10 % 640 is -10; adding size and taking % again fixes that up.
+pub fn wrap(v: f64, size: f64) -> f64 {
+    ((v % size) + size) % size
+}
+
+/*
+ * The drawing trick. If the square is halfway off the right-hand edge, the
+ * half that's gone should be peeking in on the left. So we draw it twice:
+ * once where it really is, and once a whole screen-width to the left. Off a
+ * corner it needs four copies. These are the offsets to draw it at, always
+ * starting with (0, 0) for where it actually is.
+ */
+pub fn wrap_offsets(bounds: Aabb, width: f64, height: f64) -> Vec<(f64, f64)> {
+    let shifts = |lo: f64, hi: f64, size: f64| {
+        let mut s = vec![0.0];
+        if lo < 0.0 { s.push(size) }
+        if hi > size { s.push(-size) }
+        s
+    };
+    let xs = shifts(bounds.x, bounds.right(), width);
+    let ys = shifts(bounds.y, bounds.bottom(), height);
+    let mut offsets = Vec::new();
+    for &y in ys.iter() {
+        for &x in xs.iter() {
+            offsets.push((x, y));
+        }
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collision::Aabb;
+
+    #[test]
+    fn wraps_both_ways() {
+        assert_eq!(wrap(650.0, 640.0), 10.0);
+        assert_eq!(wrap(-10.0, 640.0), 630.0);
+        assert_eq!(wrap(320.0, 640.0), 320.0);
+        assert_eq!(wrap(640.0, 640.0), 0.0);
+    }
+
+    #[test]
+    fn straddling_squares_are_drawn_again() {
+        // Right in the middle: just the once.
+        let middle = Aabb::new(300.0, 200.0, 10.0, 10.0);
+        assert_eq!(wrap_offsets(middle, 640.0, 480.0), vec![(0.0, 0.0)]);
+        // Over the right edge: again on the left.
+        let right = Aabb::new(635.0, 200.0, 10.0, 10.0);
+        assert_eq!(wrap_offsets(right, 640.0, 480.0), vec![(0.0, 0.0), (-640.0, 0.0)]);
+        // Over the bottom-right corner: in all four corners.
+        let corner = Aabb::new(635.0, 475.0, 10.0, 10.0);
+        assert_eq!(wrap_offsets(corner, 640.0, 480.0),
+                   vec![(0.0, 0.0), (-640.0, 0.0), (0.0, -480.0), (-640.0, -480.0)]);
+    }
+}